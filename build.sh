@@ -6,6 +6,8 @@ set -e
 # Default values
 OUTPUT_DIR="./dist"
 OUTPUT_NAME="data-viewer-3d"
+FEATURE_ARGS=()
+RUN_WASM_OPT=true
 
 # Parse command line arguments
 while [[ $# -gt 0 ]]; do
@@ -18,9 +20,21 @@ while [[ $# -gt 0 ]]; do
             OUTPUT_NAME="$2"
             shift 2
             ;;
+        --features)
+            FEATURE_ARGS+=(--features "$2")
+            shift 2
+            ;;
+        --no-default-features)
+            FEATURE_ARGS+=(--no-default-features)
+            shift
+            ;;
+        --no-opt)
+            RUN_WASM_OPT=false
+            shift
+            ;;
         *)
             echo "Unknown option: $1"
-            echo "Usage: $0 [--output-dir <dir>] [--output-name <name>] [--no-embed]"
+            echo "Usage: $0 [--output-dir <dir>] [--output-name <name>] [--features <list>] [--no-default-features] [--no-opt]"
             exit 1
             ;;
     esac
@@ -30,8 +44,9 @@ echo "🦀 Building Rust project for WebAssembly..."
 echo "📁 Output directory: $OUTPUT_DIR"
 echo "📝 Output name: $OUTPUT_NAME"
 
-# Build the Rust project for WebAssembly
-if ! cargo build --target wasm32-unknown-unknown --release; then
+# Build the Rust project for WebAssembly, `--no-default-features` leaves out the TIFF decoder and
+# the translations for backends sending raw images
+if ! cargo build --target wasm32-unknown-unknown --profile wasm-release "${FEATURE_ARGS[@]}"; then
     echo "❌ Rust compilation failed!"
     exit 1
 fi
@@ -41,10 +56,22 @@ echo "✅ Rust compilation successful!"
 echo "🔗 Generating TypeScript bindings with web target..."
 
 # Generate TypeScript bindings with web target
-if ! wasm-bindgen --out-dir "$OUTPUT_DIR" --out-name "$OUTPUT_NAME" --target web --typescript target/wasm32-unknown-unknown/release/data-viewer-3d.wasm; then
+if ! wasm-bindgen --out-dir "$OUTPUT_DIR" --out-name "$OUTPUT_NAME" --target web --typescript target/wasm32-unknown-unknown/wasm-release/data-viewer-3d.wasm; then
     echo "❌ wasm-bindgen failed!"
     exit 1
 fi
 
+# Shrink the module further where binaryen is installed
+WASM_FILE="$OUTPUT_DIR/${OUTPUT_NAME}_bg.wasm"
+if [[ "$RUN_WASM_OPT" == true ]]; then
+    if command -v wasm-opt > /dev/null; then
+        echo "🗜️ Optimizing with wasm-opt..."
+        wasm-opt -Oz --enable-bulk-memory --enable-nontrapping-float-to-int -o "$WASM_FILE" "$WASM_FILE"
+    else
+        echo "⚠️ wasm-opt not found, skipping size optimization"
+    fi
+fi
+echo "📦 $(wc -c < "$WASM_FILE") bytes of WebAssembly"
+
 echo "✅ WebAssembly build complete! Files generated in $OUTPUT_DIR"
 
@@ -0,0 +1,122 @@
+use glam::{Mat4, Quat};
+use web_time::{Duration, Instant};
+
+use crate::camera_sync::CameraState;
+
+/// Measures the time between rendered frames so animations advance by elapsed time instead of
+/// by frame count and look the same at any refresh rate
+pub struct FrameClock {
+    last_frame: Option<Instant>,
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameClock {
+    /// Longest step an animation advances per frame. Browsers throttle hidden tabs to very few
+    /// frames, animations continue smoothly instead of jumping when the tab becomes visible again.
+    const MAX_DELTA: Duration = Duration::from_millis(100);
+
+    pub fn new() -> Self {
+        Self { last_frame: None }
+    }
+
+    /// Starts a new frame and returns the time since the previous one
+    pub fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        let delta = self
+            .last_frame
+            .map_or(Duration::ZERO, |last_frame| now - last_frame);
+        self.last_frame = Some(now);
+        delta.min(Self::MAX_DELTA)
+    }
+
+    /// Forgets the previous frame so the first frame after an idle period does not count the
+    /// idle time. Call when no animation is running.
+    pub fn pause(&mut self) {
+        self.last_frame = None;
+    }
+}
+
+/// Smooth camera move, e.g. when resetting the view
+pub struct CameraTransition {
+    from: CameraState,
+    to: CameraState,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl CameraTransition {
+    pub const DEFAULT_DURATION: Duration = Duration::from_millis(300);
+
+    pub fn new(from: CameraState, to: CameraState, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: Duration::ZERO,
+            duration,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Advances the transition by `delta` and returns the camera at the new point in time
+    pub fn advance(&mut self, delta: Duration) -> CameraState {
+        self.elapsed = (self.elapsed + delta).min(self.duration);
+        if self.is_finished() {
+            return self.to;
+        }
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        // Ease in and out so the camera does not start and stop abruptly
+        let t = t * t * (3.0 - 2.0 * t);
+        let from = Quat::from_mat4(&self.from.rotation);
+        let to = Quat::from_mat4(&self.to.rotation);
+        CameraState {
+            rotation: Mat4::from_quat(from.slerp(to, t)),
+            pan: self.from.pan.lerp(self.to.pan, t),
+            zoom: self.from.zoom + (self.to.zoom - self.from.zoom) * t,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn transition_is_independent_of_frame_rate() {
+        let from = CameraState {
+            rotation: Mat4::from_rotation_z(1.0),
+            pan: Vec2::new(0.5, -0.5),
+            zoom: 0.5,
+        };
+        let to = CameraState {
+            rotation: Mat4::IDENTITY,
+            pan: Vec2::ZERO,
+            zoom: 1.0,
+        };
+        let duration = Duration::from_millis(500);
+        let run = |rate: u32, frames: u32| {
+            let mut transition = CameraTransition::new(from, to, duration);
+            let mut camera = from;
+            for _ in 0..frames {
+                camera = transition.advance(Duration::from_secs(1) / rate);
+            }
+            camera
+        };
+        // A quarter of a second at 60 Hz and at 144 Hz
+        let at_60 = run(60, 15);
+        let at_144 = run(144, 36);
+        assert!(at_60.pan.abs_diff_eq(at_144.pan, 1e-4));
+        assert!((at_60.zoom - at_144.zoom).abs() < 1e-4);
+        assert!(at_60.rotation.abs_diff_eq(at_144.rotation, 1e-4));
+        assert_eq!(run(30, 100), to);
+    }
+}
@@ -0,0 +1,196 @@
+// Markers and overlays can belong to a named group, e.g. "scratches", "pits" or "manual notes",
+// that is shown, hidden or recolored as a whole to keep dense review sessions organized.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{image::ImageSize, markers::Marker, texture::Overlay};
+
+/// How the annotations of a group are drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupStyle {
+    pub visible: bool,
+    /// Replaces the colors of the annotations, `None` keeps their own
+    pub color: Option<[u8; 4]>,
+}
+
+impl Default for GroupStyle {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            color: None,
+        }
+    }
+}
+
+/// Styles of the annotation groups by name, ungrouped annotations are always drawn as they are
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnnotationGroups {
+    styles: BTreeMap<String, GroupStyle>,
+}
+
+impl AnnotationGroups {
+    pub fn style(&self, group: Option<&str>) -> GroupStyle {
+        group
+            .and_then(|group| self.styles.get(group))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_visible(&mut self, group: &str, visible: bool) {
+        self.styles.entry(group.to_string()).or_default().visible = visible;
+    }
+
+    pub fn set_color(&mut self, group: &str, color: Option<[u8; 4]>) {
+        self.styles.entry(group.to_string()).or_default().color = color;
+    }
+
+    /// Color to draw an annotation of `group` in, `None` while the group is hidden
+    pub fn display_color(&self, group: Option<&str>, color: [u8; 4]) -> Option<[u8; 4]> {
+        let style = self.style(group);
+        style.visible.then(|| style.color.unwrap_or(color))
+    }
+}
+
+/// Names of the groups the markers and overlays belong to, sorted
+pub fn group_names(markers: &[Marker], overlays: &[Overlay]) -> Vec<String> {
+    let names: BTreeSet<&String> = markers
+        .iter()
+        .filter_map(|marker| marker.group.as_ref())
+        .chain(overlays.iter().filter_map(|overlay| overlay.group.as_ref()))
+        .collect();
+    names.into_iter().cloned().collect()
+}
+
+/// The markers and overlays as CSV with their group, position and color, overlays at their
+/// centroid. With `groups` only the annotations of these groups are written, an empty name
+/// selects the ungrouped ones.
+pub fn to_csv(
+    markers: &[Marker],
+    overlays: &[Overlay],
+    image_size: &ImageSize,
+    groups: Option<&[String]>,
+) -> String {
+    let included = |group: &Option<String>| {
+        let group = group.as_deref().unwrap_or("");
+        groups.is_none_or(|groups| groups.iter().any(|name| name == group))
+    };
+    let hex = |color: [u8; 4]| {
+        format!(
+            "#{:02x}{:02x}{:02x}{:02x}",
+            color[0], color[1], color[2], color[3]
+        )
+    };
+    let mut csv = String::from("kind,index,group,x,y,pixels,color\n");
+    for (index, marker) in markers.iter().enumerate() {
+        if included(&marker.group) {
+            csv += &format!(
+                "marker,{},{},{},{},1,{}\n",
+                index,
+                marker.group.as_deref().unwrap_or(""),
+                marker.pixel[0],
+                marker.pixel[1],
+                hex(marker.color)
+            );
+        }
+    }
+    let width = image_size.width.get() as u64;
+    for (index, overlay) in overlays.iter().enumerate() {
+        if !included(&overlay.group) {
+            continue;
+        }
+        let (mut count, mut sum_x, mut sum_y) = (0u64, 0u64, 0u64);
+        for pixel in overlay.pixels.iter().flat_map(|range| range.clone()) {
+            count += 1;
+            sum_x += pixel as u64 % width;
+            sum_y += pixel as u64 / width;
+        }
+        let centroid = |sum: u64| {
+            if count == 0 {
+                String::new()
+            } else {
+                format!("{:.1}", sum as f64 / count as f64)
+            }
+        };
+        csv += &format!(
+            "overlay,{},{},{},{},{},{}\n",
+            index,
+            overlay.group.as_deref().unwrap_or(""),
+            centroid(sum_x),
+            centroid(sum_y),
+            count,
+            hex(overlay.color)
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{markers::MarkerShape, overlay_style::OverlayStyle, texture::displayed_overlays};
+
+    fn overlay(pixels: std::ops::Range<u32>, group: Option<&str>) -> Overlay {
+        Overlay {
+            pixels: vec![pixels],
+            color: [255, 0, 0, 200],
+            group: group.map(str::to_string),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn hidden_groups_keep_the_overlay_indices() {
+        let overlays = [
+            overlay(0..4, Some("scratches")),
+            overlay(4..8, Some("pits")),
+            overlay(8..12, None),
+        ];
+        let mut groups = AnnotationGroups::default();
+        groups.set_visible("scratches", false);
+        groups.set_color("pits", Some([0, 0, 255, 255]));
+        // Ungrouped annotations are not affected by a group of the same name
+        groups.set_visible("", false);
+
+        let displayed = displayed_overlays(&overlays, &groups, &OverlayStyle::default());
+        assert_eq!(displayed.len(), 3);
+        assert!(displayed[0].pixels.is_empty());
+        assert_eq!(displayed[1].color, [0, 0, 255, 255]);
+        assert_eq!(displayed[2].pixels, vec![8..12]);
+        assert_eq!(displayed[2].color, [255, 0, 0, 200]);
+
+        groups.set_visible("scratches", true);
+        assert_eq!(
+            groups.display_color(Some("scratches"), [1, 2, 3, 4]),
+            Some([1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn export_is_filtered_by_group() {
+        let markers = [Marker {
+            pixel: [3, 1],
+            color: [0, 255, 0, 255],
+            size: 8.0,
+            on_top: false,
+            shape: MarkerShape::Dot,
+            height: None,
+            group: Some("manual notes".to_string()),
+        }];
+        let overlays = [overlay(4..6, Some("pits")), overlay(0..1, None)];
+        let size = ImageSize::new(4, 4).unwrap();
+
+        assert_eq!(
+            group_names(&markers, &overlays),
+            vec!["manual notes".to_string(), "pits".to_string()]
+        );
+        let all = to_csv(&markers, &overlays, &size, None);
+        assert_eq!(all.lines().count(), 4);
+        let pits = to_csv(&markers, &overlays, &size, Some(&["pits".to_string()]));
+        assert_eq!(
+            pits,
+            "kind,index,group,x,y,pixels,color\noverlay,0,pits,0.5,1.0,2,#ff0000c8\n"
+        );
+        let ungrouped = to_csv(&markers, &overlays, &size, Some(&[String::new()]));
+        assert!(ungrouped.ends_with("\noverlay,1,,0.0,0.0,1,#ff0000c8\n"));
+    }
+}
@@ -0,0 +1,243 @@
+// Manufacturing QA processes need proof of a review: which overlays and markers were looked at,
+// for how long and how the operator disposed of them. The exported log is signed with
+// HMAC-SHA256 if a key is given, otherwise it only carries a SHA-256 checksum.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use std::fmt::Write as _;
+
+use crate::{
+    json::json_string,
+    provenance::format_utc,
+    selection::{Selection, SelectionKind},
+};
+
+/// Verdict of the operator on an overlay or marker
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Disposition {
+    Accepted,
+    Rejected,
+}
+
+impl Disposition {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+/// What happened to an overlay or marker
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditAction {
+    /// Selected for `seconds`
+    Viewed {
+        seconds: f64,
+    },
+    Disposed(Disposition),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch when the action started
+    pub timestamp: f64,
+    pub dataset: String,
+    pub selection: Selection,
+    pub action: AuditAction,
+}
+
+/// Log of the annotations viewed and disposed of in this session
+#[derive(Debug, Default)]
+pub struct AuditTrail {
+    records: Vec<AuditRecord>,
+    /// Annotation selected since a timestamp, recorded once the selection changes
+    viewing: Option<(String, Selection, f64)>,
+}
+
+impl AuditTrail {
+    /// Ends the view of the previous selection and starts the one of `selection` at `now`
+    pub fn select(&mut self, dataset: &str, selection: Option<Selection>, now: f64) {
+        if self
+            .viewing
+            .as_ref()
+            .is_some_and(|(_, viewed, _)| Some(*viewed) == selection)
+        {
+            return;
+        }
+        if let Some(record) = self.end_view(now) {
+            self.records.push(record);
+        }
+        self.viewing = selection.map(|selection| (dataset.to_string(), selection, now));
+    }
+
+    pub fn dispose(
+        &mut self,
+        dataset: &str,
+        selection: Selection,
+        disposition: Disposition,
+        now: f64,
+    ) {
+        log::info!(
+            "{:?} {} {}",
+            selection.kind,
+            selection.index,
+            disposition.name()
+        );
+        self.records.push(AuditRecord {
+            timestamp: now,
+            dataset: dataset.to_string(),
+            selection,
+            action: AuditAction::Disposed(disposition),
+        });
+    }
+
+    /// Record of the current view if it ended at `now`
+    fn end_view(&self, now: f64) -> Option<AuditRecord> {
+        let (dataset, selection, since) = self.viewing.as_ref()?;
+        Some(AuditRecord {
+            timestamp: *since,
+            dataset: dataset.clone(),
+            selection: *selection,
+            action: AuditAction::Viewed {
+                seconds: (now - since).max(0.0) / 1000.0,
+            },
+        })
+    }
+
+    /// All records with the current view up to `now`, in the order the actions started
+    pub fn records(&self, now: f64) -> Vec<AuditRecord> {
+        let mut records = self.records.clone();
+        records.extend(self.end_view(now));
+        records.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+        records
+    }
+
+    /// One line per record with its UTC time
+    pub fn to_csv(&self, now: f64) -> String {
+        let mut csv = String::from("time,dataset,kind,index,action,seconds\n");
+        for record in self.records(now) {
+            let (action, seconds) = action_fields(record.action);
+            let _ = writeln!(
+                csv,
+                "{},\"{}\",{},{},{},{}",
+                format_utc(record.timestamp),
+                record.dataset.replace('"', "\"\""),
+                kind_name(record.selection.kind),
+                record.selection.index,
+                action,
+                seconds.map_or(String::new(), |seconds| format!("{:.3}", seconds))
+            );
+        }
+        csv
+    }
+
+    pub fn to_json(&self, now: f64) -> String {
+        let records: Vec<String> = self
+            .records(now)
+            .into_iter()
+            .map(|record| {
+                let (action, seconds) = action_fields(record.action);
+                format!(
+                    "    {{\"time\": {}, \"dataset\": {}, \"kind\": \"{}\", \"index\": {}, \
+                     \"action\": \"{}\", \"seconds\": {}}}",
+                    json_string(&format_utc(record.timestamp)),
+                    json_string(&record.dataset),
+                    kind_name(record.selection.kind),
+                    record.selection.index,
+                    action,
+                    seconds.map_or("null".to_string(), |seconds| format!("{:.3}", seconds))
+                )
+            })
+            .collect();
+        format!("{{\n  \"records\": [\n{}\n  ]\n}}\n", records.join(",\n"))
+    }
+}
+
+/// Milliseconds since the Unix epoch
+pub fn now() -> f64 {
+    web_time::SystemTime::now()
+        .duration_since(web_time::SystemTime::UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64() * 1000.0)
+}
+
+fn kind_name(kind: SelectionKind) -> &'static str {
+    match kind {
+        SelectionKind::Overlay => "overlay",
+        SelectionKind::Marker => "marker",
+    }
+}
+
+fn action_fields(action: AuditAction) -> (&'static str, Option<f64>) {
+    match action {
+        AuditAction::Viewed { seconds } => ("viewed", Some(seconds)),
+        AuditAction::Disposed(disposition) => (disposition.name(), None),
+    }
+}
+
+/// Signature of an exported log like `hmac-sha256:<hex>`, `sha256:<hex>` without a key. It
+/// covers the bytes of the log as written, e.g. `openssl dgst -sha256 -hmac <key>` checks it.
+pub fn sign(log: &str, key: Option<&[u8]>) -> String {
+    let (algorithm, digest) = match key {
+        Some(key) => ("hmac-sha256", hmac_sha256::HMAC::mac(log, key)),
+        None => ("sha256", hmac_sha256::Hash::hash(log.as_bytes())),
+    };
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}:{}", algorithm, hex)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn views_last_until_the_selection_changes() {
+        let mut trail = AuditTrail::default();
+        trail.select("a.tif", Some(Selection::overlay(2)), 1000.0);
+        // Selecting the same annotation again continues its view
+        trail.select("a.tif", Some(Selection::overlay(2)), 2000.0);
+        trail.dispose(
+            "a.tif",
+            Selection::overlay(2),
+            Disposition::Rejected,
+            3000.0,
+        );
+        trail.select("a.tif", Some(Selection::marker(0)), 3500.0);
+        trail.select("a.tif", None, 4000.0);
+
+        let records = trail.records(9000.0);
+        let actions: Vec<AuditAction> = records.iter().map(|record| record.action).collect();
+        assert_eq!(
+            actions,
+            [
+                AuditAction::Viewed { seconds: 2.5 },
+                AuditAction::Disposed(Disposition::Rejected),
+                AuditAction::Viewed { seconds: 0.5 },
+            ]
+        );
+        assert_eq!(
+            trail.to_csv(9000.0).lines().nth(1),
+            Some("1970-01-01T00:00:01Z,\"a.tif\",overlay,2,viewed,2.500")
+        );
+        assert!(
+            trail
+                .to_json(9000.0)
+                .contains("\"action\": \"rejected\", \"seconds\": null")
+        );
+    }
+
+    #[test]
+    fn signatures_depend_on_the_key() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("what do ya want for nothing?", Some(b"Jefe")),
+            "hmac-sha256:5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("", None),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
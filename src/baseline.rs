@@ -0,0 +1,296 @@
+// In live mode, e.g. with `--watch`, new measurements can be compared with a baseline instead of
+// a nominal surface: the first frame or a rolling average of the frames so far. A compute shader
+// folds every frame into the baseline on the GPU, so drift and process changes show up in the
+// residual view right away.
+
+use anyhow::anyhow;
+use std::{borrow::Cow, fmt, str::FromStr};
+
+use crate::{
+    image::ImageSize,
+    texture::{ReferenceTexture, SurfaceTexture},
+};
+
+/// Which frames the baseline is made of, the text form is `first` or `rolling[:weight]`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BaselineMode {
+    /// The first frame after the baseline was (re)started
+    FirstFrame,
+    /// Exponential moving average, every frame contributes `weight` to the baseline
+    RollingAverage { weight: f32 },
+}
+
+impl BaselineMode {
+    pub const DEFAULT_WEIGHT: f32 = 0.1;
+
+    /// Share of the next frame in the baseline after `frames` were folded into it
+    fn weight(self, frames: u32) -> f32 {
+        match self {
+            _ if frames == 0 => 1.0,
+            Self::FirstFrame => 0.0,
+            Self::RollingAverage { weight } => weight,
+        }
+    }
+}
+
+impl fmt::Display for BaselineMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FirstFrame => write!(f, "first"),
+            Self::RollingAverage { weight } => write!(f, "rolling:{}", weight),
+        }
+    }
+}
+
+impl FromStr for BaselineMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "first" => Ok(Self::FirstFrame),
+            None if s == "rolling" => Ok(Self::RollingAverage {
+                weight: Self::DEFAULT_WEIGHT,
+            }),
+            Some(("rolling", weight)) => match weight.parse::<f32>() {
+                Ok(weight) if weight > 0.0 && weight <= 1.0 => Ok(Self::RollingAverage { weight }),
+                _ => Err(anyhow!(
+                    "Rolling baseline weight must be in (0, 1]: {}",
+                    weight
+                )),
+            },
+            _ => Err(anyhow!(
+                "Invalid baseline: {}, use first or rolling[:weight]",
+                s
+            )),
+        }
+    }
+}
+
+/// Baseline of the live surfaces on the GPU, copied into the reference texture of every frame
+pub struct Baseline {
+    pub mode: BaselineMode,
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    settings_buffer: wgpu::Buffer,
+    /// Ping-pong pair, every fold reads one and writes the other. `None` until the first frame.
+    textures: Option<[wgpu::Texture; 2]>,
+    image_size: Option<ImageSize>,
+    /// Index of the texture holding the baseline
+    current: usize,
+    frames: u32,
+}
+
+impl Baseline {
+    const WORKGROUP_SIZE: u32 = 8;
+
+    pub fn new(device: &wgpu::Device, mode: BaselineMode) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("baseline_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("baseline.wgsl"))),
+        });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("baseline_bind_group_layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::R32Float,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("baseline_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("baseline_pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("fold"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        // Uniform buffers are padded to 16 bytes
+        let settings_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("baseline_settings_buffer"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            mode,
+            pipeline,
+            bind_group_layout,
+            settings_buffer,
+            textures: None,
+            image_size: None,
+            current: 0,
+            frames: 0,
+        }
+    }
+
+    /// Frames folded into the baseline since it was (re)started
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Starts the baseline anew with the next frame
+    pub fn restart(&mut self) {
+        self.frames = 0;
+    }
+
+    /// Folds the surface into the baseline and copies the baseline into `target`. The baseline
+    /// restarts with surfaces of another size.
+    pub fn fold(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface: &SurfaceTexture,
+        target: &ReferenceTexture,
+    ) {
+        let image_size = surface.image_size();
+        if self.image_size.as_ref() != Some(image_size) {
+            if self.frames > 0 {
+                log::info!("Surface size changed, restarting the baseline");
+            }
+            self.textures = Some([0, 1].map(|_| Self::create_texture(device, image_size)));
+            self.image_size = Some(image_size.clone());
+            self.frames = 0;
+        }
+        let Some(textures) = &self.textures else {
+            return;
+        };
+        let weight = self.mode.weight(self.frames);
+        queue.write_buffer(
+            &self.settings_buffer,
+            0,
+            bytemuck::cast_slice(&[weight, 0.0, 0.0, 0.0]),
+        );
+        let next = 1 - self.current;
+        let view =
+            |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("baseline_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&surface.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view(&textures[self.current])),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&view(&textures[next])),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.settings_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("baseline_encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("baseline_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(
+                image_size.width.get().div_ceil(Self::WORKGROUP_SIZE),
+                image_size.height.get().div_ceil(Self::WORKGROUP_SIZE),
+                1,
+            );
+        }
+        encoder.copy_texture_to_texture(
+            textures[next].as_image_copy(),
+            target.data.as_image_copy(),
+            textures[next].size(),
+        );
+        queue.submit(Some(encoder.finish()));
+        self.current = next;
+        self.frames = self.frames.saturating_add(1);
+    }
+
+    fn create_texture(device: &wgpu::Device, image_size: &ImageSize) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: image_size.width.get(),
+                height: image_size.height.get(),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            label: Some("baseline_texture"),
+            view_formats: &[],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modes_parse_and_weigh_frames() {
+        assert_eq!(
+            "first".parse::<BaselineMode>().unwrap(),
+            BaselineMode::FirstFrame
+        );
+        let rolling: BaselineMode = "rolling".parse().unwrap();
+        assert_eq!(
+            rolling,
+            BaselineMode::RollingAverage {
+                weight: BaselineMode::DEFAULT_WEIGHT
+            }
+        );
+        assert_eq!(
+            rolling.to_string().parse::<BaselineMode>().unwrap(),
+            rolling
+        );
+        assert!("rolling:0".parse::<BaselineMode>().is_err());
+        assert!("average".parse::<BaselineMode>().is_err());
+
+        assert_eq!(BaselineMode::FirstFrame.weight(0), 1.0);
+        assert_eq!(BaselineMode::FirstFrame.weight(3), 0.0);
+        assert_eq!(rolling.weight(3), BaselineMode::DEFAULT_WEIGHT);
+    }
+}
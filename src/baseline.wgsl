@@ -0,0 +1,35 @@
+// Folds a new frame into the baseline of the live surfaces, see `baseline.rs`
+
+@group(0) @binding(0)
+var frame: texture_2d<f32>;
+@group(0) @binding(1)
+var previous: texture_2d<f32>;
+@group(0) @binding(2)
+var baseline: texture_storage_2d<r32float, write>;
+
+struct FoldSettings {
+    // Share of the new frame in the baseline, 1 restarts it from the frame
+    weight: f32,
+}
+
+@group(0) @binding(3)
+var<uniform> settings: FoldSettings;
+
+@compute @workgroup_size(8, 8)
+fn fold(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(baseline);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let value = textureLoad(frame, pixel, 0).x;
+    let before = textureLoad(previous, pixel, 0).x;
+    var folded = mix(before, value, settings.weight);
+    // Missing pixels keep the baseline, pixels missing in the baseline so far are filled in
+    if (settings.weight >= 1.0 || before != before) {
+        folded = value;
+    } else if (value != value) {
+        folded = before;
+    }
+    textureStore(baseline, pixel, vec4<f32>(folded, 0.0, 0.0, 0.0));
+}
@@ -0,0 +1,316 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::{
+    compute::ComputeDevice, image::SurfaceAmplitudeImage, offscreen::OffscreenRenderer,
+    probe::NeighborhoodStats, provenance, registration::Registration,
+};
+
+/// Edge length of the rendered thumbnails in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Options of the `batch` subcommand
+#[derive(Debug, PartialEq)]
+pub struct BatchOptions {
+    /// File pattern, `*` and `?` are allowed in the file name
+    pub pattern: String,
+    pub out: PathBuf,
+    /// Writes the per-pixel mean and standard deviation of all surfaces to `temporal.tiff`
+    pub temporal: bool,
+    /// Aligns every surface with the first one and adds their shifts to the summary
+    pub register: bool,
+    /// Index or name of the GPU the temporal statistics run on instead of the rendering one
+    pub compute_adapter: Option<String>,
+}
+
+impl BatchOptions {
+    /// Parses the arguments after `batch`:
+    /// `<glob> [--out <dir>] [--temporal] [--register] [--compute-adapter <index|name>]`
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut pattern = None;
+        let mut out = PathBuf::from("report");
+        let mut temporal = false;
+        let mut register = false;
+        let mut compute_adapter = None;
+        while let Some(arg) = args.next() {
+            if arg == "--out" {
+                out = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--out needs a directory"))?
+                    .into();
+            } else if arg == "--temporal" {
+                temporal = true;
+            } else if arg == "--register" {
+                register = true;
+            } else if arg == "--compute-adapter" {
+                compute_adapter = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--compute-adapter needs an index or name"))?,
+                );
+            } else if pattern.is_none() {
+                pattern = Some(arg);
+            } else {
+                return Err(anyhow!("Unexpected argument: {}", arg));
+            }
+        }
+        Ok(Self {
+            pattern: pattern.ok_or_else(|| {
+                anyhow!(
+                    "Usage: batch <glob> [--out <dir>] [--temporal] [--register] \
+                     [--compute-adapter <index|name>]"
+                )
+            })?,
+            out,
+            temporal,
+            register,
+            compute_adapter,
+        })
+    }
+}
+
+/// Loads every matching file without a window, writes a thumbnail per file and a `summary.csv`
+/// with the surface statistics to the output directory. With `temporal` the files are frames of
+/// a sequence, whose per-pixel mean and standard deviation go to `temporal.tiff`. With `register`
+/// they are aligned with the first file before, their shifts are added to the summary.
+pub fn run(options: &BatchOptions) -> anyhow::Result<()> {
+    let files = expand_glob(&options.pattern)?;
+    if files.is_empty() {
+        return Err(anyhow!("No files match {}", options.pattern));
+    }
+    std::fs::create_dir_all(&options.out)
+        .with_context(|| format!("Failed to create {}", options.out.display()))?;
+    let mut offscreen = pollster::block_on(OffscreenRenderer::new(THUMBNAIL_SIZE, THUMBNAIL_SIZE))?;
+    if let Some(selector) = &options.compute_adapter {
+        let compute = pollster::block_on(ComputeDevice::open(selector))?;
+        offscreen.renderer().set_compute_device(Some(compute));
+    }
+    if options.temporal {
+        offscreen.renderer().set_temporal_collecting(true);
+    }
+
+    let mut registration = options.register.then(Registration::default);
+
+    let mut csv = String::from(
+        "file,sha256,width,height,valid_pixels,unit,min,max,mean,std,tilt_x,tilt_y,thumbnail",
+    );
+    if options.register {
+        csv.push_str(",shift_x,shift_y,shift_z");
+    }
+    csv.push('\n');
+    let mut failed = 0;
+    for file in &files {
+        match process(&mut offscreen, file, &options.out, registration.as_mut()) {
+            Ok(row) => csv.push_str(&row),
+            Err(e) => {
+                log::error!("Failed to process {}: {:#}", file.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    let summary = options.out.join("summary.csv");
+    std::fs::write(&summary, csv)?;
+    if options.temporal {
+        write_temporal_maps(&mut offscreen, &options.out.join("temporal.tiff"))?;
+    }
+    log::info!(
+        "Processed {} of {} files, summary written to {}",
+        files.len() - failed,
+        files.len(),
+        summary.display()
+    );
+    Ok(())
+}
+
+/// Renders the thumbnail of one file and returns its CSV row
+fn process(
+    offscreen: &mut OffscreenRenderer,
+    file: &Path,
+    out: &Path,
+    registration: Option<&mut Registration>,
+) -> anyhow::Result<String> {
+    let path = file
+        .to_str()
+        .ok_or_else(|| anyhow!("Path is not valid UTF-8"))?;
+    let image = SurfaceAmplitudeImage::from_file(path)?;
+    let sha256 = provenance::hash_file(file)?;
+    let surface = image.surface;
+    let stats = NeighborhoodStats::whole_image(&surface)
+        .ok_or_else(|| anyhow!("Surface contains no finite values"))?;
+    let (width, height) = (surface.size.width, surface.size.height);
+    // The statistics are those of the file, the thumbnail and the temporal maps are aligned
+    let (surface, shift) = match registration {
+        Some(registration) => {
+            let (surface, shift) = registration.register(surface);
+            (surface, Some(shift))
+        }
+        None => (surface, None),
+    };
+
+    let renderer = offscreen.renderer();
+    renderer.set_surface(surface, None, None)?;
+    // Same view a file opens with so the thumbnails are comparable
+    renderer.reset_camera();
+    let thumbnail_name = format!(
+        "{}.png",
+        file.file_stem().unwrap_or_default().to_string_lossy()
+    );
+    std::fs::write(out.join(&thumbnail_name), offscreen.render()?.to_png()?)?;
+
+    let mut row = String::new();
+    write!(
+        row,
+        "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        csv_field(path),
+        sha256,
+        width,
+        height,
+        stats.count,
+        image.unit.unwrap_or_default().symbol(),
+        stats.min,
+        stats.max,
+        stats.mean,
+        stats.std,
+        stats.gradient_x,
+        stats.gradient_y,
+        csv_field(&thumbnail_name)
+    )?;
+    if let Some(shift) = shift {
+        write!(row, ",{},{},{}", shift.x, shift.y, shift.z)?;
+    }
+    row.push('\n');
+    Ok(row)
+}
+
+/// Writes the mean as first and the standard deviation as second page, so that the file opens
+/// like a measurement with the deviation as amplitude
+fn write_temporal_maps(offscreen: &mut OffscreenRenderer, path: &Path) -> anyhow::Result<()> {
+    let maps = offscreen
+        .renderer()
+        .temporal_maps()
+        .ok_or_else(|| anyhow!("No surfaces for temporal statistics"))?;
+    let maps = pollster::block_on(maps)?;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = tiff::encoder::TiffEncoder::new(&mut file)?;
+    for map in [&maps.mean, &maps.std_dev] {
+        encoder.write_image::<tiff::encoder::colortype::Gray32Float>(
+            map.size.width.get(),
+            map.size.height.get(),
+            &map.data,
+        )?;
+    }
+    log::info!(
+        "Temporal statistics of {} surfaces written to {}",
+        maps.frames,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Lists the files matching `pattern` in sorted order. Only the file name may contain
+/// wildcards: `*` matches any number of characters and `?` exactly one.
+fn expand_glob(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let path = Path::new(pattern);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid file pattern: {}", pattern))?;
+    if !name.contains(['*', '?']) {
+        return Ok(vec![path.to_owned()]);
+    }
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to list {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|file_name| wildcard_match(name, file_name))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Position after the last `*` and the text position it was matched up to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` match one more character
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn match_wildcards() {
+        assert!(wildcard_match("*.tiff", "scan.tiff"));
+        assert!(wildcard_match("scan-??.tif*", "scan-01.tiff"));
+        assert!(!wildcard_match("*.tiff", "scan.tif"));
+        assert!(wildcard_match("a*b*c", "aXbYbc"));
+    }
+
+    #[test]
+    fn parse_options() {
+        let args = [
+            "data/*.tiff",
+            "--out",
+            "out",
+            "--temporal",
+            "--register",
+            "--compute-adapter",
+            "1",
+        ]
+        .map(String::from)
+        .into_iter();
+        assert_eq!(
+            BatchOptions::parse(args).unwrap(),
+            BatchOptions {
+                pattern: "data/*.tiff".to_owned(),
+                out: PathBuf::from("out"),
+                temporal: true,
+                register: true,
+                compute_adapter: Some("1".to_owned()),
+            }
+        );
+        assert!(BatchOptions::parse(std::iter::empty()).is_err());
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+    }
+}
@@ -0,0 +1,361 @@
+use std::fmt::Write as _;
+
+use anyhow::anyhow;
+use web_time::{Duration, Instant};
+
+use crate::{
+    generator::Generator,
+    image::{Image, ImageSize, Resampling, SurfaceAmplitudeImage},
+    json::json_string,
+    offscreen::OffscreenRenderer,
+    parallel,
+    texture::{OverlayBlendMode, example_overlays, rasterize_overlays},
+};
+
+/// Options of the `--bench` mode
+#[derive(Debug, PartialEq)]
+pub struct BenchOptions {
+    /// File of the surface, or the generator of a surface written as it is parsed
+    pub file: String,
+    /// Generates the surface instead of reading it, see `generator`
+    pub generator: Option<Generator>,
+    /// Frames of the camera sweep, one full turn around the surface
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BenchOptions {
+    /// Parses the arguments after `--bench`: `<file> [--frames <n>] [--size <width>x<height>]`,
+    /// `--generate <kind>[:<params>]` instead of the file benchmarks a generated surface
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut file = None;
+        let mut generator = None::<Generator>;
+        let mut frames = 360;
+        let (mut width, mut height) = (1280, 720);
+        while let Some(arg) = args.next() {
+            if arg == "--frames" {
+                frames = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--frames needs a number"))?
+                    .parse()?;
+            } else if arg == "--size" {
+                let size = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--size needs <width>x<height>"))?;
+                let (w, h) = size
+                    .split_once('x')
+                    .ok_or_else(|| anyhow!("Invalid size: {}", size))?;
+                (width, height) = (w.parse()?, h.parse()?);
+            } else if arg == "--generate" && file.is_none() {
+                let spec = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--generate needs <kind>[:<params>]"))?;
+                let parsed = spec.parse()?;
+                file = Some(format!("{}", parsed));
+                generator = Some(parsed);
+            } else if file.is_none() {
+                file = Some(arg);
+            } else {
+                return Err(anyhow!("Unexpected argument: {}", arg));
+            }
+        }
+        Ok(Self {
+            file: file.ok_or_else(|| {
+                anyhow!(
+                    "Usage: --bench <file>|--generate <kind>[:<params>] [--frames <n>] [--size <width>x<height>]"
+                )
+            })?,
+            generator,
+            frames: frames.max(1),
+            width,
+            height,
+        })
+    }
+}
+
+/// Timing of one frame of the sweep
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct FrameTiming {
+    azimuth: f32,
+    elevation: f32,
+    /// Encoding and submitting the frame on the CPU
+    encode: Duration,
+    /// Until the GPU finished the frame
+    total: Duration,
+}
+
+/// Time of a CPU preprocessing step on one thread and on all threads of `parallel`
+#[derive(Debug)]
+struct StepTiming {
+    step: &'static str,
+    sequential: Duration,
+    parallel: Duration,
+}
+
+/// Statistics of the frame times in milliseconds
+#[derive(Debug, PartialEq)]
+struct Summary {
+    mean: f64,
+    median: f64,
+    p95: f64,
+    max: f64,
+}
+
+impl Summary {
+    fn new(frames: &[FrameTiming]) -> Option<Self> {
+        let mut times: Vec<f64> = frames
+            .iter()
+            .map(|frame| frame.total.as_secs_f64() * 1000.0)
+            .collect();
+        if times.is_empty() {
+            return None;
+        }
+        times.sort_by(f64::total_cmp);
+        let percentile = |p: f64| times[((times.len() - 1) as f64 * p).round() as usize];
+        Some(Self {
+            mean: times.iter().sum::<f64>() / times.len() as f64,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            max: times[times.len() - 1],
+        })
+    }
+}
+
+/// Loads the file without a window, turns the camera once around it while tilting it up and
+/// down and prints the time of every frame and the memory use as JSON to stdout
+pub fn run(options: &BenchOptions) -> anyhow::Result<()> {
+    let mut offscreen = pollster::block_on(OffscreenRenderer::new(options.width, options.height))?;
+
+    let start = Instant::now();
+    let surface = match &options.generator {
+        Some(generator) => generator.generate(ImageSize::new(
+            Generator::DEFAULT_SIZE,
+            Generator::DEFAULT_SIZE,
+        )?),
+        None => SurfaceAmplitudeImage::from_file(&options.file)?.surface,
+    };
+    let decode = start.elapsed();
+    let size = surface.size.clone();
+    let preprocess = time_preprocessing(&surface)?;
+    let start = Instant::now();
+    offscreen.renderer().set_surface(surface, None, None)?;
+    offscreen.renderer().reset_camera();
+    // The first frame compiles pipelines on some backends
+    offscreen.render_and_wait()?;
+    let upload = start.elapsed();
+
+    let frames = (0..options.frames)
+        .map(|i| {
+            let turn = i as f32 / options.frames as f32;
+            let azimuth = turn * 360.0;
+            let elevation = 60.0 + 30.0 * (turn * std::f32::consts::TAU).cos();
+            offscreen
+                .renderer()
+                .transformation
+                .set_orientation(azimuth, elevation);
+            let start = Instant::now();
+            let encode = offscreen.render_and_wait()?;
+            Ok(FrameTiming {
+                azimuth,
+                elevation,
+                encode,
+                total: start.elapsed(),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let summary = Summary::new(&frames).ok_or_else(|| anyhow!("No frames rendered"))?;
+
+    let adapter = offscreen.adapter_info();
+    let mut json = String::from("{\n");
+    writeln!(json, "  \"file\": {},", json_string(&options.file))?;
+    writeln!(json, "  \"adapter\": {},", json_string(&adapter.name))?;
+    writeln!(
+        json,
+        "  \"backend\": {},",
+        json_string(&adapter.backend.to_string())
+    )?;
+    writeln!(
+        json,
+        "  \"viewport\": {{\"width\": {}, \"height\": {}}},",
+        options.width, options.height
+    )?;
+    writeln!(
+        json,
+        "  \"surface\": {{\"width\": {}, \"height\": {}}},",
+        size.width, size.height
+    )?;
+    writeln!(json, "  \"decode_ms\": {:.3},", millis(decode))?;
+    writeln!(json, "  \"upload_ms\": {:.3},", millis(upload))?;
+    writeln!(
+        json,
+        "  \"preprocess\": {{\"threads\": {}, \"steps\": [",
+        parallel::threads()
+    )?;
+    for (i, step) in preprocess.iter().enumerate() {
+        let separator = if i + 1 < preprocess.len() { "," } else { "" };
+        writeln!(
+            json,
+            "    {{\"step\": {}, \"sequential_ms\": {:.3}, \"parallel_ms\": {:.3}, \
+             \"speedup\": {:.2}}}{}",
+            json_string(step.step),
+            millis(step.sequential),
+            millis(step.parallel),
+            step.sequential.as_secs_f64() / step.parallel.as_secs_f64().max(1e-9),
+            separator
+        )?;
+    }
+    json.push_str("  ]},\n");
+    json.push_str("  \"frames\": [\n");
+    for (i, frame) in frames.iter().enumerate() {
+        let separator = if i + 1 < frames.len() { "," } else { "" };
+        writeln!(
+            json,
+            "    {{\"frame\": {}, \"azimuth\": {:.2}, \"elevation\": {:.2}, \"encode_ms\": {:.3}, \
+             \"frame_ms\": {:.3}}}{}",
+            i,
+            frame.azimuth,
+            frame.elevation,
+            millis(frame.encode),
+            millis(frame.total),
+            separator
+        )?;
+    }
+    json.push_str("  ],\n");
+    writeln!(
+        json,
+        "  \"summary\": {{\"mean_ms\": {:.3}, \"median_ms\": {:.3}, \"p95_ms\": {:.3}, \
+         \"max_ms\": {:.3}, \"fps\": {:.1}}},",
+        summary.mean,
+        summary.median,
+        summary.p95,
+        summary.max,
+        1000.0 / summary.mean
+    )?;
+    writeln!(
+        json,
+        "  \"memory\": {{\"resident_bytes\": {}, \"gpu_allocated_bytes\": {}}}",
+        json_number(resident_bytes()),
+        json_number(offscreen.allocated_bytes())
+    )?;
+    json.push('}');
+    println!("{}", json);
+    Ok(())
+}
+
+/// Times the preprocessing of a loaded surface once on a single thread and once in parallel
+fn time_preprocessing(surface: &Image<f32>) -> anyhow::Result<Vec<StepTiming>> {
+    let half = ImageSize::new(
+        (surface.size.width.get() / 2).max(1),
+        (surface.size.height.get() / 2).max(1),
+    )?;
+    let overlays = example_overlays();
+    let pixels = surface.size.pixel_count();
+    let steps: [(&'static str, &(dyn Fn() + Sync)); 4] = [
+        ("outlier_removed_data", &|| {
+            std::hint::black_box(surface.outlier_removed_data(2.0, 98.0));
+        }),
+        ("scaled_data", &|| {
+            let _ = std::hint::black_box(surface.scaled_data(0.0, 1.0));
+        }),
+        ("resize", &|| {
+            std::hint::black_box(surface.resize(&half, Resampling::Area));
+        }),
+        ("rasterize_overlays", &|| {
+            std::hint::black_box(rasterize_overlays(
+                &overlays,
+                pixels,
+                OverlayBlendMode::WeightedBlended,
+            ));
+        }),
+    ];
+    let time = |step: &(dyn Fn() + Sync)| {
+        let start = Instant::now();
+        step();
+        start.elapsed()
+    };
+    steps
+        .into_iter()
+        .map(|(name, step)| {
+            Ok(StepTiming {
+                step: name,
+                sequential: parallel::sequential(|| time(step))?,
+                parallel: time(step),
+            })
+        })
+        .collect()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn json_number(value: Option<u64>) -> String {
+    value.map_or_else(|| "null".to_owned(), |value| value.to_string())
+}
+
+/// Physical memory used by the process, only known on Linux
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generator::GeneratorKind;
+
+    #[test]
+    fn parse_options() {
+        let args = ["scan.tiff", "--frames", "10", "--size", "640x480"]
+            .into_iter()
+            .map(String::from);
+        assert_eq!(
+            BenchOptions::parse(args).unwrap(),
+            BenchOptions {
+                file: "scan.tiff".to_owned(),
+                generator: None,
+                frames: 10,
+                width: 640,
+                height: 480,
+            }
+        );
+        assert!(BenchOptions::parse(std::iter::empty()).is_err());
+
+        let args = ["--generate", "rough:height=0.5"]
+            .into_iter()
+            .map(String::from);
+        let options = BenchOptions::parse(args).unwrap();
+        assert_eq!(options.generator.unwrap().kind, GeneratorKind::Rough);
+        assert!(options.file.starts_with("rough:height=0.5,"));
+        let args = ["--generate", "rough", "scan.tiff"]
+            .into_iter()
+            .map(String::from);
+        assert!(BenchOptions::parse(args).is_err());
+    }
+
+    #[test]
+    fn summarizes_frame_times() {
+        let frames: Vec<_> = (1..=20)
+            .map(|ms| FrameTiming {
+                azimuth: 0.0,
+                elevation: 90.0,
+                encode: Duration::ZERO,
+                total: Duration::from_millis(ms),
+            })
+            .collect();
+        let summary = Summary::new(&frames).unwrap();
+        assert!((summary.mean - 10.5).abs() < 1e-9);
+        assert_eq!(summary.p95, 19.0);
+        assert_eq!(summary.max, 20.0);
+        assert_eq!(Summary::new(&[]), None);
+    }
+}
@@ -0,0 +1,95 @@
+use web_time::Duration;
+
+use crate::colormap::{Colormap, CvdSimulation};
+
+/// Display settings that are swapped when blinking between two states
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VisualState {
+    pub use_height_shader: bool,
+    pub show_residual: bool,
+    pub colormap: Colormap,
+    pub cvd_simulation: CvdSimulation,
+}
+
+/// Alternates between the current display settings and a stored snapshot, a classic way to spot
+/// subtle differences between two settings
+#[derive(Default)]
+pub struct Blink {
+    stored: Option<VisualState>,
+    /// Time between automatic swaps, `None` swaps only on request
+    interval: Option<Duration>,
+    elapsed: Duration,
+}
+
+impl Blink {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers the state to compare the current one with
+    pub fn store(&mut self, state: VisualState) {
+        self.stored = Some(state);
+    }
+
+    /// Returns the stored state to show and stores `current` in its place
+    pub fn swap(&mut self, current: VisualState) -> Option<VisualState> {
+        self.elapsed = Duration::ZERO;
+        let stored = self.stored?;
+        self.stored = Some(current);
+        Some(stored)
+    }
+
+    pub fn set_interval(&mut self, interval: Option<Duration>) {
+        self.interval = interval.filter(|interval| !interval.is_zero());
+        self.elapsed = Duration::ZERO;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.interval.is_some() && self.stored.is_some()
+    }
+
+    /// Advances automatic blinking, returns the state to show when it is time to swap
+    pub fn advance(&mut self, delta: Duration, current: VisualState) -> Option<VisualState> {
+        let interval = self.interval?;
+        self.elapsed += delta;
+        if self.elapsed < interval {
+            return None;
+        }
+        self.swap(current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state(colormap: Colormap) -> VisualState {
+        VisualState {
+            use_height_shader: true,
+            show_residual: false,
+            colormap,
+            cvd_simulation: CvdSimulation::None,
+        }
+    }
+
+    #[test]
+    fn blinks_between_stored_and_current_state() {
+        let mut blink = Blink::new();
+        assert_eq!(blink.swap(state(Colormap::Classic)), None);
+        blink.store(state(Colormap::Classic));
+        blink.set_interval(Some(Duration::from_millis(100)));
+        assert!(blink.is_running());
+        let current = state(Colormap::Viridis);
+        assert_eq!(blink.advance(Duration::from_millis(60), current), None);
+        assert_eq!(
+            blink.advance(Duration::from_millis(60), current),
+            Some(state(Colormap::Classic))
+        );
+        assert_eq!(
+            blink.swap(state(Colormap::Classic)),
+            Some(state(Colormap::Viridis))
+        );
+    }
+}
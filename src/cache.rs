@@ -0,0 +1,339 @@
+use std::{
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    data_source::{self, DataSource},
+    dataset_info::DatasetInfo,
+    error::ViewerError,
+    image::{Image, ImageSize, SurfaceAmplitudeImage, SurfaceProducts, ZValueRange},
+    provenance,
+    units::Unit,
+};
+
+/// Format version of the entries, entries of other versions are decoded again
+const MAGIC: &[u8; 8] = b"DV3DC\0\0\x01";
+
+/// Surface of a file with what is computed from it to show it
+pub struct LoadedSurface {
+    pub surface: Image<f32>,
+    pub products: SurfaceProducts,
+    pub unit: Option<Unit>,
+    pub info: DatasetInfo,
+}
+
+/// Decodes a file and computes its products, through `cache` if there is one. URLs are
+/// downloaded and not cached.
+pub fn load(cache: Option<&DatasetCache>, path: &str) -> Result<LoadedSurface, ViewerError> {
+    if data_source::is_url(path) {
+        return load_source(&*data_source::open(path));
+    }
+    match cache {
+        Some(cache) => cache.load(path),
+        None => decode(path),
+    }
+}
+
+/// Reads and decodes a dataset of any source and computes its products
+pub fn load_source(source: &dyn DataSource) -> Result<LoadedSurface, ViewerError> {
+    let image = data_source::block_on(SurfaceAmplitudeImage::from_source(source))?;
+    Ok(LoadedSurface {
+        products: SurfaceProducts::compute(&image.surface)?,
+        surface: image.surface,
+        unit: image.unit,
+        info: image.info,
+    })
+}
+
+fn decode(path: &str) -> Result<LoadedSurface, ViewerError> {
+    let mut image = SurfaceAmplitudeImage::from_file(path)?;
+    image.info.sha256 = Some(provenance::hash_file(Path::new(path))?);
+    Ok(LoadedSurface {
+        products: SurfaceProducts::compute(&image.surface)?,
+        surface: image.surface,
+        unit: image.unit,
+        info: image.info,
+    })
+}
+
+/// Cache of decoded surfaces, their mip levels, height range and checksum on disk, so that
+/// opening a large file again only reads the entry. Entries are keyed by the path of the file
+/// and dropped when the file's size or modification time changes. The least recently used
+/// entries are removed when the cache grows beyond its limit.
+#[derive(Debug)]
+pub struct DatasetCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl DatasetCache {
+    pub const DEFAULT_MAX_BYTES: u64 = 2 << 30;
+
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// `$XDG_CACHE_HOME/data-viewer-3d`, or `~/.cache/data-viewer-3d` on Unix and
+    /// `%LOCALAPPDATA%\data-viewer-3d` on Windows
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(base.join("data-viewer-3d"))
+    }
+
+    /// Reads the entry of the file if it is up to date, otherwise decodes the file and stores
+    /// a new entry. Failing to use the cache only costs time, the file is decoded then.
+    pub fn load(&self, path: &str) -> Result<LoadedSurface, ViewerError> {
+        let source = Source::of(path)?;
+        let entry = self.entry_path(path);
+        match read_entry(&entry, &source) {
+            Ok(Some((surface, products, sha256))) => {
+                log::info!("Loaded {} from the cache", path);
+                touch(&entry);
+                let (unit, mut info) = SurfaceAmplitudeImage::read_tags(path)?;
+                info.sha256 = Some(sha256);
+                return Ok(LoadedSurface {
+                    surface,
+                    products,
+                    unit,
+                    info,
+                });
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Ignoring cache entry of {}: {}", path, e),
+        }
+        let loaded = decode(path)?;
+        let sha256 = loaded.info.sha256.as_deref().unwrap_or_default();
+        if let Err(e) = self.store(&entry, &source, &loaded.surface, &loaded.products, sha256) {
+            log::warn!("Failed to cache {}: {}", path, e);
+        }
+        Ok(loaded)
+    }
+
+    fn entry_path(&self, path: &str) -> PathBuf {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.into());
+        let key = provenance::sha256_hex(path.to_string_lossy().as_bytes());
+        self.dir.join(format!("{}.bin", key))
+    }
+
+    fn store(
+        &self,
+        entry: &Path,
+        source: &Source,
+        surface: &Image<f32>,
+        products: &SurfaceProducts,
+        sha256: &str,
+    ) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        // Written to a temporary file first, so that other viewers never read half an entry
+        let temporary = entry.with_extension(format!("{}.tmp", std::process::id()));
+        {
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&temporary)?);
+            file.write_all(MAGIC)?;
+            file.write_all(&source.len.to_le_bytes())?;
+            file.write_all(&source.modified_nanos.to_le_bytes())?;
+            let mut digest = [0; 64];
+            digest[..sha256.len().min(64)]
+                .copy_from_slice(&sha256.as_bytes()[..sha256.len().min(64)]);
+            file.write_all(&digest)?;
+            let range = &products.height_range;
+            file.write_all(bytemuck::cast_slice(&[range.min(), range.max()]))?;
+            for image in std::iter::once(surface).chain(&products.mips) {
+                file.write_all(&image.size.width.get().to_le_bytes())?;
+                file.write_all(&image.size.height.get().to_le_bytes())?;
+                file.write_all(bytemuck::cast_slice(&image.data))?;
+            }
+            file.into_inner()?.sync_all()?;
+        }
+        std::fs::rename(&temporary, entry)?;
+        self.evict()
+    }
+
+    /// Removes the least recently used entries until the cache fits its limit
+    fn evict(&self) -> std::io::Result<()> {
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                let path = entry.path();
+                let used = metadata.modified().ok()?;
+                (path.extension()? == "bin").then_some((path, metadata.len(), used))
+            })
+            .collect();
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort_by_key(|(_, _, used)| *used);
+        for (path, len, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            log::info!("Removing {} from the cache", path.display());
+            std::fs::remove_file(&path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+/// Size and modification time of a file, an entry is only valid for the same ones
+#[derive(Debug, PartialEq)]
+struct Source {
+    len: u64,
+    modified_nanos: u128,
+}
+
+impl Source {
+    fn of(path: &str) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata.modified()?;
+        Ok(Self {
+            len: metadata.len(),
+            modified_nanos: modified
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos()),
+        })
+    }
+}
+
+/// Marks the entry as recently used for `DatasetCache::evict`
+fn touch(entry: &Path) {
+    let touched = std::fs::File::options()
+        .write(true)
+        .open(entry)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(e) = touched {
+        log::warn!(
+            "Failed to update the cache entry {}: {}",
+            entry.display(),
+            e
+        );
+    }
+}
+
+/// The surface, its products and the SHA-256 of the file, `None` if there is no entry for the
+/// current version of the file
+fn read_entry(
+    entry: &Path,
+    source: &Source,
+) -> Result<Option<(Image<f32>, SurfaceProducts, String)>, ViewerError> {
+    let bytes = match std::fs::read(entry) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = Reader(&bytes);
+    let stale = reader.take(MAGIC.len())? != MAGIC
+        || u64::from_le_bytes(reader.array()?) != source.len
+        || u128::from_le_bytes(reader.array()?) != source.modified_nanos;
+    if stale {
+        log::info!("Cache entry {} is outdated", entry.display());
+        std::fs::remove_file(entry)?;
+        return Ok(None);
+    }
+    let sha256 = String::from_utf8_lossy(reader.take(64)?)
+        .trim_end_matches('\0')
+        .to_owned();
+    let min = f32::from_le_bytes(reader.array()?);
+    let max = f32::from_le_bytes(reader.array()?);
+    let mut image = || -> Result<Image<f32>, ViewerError> {
+        let size = ImageSize::new(
+            u32::from_le_bytes(reader.array()?),
+            u32::from_le_bytes(reader.array()?),
+        )?;
+        let data = reader
+            .take(size.pixel_count() * 4)?
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+            .collect();
+        Image::new(size, data)
+    };
+    let surface = image()?;
+    let mips = [image()?, image()?];
+    let products = SurfaceProducts {
+        height_range: ZValueRange::new(min, max),
+        mips,
+    };
+    Ok(Some((surface, products, sha256)))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ViewerError> {
+        if self.0.len() < len {
+            return Err(ViewerError::InvalidImage(
+                "cache entry is truncated".to_owned(),
+            ));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ViewerError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entries_are_reused_until_the_file_changes() {
+        let dir = std::env::temp_dir().join(format!("data-viewer-3d-cache-{}", std::process::id()));
+        let cache = DatasetCache::new(dir.clone(), DatasetCache::DEFAULT_MAX_BYTES);
+        let surface = Image::new(
+            ImageSize::new(4, 2).unwrap(),
+            (0..8).map(|v| v as f32).collect(),
+        )
+        .unwrap();
+        let products = SurfaceProducts::compute(&surface).unwrap();
+        let source = Source {
+            len: 100,
+            modified_nanos: 7,
+        };
+        let entry = dir.join("entry.bin");
+        cache
+            .store(&entry, &source, &surface, &products, "abc")
+            .unwrap();
+
+        let (cached, cached_products, sha256) = read_entry(&entry, &source).unwrap().unwrap();
+        assert_eq!(cached.data[..], surface.data[..]);
+        assert_eq!(cached_products.mips[0].data[..], products.mips[0].data[..]);
+        assert_eq!(
+            cached_products.height_range.max(),
+            products.height_range.max()
+        );
+        assert_eq!(sha256, "abc");
+
+        let changed = Source {
+            len: 100,
+            modified_nanos: 8,
+        };
+        assert!(read_entry(&entry, &changed).unwrap().is_none());
+        assert!(!entry.exists());
+
+        // Entries beyond the limit are removed, the newest ones are kept
+        let small = DatasetCache::new(dir.clone(), 1);
+        small
+            .store(&entry, &source, &surface, &products, "abc")
+            .unwrap();
+        assert!(!entry.exists());
+
+        // The second load reads the entry and the tags of the file
+        let example = concat!(env!("CARGO_MANIFEST_DIR"), "/example-img.tiff");
+        let decoded = cache.load(example).unwrap();
+        assert!(cache.entry_path(example).exists());
+        let cached = cache.load(example).unwrap();
+        assert_eq!(cached.surface.data.len(), decoded.surface.data.len());
+        assert_eq!(cached.info.sha256, decoded.info.sha256);
+        assert_eq!(cached.info.width, decoded.info.width);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+use glam::{Mat4, Quat, Vec4};
+use std::ops::{Add, Mul, Sub};
+use web_time::Duration;
+
+use crate::camera_sync::CameraState;
+
+/// Camera pose at a point in time of a camera path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: Duration,
+    pub camera: CameraState,
+}
+
+/// Keyframes a smooth Catmull-Rom path goes through, e.g. for a fly-through video of a defect
+#[derive(Clone, Debug, Default)]
+pub struct CameraPath {
+    /// Sorted by time, no two at the same time
+    keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    /// Time between keyframes appended without a time
+    pub const DEFAULT_SPACING: Duration = Duration::from_secs(2);
+
+    /// Adds a keyframe, replacing one at the same time
+    pub fn add(&mut self, time: Duration, camera: CameraState) {
+        match self.keyframes.binary_search_by(|k| k.time.cmp(&time)) {
+            Ok(i) => self.keyframes[i].camera = camera,
+            Err(i) => self.keyframes.insert(i, Keyframe { time, camera }),
+        }
+    }
+
+    /// Adds a keyframe `DEFAULT_SPACING` after the last one and returns its time
+    pub fn append(&mut self, camera: CameraState) -> Duration {
+        let time = self
+            .keyframes
+            .last()
+            .map_or(Duration::ZERO, |last| last.time + Self::DEFAULT_SPACING);
+        self.add(time, camera);
+        time
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> Duration {
+        self.keyframes.last().map_or(Duration::ZERO, |k| k.time)
+    }
+
+    /// Camera at `time`, before the first and after the last keyframe the camera rests there
+    pub fn sample(&self, time: Duration) -> Option<CameraState> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.len() - 1;
+        let Some(next) = self.keyframes.iter().position(|k| k.time > time) else {
+            return Some(self.keyframes[last].camera);
+        };
+        if next == 0 {
+            return Some(first.camera);
+        }
+        // The end points are repeated to find the tangents of the outer segments
+        let indices = [next.saturating_sub(2), next - 1, next, (next + 1).min(last)];
+        let keyframes = indices.map(|i| self.keyframes[i]);
+        let times = keyframes.map(|k| k.time.as_secs_f32());
+        let t = time.as_secs_f32();
+
+        // Neighbouring quaternions on the same hemisphere take the short way round
+        let mut rotations = keyframes.map(|k| Vec4::from(Quat::from_mat4(&k.camera.rotation)));
+        for i in 1..rotations.len() {
+            if rotations[i].dot(rotations[i - 1]) < 0.0 {
+                rotations[i] = -rotations[i];
+            }
+        }
+        let rotation = Quat::from_vec4(catmull_rom(rotations, times, t)).normalize();
+        // Zooming by the same factor takes the same time at any magnification
+        let zoom = catmull_rom(keyframes.map(|k| k.camera.zoom.ln()), times, t).exp();
+        Some(CameraState {
+            rotation: Mat4::from_quat(rotation),
+            pan: catmull_rom(keyframes.map(|k| k.camera.pan), times, t),
+            zoom,
+        })
+    }
+}
+
+/// Value at `t` between `points[1]` and `points[2]` of a Catmull-Rom spline through `points`
+/// at non-uniformly spaced `times`
+fn catmull_rom<T>(points: [T; 4], times: [f32; 4], t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let [p0, p1, p2, p3] = points;
+    let [t0, t1, t2, t3] = times;
+    let h = t2 - t1;
+    let s = (t - t1) / h;
+    let m1 = (p2 - p0) * (h / (t2 - t0));
+    let m2 = (p3 - p1) * (h / (t3 - t1));
+    // Cubic Hermite basis
+    let s2 = s * s;
+    let s3 = s2 * s;
+    p1 * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + m1 * (s3 - 2.0 * s2 + s)
+        + p2 * (-2.0 * s3 + 3.0 * s2)
+        + m2 * (s3 - s2)
+}
+
+/// Playback of a camera path, also used to export it frame by frame
+#[derive(Debug, Default)]
+pub struct CameraPathPlayback {
+    elapsed: Duration,
+    finished: bool,
+}
+
+impl CameraPathPlayback {
+    /// Returns the camera and then advances by `delta`, `None` after the last keyframe was
+    /// returned
+    pub fn advance(&mut self, path: &CameraPath, delta: Duration) -> Option<CameraState> {
+        if self.finished {
+            return None;
+        }
+        let camera = path.sample(self.elapsed.min(path.duration()));
+        self.finished = self.elapsed >= path.duration();
+        self.elapsed += delta;
+        camera
+    }
+}
+
+/// Writes the frames of a camera path as numbered PNG files, e.g. for ffmpeg to encode a video
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FrameExport {
+    pub dir: std::path::PathBuf,
+    fps: u32,
+    pub playback: CameraPathPlayback,
+    pub frames: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FrameExport {
+    pub const DEFAULT_FPS: u32 = 30;
+
+    pub fn new(dir: std::path::PathBuf, fps: u32) -> Self {
+        Self {
+            dir,
+            fps: fps.max(1),
+            playback: CameraPathPlayback::default(),
+            frames: 0,
+        }
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.fps
+    }
+
+    /// File of the next frame, which is counted as written
+    pub fn next_file(&mut self) -> std::path::PathBuf {
+        let file = self.dir.join(format!("frame-{:05}.png", self.frames));
+        self.frames += 1;
+        file
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec2;
+
+    use super::*;
+
+    fn panned(x: f32, zoom: f32) -> CameraState {
+        CameraState {
+            rotation: Mat4::IDENTITY,
+            pan: Vec2::new(x, 0.0),
+            zoom,
+        }
+    }
+
+    #[test]
+    fn path_goes_through_keyframes() {
+        let mut path = CameraPath::default();
+        path.append(panned(0.0, 1.0));
+        path.append(panned(1.0, 0.5));
+        let end = path.append(CameraState {
+            rotation: Mat4::from_rotation_z(1.0),
+            ..panned(3.0, 0.25)
+        });
+        assert_eq!(end, Duration::from_secs(4));
+        for keyframe in path.keyframes() {
+            let camera = path.sample(keyframe.time).unwrap();
+            assert!(camera.pan.abs_diff_eq(keyframe.camera.pan, 1e-5));
+            assert!((camera.zoom - keyframe.camera.zoom).abs() < 1e-5);
+            assert!(camera.rotation.abs_diff_eq(keyframe.camera.rotation, 1e-5));
+        }
+        // Between keyframes and clamped outside the path
+        let middle = path.sample(Duration::from_secs(1)).unwrap();
+        assert!(middle.pan.x > 0.0 && middle.pan.x < 1.0);
+        assert!(middle.zoom < 1.0 && middle.zoom > 0.5);
+        assert_eq!(
+            path.sample(Duration::from_secs(9)),
+            Some(path.keyframes()[2].camera)
+        );
+
+        // Replacing a keyframe keeps the path sorted
+        path.add(Duration::from_secs(2), panned(2.0, 0.5));
+        assert_eq!(path.keyframes().len(), 3);
+        assert_eq!(path.keyframes()[1].camera.pan.x, 2.0);
+    }
+
+    #[test]
+    fn playback_ends_with_the_last_keyframe() {
+        let mut path = CameraPath::default();
+        path.append(panned(0.0, 1.0));
+        path.append(panned(1.0, 1.0));
+        let mut playback = CameraPathPlayback::default();
+        let mut cameras = vec![];
+        while let Some(camera) = playback.advance(&path, Duration::from_millis(300)) {
+            cameras.push(camera);
+        }
+        assert_eq!(cameras.len(), 8);
+        assert_eq!(cameras.last(), Some(&panned(1.0, 1.0)));
+    }
+}
@@ -0,0 +1,204 @@
+use glam::{Mat4, Vec2};
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+use winit::window::Window;
+
+/// Camera parameters exchanged between synchronized viewers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraState {
+    pub rotation: Mat4,
+    pub pan: Vec2,
+    pub zoom: f32,
+}
+
+// Only the browser needs a flat representation
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+impl CameraState {
+    const LEN: usize = 19;
+
+    /// Flat representation used to send the camera across the browser
+    pub fn to_array(self) -> [f32; Self::LEN] {
+        let mut array = [0.0; Self::LEN];
+        array[..16].copy_from_slice(&self.rotation.to_cols_array());
+        array[16..18].copy_from_slice(&self.pan.to_array());
+        array[18] = self.zoom;
+        array
+    }
+
+    pub fn from_slice(values: &[f32]) -> Option<Self> {
+        if values.len() != Self::LEN || values.iter().any(|v| !v.is_finite()) {
+            return None;
+        }
+        Some(Self {
+            rotation: Mat4::from_cols_slice(&values[..16]),
+            pan: Vec2::new(values[16], values[17]),
+            zoom: values[18],
+        })
+    }
+}
+
+type Inbox = Rc<RefCell<Option<CameraState>>>;
+
+/// Handler of the messages on a `BroadcastChannel`, kept alive as long as the channel
+#[cfg(target_arch = "wasm32")]
+type MessageHandler = wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>;
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Subscriber {
+    id: u64,
+    inbox: Inbox,
+    window: Arc<Window>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+thread_local! {
+    /// All viewers of this process share the event loop thread, so the groups live there
+    static GROUPS: RefCell<std::collections::HashMap<String, Vec<Subscriber>>> =
+        RefCell::new(std::collections::HashMap::new());
+    static NEXT_ID: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// Keeps the camera of several viewers in lockstep.
+/// Natively all viewers of the process joining the same group are linked,
+/// on the web the group is the name of a `BroadcastChannel` so viewers in other tabs follow.
+pub struct CameraSync {
+    /// Latest camera received from another viewer, applied before the next frame
+    inbox: Inbox,
+    #[cfg(not(target_arch = "wasm32"))]
+    membership: Option<(String, u64)>,
+    #[cfg(target_arch = "wasm32")]
+    channel: Option<(web_sys::BroadcastChannel, MessageHandler)>,
+}
+
+impl Default for CameraSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CameraSync {
+    pub fn new() -> Self {
+        Self {
+            inbox: Rc::new(RefCell::new(None)),
+            #[cfg(not(target_arch = "wasm32"))]
+            membership: None,
+            #[cfg(target_arch = "wasm32")]
+            channel: None,
+        }
+    }
+
+    pub fn is_joined(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.membership.is_some();
+        #[cfg(target_arch = "wasm32")]
+        return self.channel.is_some();
+    }
+
+    /// Links this viewer to all other viewers of `group`, leaving the previous group.
+    /// `window` is redrawn whenever another viewer moves its camera.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn join(&mut self, group: &str, window: Arc<Window>) -> anyhow::Result<()> {
+        self.leave();
+        let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+        GROUPS.with_borrow_mut(|groups| {
+            groups
+                .entry(group.to_owned())
+                .or_default()
+                .push(Subscriber {
+                    id,
+                    inbox: self.inbox.clone(),
+                    window,
+                })
+        });
+        self.membership = Some((group.to_owned(), id));
+        Ok(())
+    }
+
+    /// Links this viewer to all other viewers of `group`, leaving the previous group.
+    /// `window` is redrawn whenever another viewer moves its camera.
+    #[cfg(target_arch = "wasm32")]
+    pub fn join(&mut self, group: &str, window: Arc<Window>) -> anyhow::Result<()> {
+        use wasm_bindgen::JsCast;
+
+        self.leave();
+        let channel = web_sys::BroadcastChannel::new(group)
+            .map_err(|e| anyhow::anyhow!("Failed to open broadcast channel: {:?}", e))?;
+        let inbox = self.inbox.clone();
+        let on_message = wasm_bindgen::closure::Closure::<dyn FnMut(web_sys::MessageEvent)>::new(
+            move |event: web_sys::MessageEvent| {
+                let values = js_sys::Float32Array::new(&event.data()).to_vec();
+                if let Some(camera) = CameraState::from_slice(&values) {
+                    *inbox.borrow_mut() = Some(camera);
+                    window.request_redraw();
+                }
+            },
+        );
+        channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        self.channel = Some((channel, on_message));
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some((group, id)) = self.membership.take() {
+            GROUPS.with_borrow_mut(|groups| {
+                if let Some(subscribers) = groups.get_mut(&group) {
+                    subscribers.retain(|subscriber| subscriber.id != id);
+                }
+            });
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some((channel, _)) = self.channel.take() {
+            channel.set_onmessage(None);
+            channel.close();
+        }
+    }
+
+    /// Sends the camera of this viewer to all other viewers of the group
+    pub fn publish(&self, camera: CameraState) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some((group, id)) = &self.membership {
+            GROUPS.with_borrow(|groups| {
+                for subscriber in groups.get(group).into_iter().flatten() {
+                    if subscriber.id != *id {
+                        *subscriber.inbox.borrow_mut() = Some(camera);
+                        subscriber.window.request_redraw();
+                    }
+                }
+            });
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some((channel, _)) = &self.channel {
+            let values = js_sys::Float32Array::from(&camera.to_array()[..]);
+            if let Err(e) = channel.post_message(&values) {
+                log::error!("Failed to publish camera: {:?}", e);
+            }
+        }
+    }
+
+    /// Returns the camera received from another viewer since the last call
+    pub fn take_update(&self) -> Option<CameraState> {
+        self.inbox.borrow_mut().take()
+    }
+}
+
+impl Drop for CameraSync {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn camera_state_round_trips_through_array() {
+        let camera = CameraState {
+            rotation: Mat4::from_rotation_x(0.3),
+            pan: Vec2::new(0.1, -0.2),
+            zoom: 0.5,
+        };
+        assert_eq!(CameraState::from_slice(&camera.to_array()), Some(camera));
+        assert_eq!(CameraState::from_slice(&[0.0; 3]), None);
+    }
+}
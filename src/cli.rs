@@ -0,0 +1,420 @@
+// Command line of the viewer window. Subcommands like `batch` or `render` parse their own
+// arguments, everything else is a file to open or one of the options below.
+
+use anyhow::anyhow;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    baseline::BaselineMode,
+    cache,
+    color_space::{IccProfile, OutputColorSpace},
+    compositing::Compositing,
+    config::Shader,
+    generator::Generator,
+    leveling::RowLeveling,
+    navigation::NavigationSettings,
+    overlay_style::{ColorRamp, ScoreFilter},
+    power,
+    retention::RetentionPolicy,
+    texture::AmplitudeRange,
+};
+
+/// Options of the viewer window
+#[derive(Debug)]
+pub struct ViewerOptions {
+    /// Every file is opened in its own window, the first one in the initial window
+    pub files: Vec<String>,
+    pub reference: Option<String>,
+    pub report: Option<String>,
+    pub watch: Option<String>,
+    pub archive: Option<String>,
+    pub coalesce: bool,
+    pub power_preference: wgpu::PowerPreference,
+    pub max_fps: Option<f32>,
+    pub navigation: Option<NavigationSettings>,
+    pub gamepad: bool,
+    /// `Some(None)` turns the coarser mesh while moving off
+    pub target_frame_time: Option<Option<web_time::Duration>>,
+    pub pixel_aspect: Option<f32>,
+    pub raw_pixels: bool,
+    pub retention: RetentionPolicy,
+    /// Bytes of the cache of decoded files, `None` without cache
+    pub cache_size: Option<u64>,
+    pub figure: Option<String>,
+    pub row_leveling: Option<RowLeveling>,
+    pub compositing: Option<Compositing>,
+    pub overlay_ramp: Option<ColorRamp>,
+    pub overlay_filter: Option<ScoreFilter>,
+    pub audit_key: Option<Vec<u8>>,
+    pub baseline: Option<BaselineMode>,
+    pub register: bool,
+    pub compute_adapter: Option<String>,
+    pub color_space: Option<OutputColorSpace>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub shader: Option<Shader>,
+    /// Inner size of the windows in physical pixels
+    pub window_size: Option<PhysicalSize<u32>>,
+    pub amplitude_range: Option<AmplitudeRange>,
+    /// Config file read instead of `viewer.toml`
+    pub config: Option<String>,
+    /// Shows a generated surface instead of a file
+    pub generator: Option<Generator>,
+}
+
+impl ViewerOptions {
+    /// Parses the arguments of the viewer window:
+    /// `--reference <file>` compares the first file with a nominal surface,
+    /// `--report <file>` writes the pass/fail tolerance report of that comparison,
+    /// `--watch <dir>` loads the newest file of a directory as it appears and
+    /// `--archive <dir>` stores a screenshot of every file loaded that way,
+    /// `--no-coalesce` handles every mouse move instead of at most one per frame,
+    /// `--power <low|high|default>` chooses the GPU,
+    /// `--max-fps <rate>` caps the frame rate of animations,
+    /// `--navigation <settings>` sets the mouse sensitivity and inverted axes, e.g.
+    /// "rotate=1.5,invert-y",
+    /// `--pixel-aspect <ratio>` is the ratio of the y to the x pixel spacing,
+    /// `--raw-pixels` shows every pixel as a square regardless of it,
+    /// `--target-frame-time <ms|off>` draws a coarser mesh while the camera moves to hold the
+    /// frame time, 16 ms by default,
+    /// `--gamepad` navigates with gamepads and SpaceMice connected as Linux joysticks,
+    /// `--retention <keep|map|drop>` decides what happens to the CPU copy of an image after
+    /// its upload to the GPU,
+    /// `--cache-size <MB|off>` limits the cache of decoded files, 2048 MB by default,
+    /// `--figure <file>` exports the first file as SVG figure with legend and caption,
+    /// `--level-rows <offset|linear>` removes the stripes of raster-scanning instruments from
+    /// every loaded surface,
+    /// `--blend <brightness|overlay>[:weight]` modulates the shown layer by the other one,
+    /// `--overlay-ramp <key:colormap[:min:max]>` colors overlays by a metadata value,
+    /// `--overlay-min <key:min>` hides overlays with a metadata value below the minimum,
+    /// `--baseline <first|rolling[:weight]>` compares every loaded surface with the first one or
+    /// their rolling average, shown with 'E' like the deviation from a reference,
+    /// `--register` aligns every loaded surface with the first one, for drifting stages,
+    /// `--compute-adapter <index|name>` accumulates the temporal statistics on another GPU than
+    /// the one drawing,
+    /// `--color-space <srgb|display-p3|profile.icc>` renders for a wide-gamut display without
+    /// color management and tags screenshots and figures with that color space,
+    /// `--audit-key <file>` signs the exported audit trail with HMAC-SHA256 and the key in the
+    /// file,
+    /// `--record <file>` writes the mouse and keyboard input to a file,
+    /// `--replay <file>` feeds such a recording back, with its file if none is given,
+    /// `--shader <height|amplitude>` chooses what the surface is colored by,
+    /// `--size <width>x<height>` sets the size of the windows in pixels,
+    /// `--percentiles <lower>,<upper>` maps the amplitudes between these percentiles to the
+    /// amplitude colormap,
+    /// `--config <file>` reads the defaults of the viewer from that file instead of
+    /// `viewer.toml` and
+    /// `--generate <kind>[:<params>]` shows a surface with known properties, e.g.
+    /// "rough:height=0.5,period=8", see `generator`
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut options = Self {
+            files: Vec::new(),
+            reference: None,
+            report: None,
+            watch: None,
+            archive: None,
+            coalesce: true,
+            power_preference: wgpu::PowerPreference::default(),
+            max_fps: None,
+            navigation: None,
+            gamepad: false,
+            target_frame_time: None,
+            pixel_aspect: None,
+            raw_pixels: false,
+            retention: RetentionPolicy::default(),
+            cache_size: Some(cache::DatasetCache::DEFAULT_MAX_BYTES),
+            figure: None,
+            row_leveling: None,
+            compositing: None,
+            overlay_ramp: None,
+            overlay_filter: None,
+            audit_key: None,
+            baseline: None,
+            register: false,
+            compute_adapter: None,
+            color_space: None,
+            record: None,
+            replay: None,
+            shader: None,
+            window_size: None,
+            amplitude_range: None,
+            config: None,
+            generator: None,
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--reference" {
+                options.reference = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--reference needs a file"))?,
+                );
+            } else if arg == "--report" {
+                options.report = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--report needs a file"))?,
+                );
+            } else if arg == "--watch" {
+                options.watch = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--watch needs a directory"))?,
+                );
+            } else if arg == "--archive" {
+                options.archive = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--archive needs a directory"))?,
+                );
+            } else if arg == "--power" {
+                options.power_preference = power::parse_power_preference(
+                    &args
+                        .next()
+                        .ok_or_else(|| anyhow!("--power needs a preference"))?,
+                )?;
+            } else if arg == "--max-fps" {
+                options.max_fps = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--max-fps needs a frame rate"))?
+                        .parse::<f32>()?,
+                );
+            } else if arg == "--navigation" {
+                options.navigation = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--navigation needs settings"))?
+                        .parse::<NavigationSettings>()?,
+                );
+            } else if arg == "--retention" {
+                options.retention = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--retention needs a policy"))?
+                    .parse()?;
+            } else if arg == "--cache-size" {
+                let megabytes = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--cache-size needs megabytes or off"))?;
+                options.cache_size = if megabytes == "off" {
+                    None
+                } else {
+                    Some(megabytes.parse::<u64>()? << 20)
+                };
+            } else if arg == "--target-frame-time" {
+                let millis = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--target-frame-time needs milliseconds or off"))?;
+                options.target_frame_time = Some(if millis == "off" {
+                    None
+                } else {
+                    Some(web_time::Duration::try_from_secs_f32(
+                        millis.parse::<f32>()? / 1000.0,
+                    )?)
+                });
+            } else if arg == "--pixel-aspect" {
+                options.pixel_aspect = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--pixel-aspect needs a ratio"))?
+                        .parse::<f32>()?,
+                );
+            } else if arg == "--figure" {
+                options.figure = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--figure needs a file"))?,
+                );
+            } else if arg == "--level-rows" {
+                options.row_leveling = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--level-rows needs offset or linear"))?
+                        .parse::<RowLeveling>()?,
+                );
+            } else if arg == "--blend" {
+                options.compositing = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--blend needs brightness or overlay"))?
+                        .parse::<Compositing>()?,
+                );
+            } else if arg == "--overlay-ramp" {
+                options.overlay_ramp = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--overlay-ramp needs key:colormap[:min:max]"))?
+                        .parse::<ColorRamp>()?,
+                );
+            } else if arg == "--overlay-min" {
+                options.overlay_filter = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--overlay-min needs key:min"))?
+                        .parse::<ScoreFilter>()?,
+                );
+            } else if arg == "--baseline" {
+                options.baseline = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--baseline needs first or rolling[:weight]"))?
+                        .parse::<BaselineMode>()?,
+                );
+            } else if arg == "--register" {
+                options.register = true;
+            } else if arg == "--compute-adapter" {
+                options.compute_adapter = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--compute-adapter needs an index or name"))?,
+                );
+            } else if arg == "--color-space" {
+                let name = args.next().ok_or_else(|| {
+                    anyhow!("--color-space needs srgb, display-p3 or an ICC profile")
+                })?;
+                options.color_space = Some(
+                    if name.to_lowercase().ends_with(".icc")
+                        || name.to_lowercase().ends_with(".icm")
+                    {
+                        let data = std::fs::read(&name)
+                            .map_err(|e| anyhow!("Failed to read ICC profile {}: {}", name, e))?;
+                        OutputColorSpace::Icc(IccProfile::parse(data)?)
+                    } else {
+                        name.parse()?
+                    },
+                );
+            } else if arg == "--audit-key" {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--audit-key needs a file"))?;
+                let key = std::fs::read(&path)
+                    .map_err(|e| anyhow!("Failed to read audit key {}: {}", path, e))?;
+                // Keys written with `echo` end with a newline
+                options.audit_key = Some(key.trim_ascii_end().to_vec());
+            } else if arg == "--record" {
+                options.record = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--record needs a file"))?,
+                );
+            } else if arg == "--replay" {
+                options.replay = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--replay needs a file"))?,
+                );
+            } else if arg == "--shader" {
+                options.shader = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--shader needs height or amplitude"))?
+                        .parse::<Shader>()?,
+                );
+            } else if arg == "--size" {
+                options.window_size = Some(parse_size(
+                    &args
+                        .next()
+                        .ok_or_else(|| anyhow!("--size needs <width>x<height>"))?,
+                )?);
+            } else if arg == "--percentiles" {
+                options.amplitude_range =
+                    Some(parse_percentiles(&args.next().ok_or_else(|| {
+                        anyhow!("--percentiles needs <lower>,<upper>")
+                    })?)?);
+            } else if arg == "--config" {
+                options.config = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--config needs a file"))?,
+                );
+            } else if arg == "--generate" {
+                options.generator = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--generate needs <kind>[:<params>]"))?
+                        .parse()?,
+                );
+            } else if arg == "--raw-pixels" {
+                options.raw_pixels = true;
+            } else if arg == "--gamepad" {
+                options.gamepad = true;
+            } else if arg == "--no-coalesce" {
+                options.coalesce = false;
+            } else if arg.starts_with("--") {
+                return Err(anyhow!("Unknown option: {}", arg));
+            } else {
+                options.files.push(arg);
+            }
+        }
+        if options.report.is_some() && options.reference.is_none() {
+            return Err(anyhow!("--report needs a --reference to compare with"));
+        }
+        if options.archive.is_some() && options.watch.is_none() {
+            return Err(anyhow!("--archive needs a --watch directory"));
+        }
+        Ok(options)
+    }
+}
+
+/// Reads a window size like "1600x900"
+fn parse_size(s: &str) -> anyhow::Result<PhysicalSize<u32>> {
+    let invalid = || anyhow!("Invalid window size: {}", s);
+    let (width, height) = s
+        .to_lowercase()
+        .split_once('x')
+        .ok_or_else(invalid)
+        .and_then(|(width, height)| {
+            Ok((
+                width.trim().parse::<u32>().map_err(|_| invalid())?,
+                height.trim().parse::<u32>().map_err(|_| invalid())?,
+            ))
+        })?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok(PhysicalSize::new(width, height))
+}
+
+/// Reads percentile limits like "1,99"
+fn parse_percentiles(s: &str) -> anyhow::Result<AmplitudeRange> {
+    let invalid = || anyhow!("Invalid percentiles: {}", s);
+    let (lower, upper) = s.split_once(',').ok_or_else(invalid)?;
+    let lower = lower.trim().parse::<f32>().map_err(|_| invalid())?;
+    let upper = upper.trim().parse::<f32>().map_err(|_| invalid())?;
+    AmplitudeRange::percentile(lower, upper)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> anyhow::Result<ViewerOptions> {
+        ViewerOptions::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn shader_size_and_percentiles_are_parsed() {
+        let options = parse(&[
+            "path/to/scan.tiff",
+            "--shader",
+            "height",
+            "--size",
+            "1600x900",
+            "--percentiles",
+            "1,99",
+            "--config",
+            "lab.toml",
+            "--generate",
+            "steps:height=2",
+        ])
+        .unwrap();
+        assert_eq!(options.files, ["path/to/scan.tiff"]);
+        assert_eq!(options.shader, Some(Shader::Height));
+        assert_eq!(options.window_size, Some(PhysicalSize::new(1600, 900)));
+        assert_eq!(options.amplitude_range, Some(AmplitudeRange::PERCENTILES));
+        assert_eq!(options.config.as_deref(), Some("lab.toml"));
+        assert_eq!(options.generator.unwrap().params.height, 2.0);
+
+        let options = parse(&[]).unwrap();
+        assert!(options.files.is_empty());
+        assert_eq!(options.shader, None);
+        assert_eq!(options.window_size, None);
+        assert!(options.coalesce);
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        for args in [
+            &["--shader", "wireframe"][..],
+            &["--shader"],
+            &["--size", "1600"],
+            &["--size", "0x900"],
+            &["--percentiles", "99,1"],
+            &["--percentiles", "1,101"],
+            &["--report", "report.html"],
+            &["--frobnicate"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+}
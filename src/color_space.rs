@@ -0,0 +1,275 @@
+// The shaders compute colors in linear sRGB and the window shows them through an sRGB view of
+// the swapchain. Wide-gamut displays without color management, common outside macOS, show those
+// values with their own, more saturated primaries. Rendering for such a display converts to its
+// primaries before the sRGB transfer curve is applied, and exported images are tagged with the
+// color space their pixels are in. Browsers color-manage the canvas, so the web uses sRGB.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use anyhow::anyhow;
+use glam::{Mat3, Vec3};
+
+use crate::error::ViewerError;
+
+/// sRGB primaries in CIE XYZ, adapted to the D50 white of ICC profiles with Bradford
+const SRGB_TO_XYZ_D50: Mat3 = Mat3::from_cols_array(&[
+    0.436_074_7,
+    0.222_504_5,
+    0.013_932_2,
+    0.385_064_9,
+    0.716_878_6,
+    0.097_104_5,
+    0.143_080_4,
+    0.060_616_9,
+    0.714_173_3,
+]);
+
+/// Linear sRGB to linear Display P3, both with the D65 white
+const SRGB_TO_DISPLAY_P3: Mat3 = Mat3::from_cols_array(&[
+    0.822_462_1,
+    0.033_194_2,
+    0.017_082_7,
+    0.177_538,
+    0.966_805_8,
+    0.072_397_4,
+    0.0,
+    0.0,
+    0.910_519_9,
+]);
+
+/// Chromaticities of the red, green and blue primaries of Display P3
+pub const DISPLAY_P3_PRIMARIES: [(f32, f32); 3] = [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)];
+pub const D65_WHITE: (f32, f32) = (0.3127, 0.3290);
+
+/// Color space the rendered pixels are meant for
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+    /// RGB display described by the matrix of its ICC profile
+    Icc(IccProfile),
+}
+
+impl OutputColorSpace {
+    /// Converts linear sRGB to the linear RGB of the color space, the transfer curve is the
+    /// one of sRGB for all of them
+    pub fn matrix(&self) -> Mat3 {
+        match self {
+            Self::Srgb => Mat3::IDENTITY,
+            Self::DisplayP3 => SRGB_TO_DISPLAY_P3,
+            Self::Icc(profile) => profile.from_srgb,
+        }
+    }
+}
+
+impl fmt::Display for OutputColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Srgb => write!(f, "srgb"),
+            Self::DisplayP3 => write!(f, "display-p3"),
+            Self::Icc(profile) => write!(f, "ICC profile {}", profile.description),
+        }
+    }
+}
+
+impl FromStr for OutputColorSpace {
+    type Err = anyhow::Error;
+
+    /// Named color spaces only, profiles are read with `IccProfile::parse`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "srgb" => Ok(Self::Srgb),
+            "display-p3" | "p3" => Ok(Self::DisplayP3),
+            _ => Err(anyhow!("Unsupported color space: {}", s)),
+        }
+    }
+}
+
+/// RGB matrix/TRC ICC profile of a display, kept as read to embed it in exported images
+#[derive(Clone, Debug)]
+pub struct IccProfile {
+    pub data: Arc<[u8]>,
+    /// Name of the profile, or its size where it has none this parser reads
+    pub description: String,
+    from_srgb: Mat3,
+}
+
+impl PartialEq for IccProfile {
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl Eq for IccProfile {}
+
+impl IccProfile {
+    /// Reads the colorants of an RGB display profile. Their tone curves are not, the display
+    /// is expected to follow the sRGB curve like most do closely enough.
+    pub fn parse(data: Vec<u8>) -> Result<Self, ViewerError> {
+        let invalid = |reason: &str| ViewerError::InvalidIccProfile(reason.to_owned());
+        if data.len() < 132 || &data[36..40] != b"acsp" {
+            return Err(invalid("not an ICC profile"));
+        }
+        if &data[16..20] != b"RGB " {
+            return Err(invalid("not an RGB profile"));
+        }
+        let u32_at = |offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        let tag = |signature: &[u8; 4]| {
+            let count = u32_at(128)? as usize;
+            (0..count).map(|index| 132 + index * 12).find_map(|entry| {
+                (data.get(entry..entry + 4)? == signature).then(|| {
+                    let offset = u32_at(entry + 4)? as usize;
+                    let size = u32_at(entry + 8)? as usize;
+                    data.get(offset..offset.checked_add(size)?)
+                })?
+            })
+        };
+        let xyz = |signature: &[u8; 4]| {
+            let tag = tag(signature)
+                .filter(|tag| tag.len() >= 20 && &tag[..4] == b"XYZ ")
+                .ok_or_else(|| invalid("no matrix colorants"))?;
+            let value = |offset: usize| {
+                i32::from_be_bytes(tag[offset..offset + 4].try_into().unwrap()) as f32 / 65536.0
+            };
+            Ok::<_, ViewerError>(Vec3::new(value(8), value(12), value(16)))
+        };
+        let to_xyz = Mat3::from_cols(xyz(b"rXYZ")?, xyz(b"gXYZ")?, xyz(b"bXYZ")?);
+        if to_xyz.determinant().abs() < 1e-6 {
+            return Err(invalid("degenerate colorants"));
+        }
+        let description = tag(b"desc")
+            .and_then(Self::description)
+            .unwrap_or_else(|| format!("of {} bytes", data.len()));
+        Ok(Self {
+            from_srgb: to_xyz.inverse() * SRGB_TO_XYZ_D50,
+            description,
+            data: data.into(),
+        })
+    }
+
+    /// Text of a `desc` tag of version 2 or the first record of a `mluc` tag of version 4
+    fn description(tag: &[u8]) -> Option<String> {
+        let u32_at = |offset: usize| {
+            tag.get(offset..offset + 4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+        };
+        let text = match tag.get(..4)? {
+            b"desc" => String::from_utf8_lossy(tag.get(12..12 + u32_at(8)?)?).into_owned(),
+            b"mluc" => {
+                let (length, offset) = (u32_at(20)?, u32_at(24)?);
+                let utf16 = tag.get(offset..offset + length)?;
+                String::from_utf16_lossy(
+                    &utf16
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            _ => return None,
+        };
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Minimal version 2 profile with the colorants and a description
+    fn profile(colorants: [[f32; 3]; 3], name: &str) -> Vec<u8> {
+        let mut tags: Vec<(&[u8; 4], Vec<u8>)> = [b"rXYZ", b"gXYZ", b"bXYZ"]
+            .into_iter()
+            .zip(colorants)
+            .map(|(signature, xyz)| {
+                let mut tag = b"XYZ \0\0\0\0".to_vec();
+                for value in xyz {
+                    tag.extend(((value * 65536.0).round() as i32).to_be_bytes());
+                }
+                (signature, tag)
+            })
+            .collect();
+        let mut desc = b"desc\0\0\0\0".to_vec();
+        desc.extend((name.len() as u32 + 1).to_be_bytes());
+        desc.extend(name.as_bytes());
+        desc.push(0);
+        tags.push((b"desc", desc));
+
+        let mut data = vec![0; 128];
+        data[16..20].copy_from_slice(b"RGB ");
+        data[36..40].copy_from_slice(b"acsp");
+        data.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        for (signature, tag) in &tags {
+            data.extend(*signature);
+            data.extend((offset as u32).to_be_bytes());
+            data.extend((tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+        }
+        for (_, tag) in tags {
+            data.extend(tag);
+        }
+        data
+    }
+
+    #[test]
+    fn profiles_convert_from_srgb_to_their_primaries() {
+        let srgb = IccProfile::parse(profile(
+            [
+                [0.4361, 0.2225, 0.0139],
+                [0.3851, 0.7169, 0.0971],
+                [0.1431, 0.0606, 0.7141],
+            ],
+            "sRGB",
+        ))
+        .unwrap();
+        assert_eq!(srgb.description, "sRGB");
+        assert!(srgb.from_srgb.abs_diff_eq(Mat3::IDENTITY, 1e-3));
+
+        let display_p3 = IccProfile::parse(profile(
+            [
+                [0.5151, 0.2412, -0.0011],
+                [0.2920, 0.6922, 0.0419],
+                [0.1571, 0.0666, 0.7841],
+            ],
+            "Display P3",
+        ))
+        .unwrap();
+        assert!(
+            OutputColorSpace::Icc(display_p3)
+                .matrix()
+                .abs_diff_eq(OutputColorSpace::DisplayP3.matrix(), 2e-3)
+        );
+        // White stays white
+        let white = OutputColorSpace::DisplayP3.matrix() * Vec3::ONE;
+        assert!(white.abs_diff_eq(Vec3::ONE, 1e-4));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(IccProfile::parse(vec![0; 200]).is_err());
+        let mut cmyk = profile([[0.0; 3]; 3], "");
+        cmyk[16..20].copy_from_slice(b"CMYK");
+        assert!(IccProfile::parse(cmyk).is_err());
+        let mut truncated = profile([[0.0; 3]; 3], "");
+        truncated.truncate(150);
+        assert!(IccProfile::parse(truncated).is_err());
+    }
+
+    #[test]
+    fn named_color_spaces() {
+        assert_eq!(
+            "Display-P3".parse::<OutputColorSpace>().unwrap(),
+            OutputColorSpace::DisplayP3
+        );
+        assert_eq!(
+            "srgb".parse::<OutputColorSpace>().unwrap(),
+            OutputColorSpace::Srgb
+        );
+        assert!("adobe-rgb".parse::<OutputColorSpace>().is_err());
+    }
+}
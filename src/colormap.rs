@@ -0,0 +1,190 @@
+use anyhow::anyhow;
+use glam::Mat3;
+use std::str::FromStr;
+
+/// Maps heights and amplitudes to colors
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    /// Grayscale heights and red to green amplitudes
+    #[default]
+    Classic,
+    /// Perceptually uniform and readable with all common color vision deficiencies
+    Viridis,
+    /// Dark blue over gray to yellow, only varies along the blue-yellow axis that protanopes
+    /// and deuteranopes still perceive
+    BlueYellow,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 3] = [Colormap::Classic, Colormap::Viridis, Colormap::BlueYellow];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Colormap::Classic => "classic",
+            Colormap::Viridis => "viridis",
+            Colormap::BlueYellow => "blue-yellow",
+        }
+    }
+
+    /// Returns the colormap following this one, used to cycle through colormaps with a key
+    pub fn next(&self) -> Colormap {
+        let index = Self::ALL.iter().position(|c| c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// sRGB color of a height normalized to [0, 1] like the shader computes it, for previews
+    /// made on the CPU. The classic colormap shows heights in gray.
+    pub fn srgb(&self, t: f32) -> [f32; 3] {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Colormap::Classic => [t; 3],
+            Colormap::Viridis => {
+                // Polynomial fit of matplotlib's viridis, see `viridis` in shader.wgsl
+                const C: [[f32; 3]; 7] = [
+                    [0.277_727_33, 0.005_407_344_5, 0.334_099_8],
+                    [0.105_093_04, 1.404_613_5, 1.384_590_1],
+                    [-0.330_861_83, 0.214_847_56, 0.095_095_16],
+                    [-4.634_230_6, -5.799_101, -19.332_441],
+                    [6.228_27, 14.179_933, 56.690_55],
+                    [4.776_385, -13.745_145, -65.353_03],
+                    [-5.435_456, 4.645_852_6, 26.312_435],
+                ];
+                std::array::from_fn(|channel| {
+                    C.iter()
+                        .rev()
+                        .fold(0.0, |sum, c| sum * t + c[channel])
+                        .clamp(0.0, 1.0)
+                })
+            }
+            Colormap::BlueYellow => {
+                let (from, to, t) = if t < 0.5 {
+                    ([0.0, 0.125, 0.302], [0.486, 0.482, 0.471], t * 2.0)
+                } else {
+                    ([0.486, 0.482, 0.471], [0.996, 0.910, 0.218], t * 2.0 - 1.0)
+                };
+                std::array::from_fn(|channel| from[channel] + (to[channel] - from[channel]) * t)
+            }
+        }
+    }
+
+    /// Index of the colormap in the shader
+    pub(crate) fn shader_index(&self) -> u32 {
+        match self {
+            Colormap::Classic => 0,
+            Colormap::Viridis => 1,
+            Colormap::BlueYellow => 2,
+        }
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Colormap::ALL
+            .into_iter()
+            .find(|c| c.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported colormap: {}", s))
+    }
+}
+
+/// Simulates how the rendered frame looks with a color vision deficiency, to check that
+/// figures stay readable for everyone
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CvdSimulation {
+    #[default]
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl CvdSimulation {
+    pub const ALL: [CvdSimulation; 4] = [
+        CvdSimulation::None,
+        CvdSimulation::Protanopia,
+        CvdSimulation::Deuteranopia,
+        CvdSimulation::Tritanopia,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CvdSimulation::None => "none",
+            CvdSimulation::Protanopia => "protanopia",
+            CvdSimulation::Deuteranopia => "deuteranopia",
+            CvdSimulation::Tritanopia => "tritanopia",
+        }
+    }
+
+    pub fn next(&self) -> CvdSimulation {
+        let index = Self::ALL.iter().position(|c| c == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Matrix applied to linear RGB colors, from Machado, Oliveira and Fernandes (2009)
+    /// at full severity
+    pub fn matrix(&self) -> Mat3 {
+        let rows = match self {
+            CvdSimulation::None => return Mat3::IDENTITY,
+            CvdSimulation::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            CvdSimulation::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            CvdSimulation::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        };
+        Mat3::from_cols_array_2d(&rows).transpose()
+    }
+}
+
+impl FromStr for CvdSimulation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CvdSimulation::ALL
+            .into_iter()
+            .find(|c| c.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported color vision deficiency: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn simulation_keeps_grays() {
+        for simulation in CvdSimulation::ALL {
+            let white = simulation.matrix() * Vec3::ONE;
+            assert!(white.abs_diff_eq(Vec3::ONE, 1e-3), "{:?}", simulation);
+        }
+        // Red and green become hard to tell apart without L cones
+        let matrix = CvdSimulation::Protanopia.matrix();
+        let red = matrix * Vec3::X;
+        let green = matrix * Vec3::Y;
+        assert!(red.x < 0.2 && green.x > 1.0);
+    }
+
+    #[test]
+    fn parse_names() {
+        for colormap in Colormap::ALL {
+            assert_eq!(colormap.name().parse::<Colormap>().unwrap(), colormap);
+        }
+        assert!("jet".parse::<Colormap>().is_err());
+        assert_eq!(
+            "Deuteranopia".parse::<CvdSimulation>().unwrap(),
+            CvdSimulation::Deuteranopia
+        );
+    }
+}
@@ -0,0 +1,141 @@
+// The shown layer colors the surface with its colormap, the other layer of the dataset can
+// modulate that color, e.g. to see the texture of the amplitudes on the height colors.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use anyhow::anyhow;
+use std::{fmt, str::FromStr};
+
+/// How the other layer modulates the colors of the shown one
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayerBlend {
+    /// Darkens the colors where the other layer is low
+    Brightness,
+    /// Mixes in the colors of the other layer's colormap
+    Overlay,
+}
+
+impl LayerBlend {
+    pub const ALL: [LayerBlend; 2] = [Self::Brightness, Self::Overlay];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Brightness => "brightness",
+            Self::Overlay => "overlay",
+        }
+    }
+}
+
+impl FromStr for LayerBlend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LayerBlend::ALL
+            .into_iter()
+            .find(|blend| blend.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported layer blend: {}", s))
+    }
+}
+
+/// Blend of the shown layer with the other one. The text form is `none` or the blend and its
+/// weight like `brightness:0.5`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Compositing {
+    pub blend: Option<LayerBlend>,
+    /// From 0, only the shown layer, to 1, the full effect of the other layer
+    pub weight: f32,
+}
+
+impl Compositing {
+    pub const DEFAULT_WEIGHT: f32 = 0.5;
+
+    pub fn new(blend: Option<LayerBlend>, weight: f32) -> Self {
+        Self {
+            blend,
+            weight: weight.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Cycles from no blend through all blends, keeping the weight
+    pub fn next(self) -> Self {
+        let blend = match self.blend {
+            None => Some(LayerBlend::Brightness),
+            Some(LayerBlend::Brightness) => Some(LayerBlend::Overlay),
+            Some(LayerBlend::Overlay) => None,
+        };
+        Self { blend, ..self }
+    }
+
+    /// 0 without blending, see `fs_height` and `fs_amplitude` in the shader
+    pub fn shader_index(&self) -> u32 {
+        match self.blend {
+            None => 0,
+            Some(LayerBlend::Brightness) => 1,
+            Some(LayerBlend::Overlay) => 2,
+        }
+    }
+}
+
+impl Default for Compositing {
+    fn default() -> Self {
+        Self::new(None, Self::DEFAULT_WEIGHT)
+    }
+}
+
+impl fmt::Display for Compositing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.blend {
+            None => write!(f, "none"),
+            Some(blend) => write!(f, "{}:{}", blend.name(), self.weight),
+        }
+    }
+}
+
+impl FromStr for Compositing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "none" {
+            return Ok(Self::default());
+        }
+        let (blend, weight) = match s.split_once(':') {
+            Some((blend, weight)) => (
+                blend,
+                weight
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|weight| (0.0..=1.0).contains(weight))
+                    .ok_or_else(|| anyhow!("Invalid blend weight: {}", weight))?,
+            ),
+            None => (s, Self::DEFAULT_WEIGHT),
+        };
+        Ok(Self::new(Some(blend.parse()?), weight))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn text_form_round_trips() {
+        for compositing in [
+            Compositing::default(),
+            Compositing::new(Some(LayerBlend::Brightness), 0.25),
+            Compositing::new(Some(LayerBlend::Overlay), 1.0),
+        ] {
+            assert_eq!(
+                compositing.to_string().parse::<Compositing>().unwrap(),
+                compositing
+            );
+        }
+        assert_eq!(
+            "Overlay".parse::<Compositing>().unwrap(),
+            Compositing::new(Some(LayerBlend::Overlay), Compositing::DEFAULT_WEIGHT)
+        );
+        assert!("brightness:2".parse::<Compositing>().is_err());
+        assert!("multiply".parse::<Compositing>().is_err());
+    }
+}
@@ -0,0 +1,152 @@
+// Float TIFFs are large and measurement networks often slow, so datasets are commonly stored and
+// served compressed. Data compressed with gzip, zstd or LZ4 (frame format) is recognized by its
+// magic bytes and decompressed before it is decoded. On the web the browser already decodes
+// responses with a `Content-Encoding` it supports, only files served as they are compressed,
+// e.g. a `.tiff.zst` as `application/octet-stream`, are left to the viewer.
+
+use std::io::Read;
+
+use crate::error::ViewerError;
+
+/// Format of compressed data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+impl Compression {
+    /// Format of the data from its first bytes, `None` for uncompressed data
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            [0x04, 0x22, 0x4d, 0x18, ..] => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::Lz4 => "LZ4",
+        }
+    }
+
+    /// Reader of the decompressed data
+    #[cfg(feature = "compression")]
+    fn decoder<'a>(&self, reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>, ViewerError> {
+        Ok(match self {
+            Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(
+                ruzstd::decoding::StreamingDecoder::new(reader)
+                    .map_err(|e| ViewerError::Decompress(self.name(), e.to_string()))?,
+            ),
+            Self::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+        })
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decoder<'a>(&self, _reader: impl Read + 'a) -> Result<Box<dyn Read + 'a>, ViewerError> {
+        Err(ViewerError::Decompress(
+            self.name(),
+            "this build reads no compressed data".to_owned(),
+        ))
+    }
+
+    pub fn decompress(&self, reader: impl Read) -> Result<Vec<u8>, ViewerError> {
+        let mut data = Vec::new();
+        self.decoder(reader)?
+            .read_to_end(&mut data)
+            .map_err(|e| ViewerError::Decompress(self.name(), e.to_string()))?;
+        Ok(data)
+    }
+}
+
+/// Decompresses `bytes` if they are compressed, returns other data as it is
+pub fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>, ViewerError> {
+    match Compression::detect(&bytes) {
+        Some(compression) => {
+            log::info!(
+                "Decompressing {} bytes of {} data",
+                bytes.len(),
+                compression.name()
+            );
+            compression.decompress(&bytes[..])
+        }
+        None => Ok(bytes),
+    }
+}
+
+/// Reader of a file, decompressed into memory if the file is compressed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(path: &str) -> Result<Box<dyn ReadSeek>, ViewerError> {
+    use std::io::Seek;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut magic = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+    Ok(match Compression::detect(&magic) {
+        Some(compression) => {
+            log::info!("Decompressing {} file {}", compression.name(), path);
+            let data = compression.decompress(std::io::BufReader::new(file))?;
+            Box::new(std::io::Cursor::new(data))
+        }
+        None => Box::new(file),
+    })
+}
+
+/// Input of the TIFF decoder
+#[cfg(not(target_arch = "wasm32"))]
+pub trait ReadSeek: Read + std::io::Seek {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Read + std::io::Seek> ReadSeek for T {}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn compressed_data_is_detected_and_decompressed() {
+        let data: Vec<u8> = b"II*\0".iter().copied().cycle().take(4000).collect();
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(&data).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = ruzstd::encoding::compress_to_vec(
+            &data[..],
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        lz4.write_all(&data).unwrap();
+        let lz4 = lz4.finish().unwrap();
+
+        for (compressed, compression) in [
+            (gzip, Compression::Gzip),
+            (zstd, Compression::Zstd),
+            (lz4, Compression::Lz4),
+        ] {
+            assert!(compressed.len() < data.len());
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+            assert_eq!(decompress(compressed).unwrap(), data);
+        }
+        // TIFF files are passed through
+        assert_eq!(Compression::detect(&data), None);
+        assert_eq!(decompress(data.clone()).unwrap(), data);
+    }
+
+    #[test]
+    fn corrupted_compressed_data_is_rejected() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(&[7; 1000]).unwrap();
+        let mut gzip = gzip.finish().unwrap();
+        gzip.truncate(gzip.len() / 2);
+        assert!(decompress(gzip).is_err());
+        assert!(decompress(vec![0x28, 0xb5, 0x2f, 0xfd, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+// Multi-GPU workstations can accumulate the temporal statistics of huge sequences on a second
+// adapter, so that the one drawing the windows stays free for interaction. Devices of different
+// adapters share no memory: every frame is uploaded to the compute device from its CPU copy and
+// the maps are read back to the CPU from there. The baseline is drawn, so it stays on the
+// rendering device.
+
+use std::sync::Arc;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::ViewerError;
+
+/// Device of the adapter the compute passes run on
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+#[derive(Clone)]
+pub struct ComputeDevice {
+    pub device: Arc<wgpu::Device>,
+    pub queue: wgpu::Queue,
+    pub info: wgpu::AdapterInfo,
+}
+
+impl ComputeDevice {
+    /// Opens the adapter at `selector` as index in the list of adapters or the first one whose
+    /// name contains it, ignoring case
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn open(selector: &str) -> Result<Self, ViewerError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+        for (index, adapter) in adapters.iter().enumerate() {
+            let info = adapter.get_info();
+            log::debug!("Adapter {}: {} ({:?})", index, info.name, info.backend);
+        }
+        let adapter = match selector.parse::<usize>() {
+            Ok(index) => adapters.into_iter().nth(index),
+            Err(_) => {
+                let selector = selector.to_lowercase();
+                adapters
+                    .into_iter()
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&selector))
+            }
+        }
+        .ok_or_else(|| ViewerError::NoComputeAdapter(selector.to_owned()))?;
+        let info = adapter.get_info();
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("compute_device"),
+                ..Default::default()
+            })
+            .await?;
+        Ok(Self {
+            device: Arc::new(device),
+            queue,
+            info,
+        })
+    }
+}
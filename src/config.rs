@@ -0,0 +1,340 @@
+// Lab operators standardize the viewer across machines with a `viewer.toml` instead of
+// command line options or rebuilding it. It sets the defaults of every window, options on the
+// command line still override them:
+//
+//     shader = "amplitude"
+//     percentiles = [1, 99]
+//     background = "#202020"
+//
+//     [navigation]
+//     zoom = 0.5
+//     invert-y = true
+//
+//     [keys]
+//     toggle-shader = "v"
+//
+// Only this subset of TOML is read: tables, and strings, numbers, booleans and arrays of
+// numbers as values.
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use glam::Vec3;
+
+use crate::{keyboard::KeyBindings, navigation::NavigationSettings, texture::AmplitudeRange};
+
+/// Name of the config file in the working directory or the config directory of the user
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+const FILE_NAME: &str = "viewer.toml";
+
+/// Pipeline the surface is first drawn with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shader {
+    /// Colored by height
+    Height,
+    /// Colored by the amplitude image
+    Amplitude,
+}
+
+impl FromStr for Shader {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "height" => Ok(Self::Height),
+            "amplitude" => Ok(Self::Amplitude),
+            _ => Err(anyhow!("Unsupported shader: {}", s)),
+        }
+    }
+}
+
+/// Defaults of the windows, `None` keeps the built-in one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ViewerConfig {
+    pub shader: Option<Shader>,
+    pub amplitude_range: Option<AmplitudeRange>,
+    /// Linear sRGB color behind the surface
+    pub background: Option<Vec3>,
+    /// Mouse sensitivity, including the zoom of a scroll step, and inverted axes
+    pub navigation: Option<NavigationSettings>,
+    pub key_bindings: KeyBindings,
+}
+
+/// Value of a key
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Number(f32),
+    Bool(bool),
+    Array(Vec<f32>),
+}
+
+impl Value {
+    fn parse(s: &str) -> Option<Self> {
+        if let Some(string) = s.strip_prefix('"') {
+            return string.strip_suffix('"').map(|s| Self::String(s.to_owned()));
+        }
+        if let Some(array) = s.strip_prefix('[') {
+            return array
+                .strip_suffix(']')?
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse().ok())
+                .collect::<Option<_>>()
+                .map(Self::Array);
+        }
+        match s {
+            "true" => Some(Self::Bool(true)),
+            "false" => Some(Self::Bool(false)),
+            _ => s.parse().ok().map(Self::Number),
+        }
+    }
+}
+
+impl ViewerConfig {
+    /// Reads the file at `path`, or without one the `viewer.toml` in the working directory or
+    /// else in the config directory of the user if there is one
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: Option<&str>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => std::path::PathBuf::from(path),
+            None => {
+                let user = Self::user_dir().map(|dir| dir.join(FILE_NAME));
+                match [Some(FILE_NAME.into()), user]
+                    .into_iter()
+                    .flatten()
+                    .find(|path| path.is_file())
+                {
+                    Some(path) => path,
+                    None => return Ok(Self::default()),
+                }
+            }
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read config {}: {}", path.display(), e))?;
+        log::info!("Using config {}", path.display());
+        text.parse()
+            .map_err(|e| anyhow!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// `data-viewer-3d` in the config directory of the platform
+    #[cfg(not(target_arch = "wasm32"))]
+    fn user_dir() -> Option<std::path::PathBuf> {
+        use std::path::PathBuf;
+
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("data-viewer-3d"))
+    }
+
+    fn set(&mut self, table: &str, key: &str, value: Value) -> anyhow::Result<()> {
+        let invalid = || anyhow!("Invalid value of {}", key);
+        match (table, key, value) {
+            ("", "shader", Value::String(shader)) => self.shader = Some(shader.parse()?),
+            ("", "percentiles", Value::Array(limits)) => {
+                let [lower, upper] = limits[..] else {
+                    return Err(invalid());
+                };
+                self.amplitude_range = Some(AmplitudeRange::percentile(lower, upper)?);
+            }
+            ("", "background", Value::String(hex)) => {
+                let rgb = hex
+                    .strip_prefix('#')
+                    .filter(|hex| hex.len() == 6)
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .ok_or_else(invalid)?;
+                let channel = |shift: u32| ((rgb >> shift) & 0xff) as f32 / 255.0;
+                self.background = Some(srgb_to_linear(Vec3::new(
+                    channel(16),
+                    channel(8),
+                    channel(0),
+                )));
+            }
+            ("", "background", Value::Array(rgb)) => {
+                let [r, g, b] = rgb[..] else {
+                    return Err(invalid());
+                };
+                let rgb = Vec3::new(r, g, b);
+                if rgb.min_element() < 0.0 || rgb.max_element() > 1.0 {
+                    return Err(invalid());
+                }
+                self.background = Some(srgb_to_linear(rgb));
+            }
+            ("navigation", name, value) => {
+                // Same names as for `--navigation`
+                let settings = self.navigation.get_or_insert_with(Default::default);
+                match (name, value) {
+                    ("invert-x", Value::Bool(invert)) => settings.invert_x = invert,
+                    ("invert-y", Value::Bool(invert)) => settings.invert_y = invert,
+                    ("invert-zoom", Value::Bool(invert)) => settings.invert_zoom = invert,
+                    (name, Value::Number(number)) => {
+                        *settings = format!("{},{}={}", settings, name, number).parse()?
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            ("keys", action, Value::String(key)) => self.key_bindings.bind(action, &key)?,
+            (table, key, _) if matches!(table, "" | "navigation" | "keys") => {
+                if matches!((table, key), ("", "shader" | "percentiles" | "background")) {
+                    return Err(invalid());
+                }
+                log::warn!("Unknown config key {}", key);
+            }
+            (table, _, _) => log::warn!("Unknown config table {}", table),
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ViewerConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Self::default();
+        let mut table = String::new();
+        for (index, line) in s.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            let error = |e: anyhow::Error| anyhow!("line {}: {}", index + 1, e);
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                table = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| error(anyhow!("Unclosed table name")))?
+                    .trim()
+                    .to_owned();
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error(anyhow!("Expected key = value")))?;
+            let value = Value::parse(value.trim())
+                .ok_or_else(|| error(anyhow!("Unsupported value {}", value.trim())))?;
+            config
+                .set(&table, key.trim().trim_matches('"'), value)
+                .map_err(error)?;
+        }
+        Ok(config)
+    }
+}
+
+/// The line without a `#` comment, which may not start within a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn srgb_to_linear(rgb: Vec3) -> Vec3 {
+    let channel = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Vec3::new(channel(rgb.x), channel(rgb.y), channel(rgb.z))
+}
+
+#[cfg(test)]
+mod test {
+    use winit::keyboard::Key;
+
+    use super::*;
+
+    #[test]
+    fn parses_defaults_navigation_and_keys() {
+        let config: ViewerConfig = r##"
+            # Lab defaults
+            shader = "amplitude"
+            percentiles = [2, 98.5]
+            background = "#ffffff" # white
+
+            [navigation]
+            zoom = 0.5
+            invert-y = true
+            invert-x = true
+            invert-x = false
+
+            [keys]
+            toggle-shader = "V"
+            markers = "#"
+        "##
+        .parse()
+        .unwrap();
+        assert_eq!(config.shader, Some(Shader::Amplitude));
+        assert_eq!(
+            config.amplitude_range,
+            Some(AmplitudeRange::Percentile {
+                lower: 2.0,
+                upper: 98.5
+            })
+        );
+        assert!(config.background.unwrap().abs_diff_eq(Vec3::ONE, 1e-6));
+        let navigation = config.navigation.unwrap();
+        assert_eq!(navigation.zoom_factor(), 0.5);
+        assert!(navigation.invert_y && !navigation.invert_x);
+        assert_eq!(
+            navigation.rotate_speed,
+            NavigationSettings::default().rotate_speed
+        );
+
+        let bindings = &config.key_bindings;
+        assert_eq!(
+            bindings.resolve(Key::Character("v".into())),
+            Key::Character("s".into())
+        );
+        assert_eq!(
+            bindings.resolve(Key::Character("V".into())),
+            Key::Character("S".into())
+        );
+        assert_eq!(
+            bindings.resolve(Key::Character("#".into())),
+            Key::Character("k".into())
+        );
+        // Unbound keys are left alone
+        assert_eq!(
+            bindings.resolve(Key::Character("s".into())),
+            Key::Character("s".into())
+        );
+
+        assert_eq!("".parse::<ViewerConfig>().unwrap(), ViewerConfig::default());
+        let gray: ViewerConfig = "background = [0.5, 0.5, 0.5]".parse().unwrap();
+        assert!((gray.background.unwrap().x - 0.214).abs() < 1e-3);
+    }
+
+    #[test]
+    fn invalid_configs_name_the_line() {
+        for (config, line) in [
+            ("shader = \"wireframe\"", 1),
+            ("\npercentiles = [1]", 2),
+            ("percentiles = [99, 1]", 1),
+            ("background = \"red\"", 1),
+            ("[navigation]\nzoom = -1", 2),
+            ("[keys]\nunknown-action = \"v\"", 2),
+            ("[keys]\ncolormap = \"vw\"", 2),
+            ("shader", 1),
+            ("[keys", 1),
+            ("[navigation]\ninvert-y = 1", 2),
+        ] {
+            let error = config.parse::<ViewerConfig>().unwrap_err().to_string();
+            assert!(error.starts_with(&format!("line {}:", line)), "{}", error);
+        }
+        // Keys of newer versions don't stop older ones
+        assert!(
+            "grid = true\n[plugins]\nx = 1"
+                .parse::<ViewerConfig>()
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use glam::{Mat4, Vec2, Vec3};
+use std::sync::Arc;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    image::{Image, ZValueRange},
+    projection::Projection,
+    ray_picker,
+};
+
+/// Snapshot of all transforms between the coordinate systems of the viewer:
+///
+/// - dataset pixels: column/row of the image plus the sample value
+/// - world: position of the mesh in the scene, x/y in `[-1, 1]` and z in `[0, 1]`
+///   with z = 0 at the highest value
+/// - screen: physical pixels of the viewport, origin at the top left corner
+///
+/// Host applications use it to keep their own 2D views in sync with the 3D view.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone)]
+pub struct ViewTransform {
+    image: Arc<Image<f32>>,
+    z_range: ZValueRange<f32>,
+    view_projection: Mat4,
+    viewport: Vec2,
+}
+
+// Natively the mappings are only used by embedding applications
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+impl ViewTransform {
+    /// `view` is the camera rotation and `projection` the pan/zoom matrix as used by the shaders
+    pub(crate) fn new(
+        image: Arc<Image<f32>>,
+        z_range: ZValueRange<f32>,
+        view: Mat4,
+        projection: Mat4,
+        viewport: Vec2,
+    ) -> Self {
+        Self {
+            image,
+            z_range,
+            view_projection: projection * view,
+            viewport: viewport.max(Vec2::ONE),
+        }
+    }
+
+    pub fn pixel_to_world(&self, pixel: Vec2, value: f32) -> Vec3 {
+        let (width, height) = self.grid_extent();
+        Vec3::new(
+            2.0 * pixel.x / width - 1.0,
+            1.0 - 2.0 * pixel.y / height,
+            world_z(value, &self.z_range),
+        )
+    }
+
+    /// Returns the (sub-)pixel position and the value belonging to the height of `world`
+    pub fn world_to_pixel(&self, world: Vec3) -> (Vec2, f32) {
+        let (width, height) = self.grid_extent();
+        let pixel = Vec2::new(
+            (world.x + 1.0) / 2.0 * width,
+            (1.0 - world.y) / 2.0 * height,
+        );
+        let value = self.z_range.max() - world.z * (self.z_range.max() - self.z_range.min());
+        (pixel, value)
+    }
+
+    pub fn world_to_screen(&self, world: Vec3) -> Vec2 {
+        let ndc = self.view_projection.project_point3(world);
+        Vec2::new(
+            (ndc.x + 1.0) / 2.0 * self.viewport.x,
+            (1.0 - ndc.y) / 2.0 * self.viewport.y,
+        )
+    }
+
+    /// Returns the origin and direction of the view ray through a screen position.
+    /// The ray starts at the near plane and reaches the far plane at a parameter of one.
+    pub fn screen_to_world_ray(&self, screen: Vec2) -> (Vec3, Vec3) {
+        let ndc = self.screen_to_ndc(screen);
+        let inverse = self.view_projection.inverse();
+        let near = inverse.project_point3(Vec3::new(ndc.x, ndc.y, Projection::NEAR_DEPTH));
+        let far = inverse.project_point3(Vec3::new(ndc.x, ndc.y, Projection::FAR_DEPTH));
+        (near, far - near)
+    }
+
+    /// Screen position of a pixel as drawn on the surface, missing pixels are placed at the lowest
+    /// level like in the shader
+    pub fn pixel_to_screen(&self, pixel: Vec2) -> Vec2 {
+        let (col, row) = self.nearest_pixel(pixel);
+        let value = self.image.get_pixel(col, row);
+        let value = if value.is_finite() {
+            value
+        } else {
+            self.z_range.min()
+        };
+        self.world_to_screen(self.pixel_to_world(pixel, value))
+    }
+
+    /// Pixel of the surface visible at a screen position with its value
+    pub fn screen_to_pixel(&self, screen: Vec2) -> Option<(u32, u32, f32)> {
+        ray_picker::ray_cast(
+            &self.image,
+            &self.z_range,
+            self.view_projection,
+            self.screen_to_ndc(screen),
+        )
+    }
+
+    /// Heights between the `lower` and `upper` percentile of the valid pixels inside the
+    /// viewport, `None` if none is. Pixels hidden behind others count as well, at most about
+    /// `max_samples` evenly spread pixels are looked at.
+    pub fn visible_range(&self, lower: f32, upper: f32, max_samples: usize) -> Option<[f32; 2]> {
+        let (width, height) = (self.image.size.width.get(), self.image.size.height.get());
+        let stride = ((self.image.size.pixel_count() as f64 / max_samples.max(1) as f64)
+            .sqrt()
+            .ceil() as u32)
+            .max(1);
+        let mut values: Vec<f32> = (0..height)
+            .step_by(stride as usize)
+            .flat_map(|y| (0..width).step_by(stride as usize).map(move |x| (x, y)))
+            .map(|(x, y)| (x, y, self.image.get_pixel(x, y)))
+            .filter(|&(x, y, value)| {
+                let pixel = Vec2::new(x as f32, y as f32);
+                let screen = self.world_to_screen(self.pixel_to_world(pixel, value));
+                value.is_finite()
+                    && (0.0..=self.viewport.x).contains(&screen.x)
+                    && (0.0..=self.viewport.y).contains(&screen.y)
+            })
+            .map(|(_, _, value)| value)
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        let last = values.len() - 1;
+        let mut percentile = |percentile: f32| {
+            let index = ((percentile / 100.0 * last as f32).round() as usize).min(last);
+            *values.select_nth_unstable_by(index, f32::total_cmp).1
+        };
+        let max = percentile(upper);
+        let min = percentile(lower);
+        Some([min, max])
+    }
+
+    fn screen_to_ndc(&self, screen: Vec2) -> Vec2 {
+        Vec2::new(
+            2.0 * screen.x / self.viewport.x - 1.0,
+            1.0 - 2.0 * screen.y / self.viewport.y,
+        )
+    }
+
+    fn nearest_pixel(&self, pixel: Vec2) -> (u32, u32) {
+        let max_col = self.image.size.width.get() - 1;
+        let max_row = self.image.size.height.get() - 1;
+        (
+            (pixel.x.round().max(0.0) as u32).min(max_col),
+            (pixel.y.round().max(0.0) as u32).min(max_row),
+        )
+    }
+
+    /// Distance between the first and the last pixel center, at least one pixel
+    fn grid_extent(&self) -> (f32, f32) {
+        (
+            self.image.size.width.get().saturating_sub(1).max(1) as f32,
+            self.image.size.height.get().saturating_sub(1).max(1) as f32,
+        )
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl ViewTransform {
+    /// Returns `[x, y]` in screen pixels
+    #[wasm_bindgen(js_name = pixelToScreen)]
+    pub fn js_pixel_to_screen(&self, x: f32, y: f32) -> Vec<f32> {
+        self.pixel_to_screen(Vec2::new(x, y)).to_array().to_vec()
+    }
+
+    /// Returns `[x, y, value]` of the visible pixel or `undefined` if there is no surface
+    #[wasm_bindgen(js_name = screenToPixel)]
+    pub fn js_screen_to_pixel(&self, x: f32, y: f32) -> Option<Vec<f32>> {
+        self.screen_to_pixel(Vec2::new(x, y))
+            .map(|(x, y, value)| vec![x as f32, y as f32, value])
+    }
+
+    /// Returns `[x, y, z]` in world coordinates
+    #[wasm_bindgen(js_name = pixelToWorld)]
+    pub fn js_pixel_to_world(&self, x: f32, y: f32, value: f32) -> Vec<f32> {
+        self.pixel_to_world(Vec2::new(x, y), value)
+            .to_array()
+            .to_vec()
+    }
+
+    /// Returns `[x, y, value]` in dataset pixels
+    #[wasm_bindgen(js_name = worldToPixel)]
+    pub fn js_world_to_pixel(&self, x: f32, y: f32, z: f32) -> Vec<f32> {
+        let (pixel, value) = self.world_to_pixel(Vec3::new(x, y, z));
+        vec![pixel.x, pixel.y, value]
+    }
+
+    /// Returns `[x, y]` in screen pixels
+    #[wasm_bindgen(js_name = worldToScreen)]
+    pub fn js_world_to_screen(&self, x: f32, y: f32, z: f32) -> Vec<f32> {
+        self.world_to_screen(Vec3::new(x, y, z)).to_array().to_vec()
+    }
+}
+
+/// Nearest pixel to a world position, clamped to the image
+pub(crate) fn nearest_pixel(world: Vec3, width: u32, height: u32) -> (u32, u32) {
+    let col = (world.x + 1.0) / 2.0 * width.saturating_sub(1) as f32;
+    let row = (1.0 - world.y) / 2.0 * height.saturating_sub(1) as f32;
+    (
+        (col.round().max(0.0) as u32).min(width - 1),
+        (row.round().max(0.0) as u32).min(height - 1),
+    )
+}
+
+/// World z of a value, matching the vertex shader
+pub(crate) fn world_z(value: f32, z_range: &ZValueRange<f32>) -> f32 {
+    let extent = z_range.max() - z_range.min();
+    if extent > 0.0 {
+        1.0 - (value.clamp(z_range.min(), z_range.max()) - z_range.min()) / extent
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use super::*;
+    use crate::image::{ImageSize, value_range};
+
+    fn view_transform() -> ViewTransform {
+        let image = Image {
+            size: ImageSize {
+                width: NonZeroU32::new(5).unwrap(),
+                height: NonZeroU32::new(3).unwrap(),
+            },
+            data: (0..15).map(|v| v as f32).collect(),
+        };
+        let z_range = value_range(&image.data).unwrap();
+        ViewTransform::new(
+            Arc::new(image),
+            z_range,
+            Mat4::from_rotation_x(0.4) * Mat4::from_rotation_z(0.2),
+            Mat4::from_scale(Vec3::new(0.5, 0.5, 0.25)),
+            Vec2::new(800.0, 600.0),
+        )
+    }
+
+    #[test]
+    fn pixel_and_world_round_trip() {
+        let transform = view_transform();
+        let world = transform.pixel_to_world(Vec2::new(1.5, 2.0), 7.0);
+        let (pixel, value) = transform.world_to_pixel(world);
+        assert!((pixel - Vec2::new(1.5, 2.0)).length() < 1e-5);
+        assert!((value - 7.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn screen_position_of_pixel_picks_that_pixel() {
+        let transform = view_transform();
+        let screen = transform.pixel_to_screen(Vec2::new(3.0, 1.0));
+        assert_eq!(transform.screen_to_pixel(screen), Some((3, 1, 8.0)));
+    }
+
+    #[test]
+    fn visible_range_skips_pixels_outside_the_viewport() {
+        let transform = view_transform();
+        assert_eq!(transform.visible_range(0.0, 100.0, 1000), Some([0.0, 14.0]));
+        // Zoomed in on the center, only the middle row and columns are visible
+        let zoomed = ViewTransform::new(
+            transform.image.clone(),
+            transform.z_range.clone(),
+            Mat4::IDENTITY,
+            Mat4::from_scale(Vec3::new(1.5, 1.5, 1.0)),
+            Vec2::new(800.0, 600.0),
+        );
+        assert_eq!(zoomed.visible_range(0.0, 100.0, 1000), Some([6.0, 8.0]));
+    }
+}
@@ -0,0 +1,193 @@
+// Crash reports for bug reports from the field. The viewer keeps a small summary of its state up
+// to date while it runs, so that a panic can tell which GPU, dataset, camera and commands led to
+// it. Natively the report is written to a file, in the browser the page hosting the viewer gets
+// a `viewer-crash` event on `window` to forward it.
+
+use std::{collections::VecDeque, panic::PanicHookInfo, sync::Mutex};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Commands kept for the report, older ones are dropped
+const MAX_COMMANDS: usize = 32;
+
+struct Context {
+    adapter: Option<String>,
+    dataset: Option<String>,
+    camera: Option<String>,
+    commands: VecDeque<String>,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context {
+    adapter: None,
+    dataset: None,
+    camera: None,
+    commands: VecDeque::new(),
+});
+
+fn update(f: impl FnOnce(&mut Context)) {
+    f(&mut CONTEXT.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+pub fn set_adapter(info: &wgpu::AdapterInfo) {
+    let adapter = format!(
+        "{} ({:?}, {:?}, driver {} {})",
+        info.name, info.device_type, info.backend, info.driver, info.driver_info
+    );
+    update(|context| context.adapter = Some(adapter));
+}
+
+pub fn set_dataset(dataset: String) {
+    update(|context| context.dataset = Some(dataset));
+}
+
+pub fn set_camera(camera: String) {
+    update(|context| context.camera = Some(camera));
+}
+
+pub fn record_command(name: &str) {
+    update(|context| {
+        if context.commands.len() == MAX_COMMANDS {
+            context.commands.pop_front();
+        }
+        context.commands.push_back(name.to_owned());
+    });
+}
+
+/// What the viewer did when it panicked
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrashReport {
+    pub message: String,
+    /// `file:line:column` of the panic
+    pub location: Option<String>,
+    pub version: String,
+    pub adapter: Option<String>,
+    pub dataset: Option<String>,
+    pub camera: Option<String>,
+    /// Last commands, the oldest first
+    pub commands: Vec<String>,
+}
+
+impl CrashReport {
+    fn new(info: &PanicHookInfo) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_owned());
+        let mut report = Self {
+            message,
+            location: info.location().map(|location| location.to_string()),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            ..Default::default()
+        };
+        // The panic may have happened while the context was updated
+        if let Some(context) = match CONTEXT.try_lock() {
+            Ok(context) => Some(context),
+            Err(std::sync::TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(std::sync::TryLockError::WouldBlock) => None,
+        } {
+            report.adapter = context.adapter.clone();
+            report.dataset = context.dataset.clone();
+            report.camera = context.camera.clone();
+            report.commands = context.commands.iter().cloned().collect();
+        }
+        report
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn to_text(&self) -> String {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_owned());
+        let mut text = format!(
+            "data-viewer-3d {} panicked at {}:\n{}\n\nGPU: {}\nDataset: {}\nCamera: {}\n\nLast commands:\n",
+            self.version,
+            unknown(&self.location),
+            self.message,
+            unknown(&self.adapter),
+            unknown(&self.dataset),
+            unknown(&self.camera),
+        );
+        for command in &self.commands {
+            text.push_str(&format!("  {}\n", command));
+        }
+        text
+    }
+}
+
+/// Writes a crash report to the temporary directory on a panic, before the default hook prints
+/// the panic
+#[cfg(not(target_arch = "wasm32"))]
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut text = CrashReport::new(info).to_text();
+        text.push_str(&format!(
+            "\nBacktrace:\n{}\n",
+            std::backtrace::Backtrace::force_capture()
+        ));
+        let seconds = web_time::SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let path = std::env::temp_dir().join(format!("data-viewer-3d-crash-{}.txt", seconds));
+        match std::fs::write(&path, text) {
+            Ok(()) => eprintln!(
+                "The viewer crashed, please attach {} to the bug report",
+                path.display()
+            ),
+            Err(e) => eprintln!("Failed to write crash report {}: {}", path.display(), e),
+        }
+        default_hook(info);
+    }));
+}
+
+/// Logs the panic to the console and dispatches a `viewer-crash` event on `window` with the
+/// `CrashReport` as detail
+#[cfg(target_arch = "wasm32")]
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        console_error_panic_hook::hook(info);
+        let report = CrashReport::new(info);
+        let init = web_sys::CustomEventInit::new();
+        init.set_detail(&JsValue::from(report));
+        if let (Some(window), Ok(event)) = (
+            web_sys::window(),
+            web_sys::CustomEvent::new_with_event_init_dict("viewer-crash", &init),
+        ) {
+            let _ = window.dispatch_event(&event);
+        }
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_keeps_the_last_commands() {
+        for i in 0..MAX_COMMANDS + 3 {
+            record_command(&format!("Command{}", i));
+        }
+        set_dataset("part.tiff (4 × 2 pixels)".to_owned());
+        let context = CONTEXT.lock().unwrap();
+        let report = CrashReport {
+            message: "boom".to_owned(),
+            commands: context.commands.iter().cloned().collect(),
+            dataset: context.dataset.clone(),
+            ..Default::default()
+        };
+        drop(context);
+        assert_eq!(report.commands.len(), MAX_COMMANDS);
+        assert_eq!(report.commands[0], "Command3");
+        let text = report.to_text();
+        assert!(text.contains("boom\n"), "{}", text);
+        assert!(
+            text.contains("Dataset: part.tiff (4 × 2 pixels)"),
+            "{}",
+            text
+        );
+        assert!(text.contains("GPU: -"), "{}", text);
+        assert!(text.ends_with(&format!("  Command{}\n", MAX_COMMANDS + 2)));
+    }
+}
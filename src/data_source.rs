@@ -0,0 +1,216 @@
+// Datasets are read through a `DataSource`, so that files, downloads and whatever storage an
+// integration adds, e.g. S3/MinIO buckets or database blobs, are decoded the same way. A source
+// only delivers bytes, decompression and decoding stay in `SurfaceAmplitudeImage::from_source`.
+// Sources that can't read parts of a dataset read it whole and `cut` the range out of it.
+
+use std::ops::Range;
+
+use crate::{
+    download::{self, DownloadOptions, DownloadProgress},
+    error::ViewerError,
+};
+
+/// Bytes read by a source, only sent to other threads natively
+#[cfg(not(target_arch = "wasm32"))]
+pub type ReadFuture<'a> = futures::future::BoxFuture<'a, Result<Vec<u8>, ViewerError>>;
+#[cfg(target_arch = "wasm32")]
+pub type ReadFuture<'a> = futures::future::LocalBoxFuture<'a, Result<Vec<u8>, ViewerError>>;
+
+/// Where the bytes of a dataset come from
+pub trait DataSource {
+    /// Shown in the log and the title, e.g. the path or the URL
+    fn name(&self) -> &str;
+
+    /// All bytes of the dataset
+    fn read(&self) -> ReadFuture<'_>;
+
+    /// The bytes in `range`, fewer if the dataset ends before
+    fn read_range(&self, range: Range<u64>) -> ReadFuture<'_>;
+}
+
+/// The bytes of `data` in `range`, for sources without ranged reads. Like files, nothing is
+/// read of an inverted range.
+pub fn cut(mut data: Vec<u8>, range: Range<u64>) -> Vec<u8> {
+    let len = data.len() as u64;
+    let end = range.end.min(len);
+    let start = range.start.min(end);
+    data.truncate(end as usize);
+    data.drain(..start as usize);
+    data
+}
+
+/// Whether a location is downloaded instead of read from disk
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Source of a location given to the viewer, URLs are downloaded and everything else is a file
+#[cfg(not(target_arch = "wasm32"))]
+pub fn open(location: &str) -> Box<dyn DataSource + Send + Sync> {
+    if is_url(location) {
+        Box::new(HttpSource::new(location, DownloadOptions::default()))
+    } else {
+        Box::new(FileSource::new(location))
+    }
+}
+
+/// Runs a read on the calling thread, with the runtime the HTTP client needs
+#[cfg(not(target_arch = "wasm32"))]
+pub fn block_on<T>(future: impl Future<Output = Result<T, ViewerError>>) -> Result<T, ViewerError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(future)
+}
+
+/// File on a local or mounted disk
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct FileSource {
+    path: String,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileSource {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DataSource for FileSource {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    fn read(&self) -> ReadFuture<'_> {
+        Box::pin(async move { Ok(std::fs::read(&self.path)?) })
+    }
+
+    fn read_range(&self, range: Range<u64>) -> ReadFuture<'_> {
+        use std::io::{Read, Seek};
+
+        Box::pin(async move {
+            let mut file = std::fs::File::open(&self.path)?;
+            file.seek(std::io::SeekFrom::Start(range.start))?;
+            let mut data = Vec::new();
+            file.take(range.end.saturating_sub(range.start))
+                .read_to_end(&mut data)?;
+            Ok(data)
+        })
+    }
+}
+
+/// File on a web server, requested and retried as the options say, see `download`
+#[derive(Clone)]
+pub struct HttpSource {
+    url: String,
+    options: DownloadOptions,
+}
+
+impl HttpSource {
+    pub fn new(url: impl Into<String>, options: DownloadOptions) -> Self {
+        Self {
+            url: url.into(),
+            options,
+        }
+    }
+
+    /// All bytes of the file, `progress` is called for every chunk
+    pub async fn read_with_progress(
+        &self,
+        progress: impl FnMut(DownloadProgress),
+    ) -> Result<Vec<u8>, ViewerError> {
+        download::download(&self.url, &self.options, progress).await
+    }
+}
+
+impl DataSource for HttpSource {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    fn read(&self) -> ReadFuture<'_> {
+        Box::pin(self.read_with_progress(|_| ()))
+    }
+
+    fn read_range(&self, range: Range<u64>) -> ReadFuture<'_> {
+        Box::pin(download::download_range(&self.url, &self.options, range))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::SurfaceAmplitudeImage;
+
+    /// Source of an integration, e.g. a blob of a database row
+    struct BlobSource(Vec<u8>);
+
+    impl DataSource for BlobSource {
+        fn name(&self) -> &str {
+            "blob"
+        }
+
+        fn read(&self) -> ReadFuture<'_> {
+            Box::pin(async move { Ok(self.0.clone()) })
+        }
+
+        fn read_range(&self, range: Range<u64>) -> ReadFuture<'_> {
+            Box::pin(async move { Ok(cut(self.0.clone(), range)) })
+        }
+    }
+
+    const EXAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/example-img.tiff");
+
+    #[test]
+    fn files_are_read_whole_and_in_ranges() {
+        let file = open(EXAMPLE);
+        assert_eq!(file.name(), EXAMPLE);
+        let data = block_on(file.read()).unwrap();
+        assert_eq!(data, std::fs::read(EXAMPLE).unwrap());
+        assert_eq!(block_on(file.read_range(2..8)).unwrap(), data[2..8]);
+        let len = data.len() as u64;
+        assert_eq!(
+            block_on(file.read_range(len - 4..len + 10)).unwrap(),
+            data[data.len() - 4..]
+        );
+        assert!(
+            block_on(file.read_range(len + 1..len + 2))
+                .unwrap()
+                .is_empty()
+        );
+        let inverted = Range { start: 5, end: 2 };
+        assert!(
+            block_on(file.read_range(inverted.clone()))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(block_on(open("missing.tiff").read()).is_err());
+
+        assert_eq!(cut(vec![1, 2, 3, 4], 1..3), [2, 3]);
+        assert_eq!(cut(vec![1, 2, 3, 4], 3..9), [4]);
+        assert!(cut(vec![1, 2], 5..9).is_empty());
+        assert!(cut(vec![1, 2, 3, 4], inverted).is_empty());
+        assert!(is_url("https://example.com/scan.tiff"));
+        assert!(!is_url("scans/https.tiff"));
+    }
+
+    #[test]
+    fn other_sources_are_decoded_like_files() {
+        let blob = BlobSource(std::fs::read(EXAMPLE).unwrap());
+        let image = block_on(SurfaceAmplitudeImage::from_source(&blob)).unwrap();
+        let file = SurfaceAmplitudeImage::from_file(EXAMPLE).unwrap();
+        assert_eq!(image.surface.data[..], file.surface.data[..]);
+        assert_eq!(image.amplitude.size, file.amplitude.size);
+        assert_eq!(image.info.sha256.as_deref().map(str::len), Some(64));
+        assert_eq!(block_on(blob.read_range(0..2)).unwrap(), b"MM");
+
+        let page = BlobSource(b"<!DOCTYPE html><title>Sign in</title>".to_vec());
+        assert!(matches!(
+            block_on(SurfaceAmplitudeImage::from_source(&page)),
+            Err(ViewerError::UnsupportedFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+#[cfg(feature = "tiff")]
+use tiff::{
+    decoder::{Decoder, ifd::Value},
+    tags::Tag,
+};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::raw_image;
+
+/// Metadata of a loaded dataset, so that operators can verify which measurement they look at
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DatasetInfo {
+    /// File or dataset name
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    /// Manufacturer and model of the instrument
+    pub instrument: Option<String>,
+    pub software: Option<String>,
+    /// Time of the acquisition as written by the instrument, usually "YYYY:MM:DD HH:MM:SS"
+    pub acquired: Option<String>,
+    /// Pixels per resolution unit in x and y with the unit, e.g. "cm"
+    pub resolution: Option<(f64, f64, &'static str)>,
+    /// Image description without the lines read into `parameters`
+    pub description: Option<String>,
+    /// `key=value` or `key: value` lines of the image description, e.g. acquisition
+    /// parameters of vendor headers
+    pub parameters: Vec<(String, String)>,
+    /// SHA-256 of the file, see `provenance::Sha256`
+    pub sha256: Option<String>,
+}
+
+/// One row of the info panel
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatasetInfoEntry {
+    pub label: String,
+    pub value: String,
+}
+
+impl DatasetInfo {
+    /// Reads the tags of the current image of `decoder`, before its pixels are decoded
+    #[cfg(feature = "tiff")]
+    pub fn read<R: std::io::Read + std::io::Seek>(decoder: &mut Decoder<R>) -> Self {
+        let mut ascii = |tag| {
+            decoder
+                .get_tag_ascii_string(tag)
+                .ok()
+                .map(|value| value.trim_end_matches('\0').trim().to_owned())
+                .filter(|value| !value.is_empty())
+        };
+        let instrument = match (ascii(Tag::Make), ascii(Tag::Model)) {
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.or(model),
+        };
+        let software = ascii(Tag::Software);
+        let acquired = ascii(Tag::DateTime);
+        let description = ascii(Tag::ImageDescription);
+        let (width, height) = decoder.dimensions().unwrap_or_default();
+        let mut info = Self {
+            width,
+            height,
+            instrument,
+            software,
+            acquired,
+            resolution: read_resolution(decoder),
+            ..Self::default()
+        };
+        if let Some(description) = description {
+            info.set_description(&description);
+        }
+        info
+    }
+
+    /// Reads the tags of the first image of a TIFF file in memory, raw images only tell
+    /// their size
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if let Some((width, height)) = raw_image::dimensions(bytes) {
+            return Some(Self {
+                width,
+                height,
+                ..Self::default()
+            });
+        }
+        #[cfg(feature = "tiff")]
+        let info = Decoder::new(std::io::Cursor::new(bytes))
+            .ok()
+            .map(|mut decoder| Self::read(&mut decoder));
+        #[cfg(not(feature = "tiff"))]
+        let info = None;
+        info
+    }
+
+    /// Splits the description into its parameter lines and the remaining text
+    #[cfg_attr(not(feature = "tiff"), allow(dead_code))]
+    fn set_description(&mut self, description: &str) {
+        let mut text = Vec::new();
+        for line in description.lines().map(str::trim) {
+            let parameter = line
+                .split_once('=')
+                .or_else(|| line.split_once(": "))
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty() && key.len() <= 64);
+            match parameter {
+                Some((key, value)) => self.parameters.push((key.to_owned(), value.to_owned())),
+                None if !line.is_empty() => text.push(line),
+                None => {}
+            }
+        }
+        self.description = Some(text.join("\n")).filter(|text| !text.is_empty());
+    }
+
+    /// Rows of the info panel, the known fields first and then the parameters
+    pub fn entries(&self) -> Vec<DatasetInfoEntry> {
+        let mut entries = Vec::new();
+        let mut push = |label: &str, value: String| {
+            entries.push(DatasetInfoEntry {
+                label: label.to_owned(),
+                value,
+            })
+        };
+        if let Some(name) = &self.name {
+            push("Dataset", name.clone());
+        }
+        push("Size", format!("{} × {} pixels", self.width, self.height));
+        for (label, value) in [
+            ("Instrument", &self.instrument),
+            ("Software", &self.software),
+            ("Acquired", &self.acquired),
+        ] {
+            if let Some(value) = value {
+                push(label, value.clone());
+            }
+        }
+        if let Some(sha256) = &self.sha256 {
+            push("SHA-256", sha256.clone());
+        }
+        if let Some((x, y, unit)) = self.resolution {
+            push("Resolution", format!("{} × {} pixels per {}", x, y, unit));
+        }
+        if let Some(description) = &self.description {
+            push("Description", description.clone());
+        }
+        for (key, value) in &self.parameters {
+            push(key, value.clone());
+        }
+        entries
+    }
+}
+
+#[cfg(feature = "tiff")]
+fn read_resolution<R: std::io::Read + std::io::Seek>(
+    decoder: &mut Decoder<R>,
+) -> Option<(f64, f64, &'static str)> {
+    let mut rational = |tag| match decoder.find_tag(tag).ok()?? {
+        Value::Rational(numerator, denominator) if denominator != 0 => {
+            Some(numerator as f64 / denominator as f64)
+        }
+        _ => None,
+    };
+    let x = rational(Tag::XResolution)?;
+    let y = rational(Tag::YResolution)?;
+    let unit = match decoder
+        .find_tag(Tag::ResolutionUnit)
+        .ok()
+        .flatten()
+        .and_then(|unit| unit.into_u16().ok())
+    {
+        Some(1) => "unit",
+        Some(3) => "cm",
+        // Inch is the default of the TIFF specification
+        _ => "inch",
+    };
+    Some((x, y, unit))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn description_lines_become_parameters() {
+        let mut info = DatasetInfo {
+            width: 3,
+            height: 2,
+            ..DatasetInfo::default()
+        };
+        info.set_description("Profilometer scan\nObjective=50x\nScan speed: 2 um/s\n\nunit=um");
+        assert_eq!(info.description.as_deref(), Some("Profilometer scan"));
+        assert_eq!(
+            info.parameters,
+            [
+                ("Objective".to_owned(), "50x".to_owned()),
+                ("Scan speed".to_owned(), "2 um/s".to_owned()),
+                ("unit".to_owned(), "um".to_owned()),
+            ]
+        );
+        let entries = info.entries();
+        assert_eq!(entries[0].value, "3 × 2 pixels");
+        assert_eq!(entries[2].label, "Objective");
+    }
+}
@@ -0,0 +1,68 @@
+// Developer visualizations drawn over or instead of the shaded surface: its bounding box
+// colored by the mip level it is drawn from, the normals of the mesh on a coarse grid and a
+// heatmap of how many triangles cover each pixel.
+
+/// Which debug visualizations are shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DebugView {
+    /// Bounding box of the surface, colored by the level of detail
+    pub bounds: bool,
+    /// Normal vectors as lines on a grid of `NORMAL_GRID` × `NORMAL_GRID` vertices
+    pub normals: bool,
+    /// Additive heatmap of the triangles covering each pixel instead of the shaded surface
+    pub overdraw: bool,
+}
+
+impl DebugView {
+    /// Vertices along each side of the grid the normals are drawn on
+    pub const NORMAL_GRID: u32 = 48;
+
+    /// Cycles from nothing through each visualization alone
+    pub fn next(self) -> Self {
+        match (self.bounds, self.normals, self.overdraw) {
+            (false, false, false) => Self {
+                bounds: true,
+                ..Self::default()
+            },
+            (true, false, false) => Self {
+                normals: true,
+                ..Self::default()
+            },
+            (false, true, false) => Self {
+                overdraw: true,
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn next_cycles_through_each_visualization() {
+        let mut view = DebugView::default();
+        let mut shown = Vec::new();
+        for _ in 0..4 {
+            view = view.next();
+            shown.push((view.bounds, view.normals, view.overdraw));
+        }
+        assert_eq!(
+            shown,
+            [
+                (true, false, false),
+                (false, true, false),
+                (false, false, true),
+                (false, false, false)
+            ]
+        );
+        let combined = DebugView {
+            bounds: true,
+            normals: true,
+            overdraw: false,
+        };
+        assert_eq!(combined.next(), DebugView::default());
+    }
+}
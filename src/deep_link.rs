@@ -0,0 +1,185 @@
+// Links that open the same view of the same dataset. In the browser the viewer keeps the link in
+// the URL hash of the page, e.g. `#dataset=data/part.tiff&layer=height&camera=...`, so that
+// it can be shared or bookmarked and is restored when the page is opened.
+
+use anyhow::anyhow;
+use std::fmt;
+
+use crate::view_state::ViewState;
+
+/// Dataset URLs and the `ViewState` settings of a link
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeepLink {
+    /// URL of the surface
+    pub dataset: Option<String>,
+    /// URL of the amplitude image if it is a separate file
+    pub amplitude: Option<String>,
+    pub view: ViewState,
+}
+
+impl DeepLink {
+    /// Reads a link like `#dataset=...&layer=amplitude`. View settings missing in the link keep
+    /// their value in `view`.
+    pub fn parse(link: &str, view: ViewState) -> anyhow::Result<Self> {
+        let mut dataset = None;
+        let mut amplitude = None;
+        let mut settings = Vec::new();
+        for setting in link.trim_start_matches(['#', '?']).split('&') {
+            if let Some(url) = setting.strip_prefix("dataset=") {
+                dataset = Some(decode(url)?);
+            } else if let Some(url) = setting.strip_prefix("amplitude=") {
+                amplitude = Some(decode(url)?);
+            } else {
+                settings.push(setting);
+            }
+        }
+        let mut link = Self {
+            dataset,
+            amplitude,
+            view,
+        };
+        link.view.update(&settings.join("&"))?;
+        Ok(link)
+    }
+}
+
+impl fmt::Display for DeepLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(dataset) = &self.dataset {
+            write!(f, "dataset={}&", encode(dataset))?;
+        }
+        if let Some(amplitude) = &self.amplitude {
+            write!(f, "amplitude={}&", encode(amplitude))?;
+        }
+        write!(f, "{}", self.view)
+    }
+}
+
+/// Percent-encodes everything but unreserved characters and the separators of paths
+fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn decode(text: &str) -> anyhow::Result<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| anyhow!("Invalid percent-encoding in {}", text))?;
+            bytes.push(hex);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// URL hash of the page, empty without one
+#[cfg(target_arch = "wasm32")]
+pub fn read_hash() -> String {
+    web_sys::window()
+        .and_then(|window| window.location().hash().ok())
+        .unwrap_or_default()
+}
+
+/// Keeps the URL hash of the page up to date with the shown view
+#[cfg(target_arch = "wasm32")]
+#[derive(Default)]
+pub struct UrlHash {
+    /// Last link written to the hash
+    written: String,
+    last_update: Option<web_time::Instant>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl UrlHash {
+    /// Time between updates, the camera changes with every frame while dragging and browsers
+    /// limit how often the URL may change
+    const INTERVAL: web_time::Duration = web_time::Duration::from_millis(250);
+
+    /// Replaces the hash if the link changed, without adding a history entry per change.
+    /// Returns when to check again if it is too early.
+    pub fn update(
+        &mut self,
+        now: web_time::Instant,
+        link: impl FnOnce() -> DeepLink,
+    ) -> Option<web_time::Instant> {
+        if let Some(at) = self.last_update.map(|last| last + Self::INTERVAL)
+            && at > now
+        {
+            return Some(at);
+        }
+        let link = link().to_string();
+        if link == self.written {
+            return None;
+        }
+        self.last_update = Some(now);
+        let history = web_sys::window().and_then(|window| window.history().ok())?;
+        if let Err(e) = history.replace_state_with_url(
+            &wasm_bindgen::JsValue::NULL,
+            "",
+            Some(&format!("#{}", link)),
+        ) {
+            log::warn!("Failed to update the URL hash: {:?}", e);
+        }
+        self.written = link;
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{colormap::Colormap, histogram::Layer};
+
+    #[test]
+    fn links_round_trip() {
+        let link = DeepLink {
+            dataset: Some("https://example.com/data/part 1.tiff?v=2&x=%".to_owned()),
+            amplitude: Some("part-amplitude.tiff".to_owned()),
+            view: ViewState {
+                layer: Layer::Amplitude,
+                z_scale: 4.0,
+                ..Default::default()
+            },
+        };
+        let text = link.to_string();
+        assert!(text.starts_with(
+            "dataset=https://example.com/data/part%201.tiff%3Fv%3D2%26x%3D%25\
+             &amplitude=part-amplitude.tiff&layer=amplitude&"
+        ));
+        assert_eq!(
+            DeepLink::parse(&format!("#{}", text), ViewState::default()).unwrap(),
+            link
+        );
+    }
+
+    #[test]
+    fn missing_settings_keep_the_current_view() {
+        let view = ViewState {
+            colormap: Colormap::Viridis,
+            height_range: [-2.0, 3.0],
+            ..Default::default()
+        };
+        let link = DeepLink::parse("#layer=amplitude", view).unwrap();
+        assert_eq!((link.dataset, link.amplitude), (None, None));
+        assert_eq!(link.view.layer, Layer::Amplitude);
+        assert_eq!(link.view.colormap, Colormap::Viridis);
+        assert_eq!(link.view.height_range, [-2.0, 3.0]);
+        assert!(DeepLink::parse("#dataset=a%2", view).is_err());
+    }
+}
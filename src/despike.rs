@@ -0,0 +1,113 @@
+// Spikes are single pixels far off their neighbors, e.g. from dust or reflections. Clamping the
+// heights at percentiles flattens them into plateaus at the clamp limits, despiking replaces
+// them by the median of their neighbors instead and leaves every other pixel untouched.
+
+use crate::{
+    image::{Image, ImageData},
+    parallel,
+};
+
+/// Deviations from the neighbors, in noise levels, above which a pixel is a spike
+pub const DEFAULT_THRESHOLD: f32 = 5.0;
+
+/// Factor from the median absolute deviation to the standard deviation of normal noise
+const MAD_TO_SIGMA: f32 = 1.4826;
+
+fn median(values: &mut [f32]) -> f32 {
+    if values.is_empty() {
+        return f32::NAN;
+    }
+    let middle = values.len() / 2;
+    *values.select_nth_unstable_by(middle, f32::total_cmp).1
+}
+
+/// Median of the valid 8 neighbors of every pixel, NaN without any
+fn neighbor_medians(image: &Image<f32>) -> Vec<f32> {
+    let (width, height) = (image.size.width.get(), image.size.height.get());
+    let mut medians = vec![f32::NAN; image.data.len()];
+    parallel::for_each_chunk_mut(&mut medians, width as usize, |y, row| {
+        let y = y as u32;
+        let mut neighbors = Vec::with_capacity(8);
+        for (x, median_value) in (0..width).zip(row.iter_mut()) {
+            neighbors.clear();
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let value = image.get_pixel(nx, ny);
+                    if (nx, ny) != (x, y) && value.is_finite() {
+                        neighbors.push(value);
+                    }
+                }
+            }
+            *median_value = median(&mut neighbors);
+        }
+    });
+    medians
+}
+
+/// Copy of `image` with the pixels that deviate from the median of their neighbors by more than
+/// `threshold` times the noise level replaced by that median, and the number of replaced pixels.
+/// The noise level is estimated from the deviations of all pixels, so that the surface's own
+/// slopes and texture don't count as spikes.
+pub(crate) fn despike(image: &Image<f32>, threshold: f32) -> (Image<f32>, usize) {
+    let medians = neighbor_medians(image);
+    let mut deviations: Vec<f32> = image
+        .data
+        .iter()
+        .zip(&medians)
+        .map(|(value, median)| (value - median).abs())
+        .filter(|deviation| deviation.is_finite())
+        .collect();
+    let sigma = MAD_TO_SIGMA * median(&mut deviations);
+    // Without noise only deviations from a perfectly smooth surface are spikes
+    let limit = threshold * sigma.max(f32::EPSILON);
+
+    let mut replaced = 0;
+    let data = image
+        .data
+        .iter()
+        .zip(&medians)
+        .map(|(&value, &median)| {
+            if (value - median).abs() > limit {
+                replaced += 1;
+                median
+            } else {
+                value
+            }
+        })
+        .collect();
+    let despiked = Image {
+        size: image.size.clone(),
+        data: ImageData::Owned(data),
+    };
+    (despiked, replaced)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    #[test]
+    fn spikes_are_replaced_by_their_neighbors() {
+        let size = ImageSize::new(6, 5).unwrap();
+        // A slope with some noise, a spike, a pit and a missing pixel
+        let data = (0..30)
+            .map(|i| match i {
+                8 => 50.0,
+                21 => -40.0,
+                27 => f32::NAN,
+                _ => (i % 6) as f32 * 0.5 + if i % 2 == 0 { 0.02 } else { -0.02 },
+            })
+            .collect();
+        let image = Image::new(size, data).unwrap();
+        let (despiked, replaced) = despike(&image, DEFAULT_THRESHOLD);
+        assert_eq!(replaced, 2);
+        for (i, (&before, &after)) in image.data.iter().zip(despiked.data.iter()).enumerate() {
+            match i {
+                8 | 21 => assert!(after.abs() < 3.0, "pixel {} is {}", i, after),
+                27 => assert!(after.is_nan()),
+                _ => assert_eq!(after, before, "pixel {}", i),
+            }
+        }
+    }
+}
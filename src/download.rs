@@ -0,0 +1,678 @@
+// Kiosks and web deployments load their datasets over lab networks that drop connections and
+// restart proxies. Failed downloads are retried with exponential backoff, and continue with an
+// HTTP range request after the bytes already received where the server supports it. Progress
+// is reported as the body arrives, so that the viewer can show how far a large file is. Data
+// behind SSO-protected APIs is requested with headers from the caller, e.g. a bearer token,
+// which a callback can refresh before every attempt.
+
+use std::ops::Range;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+use web_time::Duration;
+
+use crate::{data_source, error::ViewerError};
+
+/// How often and when a failed download is tried again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, 1 doesn't retry
+    pub attempts: u32,
+    /// Wait before the first retry, doubled for every further one
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(16),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retrying after `failures` failed attempts in a row
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+}
+
+/// Headers of a request for a URL, called before every attempt
+#[cfg(not(target_arch = "wasm32"))]
+pub type RequestCallback = Arc<dyn Fn(&str) -> Vec<(String, String)> + Send + Sync>;
+
+/// How files are requested
+#[derive(Clone, Default)]
+pub struct DownloadOptions {
+    pub retry: RetryPolicy,
+    /// Sent with every request, e.g. `Authorization: Bearer <token>`
+    pub headers: Vec<(String, String)>,
+    /// Adds headers to every request after `headers`, so that short-lived tokens are fetched
+    /// when they are needed
+    #[cfg(not(target_arch = "wasm32"))]
+    pub callback: Option<RequestCallback>,
+    /// JS function called with the URL, returning an object of header names and values or a
+    /// promise of one
+    #[cfg(target_arch = "wasm32")]
+    pub callback: Option<js_sys::Function>,
+    /// Sends the cookies of the page with cross-origin requests, for SSO with session cookies
+    #[cfg(target_arch = "wasm32")]
+    pub include_credentials: bool,
+}
+
+impl DownloadOptions {
+    /// Sets the header `name`, replacing a header of the same name, or removes it with `None`
+    pub fn set_header(&mut self, name: &str, value: Option<String>) {
+        self.headers
+            .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        if let Some(value) = value {
+            self.headers.push((name.to_owned(), value));
+        }
+    }
+
+    /// Headers of a request for `url`
+    async fn request_headers(&self, url: &str) -> Result<Vec<(String, String)>, Failure> {
+        let mut headers = self.headers.clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(callback) = &self.callback {
+            headers.extend(callback(url));
+        }
+        #[cfg(target_arch = "wasm32")]
+        if let Some(callback) = &self.callback {
+            let fatal = |error: wasm_bindgen::JsValue| Failure {
+                retry: false,
+                ..Failure::js(error)
+            };
+            let mut result = callback
+                .call1(&wasm_bindgen::JsValue::NULL, &url.into())
+                .map_err(fatal)?;
+            if let Some(promise) = result.dyn_ref::<js_sys::Promise>() {
+                result = wasm_bindgen_futures::JsFuture::from(promise.clone())
+                    .await
+                    .map_err(fatal)?;
+            }
+            if result.is_object() {
+                for entry in js_sys::Object::entries(result.unchecked_ref()) {
+                    let entry: js_sys::Array = entry.unchecked_into();
+                    if let (Some(name), Some(value)) =
+                        (entry.get(0).as_string(), entry.get(1).as_string())
+                    {
+                        headers.push((name, value));
+                    }
+                }
+            }
+        }
+        Ok(headers)
+    }
+
+    /// Request of the browser's fetch with the headers and credentials of the options
+    #[cfg(target_arch = "wasm32")]
+    pub async fn request_init(
+        &self,
+        url: &str,
+        range: Option<&str>,
+    ) -> Result<web_sys::RequestInit, Failure> {
+        let fatal = |error: wasm_bindgen::JsValue| Failure {
+            retry: false,
+            ..Failure::js(error)
+        };
+        let init = web_sys::RequestInit::new();
+        let headers = web_sys::Headers::new().map_err(fatal)?;
+        for (name, value) in self.request_headers(url).await? {
+            headers.append(&name, &value).map_err(fatal)?;
+        }
+        if let Some(range) = range {
+            headers.set("Range", range).map_err(fatal)?;
+        }
+        init.set_headers(&headers);
+        if self.include_credentials {
+            init.set_credentials(web_sys::RequestCredentials::Include);
+        }
+        Ok(init)
+    }
+}
+
+/// Bytes of a download received so far
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DownloadProgress {
+    pub received: u64,
+    /// Size of the file, `None` if the server didn't send it
+    pub total: Option<u64>,
+}
+
+impl DownloadProgress {
+    /// Received fraction from 0 to 1, `None` without a total
+    pub fn fraction(&self) -> Option<f32> {
+        self.total
+            .filter(|total| *total > 0)
+            .map(|total| (self.received as f64 / total as f64).min(1.0) as f32)
+    }
+
+    /// Percent received, or MiB without a total, to report progress in coarse steps
+    pub fn step(&self) -> u64 {
+        match self.fraction() {
+            Some(fraction) => (fraction * 100.0) as u64,
+            None => self.received >> 20,
+        }
+    }
+}
+
+/// Error of an attempt and whether another attempt may succeed
+#[derive(Debug)]
+pub struct Failure {
+    pub error: ViewerError,
+    pub retry: bool,
+}
+
+impl Failure {
+    fn retry(reason: impl Into<String>) -> Self {
+        Self {
+            error: ViewerError::Network(reason.into()),
+            retry: true,
+        }
+    }
+
+    fn fatal(reason: impl Into<String>) -> Self {
+        Self {
+            error: ViewerError::Network(reason.into()),
+            retry: false,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<reqwest::Error> for Failure {
+    fn from(error: reqwest::Error) -> Self {
+        Self {
+            retry: !error.is_builder(),
+            error: error.into(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Failure {
+    /// A rejected `fetch` or body read, which is how browsers report network errors
+    fn js(error: wasm_bindgen::JsValue) -> Self {
+        Self::retry(error.as_string().unwrap_or_else(|| format!("{:?}", error)))
+    }
+}
+
+/// Status and headers of a response that decide how its body continues the download
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResponseHead<'a> {
+    pub status: u16,
+    pub content_range: Option<&'a str>,
+    pub content_length: Option<u64>,
+    /// The client decoded a `Content-Encoding`, lengths and ranges are the ones of the encoded
+    /// body then and don't apply to the received bytes
+    pub decoded: bool,
+}
+
+/// Data of a download across its attempts
+#[derive(Debug)]
+pub struct PartialDownload {
+    data: Vec<u8>,
+    total: Option<u64>,
+    /// Whether the next attempt asks for the rest only
+    resumable: bool,
+}
+
+impl Default for PartialDownload {
+    fn default() -> Self {
+        Self {
+            data: Vec::new(),
+            total: None,
+            resumable: true,
+        }
+    }
+}
+
+impl PartialDownload {
+    /// Value of the `Range` header of the next request, `None` to request the whole file
+    pub fn range(&self) -> Option<String> {
+        (self.resumable && !self.data.is_empty()).then(|| format!("bytes={}-", self.data.len()))
+    }
+
+    /// Checks a response before its body is pushed
+    pub fn begin(&mut self, head: ResponseHead) -> Result<(), Failure> {
+        match head.status {
+            206 => {
+                let resumed = head
+                    .content_range
+                    .and_then(parse_content_range)
+                    .filter(|(start, _)| *start == self.data.len() as u64);
+                match resumed {
+                    Some((_, total)) if !head.decoded => {
+                        self.total = total.or(self.total);
+                        Ok(())
+                    }
+                    // Unusable range, e.g. where proxies hide the `Content-Range` header
+                    _ => {
+                        self.restart(false);
+                        Err(Failure::retry("server can't resume the download"))
+                    }
+                }
+            }
+            200..=299 => {
+                self.restart(!head.decoded);
+                self.total = head.content_length.filter(|_| !head.decoded);
+                Ok(())
+            }
+            // The file changed or the server miscounted, start over
+            416 => {
+                self.restart(false);
+                Err(Failure::retry("server can't resume the download"))
+            }
+            408 | 429 | 500..=599 => Err(Failure::retry(format!("HTTP status {}", head.status))),
+            status => Err(Failure::fatal(format!("HTTP status {}", status))),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+    }
+
+    /// Checks that the body was complete
+    pub fn finish(&self) -> Result<(), Failure> {
+        match self.total {
+            Some(total) if (self.data.len() as u64) < total => Err(Failure::retry(format!(
+                "connection closed after {} of {} bytes",
+                self.data.len(),
+                total
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn progress(&self) -> DownloadProgress {
+        DownloadProgress {
+            received: self.data.len() as u64,
+            total: self.total,
+        }
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    fn restart(&mut self, resumable: bool) {
+        self.data.clear();
+        self.total = None;
+        self.resumable = resumable;
+    }
+}
+
+/// First byte and size of the file of a `Content-Range` header like "bytes 100-199/1000"
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+/// Downloads `url`, retrying failed attempts as the options allow. Attempts that received more
+/// of the file than any before don't count as failures, so that slow but working connections
+/// finish. `progress` is called for every chunk of the body.
+pub async fn download(
+    url: &str,
+    options: &DownloadOptions,
+    mut progress: impl FnMut(DownloadProgress),
+) -> Result<Vec<u8>, ViewerError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .read_timeout(Duration::from_secs(30))
+        .build()?;
+    let mut partial = PartialDownload::default();
+    let mut most_received = 0;
+    let mut failures = 0;
+    loop {
+        #[cfg(not(target_arch = "wasm32"))]
+        let result = attempt(&client, url, options, &mut partial, &mut progress).await;
+        #[cfg(target_arch = "wasm32")]
+        let result = attempt(url, options, &mut partial, &mut progress).await;
+        let failure = match result {
+            Ok(()) => return Ok(partial.into_data()),
+            Err(failure) => failure,
+        };
+        if partial.len() > most_received {
+            most_received = partial.len();
+            failures = 0;
+        }
+        failures += 1;
+        if !failure.retry || failures >= options.retry.attempts {
+            return Err(failure.error);
+        }
+        let delay = options.retry.delay(failures);
+        log::warn!(
+            "Download of {} failed: {}, retrying in {:.1} s",
+            url,
+            failure.error,
+            delay.as_secs_f32()
+        );
+        sleep(delay).await;
+    }
+}
+
+/// Downloads the bytes in `range` of `url` with a single request. Servers that ignore ranges
+/// send the whole file, which is cut to the range then.
+pub async fn download_range(
+    url: &str,
+    options: &DownloadOptions,
+    range: Range<u64>,
+) -> Result<Vec<u8>, ViewerError> {
+    if range.is_empty() {
+        return Ok(Vec::new());
+    }
+    let header = format!("bytes={}-{}", range.start, range.end - 1);
+    #[cfg(not(target_arch = "wasm32"))]
+    let (status, body) = {
+        let mut request = reqwest::Client::new().get(url);
+        for (name, value) in options
+            .request_headers(url)
+            .await
+            .map_err(|failure| failure.error)?
+        {
+            request = request.header(name, value);
+        }
+        let response = request
+            .header(reqwest::header::RANGE, header)
+            .send()
+            .await?;
+        (response.status().as_u16(), response.bytes().await?.to_vec())
+    };
+    #[cfg(target_arch = "wasm32")]
+    let (status, body) = {
+        let error = |error| Failure::js(error).error;
+        let window = web_sys::window().ok_or_else(|| ViewerError::Network("no window".into()))?;
+        let init = options
+            .request_init(url, Some(&header))
+            .await
+            .map_err(|failure| failure.error)?;
+        let response: web_sys::Response =
+            wasm_bindgen_futures::JsFuture::from(window.fetch_with_str_and_init(url, &init))
+                .await
+                .map_err(error)?
+                .dyn_into()
+                .map_err(error)?;
+        let buffer = wasm_bindgen_futures::JsFuture::from(response.array_buffer().map_err(error)?)
+            .await
+            .map_err(error)?;
+        (response.status(), js_sys::Uint8Array::new(&buffer).to_vec())
+    };
+    match status {
+        206 => Ok(body),
+        200 => Ok(data_source::cut(body, range)),
+        // The range starts after the end of the file
+        416 => Ok(Vec::new()),
+        status => Err(ViewerError::Network(format!(
+            "HTTP status {} for {}",
+            status, url
+        ))),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn attempt(
+    client: &reqwest::Client,
+    url: &str,
+    options: &DownloadOptions,
+    partial: &mut PartialDownload,
+    progress: &mut impl FnMut(DownloadProgress),
+) -> Result<(), Failure> {
+    let mut request = client.get(url);
+    for (name, value) in options.request_headers(url).await? {
+        request = request.header(name, value);
+    }
+    if let Some(range) = partial.range() {
+        request = request.header(reqwest::header::RANGE, range);
+    }
+    let mut response = request.send().await?;
+    let content_range = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    partial.begin(ResponseHead {
+        status: response.status().as_u16(),
+        content_range: content_range.as_deref(),
+        content_length: response.content_length(),
+        decoded: false,
+    })?;
+    while let Some(chunk) = response.chunk().await? {
+        partial.push(&chunk);
+        progress(partial.progress());
+    }
+    partial.finish()
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn attempt(
+    url: &str,
+    options: &DownloadOptions,
+    partial: &mut PartialDownload,
+    progress: &mut impl FnMut(DownloadProgress),
+) -> Result<(), Failure> {
+    let window = web_sys::window().ok_or_else(|| Failure::fatal("no window"))?;
+    let init = options
+        .request_init(url, partial.range().as_deref())
+        .await?;
+    // The browser's fetch instead of an HTTP client keeps the module small
+    let response: web_sys::Response =
+        wasm_bindgen_futures::JsFuture::from(window.fetch_with_str_and_init(url, &init))
+            .await
+            .map_err(Failure::js)?
+            .dyn_into()
+            .map_err(Failure::js)?;
+    let headers = response.headers();
+    let header = |name: &str| headers.get(name).ok().flatten();
+    // The browser already decoded it, files served compressed as they are are recognized when
+    // they are decoded, see `compression`
+    let encoding = header("content-encoding");
+    if let Some(encoding) = &encoding {
+        log::info!("Received {} with content encoding {}", url, encoding);
+    }
+    let content_range = header("content-range");
+    partial.begin(ResponseHead {
+        status: response.status(),
+        content_range: content_range.as_deref(),
+        content_length: header("content-length").and_then(|length| length.parse().ok()),
+        decoded: encoding.is_some(),
+    })?;
+    let reader: web_sys::ReadableStreamDefaultReader = response
+        .body()
+        .ok_or_else(|| Failure::fatal("response has no body"))?
+        .get_reader()
+        .dyn_into()
+        .map_err(|reader| Failure::js(reader.into()))?;
+    loop {
+        let chunk = wasm_bindgen_futures::JsFuture::from(reader.read())
+            .await
+            .map_err(Failure::js)?;
+        if js_sys::Reflect::get(&chunk, &"done".into())
+            .map_err(Failure::js)?
+            .is_truthy()
+        {
+            break;
+        }
+        let value = js_sys::Reflect::get(&chunk, &"value".into()).map_err(Failure::js)?;
+        partial.push(&js_sys::Uint8Array::new(&value).to_vec());
+        progress(partial.progress());
+    }
+    partial.finish()
+}
+
+/// Waits without blocking the executor, independent of the async runtime polling the download
+#[cfg(not(target_arch = "wasm32"))]
+async fn sleep(duration: Duration) {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        let _ = sender.send(());
+    });
+    let _ = receiver.await;
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn sleep(duration: Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let scheduled = web_sys::window().is_some_and(|window| {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    &resolve,
+                    duration.as_millis() as i32,
+                )
+                .is_ok()
+        });
+        if !scheduled {
+            let _ = resolve.call0(&wasm_bindgen::JsValue::NULL);
+        }
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn headers_replace_those_of_the_same_name() {
+        let mut options = DownloadOptions::default();
+        options.set_header("Authorization", Some("Bearer old".to_owned()));
+        options.set_header("X-Lab", Some("3".to_owned()));
+        options.set_header("authorization", Some("Bearer new".to_owned()));
+        assert_eq!(
+            options.headers,
+            [
+                ("X-Lab".to_owned(), "3".to_owned()),
+                ("authorization".to_owned(), "Bearer new".to_owned())
+            ]
+        );
+        options.callback = Some(Arc::new(|url: &str| {
+            vec![("X-Url".to_owned(), url.to_owned())]
+        }));
+        let headers = pollster::block_on(options.request_headers("https://lab/scan.tiff")).unwrap();
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers[2].1, "https://lab/scan.tiff");
+        options.set_header("X-Lab", None);
+        assert_eq!(options.headers.len(), 1);
+    }
+
+    #[test]
+    fn retries_back_off_exponentially_up_to_the_maximum() {
+        let policy = RetryPolicy::default();
+        let delays: Vec<u128> = (1..=7)
+            .map(|failures| policy.delay(failures).as_millis())
+            .collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 8000, 16000, 16000]);
+        assert_eq!(policy.delay(100), policy.max_delay);
+    }
+
+    #[test]
+    fn interrupted_downloads_resume_after_the_received_bytes() {
+        let mut partial = PartialDownload::default();
+        assert_eq!(partial.range(), None);
+        partial
+            .begin(ResponseHead {
+                status: 200,
+                content_length: Some(10),
+                ..Default::default()
+            })
+            .unwrap();
+        partial.push(b"0123");
+        assert_eq!(partial.progress().fraction(), Some(0.4));
+        let failure = partial.finish().unwrap_err();
+        assert!(failure.retry);
+
+        assert_eq!(partial.range().as_deref(), Some("bytes=4-"));
+        partial
+            .begin(ResponseHead {
+                status: 206,
+                content_range: Some("bytes 4-9/10"),
+                content_length: Some(6),
+                ..Default::default()
+            })
+            .unwrap();
+        partial.push(b"456789");
+        partial.finish().unwrap();
+        assert_eq!(partial.progress().step(), 100);
+        assert_eq!(partial.into_data(), b"0123456789");
+    }
+
+    #[test]
+    fn unusable_ranges_restart_the_download() {
+        let mut partial = PartialDownload::default();
+        partial
+            .begin(ResponseHead {
+                status: 200,
+                ..Default::default()
+            })
+            .unwrap();
+        partial.push(b"0123");
+        // A server ignoring the range sends the whole file again
+        partial
+            .begin(ResponseHead {
+                status: 200,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(partial.len(), 0);
+
+        partial.push(b"0123");
+        let failure = partial
+            .begin(ResponseHead {
+                status: 206,
+                content_range: Some("bytes 2-9/10"),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(failure.retry);
+        assert_eq!(partial.len(), 0);
+        // Without ranges from now on
+        partial.push(b"01");
+        assert_eq!(partial.range(), None);
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retried() {
+        for (status, retry) in [(404, false), (403, false), (429, true), (503, true)] {
+            let failure = PartialDownload::default()
+                .begin(ResponseHead {
+                    status,
+                    ..Default::default()
+                })
+                .unwrap_err();
+            assert_eq!(failure.retry, retry, "{}", status);
+        }
+    }
+
+    #[test]
+    fn decoded_bodies_have_no_total() {
+        let mut partial = PartialDownload::default();
+        partial
+            .begin(ResponseHead {
+                status: 200,
+                content_length: Some(3),
+                decoded: true,
+                ..Default::default()
+            })
+            .unwrap();
+        partial.push(b"decoded body");
+        partial.finish().unwrap();
+        assert_eq!(partial.progress().fraction(), None);
+        assert_eq!(partial.range(), None);
+        assert_eq!(parse_content_range("bytes 0-99/*"), Some((0, None)));
+    }
+}
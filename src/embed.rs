@@ -0,0 +1,319 @@
+// Host applications like Qt, GTK or game engines own their windows and event loops. They embed
+// the viewer with the raw handles of one of their native windows, forward its input with
+// `handle_event` and call `render_frame` whenever they repaint, so that no winit window or event
+// loop is involved. On the web the same way renders into an `OffscreenCanvas` from a worker, see
+// `WorkerViewer`, keeping the main thread of the page responsive.
+
+use std::sync::Arc;
+
+use glam::{Vec2, Vec3};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use wgpu::rwh::{RawDisplayHandle, RawWindowHandle};
+use winit::dpi::PhysicalSize;
+
+#[cfg(target_arch = "wasm32")]
+use crate::image::Image;
+use crate::{
+    error::ViewerError, pixel_picker::PixelPicker, renderer::Renderer,
+    transformation::RotationConstraint,
+};
+
+/// Smallest zoom factor of one scroll event, a fast wheel must not flip the surface
+const MIN_ZOOM_STEP: f32 = 0.1;
+
+/// Button of a pointer event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointerButton {
+    /// Dragging rotates the surface
+    Primary,
+    /// Dragging pans the view
+    Secondary,
+}
+
+/// Input forwarded by the host, positions in physical pixels from the top left of the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmbedEvent {
+    Resized {
+        width: u32,
+        height: u32,
+    },
+    PointerMoved {
+        x: f32,
+        y: f32,
+    },
+    PointerButton {
+        button: PointerButton,
+        pressed: bool,
+    },
+    /// Scroll distance in lines, positive away from the user zooms in
+    Wheel {
+        lines: f32,
+    },
+    /// Shows the whole surface again
+    ResetCamera,
+}
+
+/// Viewer drawing into a native window of the host application
+pub struct EmbeddedViewer {
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    renderer: Renderer,
+    pixel_picker: PixelPicker,
+    /// Last pointer position in device coordinates
+    pointer: Vec2,
+    drag: Option<PointerButton>,
+}
+
+impl EmbeddedViewer {
+    /// Creates the viewer for a window of `size` physical pixels
+    ///
+    /// # Safety
+    ///
+    /// The display and the window of the handles must outlive the viewer.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async unsafe fn new(
+        display: RawDisplayHandle,
+        window: RawWindowHandle,
+        size: PhysicalSize<u32>,
+        power_preference: wgpu::PowerPreference,
+    ) -> Result<Self, ViewerError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        // SAFETY: the caller keeps the display and the window alive
+        let surface = unsafe {
+            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
+                raw_display_handle: display,
+                raw_window_handle: window,
+            })
+        }?;
+        Self::with_surface(&instance, surface, size, power_preference).await
+    }
+
+    /// Creates the viewer for a canvas the page transferred to a worker
+    #[cfg(target_arch = "wasm32")]
+    pub async fn from_offscreen_canvas(
+        canvas: web_sys::OffscreenCanvas,
+        power_preference: wgpu::PowerPreference,
+    ) -> Result<Self, ViewerError> {
+        let size = PhysicalSize::new(canvas.width(), canvas.height());
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance.create_surface(wgpu::SurfaceTarget::OffscreenCanvas(canvas))?;
+        Self::with_surface(&instance, surface, size, power_preference).await
+    }
+
+    async fn with_surface(
+        instance: &wgpu::Instance,
+        surface: wgpu::Surface<'static>,
+        size: PhysicalSize<u32>,
+        power_preference: wgpu::PowerPreference,
+    ) -> Result<Self, ViewerError> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference,
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
+            .await?;
+        log::info!("Embedded viewer using {}", adapter.get_info().name);
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default())
+            .await?;
+        let format = *surface
+            .get_capabilities(&adapter)
+            .formats
+            .first()
+            .ok_or(ViewerError::NoSurfaceFormat)?;
+        let device = Arc::new(device);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            view_formats: vec![format.add_srgb_suffix()],
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            desired_maximum_frame_latency: 2,
+            present_mode: wgpu::PresentMode::AutoVsync,
+        };
+        surface.configure(&device, &config);
+        Ok(Self {
+            surface,
+            config,
+            renderer: Renderer::new(device.clone(), queue, format.add_srgb_suffix(), size),
+            pixel_picker: PixelPicker::new(&device, size),
+            pointer: Vec2::ZERO,
+            drag: None,
+        })
+    }
+
+    /// Scene to render, set surface, overlays and markers here
+    pub(crate) fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    /// Configures the surface for the new size of the window, which the host has to call
+    /// before the next frame, directly or with `EmbedEvent::Resized`
+    pub fn reconfigure(&mut self, size: PhysicalSize<u32>) {
+        self.config.width = size.width.max(1);
+        self.config.height = size.height.max(1);
+        self.surface.configure(self.renderer.device(), &self.config);
+        self.renderer.resize(size);
+        self.pixel_picker.resize(self.renderer.device(), size);
+    }
+
+    /// Applies the input, returns whether the view changed and a frame should be rendered
+    pub fn handle_event(&mut self, event: EmbedEvent) -> bool {
+        match event {
+            EmbedEvent::Resized { width, height } => {
+                self.reconfigure(PhysicalSize::new(width, height));
+                true
+            }
+            EmbedEvent::PointerMoved { x, y } => {
+                self.pointer = self.device_coordinates(x, y);
+                match self.drag {
+                    Some(PointerButton::Primary) => self.renderer.transformation.rotate(
+                        Vec3::from((self.pointer, 1.0)),
+                        RotationConstraint::default(),
+                    ),
+                    Some(PointerButton::Secondary) => {
+                        self.renderer.projection.change_position(self.pointer)
+                    }
+                    None => return false,
+                }
+                true
+            }
+            EmbedEvent::PointerButton { button, pressed } => {
+                if !pressed {
+                    self.drag = self.drag.filter(|dragged| *dragged != button);
+                    return false;
+                }
+                match button {
+                    PointerButton::Primary => self
+                        .renderer
+                        .transformation
+                        .start_move(Vec3::from((self.pointer, 1.0))),
+                    PointerButton::Secondary => self.renderer.projection.start_move(self.pointer),
+                }
+                self.drag = Some(button);
+                false
+            }
+            EmbedEvent::Wheel { lines } => {
+                let zoom = self.renderer.projection.get_zoom();
+                self.renderer
+                    .projection
+                    .zoom(zoom * (1.0 - 0.1 * lines).max(MIN_ZOOM_STEP));
+                true
+            }
+            EmbedEvent::ResetCamera => {
+                self.renderer.reset_camera();
+                true
+            }
+        }
+    }
+
+    /// Draws one frame into the window. A frame the surface was lost for is skipped after
+    /// configuring the surface again.
+    pub fn render_frame(&mut self) -> Result<(), ViewerError> {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.reconfigure(self.renderer.size());
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.config.format.add_srgb_suffix()),
+            ..Default::default()
+        });
+        let mut encoder = self
+            .renderer
+            .device()
+            .create_command_encoder(&Default::default());
+        self.renderer
+            .render(&mut encoder, &view, &self.pixel_picker.picking_texture_view);
+        self.renderer.queue().submit([encoder.finish()]);
+        frame.present();
+        Ok(())
+    }
+
+    /// Same mapping as `Mouse::get_device_coordinates`
+    fn device_coordinates(&self, x: f32, y: f32) -> Vec2 {
+        let size = self.renderer.size();
+        let width = size.width.saturating_sub(1).max(1) as f32;
+        let height = size.height.saturating_sub(1).max(1) as f32;
+        Vec2::new(2.0 * x / width - 1.0, 1.0 - 2.0 * y / height)
+    }
+}
+
+/// Viewer drawing into an `OffscreenCanvas` in a worker. The page forwards the pointer input of
+/// its canvas and the worker calls `render_frame` when a call returned that the view changed.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub struct WorkerViewer {
+    viewer: EmbeddedViewer,
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl WorkerViewer {
+    /// Creates the viewer for a canvas from `transferControlToOffscreen`, fails where workers
+    /// have no WebGPU
+    pub async fn create(canvas: web_sys::OffscreenCanvas) -> Result<WorkerViewer, JsValue> {
+        // The worker has a module instance of its own, logging isn't set up yet
+        let _ = console_log::init_with_level(log::Level::Info);
+        let viewer =
+            EmbeddedViewer::from_offscreen_canvas(canvas, wgpu::PowerPreference::default())
+                .await
+                .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        Ok(Self { viewer })
+    }
+
+    /// Shows the surface of a TIFF file as a whole
+    pub fn load(&mut self, data: Vec<u8>) -> Result<(), JsValue> {
+        let image = Image::<f32>::try_from(data)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        let renderer = self.viewer.renderer();
+        renderer
+            .set_surface(image, None, None)
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))?;
+        renderer.reset_camera();
+        Ok(())
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> bool {
+        self.viewer
+            .handle_event(EmbedEvent::Resized { width, height })
+    }
+
+    /// Position in physical pixels of the canvas
+    pub fn pointer_moved(&mut self, x: f32, y: f32) -> bool {
+        self.viewer.handle_event(EmbedEvent::PointerMoved { x, y })
+    }
+
+    /// `button` of a `PointerEvent`, dragging with 0 rotates and with 2 pans
+    pub fn pointer_button(&mut self, button: i16, pressed: bool) -> bool {
+        let button = match button {
+            0 => PointerButton::Primary,
+            2 => PointerButton::Secondary,
+            _ => return false,
+        };
+        self.viewer
+            .handle_event(EmbedEvent::PointerButton { button, pressed })
+    }
+
+    /// Scroll distance in lines, positive away from the user zooms in
+    pub fn wheel(&mut self, lines: f32) -> bool {
+        self.viewer.handle_event(EmbedEvent::Wheel { lines })
+    }
+
+    pub fn reset_camera(&mut self) -> bool {
+        self.viewer.handle_event(EmbedEvent::ResetCamera)
+    }
+
+    pub fn render_frame(&mut self) -> Result<(), JsValue> {
+        self.viewer
+            .render_frame()
+            .map_err(|e| JsValue::from_str(&format!("Error: {}", e)))
+    }
+}
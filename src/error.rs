@@ -0,0 +1,56 @@
+/// Everything that can go wrong while setting up the viewer or loading data
+#[derive(Debug, thiserror::Error)]
+pub enum ViewerError {
+    #[error("No compatible graphics adapter found: {0}")]
+    NoAdapter(#[from] wgpu::RequestAdapterError),
+    #[error("Failed to open graphics device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+    #[error("Failed to create drawing surface: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    #[error("Drawing surface has no supported texture format")]
+    NoSurfaceFormat,
+    #[error("Failed to create window: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[error("Failed to read back from the GPU: {0}")]
+    Readback(String),
+    #[error("Failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to download image: {0}")]
+    Download(#[from] reqwest::Error),
+    #[error("Failed to download: {0}")]
+    Network(String),
+    #[cfg(feature = "tiff")]
+    #[error("Failed to decode image: {0}")]
+    Decode(#[from] tiff::TiffError),
+    #[error("Failed to decompress {0} data: {1}")]
+    Decompress(&'static str, String),
+    #[error("Unsupported {0} image format")]
+    UnsupportedFormat(&'static str),
+    #[error("Invalid image: {0}")]
+    InvalidImage(String),
+    #[error("Cancelled")]
+    Cancelled,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Failed to encode image: {0}")]
+    Encode(#[from] png::EncodingError),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("No graphics adapter matches {0}")]
+    NoComputeAdapter(String),
+    #[error("Invalid ICC profile: {0}")]
+    InvalidIccProfile(String),
+    #[error("Failed to acquire the next frame: {0}")]
+    Frame(#[from] wgpu::SurfaceError),
+}
+
+impl From<wgpu::BufferAsyncError> for ViewerError {
+    fn from(error: wgpu::BufferAsyncError) -> Self {
+        Self::Readback(error.to_string())
+    }
+}
+
+impl From<wgpu::PollError> for ViewerError {
+    fn from(error: wgpu::PollError) -> Self {
+        Self::Readback(error.to_string())
+    }
+}
@@ -0,0 +1,173 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    image::Image,
+    markers::{Marker, MarkerShape},
+    units::Units,
+};
+
+/// Whether an extremum is a local maximum or minimum of the heights
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtremumKind {
+    Peak,
+    Valley,
+}
+
+/// Highest or lowest pixel of its surroundings, see `find_extrema`
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Extremum {
+    pub kind: ExtremumKind,
+    pub x: u32,
+    pub y: u32,
+    pub height: f32,
+}
+
+impl ExtremumKind {
+    /// Annotation group of the markers of this kind
+    pub fn group(self) -> &'static str {
+        match self {
+            Self::Peak => "peaks",
+            Self::Valley => "valleys",
+        }
+    }
+}
+
+impl Extremum {
+    /// Number of peaks and valleys marked when none is given
+    pub const DEFAULT_COUNT: u32 = 5;
+    /// Distance in pixels extrema keep from each other when none is given
+    pub const DEFAULT_SEPARATION: f32 = 10.0;
+
+    pub fn to_display(self, units: Units) -> Self {
+        Self {
+            height: units.to_display(self.height),
+            ..self
+        }
+    }
+
+    /// Red dots for peaks, blue ones for valleys, drawn at the pixel heights
+    pub(crate) fn to_marker(self) -> Marker {
+        Marker {
+            pixel: [self.x, self.y],
+            color: match self.kind {
+                ExtremumKind::Peak => [230, 40, 40, 255],
+                ExtremumKind::Valley => [40, 110, 240, 255],
+            },
+            size: 12.0,
+            on_top: true,
+            shape: MarkerShape::Dot,
+            height: None,
+            group: Some(self.kind.group().to_string()),
+        }
+    }
+}
+
+/// The `count` highest peaks and `count` deepest valleys, each at least `min_separation` pixels
+/// from the others of its kind, the most extreme first. Candidates are pixels that are not
+/// lower (higher for valleys) than any valid neighbor, missing pixels are skipped.
+pub(crate) fn find_extrema(image: &Image<f32>, count: u32, min_separation: f32) -> Vec<Extremum> {
+    let mut extrema = select(image, ExtremumKind::Peak, count, min_separation);
+    extrema.extend(select(image, ExtremumKind::Valley, count, min_separation));
+    extrema
+}
+
+fn select(
+    image: &Image<f32>,
+    kind: ExtremumKind,
+    count: u32,
+    min_separation: f32,
+) -> Vec<Extremum> {
+    let (width, height) = (image.size.width.get(), image.size.height.get());
+    // Flipping the sign turns valleys into peaks
+    let sign = match kind {
+        ExtremumKind::Peak => 1.0,
+        ExtremumKind::Valley => -1.0,
+    };
+    let value = |x: u32, y: u32| sign * image.get_pixel(x, y);
+    let mut candidates: Vec<(u32, u32, f32)> = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let center = value(x, y);
+            if !center.is_finite() {
+                continue;
+            }
+            let is_extremum = (y.saturating_sub(1)..(y + 2).min(height))
+                .flat_map(|ny| (x.saturating_sub(1)..(x + 2).min(width)).map(move |nx| (nx, ny)))
+                .map(|(nx, ny)| value(nx, ny))
+                .all(|neighbor| neighbor.is_nan() || neighbor <= center);
+            if is_extremum {
+                candidates.push((x, y, center));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    // Greedy non-maximum suppression, plateaus yield only one extremum
+    let min_distance_squared = min_separation.max(1.0).powi(2);
+    let mut selected: Vec<Extremum> = Vec::new();
+    for (x, y, height) in candidates {
+        if selected.len() as u32 >= count {
+            break;
+        }
+        let far_enough = selected.iter().all(|other| {
+            let dx = other.x as f32 - x as f32;
+            let dy = other.y as f32 - y as f32;
+            dx * dx + dy * dy >= min_distance_squared
+        });
+        if far_enough {
+            selected.push(Extremum {
+                kind,
+                x,
+                y,
+                height: sign * height,
+            });
+        }
+    }
+    selected
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    #[test]
+    fn finds_peaks_and_valleys_in_order() {
+        let surface = Image::from_fn(ImageSize::new(20, 10).unwrap(), |x, y| match (x, y) {
+            (3, 3) => 5.0,
+            (15, 6) => 3.0,
+            (5, 5) => 4.0,
+            (10, 8) => -2.0,
+            (17, 1) => f32::NAN,
+            _ => 0.0,
+        });
+        let extrema = find_extrema(&surface, 2, 3.0);
+        let peaks: Vec<_> = extrema
+            .iter()
+            .filter(|e| e.kind == ExtremumKind::Peak)
+            .map(|e| (e.x, e.y, e.height))
+            .collect();
+        // (4, 4) is too close to the higher peak
+        assert_eq!(peaks, [(3, 3, 5.0), (15, 6, 3.0)]);
+        let valley = extrema
+            .iter()
+            .find(|e| e.kind == ExtremumKind::Valley)
+            .unwrap();
+        assert_eq!((valley.x, valley.y, valley.height), (10, 8, -2.0));
+    }
+
+    #[test]
+    fn plateaus_give_one_extremum() {
+        let surface = Image::from_fn(
+            ImageSize::new(8, 8).unwrap(),
+            |x, _| if x < 4 { 1.0 } else { 0.0 },
+        );
+        let extrema = find_extrema(&surface, 3, 100.0);
+        assert_eq!(extrema.len(), 2);
+        assert_eq!(extrema[0].height, 1.0);
+        assert_eq!(extrema[1].height, 0.0);
+    }
+}
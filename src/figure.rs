@@ -0,0 +1,224 @@
+use base64::Engine;
+
+use crate::{
+    colormap::Colormap, error::ViewerError, image::RgbaImage, provenance::ProvenanceEntry,
+    report::escape, units::Units,
+};
+
+/// Height of the strip below the view with the legend and the caption
+const FOOTER_HEIGHT: u32 = 150;
+const MARGIN: u32 = 16;
+const LEGEND_WIDTH: u32 = 256;
+/// Characters per caption line, SVG text doesn't wrap by itself
+const CAPTION_LINE_LENGTH: usize = 110;
+
+/// The shown view composed with its color legend, a scale bar and a caption as an SVG that can
+/// go into a publication as it is
+pub struct Figure<'a> {
+    pub dataset: &'a str,
+    pub view: &'a RgbaImage,
+    pub colormap: Colormap,
+    /// Heights at the ends of the colormap in the data unit
+    pub height_range: [f32; 2],
+    pub units: Units,
+    /// Screen pixels per data unit along the surface at the center of the view
+    pub pixels_per_unit: f32,
+    /// Vertical exaggeration of the view
+    pub z_scale: f32,
+    /// How the surface was loaded and processed, see `ProvenanceLog`
+    pub provenance: &'a [ProvenanceEntry],
+}
+
+impl Figure<'_> {
+    /// Dataset, units, color range and processing steps in one paragraph
+    pub fn caption(&self) -> String {
+        // The first step is the load itself, file hashes are in the provenance log
+        let steps: Vec<&str> = self
+            .provenance
+            .iter()
+            .skip(1)
+            .map(|entry| entry.step.as_str())
+            .filter(|step| !step.contains("SHA-256"))
+            .collect();
+        let processing = if steps.is_empty() {
+            "none".to_owned()
+        } else {
+            steps.join("; ")
+        };
+        let mut caption = format!(
+            "{}. Heights in {} from {} to {} ({} colormap)",
+            self.dataset,
+            self.units.display.symbol(),
+            self.units.format(self.height_range[0]),
+            self.units.format(self.height_range[1]),
+            self.colormap.name(),
+        );
+        if self.z_scale != 1.0 {
+            caption.push_str(&format!(", vertically exaggerated {}×", self.z_scale));
+        }
+        caption.push_str(&format!(
+            ". Scale bar at the center of the view. Processing: {}.",
+            processing
+        ));
+        caption
+    }
+
+    /// Longest scale bar with a length of 1, 2 or 5 times a power of ten in the display unit that
+    /// fits into a fifth of the view, its length in the display unit and in screen pixels
+    fn scale_bar(&self) -> Option<(f32, f32)> {
+        let pixels_per_unit = self.pixels_per_unit / self.units.to_display(1.0);
+        if !pixels_per_unit.is_finite() || pixels_per_unit <= 0.0 {
+            return None;
+        }
+        let max_length = self.view.width as f32 / 5.0 / pixels_per_unit;
+        let power = 10f32.powf(max_length.log10().floor());
+        let length = [5.0, 2.0, 1.0]
+            .into_iter()
+            .map(|factor| factor * power)
+            .find(|&length| length <= max_length)?;
+        Some((length, length * pixels_per_unit))
+    }
+
+    pub fn to_svg(&self) -> Result<String, ViewerError> {
+        let (width, height) = (self.view.width, self.view.height);
+        let view = base64::engine::general_purpose::STANDARD.encode(self.view.to_png()?);
+        let stops: String = (0..=10)
+            .map(|step| {
+                let t = step as f32 / 10.0;
+                let [r, g, b] = self.colormap.srgb(t).map(|c| (c * 255.0).round() as u8);
+                format!(
+                    "<stop offset=\"{}\" stop-color=\"#{:02x}{:02x}{:02x}\"/>\n",
+                    t, r, g, b
+                )
+            })
+            .collect();
+        let scale_bar = self.scale_bar().map_or(String::new(), |(length, pixels)| {
+            let (x, y) = (
+                width as f32 - MARGIN as f32 - pixels,
+                (height - MARGIN) as f32,
+            );
+            format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{pixels}\" height=\"6\" fill=\"white\" \
+                 stroke=\"black\"/>\n\
+                 <text x=\"{center}\" y=\"{label}\" text-anchor=\"middle\" fill=\"white\" \
+                 stroke=\"black\" stroke-width=\"0.5\">{length} {unit}</text>\n",
+                center = x + pixels / 2.0,
+                label = y - 6.0,
+                unit = self.units.display.symbol(),
+            )
+        });
+        let caption: String = wrap(&self.caption(), CAPTION_LINE_LENGTH)
+            .iter()
+            .enumerate()
+            .map(|(line, text)| {
+                format!(
+                    "<text x=\"{}\" y=\"{}\">{}</text>\n",
+                    MARGIN,
+                    height + 70 + line as u32 * 18,
+                    escape(text)
+                )
+            })
+            .collect();
+        Ok(format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{total_height}" font-family="sans-serif" font-size="13">
+<defs>
+<linearGradient id="colormap">
+{stops}</linearGradient>
+</defs>
+<rect width="100%" height="100%" fill="white"/>
+<image width="{width}" height="{height}" href="data:image/png;base64,{view}"/>
+{scale_bar}<rect x="{margin}" y="{legend_y}" width="{legend_width}" height="14" fill="url(#colormap)" stroke="black"/>
+<text x="{margin}" y="{label_y}">{min}</text>
+<text x="{legend_end}" y="{label_y}" text-anchor="end">{max}</text>
+{caption}</svg>
+"#,
+            total_height = height + FOOTER_HEIGHT,
+            margin = MARGIN,
+            legend_y = height + MARGIN,
+            legend_width = LEGEND_WIDTH,
+            legend_end = MARGIN + LEGEND_WIDTH,
+            label_y = height + MARGIN + 30,
+            min = escape(&self.units.format(self.height_range[0])),
+            max = escape(&self.units.format(self.height_range[1])),
+        ))
+    }
+
+    pub fn write(&self, path: &std::path::Path) -> Result<(), ViewerError> {
+        std::fs::write(path, self.to_svg()?)?;
+        Ok(())
+    }
+}
+
+/// Splits `text` into lines of at most `length` characters at spaces
+fn wrap(text: &str, length: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split(' ') {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= length => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_owned()),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::units::Unit;
+
+    #[test]
+    fn figure_has_legend_scale_bar_and_caption() {
+        let view = RgbaImage {
+            width: 500,
+            height: 300,
+            pixels: vec![128; 500 * 300 * 4],
+            color_space: Default::default(),
+        };
+        let provenance = [
+            ProvenanceEntry {
+                timestamp: 0.0,
+                step: "Loaded part.tiff".to_owned(),
+            },
+            ProvenanceEntry {
+                timestamp: 1.0,
+                step: "SHA-256 abc".to_owned(),
+            },
+            ProvenanceEntry {
+                timestamp: 2.0,
+                step: "Row leveling (offset)".to_owned(),
+            },
+        ];
+        let figure = Figure {
+            dataset: "part <1>.tiff",
+            view: &view,
+            colormap: Colormap::Viridis,
+            height_range: [-0.5, 1.5],
+            units: Units::new(Unit::Micrometer),
+            // 100 µm are at most 85 of the 500 pixels
+            pixels_per_unit: 0.85,
+            z_scale: 2.0,
+            provenance: &provenance,
+        };
+        assert_eq!(
+            figure.caption(),
+            "part <1>.tiff. Heights in µm from -0.500 µm to 1.500 µm (viridis colormap), \
+             vertically exaggerated 2×. Scale bar at the center of the view. \
+             Processing: Row leveling (offset)."
+        );
+        assert_eq!(figure.scale_bar(), Some((100.0, 85.0)));
+        let svg = figure.to_svg().unwrap();
+        assert!(svg.contains("part &lt;1&gt;.tiff"));
+        assert!(svg.contains(">100 µm</text>"));
+        assert!(svg.contains("fill=\"url(#colormap)\""));
+        assert!(svg.contains("href=\"data:image/png;base64,"));
+    }
+
+    #[test]
+    fn captions_wrap_at_spaces() {
+        assert_eq!(wrap("aa bb cc dd", 5), ["aa bb", "cc dd"]);
+        assert_eq!(wrap("toolongword x", 5), ["toolongword", "x"]);
+    }
+}
@@ -0,0 +1,366 @@
+use glam::{Vec2, Vec3};
+use std::ops::Add;
+use web_time::{Duration, Instant};
+
+use crate::navigation::NavigationSettings;
+
+/// Rotation in degrees per second at full deflection
+const ROTATE_SPEED: f32 = 90.0;
+/// Pan in normalized device coordinates per second at full deflection and zoom 1
+const PAN_SPEED: f32 = 1.0;
+/// Zoom factor per second at full deflection
+const ZOOM_SPEED: f32 = 2.0;
+/// Longest time a single poll moves the view for, so that a stalled frame doesn't jump
+const MAX_STEP: Duration = Duration::from_millis(100);
+
+/// Deflection of the axes of a gamepad or a 6DoF device like a 3Dconnexion SpaceMouse, each
+/// from -1 to 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Axes {
+    /// Moves the surface right and up
+    pub pan: Vec2,
+    /// Positive zooms in
+    pub zoom: f32,
+    /// Turns the surface around the x, y and z axis of the screen, like a drag up, a drag to
+    /// the right and a roll
+    pub rotate: Vec3,
+}
+
+impl Axes {
+    /// Gamepad with the browser's standard layout: the left stick rotates, the right stick pans
+    /// and the right and left trigger zoom in and out
+    #[cfg_attr(
+        not(any(target_os = "linux", target_arch = "wasm32")),
+        allow(dead_code)
+    )]
+    pub fn from_standard_gamepad(axes: &[f32], buttons: &[f32]) -> Self {
+        let axis = |i: usize| axes.get(i).copied().unwrap_or_default();
+        let button = |i: usize| buttons.get(i).copied().unwrap_or_default();
+        Self {
+            pan: Vec2::new(axis(2), -axis(3)),
+            zoom: button(7) - button(6),
+            rotate: Vec3::new(axis(1), axis(0), 0.0),
+        }
+        .clamped()
+    }
+
+    /// 6DoF device reporting the translation along x, y and z followed by the rotation around
+    /// them, pushing the cap forward zooms in and lifting it pans up
+    #[cfg_attr(
+        not(any(target_os = "linux", target_arch = "wasm32")),
+        allow(dead_code)
+    )]
+    pub fn from_six_dof(axes: &[f32]) -> Self {
+        let axis = |i: usize| axes.get(i).copied().unwrap_or_default();
+        Self {
+            pan: Vec2::new(axis(0), -axis(2)),
+            zoom: -axis(1),
+            rotate: Vec3::new(axis(3), axis(5), -axis(4)),
+        }
+        .clamped()
+    }
+
+    fn clamped(self) -> Self {
+        Self {
+            pan: self.pan.clamp(Vec2::NEG_ONE, Vec2::ONE),
+            zoom: self.zoom.clamp(-1.0, 1.0),
+            rotate: self.rotate.clamp(Vec3::NEG_ONE, Vec3::ONE),
+        }
+    }
+
+    /// Ignores deflections within `dead_zone` and rescales the rest of the range to 0 to 1
+    pub fn without_dead_zone(self, dead_zone: f32) -> Self {
+        let filter = |value: f32| {
+            let magnitude = ((value.abs() - dead_zone) / (1.0 - dead_zone)).max(0.0);
+            magnitude * value.signum()
+        };
+        Self {
+            pan: self.pan.map(filter),
+            zoom: filter(self.zoom),
+            rotate: self.rotate.map(filter),
+        }
+    }
+
+    pub fn is_at_rest(&self) -> bool {
+        self.pan == Vec2::ZERO && self.zoom == 0.0 && self.rotate == Vec3::ZERO
+    }
+}
+
+impl Add for Axes {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            pan: self.pan + other.pan,
+            zoom: self.zoom + other.zoom,
+            rotate: self.rotate + other.rotate,
+        }
+        .clamped()
+    }
+}
+
+/// Change of the view for axes held over some time
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Motion {
+    /// Radians around the x, y and z axis of the screen, see `Transformation::rotate_by`
+    pub rotate: Vec3,
+    /// Normalized device coordinates at zoom 1
+    pub pan: Vec2,
+    /// Factor on the zoom of the projection, below 1 magnifies
+    pub zoom_factor: f32,
+}
+
+impl Motion {
+    /// `None` while all axes are within the dead zone
+    pub fn new(axes: Axes, settings: &NavigationSettings, elapsed: Duration) -> Option<Self> {
+        let axes = axes.without_dead_zone(settings.dead_zone);
+        if axes.is_at_rest() {
+            return None;
+        }
+        let time = elapsed.min(MAX_STEP).as_secs_f32() * settings.axis_speed;
+        let sign = |invert: bool| if invert { -1.0 } else { 1.0 };
+        let rotate = axes.rotate
+            * Vec3::new(sign(settings.invert_y), sign(settings.invert_x), 1.0)
+            * (ROTATE_SPEED.to_radians() * time);
+        Some(Self {
+            rotate,
+            pan: axes.pan * PAN_SPEED * time,
+            zoom_factor: ZOOM_SPEED.powf(-axes.zoom * sign(settings.invert_zoom) * time),
+        })
+    }
+}
+
+/// Gamepads and 6DoF devices used to navigate, read from the browser's Gamepad API or the
+/// Linux joystick devices. There is no backend for other platforms yet.
+#[derive(Default)]
+pub struct Gamepads {
+    enabled: bool,
+    last_poll: Option<Instant>,
+    #[cfg(target_os = "linux")]
+    joysticks: Vec<linux::Joystick>,
+}
+
+impl Gamepads {
+    /// How often the devices are read while all axes rest
+    pub const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+    /// How often the devices are read while the view moves
+    pub const ACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+    /// Starts or stops reading the devices. Natively the devices connected at this time are used.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.last_poll = None;
+        #[cfg(target_os = "linux")]
+        {
+            self.joysticks = if enabled {
+                linux::Joystick::open_all()
+            } else {
+                Vec::new()
+            };
+            if enabled && self.joysticks.is_empty() {
+                log::warn!("No gamepad or SpaceMouse found");
+            }
+        }
+        #[cfg(not(any(target_os = "linux", target_arch = "wasm32")))]
+        if enabled {
+            log::warn!("Gamepads are not supported on this platform");
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Reads the devices and returns how the view moves since the last poll
+    pub fn poll(&mut self, now: Instant, settings: &NavigationSettings) -> Option<Motion> {
+        if !self.enabled {
+            return None;
+        }
+        let elapsed = self
+            .last_poll
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        self.last_poll = Some(now);
+        Motion::new(self.read(), settings, elapsed)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn read(&self) -> Axes {
+        self.joysticks
+            .iter()
+            .map(linux::Joystick::axes)
+            .fold(Axes::default(), Add::add)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn read(&self) -> Axes {
+        use wasm_bindgen::JsCast;
+
+        let Some(gamepads) = web_sys::window().and_then(|w| w.navigator().get_gamepads().ok())
+        else {
+            return Axes::default();
+        };
+        let values = |array: js_sys::Array| -> Vec<f32> {
+            array
+                .iter()
+                .map(|value| match value.dyn_into::<web_sys::GamepadButton>() {
+                    Ok(button) => button.value() as f32,
+                    Err(value) => value.as_f64().unwrap_or_default() as f32,
+                })
+                .collect()
+        };
+        gamepads
+            .iter()
+            .filter_map(|gamepad| gamepad.dyn_into::<web_sys::Gamepad>().ok())
+            .filter(web_sys::Gamepad::connected)
+            .map(|gamepad| {
+                let axes = values(gamepad.axes());
+                if gamepad.mapping() == web_sys::GamepadMappingType::Standard {
+                    Axes::from_standard_gamepad(&axes, &values(gamepad.buttons()))
+                } else {
+                    Axes::from_six_dof(&axes)
+                }
+            })
+            .fold(Axes::default(), Add::add)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_arch = "wasm32")))]
+    fn read(&self) -> Axes {
+        Axes::default()
+    }
+}
+
+/// Joysticks of the Linux joydev driver, which also exposes 3Dconnexion devices
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        io::Read,
+        sync::{Arc, Mutex, Weak},
+    };
+
+    use super::Axes;
+
+    const MAX_DEVICES: usize = 8;
+    const MAX_AXES: usize = 8;
+    /// `JS_EVENT_AXIS` of linux/joystick.h, ORed with `JS_EVENT_INIT` for the initial state
+    const EVENT_AXIS: u8 = 0x02;
+    const EVENT_INIT: u8 = 0x80;
+
+    pub struct Joystick {
+        six_dof: bool,
+        axes: Arc<Mutex<[f32; MAX_AXES]>>,
+    }
+
+    impl Joystick {
+        pub fn open_all() -> Vec<Self> {
+            (0..MAX_DEVICES)
+                .filter_map(|i| {
+                    let file = std::fs::File::open(format!("/dev/input/js{}", i)).ok()?;
+                    let name =
+                        std::fs::read_to_string(format!("/sys/class/input/js{}/device/name", i))
+                            .unwrap_or_default();
+                    log::info!("Navigating with {}", name.trim());
+                    let joystick = Self {
+                        six_dof: ["3Dconnexion", "SpaceMouse", "SpaceNavigator"]
+                            .iter()
+                            .any(|vendor| name.contains(vendor)),
+                        axes: Arc::new(Mutex::new([0.0; MAX_AXES])),
+                    };
+                    let axes = Arc::downgrade(&joystick.axes);
+                    std::thread::Builder::new()
+                        .name(format!("joystick-{}", i))
+                        .spawn(move || read_events(file, axes))
+                        .map_err(|e| log::error!("Failed to read joystick: {}", e))
+                        .ok()?;
+                    Some(joystick)
+                })
+                .collect()
+        }
+
+        /// Xbox-like gamepads report the left stick, left trigger, right stick and right
+        /// trigger, the triggers rest at -1
+        pub fn axes(&self) -> Axes {
+            let Ok(axes) = self.axes.lock() else {
+                return Axes::default();
+            };
+            if self.six_dof {
+                Axes::from_six_dof(&axes[..6])
+            } else {
+                let trigger = |value: f32| (value + 1.0) / 2.0;
+                Axes::from_standard_gamepad(
+                    &[axes[0], axes[1], axes[3], axes[4]],
+                    &[
+                        0.0,
+                        0.0,
+                        0.0,
+                        0.0,
+                        0.0,
+                        0.0,
+                        trigger(axes[2]),
+                        trigger(axes[5]),
+                    ],
+                )
+            }
+        }
+    }
+
+    /// Stores the axis events until the device disconnects or the joystick is dropped
+    fn read_events(mut file: std::fs::File, axes: Weak<Mutex<[f32; MAX_AXES]>>) {
+        // `struct js_event`: u32 timestamp, i16 value, u8 type, u8 number
+        let mut event = [0u8; 8];
+        while file.read_exact(&mut event).is_ok() {
+            let Some(axes) = axes.upgrade() else {
+                return;
+            };
+            let value = f32::from(i16::from_ne_bytes([event[4], event[5]])) / f32::from(i16::MAX);
+            let number = usize::from(event[7]);
+            if event[6] & !EVENT_INIT == EVENT_AXIS
+                && number < MAX_AXES
+                && let Ok(mut axes) = axes.lock()
+            {
+                axes[number] = value;
+            }
+        }
+        log::info!("Joystick disconnected");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dead_zone_is_ignored() {
+        let axes = Axes {
+            pan: Vec2::new(0.05, -0.55),
+            zoom: 1.0,
+            rotate: Vec3::ZERO,
+        }
+        .without_dead_zone(0.1);
+        assert_eq!(axes.pan.x, 0.0);
+        assert!((axes.pan.y + 0.5).abs() < 1e-6);
+        assert_eq!(axes.zoom, 1.0);
+        let settings = NavigationSettings::default();
+        let resting = Axes::from_six_dof(&[0.05, -0.02, 0.0, 0.01, 0.0, 0.0]);
+        assert_eq!(
+            Motion::new(resting, &settings, Duration::from_millis(16)),
+            None
+        );
+    }
+
+    #[test]
+    fn motion_scales_with_time() {
+        let settings: NavigationSettings = "axes=2,dead-zone=0".parse().unwrap();
+        let mut buttons = [0.0; 8];
+        buttons[7] = 1.0;
+        let axes = Axes::from_standard_gamepad(&[1.0, 0.0, 0.0, 0.0], &buttons);
+        let motion = Motion::new(axes, &settings, Duration::from_millis(50)).unwrap();
+        assert!((motion.rotate.y - 9.0_f32.to_radians()).abs() < 1e-6);
+        assert!(motion.zoom_factor < 1.0);
+        // A stalled frame moves the view by `MAX_STEP` only
+        let long = Motion::new(axes, &settings, Duration::from_secs(5)).unwrap();
+        assert!((long.rotate.y - 18.0_f32.to_radians()).abs() < 1e-6);
+        let inverted: NavigationSettings = "dead-zone=0,invert-zoom".parse().unwrap();
+        let motion = Motion::new(axes, &inverted, Duration::from_millis(50)).unwrap();
+        assert!(motion.zoom_factor > 1.0);
+    }
+}
@@ -0,0 +1,345 @@
+// Surfaces with known properties for demos, benchmarks and checking the measurements of the
+// viewer against ground truth: a rough surface generated with Sq 0.5 must measure Sq 0.5, a
+// step of height 2 must measure 2. Heights are in data units, lengths in pixels.
+
+use std::{f32::consts::TAU, fmt, str::FromStr};
+
+use anyhow::anyhow;
+
+use crate::image::{Image, ImageData, ImageSize};
+
+/// Kind of generated surface, see `Image::generate`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeneratorKind {
+    /// Sinusoid of amplitude `height` with a wavelength of `period` in the direction `angle`
+    Grating,
+    /// Terraces alternating between 0 and `height`, `period` pixels per pair, like a step
+    /// height standard or the steps stitching leaves
+    Steps,
+    /// Gaussian random roughness with an RMS height (Sq) of `height` and a correlation
+    /// length of `period`
+    Rough,
+    /// Plane rising by `slope` per pixel in the direction `angle`, mean height 0
+    Plane,
+}
+
+impl GeneratorKind {
+    pub const ALL: [Self; 4] = [Self::Grating, Self::Steps, Self::Rough, Self::Plane];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Grating => "grating",
+            Self::Steps => "steps",
+            Self::Rough => "rough",
+            Self::Plane => "plane",
+        }
+    }
+}
+
+impl FromStr for GeneratorKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow!("Unsupported surface kind: {}", s))
+    }
+}
+
+/// Parameters of generated surfaces, each kind uses some of them. Written as comma separated
+/// entries like "height=0.5,period=8", omitted entries keep their default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeneratorParams {
+    /// Amplitude of gratings, height of steps and Sq of rough surfaces
+    pub height: f32,
+    /// Wavelength of gratings, width of a pair of steps and correlation length of rough
+    /// surfaces in pixels
+    pub period: f32,
+    /// Direction of gratings, steps and planes in degrees from the x axis
+    pub angle: f32,
+    /// Height change per pixel of planes
+    pub slope: f32,
+    /// Rough surfaces of the same seed are the same
+    pub seed: u64,
+}
+
+impl Default for GeneratorParams {
+    fn default() -> Self {
+        Self {
+            height: 1.0,
+            period: 32.0,
+            angle: 0.0,
+            slope: 0.01,
+            seed: 0,
+        }
+    }
+}
+
+impl FromStr for GeneratorParams {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = Self::default();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Unsupported surface parameter: {}", entry))?;
+            let (name, value) = (name.trim(), value.trim());
+            if name == "seed" {
+                params.seed = value
+                    .parse()
+                    .map_err(|_| anyhow!("Unsupported seed: {}", value))?;
+                continue;
+            }
+            let value = value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| anyhow!("Unsupported {} value: {}", name, value))?;
+            match name {
+                "height" => params.height = value,
+                "period" if value > 0.0 => params.period = value,
+                "period" => return Err(anyhow!("period must be positive")),
+                "angle" => params.angle = value,
+                "slope" => params.slope = value,
+                _ => return Err(anyhow!("Unsupported surface parameter: {}", entry)),
+            }
+        }
+        Ok(params)
+    }
+}
+
+impl fmt::Display for GeneratorParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "height={},period={},angle={},slope={},seed={}",
+            self.height, self.period, self.angle, self.slope, self.seed
+        )
+    }
+}
+
+/// Surface to generate, written as "<kind>[:<params>]", e.g. "rough:height=0.5,period=8"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Generator {
+    pub kind: GeneratorKind,
+    pub params: GeneratorParams,
+}
+
+impl Generator {
+    /// Size of surfaces generated for the command line
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub const DEFAULT_SIZE: u32 = 1024;
+
+    pub fn generate(&self, size: ImageSize) -> Image<f32> {
+        Image::generate(self.kind, size, self.params)
+    }
+}
+
+impl FromStr for Generator {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').unwrap_or((s, ""));
+        Ok(Self {
+            kind: kind.parse()?,
+            params: params.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind.name(), self.params)
+    }
+}
+
+impl Image<f32> {
+    /// Surface of `kind` with known properties, the same for the same arguments
+    pub fn generate(kind: GeneratorKind, size: ImageSize, params: GeneratorParams) -> Self {
+        let (width, height) = (size.width.get() as usize, size.height.get() as usize);
+        let (sin, cos) = params.angle.to_radians().sin_cos();
+        // Distance of a pixel along the direction `angle` from the center
+        let along = |index: usize| {
+            let x = (index % width) as f32 - (width - 1) as f32 / 2.0;
+            let y = (index / width) as f32 - (height - 1) as f32 / 2.0;
+            x * cos + y * sin
+        };
+        let data = match kind {
+            GeneratorKind::Grating => (0..width * height)
+                .map(|index| params.height * (TAU * along(index) / params.period).sin())
+                .collect(),
+            GeneratorKind::Steps => (0..width * height)
+                .map(|index| {
+                    let phase = (along(index) / params.period).rem_euclid(1.0);
+                    if phase < 0.5 { 0.0 } else { params.height }
+                })
+                .collect(),
+            GeneratorKind::Rough => rough(width, height, &params),
+            GeneratorKind::Plane => (0..width * height)
+                .map(|index| params.slope * along(index))
+                .collect(),
+        };
+        Self {
+            size,
+            data: ImageData::Owned(data),
+        }
+    }
+}
+
+/// White noise smoothed with a Gaussian, which gives a Gaussian autocorrelation falling to 1/e
+/// at the correlation length, scaled to the exact Sq. Wraps around at the edges, so that the
+/// roughness is the same everywhere.
+fn rough(width: usize, height: usize, params: &GeneratorParams) -> Vec<f32> {
+    let mut random = SplitMix64(params.seed);
+    let mut data: Vec<f32> = (0..width * height)
+        .map(|_| {
+            // Box-Muller transform of two uniform numbers in (0, 1]
+            let u = 1.0 - random.next_f64();
+            let v = random.next_f64();
+            ((-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()) as f32
+        })
+        .collect();
+    let sigma = params.period / 2.0;
+    let radius = (3.0 * sigma).ceil() as isize;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let blur = |data: &[f32], stride: usize, len: usize, lines: usize, step: usize| {
+        let mut out = vec![0.0; data.len()];
+        for line in 0..lines {
+            for i in 0..len {
+                out[line * step + i * stride] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| {
+                        let j = (i as isize + k as isize - radius).rem_euclid(len as isize);
+                        weight * data[line * step + j as usize * stride]
+                    })
+                    .sum();
+            }
+        }
+        out
+    };
+    data = blur(&data, 1, width, height, width);
+    data = blur(&data, width, height, width, 1);
+    let mean = data.iter().map(|&z| z as f64).sum::<f64>() / data.len() as f64;
+    let rms =
+        (data.iter().map(|&z| (z as f64 - mean).powi(2)).sum::<f64>() / data.len() as f64).sqrt();
+    let scale = if rms > 0.0 {
+        params.height as f64 / rms
+    } else {
+        0.0
+    };
+    data.iter()
+        .map(|&z| ((z as f64 - mean) * scale) as f32)
+        .collect()
+}
+
+/// Small generator of pseudo-random numbers, so that a seed gives the same surface everywhere
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn size(width: u32, height: u32) -> ImageSize {
+        ImageSize::new(width, height).unwrap()
+    }
+
+    fn sq(data: &[f32]) -> f32 {
+        let mean = data.iter().sum::<f32>() / data.len() as f32;
+        (data.iter().map(|z| (z - mean).powi(2)).sum::<f32>() / data.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn surfaces_have_their_ground_truth() {
+        let params = GeneratorParams {
+            height: 0.5,
+            period: 8.0,
+            ..Default::default()
+        };
+        let rough = Image::generate(GeneratorKind::Rough, size(128, 96), params);
+        assert!((sq(&rough.data) - 0.5).abs() < 1e-4);
+        // Neighbors within the correlation length are alike, distant pixels are not
+        let correlation = |shift: usize| {
+            let pairs = rough.data.len() - shift;
+            (0..pairs)
+                .map(|i| rough.data[i] * rough.data[i + shift])
+                .sum::<f32>()
+                / pairs as f32
+                / 0.25
+        };
+        assert!(correlation(1) > 0.9);
+        assert!(
+            (correlation(8) - (-1f32).exp()).abs() < 0.15,
+            "{}",
+            correlation(8)
+        );
+        assert!(correlation(32).abs() < 0.15);
+        let same = Image::generate(GeneratorKind::Rough, size(128, 96), params);
+        assert_eq!(rough.data[..], same.data[..]);
+        let other = GeneratorParams { seed: 1, ..params };
+        let other = Image::generate(GeneratorKind::Rough, size(128, 96), other);
+        assert_ne!(rough.data[..], other.data[..]);
+
+        let steps = Image::generate(GeneratorKind::Steps, size(64, 4), params);
+        assert!(steps.data.iter().all(|&z| z == 0.0 || z == 0.5));
+        assert_eq!(steps.data[..8].iter().filter(|&&z| z == 0.5).count(), 4);
+
+        let grating = Image::generate(GeneratorKind::Grating, size(64, 64), params);
+        assert!((sq(&grating.data) - 0.5 / 2f32.sqrt()).abs() < 1e-3);
+
+        let plane = GeneratorParams {
+            slope: 0.25,
+            angle: 90.0,
+            ..params
+        };
+        let plane = Image::generate(GeneratorKind::Plane, size(3, 5), plane);
+        assert!((plane.data[3] - plane.data[0] - 0.25).abs() < 1e-6);
+        assert!((plane.data[1] - plane.data[0]).abs() < 1e-6);
+        assert!(plane.data.iter().sum::<f32>().abs() < 1e-5);
+    }
+
+    #[test]
+    fn generators_are_parsed_and_written() {
+        let generator: Generator = "Rough:height=0.5, period=8,seed=7".parse().unwrap();
+        assert_eq!(generator.kind, GeneratorKind::Rough);
+        assert_eq!(generator.params.height, 0.5);
+        assert_eq!(generator.params.seed, 7);
+        assert_eq!(generator.params.angle, 0.0);
+        assert_eq!(
+            generator.to_string().parse::<Generator>().unwrap(),
+            generator
+        );
+        assert_eq!(
+            "plane".parse::<Generator>().unwrap().params,
+            GeneratorParams::default()
+        );
+        for invalid in [
+            "wave",
+            "rough:period=0",
+            "rough:height",
+            "rough:sq=1",
+            "rough:seed=-1",
+        ] {
+            assert!(invalid.parse::<Generator>().is_err(), "{}", invalid);
+        }
+    }
+}
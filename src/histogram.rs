@@ -0,0 +1,132 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Layer of the dataset that is shown with its own display range
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Height,
+    Amplitude,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 2] = [Layer::Height, Layer::Amplitude];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Height => "height",
+            Layer::Amplitude => "amplitude",
+        }
+    }
+}
+
+impl FromStr for Layer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Layer::ALL
+            .into_iter()
+            .find(|layer| layer.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported layer: {}", s))
+    }
+}
+
+/// Distribution of the values of a layer together with its display range, drawn as a "levels"
+/// widget whose handles set the display range
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Lower edge of the first bin
+    pub min: f32,
+    /// Upper edge of the last bin
+    pub max: f32,
+    /// Value mapped to the lower end of the colormap
+    pub display_min: f32,
+    /// Value mapped to the upper end of the colormap
+    pub display_max: f32,
+    counts: Vec<u32>,
+}
+
+impl Histogram {
+    pub const DEFAULT_BINS: usize = 256;
+
+    /// Counts the finite `values` in `bins` equally wide bins between their minimum and maximum,
+    /// `None` if there is no finite value
+    pub fn new(values: impl Iterator<Item = f32> + Clone, bins: usize) -> Option<Self> {
+        let (min, max) = values.clone().filter(|value| value.is_finite()).fold(
+            None,
+            |range: Option<(f32, f32)>, value| {
+                Some(range.map_or((value, value), |(min, max)| {
+                    (min.min(value), max.max(value))
+                }))
+            },
+        )?;
+        let bins = bins.max(1);
+        let mut counts = vec![0; bins];
+        let scale = if max > min {
+            bins as f32 / (max - min)
+        } else {
+            0.0
+        };
+        for value in values.filter(|value| value.is_finite()) {
+            let bin = (((value - min) * scale) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+        Some(Self {
+            min,
+            max,
+            display_min: min,
+            display_max: max,
+            counts,
+        })
+    }
+
+    pub fn with_display_range(self, display_min: f32, display_max: f32) -> Self {
+        Self {
+            display_min,
+            display_max,
+            ..self
+        }
+    }
+
+    /// Converts the value axis, e.g. from the data unit to the display unit
+    pub fn map_values(self, f: impl Fn(f32) -> f32) -> Self {
+        Self {
+            min: f(self.min),
+            max: f(self.max),
+            display_min: f(self.display_min),
+            display_max: f(self.display_max),
+            ..self
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl Histogram {
+    /// Number of values per bin, from `min` to `max`
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter))]
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn counts(&self) -> Vec<u32> {
+        self.counts.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_finite_values() {
+        let values = [0.0, 1.0, 2.5, 4.0, f32::NAN, 4.0];
+        let histogram = Histogram::new(values.into_iter(), 4).unwrap();
+        assert_eq!((histogram.min, histogram.max), (0.0, 4.0));
+        assert_eq!(histogram.counts(), vec![1, 1, 1, 2]);
+        assert!(Histogram::new([f32::NAN].into_iter(), 4).is_none());
+        assert_eq!(
+            Histogram::new([3.0, 3.0].into_iter(), 4).unwrap().counts(),
+            vec![2, 0, 0, 0]
+        );
+        assert_eq!("Amplitude".parse::<Layer>().unwrap(), Layer::Amplitude);
+    }
+}
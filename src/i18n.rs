@@ -0,0 +1,264 @@
+use anyhow::anyhow;
+#[cfg(feature = "i18n")]
+use fluent_bundle::{FluentBundle, FluentResource};
+use std::str::FromStr;
+#[cfg(feature = "i18n")]
+use unic_langid::LanguageIdentifier;
+
+/// Values of the placeables of a message
+#[cfg(feature = "i18n")]
+pub use fluent_bundle::FluentArgs as MessageArgs;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    Japanese,
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::English, Language::German, Language::Japanese];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+            Language::Japanese => "ja",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+            Language::Japanese => "日本語",
+        }
+    }
+
+    /// Returns the language following this one, used to cycle through languages with a key
+    pub fn next(&self) -> Language {
+        let index = Self::ALL.iter().position(|l| l == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Detects the preferred language of the user, falling back to English
+    pub fn from_environment() -> Language {
+        #[cfg(not(target_arch = "wasm32"))]
+        let locale = std::env::var("LC_ALL")
+            .or_else(|_| std::env::var("LANG"))
+            .ok();
+        #[cfg(target_arch = "wasm32")]
+        let locale = web_sys::window().and_then(|w| w.navigator().language());
+        locale
+            .and_then(|locale| Language::from_str(&locale).ok())
+            .unwrap_or_default()
+    }
+
+    fn resource(&self) -> &'static str {
+        match self {
+            Language::English => include_str!("locales/en.ftl"),
+            Language::German => include_str!("locales/de.ftl"),
+            Language::Japanese => include_str!("locales/ja.ftl"),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    /// Parses language tags like "de", "de-CH" or "ja_JP.UTF-8"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let primary = s
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        Language::ALL
+            .into_iter()
+            .find(|l| l.code() == primary)
+            .ok_or(anyhow!("Unsupported language: {}", s))
+    }
+}
+
+/// Looks up on-screen text for the selected language.
+/// Messages missing in a translation fall back to English.
+#[cfg(feature = "i18n")]
+pub struct Localizer {
+    language: Language,
+    bundle: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+impl Default for Localizer {
+    fn default() -> Self {
+        Self::new(Language::default())
+    }
+}
+
+#[cfg(feature = "i18n")]
+impl Localizer {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            bundle: create_bundle(language),
+            fallback: create_bundle(Language::English),
+        }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        if self.language != language {
+            self.language = language;
+            self.bundle = create_bundle(language);
+        }
+    }
+
+    pub fn text(&self, id: &str) -> String {
+        self.format(id, None)
+    }
+
+    pub fn format(&self, id: &str, args: Option<&MessageArgs>) -> String {
+        for bundle in [&self.bundle, &self.fallback] {
+            if let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) {
+                let mut errors = vec![];
+                let text = bundle.format_pattern(pattern, args, &mut errors);
+                if !errors.is_empty() {
+                    log::warn!("Failed to format message '{}': {:?}", id, errors);
+                }
+                return text.into_owned();
+            }
+        }
+        log::warn!("Missing translation for '{}'", id);
+        id.to_string()
+    }
+}
+
+#[cfg(feature = "i18n")]
+fn create_bundle(language: Language) -> FluentBundle<FluentResource> {
+    let identifier: LanguageIdentifier = language
+        .code()
+        .parse()
+        .expect("Invalid built-in language identifier");
+    let resource = FluentResource::try_new(language.resource().to_string())
+        .expect("Invalid built-in translation resource");
+    let mut bundle = FluentBundle::new(vec![identifier]);
+    // Unicode isolation marks show up as garbage in window titles
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("Duplicate message in built-in translation resource");
+    bundle
+}
+
+/// Values of the placeables of a message
+#[cfg(not(feature = "i18n"))]
+#[derive(Default)]
+pub struct MessageArgs(Vec<(&'static str, String)>);
+
+#[cfg(not(feature = "i18n"))]
+impl MessageArgs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, key: &'static str, value: impl ToString) {
+        self.0.push((key, value.to_string()));
+    }
+}
+
+/// Shows the English text of the messages in builds without Fluent, which only substitutes
+/// `{ $name }` placeables. The selected language is kept for the settings that show it.
+#[cfg(not(feature = "i18n"))]
+pub struct Localizer {
+    language: Language,
+}
+
+#[cfg(not(feature = "i18n"))]
+impl Localizer {
+    pub fn new(language: Language) -> Self {
+        Self { language }
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    pub fn set_language(&mut self, language: Language) {
+        self.language = language;
+    }
+
+    pub fn text(&self, id: &str) -> String {
+        self.format(id, None)
+    }
+
+    pub fn format(&self, id: &str, args: Option<&MessageArgs>) -> String {
+        let Some(text) = Language::English.resource().lines().find_map(|line| {
+            line.split_once(" = ")
+                .filter(|(message, _)| *message == id)
+                .map(|(_, text)| text)
+        }) else {
+            log::warn!("Missing translation for '{}'", id);
+            return id.to_string();
+        };
+        args.map_or(&[][..], |args| &args.0)
+            .iter()
+            .fold(text.to_owned(), |text, (key, value)| {
+                text.replace(&format!("{{ ${} }}", key), value)
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[cfg(feature = "i18n")]
+    use crate::i18n::create_bundle;
+    use crate::i18n::{Language, Localizer, MessageArgs};
+    use std::str::FromStr;
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn test_all_languages_translate_all_messages() {
+        let english_ids = Language::English
+            .resource()
+            .lines()
+            .filter_map(|line| line.split_once(" = ").map(|(id, _)| id));
+        for language in Language::ALL {
+            let bundle = create_bundle(language);
+            for id in english_ids.clone() {
+                assert!(bundle.has_message(id), "{:?} misses '{}'", language, id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_language_tags() {
+        assert_eq!(Language::from_str("de-CH").unwrap(), Language::German);
+        assert_eq!(
+            Language::from_str("ja_JP.UTF-8").unwrap(),
+            Language::Japanese
+        );
+        assert_eq!(Language::from_str("EN").unwrap(), Language::English);
+        assert!(Language::from_str("fr").is_err());
+    }
+
+    #[test]
+    fn test_format_substitutes_arguments() {
+        let mut args = MessageArgs::new();
+        args.set("percent", 42.0);
+        assert_eq!(
+            Localizer::new(Language::English).format("title-loading-percent", Some(&args)),
+            "Loading 42%"
+        );
+        let mut args = MessageArgs::new();
+        args.set("name", "Despike");
+        args.set("error", "cancelled");
+        assert_eq!(
+            Localizer::new(Language::English).format("notify-failed", Some(&args)),
+            "Despike failed: cancelled"
+        );
+    }
+}
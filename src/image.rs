@@ -1,35 +1,138 @@
-use anyhow::anyhow;
-use bytemuck::NoUninit;
-use log::info;
+use bytemuck::{NoUninit, Pod};
 #[cfg(not(target_arch = "wasm32"))]
-use std::fs::File;
+use log::info;
 use std::{num::NonZeroU32, ops::Range};
+#[cfg(feature = "tiff")]
 use tiff::decoder::{Decoder, DecodingResult};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::color_space::{D65_WHITE, DISPLAY_P3_PRIMARIES};
+use crate::{
+    color_space::OutputColorSpace, compression, dataset_info::DatasetInfo, error::ViewerError,
+    parallel, raw_image, simd, units::Unit,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    data_source::DataSource,
+    download::{self, DownloadOptions, DownloadProgress},
+    provenance,
+    retention::MappedFile,
+};
 
 pub struct Image<T> {
     pub size: ImageSize,
-    pub data: Vec<T>,
+    pub data: ImageData<T>,
+}
+
+/// Pixel values of an image, either in memory or in a file mapped into memory
+pub enum ImageData<T> {
+    Owned(Vec<T>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped(MappedFile),
+}
+
+impl<T: Pod> std::ops::Deref for ImageData<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match self {
+            Self::Owned(data) => data,
+            // Mappings start at a page boundary, so they are aligned for any pixel type
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Mapped(file) => bytemuck::cast_slice(file.bytes()),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for ImageData<T> {
+    fn from(data: Vec<T>) -> Self {
+        Self::Owned(data)
+    }
+}
+
+impl<T> FromIterator<T> for ImageData<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::Owned(iter.into_iter().collect())
+    }
 }
 
 impl<T> Image<T>
 where
-    T: PartialOrd + Copy + NoUninit,
+    T: PartialOrd + Pod,
 {
+    /// Creates an image after checking that `data` holds exactly one value per pixel
+    pub fn new(size: ImageSize, data: Vec<T>) -> Result<Self, ViewerError> {
+        let expected = size.pixel_count();
+        if data.len() != expected {
+            return Err(ViewerError::InvalidImage(format!(
+                "expected {} values for {}x{} pixels but got {}",
+                expected,
+                size.width,
+                size.height,
+                data.len()
+            )));
+        }
+        Ok(Self {
+            size,
+            data: data.into(),
+        })
+    }
+
+    /// Creates an image with the value of `f` at every pixel, row by row
+    pub fn from_fn(size: ImageSize, mut f: impl FnMut(u32, u32) -> T) -> Self {
+        let (width, height) = (size.width.get(), size.height.get());
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+        Self { size, data }
+    }
+
+    /// Copy of the image with its pixels in a temporary file mapped into memory
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_mapped(&self) -> std::io::Result<Self> {
+        Ok(Self {
+            size: self.size.clone(),
+            data: ImageData::Mapped(MappedFile::new(bytemuck::cast_slice(&self.data))?),
+        })
+    }
+
+    /// Whether the pixels are in a mapped file instead of memory
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.data, ImageData::Owned(_))
+    }
+
     pub fn outlier_removed_data(&self, lower_percentile: f32, upper_percentile: f32) -> Vec<T>
     where
-        T: num_traits::Float,
+        T: num_traits::Float + Send + Sync,
     {
-        let mut sorted_data = self.data.clone();
-        sorted_data.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        let len = sorted_data.len();
-        let lower_index = ((lower_percentile / 100.0) * len as f32).round() as usize;
-        let upper_index = ((upper_percentile / 100.0) * len as f32).round() as usize;
-        let min_value = sorted_data[lower_index];
-        let max_value = sorted_data[upper_index];
-        self.data
+        // Missing (non-finite) pixels are ignored for the percentiles
+        let mut finite_data: Vec<T> = self
+            .data
             .iter()
-            .map(|&pixel| pixel.clamp(min_value, max_value))
-            .collect()
+            .copied()
+            .filter(|v| v.is_finite())
+            .collect();
+        if finite_data.is_empty() {
+            return self.data.to_vec();
+        }
+        let len = finite_data.len();
+        let lower_index = ((lower_percentile / 100.0) * len as f32).round() as usize;
+        let upper_index = (((upper_percentile / 100.0) * len as f32).round() as usize).min(len - 1);
+        // Selecting the two values takes linear time, sorting all of them doesn't
+        let mut percentile = |index| {
+            *finite_data
+                .select_nth_unstable_by(index, |a, b| {
+                    a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .1
+        };
+        let max_value = percentile(upper_index);
+        let min_value = percentile(lower_index);
+        parallel::map(&self.data, |&pixel| pixel.clamp(min_value, max_value))
     }
 
     pub fn get_pixel(&self, x: u32, y: u32) -> T
@@ -39,181 +142,515 @@ where
         self.data[(y * self.size.width.get() + x) as usize]
     }
 
-    pub fn scaled_data(&self, new_min: T, new_max: T) -> Vec<T>
+    pub fn resize(&self, new_size: &ImageSize, resampling: Resampling) -> Image<T>
     where
-        T: num_traits::Float
-            + std::ops::Sub<Output = T>
-            + std::ops::Add<Output = T>
-            + std::ops::Mul<Output = T>
-            + std::ops::Div<Output = T>,
+        T: num_traits::Float + Send + Sync,
     {
-        let value_range = value_range(&self.data);
-        let old_min = value_range.0.start;
-        let old_max = value_range.0.end;
-        let scale = (new_max - new_min) / (old_max - old_min);
-        self.data
-            .iter()
-            .map(|&value| new_min + (value - old_min) * scale)
-            .collect()
-    }
+        let width = self.size.width.get() as usize;
+        let height = self.size.height.get() as usize;
+        let mut new_data = vec![T::zero(); new_size.pixel_count()];
+        let x_ratio = width as f32 / new_size.width.get() as f32;
+        let y_ratio = height as f32 / new_size.height.get() as f32;
+        let data: &[T] = &self.data;
+        // The weights are separable, so the ones of the columns are the same for every row
+        let column_weights: Vec<_> = (0..new_size.width.get() as usize)
+            .map(|i| resampling.weights(i, x_ratio, width))
+            .collect();
 
-    pub fn resize(&self, new_size: &ImageSize) -> Image<T>
-    where
-        T: num_traits::Float,
-    {
-        let mut new_data = vec![T::zero(); (new_size.width.get() * new_size.height.get()) as usize];
-        let x_ratio = self.size.width.get() as f32 / new_size.width.get() as f32;
-        let y_ratio = self.size.height.get() as f32 / new_size.height.get() as f32;
-
-        for j in 0..new_size.height.get() {
-            for i in 0..new_size.width.get() {
-                let px = (i as f32 * x_ratio).floor() as u32;
-                let py = (j as f32 * y_ratio).floor() as u32;
-                new_data[(j * new_size.width.get() + i) as usize] =
-                    self.data[(py * self.size.width.get() + px) as usize];
+        parallel::for_each_chunk_mut(&mut new_data, new_size.width.get() as usize, |j, row| {
+            let row_weights = resampling.weights(j, y_ratio, height);
+            for (i, pixel) in row.iter_mut().enumerate() {
+                let weighted = samples(data, width, &row_weights, &column_weights[i]);
+                *pixel = match resampling {
+                    // The negative lobes make a mean of fewer samples unstable, missing samples
+                    // fall back to bilinear interpolation
+                    Resampling::Bicubic => {
+                        let value = weighted.fold(T::zero(), |sum, (value, weight)| {
+                            sum + value * T::from(weight).unwrap_or_else(T::zero)
+                        });
+                        if value.is_nan() {
+                            weighted_mean(samples(
+                                data,
+                                width,
+                                &Resampling::Bilinear.weights(j, y_ratio, height),
+                                &Resampling::Bilinear.weights(i, x_ratio, width),
+                            ))
+                        } else {
+                            value
+                        }
+                    }
+                    _ => weighted_mean(weighted),
+                };
             }
-        }
+        });
 
         Image {
             size: new_size.clone(),
-            data: new_data,
+            data: new_data.into(),
         }
     }
 }
 
+/// How `Image::resize` computes the new pixels from the old ones. Missing (NaN) pixels don't
+/// contribute, a new pixel is only missing if all pixels it is computed from are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resampling {
+    /// The old pixel under the corner of the new one, blocky and aliased when downsampling
+    #[allow(dead_code)]
+    Nearest,
+    /// Interpolates the 2 × 2 old pixels around the center of the new one
+    #[allow(dead_code)]
+    Bilinear,
+    /// Mean of the old pixels the new one covers, weighted by the covered area. Mip levels use it
+    /// because it doesn't alias when downsampling.
+    Area,
+    /// Catmull-Rom interpolation of the 4 × 4 old pixels around the center of the new one,
+    /// sharper than bilinear but it may overshoot at steps
+    #[allow(dead_code)]
+    Bicubic,
+}
+
+impl Resampling {
+    /// Old pixels along one axis with their weights for the new pixel `index`, `ratio` is the
+    /// old size over the new size
+    fn weights(self, index: usize, ratio: f32, len: usize) -> Vec<(usize, f32)> {
+        let last = len as isize - 1;
+        let clamped = |position: isize| position.clamp(0, last) as usize;
+        // Position of the center of the new pixel in old pixels
+        let center = ((index as f32 + 0.5) * ratio - 0.5).clamp(0.0, last as f32);
+        let base = center.floor();
+        let t = center - base;
+        let base = base as isize;
+        match self {
+            Self::Nearest => vec![(clamped((index as f32 * ratio).floor() as isize), 1.0)],
+            Self::Bilinear => vec![(clamped(base), 1.0 - t), (clamped(base + 1), t)],
+            Self::Bicubic => {
+                let (t2, t3) = (t * t, t * t * t);
+                vec![
+                    (clamped(base - 1), (-t3 + 2.0 * t2 - t) / 2.0),
+                    (clamped(base), (3.0 * t3 - 5.0 * t2 + 2.0) / 2.0),
+                    (clamped(base + 1), (-3.0 * t3 + 4.0 * t2 + t) / 2.0),
+                    (clamped(base + 2), (t3 - t2) / 2.0),
+                ]
+            }
+            Self::Area => {
+                let (start, end) = (index as f32 * ratio, (index + 1) as f32 * ratio);
+                (start.floor() as usize..(end.ceil() as usize).min(len))
+                    .map(|position| {
+                        let covered = end.min(position as f32 + 1.0) - start.max(position as f32);
+                        (position, covered / ratio)
+                    })
+                    .filter(|&(_, weight)| weight > 0.0)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Old pixels with the product of their row and column weights
+fn samples<'a, T: Copy>(
+    data: &'a [T],
+    width: usize,
+    row_weights: &'a [(usize, f32)],
+    column_weights: &'a [(usize, f32)],
+) -> impl Iterator<Item = (T, f32)> + 'a {
+    row_weights.iter().flat_map(move |&(y, weight_y)| {
+        column_weights
+            .iter()
+            .map(move |&(x, weight_x)| (data[y * width + x], weight_y * weight_x))
+    })
+}
+
+/// Mean of the samples that aren't NaN, NaN if all are
+fn weighted_mean<T: num_traits::Float>(samples: impl Iterator<Item = (T, f32)>) -> T {
+    let (sum, total) = samples.filter(|(value, _)| !value.is_nan()).fold(
+        (T::zero(), 0.0),
+        |(sum, total), (value, weight)| {
+            (
+                sum + value * T::from(weight).unwrap_or_else(T::zero),
+                total + weight,
+            )
+        },
+    );
+    if total > 0.0 {
+        sum / T::from(total).unwrap_or_else(T::one)
+    } else {
+        T::nan()
+    }
+}
+
+impl Image<f32> {
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn scaled_data(&self, new_min: f32, new_max: f32) -> Result<Vec<f32>, ViewerError> {
+        let value_range = value_range(&self.data)?;
+        let old_min = value_range.0.start;
+        let old_max = value_range.0.end;
+        let scale = (new_max - new_min) / (old_max - old_min);
+        Ok(parallel::map(&self.data, |&value| {
+            new_min + (value - old_min) * scale
+        }))
+    }
+
+    /// Built-in surface for starting without a file: a sinc ripple in the center of rolling
+    /// terrain, heights roughly from -0.5 to 1
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn demo(size: ImageSize) -> Self {
+        let (width, height) = (size.width.get(), size.height.get());
+        // Coordinates from -1 to 1 along the longer side
+        let scale = 2.0 / width.max(height) as f32;
+        Self::from_fn(size, |x, y| {
+            let u = (x as f32 - width as f32 / 2.0) * scale;
+            let v = (y as f32 - height as f32 / 2.0) * scale;
+            let r = 6.0 * std::f32::consts::PI * (u * u + v * v).sqrt();
+            let sinc = if r > 0.0 { r.sin() / r } else { 1.0 };
+            sinc + 0.3 * terrain(u, v)
+        })
+    }
+}
+
+/// Fractal value noise from -1 to 1, the same for every run
+fn terrain(u: f32, v: f32) -> f32 {
+    fn lattice(x: i32, y: i32) -> f32 {
+        let mut hash = (x as u32).wrapping_mul(0x27d4_eb2d) ^ (y as u32).wrapping_mul(0x1656_67b1);
+        hash ^= hash >> 15;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 13;
+        hash as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+    fn noise(x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = lattice(x0, y0) * (1.0 - tx) + lattice(x0 + 1, y0) * tx;
+        let bottom = lattice(x0, y0 + 1) * (1.0 - tx) + lattice(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+    (0..5)
+        .map(|octave| {
+            let frequency = 3.0 * 2f32.powi(octave);
+            noise(u * frequency, v * frequency) / 2f32.powi(octave)
+        })
+        .sum::<f32>()
+        / 1.9375
+}
+
+/// Raw images, see `raw_image`, or with the `tiff` feature TIFF files
 impl TryFrom<Vec<u8>> for Image<f32> {
-    type Error = anyhow::Error;
+    type Error = ViewerError;
     fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut decoder = Decoder::new(std::io::Cursor::new(bytes))?;
-        let dimensions = decoder.dimensions()?;
-        let image = match decoder.read_image()? {
-            DecodingResult::F32(data) => Ok(Image {
-                size: ImageSize {
-                    width: NonZeroU32::new(dimensions.0).ok_or(anyhow!("Invalid width"))?,
-                    height: NonZeroU32::new(dimensions.1).ok_or(anyhow!("Invalid height"))?,
-                },
-                data,
-            }),
-            _ => Err(anyhow::anyhow!("Unsupported surface image format")),
-        }?;
-        Ok(Image {
-            size: ImageSize {
-                width: NonZeroU32::new(dimensions.0).unwrap(),
-                height: NonZeroU32::new(dimensions.1).unwrap(),
-            },
-            data: image.data,
-        })
+        let bytes = compression::decompress(bytes)?;
+        if raw_image::is_raw(&bytes) {
+            return raw_image::decode(&bytes);
+        }
+        if raw_image::is_tile(&bytes) {
+            return raw_image::assemble(&bytes);
+        }
+        #[cfg(feature = "tiff")]
+        {
+            let mut decoder = Decoder::new(std::io::Cursor::new(bytes))?;
+            let dimensions = decoder.dimensions()?;
+            if let DecodingResult::F32(data) = decoder.read_image()? {
+                return Image::new(ImageSize::new(dimensions.0, dimensions.1)?, data);
+            }
+        }
+        Err(ViewerError::UnsupportedFormat("surface"))
     }
 }
 
 impl TryFrom<Vec<u8>> for Image<u16> {
-    type Error = anyhow::Error;
+    type Error = ViewerError;
     fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
-        let mut decoder = Decoder::new(std::io::Cursor::new(bytes))?;
-        let dimensions = decoder.dimensions()?;
-        let image = match decoder.read_image()? {
-            DecodingResult::U16(data) => Ok(Image {
-                size: ImageSize {
-                    width: NonZeroU32::new(dimensions.0).ok_or(anyhow!("Invalid width"))?,
-                    height: NonZeroU32::new(dimensions.1).ok_or(anyhow!("Invalid height"))?,
-                },
-                data,
-            }),
-            _ => Err(anyhow::anyhow!("Unsupported image format")),
-        }?;
-        Ok(Image {
-            size: ImageSize {
-                width: NonZeroU32::new(dimensions.0).unwrap(),
-                height: NonZeroU32::new(dimensions.1).unwrap(),
-            },
-            data: image.data,
-        })
+        let bytes = compression::decompress(bytes)?;
+        if raw_image::is_raw(&bytes) {
+            return raw_image::decode(&bytes);
+        }
+        #[cfg(feature = "tiff")]
+        {
+            let mut decoder = Decoder::new(std::io::Cursor::new(bytes))?;
+            let dimensions = decoder.dimensions()?;
+            if let DecodingResult::U16(data) = decoder.read_image()? {
+                return Image::new(ImageSize::new(dimensions.0, dimensions.1)?, data);
+            }
+        }
+        Err(ViewerError::UnsupportedFormat("amplitude"))
+    }
+}
+
+/// 8 bit RGBA image, e.g. read back from an offscreen render or a thumbnail
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed RGBA rows, encoded with the sRGB curve
+    pub pixels: Vec<u8>,
+    /// Primaries of the pixels, those of sRGB unless rendered for another display
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen(skip))]
+    pub color_space: OutputColorSpace,
+}
+
+impl RgbaImage {
+    /// FNV-1a hash of size and pixels, stable across platforms and Rust versions
+    /// so it can be stored as a golden value
+    #[allow(dead_code)]
+    pub fn hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+        self.width
+            .to_le_bytes()
+            .iter()
+            .chain(self.height.to_le_bytes().iter())
+            .chain(self.pixels.iter())
+            .fold(OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+            })
+    }
+
+    /// Encodes the image as PNG tagged with its color space, e.g. to embed it in a report
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_png(&self) -> Result<Vec<u8>, ViewerError> {
+        let mut png = Vec::new();
+        let mut info = png::Info::with_size(self.width, self.height);
+        info.color_type = png::ColorType::Rgba;
+        info.bit_depth = png::BitDepth::Eight;
+        if let OutputColorSpace::Icc(profile) = &self.color_space {
+            info.icc_profile = Some(profile.data.to_vec().into());
+        }
+        let mut encoder = png::Encoder::with_info(&mut png, info)?;
+        match &self.color_space {
+            OutputColorSpace::Srgb => {
+                encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+            }
+            OutputColorSpace::DisplayP3 => {
+                let [red, green, blue] = DISPLAY_P3_PRIMARIES;
+                encoder.set_source_chromaticities(png::SourceChromaticities::new(
+                    D65_WHITE, red, green, blue,
+                ));
+                // The sRGB curve, as the gamma viewers without cHRM support also read
+                encoder.set_source_gamma(png::ScaledFloat::new(1.0 / 2.2));
+            }
+            OutputColorSpace::Icc(_) => {}
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(png)
+    }
+
+    #[allow(dead_code)]
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let start = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[start],
+            self.pixels[start + 1],
+            self.pixels[start + 2],
+            self.pixels[start + 3],
+        ]
     }
 }
 
 pub struct SurfaceAmplitudeImage {
     pub surface: Image<f32>,
+    #[allow(dead_code)]
     pub amplitude: Image<f32>,
+    /// Unit of the height values if the file tells it
+    #[allow(dead_code)]
+    pub unit: Option<Unit>,
+    /// Tags of the surface image
+    #[allow(dead_code)]
+    pub info: DatasetInfo,
+}
+
+/// Reads the height unit from the image description of the current image
+#[cfg(not(target_arch = "wasm32"))]
+fn read_unit<R: std::io::Read + std::io::Seek>(decoder: &mut Decoder<R>) -> Option<Unit> {
+    decoder
+        .get_tag_ascii_string(tiff::tags::Tag::ImageDescription)
+        .ok()
+        .and_then(|description| Unit::from_description(&description))
 }
 
 impl SurfaceAmplitudeImage {
+    /// Shaded preview of the heights in the default colormap, see `thumbnail::thumbnail`
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn thumbnail(&self, max_dim: u32) -> RgbaImage {
+        crate::thumbnail::thumbnail(&self.surface, max_dim, Default::default())
+    }
+
+    /// Downloads and decodes a file, requested and retried as `options` say, see `download`
+    #[cfg(not(target_arch = "wasm32"))]
     #[allow(dead_code)]
-    pub async fn from_url(url: &str) -> anyhow::Result<Self> {
-        let response = reqwest::get(url).await?;
-        let body = response.bytes().await?;
-        let mut decoder = Decoder::new(std::io::Cursor::new(body))?;
-        let dimensions = decoder.dimensions()?;
-        let surface = match decoder.read_image()? {
-            DecodingResult::F32(data) => Ok(Image {
-                size: ImageSize {
-                    width: NonZeroU32::new(dimensions.0).ok_or(anyhow!("Invalid width"))?,
-                    height: NonZeroU32::new(dimensions.1).ok_or(anyhow!("Invalid height"))?,
-                },
-                data,
-            }),
-            _ => Err(anyhow::anyhow!("Unsupported surface image format")),
-        }?;
-        decoder.next_image()?;
-        let dimensions = decoder.dimensions()?;
-        let amplitude = match decoder.read_image()? {
-            DecodingResult::F32(data) => Ok(Image {
-                size: ImageSize {
-                    width: NonZeroU32::new(dimensions.0).ok_or(anyhow!("Invalid width"))?,
-                    height: NonZeroU32::new(dimensions.1).ok_or(anyhow!("Invalid height"))?,
-                },
-                data,
-            }),
-            _ => Err(anyhow::anyhow!("Unsupported amplitude image format")),
-        }?;
-        info!(
-            "Loaded surface & amplitude image with size {}x{} from {}",
-            surface.size.width, surface.size.height, url,
-        );
-        Ok(Self { surface, amplitude })
+    pub async fn from_url(
+        url: &str,
+        options: &DownloadOptions,
+        progress: impl FnMut(DownloadProgress),
+    ) -> Result<Self, ViewerError> {
+        let body = compression::decompress(download::download(url, options, progress).await?)?;
+        Self::decode(std::io::Cursor::new(body), url)
+    }
+
+    /// Reads and decodes a dataset of any source, see `data_source`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_source(source: &(impl DataSource + ?Sized)) -> Result<Self, ViewerError> {
+        // Rejects e.g. the login page of an expired session before a long download
+        let magic = source.read_range(0..4).await?;
+        if !matches!(
+            magic[..],
+            [b'I', b'I', 42 | 43, 0] | [b'M', b'M', 0, 42 | 43]
+        ) && compression::Compression::detect(&magic).is_none()
+        {
+            return Err(ViewerError::UnsupportedFormat("dataset"));
+        }
+        let bytes = source.read().await?;
+        let sha256 = provenance::sha256_hex(&bytes);
+        let body = compression::decompress(bytes)?;
+        let mut image = Self::decode(std::io::Cursor::new(body), source.name())?;
+        image.info.sha256 = Some(sha256);
+        Ok(image)
+    }
+
+    /// Unit and tags of the surface of a file without decoding its pixels
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_tags(path: &str) -> Result<(Option<Unit>, DatasetInfo), ViewerError> {
+        let mut decoder = Decoder::new(compression::open(path)?)?;
+        Ok((read_unit(&mut decoder), DatasetInfo::read(&mut decoder)))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: &str) -> Result<Self, ViewerError> {
+        Self::decode(compression::open(path)?, path)
     }
 
+    /// Surface and amplitude pages of a TIFF file, `source` is logged
     #[cfg(not(target_arch = "wasm32"))]
-    pub fn from_file(path: &str) -> anyhow::Result<Self> {
-        let img_file = File::open(path)?;
-        let mut decoder = Decoder::new(img_file)?;
+    fn decode<R: std::io::Read + std::io::Seek>(
+        reader: R,
+        source: &str,
+    ) -> Result<Self, ViewerError> {
+        let mut decoder = Decoder::new(reader)?;
+        let unit = read_unit(&mut decoder);
+        let info = DatasetInfo::read(&mut decoder);
         let dimensions = decoder.dimensions()?;
         let surface = match decoder.read_image()? {
-            DecodingResult::F32(data) => Ok(Image {
-                size: ImageSize {
-                    width: NonZeroU32::new(dimensions.0).ok_or(anyhow!("Invalid width"))?,
-                    height: NonZeroU32::new(dimensions.1).ok_or(anyhow!("Invalid height"))?,
-                },
-                data,
-            }),
-            _ => Err(anyhow::anyhow!("Unsupported surface image format")),
+            DecodingResult::F32(data) => {
+                Image::new(ImageSize::new(dimensions.0, dimensions.1)?, data)
+            }
+            _ => Err(ViewerError::UnsupportedFormat("surface")),
         }?;
         decoder.next_image()?;
         let dimensions = decoder.dimensions()?;
         let amplitude = match decoder.read_image()? {
-            DecodingResult::F32(data) => Ok(Image {
-                size: ImageSize {
-                    width: NonZeroU32::new(dimensions.0).ok_or(anyhow!("Invalid width"))?,
-                    height: NonZeroU32::new(dimensions.1).ok_or(anyhow!("Invalid height"))?,
-                },
-                data,
-            }),
-            _ => Err(anyhow::anyhow!("Unsupported amplitude image format")),
+            DecodingResult::F32(data) => {
+                Image::new(ImageSize::new(dimensions.0, dimensions.1)?, data)
+            }
+            _ => Err(ViewerError::UnsupportedFormat("amplitude")),
         }?;
         info!(
             "Loaded surface & amplitude image with size {}x{} from {}",
-            surface.size.width, surface.size.height, path,
+            surface.size.width, surface.size.height, source,
         );
-        Ok(Self { surface, amplitude })
+        Ok(Self {
+            surface,
+            amplitude,
+            unit,
+            info,
+        })
     }
 }
 
-#[derive(Clone)]
+/// What is computed from a surface when it is shown, so that it can be computed in the
+/// background and cached with the surface, see `cache::DatasetCache`
+pub struct SurfaceProducts {
+    /// Heights between the 2nd and 98th percentile, the colors are spread over them
+    pub height_range: ZValueRange<f32>,
+    /// Mip levels 1 and 2 of the surface texture
+    pub mips: [Image<f32>; 2],
+}
+
+impl SurfaceProducts {
+    pub fn compute(surface: &Image<f32>) -> Result<Self, ViewerError> {
+        if !surface.data.iter().any(|v| v.is_finite()) {
+            return Err(ViewerError::InvalidImage(
+                "surface contains no finite values".to_owned(),
+            ));
+        }
+        let outlier_removed_data = surface.outlier_removed_data(2.0, 98.0);
+        Ok(Self {
+            height_range: value_range(&outlier_removed_data)?,
+            mips: [1, 2].map(|level| surface.resize(&surface.size.mip(level), Resampling::Area)),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct ImageSize {
     pub width: NonZeroU32,
     pub height: NonZeroU32,
 }
 
 impl ImageSize {
+    pub(crate) fn new(width: u32, height: u32) -> Result<Self, ViewerError> {
+        // Pixels are addressed with u32 vertex indices in the shaders
+        if width.checked_mul(height).is_none() {
+            return Err(ViewerError::InvalidImage(format!(
+                "{}x{} pixels are too many to be addressed",
+                width, height
+            )));
+        }
+        Ok(Self {
+            width: NonZeroU32::new(width)
+                .ok_or_else(|| ViewerError::InvalidImage("width is zero".to_owned()))?,
+            height: NonZeroU32::new(height)
+                .ok_or_else(|| ViewerError::InvalidImage("height is zero".to_owned()))?,
+        })
+    }
+
+    /// Size of a mip level, each level halves the size
+    pub(crate) fn mip(&self, level: u32) -> ImageSize {
+        ImageSize {
+            width: NonZeroU32::new(self.width.get() >> level).unwrap_or(NonZeroU32::MIN),
+            height: NonZeroU32::new(self.height.get() >> level).unwrap_or(NonZeroU32::MIN),
+        }
+    }
+
+    pub(crate) fn pixel_count(&self) -> usize {
+        self.width.get() as usize * self.height.get() as usize
+    }
+
+    /// Scale of the [-1, 1] mesh in x and y that shows the grid with its proportions, the
+    /// longer side keeps its size. `pixel_aspect` is the ratio of the y to the x pixel spacing.
+    pub(crate) fn lateral_scale(&self, pixel_aspect: f32) -> glam::Vec2 {
+        let width = self.width.get().saturating_sub(1).max(1) as f32;
+        let height = self.height.get().saturating_sub(1).max(1) as f32 * pixel_aspect;
+        let longer = width.max(height);
+        glam::Vec2::new(width / longer, height / longer)
+    }
+
+    /// Checks that textures and mesh buffers for an image of this size can be created on a
+    /// device with the given limits
+    pub(crate) fn check_limits(&self, limits: &wgpu::Limits) -> Result<(), ViewerError> {
+        let max_dimension = limits.max_texture_dimension_2d;
+        if self.width.get() > max_dimension || self.height.get() > max_dimension {
+            return Err(ViewerError::InvalidImage(format!(
+                "{}x{} pixels exceeds the maximum texture size of {} pixels of this GPU",
+                self.width, self.height, max_dimension
+            )));
+        }
+        // The triangle strip visits every pixel about twice, each index is a u32
+        let index_buffer_size = 2 * self.pixel_count() as u64 * std::mem::size_of::<u32>() as u64;
+        if index_buffer_size > limits.max_buffer_size {
+            return Err(ViewerError::InvalidImage(format!(
+                "{}x{} pixels needs a mesh of {} MiB but this GPU allows at most {} MiB per buffer",
+                self.width,
+                self.height,
+                index_buffer_size >> 20,
+                limits.max_buffer_size >> 20
+            )));
+        }
+        Ok(())
+    }
+
     pub(crate) fn create_buffer(device: &wgpu::Device) -> wgpu::Buffer {
         device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("image_dims_buffer"),
@@ -231,7 +668,7 @@ impl ImageSize {
         );
     }
 
-    pub fn get_bind_group_entry(buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry {
+    pub fn get_bind_group_entry(buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
         wgpu::BindGroupEntry {
             binding: 0,
             resource: buffer.as_entire_binding(),
@@ -252,9 +689,22 @@ impl ImageSize {
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct ZValueRange<T: NoUninit>(Range<T>);
 
 impl<T: NoUninit> ZValueRange<T> {
+    pub(crate) fn new(min: T, max: T) -> Self {
+        Self(min..max)
+    }
+
+    pub(crate) fn min(&self) -> T {
+        self.0.start
+    }
+
+    pub(crate) fn max(&self) -> T {
+        self.0.end
+    }
+
     pub(crate) fn create_buffer(device: &wgpu::Device) -> wgpu::Buffer {
         device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("z_value_range_buffer"),
@@ -268,7 +718,7 @@ impl<T: NoUninit> ZValueRange<T> {
         queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[self.0.start, self.0.end]));
     }
 
-    pub fn get_bind_group_entry(buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry {
+    pub fn get_bind_group_entry(buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
         wgpu::BindGroupEntry {
             binding: 1,
             resource: buffer.as_entire_binding(),
@@ -289,16 +739,226 @@ impl<T: NoUninit> ZValueRange<T> {
     }
 }
 
-pub fn value_range<T: PartialOrd + Copy + NoUninit>(data: &Vec<T>) -> ZValueRange<T> {
-    let mut min_value = data[0];
-    let mut max_value = data[0];
-    for &value in data {
-        if value < min_value {
-            min_value = value;
+pub fn value_range(data: &[f32]) -> Result<ZValueRange<f32>, ViewerError> {
+    if data.is_empty() {
+        return Err(ViewerError::InvalidImage(
+            "image contains no data".to_owned(),
+        ));
+    }
+    // NaN values are not comparable and don't count, a range of only NaN values is NaN
+    let (min_value, max_value) = simd::min_max(data).unwrap_or((f32::NAN, f32::NAN));
+    Ok(ZValueRange(min_value..max_value))
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_png_is_tagged_with_its_color_space() {
+        let mut image = RgbaImage {
+            width: 1,
+            height: 1,
+            pixels: vec![255, 0, 0, 255],
+            color_space: OutputColorSpace::Srgb,
+        };
+        let has_chunk = |png: &[u8], chunk: &[u8]| png.windows(4).any(|name| name == chunk);
+        let png = image.to_png().unwrap();
+        assert!(has_chunk(&png, b"sRGB") && !has_chunk(&png, b"cHRM"));
+
+        image.color_space = OutputColorSpace::DisplayP3;
+        let png = image.to_png().unwrap();
+        assert!(has_chunk(&png, b"cHRM") && has_chunk(&png, b"gAMA"));
+        assert!(!has_chunk(&png, b"sRGB"));
+        let decoder = png::Decoder::new(std::io::Cursor::new(png));
+        let reader = decoder.read_info().unwrap();
+        let chromaticities = reader.info().source_chromaticities.unwrap();
+        assert_eq!(chromaticities.red.0.into_value(), 0.68);
+    }
+
+    #[test]
+    fn test_resampling_modes() {
+        let image = Image::new(
+            ImageSize::new(4, 2).unwrap(),
+            vec![0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0, f32::NAN, 7.0],
+        )
+        .unwrap();
+        let half = ImageSize::new(2, 1).unwrap();
+        assert_eq!(
+            image.resize(&half, Resampling::Nearest).data[..],
+            [0.0, 2.0]
+        );
+        // Means of the 2 × 2 blocks without the missing pixel
+        assert_eq!(image.resize(&half, Resampling::Area).data[..], [2.5, 4.0]);
+
+        // Interpolation reproduces a ramp away from the borders
+        let ramp = Image::new(
+            ImageSize::new(6, 1).unwrap(),
+            vec![0.0f32, 1.0, 2.0, 3.0, 4.0, 5.0],
+        )
+        .unwrap();
+        let double = ImageSize::new(12, 1).unwrap();
+        let interior = [1.75, 2.25, 2.75, 3.25];
+        assert_eq!(
+            ramp.resize(&double, Resampling::Bilinear).data[4..8],
+            interior
+        );
+        assert_eq!(
+            ramp.resize(&double, Resampling::Bicubic).data[4..8],
+            interior
+        );
+        // Missing pixels fall back to bilinear interpolation of the others
+        let gap = Image::new(ImageSize::new(3, 1).unwrap(), vec![0.0f32, f32::NAN, 2.0]).unwrap();
+        let resized = gap.resize(&ImageSize::new(6, 1).unwrap(), Resampling::Bicubic);
+        assert!(resized.data.iter().all(|value| value.is_finite()));
+    }
+
+    #[test]
+    fn test_value_range_of_empty_data_is_an_error() {
+        assert!(value_range(&[]).is_err());
+        let range = value_range(&[f32::NAN, 2.0, -1.0]).unwrap();
+        assert_eq!((range.min(), range.max()), (-1.0, 2.0));
+    }
+
+    #[test]
+    fn test_mapped_image_has_the_same_pixels() {
+        let image =
+            Image::new(ImageSize::new(3, 1).unwrap(), vec![1.0f32, f32::NAN, -2.5]).unwrap();
+        let mapped = image.to_mapped().unwrap();
+        assert!(mapped.is_mapped() && !image.is_mapped());
+        assert_eq!(mapped.data[0], 1.0);
+        assert!(mapped.data[1].is_nan());
+        assert_eq!(mapped.data[2], -2.5);
+    }
+
+    #[test]
+    fn test_lateral_scale_keeps_physical_proportions() {
+        let size = ImageSize::new(201, 101).unwrap();
+        assert_eq!(size.lateral_scale(1.0), glam::Vec2::new(1.0, 0.5));
+        assert_eq!(size.lateral_scale(4.0), glam::Vec2::new(0.5, 1.0));
+    }
+
+    #[test]
+    fn test_demo_surface_peaks_in_the_center() {
+        let demo = Image::demo(ImageSize::new(201, 101).unwrap());
+        let range = value_range(&demo.data).unwrap().0;
+        assert!(range.start >= -1.0 && range.end <= 1.5, "{:?}", range);
+        let peak = demo
+            .data
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(index, _)| (index as u32 % 201, index as u32 / 201))
+            .unwrap();
+        assert!(
+            peak.0.abs_diff(100) < 10 && peak.1.abs_diff(50) < 10,
+            "{:?}",
+            peak
+        );
+        // Deterministic, so that screenshots of the demo compare
+        let again = Image::demo(ImageSize::new(201, 101).unwrap());
+        assert_eq!(demo.data[..], again.data[..]);
+    }
+
+    #[test]
+    fn test_zero_sized_image_is_an_error() {
+        assert!(matches!(
+            ImageSize::new(0, 10),
+            Err(ViewerError::InvalidImage(_))
+        ));
+        assert!(ImageSize::new(1, 1).is_ok());
+        assert!(ImageSize::new(1 << 20, 1 << 20).is_err());
+    }
+
+    #[test]
+    fn test_data_length_must_match_size() {
+        let size = ImageSize::new(3, 2).unwrap();
+        assert!(Image::new(size.clone(), vec![0.0f32; 5]).is_err());
+        assert!(Image::new(size, vec![0.0f32; 6]).is_ok());
+    }
+
+    #[test]
+    fn test_size_beyond_gpu_limits_is_an_error() {
+        let limits = wgpu::Limits::downlevel_webgl2_defaults();
+        let max = limits.max_texture_dimension_2d;
+        assert!(
+            ImageSize::new(max, 1)
+                .unwrap()
+                .check_limits(&limits)
+                .is_ok()
+        );
+        assert!(
+            ImageSize::new(max + 1, 1)
+                .unwrap()
+                .check_limits(&limits)
+                .is_err()
+        );
+    }
+
+    /// TIFF file with a float surface and an amplitude page of the same size
+    fn tiff_file(width: u32, height: u32, surface: &[f32]) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        let mut encoder = tiff::encoder::TiffEncoder::new(&mut bytes).unwrap();
+        encoder
+            .write_image::<tiff::encoder::colortype::Gray32Float>(width, height, surface)
+            .unwrap();
+        encoder
+            .write_image::<tiff::encoder::colortype::Gray32Float>(width, height, surface)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    /// Runs every TIFF loader on the bytes, they have to fail with an error instead of panicking
+    fn decode_all(bytes: &[u8]) -> Result<SurfaceAmplitudeImage, ViewerError> {
+        let _ = Image::<f32>::try_from(bytes.to_vec());
+        let _ = Image::<u16>::try_from(bytes.to_vec());
+        let _ = DatasetInfo::from_bytes(bytes);
+        SurfaceAmplitudeImage::decode(std::io::Cursor::new(bytes), "test")
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes_are_rejected(bytes in proptest::collection::vec(any::<u8>(), 0..512)) {
+            // Starts like a TIFF file half of the time, to get past the magic number
+            let _ = decode_all(&bytes);
+            let mut tiff = b"II*\0".to_vec();
+            tiff.extend(&bytes);
+            let _ = decode_all(&tiff);
+        }
+
+        #[test]
+        fn corrupted_files_are_rejected(
+            width in 1u32..16,
+            height in 1u32..16,
+            changes in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..8),
+            truncate in any::<prop::sample::Index>(),
+        ) {
+            let surface = vec![1.5; (width * height) as usize];
+            let mut bytes = tiff_file(width, height, &surface);
+            for (index, value) in changes {
+                let index = index.index(bytes.len());
+                bytes[index] = value;
+            }
+            if let Ok(image) = decode_all(&bytes) {
+                prop_assert_eq!(image.surface.data.len(), image.surface.size.pixel_count());
+                prop_assert_eq!(image.amplitude.data.len(), image.amplitude.size.pixel_count());
+            }
+            let len = truncate.index(bytes.len());
+            let _ = decode_all(&bytes[..len]);
         }
-        if value > max_value {
-            max_value = value;
+
+        #[test]
+        fn valid_files_round_trip(
+            width in 1u32..32,
+            height in 1u32..32,
+            data in proptest::collection::vec(any::<f32>(), 32 * 32),
+        ) {
+            let surface = &data[..(width * height) as usize];
+            let image = decode_all(&tiff_file(width, height, surface)).unwrap();
+            prop_assert_eq!((image.surface.size.width.get(), image.surface.size.height.get()), (width, height));
+            prop_assert!(image.surface.data.iter().zip(surface).all(|(a, b)| a.to_bits() == b.to_bits()));
         }
     }
-    ZValueRange(min_value..max_value)
 }
@@ -1,6 +1,33 @@
 use wgpu::util::DeviceExt;
 
-use crate::image::ImageSize;
+use crate::image::{Image, ImageSize};
+
+/// Index value that restarts the triangle strip
+const PRIMITIVE_RESTART: u32 = u32::MAX;
+
+/// How the surface is tessellated into triangles
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MeshMode {
+    /// One vertex per pixel
+    #[default]
+    Full,
+    /// Flat regions are coarsened as long as the surface deviates less than
+    /// `relative_tolerance` times the displayed z-range from the coarse mesh
+    Adaptive { relative_tolerance: f32 },
+}
+
+impl MeshMode {
+    pub const DEFAULT_ADAPTIVE: MeshMode = MeshMode::Adaptive {
+        relative_tolerance: 0.005,
+    };
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            MeshMode::Full => Self::DEFAULT_ADAPTIVE,
+            MeshMode::Adaptive { .. } => MeshMode::Full,
+        }
+    }
+}
 
 pub(crate) struct IndexBufferBuilder {
     indices: Vec<u32>,
@@ -13,28 +40,90 @@ impl IndexBufferBuilder {
             for mut col in 0..(image_size.width.get()) {
                 if row % 2 == 0 {
                     if col > 0 {
-                        indices.push((row * image_size.width.get() + col) as u32);
+                        indices.push(row * image_size.width.get() + col);
                     }
-                    indices.push(((row + 1) * image_size.width.get() + col) as u32);
+                    indices.push((row + 1) * image_size.width.get() + col);
                     if col == image_size.width.get() - 1 && row < image_size.height.get() - 2 {
                         // index is added twice to have smooth transition to next row
-                        indices.push(((row + 1) * image_size.width.get() + col - 1) as u32);
+                        indices.push((row + 1) * image_size.width.get() + col - 1);
                     }
                 } else {
                     col = image_size.width.get() - 1 - col;
                     if col < image_size.width.get() - 1 {
-                        indices.push((row * image_size.width.get() + col) as u32);
+                        indices.push(row * image_size.width.get() + col);
                     }
-                    indices.push(((row + 1) * image_size.width.get() + col) as u32);
+                    indices.push((row + 1) * image_size.width.get() + col);
                     if col == 0 && row < image_size.height.get() - 2 {
                         // index is added twice to have smooth transition to next row
-                        indices.push(((row + 1) * image_size.width.get() + 1) as u32);
+                        indices.push((row + 1) * image_size.width.get() + 1);
                     }
                 }
             }
         }
         log::info!("Index buffer length: {:?}", indices.len());
-        log::info!("Number of triangles: {:?}", indices.len() - 2);
+        log::info!("Number of triangles: {:?}", indices.len().saturating_sub(2));
+        Self { indices }
+    }
+
+    /// Builds triangle strips through every `step`-th row and column and the last ones, one strip
+    /// per row separated by primitive restarts, e.g. as a cheap mesh while the camera moves
+    pub(crate) fn new_strided(image_size: &ImageSize, step: u32) -> Self {
+        let width = image_size.width.get();
+        let strided = |len: u32| {
+            let mut lines: Vec<u32> = (0..len).step_by(step.max(1) as usize).collect();
+            if lines.last() != Some(&(len - 1)) {
+                lines.push(len - 1);
+            }
+            lines
+        };
+        let columns = strided(width);
+        let rows = strided(image_size.height.get());
+        let mut indices = Vec::with_capacity(rows.len() * (columns.len() * 2 + 1));
+        for pair in rows.windows(2) {
+            for col in &columns {
+                indices.extend([pair[0] * width + col, pair[1] * width + col]);
+            }
+            indices.push(PRIMITIVE_RESTART);
+        }
+        Self { indices }
+    }
+
+    /// Builds a triangle strip of quads, separated by primitive restarts, that are as large
+    /// as possible while the surface stays within `tolerance` of the bilinear patch spanned
+    /// by the quad corners. Quads touching non-finite pixels are refined down to single pixels.
+    /// Neighbouring quads of different size may leave cracks of at most `tolerance` height.
+    pub(crate) fn new_adaptive(image: &Image<f32>, tolerance: f32) -> Self {
+        let width = image.size.width.get();
+        let height = image.size.height.get();
+        let mut indices: Vec<u32> = Vec::new();
+        let max_block_size = (width - 1).max(height - 1).max(1).next_power_of_two();
+        let mut blocks = vec![(0, 0, max_block_size)];
+        while let Some((x0, y0, size)) = blocks.pop() {
+            if x0 >= width - 1 || y0 >= height - 1 {
+                continue;
+            }
+            let x1 = (x0 + size).min(width - 1);
+            let y1 = (y0 + size).min(height - 1);
+            if size == 1 || is_flat(image, (x0, y0), (x1, y1), tolerance) {
+                indices.extend([
+                    y0 * width + x0,
+                    y1 * width + x0,
+                    y0 * width + x1,
+                    y1 * width + x1,
+                    PRIMITIVE_RESTART,
+                ]);
+            } else {
+                let half = size / 2;
+                blocks.extend([
+                    (x0, y0, half),
+                    (x0 + half, y0, half),
+                    (x0, y0 + half, half),
+                    (x0 + half, y0 + half, half),
+                ]);
+            }
+        }
+        log::info!("Index buffer length: {:?}", indices.len());
+        log::info!("Number of triangles: {:?}", indices.len() / 5 * 2);
         Self { indices }
     }
 
@@ -52,9 +141,37 @@ pub(crate) struct IndexBuffer {
     pub(crate) buffer: wgpu::Buffer,
 }
 
+/// Checks whether all pixels between the corners are finite and deviate at most
+/// `tolerance` from the bilinear interpolation of the corner values
+fn is_flat(image: &Image<f32>, (x0, y0): (u32, u32), (x1, y1): (u32, u32), tolerance: f32) -> bool {
+    let z00 = image.get_pixel(x0, y0);
+    let z10 = image.get_pixel(x1, y0);
+    let z01 = image.get_pixel(x0, y1);
+    let z11 = image.get_pixel(x1, y1);
+    for y in y0..=y1 {
+        let v = (y - y0) as f32 / (y1 - y0) as f32;
+        for x in x0..=x1 {
+            let u = (x - x0) as f32 / (x1 - x0) as f32;
+            let interpolated =
+                (z00 * (1.0 - u) + z10 * u) * (1.0 - v) + (z01 * (1.0 - u) + z11 * u) * v;
+            // non-finite pixels or corners lead to a non-finite deviation
+            let deviation = (image.get_pixel(x, y) - interpolated).abs();
+            if !deviation.is_finite() || deviation > tolerance {
+                return false;
+            }
+        }
+    }
+    true
+}
+
 #[cfg(test)]
 mod test {
-    use crate::{image::ImageSize, index_buffer::IndexBufferBuilder};
+    use proptest::prelude::*;
+
+    use crate::{
+        image::{Image, ImageSize},
+        index_buffer::{IndexBufferBuilder, PRIMITIVE_RESTART},
+    };
 
     #[test]
     fn test_triangle_strip_minimal() {
@@ -99,4 +216,111 @@ mod test {
         let expected_indices = vec![0, 5, 1, 6, 2, 7, 3, 8, 4, 9];
         assert_eq!(indices, expected_indices);
     }
+
+    #[test]
+    fn test_strided_keeps_last_row_and_column() {
+        let image_size = ImageSize {
+            width: std::num::NonZeroU32::new(4).unwrap(),
+            height: std::num::NonZeroU32::new(3).unwrap(),
+        };
+        let indices = IndexBufferBuilder::new_strided(&image_size, 2).indices;
+        let expected_indices = vec![0, 8, 2, 10, 3, 11, PRIMITIVE_RESTART];
+        assert_eq!(indices, expected_indices);
+    }
+
+    #[test]
+    fn test_adaptive_tilted_plane_is_single_quad() {
+        let size = ImageSize {
+            width: std::num::NonZeroU32::new(5).unwrap(),
+            height: std::num::NonZeroU32::new(5).unwrap(),
+        };
+        let data = (0..25)
+            .map(|i| (i % 5) as f32 * 0.5 + (i / 5) as f32)
+            .collect();
+        let image = Image { size, data };
+        let indices = IndexBufferBuilder::new_adaptive(&image, 0.01).indices;
+        assert_eq!(indices, vec![0, 20, 4, 24, PRIMITIVE_RESTART]);
+    }
+
+    #[test]
+    fn test_adaptive_refines_around_spike() {
+        let size = ImageSize {
+            width: std::num::NonZeroU32::new(5).unwrap(),
+            height: std::num::NonZeroU32::new(5).unwrap(),
+        };
+        let mut data = vec![0.0; 25];
+        data[6] = 1.0;
+        let image = Image {
+            size,
+            data: data.into(),
+        };
+        let indices = IndexBufferBuilder::new_adaptive(&image, 0.01).indices;
+        // The quadrant holding the spike is split into single pixel quads, the others stay coarse
+        assert_eq!(indices.len(), (3 + 4) * 5);
+        assert!(indices.chunks(5).all(|quad| quad[4] == PRIMITIVE_RESTART));
+        assert!(indices.chunks(5).any(|quad| quad[..4] == [0, 5, 1, 6]));
+    }
+
+    fn image_size(width: u32, height: u32) -> ImageSize {
+        ImageSize::new(width, height).unwrap()
+    }
+
+    /// Indices address pixels of the image, apart from primitive restarts
+    fn in_bounds(indices: &[u32], size: &ImageSize) -> bool {
+        indices
+            .iter()
+            .all(|&index| index == PRIMITIVE_RESTART || (index as usize) < size.pixel_count())
+    }
+
+    proptest! {
+        #[test]
+        fn triangle_strip_covers_every_pixel(width in 1u32..40, height in 1u32..40) {
+            let size = image_size(width, height);
+            let indices = IndexBufferBuilder::new_triangle_strip(&size).indices;
+            prop_assert!(in_bounds(&indices, &size));
+            if width > 1 && height > 1 {
+                let mut used = vec![false; size.pixel_count()];
+                indices.iter().for_each(|&index| used[index as usize] = true);
+                prop_assert!(used.iter().all(|&used| used));
+            }
+        }
+
+        #[test]
+        fn strided_strips_stay_in_bounds(width in 1u32..40, height in 1u32..40, step in 0u32..10) {
+            let size = image_size(width, height);
+            let indices = IndexBufferBuilder::new_strided(&size, step).indices;
+            prop_assert!(in_bounds(&indices, &size));
+            // The corners are always part of the mesh
+            if height > 1 {
+                for corner in [0, width - 1, (height - 1) * width, height * width - 1] {
+                    prop_assert!(indices.contains(&corner));
+                }
+            }
+        }
+
+        #[test]
+        fn adaptive_quads_tile_the_surface(
+            width in 1u32..24,
+            height in 1u32..24,
+            data in proptest::collection::vec(
+                prop_oneof![Just(f32::NAN), -10.0f32..10.0],
+                24 * 24,
+            ),
+            tolerance in 0.0f32..5.0,
+        ) {
+            let size = image_size(width, height);
+            let image = Image::new(size.clone(), data[..size.pixel_count()].to_vec()).unwrap();
+            let indices = IndexBufferBuilder::new_adaptive(&image, tolerance).indices;
+            prop_assert!(in_bounds(&indices, &size));
+            let area: u32 = indices
+                .chunks(5)
+                .map(|quad| {
+                    let (x0, y0) = (quad[0] % width, quad[0] / width);
+                    let (x1, y1) = (quad[3] % width, quad[3] / width);
+                    (x1 - x0) * (y1 - y0)
+                })
+                .sum();
+            prop_assert_eq!(area, (width - 1) * (height - 1));
+        }
+    }
 }
@@ -0,0 +1,392 @@
+use anyhow::anyhow;
+use std::{fmt, str::FromStr};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{Key, NamedKey},
+};
+
+/// Named keys the viewer reacts to, other named keys are not recorded
+const NAMED_KEYS: [(NamedKey, &str); 12] = [
+    (NamedKey::Control, "Control"),
+    (NamedKey::Shift, "Shift"),
+    (NamedKey::Alt, "Alt"),
+    (NamedKey::F11, "F11"),
+    (NamedKey::Escape, "Escape"),
+    (NamedKey::Delete, "Delete"),
+    (NamedKey::Enter, "Enter"),
+    (NamedKey::Backspace, "Backspace"),
+    (NamedKey::ArrowLeft, "ArrowLeft"),
+    (NamedKey::ArrowRight, "ArrowRight"),
+    (NamedKey::ArrowUp, "ArrowUp"),
+    (NamedKey::ArrowDown, "ArrowDown"),
+];
+
+/// Mouse, keyboard and size changes of a window, the part of its events that decides what the
+/// user did. Unlike `WindowEvent` it can be written to a recording and created again to replay
+/// it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    /// Mouse coordinates depend on the window size
+    Resized(PhysicalSize<u32>),
+    CursorMoved(PhysicalPosition<f64>),
+    CursorLeft,
+    MouseInput {
+        button: MouseButton,
+        state: ElementState,
+    },
+    MouseWheel(MouseScrollDelta),
+    Key {
+        key: Key,
+        state: ElementState,
+        /// Sent again while the key is held down
+        repeat: bool,
+    },
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::Resized(size) => Some(Self::Resized(*size)),
+            WindowEvent::CursorMoved { position, .. } => Some(Self::CursorMoved(*position)),
+            WindowEvent::CursorLeft { .. } => Some(Self::CursorLeft),
+            WindowEvent::MouseInput { state, button, .. } => Some(Self::MouseInput {
+                button: *button,
+                state: *state,
+            }),
+            WindowEvent::MouseWheel { delta, .. } => Some(Self::MouseWheel(*delta)),
+            WindowEvent::KeyboardInput { event, .. } => Some(Self::Key {
+                key: event.logical_key.clone(),
+                state: event.state,
+                repeat: event.repeat,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn state_name(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released",
+    }
+}
+
+fn parse_state(name: &str) -> anyhow::Result<ElementState> {
+    match name {
+        "pressed" => Ok(ElementState::Pressed),
+        "released" => Ok(ElementState::Released),
+        _ => Err(anyhow!("Invalid key or button state: {}", name)),
+    }
+}
+
+/// Text form of an event in a recording, e.g. `button left pressed` or `key char:s released`
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resized(size) => write!(f, "resize {} {}", size.width, size.height),
+            Self::CursorMoved(position) => write!(f, "move {} {}", position.x, position.y),
+            Self::CursorLeft => write!(f, "leave"),
+            Self::MouseInput { button, state } => {
+                let button = match button {
+                    MouseButton::Left => "left".to_owned(),
+                    MouseButton::Right => "right".to_owned(),
+                    MouseButton::Middle => "middle".to_owned(),
+                    MouseButton::Back => "back".to_owned(),
+                    MouseButton::Forward => "forward".to_owned(),
+                    MouseButton::Other(id) => id.to_string(),
+                };
+                write!(f, "button {} {}", button, state_name(*state))
+            }
+            Self::MouseWheel(MouseScrollDelta::LineDelta(x, y)) => {
+                write!(f, "wheel line {} {}", x, y)
+            }
+            Self::MouseWheel(MouseScrollDelta::PixelDelta(delta)) => {
+                write!(f, "wheel pixel {} {}", delta.x, delta.y)
+            }
+            Self::Key { key, state, repeat } => {
+                let name = match key {
+                    Key::Character(c) => format!("char:{}", c),
+                    Key::Named(named) => NAMED_KEYS
+                        .iter()
+                        .find(|(key, _)| key == named)
+                        .map_or_else(|| "unknown".to_owned(), |(_, name)| name.to_string()),
+                    _ => "unknown".to_owned(),
+                };
+                write!(f, "key {} {}", name, state_name(*state))?;
+                if *repeat {
+                    write!(f, " repeat")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for InputEvent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("Invalid input event: {}", s);
+        let fields: Vec<&str> = s.split(' ').collect();
+        let number = |i: usize| -> anyhow::Result<f64> {
+            let value: f64 = fields.get(i).ok_or_else(invalid)?.parse()?;
+            value.is_finite().then_some(value).ok_or_else(invalid)
+        };
+        match fields[..] {
+            ["resize", width, height] => Ok(Self::Resized(PhysicalSize::new(
+                width.parse()?,
+                height.parse()?,
+            ))),
+            ["move", _, _] => Ok(Self::CursorMoved(PhysicalPosition::new(
+                number(1)?,
+                number(2)?,
+            ))),
+            ["leave"] => Ok(Self::CursorLeft),
+            ["button", button, state] => Ok(Self::MouseInput {
+                button: match button {
+                    "left" => MouseButton::Left,
+                    "right" => MouseButton::Right,
+                    "middle" => MouseButton::Middle,
+                    "back" => MouseButton::Back,
+                    "forward" => MouseButton::Forward,
+                    id => MouseButton::Other(id.parse()?),
+                },
+                state: parse_state(state)?,
+            }),
+            ["wheel", "line", _, _] => Ok(Self::MouseWheel(MouseScrollDelta::LineDelta(
+                number(2)? as f32,
+                number(3)? as f32,
+            ))),
+            ["wheel", "pixel", _, _] => Ok(Self::MouseWheel(MouseScrollDelta::PixelDelta(
+                PhysicalPosition::new(number(2)?, number(3)?),
+            ))),
+            ["key", name, state] | ["key", name, state, "repeat"] => {
+                let key = match name.strip_prefix("char:") {
+                    Some(c) if !c.is_empty() => Key::Character(c.into()),
+                    _ => NAMED_KEYS
+                        .iter()
+                        .find(|(_, key_name)| *key_name == name)
+                        .map(|(key, _)| Key::Named(*key))
+                        .ok_or_else(invalid)?,
+                };
+                Ok(Self::Key {
+                    key,
+                    state: parse_state(state)?,
+                    repeat: fields.len() == 4,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Writes the input of a window with the milliseconds since the recording started to a text
+/// file, one event per line, to reproduce interaction bugs with `InputReplay`
+#[cfg(not(target_arch = "wasm32"))]
+pub struct InputRecorder {
+    writer: std::io::BufWriter<std::fs::File>,
+    start: Option<web_time::Instant>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InputRecorder {
+    /// `dataset` is the file shown, which a replay opens if no other is given
+    pub fn create(path: &std::path::Path, dataset: Option<&str>) -> std::io::Result<Self> {
+        use std::io::Write;
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        writeln!(writer, "# data-viewer-3d input recording")?;
+        if let Some(dataset) = dataset {
+            writeln!(writer, "dataset {}", dataset)?;
+        }
+        writer.flush()?;
+        Ok(Self {
+            writer,
+            start: None,
+        })
+    }
+
+    /// Starts the clock of the recording with the first event
+    pub fn record(&mut self, event: &InputEvent) {
+        use std::io::Write;
+        if matches!(event, InputEvent::Key { key, .. } if !is_recorded(key)) {
+            return;
+        }
+        let now = web_time::Instant::now();
+        let millis = now
+            .duration_since(*self.start.get_or_insert(now))
+            .as_millis();
+        // Flushed right away so that the recording is complete even if the viewer crashes
+        if let Err(e) =
+            writeln!(self.writer, "{} {}", millis, event).and_then(|()| self.writer.flush())
+        {
+            log::error!("Failed to record input: {}", e);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_recorded(key: &Key) -> bool {
+    match key {
+        Key::Character(c) => !c.contains(char::is_whitespace),
+        Key::Named(named) => NAMED_KEYS.iter().any(|(key, _)| key == named),
+        _ => false,
+    }
+}
+
+/// Feeds the events of a recording back at their original pace
+#[cfg(not(target_arch = "wasm32"))]
+pub struct InputReplay {
+    /// File shown while recording
+    pub dataset: Option<String>,
+    events: std::collections::VecDeque<(web_time::Duration, InputEvent)>,
+    start: Option<web_time::Instant>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InputReplay {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        text.parse()
+    }
+
+    pub fn start(&mut self, now: web_time::Instant) {
+        self.start = Some(now);
+    }
+
+    /// Events that are due, none before the replay is started
+    pub fn poll(&mut self, now: web_time::Instant) -> Vec<InputEvent> {
+        let Some(start) = self.start else {
+            return Vec::new();
+        };
+        let elapsed = now.duration_since(start);
+        let mut due = Vec::new();
+        while self.events.front().is_some_and(|(at, _)| *at <= elapsed) {
+            due.extend(self.events.pop_front().map(|(_, event)| event));
+        }
+        due
+    }
+
+    /// When the next event is due, `None` once all events were replayed
+    pub fn next_at(&self) -> Option<web_time::Instant> {
+        let start = self.start?;
+        self.events.front().map(|(at, _)| start + *at)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FromStr for InputReplay {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut replay = Self {
+            dataset: None,
+            events: Default::default(),
+            start: None,
+        };
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(dataset) = line.strip_prefix("dataset ") {
+                replay.dataset = Some(dataset.to_owned());
+                continue;
+            }
+            let parse = || -> anyhow::Result<_> {
+                let (millis, event) = line.split_once(' ').ok_or_else(|| anyhow!("No event"))?;
+                Ok((
+                    web_time::Duration::from_millis(millis.parse()?),
+                    event.parse()?,
+                ))
+            };
+            let (at, event) = parse().map_err(|e| anyhow!("Line {}: {}", number + 1, e))?;
+            if replay.events.back().is_some_and(|(last, _)| at < *last) {
+                return Err(anyhow!("Line {}: events are not in order", number + 1));
+            }
+            replay.events.push_back((at, event));
+        }
+        Ok(replay)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn events_round_trip() {
+        let events = [
+            InputEvent::Resized(PhysicalSize::new(800, 600)),
+            InputEvent::CursorMoved(PhysicalPosition::new(12.5, 300.0)),
+            InputEvent::CursorLeft,
+            InputEvent::MouseInput {
+                button: MouseButton::Left,
+                state: ElementState::Pressed,
+            },
+            InputEvent::MouseInput {
+                button: MouseButton::Other(7),
+                state: ElementState::Released,
+            },
+            InputEvent::MouseWheel(MouseScrollDelta::LineDelta(0.0, -1.0)),
+            InputEvent::MouseWheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(
+                0.0, 24.5,
+            ))),
+            InputEvent::Key {
+                key: Key::Character("s".into()),
+                state: ElementState::Pressed,
+                repeat: false,
+            },
+            InputEvent::Key {
+                key: Key::Character("x".into()),
+                state: ElementState::Pressed,
+                repeat: true,
+            },
+            InputEvent::Key {
+                key: Key::Named(NamedKey::Control),
+                state: ElementState::Released,
+                repeat: false,
+            },
+        ];
+        for event in events {
+            assert_eq!(event.to_string().parse::<InputEvent>().unwrap(), event);
+        }
+        assert!("key Tab pressed".parse::<InputEvent>().is_err());
+        assert!("move 1".parse::<InputEvent>().is_err());
+    }
+
+    #[test]
+    fn replays_events_when_due() {
+        let mut replay: InputReplay = "# data-viewer-3d input recording\n\
+             dataset part.tiff\n\
+             0 resize 640 480\n\
+             0 move 10 20\n\
+             50 button left pressed\n"
+            .parse()
+            .unwrap();
+        assert_eq!(replay.dataset.as_deref(), Some("part.tiff"));
+        let start = web_time::Instant::now();
+        assert!(replay.poll(start).is_empty());
+        replay.start(start);
+        assert_eq!(replay.poll(start).len(), 2);
+        assert_eq!(
+            replay.next_at(),
+            Some(start + web_time::Duration::from_millis(50))
+        );
+        assert!(replay.poll(start).is_empty());
+        assert_eq!(
+            replay.poll(start + web_time::Duration::from_millis(60)),
+            [InputEvent::MouseInput {
+                button: MouseButton::Left,
+                state: ElementState::Pressed,
+            }]
+        );
+        assert!(replay.is_finished());
+        assert!("50 leave\n10 leave".parse::<InputReplay>().is_err());
+    }
+}
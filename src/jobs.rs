@@ -0,0 +1,299 @@
+use std::{
+    any::Any,
+    collections::VecDeque,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc,
+    },
+};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{error::ViewerError, window_title::Progress};
+
+/// Handed to the work of a job to report progress and notice cancellation
+#[derive(Clone)]
+pub struct JobContext {
+    cancelled: Arc<AtomicBool>,
+    /// Bits of the completed fraction, NaN while unknown
+    progress: Arc<AtomicU32>,
+}
+
+impl JobContext {
+    fn new() -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            progress: Arc::new(AtomicU32::new(f32::NAN.to_bits())),
+        }
+    }
+
+    /// `fraction` of the work from 0 to 1 is done
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn set_progress(&self, fraction: f32) {
+        self.progress
+            .store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    fn progress(&self) -> Option<f32> {
+        Some(f32::from_bits(self.progress.load(Ordering::Relaxed))).filter(|p| !p.is_nan())
+    }
+
+    /// Returns an error if the job was cancelled, to stop the work with `?`
+    pub fn check(&self) -> Result<(), ViewerError> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err(ViewerError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Running job as listed for the user, e.g. to offer cancelling it
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobStatus {
+    pub id: u32,
+    pub name: String,
+    /// Completed fraction from 0 to 1, `None` if unknown
+    pub progress: Option<f32>,
+}
+
+type Output = Box<dyn Any + Send>;
+/// Applies the output of a job to `T`
+pub type Apply<T> = Box<dyn FnOnce(&mut T)>;
+type Done<T> = Box<dyn FnOnce(&mut T, Output)>;
+
+/// Outcome of a job with its name
+pub enum Finished<T> {
+    Done(Apply<T>),
+    Failed(String, ViewerError),
+}
+
+struct Job<T> {
+    id: u32,
+    /// `None` for the continuations queued with `then`, which can't be cancelled
+    name: Option<String>,
+    context: JobContext,
+    done: Done<T>,
+    output: Option<Result<Output, ViewerError>>,
+}
+
+/// Runs long operations in the background, on threads natively and as tasks after the current
+/// event in the browser. Results are applied to `T` on the event loop thread in the order the
+/// jobs were started, so a reference never arrives before the surface it belongs to.
+pub struct Jobs<T> {
+    next_id: u32,
+    queue: VecDeque<Job<T>>,
+    sender: mpsc::Sender<(u32, Result<Output, ViewerError>)>,
+    receiver: mpsc::Receiver<(u32, Result<Output, ViewerError>)>,
+}
+
+impl<T> Default for Jobs<T> {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            next_id: 1,
+            queue: VecDeque::new(),
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl<T: 'static> Jobs<T> {
+    /// How often finished jobs and progress are collected while jobs are running
+    pub const POLL_INTERVAL: web_time::Duration = web_time::Duration::from_millis(50);
+
+    /// Runs `work` in the background and `done` with its output once all earlier jobs are done
+    pub fn spawn<R: Send + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        work: impl FnOnce(&JobContext) -> Result<R, ViewerError> + Send + 'static,
+        done: impl FnOnce(&mut T, R) + 'static,
+    ) -> u32 {
+        let (id, context) = self.push(Some(name.into()), done);
+        let sender = self.sender.clone();
+        let run = move || {
+            let output = context
+                .check()
+                .and_then(|()| work(&context))
+                .map(|output| Box::new(output) as Output);
+            // The receiver is gone when the window was closed meanwhile
+            let _ = sender.send((id, output));
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = std::thread::Builder::new()
+            .name(format!("job-{}", id))
+            .spawn(run)
+        {
+            log::error!("Failed to start job: {}", e);
+            let _ = self.sender.send((id, Err(e.into())));
+        }
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move { run() });
+        id
+    }
+
+    /// Runs `apply` once all jobs started so far are done
+    pub fn then(&mut self, apply: impl FnOnce(&mut T) + 'static) {
+        self.push(None, move |target, ()| apply(target));
+        if let Some(job) = self.queue.back_mut() {
+            job.output = Some(Ok(Box::new(())));
+        }
+    }
+
+    fn push<R: 'static>(
+        &mut self,
+        name: Option<String>,
+        done: impl FnOnce(&mut T, R) + 'static,
+    ) -> (u32, JobContext) {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        let context = JobContext::new();
+        self.queue.push_back(Job {
+            id,
+            name,
+            context: context.clone(),
+            done: Box::new(move |target, output| {
+                if let Ok(output) = output.downcast::<R>() {
+                    done(target, *output);
+                }
+            }),
+            output: None,
+        });
+        (id, context)
+    }
+
+    /// Stops the job with `id`, or all jobs for `None`. Their results are discarded, the work
+    /// itself stops at its next check. Returns the names of the cancelled jobs.
+    pub fn cancel(&mut self, id: Option<u32>) -> Vec<String> {
+        let mut cancelled = vec![];
+        self.queue.retain(|job| {
+            let Some(name) = &job.name else {
+                return true;
+            };
+            let cancel = id.is_none_or(|id| id == job.id);
+            if cancel {
+                job.context.cancelled.store(true, Ordering::Relaxed);
+                cancelled.push(name.clone());
+            }
+            !cancel
+        });
+        cancelled
+    }
+
+    /// Collects the outputs that arrived and returns the jobs finished now, in start order
+    pub fn take_finished(&mut self) -> Vec<Finished<T>> {
+        while let Ok((id, output)) = self.receiver.try_recv() {
+            if let Some(job) = self.queue.iter_mut().find(|job| job.id == id) {
+                job.output = Some(output);
+            }
+        }
+        let mut finished = vec![];
+        while self.queue.front().is_some_and(|job| job.output.is_some()) {
+            let Some(Job {
+                name, done, output, ..
+            }) = self.queue.pop_front()
+            else {
+                break;
+            };
+            let name = name.unwrap_or_default();
+            finished.push(match output {
+                Some(Ok(output)) => Finished::Done(Box::new(move |target| done(target, output))),
+                // Only cancelled jobs stop with this error, and they left the queue already
+                Some(Err(ViewerError::Cancelled)) | None => continue,
+                Some(Err(e)) => Finished::Failed(name, e),
+            });
+        }
+        finished
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    pub fn status(&self) -> Vec<JobStatus> {
+        self.queue
+            .iter()
+            .filter_map(|job| {
+                Some(JobStatus {
+                    id: job.id,
+                    name: job.name.clone()?,
+                    progress: job.context.progress(),
+                })
+            })
+            .collect()
+    }
+
+    /// Combined progress of all jobs, `None` if none is running
+    pub fn progress(&self) -> Option<Progress> {
+        let status = self.status();
+        if status.is_empty() {
+            return None;
+        }
+        let fraction = status
+            .iter()
+            .map(|job| job.progress)
+            .sum::<Option<f32>>()
+            .map(|sum| sum / status.len() as f32);
+        Some(Progress { fraction })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wait_for<T: 'static>(jobs: &mut Jobs<T>, target: &mut T) {
+        let start = web_time::Instant::now();
+        while jobs.is_busy() && start.elapsed() < web_time::Duration::from_secs(5) {
+            for finished in jobs.take_finished() {
+                if let Finished::Done(apply) = finished {
+                    apply(target);
+                }
+            }
+            std::thread::sleep(web_time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn applies_results_in_start_order() {
+        let mut jobs = Jobs::<Vec<u32>>::default();
+        let mut results = vec![];
+        jobs.spawn(
+            "slow",
+            |_| {
+                std::thread::sleep(web_time::Duration::from_millis(50));
+                Ok(1)
+            },
+            |results, value| results.push(value),
+        );
+        jobs.spawn("fast", |_| Ok(2), |results, value| results.push(value));
+        jobs.then(|results| results.push(3));
+        assert_eq!(jobs.status().len(), 2);
+        wait_for(&mut jobs, &mut results);
+        assert_eq!(results, vec![1, 2, 3]);
+        assert_eq!(jobs.progress(), None);
+    }
+
+    #[test]
+    fn cancelled_jobs_are_discarded() {
+        let mut jobs = Jobs::<Vec<u32>>::default();
+        let mut results = vec![];
+        let id = jobs.spawn(
+            "endless",
+            |context| loop {
+                context.set_progress(0.5);
+                context.check()?;
+                std::thread::sleep(web_time::Duration::from_millis(1));
+            },
+            |results, ()| results.push(1),
+        );
+        jobs.spawn("next", |_| Ok(2), |results, value| results.push(value));
+        assert_eq!(jobs.cancel(Some(id)), vec!["endless".to_owned()]);
+        wait_for(&mut jobs, &mut results);
+        assert_eq!(results, vec![2]);
+    }
+}
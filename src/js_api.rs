@@ -0,0 +1,65 @@
+// Front ends like Tauri or Electron apps integrate the viewer through the `data-viewer-3d.d.ts`
+// that `build.sh` generates. Besides the exports it declares the events the viewer dispatches
+// with the types of their details. The JS API follows semver with the version of the npm
+// package: removing or changing an export or event is a major, adding one a minor version.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Version of the JS API, the same as in `package.json`
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub const API_VERSION: &str = "0.4.0";
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(typescript_custom_section)]
+const VIEWER_EVENTS: &str = r#"
+/** Events dispatched on the canvas of the viewer, by name */
+export interface ViewerEventMap {
+    "notification": CustomEvent<Notification>;
+    "dataset-info": CustomEvent<DatasetInfoEntry[]>;
+    "annotation-properties": CustomEvent<Selection>;
+    "threshold-areas": CustomEvent<ThresholdAreas>;
+    "near-clip": CustomEvent<number>;
+    "extrema": CustomEvent<Extremum[]>;
+    "ui-scale": CustomEvent<number>;
+}
+
+/** Name of an event dispatched on the canvas */
+export type ViewerEventName = keyof ViewerEventMap;
+
+/** Any event dispatched on the canvas, narrowed by its `type` */
+export type ViewerEvent = {
+    [K in ViewerEventName]: ViewerEventMap[K] & { readonly type: K };
+}[ViewerEventName];
+
+/** Bytes of a file received by `load_url`, `total` is missing if the server didn't send it */
+export interface DownloadProgress {
+    url: string;
+    received: number;
+    total?: number;
+}
+
+/** Events dispatched on `window` */
+export interface ViewerWindowEventMap {
+    "viewer-crash": CustomEvent<CrashReport>;
+    "download-progress": CustomEvent<DownloadProgress>;
+}
+"#;
+
+/// Version of the JS API, to check that a front end works with this build
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = apiVersion)]
+pub fn api_version() -> String {
+    API_VERSION.to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn api_version_is_the_package_version() {
+        let package = include_str!("../package.json");
+        assert!(package.contains(&format!("\"version\": \"{}\"", API_VERSION)));
+    }
+}
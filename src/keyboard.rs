@@ -1,7 +1,122 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use glam::Vec3;
 use winit::event::ElementState;
+use winit::keyboard::{Key, NamedKey};
+
+use crate::audit::Disposition;
+use crate::transformation::RotationConstraint;
+
+/// Actions of character keys that can be bound to another key, with their default key
+pub const ACTIONS: [(&str, char); 23] = [
+    ("toggle-shader", 's'),
+    ("overlays", 't'),
+    ("markers", 'k'),
+    ("blend", 'b'),
+    ("holes", 'h'),
+    ("mesh", 'm'),
+    ("language", 'l'),
+    ("colormap", 'c'),
+    ("amplitude-colormap", 'a'),
+    ("color-vision", 'd'),
+    ("units", 'i'),
+    ("store-blink", 'p'),
+    ("blink", 'w'),
+    ("residual", 'e'),
+    ("angle", 'g'),
+    ("fit", 'f'),
+    ("extrema", 'q'),
+    ("hud", 'u'),
+    ("turntable", 'r'),
+    ("keyframe", 'j'),
+    ("camera-path", 'n'),
+    ("log-view", 'v'),
+    ("origin", 'o'),
+];
+
+/// Keys locking the rotation to an axis of the surface while held, with their default key.
+/// They are bound apart from `ACTIONS`, binding an action to an axis key keeps its lock.
+pub const AXIS_LOCKS: [(&str, char); 3] = [("lock-x", 'x'), ("lock-y", 'y'), ("lock-z", 'z')];
+
+/// Keys pressed instead of the default keys of actions. A bound key acts like the default key
+/// with every modifier, the default key keeps working unless another action is bound to it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyBindings {
+    /// Default key of the action bound to a key, by the lowercase key
+    actions: HashMap<char, char>,
+    /// Default key of the axis lock bound to a key, by the lowercase key
+    axis_locks: HashMap<char, char>,
+}
+
+impl KeyBindings {
+    /// Binds `action`, one of `ACTIONS` or `AXIS_LOCKS`, to a single character key
+    pub fn bind(&mut self, action: &str, key: &str) -> anyhow::Result<()> {
+        let (bindings, default) = match ACTIONS.iter().find(|(name, _)| *name == action) {
+            Some((_, default)) => (&mut self.actions, default),
+            None => AXIS_LOCKS
+                .iter()
+                .find(|(name, _)| *name == action)
+                .map(|(_, default)| (&mut self.axis_locks, default))
+                .ok_or_else(|| anyhow!("Unsupported key binding action: {}", action))?,
+        };
+        let mut chars = key.chars().flat_map(char::to_lowercase);
+        let (Some(key), None) = (chars.next(), chars.next()) else {
+            return Err(anyhow!(
+                "Key of {} must be a single character: {}",
+                action,
+                key
+            ));
+        };
+        bindings.insert(key, *default);
+        Ok(())
+    }
+
+    /// Axis (X, Y or Z) locked by holding the lowercase `key`
+    fn axis_lock(&self, key: char) -> Option<char> {
+        match self.axis_locks.get(&key) {
+            Some(axis) => Some(*axis),
+            None => matches!(key, 'x' | 'y' | 'z').then_some(key),
+        }
+    }
+
+    /// The key the viewer handles for the pressed `key`, uppercase where Shift made it so
+    pub fn resolve(&self, key: Key) -> Key {
+        let Key::Character(c) = &key else {
+            return key;
+        };
+        let mut chars = c.chars();
+        let (Some(pressed), None) = (chars.next(), chars.next()) else {
+            return key;
+        };
+        let lowercase = pressed.to_lowercase().next().unwrap_or(pressed);
+        match self.actions.get(&lowercase) {
+            Some(default) if pressed.is_uppercase() => {
+                Key::Character(default.to_uppercase().collect::<String>().into())
+            }
+            Some(default) => Key::Character(default.to_string().into()),
+            None => key,
+        }
+    }
+}
+
+/// What a key does to the audit trail. These are chords of Ctrl and named keys, so that holding
+/// X, Y or Z to lock a rotation axis never disposes of an annotation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuditKey {
+    /// Ctrl+Enter accepts the selection, Ctrl+Backspace rejects it
+    Dispose(Disposition),
+    /// Ctrl+Shift+Enter exports the audit trail
+    Export,
+}
 
 pub struct Keyboard {
     control_button: ElementState,
+    shift_button: ElementState,
+    alt_button: ElementState,
+    /// Axis (X, Y or Z) whose lock key is currently held down
+    axis_key: Option<char>,
+    pub bindings: KeyBindings,
 }
 
 impl Default for Keyboard {
@@ -14,6 +129,10 @@ impl Keyboard {
     pub fn new() -> Self {
         Self {
             control_button: ElementState::Released,
+            shift_button: ElementState::Released,
+            alt_button: ElementState::Released,
+            axis_key: None,
+            bindings: KeyBindings::default(),
         }
     }
 
@@ -21,16 +140,158 @@ impl Keyboard {
         self.control_button == ElementState::Pressed
     }
 
-    pub fn register_event(&mut self, event: winit::event::KeyEvent) {
-        match event.logical_key {
-            winit::keyboard::Key::Named(winit::keyboard::NamedKey::Control) => {
-                if event.state == ElementState::Pressed {
-                    self.control_button = ElementState::Pressed;
-                } else {
-                    self.control_button = ElementState::Released;
+    pub fn is_shift_pressed(&self) -> bool {
+        self.shift_button == ElementState::Pressed
+    }
+
+    /// Holding Alt probes the neighborhood of the pixel under the mouse
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn is_alt_pressed(&self) -> bool {
+        self.alt_button == ElementState::Pressed
+    }
+
+    /// Rotation constraint selected by the held modifiers: X/Y/Z lock the rotation to that axis
+    /// of the surface, Shift snaps the angle to 15° steps
+    pub fn rotation_constraint(&self) -> RotationConstraint {
+        RotationConstraint {
+            axis: self.axis_key.map(|key| match key {
+                'x' => Vec3::X,
+                'y' => Vec3::Y,
+                _ => Vec3::Z,
+            }),
+            snap_degrees: self
+                .is_shift_pressed()
+                .then_some(RotationConstraint::SNAP_DEGREES),
+        }
+    }
+
+    /// What pressing `key` does to the audit trail. Repeats of a held key are ignored, so that
+    /// a long press records a single verdict.
+    pub fn audit_key(&self, key: &Key, state: ElementState, repeat: bool) -> Option<AuditKey> {
+        if state != ElementState::Pressed || repeat || !self.is_control_pressed() {
+            return None;
+        }
+        match (key, self.is_shift_pressed()) {
+            (Key::Named(NamedKey::Enter), false) => Some(AuditKey::Dispose(Disposition::Accepted)),
+            (Key::Named(NamedKey::Backspace), false) => {
+                Some(AuditKey::Dispose(Disposition::Rejected))
+            }
+            (Key::Named(NamedKey::Enter), true) => Some(AuditKey::Export),
+            _ => None,
+        }
+    }
+
+    /// Forgets the held keys, after a dialog that received their release
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn release_all(&mut self) {
+        *self = Self {
+            bindings: std::mem::take(&mut self.bindings),
+            ..Self::new()
+        };
+    }
+
+    /// Records the modifiers and axis locks held, `key` is the pressed key before `resolve`
+    pub fn register_event(&mut self, key: &Key, state: ElementState) {
+        match key {
+            Key::Named(NamedKey::Control) => self.control_button = state,
+            Key::Named(NamedKey::Shift) => self.shift_button = state,
+            Key::Named(NamedKey::Alt) => self.alt_button = state,
+            Key::Character(c) => {
+                // Shift changes the reported character, so compare case-insensitively
+                let axis = c
+                    .to_lowercase()
+                    .chars()
+                    .next()
+                    .and_then(|key| self.bindings.axis_lock(key));
+                if let Some(axis) = axis {
+                    match state {
+                        ElementState::Pressed => self.axis_key = Some(axis),
+                        ElementState::Released if self.axis_key == Some(axis) => {
+                            self.axis_key = None
+                        }
+                        ElementState::Released => (),
+                    }
                 }
             }
             _ => (),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{audit::AuditTrail, selection::Selection};
+
+    #[derive(Default)]
+    struct Review {
+        keyboard: Keyboard,
+        trail: AuditTrail,
+    }
+
+    impl Review {
+        /// Handles a key event like the viewer does
+        fn key(&mut self, key: &Key, state: ElementState, repeat: bool) {
+            self.keyboard.register_event(key, state);
+            let key = self.keyboard.bindings.resolve(key.clone());
+            if let Some(AuditKey::Dispose(disposition)) =
+                self.keyboard.audit_key(&key, state, repeat)
+            {
+                self.trail
+                    .dispose("a.tif", Selection::overlay(0), disposition, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn holding_x_while_dragging_leaves_the_audit_trail_unchanged() {
+        let mut review = Review::default();
+        let x = Key::Character("x".into());
+        review.key(&x, ElementState::Pressed, false);
+        // Auto-repeat while the mouse drags
+        for _ in 0..5 {
+            review.key(&x, ElementState::Pressed, true);
+            assert_eq!(review.keyboard.rotation_constraint().axis, Some(Vec3::X));
+        }
+        review.key(&x, ElementState::Released, false);
+        assert_eq!(review.keyboard.rotation_constraint().axis, None);
+        assert!(review.trail.records(0.0).is_empty());
+    }
+
+    #[test]
+    fn long_press_disposes_once() {
+        let mut review = Review::default();
+        let enter = Key::Named(NamedKey::Enter);
+        review.key(&Key::Named(NamedKey::Control), ElementState::Pressed, false);
+        review.key(&enter, ElementState::Pressed, false);
+        review.key(&enter, ElementState::Pressed, true);
+        review.key(&enter, ElementState::Pressed, true);
+        assert_eq!(review.trail.records(0.0).len(), 1);
+    }
+
+    #[test]
+    fn rebound_actions_keep_the_axis_lock() {
+        let mut review = Review::default();
+        let x = Key::Character("x".into());
+        // Accept and reject are no character actions, binding them fails without moving X
+        assert!(review.keyboard.bindings.bind("reject", "v").is_err());
+        review.keyboard.bindings.bind("fit", "x").unwrap();
+        review.key(&x, ElementState::Pressed, false);
+        assert_eq!(review.keyboard.rotation_constraint().axis, Some(Vec3::X));
+        assert_eq!(
+            review.keyboard.bindings.resolve(x.clone()),
+            Key::Character("f".into())
+        );
+        review.key(&x, ElementState::Released, false);
+
+        // The axis lock is bound on its own, the action of its new key stays
+        review.keyboard.bindings.bind("lock-y", "v").unwrap();
+        let v = Key::Character("v".into());
+        review.key(&v, ElementState::Pressed, false);
+        assert_eq!(review.keyboard.rotation_constraint().axis, Some(Vec3::Y));
+        assert_eq!(review.keyboard.bindings.resolve(v.clone()), v);
+        review.key(&v, ElementState::Released, false);
+        assert_eq!(review.keyboard.rotation_constraint().axis, None);
+        assert!(review.trail.records(0.0).is_empty());
+    }
+}
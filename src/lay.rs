@@ -0,0 +1,111 @@
+// The lay is the direction of the dominant surface pattern, e.g. of the marks left by turning or
+// grinding. Heights change fastest across the marks, so the lay is perpendicular to the mean
+// orientation of the gradients, estimated with their structure tensor.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::image::Image;
+
+/// Below this coherence the gradients point in all directions and there is no lay
+pub const MIN_COHERENCE: f32 = 0.1;
+
+/// Dominant lateral direction of the surface texture
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lay {
+    /// Angle of the marks in degrees from -90 to 90, counterclockwise from the x axis with the
+    /// first row on top
+    pub angle: f32,
+    /// From 0 for isotropic textures to 1 for perfectly parallel marks
+    pub coherence: f32,
+}
+
+/// Lay of `image` in the shown proportions, `pixel_aspect` is the ratio of the y to the x pixel
+/// spacing. At most about `max_samples` gradients are evaluated, `None` without valid gradients.
+pub(crate) fn lay(image: &Image<f32>, pixel_aspect: f32, max_samples: usize) -> Option<Lay> {
+    let (width, height) = (image.size.width.get(), image.size.height.get());
+    if width < 3 || height < 3 {
+        return None;
+    }
+    let pixels = (width - 2) as usize * (height - 2) as usize;
+    let stride = ((pixels as f64 / max_samples.max(1) as f64).sqrt().ceil() as u32).max(1);
+
+    let (mut xx, mut yy, mut xy) = (0.0f64, 0.0f64, 0.0f64);
+    for y in (1..height - 1).step_by(stride as usize) {
+        for x in (1..width - 1).step_by(stride as usize) {
+            // Central differences with y pointing up, as the surface is shown
+            let gx = (image.get_pixel(x + 1, y) - image.get_pixel(x - 1, y)) / 2.0;
+            let gy = (image.get_pixel(x, y - 1) - image.get_pixel(x, y + 1)) / (2.0 * pixel_aspect);
+            if gx.is_finite() && gy.is_finite() {
+                let (gx, gy) = (gx as f64, gy as f64);
+                xx += gx * gx;
+                yy += gy * gy;
+                xy += gx * gy;
+            }
+        }
+    }
+    let energy = xx + yy;
+    if energy <= 0.0 {
+        return None;
+    }
+    let gradient = 0.5 * (2.0 * xy).atan2(xx - yy);
+    let mut angle = (gradient.to_degrees() + 90.0) as f32;
+    if angle > 90.0 {
+        angle -= 180.0;
+    }
+    let coherence = ((xx - yy).powi(2) + 4.0 * xy * xy).sqrt() / energy;
+    Some(Lay {
+        angle,
+        coherence: coherence as f32,
+    })
+}
+
+/// Azimuth in degrees that turns the lay horizontal, of the two directions the one closer to
+/// `azimuth`
+pub(crate) fn horizontal_azimuth(lay: Lay, azimuth: f32) -> f32 {
+    let target = -lay.angle;
+    target + ((azimuth - target) / 180.0).round() * 180.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    /// Marks at `angle` degrees with the pixel aspect `aspect`
+    fn marks(angle: f32, aspect: f32) -> Image<f32> {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let data = (0..64 * 64)
+            .map(|i| {
+                let (x, y) = ((i % 64) as f32, -((i / 64) as f32) * aspect);
+                // Distance across the marks
+                (0.3 * (y * cos - x * sin)).sin()
+            })
+            .collect();
+        Image::new(ImageSize::new(64, 64).unwrap(), data).unwrap()
+    }
+
+    #[test]
+    fn lay_follows_the_marks() {
+        for angle in [0.0, 30.0, -60.0, 90.0] {
+            let lay = lay(&marks(angle, 1.0), 1.0, usize::MAX).unwrap();
+            let error = (lay.angle - angle + 90.0).rem_euclid(180.0) - 90.0;
+            assert!(error.abs() < 1.0, "{} instead of {}", lay.angle, angle);
+            assert!(lay.coherence > 0.9);
+        }
+        // Stretched pixels are measured in the shown proportions
+        let lay = lay(&marks(45.0, 2.0), 2.0, 1000).unwrap();
+        assert!((lay.angle - 45.0).abs() < 2.0, "{}", lay.angle);
+    }
+
+    #[test]
+    fn marks_are_turned_horizontal_the_short_way() {
+        let lay = Lay {
+            angle: 30.0,
+            coherence: 1.0,
+        };
+        assert_eq!(horizontal_azimuth(lay, 0.0), -30.0);
+        assert_eq!(horizontal_azimuth(lay, 170.0), 150.0);
+    }
+}
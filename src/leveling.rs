@@ -0,0 +1,153 @@
+// Raster-scanning instruments measure the surface line by line, and drift between the lines
+// shows up as stripes along the rows. Leveling fits every row on its own and removes the fit.
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use anyhow::anyhow;
+use std::str::FromStr;
+
+use crate::{
+    image::{Image, ImageData},
+    parallel,
+};
+
+/// What is removed from every row
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowLeveling {
+    /// The median of the row, robust against spikes and steps
+    Offset,
+    /// The least-squares line through the row, also removes a tilt along the rows
+    Linear,
+}
+
+impl RowLeveling {
+    pub const ALL: [RowLeveling; 2] = [Self::Offset, Self::Linear];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Offset => "offset",
+            Self::Linear => "linear",
+        }
+    }
+}
+
+impl FromStr for RowLeveling {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RowLeveling::ALL
+            .into_iter()
+            .find(|leveling| leveling.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported row leveling: {}", s))
+    }
+}
+
+/// Level of a row at its center and its slope per pixel, `None` without valid pixels
+fn fit_row(row: &[f32], leveling: RowLeveling) -> Option<(f32, f32)> {
+    let mut values: Vec<f32> = row.iter().copied().filter(|v| v.is_finite()).collect();
+    if values.is_empty() {
+        return None;
+    }
+    match leveling {
+        RowLeveling::Offset => {
+            let middle = values.len() / 2;
+            let (_, median, _) = values.select_nth_unstable_by(middle, f32::total_cmp);
+            Some((*median, 0.0))
+        }
+        RowLeveling::Linear => {
+            let center = (row.len() - 1) as f64 / 2.0;
+            let points = row
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.is_finite())
+                .map(|(x, &v)| (x as f64 - center, v as f64));
+            let (mut n, mut sx, mut sy, mut sxx, mut sxy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for (x, y) in points {
+                n += 1.0;
+                sx += x;
+                sy += y;
+                sxx += x * x;
+                sxy += x * y;
+            }
+            let denominator = n * sxx - sx * sx;
+            let slope = if denominator > 0.0 {
+                (n * sxy - sx * sy) / denominator
+            } else {
+                0.0
+            };
+            let level = (sy - slope * sx) / n;
+            Some((level as f32, slope as f32))
+        }
+    }
+}
+
+/// Copy of `image` with the fit of every row subtracted. The median level of the rows is added
+/// back, so the heights stay where they were. Missing pixels and rows without valid pixels are
+/// kept as they are.
+pub(crate) fn level_rows(image: &Image<f32>, leveling: RowLeveling) -> Image<f32> {
+    let width = image.size.width.get() as usize;
+    let rows: Vec<&[f32]> = image.data.chunks(width).collect();
+    let fits = parallel::map(&rows, |row| fit_row(row, leveling));
+    let mut levels: Vec<f32> = fits.iter().flatten().map(|&(level, _)| level).collect();
+    let base = if levels.is_empty() {
+        0.0
+    } else {
+        let middle = levels.len() / 2;
+        *levels.select_nth_unstable_by(middle, f32::total_cmp).1
+    };
+
+    let mut data = image.data.to_vec();
+    let center = (width - 1) as f32 / 2.0;
+    parallel::for_each_chunk_mut(&mut data, width, |y, row| {
+        if let Some((level, slope)) = fits[y] {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value -= level - base + slope * (x as f32 - center);
+            }
+        }
+    });
+    Image {
+        size: image.size.clone(),
+        data: ImageData::Owned(data),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    #[test]
+    fn offsets_are_removed_despite_spikes() {
+        // Rows shifted by their index, with a spike and a missing pixel
+        let striped = Image::from_fn(ImageSize::new(5, 3).unwrap(), |x, y| match (x, y) {
+            (2, 0) => 100.0,
+            (4, 2) => f32::NAN,
+            _ => 2.0 + y as f32,
+        });
+        let leveled = level_rows(&striped, RowLeveling::Offset);
+        for y in 0..3 {
+            for x in 0..5 {
+                let value = leveled.get_pixel(x, y);
+                match (x, y) {
+                    (2, 0) => assert_eq!(value, 101.0),
+                    (4, 2) => assert!(value.is_nan()),
+                    _ => assert_eq!(value, 3.0, "pixel ({}, {})", x, y),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_trends_are_removed() {
+        let tilted = Image::from_fn(ImageSize::new(4, 4).unwrap(), |x, y| {
+            y as f32 * (x as f32 - 1.5) + 0.5 * y as f32
+        });
+        let leveled = level_rows(&tilted, RowLeveling::Linear);
+        // The median of the row levels 0, 0.5, 1 and 1.5 is the upper middle one
+        assert!(leveled.data.iter().all(|&v| (v - 1.0).abs() < 1e-5));
+        let row = Image::from_fn(ImageSize::new(3, 1).unwrap(), |_, _| f32::NAN);
+        assert!(level_rows(&row, RowLeveling::Linear).data[0].is_nan());
+    }
+}
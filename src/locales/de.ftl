@@ -0,0 +1,191 @@
+window-title = 3D-Datenbetrachter
+language-changed = Sprache auf { $language } gesetzt
+title-loading = Lädt…
+title-loading-percent = Lädt { $percent } %
+title-empty = Oberfläche mit Strg+O öffnen oder hierher ziehen
+demo-surface = Demo-Oberfläche
+
+controls-title = Steuerung
+language = Sprache
+shader-mode = Darstellung
+shader-height = Höhe
+shader-amplitude = Amplitude
+view = Ansicht
+reset-view = Ansicht zurücksetzen
+set-overlay = Overlay anzeigen
+clear-overlay = Overlay entfernen
+focus-selection = Auswahl hervorheben
+annotation-groups = Annotationsgruppen
+export-annotations = CSV exportieren
+color-by-score = Nach Score färben
+review-previous = Vorherige
+review-next = Nächste
+review-by-score = Nach Score
+accept = Annehmen
+reject = Ablehnen
+export-audit = Prüfprotokoll exportieren
+camera-angles = Kamerawinkel
+azimuth = Azimut
+elevation = Elevation
+zoom = Zoom
+set-view = Ansicht übernehmen
+align-lay = Riefen ausrichten
+near-clip = Abschälen
+reference-surface = Referenzfläche
+load-reference = Referenz laden
+tolerance = Toleranz ±
+show-deviation = Abweichung zeigen
+evaluate-tolerance = Auswerten
+checkerboard = Schachbrett
+live-baseline = Live-Basislinie
+collect-frames = Bilder sammeln
+temporal-mean = Mittelwert
+temporal-std-dev = Standardabw.
+register-frames = Bilder ausrichten
+frame-shifts = Bildversätze
+track-selection = Auswahl verfolgen
+export-track = Verlauf exportieren
+tile-size = Kachelgröße (px)
+amplitude-contrast = Amplitudenkontrast
+amplitude-min = Min
+amplitude-max = Max
+apply-range = Bereich anwenden
+clip-percentiles = 1–99 %
+amplitude-colormap = Farbskala
+display-range = Anzeigebereich
+auto-contrast = An Ansicht anpassen
+layer-blend = Ebenen mischen
+blend-brightness = Helligkeit
+blend-overlay = Überlagerung
+threshold = Schwellwert
+threshold-preview = Vorschau
+measure = Messen
+pixel-pitch = Pixelabstand
+pixel-aspect = Pixelseitenverhältnis (Y/X)
+raw-pixels = Rohpixel
+measure-angle = Winkel
+measure-circle = Kreis
+measure-sphere = Kugel
+selection = Auswahl
+selection-hint = Overlay oder Marker anklicken, Enter zeigt seine Eigenschaften
+delete-selection = Löschen
+dataset-info = Datensatz-Info
+dataset-info-empty = Kein Datensatz geladen
+notifications = Meldungen
+clear-notifications = Leeren
+notifications-empty = Noch keine Meldungen
+pixel-under-cursor = Pixel unter dem Mauszeiger
+pixel-hint = Wird beim Bewegen des Mauszeigers aktualisiert
+probe-hint = Alt gedrückt halten, um die 5 × 5 Umgebung zu untersuchen
+probe-mean = Mittelwert
+probe-std = Std.-Abw.
+probe-range = Min / Max
+probe-gradient = Gradient
+debug = Debug
+debug-bounds = Hülle / LOD
+debug-normals = Normalen
+debug-overdraw = Überzeichnung
+
+keyboard-shortcuts = Tastenkürzel
+shortcut-toggle-shader = Darstellung wechseln
+shortcut-toggle-overlay = Overlay ein/aus
+shortcut-focus-selection = Außerhalb des gewählten Overlays abdunkeln
+shortcut-review = Nächste / vorherige Annotation
+shortcut-disposition = Annotation annehmen / ablehnen
+shortcut-overlay-blending = Overlay-Mischung
+shortcut-layer-blend = Ebenenmischung wechseln
+shortcut-reset-view = Ansicht zurücksetzen
+shortcut-switch-language = Sprache wechseln
+shortcut-align-lay = Riefen waagerecht drehen
+shortcut-cut-holes = Löcher bei fehlenden Daten
+shortcut-adaptive-mesh = Adaptives Netz
+shortcut-example-markers = Beispielmarker
+shortcut-turntable = Horizont waagerecht
+shortcut-log-view = Blickwinkel ausgeben
+shortcut-fullscreen = Vollbild
+shortcut-debug-view = Debug-Ansichten durchschalten
+shortcut-toggle-hud = HUD ein/aus
+shortcut-colormap = Farbskala wechseln
+shortcut-auto-contrast = Farbskala an Ansicht anpassen
+shortcut-amplitude-colormap = Amplituden-Farbskala wechseln
+shortcut-amplitude-percentiles = Amplituden bei 1–99 % begrenzen
+shortcut-cvd-simulation = Farbenblindheit simulieren
+shortcut-cycle-units = Anzeigeeinheit wechseln
+shortcut-store-compare = Einstellungen zum Vergleich merken
+shortcut-blink = Mit gemerkten Einstellungen tauschen
+shortcut-auto-blink = Automatisch wechseln
+shortcut-residual = Abweichung von der Referenz umschalten
+shortcut-checkerboard = Schachbrett mit Referenz
+shortcut-baseline = Live-Basislinie neu starten
+shortcut-temporal = Bilder für Mittelwert und Abweichung sammeln
+shortcut-track = Ausgewähltes Overlay über Bilder verfolgen
+shortcut-select = Overlay oder Marker auswählen
+shortcut-delete-selection = Auswahl löschen
+shortcut-nudge-selection = Auswahl verschieben (Shift: 10 px)
+shortcut-selection-properties = Eigenschaften der Auswahl
+shortcut-measure-angle = Winkel messen
+shortcut-fit-circle = Kreis anpassen (Shift: Kugel)
+cancel-jobs = Hintergrundaufgaben abbrechen
+shortcut-add-keyframe = Kamera-Keyframe hinzufügen (Shift: löschen)
+shortcut-play-camera-path = Kamerafahrt abspielen
+shortcut-rotate = Drehen
+shortcut-pan = Verschieben
+shortcut-axis-lock = Um Achse drehen
+shortcut-snap-rotation = In 15°-Schritten drehen
+shortcut-zoom = Zoomen
+shortcut-near-clip = Vorderseite abschälen
+
+notify-angle = Winkel { $in-plane }° in der Ebene, { $spatial }° im Raum, Linien steigen um { $first }° und { $second }°
+notify-radius-fit = Radius { $radius } um [{ $x }/{ $y }]={ $z }, Restfehler RMS { $rms } max. { $max } über { $points } Pixel
+notify-nothing-to-review = Keine Overlays oder Marker zu prüfen
+notify-select-to-dispose = Overlay oder Marker auswählen, um es anzunehmen oder abzulehnen
+notify-loaded = { $dataset } geladen ({ $width } × { $height } Pixel)
+notify-region-lost = Verfolgte Region in dieser Oberfläche nicht gefunden
+notify-surface-failed = Oberfläche konnte nicht gesetzt werden: { $error }
+notify-tile-failed = Kachel der Oberfläche konnte nicht angezeigt werden: { $error }
+notify-reference-loaded = Referenzoberfläche geladen
+notify-reference-failed = Referenzoberfläche konnte nicht gesetzt werden: { $error }
+notify-reference-missing = Zum Vergleich eine Referenzoberfläche laden
+notify-tolerance = { $out } von { $evaluated } Pixeln außerhalb der Toleranz, größte Abweichung { $largest }
+notify-invalid-tolerance = Ungültiges Toleranzband von { $lower } bis { $upper }
+notify-applied = { $step } angewendet
+notify-failed = { $name } fehlgeschlagen: { $error }
+notify-cancelled = { $name } abgebrochen
+notify-no-surface-visible = Keine Oberfläche sichtbar
+notify-lay = Vorzugsrichtung bei { $angle }° (Kohärenz { $coherence }) waagerecht gedreht
+notify-no-lay = Die Oberfläche hat keine Vorzugsrichtung
+notify-nothing-to-undo = Kein Vorverarbeitungsschritt zum Rückgängigmachen
+notify-undone = Vorverarbeitungsschritt rückgängig gemacht
+notify-undo-failed = Rückgängigmachen fehlgeschlagen: { $error }
+notify-extrema = { $peaks } Spitzen und { $valleys } Täler markiert
+notify-report-written = Toleranzbericht nach { $path } geschrieben
+notify-report-failed = Toleranzbericht konnte nicht geschrieben werden: { $error }
+notify-annotations-exported = Annotationen nach { $path } exportiert
+notify-annotations-failed = Annotationen konnten nicht exportiert werden: { $error }
+notify-audit-exported = Prüfprotokoll nach { $path } exportiert
+notify-audit-failed = Prüfprotokoll konnte nicht exportiert werden: { $error }
+notify-figure-exported = Abbildung nach { $path } exportiert
+notify-figure-failed = Abbildung konnte nicht exportiert werden: { $error }
+notify-screenshot-archived = Bildschirmfoto nach { $path } archiviert
+notify-screenshot-failed = Bildschirmfoto konnte nicht archiviert werden: { $error }
+notify-temporal-collecting = Oberflächen für zeitliche Statistik werden gesammelt
+notify-temporal-collected = { $frames } Oberflächen für zeitliche Statistik gesammelt
+notify-temporal-empty = Zuerst Oberflächen für zeitliche Statistik sammeln
+notify-temporal-failed = Zeitliche Statistik konnte nicht gelesen werden: { $error }
+notify-registration-started = Neue Oberflächen werden an der aktuellen ausgerichtet
+notify-registration-stopped = Ausrichten neuer Oberflächen beendet
+notify-tracking-started = { $width } × { $height } Pixel bei { $x }, { $y } werden durch neue Oberflächen verfolgt
+notify-tracking-stopped = Verfolgung der Region beendet
+notify-tracking-invalid = Die zu verfolgende Region muss in der Oberfläche liegen und gültige Pixel haben
+notify-select-to-track = Zum Verfolgen ein Overlay auswählen
+notify-track-exported = Regionsverlauf nach { $path } exportiert
+notify-track-failed = Regionsverlauf konnte nicht exportiert werden: { $error }
+notify-baseline-first-frame = Neue Oberflächen werden mit der aktuellen verglichen
+notify-baseline-rolling = Neue Oberflächen werden mit ihrem gleitenden Mittel verglichen, Gewicht { $weight }
+notify-invalid-display-range = Ungültiger Anzeigebereich von { $min } bis { $max }
+notify-invalid-keyframe-time = Ungültige Schlüsselbildzeit { $seconds }: { $error }
+notify-keyframe = Schlüsselbild { $keyframe } bei { $seconds } s
+notify-camera-path-cleared = Kamerapfad gelöscht
+notify-camera-path-too-short = Ein Kamerapfad braucht mindestens zwei Schlüsselbilder
+notify-camera-path-exported = { $frames } Bilder des Kamerapfads nach { $path } exportiert
+notify-camera-path-failed = Kamerapfad konnte nicht exportiert werden: { $error }
@@ -0,0 +1,191 @@
+window-title = 3D Data Viewer
+language-changed = Language set to { $language }
+title-loading = Loading…
+title-loading-percent = Loading { $percent }%
+title-empty = Open a surface with Ctrl+O or drop it here
+demo-surface = Demo surface
+
+controls-title = Controls
+language = Language
+shader-mode = Shader Mode
+shader-height = Height
+shader-amplitude = Amplitude
+view = View
+reset-view = Reset View
+set-overlay = Set Overlay
+clear-overlay = Clear Overlay
+focus-selection = Focus Selection
+annotation-groups = Annotation Groups
+export-annotations = Export CSV
+color-by-score = Color by Score
+review-previous = Previous
+review-next = Next
+review-by-score = By Score
+accept = Accept
+reject = Reject
+export-audit = Export Audit
+camera-angles = Camera Angles
+azimuth = Azimuth
+elevation = Elevation
+zoom = Zoom
+set-view = Apply View
+align-lay = Align Lay
+near-clip = Peel
+reference-surface = Reference Surface
+load-reference = Load Reference
+tolerance = Tolerance ±
+show-deviation = Show Deviation
+evaluate-tolerance = Evaluate
+checkerboard = Checkerboard
+live-baseline = Live Baseline
+collect-frames = Collect Frames
+temporal-mean = Mean
+temporal-std-dev = Std Dev
+register-frames = Register Frames
+frame-shifts = Frame Shifts
+track-selection = Track Selection
+export-track = Export Track
+tile-size = Tile size (px)
+amplitude-contrast = Amplitude Contrast
+amplitude-min = Min
+amplitude-max = Max
+apply-range = Apply Range
+clip-percentiles = 1–99 %
+amplitude-colormap = Colormap
+display-range = Display Range
+auto-contrast = Fit to View
+layer-blend = Blend Layers
+blend-brightness = Brightness
+blend-overlay = Overlay
+threshold = Threshold
+threshold-preview = Preview
+measure = Measure
+pixel-pitch = Pixel pitch
+pixel-aspect = Pixel aspect (Y/X)
+raw-pixels = Raw Pixels
+measure-angle = Angle
+measure-circle = Circle
+measure-sphere = Sphere
+selection = Selection
+selection-hint = Click an overlay or marker, press Enter for its properties
+delete-selection = Delete
+dataset-info = Dataset Info
+dataset-info-empty = No dataset loaded
+notifications = Notifications
+clear-notifications = Clear
+notifications-empty = No notifications yet
+pixel-under-cursor = Pixel Under Cursor
+pixel-hint = Updates as you move the cursor
+probe-hint = Hold Alt to probe the 5 × 5 neighborhood
+probe-mean = Mean
+probe-std = Std. Dev.
+probe-range = Min / Max
+probe-gradient = Gradient
+debug = Debug
+debug-bounds = Bounds / LOD
+debug-normals = Normals
+debug-overdraw = Overdraw
+
+keyboard-shortcuts = Keyboard Shortcuts
+shortcut-toggle-shader = Toggle Shader
+shortcut-toggle-overlay = Toggle Overlay
+shortcut-focus-selection = Dim outside selected overlay
+shortcut-review = Next / previous annotation
+shortcut-disposition = Accept / reject annotation
+shortcut-overlay-blending = Overlay Blending
+shortcut-layer-blend = Cycle layer blending
+shortcut-reset-view = Reset View
+shortcut-switch-language = Switch Language
+shortcut-align-lay = Turn marks horizontal
+shortcut-cut-holes = Holes at Missing Data
+shortcut-adaptive-mesh = Adaptive Mesh
+shortcut-example-markers = Example Markers
+shortcut-turntable = Level Horizon
+shortcut-log-view = Log View Angles
+shortcut-fullscreen = Fullscreen
+shortcut-debug-view = Cycle debug views
+shortcut-toggle-hud = Show/Hide HUD
+shortcut-colormap = Cycle Colormap
+shortcut-auto-contrast = Fit colormap to view
+shortcut-amplitude-colormap = Cycle amplitude colormap
+shortcut-amplitude-percentiles = Clip amplitudes at 1–99 %
+shortcut-cvd-simulation = Simulate Color Blindness
+shortcut-cycle-units = Cycle display units
+shortcut-store-compare = Store settings to compare
+shortcut-blink = Swap with stored settings
+shortcut-auto-blink = Blink automatically
+shortcut-residual = Toggle deviation from reference
+shortcut-checkerboard = Checkerboard with reference
+shortcut-baseline = Restart live baseline
+shortcut-temporal = Collect frames for mean and deviation
+shortcut-track = Track selected overlay through frames
+shortcut-select = Select overlay or marker
+shortcut-delete-selection = Delete selection
+shortcut-nudge-selection = Move selection (Shift: 10 px)
+shortcut-selection-properties = Selection properties
+shortcut-measure-angle = Measure angle
+shortcut-fit-circle = Fit circle (Shift: sphere)
+cancel-jobs = Cancel background jobs
+shortcut-add-keyframe = Add camera keyframe (Shift: clear)
+shortcut-play-camera-path = Play camera path
+shortcut-rotate = Rotate
+shortcut-pan = Pan
+shortcut-axis-lock = Rotate Around Axis
+shortcut-snap-rotation = Rotate in 15° Steps
+shortcut-zoom = Zoom
+shortcut-near-clip = Peel away the front
+
+notify-angle = Angle { $in-plane }° in plane, { $spatial }° in space, lines rising { $first }° and { $second }°
+notify-radius-fit = Radius { $radius } centered at [{ $x }/{ $y }]={ $z }, residual rms { $rms } max { $max } over { $points } pixels
+notify-nothing-to-review = No overlays or markers to review
+notify-select-to-dispose = Select an overlay or marker to accept or reject
+notify-loaded = Loaded { $dataset } ({ $width } × { $height } pixels)
+notify-region-lost = Tracked region not found in this surface
+notify-surface-failed = Failed to set surface: { $error }
+notify-tile-failed = Failed to show surface tile: { $error }
+notify-reference-loaded = Reference surface loaded
+notify-reference-failed = Failed to set reference surface: { $error }
+notify-reference-missing = Load a reference surface to compare with
+notify-tolerance = { $out } of { $evaluated } pixels out of tolerance, largest violation { $largest }
+notify-invalid-tolerance = Invalid tolerance band from { $lower } to { $upper }
+notify-applied = { $step } applied
+notify-failed = { $name } failed: { $error }
+notify-cancelled = { $name } cancelled
+notify-no-surface-visible = No surface visible
+notify-lay = Lay at { $angle }° (coherence { $coherence }) turned horizontal
+notify-no-lay = The surface has no dominant direction
+notify-nothing-to-undo = No preprocessing step to undo
+notify-undone = Preprocessing step undone
+notify-undo-failed = Failed to undo: { $error }
+notify-extrema = Marked { $peaks } peaks and { $valleys } valleys
+notify-report-written = Wrote tolerance report to { $path }
+notify-report-failed = Failed to write tolerance report: { $error }
+notify-annotations-exported = Exported annotations to { $path }
+notify-annotations-failed = Failed to export annotations: { $error }
+notify-audit-exported = Exported audit trail to { $path }
+notify-audit-failed = Failed to export audit trail: { $error }
+notify-figure-exported = Exported figure to { $path }
+notify-figure-failed = Failed to export figure: { $error }
+notify-screenshot-archived = Archived screenshot to { $path }
+notify-screenshot-failed = Failed to archive screenshot: { $error }
+notify-temporal-collecting = Collecting surfaces for temporal statistics
+notify-temporal-collected = Collected { $frames } surfaces for temporal statistics
+notify-temporal-empty = Collect surfaces for temporal statistics first
+notify-temporal-failed = Failed to read temporal statistics: { $error }
+notify-registration-started = Aligning new surfaces with the current one
+notify-registration-stopped = Stopped aligning new surfaces
+notify-tracking-started = Tracking the { $width } × { $height } pixels at { $x }, { $y } through new surfaces
+notify-tracking-stopped = Stopped tracking the region
+notify-tracking-invalid = Region to track must be inside the surface and have valid pixels
+notify-select-to-track = Select an overlay to track
+notify-track-exported = Exported region track to { $path }
+notify-track-failed = Failed to export region track: { $error }
+notify-baseline-first-frame = Comparing new surfaces with the current one
+notify-baseline-rolling = Comparing new surfaces with their rolling average, weight { $weight }
+notify-invalid-display-range = Invalid display range from { $min } to { $max }
+notify-invalid-keyframe-time = Invalid keyframe time { $seconds }: { $error }
+notify-keyframe = Keyframe { $keyframe } at { $seconds } s
+notify-camera-path-cleared = Camera path cleared
+notify-camera-path-too-short = A camera path needs at least two keyframes
+notify-camera-path-exported = Exported { $frames } frames of the camera path to { $path }
+notify-camera-path-failed = Failed to export camera path: { $error }
@@ -0,0 +1,191 @@
+window-title = 3Dデータビューア
+language-changed = 言語を { $language } に設定しました
+title-loading = 読み込み中…
+title-loading-percent = 読み込み中 { $percent }%
+title-empty = Ctrl+O でサーフェスを開くか、ここにドロップ
+demo-surface = デモサーフェス
+
+controls-title = 操作
+language = 言語
+shader-mode = 表示モード
+shader-height = 高さ
+shader-amplitude = 振幅
+view = ビュー
+reset-view = ビューをリセット
+set-overlay = オーバーレイを表示
+clear-overlay = オーバーレイを消去
+focus-selection = 選択を強調
+annotation-groups = 注釈グループ
+export-annotations = CSVをエクスポート
+color-by-score = スコアで色分け
+review-previous = 前へ
+review-next = 次へ
+review-by-score = スコア順
+accept = 承認
+reject = 却下
+export-audit = 監査ログを書き出す
+camera-angles = カメラ角度
+azimuth = 方位角
+elevation = 仰角
+zoom = ズーム
+set-view = ビューを適用
+align-lay = 筋目を整列
+near-clip = 手前を除去
+reference-surface = 基準面
+load-reference = 基準面を読み込む
+tolerance = 許容差 ±
+show-deviation = 偏差を表示
+evaluate-tolerance = 評価
+checkerboard = チェッカーボード
+live-baseline = ライブ基準
+collect-frames = フレーム収集
+temporal-mean = 平均
+temporal-std-dev = 標準偏差
+register-frames = フレーム位置合わせ
+frame-shifts = フレームのずれ
+track-selection = 選択を追跡
+export-track = 追跡を書き出す
+tile-size = タイルサイズ (px)
+amplitude-contrast = 振幅コントラスト
+amplitude-min = 最小
+amplitude-max = 最大
+apply-range = 範囲を適用
+clip-percentiles = 1–99 %
+amplitude-colormap = カラーマップ
+display-range = 表示範囲
+auto-contrast = 表示に合わせる
+layer-blend = レイヤー合成
+blend-brightness = 明るさ
+blend-overlay = オーバーレイ
+threshold = しきい値
+threshold-preview = プレビュー
+measure = 測定
+pixel-pitch = ピクセルピッチ
+pixel-aspect = ピクセル縦横比 (Y/X)
+raw-pixels = 生ピクセル
+measure-angle = 角度
+measure-circle = 円
+measure-sphere = 球
+selection = 選択
+selection-hint = オーバーレイまたはマーカーをクリックし、Enter でプロパティを表示
+delete-selection = 削除
+dataset-info = データセット情報
+dataset-info-empty = データセットが読み込まれていません
+notifications = 通知
+clear-notifications = クリア
+notifications-empty = 通知はまだありません
+pixel-under-cursor = カーソル位置のピクセル
+pixel-hint = カーソルを動かすと更新されます
+probe-hint = Alt キーを押したままで 5 × 5 の近傍を調べます
+probe-mean = 平均
+probe-std = 標準偏差
+probe-range = 最小 / 最大
+probe-gradient = 勾配
+debug = デバッグ
+debug-bounds = 境界 / LOD
+debug-normals = 法線
+debug-overdraw = オーバードロー
+
+keyboard-shortcuts = キーボードショートカット
+shortcut-toggle-shader = 表示モード切替
+shortcut-toggle-overlay = オーバーレイ切替
+shortcut-focus-selection = 選択したオーバーレイの外側を暗くする
+shortcut-review = 次 / 前の注釈
+shortcut-disposition = 注釈を承認 / 却下
+shortcut-overlay-blending = オーバーレイ合成
+shortcut-layer-blend = レイヤー合成を切り替え
+shortcut-reset-view = ビューをリセット
+shortcut-switch-language = 言語切替
+shortcut-align-lay = 筋目を水平に回転
+shortcut-cut-holes = 欠損データを穴として表示
+shortcut-adaptive-mesh = 適応メッシュ
+shortcut-example-markers = サンプルマーカー
+shortcut-turntable = 水平を維持
+shortcut-log-view = 視点角度を出力
+shortcut-fullscreen = 全画面表示
+shortcut-debug-view = デバッグ表示の切り替え
+shortcut-toggle-hud = HUDの表示切替
+shortcut-colormap = カラーマップ切替
+shortcut-auto-contrast = カラーマップを表示に合わせる
+shortcut-amplitude-colormap = 振幅カラーマップを切り替え
+shortcut-amplitude-percentiles = 振幅を1–99 %でクリップ
+shortcut-cvd-simulation = 色覚異常シミュレーション
+shortcut-cycle-units = 表示単位の切り替え
+shortcut-store-compare = 比較用に設定を保存
+shortcut-blink = 保存した設定と切り替え
+shortcut-auto-blink = 自動で点滅
+shortcut-residual = 基準面からの偏差の切り替え
+shortcut-checkerboard = 参照とのチェッカーボード
+shortcut-baseline = ライブ基準を再開
+shortcut-temporal = 平均と偏差のためにフレームを収集
+shortcut-track = 選択したオーバーレイをフレーム間で追跡
+shortcut-select = オーバーレイまたはマーカーを選択
+shortcut-delete-selection = 選択を削除
+shortcut-nudge-selection = 選択を移動（Shift: 10 px）
+shortcut-selection-properties = 選択のプロパティ
+shortcut-measure-angle = 角度を測定
+shortcut-fit-circle = 円をフィット（Shift: 球）
+cancel-jobs = バックグラウンド処理をキャンセル
+shortcut-add-keyframe = カメラキーフレームを追加（Shift: クリア）
+shortcut-play-camera-path = カメラパスを再生
+shortcut-rotate = 回転
+shortcut-pan = 移動
+shortcut-axis-lock = 軸周りに回転
+shortcut-snap-rotation = 15°刻みで回転
+shortcut-zoom = ズーム
+shortcut-near-clip = 手前を削って表示
+
+notify-angle = 角度 平面内 { $in-plane }°、空間 { $spatial }°、線の傾き { $first }° と { $second }°
+notify-radius-fit = 半径 { $radius }、中心 [{ $x }/{ $y }]={ $z }、残差 RMS { $rms } 最大 { $max }（{ $points } ピクセル）
+notify-nothing-to-review = レビューするオーバーレイやマーカーがありません
+notify-select-to-dispose = 承認または却下するオーバーレイかマーカーを選択してください
+notify-loaded = { $dataset } を読み込みました（{ $width } × { $height } ピクセル）
+notify-region-lost = 追跡中の領域がこのサーフェスに見つかりません
+notify-surface-failed = サーフェスを設定できませんでした: { $error }
+notify-tile-failed = サーフェスのタイルを表示できませんでした: { $error }
+notify-reference-loaded = 参照サーフェスを読み込みました
+notify-reference-failed = 参照サーフェスを設定できませんでした: { $error }
+notify-reference-missing = 比較する参照サーフェスを読み込んでください
+notify-tolerance = { $evaluated } ピクセル中 { $out } ピクセルが公差外、最大逸脱 { $largest }
+notify-invalid-tolerance = 無効な公差範囲: { $lower } から { $upper }
+notify-applied = { $step } を適用しました
+notify-failed = { $name } に失敗しました: { $error }
+notify-cancelled = { $name } をキャンセルしました
+notify-no-surface-visible = 表示中のサーフェスがありません
+notify-lay = { $angle }° の筋目（コヒーレンス { $coherence }）を水平にしました
+notify-no-lay = サーフェスに支配的な方向がありません
+notify-nothing-to-undo = 元に戻す前処理ステップがありません
+notify-undone = 前処理ステップを元に戻しました
+notify-undo-failed = 元に戻せませんでした: { $error }
+notify-extrema = 山 { $peaks } 個と谷 { $valleys } 個をマークしました
+notify-report-written = 公差レポートを { $path } に書き出しました
+notify-report-failed = 公差レポートを書き出せませんでした: { $error }
+notify-annotations-exported = 注釈を { $path } にエクスポートしました
+notify-annotations-failed = 注釈をエクスポートできませんでした: { $error }
+notify-audit-exported = 監査証跡を { $path } にエクスポートしました
+notify-audit-failed = 監査証跡をエクスポートできませんでした: { $error }
+notify-figure-exported = 図を { $path } にエクスポートしました
+notify-figure-failed = 図をエクスポートできませんでした: { $error }
+notify-screenshot-archived = スクリーンショットを { $path } に保存しました
+notify-screenshot-failed = スクリーンショットを保存できませんでした: { $error }
+notify-temporal-collecting = 時間統計のためにサーフェスを収集しています
+notify-temporal-collected = 時間統計のために { $frames } 個のサーフェスを収集しました
+notify-temporal-empty = 先に時間統計のためのサーフェスを収集してください
+notify-temporal-failed = 時間統計を読み取れませんでした: { $error }
+notify-registration-started = 新しいサーフェスを現在のサーフェスに位置合わせします
+notify-registration-stopped = 新しいサーフェスの位置合わせを停止しました
+notify-tracking-started = { $x }, { $y } の { $width } × { $height } ピクセルを新しいサーフェスで追跡します
+notify-tracking-stopped = 領域の追跡を停止しました
+notify-tracking-invalid = 追跡する領域はサーフェス内にあり、有効なピクセルを含む必要があります
+notify-select-to-track = 追跡するオーバーレイを選択してください
+notify-track-exported = 領域の追跡結果を { $path } にエクスポートしました
+notify-track-failed = 領域の追跡結果をエクスポートできませんでした: { $error }
+notify-baseline-first-frame = 新しいサーフェスを現在のサーフェスと比較します
+notify-baseline-rolling = 新しいサーフェスを移動平均と比較します（重み { $weight }）
+notify-invalid-display-range = 無効な表示範囲: { $min } から { $max }
+notify-invalid-keyframe-time = 無効なキーフレーム時刻 { $seconds }: { $error }
+notify-keyframe = キーフレーム { $keyframe }（{ $seconds } 秒）
+notify-camera-path-cleared = カメラパスを消去しました
+notify-camera-path-too-short = カメラパスには少なくとも 2 つのキーフレームが必要です
+notify-camera-path-exported = カメラパスの { $frames } フレームを { $path } にエクスポートしました
+notify-camera-path-failed = カメラパスをエクスポートできませんでした: { $error }
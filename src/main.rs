@@ -1,11 +1,10 @@
 use anyhow::anyhow;
-use futures::{FutureExt, future::Shared};
-use glam::{Vec2, Vec3};
+use futures::FutureExt;
+use glam::Vec3;
 use log::error;
-use std::{borrow::Cow, sync::Arc, vec};
+use std::{cell::Cell, collections::HashMap, rc::Rc, sync::Arc, vec};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use winit::{
     application::ApplicationHandler,
     event::WindowEvent,
@@ -18,44 +17,542 @@ use winit::{
 enum ViewerCommand {
     SetSurface(Image<f32>),
     SetAmplitude(Image<u16>),
-    SetState(State),
+    SetState(Box<State>),
     BackToOrigin,
+    SetView {
+        azimuth: f32,
+        elevation: f32,
+        zoom: Option<f32>,
+    },
+    /// Fraction of the surface depth clipped in front of the camera
+    SetNearClip(f32),
     SetAmplitudeShader,
     SetHeightShader,
     SetOverlays(Arc<Vec<Overlay>>),
     ClearOverlays,
-    GetPixel(
-        futures::channel::oneshot::Sender<
-            Shared<std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>>>,
-        >,
-    ),
+    SetLanguage(Language),
+    SetUiScale(Option<f32>),
+    SetOverlayBlendMode(OverlayBlendMode),
+    SetDimOutsideSelection(bool),
+    SetCutHoles(bool),
+    SetDebugView(DebugView),
+    SetMeshMode(MeshMode),
+    SetRotationMode(RotationMode),
+    SetPickingMethod(Option<PickingMethod>),
+    SetCameraSync(Option<String>),
+    SetMarkers(Arc<Vec<Marker>>),
+    ClearMarkers,
+    #[cfg(not(target_arch = "wasm32"))]
+    LoadFile(String),
+    /// Shows the built-in demo surface, for starting without a file
+    #[cfg(not(target_arch = "wasm32"))]
+    LoadDemo,
+    /// Opens a new window, optionally showing the given file
+    #[cfg(not(target_arch = "wasm32"))]
+    OpenWindow(Option<String>),
+    /// Asks for a file with the dialog of the system and shows it instead of the current one
+    #[cfg(not(target_arch = "wasm32"))]
+    OpenFileDialog,
+    /// Loads the newest file of a directory whenever one appears, `None` stops watching
+    #[cfg(not(target_arch = "wasm32"))]
+    WatchFolder(Option<watch::FolderWatch>),
+    /// Writes the input of the active window to a file, `None` stops recording
+    #[cfg(not(target_arch = "wasm32"))]
+    RecordInput(Option<input::InputRecorder>),
+    /// Feeds recorded input to the active window, `None` stops the replay
+    #[cfg(not(target_arch = "wasm32"))]
+    ReplayInput(Option<input::InputReplay>),
+    /// Runs the temporal statistics of every window on another device, `None` on the one drawing
+    #[cfg(not(target_arch = "wasm32"))]
+    SetComputeDevice(Option<compute::ComputeDevice>),
+    /// Renders every window for a wide-gamut display and tags exported images with its profile
+    #[cfg(not(target_arch = "wasm32"))]
+    SetColorSpace(color_space::OutputColorSpace),
+    SetColormap(Colormap),
+    SetAmplitudeColormap(Colormap),
+    SetAmplitudeRange(AmplitudeRange),
+    SetCompositing(Compositing),
+    GetAmplitudeLimits(futures::channel::oneshot::Sender<Option<AmplitudeLimits>>),
+    /// Values mapped to the ends of the colormap of a layer, heights in the display unit
+    SetDisplayRange {
+        layer: Layer,
+        min: f32,
+        max: f32,
+    },
+    /// Histogram of a layer, heights in the display unit
+    GetHistogram {
+        layer: Layer,
+        sender: futures::channel::oneshot::Sender<Option<Histogram>>,
+    },
+    /// Clicks pick the points of a measurement instead of selecting, `None` ends measuring
+    SetMeasureTool(Option<MeasureKind>),
+    /// Lateral size of a pixel in the display unit
+    SetPixelPitch(f32),
+    /// Ratio of the y to the x spacing of the pixels
+    SetPixelAspect(f32),
+    /// Shows the surface with the pixel aspect, `false` in raw pixel space
+    SetPhysicalProportions(bool),
+    GetAngle(futures::channel::oneshot::Sender<Option<AngleMeasurement>>),
+    /// Circle or sphere fitted with the measure tool, lengths in the display unit
+    GetRadiusFit(futures::channel::oneshot::Sender<Option<RadiusFit>>),
+    /// Overlay or marker last clicked on
+    GetSelection(futures::channel::oneshot::Sender<Option<Selection>>),
+    DeleteSelection,
+    GetJobs(futures::channel::oneshot::Sender<Vec<JobStatus>>),
+    GetNotifications(futures::channel::oneshot::Sender<Vec<Notification>>),
+    /// Metadata read from the file of the surface, e.g. TIFF tags
+    SetDatasetInfo(DatasetInfo),
+    GetDatasetInfo(futures::channel::oneshot::Sender<Vec<DatasetInfoEntry>>),
+    /// Steps since the surface was loaded, see `ProvenanceLog`
+    GetProvenance(futures::channel::oneshot::Sender<Vec<ProvenanceEntry>>),
+    /// Layer, colormaps, display ranges, z-scale, camera and overlay visibility at once
+    GetViewState(futures::channel::oneshot::Sender<Option<ViewState>>),
+    ApplyViewState(ViewState),
+    /// URLs the surface and amplitude were loaded from, written into deep links
+    #[cfg(target_arch = "wasm32")]
+    SetDatasetUrls {
+        surface: Option<String>,
+        amplitude: Option<String>,
+    },
+    /// Keeps the URL hash of the page up to date with the view, see `deep_link`
+    #[cfg(target_arch = "wasm32")]
+    SetUrlHashSync(bool),
+    /// Rows of a surface streamed from a backend, see `raw_image`
+    #[cfg(target_arch = "wasm32")]
+    AddSurfaceTile(raw_image::SurfaceTile),
+    ClearNotifications,
+    /// Adds the current camera to the camera path at a time in seconds, `None` appends it
+    AddKeyframe(Option<f32>),
+    ClearKeyframes,
+    PlayCameraPath(bool),
+    /// Writes the frames of the camera path as PNG files into a directory at a frame rate
+    #[cfg(not(target_arch = "wasm32"))]
+    ExportCameraPath {
+        dir: Option<String>,
+        fps: u32,
+    },
+    /// Cancels the background job with the ID, `None` cancels all
+    CancelJob(Option<u32>),
+    SetCvdSimulation(CvdSimulation),
+    /// Applies only the latest cursor move per frame, `false` handles every move right away
+    SetCoalesceMouseMoves(bool),
+    /// Frame rate cap of animations, `None` renders them at the display refresh rate
+    SetMaxFps(Option<f32>),
+    SetNavigation(NavigationSettings),
+    /// Frame time to hold with a coarser mesh while the camera moves, `None` always draws the
+    /// full mesh
+    SetTargetFrameTime(Option<web_time::Duration>),
+    /// Navigates with gamepads and 6DoF devices like a 3Dconnexion SpaceMouse
+    SetGamepadEnabled(bool),
+    SetFullscreen(bool),
+    SetHudVisible(bool),
+    /// Nominal surface, e.g. from CAD, the surface is compared with
+    SetReference(Image<f32>),
+    #[cfg(not(target_arch = "wasm32"))]
+    LoadReference(String),
+    SetResidualMode(bool),
+    /// Starts the per-pixel statistics of the surfaces anew with the current one and adds every
+    /// surface loaded from now on, `false` stops adding surfaces
+    SetTemporalCollecting(bool),
+    /// Mean and standard deviation maps of the collected surfaces, `None` without any
+    #[cfg(target_arch = "wasm32")]
+    GetTemporalMaps(futures::channel::oneshot::Sender<Option<temporal::TemporalFuture>>),
+    /// Shows a map of the temporal statistics instead of the surface
+    #[cfg(target_arch = "wasm32")]
+    SetTemporalMap(Box<TemporalMaps>, TemporalMap),
+    /// Compares the surfaces loaded from now on with their first one or a rolling average instead
+    /// of a reference, starting with the current surface. `None` stops keeping a baseline.
+    SetBaseline(Option<BaselineMode>),
+    /// Aligns every surface loaded from now on with the first of them, `false` stops
+    SetRegistration(bool),
+    /// Follows the region of the current surface through the surfaces loaded from now on,
+    /// `None` stops
+    TrackRegion(Option<Region>),
+    /// Tracks the bounding box of the selected overlay
+    TrackSelection,
+    /// Positions and height statistics of the tracked region. Returned if a sender is given,
+    /// `None` without a tracked region, otherwise written next to the dataset.
+    ExportRegionTrack {
+        format: TrackFormat,
+        sender: Option<futures::channel::oneshot::Sender<Option<String>>>,
+    },
+    /// Shifts of the surfaces loaded since registration was turned on
+    #[cfg(target_arch = "wasm32")]
+    GetFrameShifts(futures::channel::oneshot::Sender<Vec<FrameShift>>),
+    /// Alternates tiles of the surface and the reference, `None` as tile size takes an eighth of
+    /// the longer side
+    SetCheckerboard {
+        enabled: bool,
+        tile_size: Option<u32>,
+    },
+    EvaluateTolerance(futures::channel::oneshot::Sender<Option<ToleranceEvaluation>>),
+    /// Writes a pass/fail report of the tolerance evaluation to the given HTML file
+    #[cfg(not(target_arch = "wasm32"))]
+    WriteToleranceReport(Option<String>),
+    /// Writes the heights as shown with legend, scale bar and caption to the given SVG file
+    #[cfg(not(target_arch = "wasm32"))]
+    ExportFigure(Option<String>),
+    /// Tolerance band in the display unit
+    SetTolerance {
+        lower: f32,
+        upper: f32,
+    },
+    StoreBlinkState,
+    SwapBlinkState,
+    SetBlinkInterval(Option<web_time::Duration>),
+    SetDatasetName(String),
+    SetLoading(Option<Progress>),
+    GetPixel(futures::channel::oneshot::Sender<PixelFuture>),
+    GetViewTransform(futures::channel::oneshot::Sender<Option<ViewTransform>>),
+    /// Unit the surface heights are stored in
+    SetDataUnit(Unit),
+    /// Unit heights are shown in
+    SetDisplayUnit(Unit),
+    GetUnits(futures::channel::oneshot::Sender<Units>),
+    /// Statistics of the `size` × `size` pixels around `pixel` in the display unit
+    ProbeNeighborhood {
+        pixel: [u32; 2],
+        size: u32,
+        sender: futures::channel::oneshot::Sender<Option<NeighborhoodStats>>,
+    },
+    /// Marks the `count` highest peaks and deepest valleys at least `min_separation` pixels
+    /// apart, the result is returned in the display unit if a sender is given
+    FindExtrema {
+        count: u32,
+        min_separation: f32,
+        sender: Option<futures::channel::oneshot::Sender<Vec<Extremum>>>,
+    },
+    /// Removes the offset or trend of every row from the surface
+    LevelRows(RowLeveling),
+    /// Replaces pixels deviating by more than `threshold` noise levels from their neighbors
+    Despike(f32),
+    /// Leveling applied to every surface that is loaded, `None` keeps them as they are
+    SetRowLeveling(Option<RowLeveling>),
+    /// Restores the surface before the last preprocessing step like `LevelRows`
+    UndoPreprocessing,
+    /// Height in the display unit that splits the surface into two colors, `None` ends the
+    /// preview
+    SetThreshold(Option<f32>),
+    /// Pixels and areas on both sides of the threshold, `None` without threshold
+    GetThresholdAreas(futures::channel::oneshot::Sender<Option<ThresholdAreas>>),
+    /// Spreads the height colormap over the heights in the viewport, the range is returned in
+    /// the display unit if a sender is given, `None` if no surface is visible
+    AutoContrastVisible(Option<futures::channel::oneshot::Sender<Option<[f32; 2]>>>),
+    /// Turns the surface so that its lay runs horizontally, the lay is returned if a sender is
+    /// given, `None` without a dominant direction
+    AlignLay(Option<futures::channel::oneshot::Sender<Option<Lay>>>),
+    /// Shows or hides the markers and overlays of an annotation group
+    SetGroupVisible {
+        group: String,
+        visible: bool,
+    },
+    /// Draws the markers and overlays of an annotation group in a color, `None` restores their
+    /// own colors
+    SetGroupColor {
+        group: String,
+        color: Option<[u8; 4]>,
+    },
+    /// Names of the annotation groups of the markers and overlays
+    GetAnnotationGroups(futures::channel::oneshot::Sender<Vec<String>>),
+    /// Markers and overlays as CSV, only those of `groups` if given and else those shown. The CSV
+    /// is returned if a sender is given, `None` without a surface, otherwise it is written next
+    /// to the dataset.
+    ExportAnnotations {
+        groups: Option<Vec<String>>,
+        sender: Option<futures::channel::oneshot::Sender<Option<String>>>,
+    },
+    /// Colors the overlays by a metadata value, `None` restores their own colors
+    SetOverlayColorRamp(Option<ColorRamp>),
+    /// Hides the overlays with a metadata value below a minimum, `None` shows all
+    SetOverlayFilter(Option<ScoreFilter>),
+    /// Selects the next shown overlay or marker in the review order and frames it, the previous
+    /// one if `false`
+    StepReview(bool),
+    SetReviewOrder(ReviewOrder),
+    /// Records the verdict on the selection in the audit trail and reviews the next annotation
+    SetDisposition(Disposition),
+    /// Audit trail as CSV, or JSON if `json`, and its signature. Both are returned if a sender
+    /// is given, otherwise they are written next to the dataset.
+    ExportAuditTrail {
+        json: bool,
+        sender: Option<futures::channel::oneshot::Sender<[String; 2]>>,
+    },
+    /// Key for HMAC-SHA256 signatures of the audit trail, `None` for plain SHA-256 checksums
+    SetAuditKey(Option<Vec<u8>>),
+}
+
+impl ViewerCommand {
+    /// Name of the command for crash reports, without its data
+    fn name(&self) -> &'static str {
+        match self {
+            Self::SetSurface(..) => "SetSurface",
+            Self::SetAmplitude(..) => "SetAmplitude",
+            Self::SetState(..) => "SetState",
+            Self::BackToOrigin => "BackToOrigin",
+            Self::SetView { .. } => "SetView",
+            Self::SetNearClip(..) => "SetNearClip",
+            Self::SetAmplitudeShader => "SetAmplitudeShader",
+            Self::SetHeightShader => "SetHeightShader",
+            Self::SetOverlays(..) => "SetOverlays",
+            Self::ClearOverlays => "ClearOverlays",
+            Self::SetLanguage(..) => "SetLanguage",
+            Self::SetUiScale(..) => "SetUiScale",
+            Self::SetOverlayBlendMode(..) => "SetOverlayBlendMode",
+            Self::SetDimOutsideSelection(..) => "SetDimOutsideSelection",
+            Self::SetCutHoles(..) => "SetCutHoles",
+            Self::SetDebugView(..) => "SetDebugView",
+            Self::SetMeshMode(..) => "SetMeshMode",
+            Self::SetRotationMode(..) => "SetRotationMode",
+            Self::SetPickingMethod(..) => "SetPickingMethod",
+            Self::SetCameraSync(..) => "SetCameraSync",
+            Self::SetMarkers(..) => "SetMarkers",
+            Self::ClearMarkers => "ClearMarkers",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::LoadFile(..) => "LoadFile",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::LoadDemo => "LoadDemo",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::OpenWindow(..) => "OpenWindow",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::OpenFileDialog => "OpenFileDialog",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::WatchFolder(..) => "WatchFolder",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::RecordInput(..) => "RecordInput",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::ReplayInput(..) => "ReplayInput",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::SetComputeDevice(..) => "SetComputeDevice",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::SetColorSpace(..) => "SetColorSpace",
+            Self::SetColormap(..) => "SetColormap",
+            Self::SetAmplitudeColormap(..) => "SetAmplitudeColormap",
+            Self::SetCompositing(..) => "SetCompositing",
+            Self::SetAmplitudeRange(..) => "SetAmplitudeRange",
+            Self::GetAmplitudeLimits(..) => "GetAmplitudeLimits",
+            Self::SetDisplayRange { .. } => "SetDisplayRange",
+            Self::GetHistogram { .. } => "GetHistogram",
+            Self::SetMeasureTool(..) => "SetMeasureTool",
+            Self::SetPixelPitch(..) => "SetPixelPitch",
+            Self::SetPixelAspect(..) => "SetPixelAspect",
+            Self::SetPhysicalProportions(..) => "SetPhysicalProportions",
+            Self::GetAngle(..) => "GetAngle",
+            Self::GetRadiusFit(..) => "GetRadiusFit",
+            Self::GetSelection(..) => "GetSelection",
+            Self::DeleteSelection => "DeleteSelection",
+            Self::GetJobs(..) => "GetJobs",
+            Self::GetNotifications(..) => "GetNotifications",
+            Self::SetDatasetInfo(..) => "SetDatasetInfo",
+            Self::GetDatasetInfo(..) => "GetDatasetInfo",
+            Self::GetProvenance(..) => "GetProvenance",
+            Self::GetViewState(..) => "GetViewState",
+            Self::ApplyViewState(..) => "ApplyViewState",
+            #[cfg(target_arch = "wasm32")]
+            Self::SetDatasetUrls { .. } => "SetDatasetUrls",
+            #[cfg(target_arch = "wasm32")]
+            Self::SetUrlHashSync(..) => "SetUrlHashSync",
+            #[cfg(target_arch = "wasm32")]
+            Self::AddSurfaceTile(..) => "AddSurfaceTile",
+            Self::ClearNotifications => "ClearNotifications",
+            Self::AddKeyframe(..) => "AddKeyframe",
+            Self::ClearKeyframes => "ClearKeyframes",
+            Self::PlayCameraPath(..) => "PlayCameraPath",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::ExportCameraPath { .. } => "ExportCameraPath",
+            Self::CancelJob(..) => "CancelJob",
+            Self::SetCvdSimulation(..) => "SetCvdSimulation",
+            Self::SetCoalesceMouseMoves(..) => "SetCoalesceMouseMoves",
+            Self::SetMaxFps(..) => "SetMaxFps",
+            Self::SetNavigation(..) => "SetNavigation",
+            Self::SetTargetFrameTime(..) => "SetTargetFrameTime",
+            Self::SetGamepadEnabled(..) => "SetGamepadEnabled",
+            Self::SetFullscreen(..) => "SetFullscreen",
+            Self::SetHudVisible(..) => "SetHudVisible",
+            Self::SetReference(..) => "SetReference",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::LoadReference(..) => "LoadReference",
+            Self::SetResidualMode(..) => "SetResidualMode",
+            Self::SetBaseline(..) => "SetBaseline",
+            Self::SetRegistration(..) => "SetRegistration",
+            Self::TrackRegion(..) => "TrackRegion",
+            Self::TrackSelection => "TrackSelection",
+            Self::ExportRegionTrack { .. } => "ExportRegionTrack",
+            #[cfg(target_arch = "wasm32")]
+            Self::GetFrameShifts(..) => "GetFrameShifts",
+            Self::SetTemporalCollecting(..) => "SetTemporalCollecting",
+            #[cfg(target_arch = "wasm32")]
+            Self::GetTemporalMaps(..) => "GetTemporalMaps",
+            #[cfg(target_arch = "wasm32")]
+            Self::SetTemporalMap(..) => "SetTemporalMap",
+            Self::SetCheckerboard { .. } => "SetCheckerboard",
+            Self::EvaluateTolerance(..) => "EvaluateTolerance",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::WriteToleranceReport(..) => "WriteToleranceReport",
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::ExportFigure(..) => "ExportFigure",
+            Self::SetTolerance { .. } => "SetTolerance",
+            Self::StoreBlinkState => "StoreBlinkState",
+            Self::SwapBlinkState => "SwapBlinkState",
+            Self::SetBlinkInterval(..) => "SetBlinkInterval",
+            Self::SetDatasetName(..) => "SetDatasetName",
+            Self::SetLoading(..) => "SetLoading",
+            Self::GetPixel(..) => "GetPixel",
+            Self::GetViewTransform(..) => "GetViewTransform",
+            Self::SetDataUnit(..) => "SetDataUnit",
+            Self::SetDisplayUnit(..) => "SetDisplayUnit",
+            Self::GetUnits(..) => "GetUnits",
+            Self::ProbeNeighborhood { .. } => "ProbeNeighborhood",
+            Self::FindExtrema { .. } => "FindExtrema",
+            Self::LevelRows(..) => "LevelRows",
+            Self::SetRowLeveling(..) => "SetRowLeveling",
+            Self::Despike(..) => "Despike",
+            Self::UndoPreprocessing => "UndoPreprocessing",
+            Self::SetThreshold(..) => "SetThreshold",
+            Self::GetThresholdAreas(..) => "GetThresholdAreas",
+            Self::AutoContrastVisible(..) => "AutoContrastVisible",
+            Self::AlignLay(..) => "AlignLay",
+            Self::SetGroupVisible { .. } => "SetGroupVisible",
+            Self::SetGroupColor { .. } => "SetGroupColor",
+            Self::GetAnnotationGroups(..) => "GetAnnotationGroups",
+            Self::ExportAnnotations { .. } => "ExportAnnotations",
+            Self::SetOverlayColorRamp(..) => "SetOverlayColorRamp",
+            Self::SetOverlayFilter(..) => "SetOverlayFilter",
+            Self::StepReview(..) => "StepReview",
+            Self::SetReviewOrder(..) => "SetReviewOrder",
+            Self::SetDisposition(..) => "SetDisposition",
+            Self::ExportAuditTrail { .. } => "ExportAuditTrail",
+            Self::SetAuditKey(..) => "SetAuditKey",
+        }
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub struct WasmViewer {
     proxy: Option<winit::event_loop::EventLoopProxy<ViewerCommand>>,
+    localizer: Localizer,
+    power_preference: wgpu::PowerPreference,
+    /// Applied to the downloads of `load_url` and `stream_surface`
+    download: download::DownloadOptions,
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 impl WasmViewer {
     pub fn new() -> Result<Self, wasm_bindgen::JsValue> {
-        Ok(Self { proxy: None })
+        Ok(Self {
+            proxy: None,
+            localizer: Localizer::new(Language::from_environment()),
+            power_preference: wgpu::PowerPreference::default(),
+            download: download::DownloadOptions::default(),
+        })
+    }
+
+    /// Shaded preview of a TIFF file with at most `max_dim` pixels on the longer side, without
+    /// loading it into a viewer, e.g. for file lists. The pixels are sRGB RGBA rows like those
+    /// of `ImageData`.
+    pub fn thumbnail(
+        data: Vec<u8>,
+        max_dim: u32,
+        colormap: Option<String>,
+    ) -> Result<image::RgbaImage, wasm_bindgen::JsValue> {
+        let colormap = colormap
+            .map(|colormap| colormap.parse())
+            .transpose()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+            .unwrap_or_default();
+        let image = Image::<f32>::try_from(data)
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        Ok(thumbnail::thumbnail(&image, max_dim, colormap))
+    }
+
+    /// GPU to request, "low" to save battery, "high" for a discrete GPU or "default".
+    /// Only takes effect when called before `run`.
+    pub fn set_power_preference(&mut self, preference: &str) -> Result<(), wasm_bindgen::JsValue> {
+        self.power_preference = power::parse_power_preference(preference)
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Shows a surface with known properties, e.g. `generate_surface("rough", 512, 512,
+    /// "height=0.5,period=8")` for a rough surface with Sq 0.5, see `generator`
+    pub fn generate_surface(
+        &self,
+        kind: &str,
+        width: u32,
+        height: u32,
+        params: &str,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let Some(proxy) = &self.proxy else {
+            return Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ));
+        };
+        let generator = Generator {
+            kind: kind
+                .parse()
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?,
+            params: params
+                .parse()
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?,
+        };
+        let size = image::ImageSize::new(width, height)
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        proxy
+            .send_event(ViewerCommand::SetDatasetName(generator.to_string()))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        proxy
+            .send_event(ViewerCommand::SetSurface(generator.generate(size)))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Tries the downloads of `load_url` `attempts` times in total, waiting `initial_delay_ms`
+    /// before the first retry and twice as long before every further one, up to `max_delay_ms`
+    pub fn set_download_retries(
+        &mut self,
+        attempts: u32,
+        initial_delay_ms: u32,
+        max_delay_ms: u32,
+    ) {
+        self.download.retry = download::RetryPolicy {
+            attempts: attempts.max(1),
+            initial_delay: web_time::Duration::from_millis(initial_delay_ms.into()),
+            max_delay: web_time::Duration::from_millis(max_delay_ms.into()),
+        };
+    }
+
+    /// Sends the header with every request of `load_url` and `stream_surface`, e.g.
+    /// `Authorization` with `Bearer <token>` for SSO-protected APIs. `undefined` removes it.
+    pub fn set_request_header(&mut self, name: &str, value: Option<String>) {
+        self.download.set_header(name, value);
+    }
+
+    /// Function called with the URL before every request, returning an object of further
+    /// headers or a promise of one, so that short-lived tokens can be refreshed. `undefined`
+    /// removes it.
+    pub fn set_request_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.download.callback = callback;
+    }
+
+    /// Sends the cookies of the page with cross-origin requests, for session-based SSO
+    pub fn set_include_credentials(&mut self, include: bool) {
+        self.download.include_credentials = include;
     }
 
     pub fn run(&mut self) -> Result<(), wasm_bindgen::JsValue> {
         console_log::init_with_level(log::Level::Info).map_err(|e| {
             wasm_bindgen::JsValue::from_str(&format!("Error initializing console_log: {}", e))
         })?;
-        console_error_panic_hook::set_once();
+        crash::install();
 
         let event_loop = EventLoop::with_user_event().build().map_err(|e| {
             wasm_bindgen::JsValue::from_str(&format!("Error initializing console_log: {}", e))
         })?;
         self.proxy = Some(event_loop.create_proxy());
+        let power_preference = self.power_preference;
         wasm_bindgen_futures::spawn_local(async move {
-            let mut app = ImageViewer3D::new(&event_loop);
+            let mut app = ImageViewer3D::new(&event_loop, power_preference);
             event_loop
                 .run_app(&mut app)
                 .map_err(|e| {
@@ -71,11 +568,29 @@ impl WasmViewer {
 
     pub async fn set_surface(&self, data: Vec<u8>) -> Result<(), wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
+            let sha256 = provenance::sha256_hex(&data);
+            let data = compression::decompress(data)
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            let info = DatasetInfo::from_bytes(&data).map(|info| DatasetInfo {
+                sha256: Some(sha256),
+                ..info
+            });
             let image = Image::<f32>::try_from(data)
                 .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
             proxy
                 .send_event(ViewerCommand::SetSurface(image))
                 .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            proxy
+                .send_event(ViewerCommand::SetDatasetUrls {
+                    surface: None,
+                    amplitude: None,
+                })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            if let Some(info) = info {
+                proxy
+                    .send_event(ViewerCommand::SetDatasetInfo(info))
+                    .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            }
             Ok(())
         } else {
             Err(wasm_bindgen::JsValue::from_str(
@@ -99,28 +614,198 @@ impl WasmViewer {
         }
     }
 
-    pub async fn get_pixel_value(&self) -> Result<Vec<f32>, wasm_bindgen::JsValue> {
+    /// Loads a surface TIFF and optionally a separate amplitude TIFF from URLs, which deep links
+    /// then point to, see `set_url_hash_sync`. Failed downloads are retried and resumed, see
+    /// `set_download_retries`.
+    pub async fn load_url(
+        &self,
+        url: String,
+        amplitude_url: Option<String>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let Some(proxy) = &self.proxy else {
+            return Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ));
+        };
+        proxy
+            .send_event(ViewerCommand::SetLoading(Some(Progress { fraction: None })))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        let result = async {
+            self.set_surface(fetch(&url, &self.download, proxy).await?)
+                .await?;
+            if let Some(amplitude_url) = &amplitude_url {
+                self.set_amplitude(fetch(amplitude_url, &self.download, proxy).await?)
+                    .await?;
+            }
+            Ok::<_, wasm_bindgen::JsValue>(())
+        }
+        .await;
+        proxy
+            .send_event(ViewerCommand::SetLoading(None))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        result?;
+        proxy
+            .send_event(ViewerCommand::SetDatasetName(file_name(&url).to_owned()))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        proxy
+            .send_event(ViewerCommand::SetDatasetUrls {
+                surface: Some(url),
+                amplitude: amplitude_url,
+            })
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        Ok(())
+    }
+
+    /// Loads a surface from a stream of raw tiles, see `raw_image`. Its rows are shown as they
+    /// arrive, so that large surfaces appear before the download completes.
+    pub async fn stream_surface(&self, url: String) -> Result<(), wasm_bindgen::JsValue> {
+        let Some(proxy) = &self.proxy else {
+            return Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ));
+        };
+        proxy
+            .send_event(ViewerCommand::SetDatasetName(file_name(&url).to_owned()))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        let reader: web_sys::ReadableStreamDefaultReader = fetch_response(&url, &self.download)
+            .await?
+            .body()
+            .ok_or_else(|| wasm_bindgen::JsValue::from_str("Error: response has no body"))?
+            .get_reader()
+            .dyn_into()?;
+        let mut tiles = raw_image::TileReader::default();
+        loop {
+            let chunk = wasm_bindgen_futures::JsFuture::from(reader.read()).await?;
+            if js_sys::Reflect::get(&chunk, &"done".into())?.is_truthy() {
+                break;
+            }
+            let bytes = js_sys::Uint8Array::new(&js_sys::Reflect::get(&chunk, &"value".into())?);
+            for tile in tiles
+                .push(&bytes.to_vec())
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+            {
+                proxy
+                    .send_event(ViewerCommand::AddSurfaceTile(tile))
+                    .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            }
+        }
+        if !tiles.is_empty() {
+            return Err(wasm_bindgen::JsValue::from_str(
+                "Error: stream ended within a surface tile",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Keeps the URL hash of the page up to date with the dataset URLs and the view, so that
+    /// the address can be shared to open the same view, see `restore_url_hash`
+    pub fn set_url_hash_sync(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetUrlHashSync(enabled))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Opens the datasets and the view of a link in the URL hash of the page. Settings missing
+    /// in the link keep their value. Returns whether the link named a dataset, `false` without
+    /// a hash.
+    pub async fn restore_url_hash(&self) -> Result<bool, wasm_bindgen::JsValue> {
+        let hash = deep_link::read_hash();
+        if hash.trim_start_matches('#').is_empty() {
+            return Ok(false);
+        }
+        let link = deep_link::DeepLink::parse(&hash, ViewState::default())
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(url) = &link.dataset {
+            self.load_url(url.clone(), link.amplitude.clone()).await?;
+        }
+        let Some(proxy) = &self.proxy else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        };
+        // The display ranges of a new surface are the base for the settings of the link
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        proxy
+            .send_event(ViewerCommand::GetViewState(sender))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        let current = receiver
+            .await
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+            .unwrap_or_default();
+        let link = deep_link::DeepLink::parse(&hash, current)
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        proxy
+            .send_event(ViewerCommand::ApplyViewState(link.view))
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        Ok(link.dataset.is_some())
+    }
+
+    /// Sets the nominal surface, e.g. from CAD, the surface is compared with in residual mode.
+    /// It must have the size of the surface.
+    pub async fn set_reference(&self, data: Vec<u8>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let image = Image::<f32>::try_from(data)
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            proxy
+                .send_event(ViewerCommand::SetReference(image))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Starts the per-pixel mean and standard deviation of the current surface and every surface
+    /// set from now on, e.g. for repeatability studies. `false` stops adding surfaces.
+    pub fn set_temporal_collecting(&self, collecting: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetTemporalCollecting(collecting))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Stops collecting surfaces and shows a map of their temporal statistics instead of the
+    /// surface
+    pub async fn show_temporal_map(&self, map: TemporalMap) -> Result<(), wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
             let (sender, receiver) = futures::channel::oneshot::channel();
             proxy
-                .send_event(ViewerCommand::GetPixel(sender))
+                .send_event(ViewerCommand::GetTemporalMaps(sender))
                 .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
-            let pixels = receiver
+            let maps = receiver
                 .await
                 .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No surfaces collected"))?
                 .await
-                .map(|(x, y, z)| vec![x as f32, y as f32, z])
                 .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
-            Ok(pixels)
+            proxy
+                .send_event(ViewerCommand::SetTemporalMap(Box::new(maps), map))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            Ok(())
         } else {
             wasm_bindgen::throw_str("Event loop proxy not initialized");
         }
     }
 
-    pub fn set_height_shader(&self) -> Result<(), wasm_bindgen::JsValue> {
+    /// Aligns every surface set from now on with the first of them, so that a drifting stage
+    /// doesn't blur the baseline or the temporal statistics. `false` stops.
+    pub fn set_registration(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
             proxy
-                .send_event(ViewerCommand::SetHeightShader)
+                .send_event(ViewerCommand::SetRegistration(enabled))
                 .map_err(|e| e.to_string())?;
             Ok(())
         } else {
@@ -130,10 +815,39 @@ impl WasmViewer {
         }
     }
 
-    pub fn set_amplitude_shader(&self) -> Result<(), wasm_bindgen::JsValue> {
+    /// Estimated shifts of the surfaces set since registration was turned on, in pixels and the
+    /// display unit. The first surface is the one the others are aligned with.
+    pub async fn frame_shifts(&self) -> Result<Vec<FrameShift>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetFrameShifts(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Follows the `width` × `height` pixels at `x`, `y` of the current surface through every
+    /// surface set from now on by template matching, see `export_region_track`
+    pub fn track_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
             proxy
-                .send_event(ViewerCommand::SetAmplitudeShader)
+                .send_event(ViewerCommand::TrackRegion(Some(Region {
+                    x,
+                    y,
+                    width,
+                    height,
+                })))
                 .map_err(|e| e.to_string())?;
             Ok(())
         } else {
@@ -143,12 +857,11 @@ impl WasmViewer {
         }
     }
 
-    pub fn set_overlays(&self) -> Result<(), wasm_bindgen::JsValue> {
+    /// Follows the bounding box of the selected overlay like `track_region`
+    pub fn track_selection(&self) -> Result<(), wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
             proxy
-                .send_event(ViewerCommand::SetOverlays(Arc::new(
-                    texture::example_overlays(),
-                )))
+                .send_event(ViewerCommand::TrackSelection)
                 .map_err(|e| e.to_string())?;
             Ok(())
         } else {
@@ -158,10 +871,10 @@ impl WasmViewer {
         }
     }
 
-    pub fn clear_overlays(&self) -> Result<(), wasm_bindgen::JsValue> {
+    pub fn stop_tracking(&self) -> Result<(), wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
             proxy
-                .send_event(ViewerCommand::ClearOverlays)
+                .send_event(ViewerCommand::TrackRegion(None))
                 .map_err(|e| e.to_string())?;
             Ok(())
         } else {
@@ -171,10 +884,41 @@ impl WasmViewer {
         }
     }
 
-    pub fn back_to_origin(&self) -> Result<(), wasm_bindgen::JsValue> {
+    /// Position, match score and height statistics of the tracked region in every surface as
+    /// CSV or JSON, or their plot as SVG. Heights are in the display unit.
+    pub async fn export_region_track(
+        &self,
+        format: TrackFormat,
+    ) -> Result<String, wasm_bindgen::JsValue> {
         if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
             proxy
-                .send_event(ViewerCommand::BackToOrigin)
+                .send_event(ViewerCommand::ExportRegionTrack {
+                    format,
+                    sender: Some(sender),
+                })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No region tracked"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Compares every surface set from now on with a baseline instead of a reference: "first"
+    /// keeps the current surface, "rolling" or "rolling:<weight>" a rolling average with the
+    /// given share of every new surface. The deviation is shown with `set_residual_mode`, no
+    /// mode stops keeping a baseline.
+    pub fn set_baseline(&self, mode: Option<String>) -> Result<(), wasm_bindgen::JsValue> {
+        let mode = mode
+            .map(|mode| mode.parse::<BaselineMode>())
+            .transpose()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetBaseline(mode))
                 .map_err(|e| e.to_string())?;
             Ok(())
         } else {
@@ -183,769 +927,5841 @@ impl WasmViewer {
             ))
         }
     }
-}
-
-#[cfg(target_arch = "wasm32")]
-mod wasm_commands {
-    use std::cell::RefCell;
-    use std::sync::Arc;
-    use winit::window::Window;
 
-    thread_local! {
-        /// Reference to the window for requesting redraws
-        pub static WINDOW: RefCell<Option<Arc<Window>>> = RefCell::new(None);
+    /// Colors the deviation from the reference surface by the tolerance band instead of heights
+    pub fn set_residual_mode(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetResidualMode(enabled))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
     }
 
-    pub fn set_window(window: Arc<Window>) {
-        WINDOW.with(|w| *w.borrow_mut() = Some(window));
+    /// Shows the surface and the reference in alternating tiles with the height colormap, to check
+    /// their registration. `tile_size` is in pixels, an eighth of the longer side if omitted.
+    pub fn set_checkerboard(
+        &self,
+        enabled: bool,
+        tile_size: Option<u32>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetCheckerboard { enabled, tile_size })
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
     }
-}
-
-mod image;
-mod index_buffer;
-mod keyboard;
-mod mouse;
-mod pixel_picker;
-mod projection;
-mod texture;
-mod transformation;
-mod vertex_buffer;
-use image::SurfaceAmplitudeImage;
-use mouse::Mouse;
-use projection::Projection;
 
-use crate::{
-    image::{Image, ImageSize, ZValueRange},
-    index_buffer::{IndexBuffer, IndexBufferBuilder},
-    keyboard::Keyboard,
-    pixel_picker::{PixelPicker, PixelResult},
-    texture::{Overlay, Texture},
-    transformation::Transformation,
-    vertex_buffer::VertexBuffer,
-};
+    /// Accepted deviation from the reference in the display unit, e.g. `-0.5` and `0.5`
+    pub fn set_tolerance(&self, lower: f32, upper: f32) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetTolerance { lower, upper })
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-struct State {
-    window: Arc<Window>,
-    device: Arc<wgpu::Device>,
-    queue: wgpu::Queue,
-    surface: wgpu::Surface<'static>,
-    surface_format: wgpu::TextureFormat,
-    mouse: Mouse,
-    keyboard: Keyboard,
-    transformation: Transformation,
-    projection: Projection,
-    render_pipeline_amplitude: wgpu::RenderPipeline,
-    render_pipeline_height: wgpu::RenderPipeline,
-    use_height_shader: bool,
-    texture_bind_group_layout: wgpu::BindGroupLayout,
-    vertex_buffer: Option<VertexBuffer>,
-    index_buffer: Option<IndexBuffer>,
-    texture: Option<Texture>,
-    image_dims_buffer: wgpu::Buffer,
-    z_value_range_buffer: wgpu::Buffer,
-    image_info_bind_group: wgpu::BindGroup,
-    depth_view: wgpu::TextureView,
-    pixel_picker: PixelPicker,
-    zoom_buffer: wgpu::Buffer,
-}
+    /// Counts the pixels outside of the tolerance band and finds the largest violation.
+    /// Fails if no reference surface is set.
+    pub async fn evaluate_tolerance(&self) -> Result<ToleranceEvaluation, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::EvaluateTolerance(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No reference surface set"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-impl State {
-    async fn new(window: Arc<Window>) -> State {
-        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor::default())
-            .await
-            .unwrap();
-        let device = Arc::new(device);
+    pub async fn get_pixel_value(&self) -> Result<Vec<f32>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetPixel(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            let pixels = receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .await
+                .map(|(x, y, z)| vec![x as f32, y as f32, z])
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            Ok(pixels)
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-        let surface = instance.create_surface(window.clone()).unwrap();
-        let cap = surface.get_capabilities(&adapter);
-        let surface_format = cap.formats[0];
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-        });
-
-        let image_info_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("image_info_bind_group_layout"),
-                entries: &[
-                    ImageSize::get_bind_group_layout_entry(),
-                    ZValueRange::<f32>::get_bind_group_layout_entry(),
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
-                ],
-            });
-
-        let texture_bind_group_layout = Texture::create_bind_group_layout(&device);
-
-        let pixel_picker = PixelPicker::new(&device, window.inner_size());
-        let zoom_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("mip_level_buffer"),
-            contents: bytemuck::cast_slice(&[2u32]),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-        });
+    /// Statistics of the heights in the `size` × `size` neighborhood (5 × 5 by default) of the
+    /// pixel under the mouse, in the display unit. Useful to judge the noise level at a point.
+    pub async fn probe_neighborhood(
+        &self,
+        size: Option<u32>,
+    ) -> Result<NeighborhoodStats, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetPixel(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            let (x, y, _) = receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::ProbeNeighborhood {
+                    pixel: [x, y],
+                    size: size.unwrap_or(NeighborhoodStats::DEFAULT_SIZE),
+                    sender,
+                })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No valid pixel under the mouse"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-        let image_dims_buffer = ImageSize::create_buffer(&device);
-        let z_value_range_buffer = ZValueRange::<f32>::create_buffer(&device);
-        let image_info_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("image_info_bind_group"),
-            layout: &image_info_bind_group_layout,
-            entries: &[
-                ImageSize::get_bind_group_entry(&image_dims_buffer),
-                ZValueRange::<f32>::get_bind_group_entry(&z_value_range_buffer),
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: zoom_buffer.as_entire_binding(),
-                },
-            ],
-        });
+    /// Marks the `count` highest peaks and deepest valleys, at least `min_separation` pixels
+    /// apart, with red and blue markers and returns them with their heights in the display unit.
+    /// They are also dispatched as `extrema` event on the canvas.
+    pub async fn find_extrema(
+        &self,
+        count: Option<u32>,
+        min_separation: Option<f32>,
+    ) -> Result<Vec<Extremum>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::FindExtrema {
+                    count: count.unwrap_or(Extremum::DEFAULT_COUNT),
+                    min_separation: min_separation.unwrap_or(Extremum::DEFAULT_SEPARATION),
+                    sender: Some(sender),
+                })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-        let mut transformation = Transformation::default();
-        let transformation_bind_group_layout = transformation.create_bind_group(&device);
-        let mut projection = Projection::default();
-        let projection_bind_group_layout = projection.create_bind_group(&device);
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("render_pipeline_layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    &image_info_bind_group_layout,
-                    &transformation_bind_group_layout,
-                    &projection_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
+    /// Removes the stripes of raster-scanning instruments by subtracting the median or the
+    /// fitted line of every row, see `undo_preprocessing`
+    pub fn level_rows(&self, leveling: RowLeveling) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::LevelRows(leveling))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-        const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-
-        // Two render targets: main color + picking texture
-        let texture_formats = [
-            Some(surface_format.add_srgb_suffix().into()),
-            Some(PixelPicker::PICKING_FORMAT.into()),
-        ];
-        let amplitude_pipeline_descriptor = &wgpu::RenderPipelineDescriptor {
-            label: Some("amplitude_pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[VertexBuffer::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_amplitude"),
-                compilation_options: Default::default(),
-                targets: &texture_formats,
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: Some(wgpu::IndexFormat::Uint32),
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        };
+    /// Replaces spikes, pixels that deviate by more than `threshold` times the noise level from
+    /// the median of their neighbors, by that median. 5 by default, see `undo_preprocessing`.
+    pub fn despike(&self, threshold: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::Despike(
+                    threshold.unwrap_or(despike::DEFAULT_THRESHOLD),
+                ))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-        let render_pipeline_amplitude =
-            device.create_render_pipeline(amplitude_pipeline_descriptor);
+    /// Levels the rows of every surface that is loaded from now on, `undefined` turns it off
+    pub fn set_row_leveling(
+        &self,
+        leveling: Option<RowLeveling>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetRowLeveling(leveling))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-        let mut height_pipeline_descriptor = amplitude_pipeline_descriptor.clone();
-        height_pipeline_descriptor.label = Some("height_pipeline");
-        height_pipeline_descriptor.fragment = Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: Some("fs_height"),
-            compilation_options: Default::default(),
-            targets: &texture_formats,
-        });
-        let render_pipeline_height = device.create_render_pipeline(&height_pipeline_descriptor);
-
-        // Create depth texture view
-        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("depth_texture"),
-            size: wgpu::Extent3d {
-                width: window.inner_size().width.max(1),
-                height: window.inner_size().height.max(1),
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    /// Colors the heights at or above `threshold`, in the display unit, orange and those below
+    /// blue, to choose a segmentation threshold. Every change dispatches a `threshold-areas`
+    /// event with the `ThresholdAreas` as `detail`, `undefined` ends the preview.
+    pub fn set_threshold(&self, threshold: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetThreshold(threshold))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-        let mut state = State {
-            window,
-            device,
-            queue,
-            surface,
-            surface_format,
-            mouse: Mouse::new(),
-            keyboard: Keyboard::new(),
-            transformation,
-            projection,
-            render_pipeline_amplitude,
-            render_pipeline_height,
-            use_height_shader: true,
-            texture_bind_group_layout,
-            vertex_buffer: None,
-            index_buffer: None,
-            texture: None,
-            image_dims_buffer,
-            z_value_range_buffer,
-            image_info_bind_group,
-            depth_view,
-            pixel_picker,
-            zoom_buffer,
-        };
+    /// Pixels and areas on both sides of the threshold, `undefined` without threshold
+    pub async fn threshold_areas(&self) -> Option<ThresholdAreas> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetThresholdAreas(sender))
+                .ok()?;
+            receiver.await.ok().flatten()
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-        // Configure surface for the first time
-        state.configure_surface();
+    /// Spreads the height colormap over the 2nd to 98th percentile of the heights visible in the
+    /// viewport, so that a shallow feature that is zoomed into gets the full contrast.
+    /// Returns `[min, max]` in the display unit.
+    pub async fn auto_contrast_visible(&self) -> Result<Vec<f32>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::AutoContrastVisible(Some(sender)))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .map(|range| range.to_vec())
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No surface visible"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-        state
+    /// Turns the surface around its normal so that the dominant direction of its texture, e.g.
+    /// machining marks, runs horizontally. Resolves to the `Lay` before the rotation, rejects
+    /// if the texture has no dominant direction.
+    pub async fn align_lay(&self) -> Result<Lay, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::AlignLay(Some(sender)))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No dominant direction"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
     }
 
-    fn get_window(&self) -> &Window {
-        &self.window
+    /// Shows or hides the markers and overlays of the annotation group `group`
+    pub fn set_annotation_group_visible(
+        &self,
+        group: String,
+        visible: bool,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetGroupVisible { group, visible })
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
     }
 
-    fn configure_surface(&mut self) {
-        let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: self.surface_format,
-            // Request compatibility with the sRGB-format texture view we‘re going to create later.
-            view_formats: vec![self.surface_format.add_srgb_suffix()],
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            width: self.window.inner_size().width,
-            height: self.window.inner_size().height,
-            desired_maximum_frame_latency: 2,
-            present_mode: wgpu::PresentMode::AutoVsync,
+    /// Draws the markers and overlays of the annotation group `group` in an RGBA `color`,
+    /// `undefined` restores their own colors
+    pub fn set_annotation_group_color(
+        &self,
+        group: String,
+        color: Option<Vec<u8>>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let color = match color.as_deref() {
+            None => None,
+            Some(&[r, g, b, a]) => Some([r, g, b, a]),
+            Some(_) => {
+                return Err(wasm_bindgen::JsValue::from_str(
+                    "Expected four color channels",
+                ));
+            }
         };
-        self.surface.configure(&self.device, &surface_config);
-        // Recreate depth texture to match the new size
-        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("depth_texture"),
-            size: wgpu::Extent3d {
-                width: self.window.inner_size().width.max(1),
-                height: self.window.inner_size().height.max(1),
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetGroupColor { group, color })
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Colors the overlays by their metadata value `key` with "classic", "viridis" or
+    /// "blue-yellow", `min` and `max` map to the ends of the colormap, 0 and 1 by default.
+    /// Without `key` the overlays get their own colors back.
+    pub fn set_overlay_color_ramp(
+        &self,
+        key: Option<String>,
+        colormap: &str,
+        min: Option<f32>,
+        max: Option<f32>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let colormap = colormap
+            .parse::<Colormap>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        let [default_min, default_max] = ColorRamp::DEFAULT_RANGE;
+        let ramp = key.map(|key| ColorRamp {
+            key,
+            colormap,
+            range: [min.unwrap_or(default_min), max.unwrap_or(default_max)],
         });
-        self.depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetOverlayColorRamp(ramp))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
     }
 
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        self.configure_surface();
-        // Resize the picking texture to match the new window size
-        self.pixel_picker.resize(&self.device, new_size);
+    /// Hides the overlays whose metadata value `key` is below `min`, without `key` all are shown
+    pub fn set_overlay_filter(
+        &self,
+        key: Option<String>,
+        min: f32,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let filter = key.map(|key| ScoreFilter { key, min });
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetOverlayFilter(filter))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
     }
 
-    fn render(&mut self) {
-        // Create texture view
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .expect("failed to acquire next swapchain texture");
-        let texture_view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor {
-                // Without add_srgb_suffix() the image we will be working with
-                // might not be "gamma correct".
-                format: Some(self.surface_format.add_srgb_suffix()),
-                ..Default::default()
-            });
+    /// Selects the next shown overlay or marker and moves the camera to it, see
+    /// `set_review_order`. The selection is reported with an `annotation-properties` event.
+    pub fn review_next(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::StepReview(true))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-        let mut encoder = self.device.create_command_encoder(&Default::default());
-
-        // Create the renderpass which will clear the screen.
-        // Two color attachments: main color + picking texture
-        let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &[
-                Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                }),
-                Some(wgpu::RenderPassColorAttachment {
-                    view: &self.pixel_picker.picking_texture_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
-                        store: wgpu::StoreOp::Store,
-                    },
-                }),
-            ],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
-        let pipeline = if self.use_height_shader {
-            &self.render_pipeline_height
-        } else {
-            &self.render_pipeline_amplitude
-        };
-        renderpass.set_pipeline(pipeline);
-        if let Some(texture) = &self.texture {
-            renderpass.set_bind_group(0, &texture.bind_group, &[]);
-        }
-        renderpass.set_bind_group(1, &self.image_info_bind_group, &[]);
-        renderpass.set_bind_group(2, &self.transformation.bind_group, &[]);
-        renderpass.set_bind_group(3, &self.projection.bind_group, &[]);
-        if let Some(vertex_buffer) = &self.vertex_buffer {
-            renderpass.set_vertex_buffer(0, vertex_buffer.buffer.slice(..));
-        }
-        if let Some(index_buffer) = &self.index_buffer {
-            renderpass.set_index_buffer(index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint32);
-            renderpass.draw_indexed(
-                0..index_buffer.buffer.size() as u32 / std::mem::size_of::<u32>() as u32,
-                0,
-                0..1,
-            );
+    /// Selects the previous shown overlay or marker and moves the camera to it
+    pub fn review_previous(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::StepReview(false))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
         }
+    }
 
-        // End the renderpass.
-        drop(renderpass);
+    /// Reviews the annotations by "position", row by row from the top left, or by the value of
+    /// an overlay metadata key like "severity", highest first
+    pub fn set_review_order(&self, order: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let order = order
+            .parse::<ReviewOrder>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetReviewOrder(order))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
 
-        self.pixel_picker.copy_pixel_at_mouse(&mut encoder);
+    /// Names of the annotation groups of the markers and overlays, sorted
+    pub async fn annotation_groups(&self) -> Result<Vec<String>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetAnnotationGroups(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
 
-        let zoom = self.mouse.get_zoom();
-        if zoom > 0.8 {
-            self.queue
-                .write_buffer(&self.zoom_buffer, 0, bytemuck::cast_slice(&[2u32]));
-        } else if zoom > 0.2 {
-            self.queue
-                .write_buffer(&self.zoom_buffer, 0, bytemuck::cast_slice(&[1u32]));
+    /// Markers and overlays as CSV with their group, position and color. Only the annotations of
+    /// `groups` are included if given, an empty name selects the ungrouped ones, otherwise those
+    /// currently shown.
+    pub async fn export_annotations(
+        &self,
+        groups: Option<Vec<String>>,
+    ) -> Result<String, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::ExportAnnotations {
+                    groups,
+                    sender: Some(sender),
+                })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No surface loaded"))
         } else {
-            self.queue
-                .write_buffer(&self.zoom_buffer, 0, bytemuck::cast_slice(&[0u32]));
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
         }
-        self.transformation.update_gpu(&self.queue);
-        self.projection.update_gpu(&self.queue);
-        // Submit the command in the queue to execute
-        self.queue.submit([encoder.finish()]);
-        self.window.pre_present_notify();
-        surface_texture.present();
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            if let Some(texture) = &self.texture {
-                match pollster::block_on(
-                    self.pixel_picker
-                        .get(self.device.clone(), texture.surface.image.clone()),
-                ) {
-                    Ok((x, y, z)) => {
-                        log::info!("Pixel at [{}/{}]={:.3}", x, y, z);
-                    }
-                    Err(e) => {
-                        log::error!("Pixel read failed: {}", e);
+    /// Records the verdict on the selected overlay or marker in the audit trail and reviews the
+    /// next one
+    pub fn set_disposition(&self, disposition: Disposition) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDisposition(disposition))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Signs the exported audit trail with HMAC-SHA256 and `key`, without one it only carries a
+    /// SHA-256 checksum
+    pub fn set_audit_key(&self, key: Option<String>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetAuditKey(key.map(String::into_bytes)))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// The overlays and markers viewed, for how long, and the verdicts on them as CSV, or JSON
+    /// if `json`. Returns the log and its signature like `hmac-sha256:<hex>`.
+    pub async fn export_audit_trail(
+        &self,
+        json: bool,
+    ) -> Result<Vec<String>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::ExportAuditTrail {
+                    json,
+                    sender: Some(sender),
+                })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            let [log, signature] = receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            Ok(vec![log, signature])
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Restores the surface before the last preprocessing step
+    pub fn undo_preprocessing(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::UndoPreprocessing)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Snapshot of the current mapping between screen, dataset pixel and world coordinates.
+    /// Request a new one after the camera or the surface changed.
+    pub async fn view_transform(&self) -> Result<ViewTransform, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetViewTransform(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("Surface not initialized"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    pub fn set_height_shader(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetHeightShader)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn set_amplitude_shader(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetAmplitudeShader)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn set_overlays(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetOverlays(Arc::new(
+                    texture::example_overlays(),
+                )))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Selects order-independent blending of overlapping overlays, otherwise the last overlay wins
+    pub fn set_overlay_blending(
+        &self,
+        order_independent: bool,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let mode = if order_independent {
+                OverlayBlendMode::WeightedBlended
+            } else {
+                OverlayBlendMode::Topmost
+            };
+            proxy
+                .send_event(ViewerCommand::SetOverlayBlendMode(mode))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Dims and desaturates the surface outside the selected overlay, so that reviews focus on
+    /// that region of interest. Without a selected overlay the surface is shown as usual.
+    pub fn set_dim_outside_selection(&self, dim: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDimOutsideSelection(dim))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Developer visualizations: the bounding box colored by the level of detail, the normals
+    /// of the mesh and a heatmap of the triangles covering each pixel
+    pub fn set_debug_view(
+        &self,
+        bounds: bool,
+        normals: bool,
+        overdraw: bool,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDebugView(DebugView {
+                    bounds,
+                    normals,
+                    overdraw,
+                }))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Shows missing (non-finite) pixels as holes instead of stretched triangles
+    pub fn set_cut_holes(&self, cut_holes: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetCutHoles(cut_holes))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Coarsens flat regions of the mesh, `undefined` returns to one vertex per pixel.
+    /// The tolerance is given relative to the displayed z-range.
+    pub fn set_adaptive_mesh(
+        &self,
+        relative_tolerance: Option<f32>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let mode = match relative_tolerance {
+                Some(relative_tolerance) => MeshMode::Adaptive { relative_tolerance },
+                None => MeshMode::Full,
+            };
+            proxy
+                .send_event(ViewerCommand::SetMeshMode(mode))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Colors heights and amplitudes with "classic", "viridis" or "blue-yellow"
+    pub fn set_colormap(&self, colormap: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let colormap = colormap
+            .parse::<Colormap>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetColormap(colormap))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Modulates the colors of the shown layer by the other layer: "brightness" darkens them
+    /// where the other layer is low, "overlay" mixes in its colors. `weight` from 0 to 1 is the
+    /// strength, 0.5 by default, `undefined` as blend turns blending off.
+    pub fn set_layer_blend(
+        &self,
+        blend: Option<compositing::LayerBlend>,
+        weight: Option<f32>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let compositing = Compositing::new(blend, weight.unwrap_or(Compositing::DEFAULT_WEIGHT));
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetCompositing(compositing))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Colors amplitudes with "classic", "viridis" or "blue-yellow", independent of the heights
+    pub fn set_amplitude_colormap(&self, colormap: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let colormap = colormap
+            .parse::<Colormap>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetAmplitudeColormap(colormap))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Maps the raw amplitudes `min` to `max` to the amplitude colormap
+    pub fn set_amplitude_range(&self, min: f32, max: f32) -> Result<(), wasm_bindgen::JsValue> {
+        self.send_amplitude_range(AmplitudeRange::Fixed { min, max })
+    }
+
+    /// Maps the amplitudes between the `lower` and `upper` percentile of the image to the
+    /// amplitude colormap, e.g. 1 and 99 to ignore saturated pixels
+    pub fn set_amplitude_percentiles(
+        &self,
+        lower: f32,
+        upper: f32,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        self.send_amplitude_range(AmplitudeRange::Percentile { lower, upper })
+    }
+
+    fn send_amplitude_range(&self, range: AmplitudeRange) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetAmplitudeRange(range))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Amplitudes at the ends of the amplitude colormap to label its legend
+    pub async fn amplitude_limits(&self) -> Result<AmplitudeLimits, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetAmplitudeLimits(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("Surface not initialized"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Maps the values `min` to `max` of the "height" or "amplitude" layer to the ends of its
+    /// colormap, heights in the display unit
+    pub fn set_display_range(
+        &self,
+        layer: &str,
+        min: f32,
+        max: f32,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let layer = layer
+            .parse::<Layer>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDisplayRange { layer, min, max })
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Histogram of the "height" or "amplitude" layer with its current display range
+    pub async fn histogram(&self, layer: String) -> Result<Histogram, wasm_bindgen::JsValue> {
+        let layer = layer
+            .parse::<Layer>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetHistogram { layer, sender })
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("Layer has no data"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Overlay or marker last clicked on, `undefined` if nothing is selected
+    pub async fn selection(&self) -> Result<Option<Selection>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetSelection(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Background jobs like exports that are still running
+    pub async fn jobs(&self) -> Result<Vec<JobStatus>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetJobs(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Loads, errors, exports and measurement results of this session, oldest first. New ones
+    /// are also sent as `notification` events on the canvas.
+    pub async fn notifications(&self) -> Result<Vec<Notification>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetNotifications(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Metadata of the surface like the instrument, the acquisition time and the acquisition
+    /// parameters as label/value rows. A `dataset-info` event with the rows as `detail` is
+    /// dispatched on the canvas whenever they change.
+    pub async fn dataset_info(&self) -> Result<Vec<DatasetInfoEntry>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetDatasetInfo(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// How the surface was loaded and processed: its SHA-256, the reference, calibration and
+    /// processing steps with their time, for traceability of exported results
+    pub async fn provenance(&self) -> Result<Vec<ProvenanceEntry>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetProvenance(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// The whole visualization state in its text form, e.g.
+    /// `layer=height&colormap=viridis&height-range=0,1.5&z-scale=1&camera=...`, `undefined`
+    /// without a surface
+    pub async fn get_view_state(&self) -> Result<Option<String>, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetViewState(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map(|state| state.map(|state| state.to_string()))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Applies a state returned by `get_view_state`, missing settings get their default
+    pub fn apply_view_state(&self, state: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let state: ViewState = state
+            .parse()
+            .map_err(|e: anyhow::Error| wasm_bindgen::JsValue::from_str(&e.to_string()))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::ApplyViewState(state))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn clear_notifications(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::ClearNotifications)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Adds the current view as keyframe of the camera path at `time` in seconds, `undefined`
+    /// appends it two seconds after the last keyframe
+    pub fn add_keyframe(&self, time: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::AddKeyframe(time))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn clear_keyframes(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::ClearKeyframes)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Moves the camera along a smooth path through the keyframes, `false` stops it
+    pub fn play_camera_path(&self, play: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::PlayCameraPath(play))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Cancels the background job with `id`, all jobs if `undefined`
+    pub fn cancel_job(&self, id: Option<u32>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::CancelJob(id))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Removes the overlay or marker last clicked on
+    pub fn delete_selection(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::DeleteSelection)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Shows the frame as seen with "protanopia", "deuteranopia" or "tritanopia",
+    /// `undefined` turns the simulation off
+    pub fn set_cvd_simulation(
+        &self,
+        deficiency: Option<String>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let simulation = deficiency
+            .map(|d| d.parse::<CvdSimulation>())
+            .transpose()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+            .unwrap_or_default();
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetCvdSimulation(simulation))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Starts picking the points of a measurement by clicking on the surface, e.g. "angle",
+    /// `undefined` goes back to selecting overlays and markers
+    pub fn set_measure_tool(&self, tool: Option<String>) -> Result<(), wasm_bindgen::JsValue> {
+        let kind = tool
+            .map(|t| t.parse::<MeasureKind>())
+            .transpose()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetMeasureTool(kind))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Lateral size of a pixel in the display unit, needed for angles in space
+    pub fn set_pixel_pitch(&self, pitch: f32) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetPixelPitch(pitch))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Ratio of the y to the x spacing of the pixels for instruments with anisotropic lateral
+    /// sampling, so that the surface is shown with its physical proportions
+    pub fn set_pixel_aspect(&self, aspect: f32) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetPixelAspect(aspect))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// `false` shows every pixel as a square regardless of the pixel aspect
+    pub fn set_physical_proportions(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetPhysicalProportions(enabled))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Angle between the lines picked with the "angle" measure tool
+    pub async fn angle(&self) -> Result<AngleMeasurement, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetAngle(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("No angle measured"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Circle or sphere fitted with the "circle" or "sphere" measure tool,
+    /// lengths in the display unit
+    pub async fn radius_fit(&self) -> Result<RadiusFit, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetRadiusFit(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?
+                .ok_or_else(|| wasm_bindgen::JsValue::from_str("Nothing fitted"))
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Handles at most one mouse move per frame, which saves power with high polling rate mice.
+    /// On by default, `false` handles every move right away.
+    pub fn set_coalesce_mouse_moves(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetCoalesceMouseMoves(enabled))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Mouse sensitivity and inverted axes as comma separated entries, e.g.
+    /// "rotate=1.5,pan=1,zoom=0.5,invert-x,invert-y,invert-zoom". "axes" and "dead-zone" set
+    /// the speed and dead zone of gamepads.
+    pub fn set_navigation(&self, settings: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let settings = settings
+            .parse()
+            .map_err(|e: anyhow::Error| wasm_bindgen::JsValue::from_str(&e.to_string()))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetNavigation(settings))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Frame time in milliseconds to hold while the camera moves by drawing a coarser mesh, which
+    /// is replaced by the full one once the camera rests. `None` always draws the full mesh.
+    pub fn set_target_frame_time(&self, millis: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+        let target = match millis {
+            Some(millis) if millis.is_finite() && millis > 0.0 => {
+                Some(web_time::Duration::from_secs_f32(millis / 1000.0))
+            }
+            Some(millis) => {
+                return Err(wasm_bindgen::JsValue::from_str(&format!(
+                    "Invalid frame time: {}",
+                    millis
+                )));
+            }
+            None => None,
+        };
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetTargetFrameTime(target))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Rotates, pans and zooms with gamepads and 6DoF devices like a 3Dconnexion SpaceMouse,
+    /// their speed and dead zone are part of the navigation settings. Browsers only report a
+    /// device after one of its buttons was pressed.
+    pub fn set_gamepad_enabled(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetGamepadEnabled(enabled))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Frame rate cap of animations like blinking, `undefined` renders them at the display
+    /// refresh rate
+    pub fn set_max_fps(&self, max_fps: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetMaxFps(max_fps))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Unit the surface heights are stored in, e.g. "nm", "µm", "mm", "mil" or "in"
+    pub fn set_data_unit(&self, unit: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let unit = unit
+            .parse::<Unit>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDataUnit(unit))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Unit heights are returned and shown in, e.g. by `get_pixel_value`
+    pub fn set_display_unit(&self, unit: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let unit = unit
+            .parse::<Unit>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDisplayUnit(unit))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Symbol of the unit heights are shown in, it changes when the user cycles units
+    pub async fn display_unit(&self) -> Result<String, wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            proxy
+                .send_event(ViewerCommand::GetUnits(sender))
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            let units = receiver
+                .await
+                .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+            Ok(units.display.symbol().to_owned())
+        } else {
+            wasm_bindgen::throw_str("Event loop proxy not initialized");
+        }
+    }
+
+    /// Shows the viewer borderless on the whole screen. Browsers only allow this in response to
+    /// a user action like a click.
+    pub fn set_fullscreen(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetFullscreen(enabled))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Stores the current shader, colormap and color vision simulation to compare with
+    pub fn store_blink_state(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::StoreBlinkState)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Swaps the current display settings with the stored ones
+    pub fn swap_blink_state(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SwapBlinkState)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Swaps with the stored display settings every `interval_ms` milliseconds,
+    /// `undefined` stops blinking
+    pub fn set_blinking(&self, interval_ms: Option<f64>) -> Result<(), wasm_bindgen::JsValue> {
+        if interval_ms.is_some_and(|ms| !ms.is_finite() || ms < 0.0) {
+            return Err(wasm_bindgen::JsValue::from_str(
+                "Blink interval must be a positive number of milliseconds",
+            ));
+        }
+        if let Some(proxy) = &self.proxy {
+            let interval = interval_ms.map(|ms| web_time::Duration::from_secs_f64(ms / 1000.0));
+            proxy
+                .send_event(ViewerCommand::SetBlinkInterval(interval))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Hides markers and other screen-space elements, e.g. for presentations
+    pub fn set_hud_visible(&self, visible: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetHudVisible(visible))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Name of the shown dataset, e.g. the file name
+    pub fn set_dataset_name(&self, name: String) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetDatasetName(name))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Reports that data is being loaded, `fraction` is the completed part from 0 to 1 if known.
+    /// Call with `loading` set to `false` when done.
+    pub fn set_loading(
+        &self,
+        loading: bool,
+        fraction: Option<f32>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let progress = loading.then_some(Progress { fraction });
+            proxy
+                .send_event(ViewerCommand::SetLoading(progress))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Keeps the horizon level by rotating with azimuth and elevation only,
+    /// `false` returns to free rotation
+    pub fn set_turntable_rotation(&self, enabled: bool) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let mode = if enabled {
+                RotationMode::Turntable
+            } else {
+                RotationMode::Arcball
+            };
+            proxy
+                .send_event(ViewerCommand::SetRotationMode(mode))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Draws a dot per marker on the surface.
+    /// `pixels` holds x/y pairs, `colors` RGBA quadruples and `sizes` the diameters in logical pixels.
+    /// Markers with a non-zero `on_top` flag are drawn over the surface instead of being hidden by it.
+    /// `groups` names the annotation group of each marker, an empty name leaves it ungrouped.
+    pub fn set_markers(
+        &self,
+        pixels: Vec<u32>,
+        colors: Vec<u8>,
+        sizes: Vec<f32>,
+        on_top: Option<Vec<u8>>,
+        groups: Option<Vec<String>>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        let on_top = on_top.unwrap_or_else(|| vec![0; sizes.len()]);
+        let groups = groups.unwrap_or_else(|| vec![String::new(); sizes.len()]);
+        if pixels.len() != sizes.len() * 2
+            || colors.len() != sizes.len() * 4
+            || on_top.len() != sizes.len()
+            || groups.len() != sizes.len()
+        {
+            return Err(wasm_bindgen::JsValue::from_str(
+                "Expected two pixel coordinates, four color channels, one on-top flag and one group per marker size",
+            ));
+        }
+        let markers = pixels
+            .chunks_exact(2)
+            .zip(colors.chunks_exact(4))
+            .zip(sizes)
+            .zip(on_top)
+            .zip(groups)
+            .map(|((((pixel, color), size), on_top), group)| Marker {
+                pixel: [pixel[0], pixel[1]],
+                color: [color[0], color[1], color[2], color[3]],
+                size,
+                on_top: on_top != 0,
+                shape: markers::MarkerShape::Dot,
+                height: None,
+                group: Some(group).filter(|group| !group.is_empty()),
+            })
+            .collect();
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetMarkers(Arc::new(markers)))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn clear_markers(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::ClearMarkers)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Forces CPU ray casting (`true`) or GPU readback (`false`) for picking,
+    /// `undefined` chooses based on the graphics backend
+    pub fn set_ray_cast_picking(
+        &self,
+        ray_cast: Option<bool>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            let method = ray_cast.map(|ray_cast| {
+                if ray_cast {
+                    PickingMethod::RayCast
+                } else {
+                    PickingMethod::Readback
+                }
+            });
+            proxy
+                .send_event(ViewerCommand::SetPickingMethod(method))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Rotates and zooms in lockstep with all viewers in other tabs using the same `group`,
+    /// `undefined` stops following them
+    pub fn set_camera_sync(&self, group: Option<String>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetCameraSync(group))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn clear_overlays(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::ClearOverlays)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn set_language(&mut self, language: &str) -> Result<(), wasm_bindgen::JsValue> {
+        let language = language
+            .parse::<Language>()
+            .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))?;
+        self.localizer.set_language(language);
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetLanguage(language))
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn language(&self) -> String {
+        self.localizer.language().code().to_string()
+    }
+
+    /// Returns the on-screen text for the given message id in the current language
+    pub fn translate(&self, id: &str) -> String {
+        self.localizer.text(id)
+    }
+
+    /// Overrides the UI scale factor, `undefined` follows the device pixel ratio
+    pub fn set_ui_scale(&self, scale: Option<f32>) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetUiScale(scale))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    pub fn back_to_origin(&self) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::BackToOrigin)
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Sets the view angles in degrees, an elevation of 90° looks straight down on the surface.
+    /// `zoom` is the magnification, omit it to keep the current zoom.
+    pub fn set_view(
+        &self,
+        azimuth: f32,
+        elevation: f32,
+        zoom: Option<f32>,
+    ) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetView {
+                    azimuth,
+                    elevation,
+                    zoom,
+                })
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+
+    /// Peels away `fraction`, from 0 to 0.95, of the depth of the surface in front of the camera
+    /// to look under spikes towering over it, without changing the data. Every change, also with
+    /// Ctrl + scroll or Page Up/Down, dispatches a `near-clip` event with the fraction as
+    /// `detail`.
+    pub fn set_near_clip(&self, fraction: f32) -> Result<(), wasm_bindgen::JsValue> {
+        if let Some(proxy) = &self.proxy {
+            proxy
+                .send_event(ViewerCommand::SetNearClip(fraction))
+                .map_err(|e| e.to_string())?;
+            Ok(())
+        } else {
+            Err(wasm_bindgen::JsValue::from_str(
+                "Event loop proxy not initialized",
+            ))
+        }
+    }
+}
+
+/// Contents of a file on the web, relative URLs are resolved against the page. The progress is
+/// shown in the window title and dispatched as `download-progress` events on `window`.
+#[cfg(target_arch = "wasm32")]
+async fn fetch(
+    url: &str,
+    options: &download::DownloadOptions,
+    proxy: &winit::event_loop::EventLoopProxy<ViewerCommand>,
+) -> Result<Vec<u8>, wasm_bindgen::JsValue> {
+    let mut reported = None;
+    data_source::HttpSource::new(url, options.clone())
+        .read_with_progress(|progress| {
+            if reported == Some(progress.step()) {
+                return;
+            }
+            reported = Some(progress.step());
+            let _ = proxy.send_event(ViewerCommand::SetLoading(Some(Progress {
+                fraction: progress.fraction(),
+            })));
+            let detail = js_sys::Object::new();
+            let _ = js_sys::Reflect::set(&detail, &"url".into(), &url.into());
+            let _ = js_sys::Reflect::set(
+                &detail,
+                &"received".into(),
+                &(progress.received as f64).into(),
+            );
+            if let Some(total) = progress.total {
+                let _ = js_sys::Reflect::set(&detail, &"total".into(), &(total as f64).into());
+            }
+            let init = web_sys::CustomEventInit::new();
+            init.set_detail(&detail);
+            if let (Some(window), Ok(event)) = (
+                web_sys::window(),
+                web_sys::CustomEvent::new_with_event_init_dict("download-progress", &init),
+            ) {
+                let _ = window.dispatch_event(&event);
+            }
+        })
+        .await
+        .map_err(|e| wasm_bindgen::JsValue::from_str(&format!("Error: {}", e)))
+}
+
+/// Successful response for a file on the web, see `fetch`
+#[cfg(target_arch = "wasm32")]
+async fn fetch_response(
+    url: &str,
+    options: &download::DownloadOptions,
+) -> Result<web_sys::Response, wasm_bindgen::JsValue> {
+    let base = web_sys::window()
+        .and_then(|window| window.location().href().ok())
+        .unwrap_or_default();
+    let url = web_sys::Url::new_with_base(url, &base)?.href();
+    let window =
+        web_sys::window().ok_or_else(|| wasm_bindgen::JsValue::from_str("Error: no window"))?;
+    let init = options
+        .request_init(&url, None)
+        .await
+        .map_err(|failure| wasm_bindgen::JsValue::from_str(&format!("Error: {}", failure.error)))?;
+    // The browser's fetch instead of an HTTP client keeps the module small
+    let response: web_sys::Response =
+        wasm_bindgen_futures::JsFuture::from(window.fetch_with_str_and_init(&url, &init))
+            .await?
+            .dyn_into()?;
+    if !response.ok() {
+        return Err(wasm_bindgen::JsValue::from_str(&format!(
+            "Error: HTTP status {} {} for {}",
+            response.status(),
+            response.status_text(),
+            url
+        )));
+    }
+    Ok(response)
+}
+
+/// Name of the file a URL points to
+#[cfg(target_arch = "wasm32")]
+fn file_name(url: &str) -> &str {
+    url.split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .unwrap_or(url)
+}
+
+#[cfg(target_arch = "wasm32")]
+mod wasm_commands {
+    use std::cell::RefCell;
+    use std::sync::Arc;
+    use winit::window::Window;
+
+    thread_local! {
+        /// Reference to the window for requesting redraws
+        pub static WINDOW: RefCell<Option<Arc<Window>>> = RefCell::new(None);
+    }
+
+    pub fn set_window(window: Arc<Window>) {
+        WINDOW.with(|w| *w.borrow_mut() = Some(window));
+    }
+}
+
+mod animation;
+mod annotation_groups;
+mod audit;
+mod baseline;
+// `batch` subcommand: statistics and thumbnails for many files without a window
+#[cfg(not(target_arch = "wasm32"))]
+mod batch;
+// `--bench` mode: frame times of a scripted camera sweep as JSON
+#[cfg(not(target_arch = "wasm32"))]
+mod bench;
+mod blink;
+// Decoded files and their mip levels on disk, so that large files open faster the next time
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
+mod camera_path;
+mod camera_sync;
+// Options of the viewer window on the command line
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+mod color_space;
+mod colormap;
+mod compositing;
+// Defaults of the viewer from viewer.toml
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+mod config;
+// Files and downloads compressed with gzip, zstd or LZ4
+mod compression;
+mod compute;
+mod coordinates;
+// Panic hook writing what the viewer did into a crash report
+mod crash;
+// Files, downloads and sources added by integrations read the same way
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+mod data_source;
+mod dataset_info;
+mod debug_view;
+// Links in the URL hash of the web viewer that open the same view of the same dataset
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod deep_link;
+mod despike;
+// Retried and resumed downloads with progress
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod download;
+// Drawing into windows of host applications that own the event loop and into worker canvases
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+mod embed;
+mod error;
+mod extrema;
+#[cfg(not(target_arch = "wasm32"))]
+mod figure;
+mod gamepad;
+// Surfaces with known roughness, steps, gratings and tilt
+mod generator;
+mod histogram;
+mod i18n;
+mod image;
+mod index_buffer;
+mod input;
+mod jobs;
+mod js_api;
+mod json;
+mod keyboard;
+mod lay;
+mod leveling;
+mod markers;
+mod measure;
+mod mouse;
+mod navigation;
+mod notifications;
+// Screenshots for reports, the `render` subcommand and golden-image tests
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(dead_code)]
+mod offscreen;
+mod overlay_style;
+mod parallel;
+mod pixel_picker;
+mod power;
+mod probe;
+mod projection;
+mod provenance;
+mod quality;
+// Uncompressed heights for backends and web builds without the TIFF decoder, `raw` subcommand
+mod raw_image;
+mod ray_picker;
+mod registration;
+mod render_settings;
+mod renderer;
+#[cfg(not(target_arch = "wasm32"))]
+mod report;
+mod residual;
+mod retention;
+mod review;
+mod selection;
+mod simd;
+mod temporal;
+mod texture;
+mod threshold;
+mod thumbnail;
+mod tracking;
+mod transformation;
+mod ui_scale;
+mod units;
+mod vertex_buffer;
+mod view_state;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
+mod window_title;
+use image::{SurfaceAmplitudeImage, SurfaceProducts};
+use mouse::Mouse;
+
+use crate::{
+    animation::{CameraTransition, FrameClock},
+    audit::{AuditTrail, Disposition},
+    baseline::BaselineMode,
+    blink::Blink,
+    camera_path::{CameraPath, CameraPathPlayback},
+    camera_sync::{CameraState, CameraSync},
+    colormap::{Colormap, CvdSimulation},
+    compositing::Compositing,
+    coordinates::ViewTransform,
+    dataset_info::{DatasetInfo, DatasetInfoEntry},
+    debug_view::DebugView,
+    error::ViewerError,
+    extrema::{Extremum, ExtremumKind},
+    gamepad::Gamepads,
+    generator::Generator,
+    histogram::{Histogram, Layer},
+    i18n::{Language, Localizer},
+    image::Image,
+    index_buffer::MeshMode,
+    input::InputEvent,
+    jobs::{Finished, JobStatus, Jobs},
+    keyboard::{AuditKey, Keyboard},
+    lay::Lay,
+    leveling::RowLeveling,
+    markers::Marker,
+    measure::{AngleMeasurement, MeasureKind, MeasureTool, RadiusFit},
+    navigation::NavigationSettings,
+    notifications::{Notification, Notifications, Severity},
+    overlay_style::{ColorRamp, ScoreFilter},
+    pixel_picker::{PixelFuture, PixelPicker, PixelResult},
+    power::FrameLimiter,
+    probe::NeighborhoodStats,
+    provenance::{ProvenanceEntry, ProvenanceLog},
+    quality::QualityScaler,
+    ray_picker::PickingMethod,
+    registration::{FrameShift, Registration},
+    renderer::Renderer,
+    residual::{Tolerance, ToleranceEvaluation},
+    review::ReviewOrder,
+    selection::{Selection, SelectionKind},
+    temporal::{TemporalMap, TemporalMaps, TemporalStats},
+    texture::{AmplitudeLimits, AmplitudeRange, Overlay, OverlayBlendMode, Reload},
+    threshold::ThresholdAreas,
+    tracking::{Region, RegionTracker, TrackFormat},
+    transformation::RotationMode,
+    ui_scale::UiScale,
+    units::{Unit, Units},
+    view_state::ViewState,
+    window_title::{Progress, WindowTitle},
+};
+
+struct State {
+    window: Arc<Window>,
+    surface: wgpu::Surface<'static>,
+    surface_format: wgpu::TextureFormat,
+    mouse: Mouse,
+    keyboard: Keyboard,
+    renderer: Renderer,
+    pixel_picker: PixelPicker,
+    localizer: Localizer,
+    ui_scale: UiScale,
+    backend: wgpu::Backend,
+    picking_method: PickingMethod,
+    camera_sync: CameraSync,
+    frame_clock: FrameClock,
+    camera_transition: Option<CameraTransition>,
+    /// Keyframes of a fly-through
+    camera_path: CameraPath,
+    path_playback: Option<CameraPathPlayback>,
+    #[cfg(not(target_arch = "wasm32"))]
+    path_export: Option<camera_path::FrameExport>,
+    blink: Blink,
+    title: WindowTitle,
+    /// Long operations like decoding and exports running in the background
+    jobs: Jobs<State>,
+    /// Progress of the jobs as last shown in the title
+    job_progress: Option<Progress>,
+    notifications: Notifications,
+    dataset_info: DatasetInfo,
+    provenance: ProvenanceLog,
+    /// Surfaces before each preprocessing step, the last one is restored by undo
+    surface_history: Vec<Arc<Image<f32>>>,
+    /// Leveling applied to every loaded surface
+    row_leveling: Option<RowLeveling>,
+    /// Aligns every loaded surface with the first one since it was turned on
+    registration: Option<Registration>,
+    /// Region followed through the loaded surfaces
+    tracker: Option<RegionTracker>,
+    units: Units,
+    /// Last picked pixel, where the 3D cursor is drawn. Shared with the pixel futures that pick it.
+    cursor_pixel: Rc<Cell<Option<[u32; 2]>>>,
+    /// Overlay or marker last clicked on
+    selection: Option<Selection>,
+    /// Order in which `step_review` goes through the overlays and markers
+    review_order: ReviewOrder,
+    /// Annotations viewed and disposed of in this session
+    audit: AuditTrail,
+    /// Key the audit trail is signed with, see `audit::sign`
+    audit_key: Option<Vec<u8>>,
+    measure: MeasureTool,
+    /// Lateral size of a pixel in the data unit
+    pixel_pitch: f32,
+    frame_limiter: FrameLimiter,
+    navigation: NavigationSettings,
+    gamepads: Gamepads,
+    quality: QualityScaler,
+    /// Camera of the last frame, to tell whether the camera moved
+    last_rendered_camera: Option<CameraState>,
+    /// Next animation frame when the frame rate is capped, see `ImageViewer3D::about_to_wait`
+    redraw_at: Option<web_time::Instant>,
+    /// File the surface was loaded from
+    #[cfg(not(target_arch = "wasm32"))]
+    dataset_path: Option<String>,
+    /// Decoded files on disk, `None` if caching is turned off
+    #[cfg(not(target_arch = "wasm32"))]
+    cache: Option<Arc<cache::DatasetCache>>,
+    /// URLs the surface and the amplitude were loaded from, see `WasmViewer::load_url`
+    #[cfg(target_arch = "wasm32")]
+    dataset_urls: [Option<String>; 2],
+    /// Writes the view into the URL hash of the page, `None` leaves the hash alone
+    #[cfg(target_arch = "wasm32")]
+    url_hash: Option<deep_link::UrlHash>,
+    /// Rows of a surface received so far while it is streamed in tiles
+    #[cfg(target_arch = "wasm32")]
+    tile_assembly: Option<raw_image::TileAssembly>,
+}
+
+/// GPU objects shared by all windows
+#[derive(Clone)]
+struct Gpu {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    device: Arc<wgpu::Device>,
+    queue: wgpu::Queue,
+}
+
+impl Gpu {
+    async fn new(power_preference: wgpu::PowerPreference) -> Result<Self, ViewerError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference,
+                ..Default::default()
+            })
+            .await?;
+        log::info!("Using {}", adapter.get_info().name);
+        crash::set_adapter(&adapter.get_info());
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default())
+            .await?;
+        Ok(Self {
+            instance,
+            adapter,
+            device: Arc::new(device),
+            queue,
+        })
+    }
+}
+
+impl State {
+    fn new(
+        window: Arc<Window>,
+        gpu: &Gpu,
+        config: &config::ViewerConfig,
+    ) -> Result<State, ViewerError> {
+        let backend = gpu.adapter.get_info().backend;
+
+        let surface = gpu.instance.create_surface(window.clone())?;
+        let cap = surface.get_capabilities(&gpu.adapter);
+        let surface_format = *cap.formats.first().ok_or(ViewerError::NoSurfaceFormat)?;
+
+        let pixel_picker = PixelPicker::new(&gpu.device, window.inner_size());
+        let renderer = Renderer::new(
+            gpu.device.clone(),
+            gpu.queue.clone(),
+            surface_format.add_srgb_suffix(),
+            window.inner_size(),
+        );
+
+        let ui_scale = UiScale::new(window.scale_factor());
+        let mut state = State {
+            window,
+            surface,
+            surface_format,
+            mouse: Mouse::new(),
+            keyboard: Keyboard::new(),
+            renderer,
+            pixel_picker,
+            localizer: Localizer::new(Language::from_environment()),
+            ui_scale,
+            backend,
+            picking_method: PickingMethod::for_backend(backend),
+            camera_sync: CameraSync::new(),
+            frame_clock: FrameClock::new(),
+            camera_transition: None,
+            camera_path: CameraPath::default(),
+            path_playback: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            path_export: None,
+            blink: Blink::new(),
+            title: WindowTitle::default(),
+            jobs: Jobs::default(),
+            job_progress: None,
+            notifications: Notifications::default(),
+            dataset_info: DatasetInfo::default(),
+            provenance: ProvenanceLog::default(),
+            surface_history: Vec::new(),
+            row_leveling: None,
+            registration: None,
+            tracker: None,
+            units: Units::default(),
+            cursor_pixel: Rc::new(Cell::new(None)),
+            selection: None,
+            review_order: ReviewOrder::default(),
+            audit: AuditTrail::default(),
+            audit_key: None,
+            measure: MeasureTool::default(),
+            pixel_pitch: 1.0,
+            frame_limiter: FrameLimiter::default(),
+            navigation: NavigationSettings::default(),
+            gamepads: Gamepads::default(),
+            quality: QualityScaler::default(),
+            last_rendered_camera: None,
+            redraw_at: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            dataset_path: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            cache: None,
+            #[cfg(target_arch = "wasm32")]
+            dataset_urls: [None, None],
+            #[cfg(target_arch = "wasm32")]
+            url_hash: None,
+            #[cfg(target_arch = "wasm32")]
+            tile_assembly: None,
+        };
+        match config.shader {
+            Some(config::Shader::Height) => state.renderer.set_height_shader(),
+            Some(config::Shader::Amplitude) => state.renderer.set_amplitude_shader(),
+            None => {}
+        }
+        if let Some(range) = config.amplitude_range {
+            state.renderer.set_amplitude_range(range);
+        }
+        if let Some(background) = config.background {
+            state.renderer.set_background(background);
+        }
+        if let Some(navigation) = config.navigation {
+            state.set_navigation(navigation);
+        }
+        state.keyboard.bindings = config.key_bindings.clone();
+        state.renderer.set_ui_scale(state.ui_scale.get());
+        state.update_window_title();
+
+        // Configure surface for the first time
+        state.configure_surface();
+
+        Ok(state)
+    }
+
+    fn get_window(&self) -> &Window {
+        &self.window
+    }
+
+    fn configure_surface(&mut self) {
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: self.surface_format,
+            // Request compatibility with the sRGB-format texture view we‘re going to create later.
+            view_formats: vec![self.surface_format.add_srgb_suffix()],
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            width: self.window.inner_size().width,
+            height: self.window.inner_size().height,
+            desired_maximum_frame_latency: 2,
+            present_mode: wgpu::PresentMode::AutoVsync,
+        };
+        self.surface
+            .configure(self.renderer.device(), &surface_config);
+        self.renderer.resize(self.window.inner_size());
+    }
+
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+        self.configure_surface();
+        // Resize the picking texture to match the new window size
+        self.pixel_picker.resize(self.renderer.device(), new_size);
+    }
+
+    fn render(&mut self) {
+        let start = web_time::Instant::now();
+        self.frame_limiter.frame_rendered(start);
+        self.apply_pending_move();
+        if let Some(camera) = self.camera_sync.take_update() {
+            self.set_camera(camera);
+        }
+        self.advance_animations();
+        self.renderer.set_cursor(self.cursor_pixel.get());
+
+        // Create texture view
+        let surface_texture = match self.surface.get_current_texture() {
+            Ok(surface_texture) => surface_texture,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                // Happens e.g. after the window moved to another monitor, skip this frame
+                self.configure_surface();
+                self.window.request_redraw();
+                return;
+            }
+            Err(e) => {
+                log::error!("Failed to acquire next swapchain texture: {}", e);
+                return;
+            }
+        };
+        let texture_view = surface_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor {
+                // Without add_srgb_suffix() the image we will be working with
+                // might not be "gamma correct".
+                format: Some(self.surface_format.add_srgb_suffix()),
+                ..Default::default()
+            });
+
+        let mut encoder = self
+            .renderer
+            .device()
+            .create_command_encoder(&Default::default());
+
+        self.renderer.render(
+            &mut encoder,
+            &texture_view,
+            &self.pixel_picker.picking_texture_view,
+        );
+
+        if self.picking_method == PickingMethod::Readback {
+            self.pixel_picker.copy_pixel_at_mouse(&mut encoder);
+        }
+
+        // Submit the command in the queue to execute
+        self.renderer.queue().submit([encoder.finish()]);
+        self.window.pre_present_notify();
+        surface_texture.present();
+
+        let camera = self.renderer.camera();
+        let camera_moved = self.last_rendered_camera.replace(camera) != Some(camera);
+        if camera_moved {
+            let (azimuth, elevation) = self.renderer.transformation.get_orientation();
+            crash::set_camera(format!(
+                "azimuth {:.1}°, elevation {:.1}°, zoom {:.2}, pan {:?}",
+                azimuth, elevation, camera.zoom, camera.pan
+            ));
+        }
+        if let Some(step) = self
+            .quality
+            .frame_rendered(start, start.elapsed(), camera_moved)
+        {
+            self.renderer.set_preview_step(step);
+        }
+
+        // A surface dropped after its upload is not decoded again just to log the hovered pixel
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(image) = self
+                .renderer
+                .texture
+                .as_ref()
+                .and_then(|texture| texture.surface.resident_image())
+            {
+                let pixel = match self.picking_method {
+                    PickingMethod::Readback => pollster::block_on(
+                        self.pixel_picker
+                            .get(self.renderer.device().clone(), image.clone()),
+                    ),
+                    PickingMethod::RayCast => self.ray_cast_pixel(),
+                };
+                self.cursor_pixel
+                    .set(pixel.as_ref().ok().map(|&(x, y, _)| [x, y]));
+                match pixel {
+                    Ok((x, y, z)) => {
+                        log::info!("Pixel at [{}/{}]={}", x, y, self.units.format(z));
+                        if self.keyboard.is_alt_pressed()
+                            && let Some(stats) = NeighborhoodStats::sample(
+                                &image,
+                                [x, y],
+                                NeighborhoodStats::DEFAULT_SIZE,
+                            )
+                        {
+                            log::info!(
+                                "Neighborhood mean={} std={} min={} max={} gradient=({:.4}, {:.4})/px",
+                                self.units.format(stats.mean),
+                                self.units.format(stats.std),
+                                self.units.format(stats.min),
+                                self.units.format(stats.max),
+                                self.units.to_display(stats.gradient_x),
+                                self.units.to_display(stats.gradient_y),
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Pixel read failed: {}", e);
+                    }
+                };
+            }
+        }
+    }
+
+    /// Updates picking and drags the camera for a cursor move
+    fn move_cursor(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        self.mouse.register_move_event(position);
+        self.pixel_picker.update_mouse_position(position);
+        if self.mouse.is_left_button_pressed() {
+            match self.mouse.get_device_coordinates(self.window.inner_size()) {
+                Ok(new_position) => {
+                    if self.mouse.is_pointer_inside(new_position) {
+                        if self.keyboard.is_control_pressed() {
+                            self.renderer.projection.change_position(new_position);
+                        } else {
+                            self.renderer.transformation.rotate(
+                                Vec3::from((new_position, 1.0)),
+                                self.keyboard.rotation_constraint(),
+                            );
+                        }
+                        self.publish_camera();
+                    }
+                }
+                Err(e) => error!("Failed to calculate pointer position: {}", e),
+            }
+        }
+    }
+
+    /// Applies the latest coalesced cursor move, see `Mouse::queue_move`
+    fn apply_pending_move(&mut self) {
+        if let Some(position) = self.mouse.take_pending_move() {
+            self.move_cursor(position);
+        }
+    }
+
+    /// Returns the pixel under the mouse with its height in the display unit
+    fn get_pixel_value(&mut self, sender: futures::channel::oneshot::Sender<PixelFuture>) {
+        let pixel: PixelFuture = if self.picking_method == PickingMethod::RayCast {
+            let pixel = self.ray_cast_pixel();
+            let future: std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>> =
+                Box::pin(async move { pixel });
+            future.shared()
+        } else if let Some(image) = self.renderer.surface_image() {
+            self.pixel_picker.get(self.renderer.device().clone(), image)
+        } else {
+            let future: std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>> =
+                Box::pin(async move {
+                    Err::<(u32, u32, f32), Arc<anyhow::Error>>(Arc::new(anyhow!(
+                        "Surface not initialized"
+                    )))
+                });
+            future.shared()
+        };
+        let units = self.units;
+        let cursor_pixel = self.cursor_pixel.clone();
+        let future: std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>> =
+            Box::pin(async move {
+                let pixel = pixel.await;
+                cursor_pixel.set(pixel.as_ref().ok().map(|&(x, y, _)| [x, y]));
+                pixel.map(|(x, y, z)| (x, y, units.to_display(z)))
+            });
+        if sender.send(future.shared()).is_err() {
+            log::error!("Failed to return pixel value");
+        }
+    }
+
+    /// Picks the pixel under the mouse on the CPU, without waiting for the GPU
+    fn ray_cast_pixel(&self) -> PixelResult {
+        let view_transform = self
+            .renderer
+            .view_transform()
+            .ok_or_else(|| Arc::new(anyhow!("Surface not initialized")))?;
+        let mouse = self.mouse.current_position;
+        view_transform
+            .screen_to_pixel(glam::Vec2::new(mouse.x as f32, mouse.y as f32))
+            .ok_or_else(|| Arc::new(anyhow!("No surface under the mouse")))
+    }
+
+    fn view_transform(&self) -> Option<ViewTransform> {
+        self.renderer.view_transform()
+    }
+
+    /// Selects the topmost overlay or marker under the mouse, or nothing if there is none
+    fn select_at_mouse(&mut self) {
+        let selection = match self.picking_method {
+            PickingMethod::Readback => self.pixel_picker.picked_object(),
+            // Markers are not ray cast, only overlays can be selected
+            PickingMethod::RayCast => self
+                .ray_cast_pixel()
+                .ok()
+                .and_then(|(x, y, _)| self.renderer.overlay_at([x, y])),
+        };
+        match selection {
+            Some(selection) => log::info!("Selected {:?} {}", selection.kind, selection.index),
+            None => log::debug!("Nothing selected"),
+        }
+        self.set_selection(selection);
+    }
+
+    /// Clicks pick the points of the measurement `kind` instead of selecting
+    fn set_measure_tool(&mut self, kind: Option<MeasureKind>) {
+        match kind {
+            Some(kind) => log::info!("Measuring {}, click the points", kind.name()),
+            None => log::info!("Measuring stopped"),
+        }
+        self.measure.set_kind(kind);
+        self.update_measurement();
+    }
+
+    /// Adds the pixel under the mouse to the measurement and reports it once complete
+    fn add_measure_point(&mut self) {
+        let Some(pixel) = self.cursor_pixel.get() else {
+            return;
+        };
+        if self.measure.add_point(pixel) {
+            if let Some(angle) = self.measured_angle() {
+                self.notify_message(
+                    Severity::Info,
+                    "notify-angle",
+                    &[
+                        ("in-plane", format!("{:.2}", angle.in_plane)),
+                        ("spatial", format!("{:.2}", angle.spatial)),
+                        ("first", format!("{:.2}", angle.first_elevation)),
+                        ("second", format!("{:.2}", angle.second_elevation)),
+                    ],
+                );
+            }
+            if let Some(fit) = self.radius_fit() {
+                self.notify_message(
+                    Severity::Info,
+                    "notify-radius-fit",
+                    &[
+                        ("radius", self.units.format(fit.radius)),
+                        ("x", format!("{:.1}", fit.center_x)),
+                        ("y", format!("{:.1}", fit.center_y)),
+                        ("z", self.units.format(fit.center_z)),
+                        ("rms", self.units.format(fit.rms_residual)),
+                        ("max", self.units.format(fit.max_residual)),
+                        ("points", fit.points.to_string()),
+                    ],
+                );
+            }
+        }
+        self.update_measurement();
+    }
+
+    fn update_measurement(&mut self) {
+        let fit = self.radius_fit();
+        let fitted = fit.as_ref().map_or(&[][..], |fit| fit.outline());
+        self.renderer
+            .set_measurement(self.measure.points(), &self.measure.guide_pixels(), fitted);
+        self.window.request_redraw();
+    }
+
+    /// Lengths in the data unit
+    fn radius_fit(&self) -> Option<RadiusFit> {
+        let image = self.renderer.surface_image()?;
+        self.measure.radius_fit(&image, self.pixel_pitch)
+    }
+
+    fn measured_angle(&self) -> Option<AngleMeasurement> {
+        let image = self.renderer.surface_image()?;
+        self.measure.angle(&image, self.pixel_pitch)
+    }
+
+    /// `pitch` is given in the display unit
+    fn set_pixel_pitch(&mut self, pitch: f32) {
+        if !pitch.is_finite() || pitch <= 0.0 {
+            log::error!("Invalid pixel pitch {}", pitch);
+            return;
+        }
+        self.pixel_pitch = self.units.to_data(pitch);
+        self.provenance.record(format!(
+            "Pixel pitch set to {}",
+            self.units.format(self.pixel_pitch)
+        ));
+    }
+
+    fn set_pixel_aspect(&mut self, aspect: f32) {
+        if !aspect.is_finite() || aspect <= 0.0 {
+            log::error!("Invalid pixel aspect {}", aspect);
+            return;
+        }
+        self.renderer.set_pixel_aspect(aspect);
+        self.provenance
+            .record(format!("Pixel aspect set to {}", aspect));
+        self.window.request_redraw();
+    }
+
+    fn set_selection(&mut self, selection: Option<Selection>) {
+        self.selection = selection;
+        let dataset = self.title.dataset.as_deref().unwrap_or("Surface");
+        self.audit.select(dataset, selection, audit::now());
+        self.renderer.set_selection(selection);
+        self.window.request_redraw();
+    }
+
+    fn delete_selection(&mut self) {
+        if let Some(selection) = self.selection
+            && self.renderer.remove(selection)
+        {
+            log::info!("Deleted {:?} {}", selection.kind, selection.index);
+            self.set_modified();
+        }
+        self.set_selection(None);
+    }
+
+    /// Moves the selected overlay or marker by `offset` pixels
+    fn nudge_selection(&mut self, offset: [i32; 2]) {
+        if let Some(selection) = self.selection
+            && self.renderer.nudge(selection, offset)
+        {
+            self.set_modified();
+            self.window.request_redraw();
+        }
+    }
+
+    /// Selects the overlay or marker after the selected one in the review order, before it if
+    /// not `forward`, and moves the camera to it
+    fn step_review(&mut self, forward: bool) {
+        let items = self.renderer.review_items(&self.review_order);
+        let Some(item) = review::step(&items, self.selection, forward) else {
+            self.notify_message(Severity::Info, "notify-nothing-to-review", &[]);
+            return;
+        };
+        self.set_selection(Some(item.selection));
+        self.open_selection_properties();
+        self.animate_camera(|renderer| renderer.frame_pixels(item.bounds));
+    }
+
+    /// Records the verdict on the selected overlay or marker in the audit trail and reviews the
+    /// next one
+    fn dispose_selection(&mut self, disposition: Disposition) {
+        let Some(selection) = self.selection else {
+            self.notify_message(Severity::Warning, "notify-select-to-dispose", &[]);
+            return;
+        };
+        let dataset = self.title.dataset.as_deref().unwrap_or("Surface");
+        self.audit
+            .dispose(dataset, selection, disposition, audit::now());
+        self.step_review(true);
+    }
+
+    /// Asks the host to show the properties of the selection. Natively they are logged,
+    /// in the browser an `annotation-properties` event with the selection as `detail`
+    /// is dispatched on the canvas.
+    fn open_selection_properties(&self) {
+        let Some(selection) = self.selection else {
+            return;
+        };
+        let Some(properties) = self.renderer.describe(selection) else {
+            return;
+        };
+        log::info!("{}", properties);
+        #[cfg(target_arch = "wasm32")]
+        self.dispatch_canvas_event(
+            "annotation-properties",
+            &wasm_bindgen::JsValue::from(selection),
+        );
+    }
+
+    /// `None` chooses the picking method based on the graphics backend
+    fn set_picking_method(&mut self, method: Option<PickingMethod>) {
+        self.picking_method = method.unwrap_or(PickingMethod::for_backend(self.backend));
+        log::info!("Picking pixels with {:?}", self.picking_method);
+    }
+
+    fn set_language(&mut self, language: Language) {
+        self.localizer.set_language(language);
+        let mut args = i18n::MessageArgs::new();
+        args.set("language", language.name());
+        log::info!("{}", self.localizer.format("language-changed", Some(&args)));
+        self.update_window_title();
+    }
+
+    fn update_window_title(&self) {
+        self.window.set_title(&self.title.format(&self.localizer));
+    }
+
+    /// `products` are computed from the surface if the caller doesn't have them, `reload`
+    /// decodes the surface again, see `Renderer::set_surface`
+    fn set_surface(
+        &mut self,
+        mut data: Image<f32>,
+        mut products: Option<SurfaceProducts>,
+        mut reload: Option<Reload>,
+    ) {
+        let mut shift = None;
+        if let Some(registration) = &mut self.registration {
+            let (registered, frame_shift) = registration.register(data);
+            data = registered;
+            // A moved surface is no longer the one in the file
+            if frame_shift != FrameShift::default() {
+                products = None;
+                reload = None;
+                shift = Some(frame_shift);
+            }
+        }
+        let tracked = self.tracker.as_mut().map(|tracker| tracker.track(&data));
+        let size = (data.size.width.get(), data.size.height.get());
+        match self.renderer.set_surface(data, products, reload) {
+            Ok(()) => {
+                self.title.size = Some(size);
+                self.title.modified = false;
+                self.update_window_title();
+                // Replaced by the metadata of the file if the caller has it
+                self.set_dataset_info(DatasetInfo::default());
+                let dataset = self.title.dataset.as_deref().unwrap_or("Surface");
+                self.provenance.start(format!(
+                    "Loaded {} ({} × {} pixels)",
+                    dataset, size.0, size.1
+                ));
+                let dataset = dataset.to_owned();
+                self.notify_message(
+                    Severity::Success,
+                    "notify-loaded",
+                    &[
+                        ("dataset", dataset),
+                        ("width", size.0.to_string()),
+                        ("height", size.1.to_string()),
+                    ],
+                );
+                if let Some(shift) = shift {
+                    self.provenance.record(format!(
+                        "Registered with the first frame, shifted by {:.2}, {:.2} pixels and {} in height",
+                        shift.x,
+                        shift.y,
+                        self.units.format(shift.z)
+                    ));
+                }
+                match tracked {
+                    Some(Some(tracked)) => log::info!(
+                        "Tracked region at {}, {} with score {:.3}, mean height {}",
+                        tracked.region.x,
+                        tracked.region.y,
+                        tracked.score,
+                        self.units.format(tracked.stats.mean)
+                    ),
+                    Some(None) => self.notify_message(Severity::Warning, "notify-region-lost", &[]),
+                    None => {}
+                }
+                self.surface_history.clear();
+                if let Some(leveling) = self.row_leveling {
+                    self.level_rows(leveling);
+                }
+            }
+            Err(e) => self.notify_message(
+                Severity::Error,
+                "notify-surface-failed",
+                &[("error", e.to_string())],
+            ),
+        }
+    }
+
+    /// Shows the rows of a surface streamed in tiles as they arrive, the complete surface is
+    /// then set like a loaded one
+    #[cfg(target_arch = "wasm32")]
+    fn add_surface_tile(&mut self, tile: raw_image::SurfaceTile) {
+        let result = match &mut self.tile_assembly {
+            Some(assembly) if *assembly.size() == tile.surface_size => assembly
+                .insert(&tile)
+                .and_then(|()| self.renderer.write_surface_tile(&tile)),
+            // The size comes from the sender, checked before the surface is allocated
+            _ => tile
+                .surface_size
+                .check_limits(&self.renderer.device().limits())
+                .and_then(|()| {
+                    // Rows still missing are missing data until they arrive
+                    let mut assembly = raw_image::TileAssembly::new(tile.surface_size.clone());
+                    let result = assembly
+                        .insert(&tile)
+                        .and_then(|()| self.renderer.set_surface(assembly.snapshot(), None, None));
+                    self.tile_assembly = Some(assembly);
+                    result
+                }),
+        };
+        if let Err(e) = result {
+            self.tile_assembly = None;
+            self.notify_message(
+                Severity::Error,
+                "notify-tile-failed",
+                &[("error", e.to_string())],
+            );
+        } else if let Some(assembly) = self
+            .tile_assembly
+            .take_if(|assembly| assembly.is_complete())
+        {
+            self.set_surface(assembly.into_image(), None, None);
+        }
+    }
+
+    /// Loads a surface after the window is shown, the title tells that loading is in progress
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_file(&mut self, path: &str) {
+        let name = std::path::Path::new(path)
+            .file_name()
+            .map_or(path.into(), |name| name.to_string_lossy());
+        self.set_dataset_name(name.into_owned());
+        let path = path.to_owned();
+        let work = {
+            let path = path.clone();
+            let cache = self.cache.clone();
+            move |_: &jobs::JobContext| cache::load(cache.as_deref(), &path)
+        };
+        let cache = self.cache.clone();
+        self.jobs
+            .spawn(format!("Loading {}", path), work, move |state, loaded| {
+                if let Some(unit) = loaded.unit {
+                    state.set_units(Units::new(unit));
+                }
+                state.dataset_path = Some(path.clone());
+                let reload: Reload =
+                    Box::new(move || Ok(cache::load(cache.as_deref(), &path)?.surface));
+                state.set_surface(loaded.surface, Some(loaded.products), Some(reload));
+                state.set_dataset_info(loaded.info);
+                state.window.request_redraw();
+            });
+    }
+
+    /// Shows the built-in demo surface, see `Image::demo`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_demo(&mut self) {
+        const SIZE: u32 = 512;
+        let size = image::ImageSize::new(SIZE, SIZE).expect("demo size is valid");
+        self.set_dataset_name(self.localizer.text("demo-surface"));
+        self.title.demo = true;
+        self.dataset_path = None;
+        self.set_surface(Image::demo(size), None, None);
+    }
+
+    /// Asks for a surface file and loads it in place of the current one, the dialog starts in
+    /// the directory of the current file
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_file_dialog(&mut self) {
+        let mut dialog = rfd::FileDialog::new()
+            .set_title("Open surface")
+            .add_filter("Surfaces", &["tif", "tiff", "gz", "zst", "lz4"])
+            .add_filter("All files", &["*"])
+            .set_parent(&*self.window);
+        if let Some(dir) = self
+            .dataset_path
+            .as_deref()
+            .and_then(|path| std::path::Path::new(path).parent())
+            .filter(|dir| dir.is_dir())
+        {
+            dialog = dialog.set_directory(dir);
+        }
+        let path = dialog.pick_file();
+        // The dialog received the release of Ctrl
+        self.keyboard.release_all();
+        match path {
+            Some(path) => self.load_file(&path.to_string_lossy()),
+            None => log::info!("No file chosen"),
+        }
+    }
+
+    /// Shows the metadata of the current surface, the name and size are taken from the viewer
+    fn set_dataset_info(&mut self, info: DatasetInfo) {
+        let size = self.renderer.texture.as_ref().map(|texture| {
+            let size = texture.surface.image_size();
+            (size.width.get(), size.height.get())
+        });
+        self.dataset_info = DatasetInfo {
+            name: self.title.dataset.clone(),
+            width: size.map_or(info.width, |size| size.0),
+            height: size.map_or(info.height, |size| size.1),
+            ..info
+        };
+        if let Some(sha256) = &self.dataset_info.sha256 {
+            self.provenance.record(format!("SHA-256 {}", sha256));
+        }
+        crash::set_dataset(format!(
+            "{} ({} × {} pixels, SHA-256 {})",
+            self.dataset_info.name.as_deref().unwrap_or("Surface"),
+            self.dataset_info.width,
+            self.dataset_info.height,
+            self.dataset_info.sha256.as_deref().unwrap_or("-")
+        ));
+        let entries = self.dataset_info.entries();
+        #[cfg(target_arch = "wasm32")]
+        {
+            let detail = js_sys::Array::new();
+            for entry in entries {
+                detail.push(&wasm_bindgen::JsValue::from(entry));
+            }
+            self.dispatch_canvas_event("dataset-info", &detail);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        for entry in entries {
+            log::info!("{}: {}", entry.label, entry.value);
+        }
+    }
+
+    fn set_units(&mut self, units: Units) {
+        log::info!(
+            "Heights stored in {} are shown in {}",
+            units.data.symbol(),
+            units.display.symbol()
+        );
+        self.units = units;
+    }
+
+    fn set_reference(&mut self, data: Image<f32>) {
+        match self.renderer.set_reference(data) {
+            Ok(()) => {
+                self.provenance
+                    .record("Reference surface loaded".to_owned());
+                self.notify_message(Severity::Success, "notify-reference-loaded", &[]);
+                self.window.request_redraw();
+            }
+            Err(e) => self.notify_message(
+                Severity::Error,
+                "notify-reference-failed",
+                &[("error", e.to_string())],
+            ),
+        }
+    }
+
+    /// Loads the nominal surface from the first image of a TIFF file
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_reference(&mut self, path: &str) {
+        let path = path.to_owned();
+        self.jobs.spawn(
+            format!("Loading reference {}", path),
+            move |_| {
+                let image = SurfaceAmplitudeImage::from_file(&path)?;
+                let sha256 = provenance::hash_file(std::path::Path::new(&path))?;
+                Ok((image, path, sha256))
+            },
+            |state, (image, path, sha256)| {
+                state.set_reference(image.surface);
+                state
+                    .provenance
+                    .record(format!("Reference {} has SHA-256 {}", path, sha256));
+            },
+        );
+    }
+
+    /// Compares the surface with the reference in the background and returns the result in the
+    /// display unit, `None` if there is no reference
+    fn evaluate_tolerance(
+        &mut self,
+        sender: futures::channel::oneshot::Sender<Option<ToleranceEvaluation>>,
+    ) {
+        let (Some(surface), Some(reference)) = (
+            self.renderer.surface_image(),
+            self.renderer.reference.clone(),
+        ) else {
+            if sender.send(None).is_err() {
+                log::error!("Failed to return tolerance evaluation");
+            }
+            return;
+        };
+        let tolerance = self.renderer.tolerance;
+        self.jobs.spawn(
+            "Tolerance evaluation",
+            move |_| {
+                Ok(ToleranceEvaluation::evaluate(
+                    &surface, &reference, tolerance,
+                ))
+            },
+            move |state, evaluation| {
+                state.notify_message(
+                    Severity::Info,
+                    "notify-tolerance",
+                    &[
+                        ("out", evaluation.out_of_tolerance().to_string()),
+                        ("evaluated", evaluation.evaluated.to_string()),
+                        ("largest", state.units.format(evaluation.largest_violation)),
+                    ],
+                );
+                if sender
+                    .send(Some(evaluation.to_display(state.units)))
+                    .is_err()
+                {
+                    log::error!("Failed to return tolerance evaluation");
+                }
+            },
+        );
+    }
+
+    /// Edits the heights in the background, the surface before the step is kept for
+    /// `undo_preprocessing`. `step` names it in the job list and the provenance log.
+    fn preprocess(
+        &mut self,
+        step: String,
+        edit: impl FnOnce(&Image<f32>) -> Image<f32> + Send + 'static,
+    ) {
+        let Some(surface) = self.renderer.surface_image() else {
+            log::warn!("No surface for {}", step);
+            return;
+        };
+        let original = surface.clone();
+        self.jobs.spawn(
+            step.clone(),
+            move |_| Ok(edit(&surface)),
+            move |state, edited| {
+                // The edit is lost if another surface was loaded meanwhile
+                if !state
+                    .renderer
+                    .surface_image()
+                    .is_some_and(|current| Arc::ptr_eq(&current, &original))
+                {
+                    log::warn!("Surface changed during {}, discarding it", step);
+                    return;
+                }
+                match state.renderer.update_surface(Arc::new(edited)) {
+                    Ok(()) => {
+                        state.surface_history.push(original);
+                        state.provenance.record(step.clone());
+                        state.set_modified();
+                        state.notify_message(
+                            Severity::Success,
+                            "notify-applied",
+                            &[("step", step)],
+                        );
+                        state.window.request_redraw();
+                    }
+                    Err(e) => state.notify_message(
+                        Severity::Error,
+                        "notify-failed",
+                        &[("name", step), ("error", e.to_string())],
+                    ),
+                }
+            },
+        );
+    }
+
+    fn level_rows(&mut self, leveling: RowLeveling) {
+        self.preprocess(
+            format!("Row leveling ({})", leveling.name()),
+            move |surface| leveling::level_rows(surface, leveling),
+        );
+    }
+
+    fn despike(&mut self, threshold: f32) {
+        if !threshold.is_finite() || threshold <= 0.0 {
+            log::error!("Invalid despiking threshold {}", threshold);
+            return;
+        }
+        self.preprocess(format!("Despiking ({}σ)", threshold), move |surface| {
+            let (despiked, replaced) = despike::despike(surface, threshold);
+            log::info!("Replaced {} spikes", replaced);
+            despiked
+        });
+    }
+
+    /// Fits the height colormap to the heights in the viewport in the background
+    fn auto_contrast_visible(
+        &mut self,
+        sender: Option<futures::channel::oneshot::Sender<Option<[f32; 2]>>>,
+    ) {
+        let Some(view_transform) = self.view_transform() else {
+            if let Some(sender) = sender
+                && sender.send(None).is_err()
+            {
+                log::error!("Failed to return display range");
+            }
+            return;
+        };
+        self.jobs.spawn(
+            "Auto contrast",
+            // A million pixels give the percentiles precisely enough
+            move |_| Ok(view_transform.visible_range(2.0, 98.0, 1 << 20)),
+            move |state, range| {
+                match range {
+                    Some([min, max]) => {
+                        log::info!(
+                            "Height colormap fitted to the view from {} to {}",
+                            state.units.format(min),
+                            state.units.format(max)
+                        );
+                        state.renderer.set_display_range(Layer::Height, min, max);
+                        state.window.request_redraw();
+                    }
+                    None => {
+                        state.notify_message(Severity::Warning, "notify-no-surface-visible", &[])
+                    }
+                }
+                let range = range.map(|range| range.map(|value| state.units.to_display(value)));
+                if let Some(sender) = sender
+                    && sender.send(range).is_err()
+                {
+                    log::error!("Failed to return display range");
+                }
+            },
+        );
+    }
+
+    /// Estimates the lay of the surface in the background and turns the view to show it
+    /// horizontally, keeping the elevation
+    fn align_lay(&mut self, sender: Option<futures::channel::oneshot::Sender<Option<Lay>>>) {
+        let Some(surface) = self.renderer.surface_image() else {
+            if let Some(sender) = sender
+                && sender.send(None).is_err()
+            {
+                log::error!("Failed to return lay");
+            }
+            return;
+        };
+        let pixel_aspect = self.renderer.display_pixel_aspect();
+        self.jobs.spawn(
+            "Lay direction",
+            move |_| Ok(lay::lay(&surface, pixel_aspect, 1 << 20)),
+            move |state, lay| {
+                let lay = lay.filter(|lay| lay.coherence >= lay::MIN_COHERENCE);
+                match lay {
+                    Some(lay) => {
+                        let (azimuth, elevation) = state.renderer.transformation.get_orientation();
+                        let azimuth = lay::horizontal_azimuth(lay, azimuth);
+                        state.notify_message(
+                            Severity::Info,
+                            "notify-lay",
+                            &[
+                                ("angle", format!("{:.1}", lay.angle)),
+                                ("coherence", format!("{:.2}", lay.coherence)),
+                            ],
+                        );
+                        state
+                            .animate_camera(|renderer| renderer.set_view(azimuth, elevation, None));
+                    }
+                    None => state.notify_message(Severity::Warning, "notify-no-lay", &[]),
+                }
+                if let Some(sender) = sender
+                    && sender.send(lay).is_err()
+                {
+                    log::error!("Failed to return lay");
+                }
+            },
+        );
+    }
+
+    /// `threshold` is given in the display unit
+    fn set_threshold(&mut self, threshold: Option<f32>) {
+        let threshold = threshold.map(|threshold| self.units.to_data(threshold));
+        if threshold.is_some_and(|threshold| !threshold.is_finite()) {
+            log::error!("Invalid threshold {:?}", threshold);
+            return;
+        }
+        self.renderer.set_threshold(threshold);
+        self.window.request_redraw();
+        let Some(areas) = self.threshold_areas() else {
+            return;
+        };
+        #[cfg(target_arch = "wasm32")]
+        self.dispatch_canvas_event("threshold-areas", &wasm_bindgen::JsValue::from(areas));
+        #[cfg(not(target_arch = "wasm32"))]
+        log::info!(
+            "{:.2} % at or above {} {}",
+            areas.fraction_above() * 100.0,
+            areas.threshold,
+            self.units.display.symbol()
+        );
+    }
+
+    fn set_near_clip(&mut self, fraction: f32) {
+        self.renderer.set_near_clip(fraction);
+        #[cfg(target_arch = "wasm32")]
+        self.dispatch_canvas_event(
+            "near-clip",
+            &wasm_bindgen::JsValue::from(self.renderer.projection.get_near_clip()),
+        );
+        self.get_window().request_redraw();
+    }
+
+    /// Moves the threshold by a 50th of the height colormap range, starting in its middle
+    #[cfg(not(target_arch = "wasm32"))]
+    fn step_threshold(&mut self, steps: f32) {
+        let [min, max] = self.renderer.render_settings.height_range;
+        let threshold = self
+            .renderer
+            .threshold()
+            .map_or((min + max) / 2.0, |threshold| {
+                threshold + steps * (max - min) / 50.0
+            });
+        self.set_threshold(Some(self.units.to_display(threshold)));
+    }
+
+    /// In the display unit
+    fn threshold_areas(&self) -> Option<ThresholdAreas> {
+        let threshold = self.renderer.threshold()?;
+        let surface = self.renderer.surface_image()?;
+        let pixel_area = self.pixel_pitch * self.pixel_pitch * self.renderer.pixel_aspect();
+        Some(ThresholdAreas::compute(&surface, threshold, pixel_area).to_display(self.units))
+    }
+
+    fn undo_preprocessing(&mut self) {
+        let Some(previous) = self.surface_history.pop() else {
+            self.notify_message(Severity::Info, "notify-nothing-to-undo", &[]);
+            return;
+        };
+        match self.renderer.update_surface(previous) {
+            Ok(()) => {
+                self.provenance
+                    .record("Last preprocessing step undone".to_owned());
+                self.notify_message(Severity::Info, "notify-undone", &[]);
+                self.window.request_redraw();
+            }
+            Err(e) => self.notify_message(
+                Severity::Error,
+                "notify-undo-failed",
+                &[("error", e.to_string())],
+            ),
+        }
+    }
+
+    /// Searches the extrema in the background and replaces the markers with them
+    fn find_extrema(
+        &mut self,
+        count: u32,
+        min_separation: f32,
+        sender: Option<futures::channel::oneshot::Sender<Vec<Extremum>>>,
+    ) {
+        let Some(surface) = self.renderer.surface_image() else {
+            log::warn!("No surface to find peaks and valleys in");
+            return;
+        };
+        self.jobs.spawn(
+            "Peak and valley detection",
+            move |_| Ok(extrema::find_extrema(&surface, count, min_separation)),
+            move |state, extrema| {
+                let markers = extrema
+                    .iter()
+                    .map(|extremum| extremum.to_marker())
+                    .collect();
+                state.renderer.set_markers(Arc::new(markers));
+                state.set_selection(None);
+                state.set_modified();
+                let extrema: Vec<Extremum> = extrema
+                    .into_iter()
+                    .map(|extremum| extremum.to_display(state.units))
+                    .collect();
+                let peaks = extrema
+                    .iter()
+                    .filter(|extremum| extremum.kind == ExtremumKind::Peak)
+                    .count();
+                state.notify_message(
+                    Severity::Info,
+                    "notify-extrema",
+                    &[
+                        ("peaks", peaks.to_string()),
+                        ("valleys", (extrema.len() - peaks).to_string()),
+                    ],
+                );
+                #[cfg(target_arch = "wasm32")]
+                state.dispatch_canvas_event(
+                    "extrema",
+                    &extrema
+                        .iter()
+                        .map(|extremum| wasm_bindgen::JsValue::from(*extremum))
+                        .collect::<js_sys::Array>(),
+                );
+                if let Some(sender) = sender
+                    && sender.send(extrema).is_err()
+                {
+                    log::error!("Failed to return peaks and valleys");
+                }
+                state.window.request_redraw();
+            },
+        );
+    }
+
+    /// Evaluates the tolerance and writes the report with a screenshot of the deviation,
+    /// by default next to the dataset as `<dataset>-report.html`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_tolerance_report(&mut self, path: Option<String>) {
+        let (Some(surface), Some(reference)) = (
+            self.renderer.surface_image(),
+            self.renderer.reference.clone(),
+        ) else {
+            log::error!("A reference surface is needed for the tolerance report");
+            return;
+        };
+        let dataset = self.title.dataset.clone().unwrap_or_default();
+        let path = path.map(std::path::PathBuf::from).unwrap_or_else(|| {
+            let dataset_path =
+                std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"));
+            let stem = dataset_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            dataset_path.with_file_name(format!("{}-report.html", stem))
+        });
+
+        // The screenshot always shows the deviation, whatever is displayed right now
+        let show_residual = self.renderer.show_residual;
+        self.renderer.show_residual = true;
+        let screenshot =
+            offscreen::capture(&mut self.renderer, self.surface_format.add_srgb_suffix());
+        self.renderer.show_residual = show_residual;
+        let screenshot = match screenshot {
+            Ok(screenshot) => screenshot,
+            Err(e) => {
+                self.notify_message(
+                    Severity::Error,
+                    "notify-report-failed",
+                    &[("error", e.to_string())],
+                );
+                return;
+            }
+        };
+
+        // Evaluating and encoding large surfaces takes a while
+        let tolerance = self.renderer.tolerance;
+        let units = self.units;
+        let provenance = self.provenance.entries().to_vec();
+        self.jobs.spawn(
+            "Tolerance report",
+            move |context| {
+                let evaluation = ToleranceEvaluation::evaluate(&surface, &reference, tolerance);
+                context.set_progress(0.5);
+                context.check()?;
+                report::ToleranceReport {
+                    dataset: &dataset,
+                    evaluation,
+                    tolerance,
+                    max_fraction: report::DEFAULT_MAX_FRACTION,
+                    units,
+                    screenshot: &screenshot,
+                    provenance: &provenance,
+                }
+                .write(&path)?;
+                Ok(path)
+            },
+            |state, path| {
+                state.notify_message(
+                    Severity::Success,
+                    "notify-report-written",
+                    &[("path", path.display().to_string())],
+                )
+            },
+        );
+    }
+
+    /// Markers and overlays as CSV, only those of `groups` if given and else those of the shown
+    /// groups. The CSV is returned if a sender is given, otherwise written next to the dataset
+    /// as `<dataset>-annotations.csv`.
+    fn export_annotations(
+        &mut self,
+        groups: Option<Vec<String>>,
+        sender: Option<futures::channel::oneshot::Sender<Option<String>>>,
+    ) {
+        let groups = groups.unwrap_or_else(|| {
+            let shown = self
+                .renderer
+                .annotation_group_names()
+                .into_iter()
+                .filter(|group| self.renderer.annotation_groups.style(Some(group)).visible);
+            // Ungrouped annotations are always shown
+            std::iter::once(String::new()).chain(shown).collect()
+        });
+        let csv = self.renderer.annotations_csv(Some(&groups));
+        if let Some(sender) = sender {
+            if sender.send(csv).is_err() {
+                log::error!("Failed to return annotations");
+            }
+        } else {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let Some(csv) = csv else {
+                    log::error!("No surface to export annotations of");
+                    return;
+                };
+                let dataset_path =
+                    std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"));
+                let stem = dataset_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let path = dataset_path.with_file_name(format!("{}-annotations.csv", stem));
+                match std::fs::write(&path, csv) {
+                    Ok(()) => self.notify_message(
+                        Severity::Success,
+                        "notify-annotations-exported",
+                        &[("path", path.display().to_string())],
+                    ),
+                    Err(e) => self.notify_message(
+                        Severity::Error,
+                        "notify-annotations-failed",
+                        &[("error", e.to_string())],
+                    ),
+                }
+            }
+        }
+    }
+
+    /// The audit trail as CSV or JSON with its signature. Both are returned if a sender is given,
+    /// otherwise written next to the dataset as `<dataset>-audit.csv` and `<dataset>-audit.csv.sig`.
+    fn export_audit_trail(
+        &mut self,
+        json: bool,
+        sender: Option<futures::channel::oneshot::Sender<[String; 2]>>,
+    ) {
+        let now = audit::now();
+        let log = if json {
+            self.audit.to_json(now)
+        } else {
+            self.audit.to_csv(now)
+        };
+        let signature = audit::sign(&log, self.audit_key.as_deref());
+        if let Some(sender) = sender {
+            if sender.send([log, signature]).is_err() {
+                log::error!("Failed to return the audit trail");
+            }
+        } else {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let dataset_path =
+                    std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"));
+                let stem = dataset_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let extension = if json { "json" } else { "csv" };
+                let path = dataset_path.with_file_name(format!("{}-audit.{}", stem, extension));
+                let signature_path =
+                    dataset_path.with_file_name(format!("{}-audit.{}.sig", stem, extension));
+                match std::fs::write(&path, log)
+                    .and_then(|()| std::fs::write(&signature_path, signature + "\n"))
+                {
+                    Ok(()) => self.notify_message(
+                        Severity::Success,
+                        "notify-audit-exported",
+                        &[("path", path.display().to_string())],
+                    ),
+                    Err(e) => self.notify_message(
+                        Severity::Error,
+                        "notify-audit-failed",
+                        &[("error", e.to_string())],
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Writes the heights as shown, with the colormap legend, a scale bar and a caption naming
+    /// the dataset, the color range and the processing, by default next to the dataset as
+    /// `<dataset>-figure.svg`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_figure(&mut self, path: Option<String>) {
+        let Some(view_transform) = self.view_transform() else {
+            log::error!("No surface to export a figure of");
+            return;
+        };
+        let path = path.map(std::path::PathBuf::from).unwrap_or_else(|| {
+            let dataset_path =
+                std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"));
+            let stem = dataset_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            dataset_path.with_file_name(format!("{}-figure.svg", stem))
+        });
+
+        // The legend describes the height colors, whatever is displayed right now
+        let (use_height_shader, show_residual) =
+            (self.renderer.use_height_shader, self.renderer.show_residual);
+        self.renderer.use_height_shader = true;
+        self.renderer.show_residual = false;
+        let view = offscreen::capture(&mut self.renderer, self.surface_format.add_srgb_suffix());
+        self.renderer.use_height_shader = use_height_shader;
+        self.renderer.show_residual = show_residual;
+        let view = match view {
+            Ok(view) => view,
+            Err(e) => {
+                self.notify_message(
+                    Severity::Error,
+                    "notify-figure-failed",
+                    &[("error", e.to_string())],
+                );
+                return;
+            }
+        };
+
+        // Screen distance of neighboring pixels at the center
+        let center = view_transform.screen_to_pixel(glam::Vec2::new(
+            view.width as f32 / 2.0,
+            view.height as f32 / 2.0,
+        ));
+        let pixels_per_unit = center.map_or(0.0, |(x, y, value)| {
+            let pixel = glam::Vec2::new(x as f32, y as f32);
+            let screen =
+                |pixel| view_transform.world_to_screen(view_transform.pixel_to_world(pixel, value));
+            screen(pixel).distance(screen(pixel + glam::Vec2::X)) / self.pixel_pitch
+        });
+        let figure = figure::Figure {
+            dataset: self.title.dataset.as_deref().unwrap_or("Surface"),
+            view: &view,
+            colormap: self.renderer.colormap,
+            height_range: self.renderer.render_settings.height_range,
+            units: self.units,
+            pixels_per_unit,
+            z_scale: self.renderer.z_scale,
+            provenance: self.provenance.entries(),
+        };
+        match figure.write(&path) {
+            Ok(()) => self.notify_message(
+                Severity::Success,
+                "notify-figure-exported",
+                &[("path", path.display().to_string())],
+            ),
+            Err(e) => self.notify_message(
+                Severity::Error,
+                "notify-figure-failed",
+                &[("error", e.to_string())],
+            ),
+        }
+    }
+
+    /// Runs `apply` once the running jobs are done, e.g. to export a surface still loading
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn after_jobs(&mut self, apply: impl FnOnce(&mut State) + 'static) {
+        if self.jobs.is_busy() {
+            self.jobs.then(apply);
+        } else {
+            apply(self);
+        }
+    }
+
+    /// Applies the results of finished jobs and shows their progress in the title
+    fn poll_jobs(&mut self) {
+        for finished in self.jobs.take_finished() {
+            match finished {
+                Finished::Done(apply) => apply(self),
+                Finished::Failed(name, e) => self.notify_message(
+                    Severity::Error,
+                    "notify-failed",
+                    &[("name", name.to_string()), ("error", e.to_string())],
+                ),
+            }
+        }
+        let progress = self.jobs.progress();
+        if progress != self.job_progress {
+            self.job_progress = progress;
+            self.set_loading(progress);
+        }
+    }
+
+    fn cancel_jobs(&mut self, id: Option<u32>) {
+        let cancelled = self.jobs.cancel(id);
+        if cancelled.is_empty() {
+            log::info!("No job to cancel");
+        }
+        for name in cancelled {
+            self.notify_message(
+                Severity::Warning,
+                "notify-cancelled",
+                &[("name", name.to_string())],
+            );
+        }
+        self.poll_jobs();
+    }
+
+    /// Tells the user about loads, errors, exports and measurement results. The page hosting the
+    /// viewer gets a `notification` event on the canvas to show it as a toast.
+    fn notify(&mut self, severity: Severity, message: String) {
+        let notification = self.notifications.push(severity, message).clone();
+        #[cfg(target_arch = "wasm32")]
+        self.dispatch_canvas_event("notification", &wasm_bindgen::JsValue::from(notification));
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = notification;
+    }
+
+    /// Notifies with the message `id` of the locales in the current language, `args` are the
+    /// values of its placeables
+    fn notify_message(&mut self, severity: Severity, id: &str, args: &[(&'static str, String)]) {
+        let mut message_args = i18n::MessageArgs::new();
+        for (key, value) in args {
+            message_args.set(*key, value.clone());
+        }
+        let message = self.localizer.format(id, Some(&message_args));
+        self.notify(severity, message);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn dispatch_canvas_event(&self, name: &str, detail: &wasm_bindgen::JsValue) {
+        use winit::platform::web::WindowExtWebSys;
+
+        let init = web_sys::CustomEventInit::new();
+        init.set_detail(detail);
+        match web_sys::CustomEvent::new_with_event_init_dict(name, &init) {
+            Ok(event) => {
+                if let Some(canvas) = self.window.canvas() {
+                    let _ = canvas.dispatch_event(&event);
+                }
+            }
+            Err(e) => log::error!("Failed to create {} event: {:?}", name, e),
+        }
+    }
+
+    /// Writes a screenshot of the current view to `<dir>/<dataset>.png`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn archive_screenshot(&mut self, dir: &std::path::Path) {
+        let stem = std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"))
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let path = dir.join(format!("{}.png", stem));
+        let result = offscreen::capture(&mut self.renderer, self.surface_format.add_srgb_suffix())
+            .and_then(|screenshot| Ok(std::fs::write(&path, screenshot.to_png()?)?));
+        match result {
+            Ok(()) => self.notify_message(
+                Severity::Success,
+                "notify-screenshot-archived",
+                &[("path", path.display().to_string())],
+            ),
+            Err(e) => self.notify_message(
+                Severity::Error,
+                "notify-screenshot-failed",
+                &[("error", e.to_string())],
+            ),
+        }
+    }
+
+    fn set_residual_mode(&mut self, enabled: bool) {
+        self.renderer.set_show_residual(enabled);
+        self.window.request_redraw();
+    }
+
+    fn set_temporal_collecting(&mut self, collecting: bool) {
+        self.renderer.set_temporal_collecting(collecting);
+        let frames = self
+            .renderer
+            .temporal
+            .as_ref()
+            .map_or(0, TemporalStats::frames);
+        if collecting {
+            self.notify_message(Severity::Info, "notify-temporal-collecting", &[]);
+        } else {
+            self.notify_message(
+                Severity::Info,
+                "notify-temporal-collected",
+                &[("frames", frames.to_string())],
+            );
+        }
+    }
+
+    /// Shows a map of the temporal statistics of the collected surfaces instead of the surface
+    #[cfg(not(target_arch = "wasm32"))]
+    fn show_temporal_map(&mut self, map: TemporalMap) {
+        let Some(maps) = self.renderer.temporal_maps() else {
+            self.notify_message(Severity::Warning, "notify-temporal-empty", &[]);
+            return;
+        };
+        match pollster::block_on(maps) {
+            Ok(maps) => self.set_temporal_map(maps, map),
+            Err(e) => self.notify_message(
+                Severity::Error,
+                "notify-temporal-failed",
+                &[("error", e.to_string())],
+            ),
+        }
+    }
+
+    fn set_temporal_map(&mut self, maps: TemporalMaps, map: TemporalMap) {
+        // The map itself is no frame of the sequence
+        self.renderer.set_temporal_collecting(false);
+        self.set_dataset_name(format!("Temporal {} of {} frames", map.name(), maps.frames));
+        let (registration, tracker) = (self.registration.take(), self.tracker.take());
+        self.set_surface(maps.take(map), None, None);
+        (self.registration, self.tracker) = (registration, tracker);
+        self.window.request_redraw();
+    }
+
+    fn set_registration(&mut self, enabled: bool) {
+        if enabled == self.registration.is_some() {
+            return;
+        }
+        // The current surface is the first frame the others are aligned with
+        self.registration = enabled.then(|| {
+            let mut registration = Registration::default();
+            if let Some(surface) = self.renderer.surface_image() {
+                registration.start(&surface);
+            }
+            registration
+        });
+        let id = if enabled {
+            "notify-registration-started"
+        } else {
+            "notify-registration-stopped"
+        };
+        self.notify_message(Severity::Info, id, &[]);
+    }
+
+    fn track_region(&mut self, region: Option<Region>) {
+        let Some(region) = region else {
+            if self.tracker.take().is_some() {
+                self.notify_message(Severity::Info, "notify-tracking-stopped", &[]);
+            }
+            return;
+        };
+        let tracker = self.renderer.surface_image().and_then(|surface| {
+            RegionTracker::new(&surface, region, tracking::DEFAULT_SEARCH_RADIUS)
+        });
+        if tracker.is_some() {
+            self.notify_message(
+                Severity::Info,
+                "notify-tracking-started",
+                &[
+                    ("width", region.width.to_string()),
+                    ("height", region.height.to_string()),
+                    ("x", region.x.to_string()),
+                    ("y", region.y.to_string()),
+                ],
+            );
+        } else {
+            self.notify_message(Severity::Error, "notify-tracking-invalid", &[]);
+        }
+        self.tracker = tracker;
+    }
+
+    /// Tracks the bounding box of the selected overlay
+    fn track_selection(&mut self) {
+        let region = self.selection.and_then(|selection| {
+            let texture = self.renderer.texture.as_ref()?;
+            let overlay = match selection.kind {
+                SelectionKind::Overlay => texture.overlay.overlays.get(selection.index as usize)?,
+                SelectionKind::Marker => return None,
+            };
+            Region::bounding(&overlay.pixels, texture.surface.image_size().width.get())
+        });
+        if region.is_some() {
+            self.track_region(region);
+        } else {
+            self.notify_message(Severity::Warning, "notify-select-to-track", &[]);
+        }
+    }
+
+    /// The track of the region as `format`. Returned if a sender is given, otherwise written
+    /// next to the dataset as `<dataset>-track.<format>`.
+    fn export_region_track(
+        &mut self,
+        format: TrackFormat,
+        sender: Option<futures::channel::oneshot::Sender<Option<String>>>,
+    ) {
+        let track = self
+            .tracker
+            .as_ref()
+            .map(|tracker| tracker.export(format, self.units));
+        if let Some(sender) = sender {
+            if sender.send(track).is_err() {
+                log::error!("Failed to return region track");
+            }
+        } else {
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                let Some(track) = track else {
+                    log::error!("No tracked region to export");
+                    return;
+                };
+                let dataset_path =
+                    std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"));
+                let stem = dataset_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                let extension = match format {
+                    TrackFormat::Csv => "csv",
+                    TrackFormat::Json => "json",
+                    TrackFormat::Svg => "svg",
+                };
+                let path = dataset_path.with_file_name(format!("{}-track.{}", stem, extension));
+                match std::fs::write(&path, track) {
+                    Ok(()) => self.notify_message(
+                        Severity::Success,
+                        "notify-track-exported",
+                        &[("path", path.display().to_string())],
+                    ),
+                    Err(e) => self.notify_message(
+                        Severity::Error,
+                        "notify-track-failed",
+                        &[("error", e.to_string())],
+                    ),
+                }
+            }
+        }
+    }
+
+    fn set_baseline(&mut self, mode: Option<BaselineMode>) {
+        self.renderer.set_baseline(mode);
+        if let Some(mode) = mode {
+            match mode {
+                BaselineMode::FirstFrame => {
+                    self.notify_message(Severity::Info, "notify-baseline-first-frame", &[])
+                }
+                BaselineMode::RollingAverage { weight } => self.notify_message(
+                    Severity::Info,
+                    "notify-baseline-rolling",
+                    &[("weight", weight.to_string())],
+                ),
+            }
+        }
+        self.window.request_redraw();
+    }
+
+    fn set_checkerboard(&mut self, enabled: bool, tile_size: Option<u32>) {
+        if enabled && !self.renderer.has_reference() {
+            self.notify_message(Severity::Warning, "notify-reference-missing", &[]);
+        }
+        let longer_side = self.renderer.texture.as_ref().map_or(1, |texture| {
+            let size = texture.surface.image_size();
+            size.width.get().max(size.height.get())
+        });
+        let tile_size = enabled.then(|| tile_size.unwrap_or((longer_side / 8).max(1)));
+        self.renderer.set_checkerboard(tile_size);
+        self.window.request_redraw();
+    }
+
+    /// `lower` and `upper` are given in the display unit
+    fn set_tolerance(&mut self, lower: f32, upper: f32) {
+        match Tolerance::new(self.units.to_data(lower), self.units.to_data(upper)) {
+            Ok(tolerance) => {
+                self.renderer.set_tolerance(tolerance);
+                self.window.request_redraw();
+            }
+            Err(_) => self.notify_message(
+                Severity::Error,
+                "notify-invalid-tolerance",
+                &[("lower", lower.to_string()), ("upper", upper.to_string())],
+            ),
+        }
+    }
+
+    /// Heights are given in the display unit
+    fn set_display_range(&mut self, layer: Layer, min: f32, max: f32) {
+        if !min.is_finite() || !max.is_finite() || min >= max {
+            self.notify_message(
+                Severity::Error,
+                "notify-invalid-display-range",
+                &[("min", min.to_string()), ("max", max.to_string())],
+            );
+            return;
+        }
+        let (min, max) = match layer {
+            Layer::Height => (self.units.to_data(min), self.units.to_data(max)),
+            Layer::Amplitude => (min, max),
+        };
+        self.renderer.set_display_range(layer, min, max);
+        self.window.request_redraw();
+    }
+
+    /// Heights are returned in the display unit
+    fn histogram(&self, layer: Layer) -> Option<Histogram> {
+        let histogram = self.renderer.histogram(layer)?;
+        Some(match layer {
+            Layer::Height => histogram.map_values(|value| self.units.to_display(value)),
+            Layer::Amplitude => histogram,
+        })
+    }
+
+    /// Borderless fullscreen on the monitor the window is on
+    fn set_fullscreen(&mut self, enabled: bool) {
+        log::info!("Setting fullscreen to {}", enabled);
+        self.window
+            .set_fullscreen(enabled.then_some(winit::window::Fullscreen::Borderless(None)));
+    }
+
+    fn set_dataset_name(&mut self, name: String) {
+        self.title.dataset = Some(name.clone());
+        self.title.demo = false;
+        self.dataset_info.name = Some(name);
+        self.update_window_title();
+    }
+
+    fn set_loading(&mut self, progress: Option<Progress>) {
+        self.title.loading = progress;
+        self.update_window_title();
+    }
+
+    /// Marks annotations of the dataset as changed in the window title
+    fn set_modified(&mut self) {
+        if !self.title.modified {
+            self.title.modified = true;
+            self.update_window_title();
+        }
+    }
+
+    fn set_ui_scale(&mut self, scale: Option<f32>) {
+        self.ui_scale.set_override(scale);
+        self.ui_scale_changed();
+    }
+
+    /// Redraws the markers at the new scale. The page gets a `ui-scale` event with the scale
+    /// relative to the device pixel ratio, to size the notifications like the HUD.
+    fn ui_scale_changed(&mut self) {
+        log::info!("UI scale set to {:.2}", self.ui_scale.get());
+        self.renderer.set_ui_scale(self.ui_scale.get());
+        #[cfg(target_arch = "wasm32")]
+        self.dispatch_canvas_event(
+            "ui-scale",
+            &wasm_bindgen::JsValue::from(self.ui_scale.relative()),
+        );
+        self.window.request_redraw();
+    }
+
+    fn back_to_origin(&mut self) {
+        self.animate_camera(Renderer::reset_camera);
+    }
+
+    /// Moves the camera smoothly to where `change` would put it
+    fn animate_camera(&mut self, change: impl FnOnce(&mut Renderer)) {
+        let from = self.renderer.camera();
+        change(&mut self.renderer);
+        let to = self.renderer.camera();
+        self.renderer.set_camera(from);
+        self.camera_transition = Some(CameraTransition::new(
+            from,
+            to,
+            CameraTransition::DEFAULT_DURATION,
+        ));
+        self.window.request_redraw();
+    }
+
+    /// Stops a running camera animation, e.g. when the user starts dragging
+    fn stop_animations(&mut self) {
+        self.camera_transition = None;
+        self.path_playback = None;
+    }
+
+    /// Adds the current camera as keyframe of the camera path at `time` in seconds, `None`
+    /// appends it `CameraPath::DEFAULT_SPACING` after the last keyframe
+    fn add_keyframe(&mut self, time: Option<f32>) {
+        let camera = self.renderer.camera();
+        let time = match time {
+            Some(seconds) => match web_time::Duration::try_from_secs_f32(seconds) {
+                Ok(time) => {
+                    self.camera_path.add(time, camera);
+                    time
+                }
+                Err(e) => {
+                    self.notify_message(
+                        Severity::Error,
+                        "notify-invalid-keyframe-time",
+                        &[("seconds", seconds.to_string()), ("error", e.to_string())],
+                    );
+                    return;
+                }
+            },
+            None => self.camera_path.append(camera),
+        };
+        let keyframe = self.camera_path.keyframes().len();
+        self.notify_message(
+            Severity::Info,
+            "notify-keyframe",
+            &[
+                ("keyframe", keyframe.to_string()),
+                ("seconds", format!("{:.1}", time.as_secs_f32())),
+            ],
+        );
+    }
+
+    fn clear_keyframes(&mut self) {
+        self.camera_path.clear();
+        self.path_playback = None;
+        self.notify_message(Severity::Info, "notify-camera-path-cleared", &[]);
+    }
+
+    /// Whether the camera path has enough keyframes to move along, notifies the user otherwise
+    fn check_camera_path(&mut self) -> bool {
+        let enough = self.camera_path.keyframes().len() >= 2;
+        if !enough {
+            self.notify_message(Severity::Warning, "notify-camera-path-too-short", &[]);
+        }
+        enough
+    }
+
+    /// Starts or stops moving the camera along the camera path
+    fn play_camera_path(&mut self, play: bool) {
+        if play && !self.check_camera_path() {
+            return;
+        }
+        self.stop_animations();
+        self.path_playback = play.then(CameraPathPlayback::default);
+        self.request_animation_frame();
+    }
+
+    /// Writes the camera path frame by frame as PNG files into `dir`, by default next to the
+    /// dataset
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_camera_path(&mut self, dir: Option<String>, fps: u32) {
+        if !self.check_camera_path() {
+            return;
+        }
+        let dir = dir.map(std::path::PathBuf::from).unwrap_or_else(|| {
+            let dataset_path =
+                std::path::Path::new(self.dataset_path.as_deref().unwrap_or("surface"));
+            let stem = dataset_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            dataset_path.with_file_name(format!("{}-camera-path", stem))
+        });
+        // The frames are traceable to the data they show
+        let created = std::fs::create_dir_all(&dir)
+            .and_then(|()| std::fs::write(dir.join("provenance.txt"), self.provenance.to_text()));
+        if let Err(e) = created {
+            self.notify_message(
+                Severity::Error,
+                "notify-camera-path-failed",
+                &[("error", e.to_string())],
+            );
+            return;
+        }
+        self.stop_animations();
+        self.path_export = Some(camera_path::FrameExport::new(dir, fps));
+        self.request_animation_frame();
+    }
+
+    /// Renders and writes the next frame of a camera path export
+    #[cfg(not(target_arch = "wasm32"))]
+    fn export_path_frame(&mut self) {
+        let Some(export) = &mut self.path_export else {
+            return;
+        };
+        let interval = export.frame_interval();
+        let Some(camera) = export.playback.advance(&self.camera_path, interval) else {
+            let args = [
+                ("frames", export.frames.to_string()),
+                ("path", export.dir.display().to_string()),
+            ];
+            self.path_export = None;
+            self.notify_message(Severity::Success, "notify-camera-path-exported", &args);
+            return;
+        };
+        let file = export.next_file();
+        self.set_camera(camera);
+        let result = offscreen::capture(&mut self.renderer, self.surface_format.add_srgb_suffix())
+            .and_then(|frame| Ok(std::fs::write(&file, frame.to_png()?)?));
+        if let Err(e) = result {
+            self.path_export = None;
+            self.notify_message(
+                Severity::Error,
+                "notify-camera-path-failed",
+                &[("error", e.to_string())],
+            );
+        }
+    }
+
+    /// Advances running animations by the time since the last frame
+    fn advance_animations(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let exporting = self.path_export.is_some();
+        #[cfg(target_arch = "wasm32")]
+        let exporting = false;
+        if self.camera_transition.is_none()
+            && self.path_playback.is_none()
+            && !exporting
+            && !self.blink.is_running()
+        {
+            self.frame_clock.pause();
+            return;
+        }
+        let delta = self.frame_clock.tick();
+        if let Some(state) = self.blink.advance(delta, self.renderer.visual_state()) {
+            self.renderer.set_visual_state(state);
+        }
+        if let Some(transition) = &mut self.camera_transition {
+            let camera = transition.advance(delta);
+            if transition.is_finished() {
+                self.camera_transition = None;
+            }
+            self.set_camera(camera);
+            self.publish_camera();
+        }
+        if let Some(playback) = &mut self.path_playback {
+            match playback.advance(&self.camera_path, delta) {
+                Some(camera) => {
+                    self.set_camera(camera);
+                    self.publish_camera();
+                }
+                None => self.path_playback = None,
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        self.export_path_frame();
+        self.request_animation_frame();
+    }
+
+    /// Requests the next frame of a running animation, later if the frame rate is capped
+    fn request_animation_frame(&mut self) {
+        match self.frame_limiter.next_frame(web_time::Instant::now()) {
+            Some(at) => self.redraw_at = Some(at),
+            None => self.window.request_redraw(),
+        }
+    }
+
+    /// Caps the frame rate of animations like blinking, `None` removes the cap
+    fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.frame_limiter.set_max_fps(max_fps);
+        match self.frame_limiter.max_fps() {
+            Some(fps) => log::info!("Animations limited to {:.1} fps", fps),
+            None => log::info!("Animation frame rate not limited"),
+        }
+    }
+
+    /// Frame time to hold with a coarser mesh while the camera moves, `None` turns it off
+    fn set_target_frame_time(&mut self, target: Option<web_time::Duration>) {
+        match target {
+            Some(target) => log::info!("Target frame time: {:?}", target),
+            None => log::info!("Quality scaling disabled"),
+        }
+        self.quality.set_target(target);
+        self.renderer.set_preview_step(1);
+        self.window.request_redraw();
+    }
+
+    /// Refines the mesh step by step once the camera rested long enough, returns when to check
+    /// again
+    fn restore_quality(&mut self, now: web_time::Instant) -> Option<web_time::Instant> {
+        let at = self.quality.refine_at()?;
+        if at > now {
+            return Some(at);
+        }
+        self.renderer.set_refinement(self.quality.refine(now));
+        self.window.request_redraw();
+        // Fading needs a frame at the display rate, not a busy loop
+        self.quality
+            .refine_at()
+            .map(|at| at.max(now + QualityScaler::FADE_FRAME_INTERVAL))
+    }
+
+    /// Writes the dataset URL and view into the URL hash if enabled, returns when to check again
+    #[cfg(target_arch = "wasm32")]
+    fn update_url_hash(&mut self, now: web_time::Instant) -> Option<web_time::Instant> {
+        let url_hash = self.url_hash.as_mut()?;
+        self.renderer.texture.as_ref()?;
+        let [dataset, amplitude] = self.dataset_urls.clone();
+        url_hash.update(now, || deep_link::DeepLink {
+            dataset,
+            amplitude,
+            view: self.renderer.view_state(),
+        })
+    }
+
+    /// Applies the mouse sensitivity and inverted axes to rotating, panning and zooming
+    fn set_navigation(&mut self, settings: NavigationSettings) {
+        log::info!("Navigation settings: {}", settings);
+        let (x, y) = settings.rotate_factors();
+        self.renderer
+            .transformation
+            .set_speed(glam::Vec2::new(x, y));
+        self.renderer.projection.set_pan_speed(settings.pan_speed);
+        self.mouse.set_zoom_speed(settings.zoom_factor());
+        self.navigation = settings;
+    }
+
+    /// Moves the view by the axes of gamepads and 6DoF devices, returns whether it moved
+    fn poll_gamepads(&mut self, now: web_time::Instant) -> bool {
+        let Some(motion) = self.gamepads.poll(now, &self.navigation) else {
+            return false;
+        };
+        self.stop_animations();
+        self.renderer.transformation.rotate_by(motion.rotate);
+        let zoom = self.renderer.projection.get_zoom();
+        // Pan the same distance on screen at any magnification
+        let pan = self.renderer.projection.get_pan() + motion.pan * zoom;
+        self.renderer.projection.set_pan(pan);
+        self.renderer.projection.zoom(zoom * motion.zoom_factor);
+        self.mouse.set_zoom(zoom * motion.zoom_factor);
+        self.publish_camera();
+        self.window.request_redraw();
+        true
+    }
+
+    /// Stores the current shader, colormap and color vision simulation to blink against
+    fn store_blink_state(&mut self) {
+        log::info!("Stored display settings for comparison");
+        self.blink.store(self.renderer.visual_state());
+    }
+
+    fn swap_blink_state(&mut self) {
+        match self.blink.swap(self.renderer.visual_state()) {
+            Some(state) => {
+                self.renderer.set_visual_state(state);
+                self.window.request_redraw();
+            }
+            None => log::warn!("No display settings stored to compare with"),
+        }
+    }
+
+    /// Swaps the display settings every `interval`, `None` stops blinking
+    fn set_blink_interval(&mut self, interval: Option<web_time::Duration>) {
+        log::info!("Setting blink interval to {:?}", interval);
+        self.blink.set_interval(interval);
+        self.window.request_redraw();
+    }
+
+    fn set_view(&mut self, azimuth: f32, elevation: f32, zoom: Option<f32>) {
+        if zoom.is_some_and(|zoom| !zoom.is_finite() || zoom <= 0.0) {
+            log::error!("Zoom must be a positive number");
+            return;
+        }
+        log::info!(
+            "Setting view to azimuth {}°, elevation {}°",
+            azimuth,
+            elevation
+        );
+        self.animate_camera(|renderer| renderer.set_view(azimuth, elevation, zoom));
+    }
+
+    fn set_rotation_mode(&mut self, mode: RotationMode) {
+        log::info!("Setting rotation mode to {:?}", mode);
+        self.renderer.transformation.set_mode(mode);
+        self.publish_camera();
+        self.window.request_redraw();
+    }
+
+    /// Logs the current view in the form accepted by `set_view` so it can be reproduced
+    fn log_view(&self) {
+        let (azimuth, elevation) = self.renderer.transformation.get_orientation();
+        log::info!(
+            "View: azimuth {:.1}°, elevation {:.1}°, zoom {:.2}",
+            azimuth,
+            elevation,
+            1.0 / self.renderer.projection.get_zoom()
+        );
+    }
+
+    fn set_camera(&mut self, camera: CameraState) {
+        self.renderer.set_camera(camera);
+        self.mouse.set_zoom(camera.zoom);
+    }
+
+    fn apply_view_state(&mut self, view_state: &ViewState) {
+        self.renderer.apply_view_state(view_state);
+        self.mouse.set_zoom(view_state.camera.zoom);
+        self.publish_camera();
+    }
+
+    /// Lets synchronized viewers follow a camera change made in this viewer
+    fn publish_camera(&self) {
+        if self.camera_sync.is_joined() {
+            self.camera_sync.publish(self.renderer.camera());
+        }
+    }
+
+    /// `None` stops following other viewers
+    fn set_camera_sync(&mut self, group: Option<String>) {
+        match group {
+            Some(group) => match self.camera_sync.join(&group, self.window.clone()) {
+                Ok(()) => log::info!("Synchronizing camera with group '{}'", group),
+                Err(e) => log::error!("Failed to synchronize camera: {}", e),
+            },
+            None => {
+                log::info!("Stopped synchronizing camera");
+                self.camera_sync.leave();
+            }
+        }
+    }
+}
+
+struct ImageViewer3D {
+    #[cfg(target_arch = "wasm32")]
+    proxy: Option<winit::event_loop::EventLoopProxy<ViewerCommand>>,
+    states: HashMap<WindowId, State>,
+    /// Window that receives commands, the most recently focused one
+    active_window: Option<WindowId>,
+    /// Created with the first window and shared with all further windows
+    #[cfg(not(target_arch = "wasm32"))]
+    gpu: Option<Gpu>,
+    #[cfg(not(target_arch = "wasm32"))]
+    watch: Option<watch::FolderWatch>,
+    /// When the watched directory is scanned next
+    #[cfg(not(target_arch = "wasm32"))]
+    next_watch_poll: Option<web_time::Instant>,
+    #[cfg(not(target_arch = "wasm32"))]
+    recorder: Option<input::InputRecorder>,
+    #[cfg(not(target_arch = "wasm32"))]
+    replay: Option<input::InputReplay>,
+    /// GPU to prefer when the first window is opened
+    power_preference: wgpu::PowerPreference,
+    /// Applied to the images of every window, see `Renderer::retention`
+    #[cfg(not(target_arch = "wasm32"))]
+    retention: retention::RetentionPolicy,
+    /// Shared by the windows, see `State::cache`
+    #[cfg(not(target_arch = "wasm32"))]
+    cache: Option<Arc<cache::DatasetCache>>,
+    /// Applied to every window, see `Renderer::set_compute_device`
+    #[cfg(not(target_arch = "wasm32"))]
+    compute: Option<compute::ComputeDevice>,
+    /// Applied to every window, see `Renderer::set_color_space`
+    #[cfg(not(target_arch = "wasm32"))]
+    color_space: color_space::OutputColorSpace,
+    /// Inner size of new windows, chosen by the system if `None`
+    #[cfg(not(target_arch = "wasm32"))]
+    window_size: Option<winit::dpi::PhysicalSize<u32>>,
+    /// Defaults of new windows, see `State::new`
+    config: config::ViewerConfig,
+}
+
+impl ImageViewer3D {
+    pub fn new(
+        #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<ViewerCommand>,
+        power_preference: wgpu::PowerPreference,
+        #[cfg(not(target_arch = "wasm32"))] retention: retention::RetentionPolicy,
+        #[cfg(not(target_arch = "wasm32"))] cache: Option<cache::DatasetCache>,
+    ) -> Self {
+        #[cfg(target_arch = "wasm32")]
+        let proxy = Some(event_loop.create_proxy());
+        Self {
+            states: HashMap::new(),
+            active_window: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            gpu: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            watch: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            next_watch_poll: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            replay: None,
+            power_preference,
+            #[cfg(not(target_arch = "wasm32"))]
+            retention,
+            #[cfg(not(target_arch = "wasm32"))]
+            cache: cache.map(Arc::new),
+            #[cfg(not(target_arch = "wasm32"))]
+            compute: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            color_space: Default::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            window_size: None,
+            config: config::ViewerConfig::default(),
+            #[cfg(target_arch = "wasm32")]
+            proxy,
+        }
+    }
+
+    fn active_state(&mut self) -> Option<&mut State> {
+        self.active_window.and_then(|id| self.states.get_mut(&id))
+    }
+
+    fn insert_state(&mut self, state: State) {
+        let id = state.window.id();
+        self.states.insert(id, state);
+        self.active_window = Some(id);
+    }
+
+    fn window_attributes(&self) -> winit::window::WindowAttributes {
+        #[allow(unused_mut)]
+        let mut window_attributes = Window::default_attributes();
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            use wasm_bindgen::JsCast;
+            use winit::platform::web::WindowAttributesExtWebSys;
+
+            const CANVAS_ID: &str = "canvas";
+
+            let window = wgpu::web_sys::window().unwrap_throw();
+            let document = window.document().unwrap_throw();
+            let canvas = document.get_element_by_id(CANVAS_ID).unwrap_throw();
+            let html_canvas_element = canvas.unchecked_into();
+            window_attributes = window_attributes.with_canvas(Some(html_canvas_element));
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            window_attributes = window_attributes.with_window_icon(window_title::app_icon());
+            if let Some(size) = self.window_size {
+                window_attributes = window_attributes.with_inner_size(size);
+            }
+        }
+        window_attributes
+    }
+
+    /// Opens another window sharing the GPU device with the existing ones and loads `path` into it
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_window(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        path: Option<&str>,
+    ) -> Result<(), ViewerError> {
+        let window = Arc::new(event_loop.create_window(self.window_attributes())?);
+        let gpu = match &self.gpu {
+            Some(gpu) => gpu,
+            // If we are not on web we can use pollster to await the adapter and device
+            None => self
+                .gpu
+                .insert(pollster::block_on(Gpu::new(self.power_preference))?),
+        };
+        let mut state = State::new(window, gpu, &self.config)?;
+        state.renderer.retention = self.retention;
+        state.cache = self.cache.clone();
+        if self.compute.is_some() {
+            state.renderer.set_compute_device(self.compute.clone());
+        }
+        if self.color_space != color_space::OutputColorSpace::Srgb {
+            state.renderer.set_color_space(self.color_space.clone());
+        }
+        if let Some(path) = path {
+            state.load_file(path);
+        }
+        self.insert_state(state);
+        Ok(())
+    }
+
+    /// Loads the newest file of the watched directory into the active window once it is complete
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_watch(&mut self) {
+        let Some(watch) = &mut self.watch else {
+            return;
+        };
+        let Some(path) = watch.poll(std::time::Instant::now()) else {
+            return;
+        };
+        let archive = watch.archive().map(std::path::Path::to_owned);
+        if let Some(app_state) = self.active_state() {
+            app_state.load_file(&path.to_string_lossy());
+            if let Some(archive) = archive {
+                app_state.after_jobs(move |state| state.archive_screenshot(&archive));
+            }
+        }
+    }
+
+    /// Feeds the due events of the input replay to the active window
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_replay(&mut self, event_loop: &ActiveEventLoop, now: web_time::Instant) {
+        let (Some(replay), Some(id)) = (&mut self.replay, self.active_window) else {
+            return;
+        };
+        let events = replay.poll(now);
+        let finished = replay.is_finished();
+        for event in events {
+            match event {
+                // Windows can only ask for a size, the resize follows as a window event
+                InputEvent::Resized(size) => {
+                    if let Some(app_state) = self.states.get(&id) {
+                        let _ = app_state.window.request_inner_size(size);
+                    }
+                }
+                event => self.handle_input(event_loop, id, event),
+            }
+        }
+        if finished {
+            log::info!("Input replay finished");
+            self.replay = None;
+        }
+    }
+
+    /// Mouse and keyboard input of a window, from the window or replayed from a recording
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn handle_input(&mut self, event_loop: &ActiveEventLoop, id: WindowId, input: InputEvent) {
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut open_window = None;
+
+        if let Some(app_state) = self.states.get_mut(&id) {
+            match input {
+                InputEvent::Resized(size) => {
+                    app_state.resize(size);
+                }
+                InputEvent::CursorLeft => {
+                    app_state.cursor_pixel.set(None);
+                    app_state.get_window().request_redraw();
+                }
+                InputEvent::CursorMoved(position) => {
+                    // Coalesced moves are applied before the next frame is rendered
+                    if let Some(position) = app_state.mouse.queue_move(position) {
+                        app_state.move_cursor(position);
+                    }
+                    app_state.get_window().request_redraw();
+                }
+                InputEvent::MouseInput { button, state } => {
+                    app_state.apply_pending_move();
+                    app_state.mouse.register_button_event(button, state);
+                    if app_state.mouse.is_left_button_pressed() {
+                        app_state.stop_animations();
+                    }
+                    if button == winit::event::MouseButton::Left && app_state.mouse.is_click() {
+                        if app_state.measure.kind().is_some() {
+                            app_state.add_measure_point();
+                        } else {
+                            app_state.select_at_mouse();
+                        }
+                    }
+                    if app_state.mouse.is_left_button_pressed() {
+                        match app_state
+                            .mouse
+                            .get_device_coordinates(app_state.window.inner_size())
+                        {
+                            Ok(pos) => {
+                                if app_state.keyboard.is_control_pressed() {
+                                    app_state.renderer.projection.start_move(pos);
+                                } else {
+                                    app_state
+                                        .renderer
+                                        .transformation
+                                        .start_move(Vec3::from((pos, 1.0)))
+                                };
+                            }
+                            Err(e) => error!("Failed to calculate pointer position: {}", e),
+                        }
+                    }
+                }
+                // Peel the surface in front of the camera with Ctrl + scroll, 2 % per line
+                InputEvent::MouseWheel(delta) if app_state.keyboard.is_control_pressed() => {
+                    let lines = Mouse::scroll_lines(delta, &app_state.ui_scale);
+                    let near_clip = app_state.renderer.projection.get_near_clip();
+                    app_state.set_near_clip(near_clip + 0.02 * lines);
+                }
+                InputEvent::MouseWheel(delta) => {
+                    app_state.stop_animations();
+                    app_state
+                        .mouse
+                        .register_scroll_event(delta, &app_state.ui_scale);
+                    app_state
+                        .renderer
+                        .projection
+                        .zoom(app_state.mouse.get_zoom());
+                    app_state.publish_camera();
+                    app_state.get_window().request_redraw();
+                }
+                InputEvent::Key { key, state, repeat } => {
+                    app_state.keyboard.register_event(&key, state);
+                    let key = app_state.keyboard.bindings.resolve(key);
+                    // Accept the selection with Ctrl+'Enter' and reject it with
+                    // Ctrl+'Backspace' for the audit trail, Ctrl+Shift+'Enter' exports the
+                    // audit trail
+                    match app_state.keyboard.audit_key(&key, state, repeat) {
+                        Some(AuditKey::Dispose(disposition)) => {
+                            app_state.dispose_selection(disposition)
+                        }
+                        Some(AuditKey::Export) => app_state.export_audit_trail(false, None),
+                        None => (),
+                    }
+                    // Toggle fullscreen with 'F11' key
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::F11)
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        let fullscreen = app_state.window.fullscreen().is_none();
+                        app_state.set_fullscreen(fullscreen);
+                    }
+                    // Cycle the debug visualizations with 'F3' key
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::F3)
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        let debug_view = app_state.renderer.debug_view.next();
+                        app_state.renderer.set_debug_view(debug_view);
+                        app_state.get_window().request_redraw();
+                    }
+                    // Cancel all background jobs with 'Escape' key
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::Escape)
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        app_state.cancel_jobs(None);
+                    }
+                    // Export the view as figure with legend and caption with 'F12' key
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::F12)
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        app_state.export_figure(None);
+                    }
+                    // Peel the surface in front of the camera deeper with 'Page Down' key and
+                    // back with 'Page Up' key, in 5 % steps
+                    if let winit::keyboard::Key::Named(
+                        named @ (winit::keyboard::NamedKey::PageUp
+                        | winit::keyboard::NamedKey::PageDown),
+                    ) = key
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        let step = if named == winit::keyboard::NamedKey::PageDown {
+                            0.05
+                        } else {
+                            -0.05
+                        };
+                        let near_clip = app_state.renderer.projection.get_near_clip();
+                        app_state.set_near_clip(near_clip + step);
+                    }
+                    // End the threshold preview with 'Backspace' key
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::Backspace)
+                        && state == winit::event::ElementState::Pressed
+                        && !app_state.keyboard.is_control_pressed()
+                    {
+                        app_state.set_threshold(None);
+                    }
+                    // Delete the selected overlay or marker with 'Delete' key
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::Delete)
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        app_state.delete_selection();
+                    }
+                    // Review the next overlay or marker with 'Tab' key, the previous one with
+                    // Shift+Tab
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::Tab)
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        let forward = !app_state.keyboard.is_shift_pressed();
+                        app_state.step_review(forward);
+                    }
+                    // Show the properties of the selection with 'Enter' key
+                    if key == winit::keyboard::Key::Named(winit::keyboard::NamedKey::Enter)
+                        && state == winit::event::ElementState::Pressed
+                        && !app_state.keyboard.is_control_pressed()
+                    {
+                        app_state.open_selection_properties();
+                    }
+                    // Nudge the selection by a pixel with the arrow keys, by 10 with 'Shift'
+                    if let winit::keyboard::Key::Named(named) = key
+                        && state == winit::event::ElementState::Pressed
+                    {
+                        let step = if app_state.keyboard.is_shift_pressed() {
+                            10
+                        } else {
+                            1
+                        };
+                        let offset = match named {
+                            winit::keyboard::NamedKey::ArrowLeft => Some([-step, 0]),
+                            winit::keyboard::NamedKey::ArrowRight => Some([step, 0]),
+                            winit::keyboard::NamedKey::ArrowUp => Some([0, -step]),
+                            winit::keyboard::NamedKey::ArrowDown => Some([0, step]),
+                            _ => None,
+                        };
+                        if let Some(offset) = offset {
+                            app_state.nudge_selection(offset);
+                        }
+                    }
+                    if let winit::keyboard::Key::Character(ref c) = key {
+                        // Toggle shader with 'S' key
+                        if c.as_str() == "s" && state == winit::event::ElementState::Pressed {
+                            app_state.renderer.use_height_shader =
+                                !app_state.renderer.use_height_shader;
+                            app_state.get_window().request_redraw();
+                        }
+                        // Toggle overlay with 'T' key,
+                        // Shift+'T' toggles dimming the surface outside the selected overlay
+                        if c.eq_ignore_ascii_case("t")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                let dim =
+                                    app_state.renderer.render_settings.dim_outside_selection == 0;
+                                app_state.renderer.set_dim_outside_selection(dim);
+                            } else if let Some(texture) = &mut app_state.renderer.texture {
+                                if texture.overlay.overlays.is_empty() {
+                                    app_state
+                                        .renderer
+                                        .set_overlays(Arc::new(texture::example_overlays()));
+                                } else {
+                                    app_state.renderer.clear_overlays();
+                                }
+                            }
+                            app_state.get_window().request_redraw();
+                        }
+                        // Export the annotations of the shown groups with Shift+G
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if c.eq_ignore_ascii_case("g")
+                            && state == winit::event::ElementState::Pressed
+                            && app_state.keyboard.is_shift_pressed()
+                        {
+                            app_state.export_annotations(None, None);
+                        }
+                        // Track the selected overlay through new surfaces with Shift+'K', again
+                        // stops and exports the track with its plot
+                        if c.as_str() == "K" && state == winit::event::ElementState::Pressed {
+                            if app_state.tracker.is_some() {
+                                #[cfg(not(target_arch = "wasm32"))]
+                                for format in [TrackFormat::Csv, TrackFormat::Svg] {
+                                    app_state.export_region_track(format, None);
+                                }
+                                app_state.track_region(None);
+                            } else {
+                                app_state.track_selection();
+                            }
+                        }
+                        // Toggle example markers with 'K' key
+                        if c.as_str() == "k" && state == winit::event::ElementState::Pressed {
+                            if let Some(texture) = &app_state.renderer.texture {
+                                if app_state.renderer.markers.is_empty() {
+                                    let markers =
+                                        markers::example_markers(texture.surface.image_size());
+                                    app_state.renderer.set_markers(Arc::new(markers));
+                                } else {
+                                    app_state.renderer.clear_markers();
+                                }
+                            }
+                            app_state.get_window().request_redraw();
+                        }
+                        // Toggle order-independent overlay blending with 'B' key,
+                        // Shift+'B' cycles how the other layer is blended into the shown one
+                        if c.eq_ignore_ascii_case("b")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                let compositing = app_state.renderer.compositing.next();
+                                app_state.renderer.set_compositing(compositing);
+                            } else {
+                                let mode = app_state.renderer.overlay_blend_mode.toggled();
+                                app_state.renderer.set_overlay_blend_mode(mode);
+                            }
+                            app_state.get_window().request_redraw();
+                        }
+                        // Toggle holes at invalid pixels with 'H' key
+                        if c.as_str() == "h" && state == winit::event::ElementState::Pressed {
+                            let cut_holes = app_state.renderer.render_settings.cut_holes == 0;
+                            app_state.renderer.set_cut_holes(cut_holes);
+                            app_state.get_window().request_redraw();
+                        }
+                        // Start collecting surfaces for temporal statistics with Shift+'M', again
+                        // stops and shows their mean, Shift+'H' shows their standard deviation
+                        if c.as_str() == "M" && state == winit::event::ElementState::Pressed {
+                            let collecting = app_state
+                                .renderer
+                                .temporal
+                                .as_ref()
+                                .is_some_and(|temporal| temporal.collecting);
+                            app_state.set_temporal_collecting(!collecting);
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if collecting {
+                                app_state.show_temporal_map(TemporalMap::Mean);
+                            }
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if c.as_str() == "H" && state == winit::event::ElementState::Pressed {
+                            app_state.show_temporal_map(TemporalMap::StdDev);
+                        }
+                        // Toggle adaptive tessellation with 'M' key
+                        if c.as_str() == "m" && state == winit::event::ElementState::Pressed {
+                            let mode = app_state.renderer.mesh_mode.toggled();
+                            app_state.renderer.set_mesh_mode(mode);
+                            app_state.get_window().request_redraw();
+                        }
+                        // Cycle through languages with 'L' key,
+                        // Shift+'L' turns the lay of the surface horizontal
+                        if c.eq_ignore_ascii_case("l")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                app_state.align_lay(None);
+                            } else {
+                                let language = app_state.localizer.language().next();
+                                app_state.set_language(language);
+                            }
+                        }
+                        // Adjust UI scale with Ctrl + '+'/'-', reset to auto-detection with Ctrl + '0'
+                        // Level the rows by their median with Ctrl + 'Y', Ctrl + Shift + 'Y'
+                        // subtracts a line from every row, Ctrl + 'X' replaces spikes by the
+                        // median of their neighbors and Ctrl + 'Z' undoes these steps
+                        // Open the dataset in another window with Ctrl + 'N' and another
+                        // dataset in this one with Ctrl + 'O'
+                        if app_state.keyboard.is_control_pressed()
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            match c.as_str() {
+                                "+" | "=" => {
+                                    app_state.ui_scale.increase();
+                                    app_state.ui_scale_changed();
+                                }
+                                "-" => {
+                                    app_state.ui_scale.decrease();
+                                    app_state.ui_scale_changed();
+                                }
+                                "0" => app_state.set_ui_scale(None),
+                                "y" | "Y" => {
+                                    let leveling = if app_state.keyboard.is_shift_pressed() {
+                                        RowLeveling::Linear
+                                    } else {
+                                        RowLeveling::Offset
+                                    };
+                                    app_state.level_rows(leveling);
+                                }
+                                "x" => app_state.despike(despike::DEFAULT_THRESHOLD),
+                                "z" => app_state.undo_preprocessing(),
+                                #[cfg(not(target_arch = "wasm32"))]
+                                "n" => open_window = Some(app_state.dataset_path.clone()),
+                                #[cfg(not(target_arch = "wasm32"))]
+                                "o" => app_state.open_file_dialog(),
+                                _ => (),
+                            }
+                        }
+                        // Cycle through colormaps with 'C' key,
+                        // Shift+'C' fits the height colormap to the heights in view
+                        if c.eq_ignore_ascii_case("c")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                app_state.auto_contrast_visible(None);
+                            } else {
+                                let colormap = app_state.renderer.colormap.next();
+                                app_state.renderer.set_colormap(colormap);
+                                app_state.get_window().request_redraw();
+                            }
+                        }
+                        // Cycle through amplitude colormaps with 'A' key,
+                        // Shift+'A' toggles clipping the amplitudes at percentiles
+                        if c.eq_ignore_ascii_case("a")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                let range = match app_state.renderer.amplitude_range {
+                                    AmplitudeRange::Fixed { .. } => AmplitudeRange::PERCENTILES,
+                                    AmplitudeRange::Percentile { .. } => AmplitudeRange::default(),
+                                };
+                                app_state.renderer.set_amplitude_range(range);
+                            } else {
+                                let colormap = app_state.renderer.amplitude_colormap.next();
+                                app_state.renderer.set_amplitude_colormap(colormap);
+                            }
+                            app_state.get_window().request_redraw();
+                        }
+                        // Cycle through color vision deficiency simulations with 'D' key
+                        if c.as_str() == "d" && state == winit::event::ElementState::Pressed {
+                            let simulation = app_state.renderer.cvd_simulation.next();
+                            app_state.renderer.set_cvd_simulation(simulation);
+                            app_state.get_window().request_redraw();
+                        }
+                        // Cycle through display units with 'I' key
+                        if c.as_str() == "i" && state == winit::event::ElementState::Pressed {
+                            let units = Units {
+                                display: app_state.units.display.next(),
+                                ..app_state.units
+                            };
+                            app_state.set_units(units);
+                        }
+                        // Store the display settings to compare with using 'P' key
+                        if c.as_str() == "p" && state == winit::event::ElementState::Pressed {
+                            app_state.store_blink_state();
+                        }
+                        // Swap with the stored display settings with 'W' key,
+                        // Shift+'W' toggles automatic blinking
+                        if c.eq_ignore_ascii_case("w")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                let interval = (!app_state.blink.is_running())
+                                    .then_some(Blink::DEFAULT_INTERVAL);
+                                app_state.set_blink_interval(interval);
+                            } else {
+                                app_state.swap_blink_state();
+                            }
+                        }
+                        // Toggle the deviation from the reference surface with 'E' key,
+                        // Ctrl+'E' writes a tolerance report, Shift+'E' toggles the
+                        // checkerboard of the surface and the reference
+                        if c.eq_ignore_ascii_case("e")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_control_pressed() {
+                                #[cfg(not(target_arch = "wasm32"))]
+                                app_state.write_tolerance_report(None);
+                            } else if app_state.keyboard.is_shift_pressed() {
+                                let enabled = app_state.renderer.checkerboard.is_none();
+                                app_state.set_checkerboard(enabled, None);
+                            } else {
+                                let show = !app_state.renderer.show_residual;
+                                app_state.set_residual_mode(show);
+                            }
+                        }
+                        // Restart the live baseline from the current surface with Shift+'R',
+                        // a rolling average if there was none
+                        if c.as_str() == "R" && state == winit::event::ElementState::Pressed {
+                            let mode = app_state.renderer.baseline.as_ref().map_or(
+                                BaselineMode::RollingAverage {
+                                    weight: BaselineMode::DEFAULT_WEIGHT,
+                                },
+                                |baseline| baseline.mode,
+                            );
+                            app_state.set_baseline(Some(mode));
+                        }
+                        // Toggle the angle measurement with 'G' key
+                        if c.as_str() == "g" && state == winit::event::ElementState::Pressed {
+                            let kind = match app_state.measure.kind() {
+                                Some(MeasureKind::Angle) => None,
+                                _ => Some(MeasureKind::Angle),
+                            };
+                            app_state.set_measure_tool(kind);
+                        }
+                        // Toggle fitting a circle to a profile with 'F' key,
+                        // Shift+'F' fits a sphere to a patch
+                        if c.eq_ignore_ascii_case("f")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            let fit = if app_state.keyboard.is_shift_pressed() {
+                                MeasureKind::Sphere
+                            } else {
+                                MeasureKind::Circle
+                            };
+                            let kind = (app_state.measure.kind() != Some(fit)).then_some(fit);
+                            app_state.set_measure_tool(kind);
+                        }
+                        // Mark the highest peaks and deepest valleys with 'Q' key
+                        if c.as_str() == "q" && state == winit::event::ElementState::Pressed {
+                            app_state.find_extrema(
+                                Extremum::DEFAULT_COUNT,
+                                Extremum::DEFAULT_SEPARATION,
+                                None,
+                            );
+                        }
+                        // Preview a height threshold with '[' and ']', which lower and raise it
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if state == winit::event::ElementState::Pressed {
+                            match c.as_str() {
+                                "[" => app_state.step_threshold(-1.0),
+                                "]" => app_state.step_threshold(1.0),
+                                _ => (),
+                            }
+                        }
+                        // Toggle HUD elements like markers with 'U' key
+                        if c.as_str() == "u" && state == winit::event::ElementState::Pressed {
+                            let visible = !app_state.renderer.hud_visible;
+                            app_state.renderer.set_hud_visible(visible);
+                            app_state.get_window().request_redraw();
+                        }
+                        // Toggle level horizon (turntable) rotation with 'R' key
+                        if c.as_str() == "r" && state == winit::event::ElementState::Pressed {
+                            let mode = app_state.renderer.transformation.get_mode().toggled();
+                            app_state.set_rotation_mode(mode);
+                        }
+                        // Add the view as camera keyframe with 'J' key, Shift+'J' clears them
+                        if c.eq_ignore_ascii_case("j")
+                            && state == winit::event::ElementState::Pressed
+                        {
+                            if app_state.keyboard.is_shift_pressed() {
+                                app_state.clear_keyframes();
+                            } else {
+                                app_state.add_keyframe(None);
+                            }
+                        }
+                        // Play or stop the camera path with 'N' key,
+                        // Ctrl+Shift+'N' exports its frames
+                        if c.as_str() == "n"
+                            && state == winit::event::ElementState::Pressed
+                            && !app_state.keyboard.is_control_pressed()
+                        {
+                            let play = app_state.path_playback.is_none();
+                            app_state.play_camera_path(play);
+                        }
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if c.as_str() == "N"
+                            && state == winit::event::ElementState::Pressed
+                            && app_state.keyboard.is_control_pressed()
+                        {
+                            app_state
+                                .export_camera_path(None, camera_path::FrameExport::DEFAULT_FPS);
+                        }
+                        // Log the current view angles with 'V' key
+                        if c.as_str() == "v" && state == winit::event::ElementState::Pressed {
+                            app_state.log_view();
+                        }
+                        // Move object to origin with 'O' key
+                        if c.as_str() == "o"
+                            && state == winit::event::ElementState::Pressed
+                            && !app_state.keyboard.is_control_pressed()
+                        {
+                            app_state.back_to_origin();
+                            app_state.get_window().request_redraw();
+                        }
                     }
-                };
+                }
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = open_window
+            && let Err(e) = self.open_window(event_loop, path.as_deref())
+        {
+            log::error!("Failed to open window: {}", e);
+        }
+    }
+}
+
+impl ApplicationHandler<ViewerCommand> for ImageViewer3D {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn new_events(&mut self, event_loop: &ActiveEventLoop, _cause: winit::event::StartCause) {
+        let now = web_time::Instant::now();
+        if self.next_watch_poll.is_some_and(|at| at <= now) {
+            self.poll_watch();
+            self.next_watch_poll = Some(now + watch::FolderWatch::POLL_INTERVAL);
+        }
+        self.poll_replay(event_loop, now);
+    }
+
+    /// Collects finished jobs and sleeps until the next capped animation frame, directory scan
+    /// or job poll is due
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let now = web_time::Instant::now();
+        let mut wake_up: Option<web_time::Instant> = None;
+        for state in self.states.values_mut() {
+            state.poll_jobs();
+            if state.jobs.is_busy() {
+                let at = now + Jobs::<State>::POLL_INTERVAL;
+                wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+            }
+            if state.gamepads.is_enabled() {
+                let at = now
+                    + if state.poll_gamepads(now) {
+                        Gamepads::ACTIVE_POLL_INTERVAL
+                    } else {
+                        Gamepads::IDLE_POLL_INTERVAL
+                    };
+                wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+            }
+            if let Some(at) = state.restore_quality(now) {
+                wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+            }
+            #[cfg(target_arch = "wasm32")]
+            if let Some(at) = state.update_url_hash(now) {
+                wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+            }
+            if let Some(at) = state.redraw_at {
+                if at <= now {
+                    state.redraw_at = None;
+                    state.window.request_redraw();
+                } else {
+                    wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+                }
+            }
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(at) = self.next_watch_poll {
+            wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(at) = self.replay.as_ref().and_then(input::InputReplay::next_at) {
+            wake_up = Some(wake_up.map_or(at, |wake_up| wake_up.min(at)));
+        }
+        event_loop.set_control_flow(match wake_up {
+            Some(at) => winit::event_loop::ControlFlow::WaitUntil(at),
+            None => winit::event_loop::ControlFlow::Wait,
+        });
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.states.is_empty() {
+            return;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = self.open_window(event_loop, None) {
+            log::error!("Failed to initialize viewer: {}", e);
+            event_loop.exit();
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let window = match event_loop.create_window(self.window_attributes()) {
+                Ok(window) => Arc::new(window),
+                Err(e) => {
+                    log::error!("{}", ViewerError::from(e));
+                    event_loop.exit();
+                    return;
+                }
+            };
+            // Run the future asynchronously and use the
+            // proxy to send the results to the event loop
+            if let Some(proxy) = self.proxy.take() {
+                let power_preference = self.power_preference;
+                let config = self.config.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let state = match Gpu::new(power_preference).await {
+                        Ok(gpu) => State::new(window, &gpu, &config),
+                        Err(e) => Err(e),
+                    };
+                    match state {
+                        Ok(state) => {
+                            if proxy
+                                .send_event(ViewerCommand::SetState(Box::new(state)))
+                                .is_err()
+                            {
+                                log::error!("Event loop closed before the viewer was initialized");
+                            }
+                        }
+                        Err(e) => log::error!("Failed to initialize viewer: {}", e),
+                    }
+                });
+            }
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        if event == WindowEvent::CloseRequested {
+            self.states.remove(&id);
+            if self.states.is_empty() {
+                println!("The close button was pressed; stopping");
+                event_loop.exit();
+            } else if self.active_window == Some(id) {
+                self.active_window = self.states.keys().next().copied();
+            }
+            return;
+        }
+        if let Some(input) = InputEvent::from_window_event(&event) {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(recorder) = &mut self.recorder {
+                recorder.record(&input);
+            }
+            self.handle_input(event_loop, id, input);
+            return;
+        }
+
+        if let Some(app_state) = self.states.get_mut(&id) {
+            match event {
+                WindowEvent::Focused(true) => {
+                    self.active_window = Some(id);
+                }
+                // Show a file dropped onto the window in place of its surface
+                #[cfg(not(target_arch = "wasm32"))]
+                WindowEvent::DroppedFile(path) => {
+                    self.active_window = Some(id);
+                    app_state.load_file(&path.to_string_lossy());
+                }
+                WindowEvent::RedrawRequested => {
+                    app_state.render();
+                }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    app_state.ui_scale.set_window_scale_factor(scale_factor);
+                    app_state.ui_scale_changed();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    #[allow(unused_mut)]
+    #[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, mut event: ViewerCommand) {
+        crash::record_command(event.name());
+        match event {
+            ViewerCommand::GetPixel(sender) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.get_pixel_value(sender);
+                }
+            }
+            ViewerCommand::GetViewTransform(sender) => {
+                let view_transform = self.active_state().and_then(|state| state.view_transform());
+                if sender.send(view_transform).is_err() {
+                    log::error!("Failed to return view transform");
+                }
+            }
+            ViewerCommand::SetDataUnit(unit) => {
+                if let Some(app_state) = self.active_state() {
+                    let units = Units {
+                        data: unit,
+                        ..app_state.units
+                    };
+                    app_state.set_units(units);
+                }
+            }
+            ViewerCommand::SetDisplayUnit(unit) => {
+                if let Some(app_state) = self.active_state() {
+                    let units = Units {
+                        display: unit,
+                        ..app_state.units
+                    };
+                    app_state.set_units(units);
+                }
+            }
+            ViewerCommand::ProbeNeighborhood {
+                pixel,
+                size,
+                sender,
+            } => {
+                let stats = self.active_state().and_then(|state| {
+                    let image = state.renderer.surface_image()?;
+                    NeighborhoodStats::sample(&image, pixel, size)
+                        .map(|stats| stats.to_display(state.units))
+                });
+                if sender.send(stats).is_err() {
+                    log::error!("Failed to return neighborhood statistics");
+                }
+            }
+            ViewerCommand::FindExtrema {
+                count,
+                min_separation,
+                sender,
+            } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.find_extrema(count, min_separation, sender);
+                }
+            }
+            ViewerCommand::LevelRows(leveling) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.level_rows(leveling);
+                }
+            }
+            ViewerCommand::Despike(threshold) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.despike(threshold);
+                }
+            }
+            ViewerCommand::SetRowLeveling(leveling) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.row_leveling = leveling;
+                }
+            }
+            ViewerCommand::SetThreshold(threshold) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_threshold(threshold);
+                }
+            }
+            ViewerCommand::GetThresholdAreas(sender) => {
+                let areas = self
+                    .active_state()
+                    .and_then(|app_state| app_state.threshold_areas());
+                if sender.send(areas).is_err() {
+                    log::error!("Failed to return threshold areas");
+                }
+            }
+            ViewerCommand::AutoContrastVisible(sender) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.auto_contrast_visible(sender);
+                }
+            }
+            ViewerCommand::AlignLay(sender) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.align_lay(sender);
+                }
+            }
+            ViewerCommand::SetGroupVisible { group, visible } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_group_visible(&group, visible);
+                }
+            }
+            ViewerCommand::SetGroupColor { group, color } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_group_color(&group, color);
+                }
+            }
+            ViewerCommand::GetAnnotationGroups(sender) => {
+                let groups = self
+                    .active_state()
+                    .map(|app_state| app_state.renderer.annotation_group_names())
+                    .unwrap_or_default();
+                if sender.send(groups).is_err() {
+                    log::error!("Failed to return annotation groups");
+                }
+            }
+            ViewerCommand::ExportAnnotations { groups, sender } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.export_annotations(groups, sender);
+                }
+            }
+            ViewerCommand::SetOverlayColorRamp(ramp) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_overlay_color_ramp(ramp);
+                }
+            }
+            ViewerCommand::SetOverlayFilter(filter) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_overlay_filter(filter);
+                }
+            }
+            ViewerCommand::StepReview(forward) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.step_review(forward);
+                }
+            }
+            ViewerCommand::SetReviewOrder(order) => {
+                if let Some(app_state) = self.active_state() {
+                    log::info!("Reviewing annotations by {}", order);
+                    app_state.review_order = order;
+                }
+            }
+            ViewerCommand::SetDisposition(disposition) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.dispose_selection(disposition);
+                }
+            }
+            ViewerCommand::ExportAuditTrail { json, sender } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.export_audit_trail(json, sender);
+                }
+            }
+            ViewerCommand::SetAuditKey(key) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.audit_key = key;
+                }
+            }
+            ViewerCommand::UndoPreprocessing => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.undo_preprocessing();
+                }
+            }
+            ViewerCommand::GetUnits(sender) => {
+                let units = self
+                    .active_state()
+                    .map(|state| state.units)
+                    .unwrap_or_default();
+                if sender.send(units).is_err() {
+                    log::error!("Failed to return units");
+                }
+            }
+            ViewerCommand::SetAmplitudeShader => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_amplitude_shader();
+                }
+            }
+            ViewerCommand::SetHeightShader => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_height_shader();
+                }
+            }
+            ViewerCommand::SetOverlays(overlays) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_overlays(overlays.clone());
+                    app_state.set_selection(None);
+                    app_state.set_modified();
+                }
+            }
+            ViewerCommand::SetOverlayBlendMode(mode) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_overlay_blend_mode(mode);
+                }
+            }
+            ViewerCommand::SetDimOutsideSelection(dim) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_dim_outside_selection(dim);
+                }
+            }
+            ViewerCommand::SetCutHoles(cut_holes) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_cut_holes(cut_holes);
+                }
+            }
+            ViewerCommand::SetDebugView(debug_view) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_debug_view(debug_view);
+                }
+            }
+            ViewerCommand::SetMeshMode(mode) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_mesh_mode(mode);
+                }
+            }
+            ViewerCommand::SetRotationMode(mode) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_rotation_mode(mode);
+                }
+            }
+            ViewerCommand::SetMarkers(markers) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_markers(markers);
+                    app_state.set_selection(None);
+                    app_state.set_modified();
+                }
+            }
+            ViewerCommand::ClearMarkers => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.clear_markers();
+                    app_state.set_selection(None);
+                    app_state.set_modified();
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::LoadFile(path) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.load_file(&path);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::LoadDemo => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.load_demo();
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::OpenWindow(path) => {
+                if let Err(e) = self.open_window(event_loop, path.as_deref()) {
+                    log::error!("Failed to open window: {}", e);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::OpenFileDialog => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.open_file_dialog();
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::SetComputeDevice(compute) => {
+                for state in self.states.values_mut() {
+                    state.renderer.set_compute_device(compute.clone());
+                }
+                self.compute = compute;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::SetColorSpace(color_space) => {
+                for state in self.states.values_mut() {
+                    state.renderer.set_color_space(color_space.clone());
+                }
+                self.color_space = color_space;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::WatchFolder(watch) => {
+                // Polled when the event loop wakes up for it, see `about_to_wait`
+                self.next_watch_poll = watch
+                    .is_some()
+                    .then(|| web_time::Instant::now() + watch::FolderWatch::POLL_INTERVAL);
+                self.watch = watch;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::RecordInput(mut recorder) => {
+                // Mouse positions only make sense with the size of the window
+                if let (Some(recorder), Some(app_state)) = (&mut recorder, self.active_state()) {
+                    recorder.record(&InputEvent::Resized(app_state.window.inner_size()));
+                }
+                log::info!("Recording input: {}", recorder.is_some());
+                self.recorder = recorder;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::ReplayInput(mut replay) => {
+                if let Some(replay) = &mut replay {
+                    log::info!("Replaying input");
+                    replay.start(web_time::Instant::now());
+                }
+                self.replay = replay;
+            }
+            ViewerCommand::SetColormap(colormap) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_colormap(colormap);
+                }
+            }
+            ViewerCommand::SetAmplitudeColormap(colormap) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_amplitude_colormap(colormap);
+                }
+            }
+            ViewerCommand::SetCompositing(compositing) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_compositing(compositing);
+                }
+            }
+            ViewerCommand::SetAmplitudeRange(range) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_amplitude_range(range);
+                }
+            }
+            ViewerCommand::GetAmplitudeLimits(sender) => {
+                let limits = self
+                    .active_state()
+                    .filter(|state| state.renderer.texture.is_some())
+                    .map(|state| state.renderer.amplitude_limits());
+                if sender.send(limits).is_err() {
+                    log::error!("Failed to return amplitude limits");
+                }
+            }
+            ViewerCommand::SetDisplayRange { layer, min, max } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_display_range(layer, min, max);
+                }
+            }
+            ViewerCommand::SetMeasureTool(kind) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_measure_tool(kind);
+                }
+            }
+            ViewerCommand::SetPixelPitch(pitch) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_pixel_pitch(pitch);
+                }
+            }
+            ViewerCommand::SetPixelAspect(aspect) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_pixel_aspect(aspect);
+                }
+            }
+            ViewerCommand::SetPhysicalProportions(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_physical_proportions(enabled);
+                    app_state.window.request_redraw();
+                }
+            }
+            ViewerCommand::GetRadiusFit(sender) => {
+                let fit = self.active_state().and_then(|state| {
+                    let units = state.units;
+                    state.radius_fit().map(|fit| fit.into_display(units))
+                });
+                if sender.send(fit).is_err() {
+                    log::error!("Failed to return radius fit");
+                }
+            }
+            ViewerCommand::GetAngle(sender) => {
+                let angle = self.active_state().and_then(|state| state.measured_angle());
+                if sender.send(angle).is_err() {
+                    log::error!("Failed to return angle");
+                }
+            }
+            ViewerCommand::GetSelection(sender) => {
+                let selection = self.active_state().and_then(|state| state.selection);
+                if sender.send(selection).is_err() {
+                    log::error!("Failed to return selection");
+                }
+            }
+            ViewerCommand::GetJobs(sender) => {
+                let jobs = self
+                    .active_state()
+                    .map(|state| state.jobs.status())
+                    .unwrap_or_default();
+                if sender.send(jobs).is_err() {
+                    log::error!("Failed to return jobs");
+                }
+            }
+            ViewerCommand::SetDatasetInfo(info) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_dataset_info(info);
+                }
+            }
+            ViewerCommand::GetDatasetInfo(sender) => {
+                let entries = self
+                    .active_state()
+                    .map(|state| state.dataset_info.entries())
+                    .unwrap_or_default();
+                if sender.send(entries).is_err() {
+                    log::error!("Failed to return dataset info");
+                }
+            }
+            ViewerCommand::GetProvenance(sender) => {
+                let entries = self
+                    .active_state()
+                    .map(|state| state.provenance.entries().to_vec())
+                    .unwrap_or_default();
+                if sender.send(entries).is_err() {
+                    log::error!("Failed to return provenance");
+                }
+            }
+            ViewerCommand::GetViewState(sender) => {
+                let state = self.active_state().map(|state| state.renderer.view_state());
+                if sender.send(state).is_err() {
+                    log::error!("Failed to return view state");
+                }
+            }
+            ViewerCommand::ApplyViewState(view_state) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.apply_view_state(&view_state);
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::SetDatasetUrls { surface, amplitude } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.dataset_urls = [surface, amplitude];
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::SetUrlHashSync(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    log::info!("Keeping the URL hash up to date: {}", enabled);
+                    app_state.url_hash = enabled.then(deep_link::UrlHash::default);
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::AddSurfaceTile(tile) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.add_surface_tile(tile);
+                }
+            }
+            ViewerCommand::GetNotifications(sender) => {
+                let notifications = self
+                    .active_state()
+                    .map(|state| state.notifications.entries().cloned().collect())
+                    .unwrap_or_default();
+                if sender.send(notifications).is_err() {
+                    log::error!("Failed to return notifications");
+                }
+            }
+            ViewerCommand::ClearNotifications => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.notifications.clear();
+                }
+            }
+            ViewerCommand::AddKeyframe(time) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.add_keyframe(time);
+                }
+            }
+            ViewerCommand::ClearKeyframes => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.clear_keyframes();
+                }
+            }
+            ViewerCommand::PlayCameraPath(play) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.play_camera_path(play);
+                }
             }
-        }
-    }
-
-    fn set_surface(&mut self, data: Image<f32>) {
-        log::info!("Setting new surface image");
-        let outlier_removed_data = data.outlier_removed_data(2.0, 98.0);
-        let z_range = image::value_range(&outlier_removed_data);
-        z_range.write_buffer(&self.queue, &self.z_value_range_buffer);
-
-        data.size.write_buffer(&self.queue, &self.image_dims_buffer);
-
-        self.vertex_buffer = Some(VertexBuffer::new(&data, &self.device));
-
-        self.index_buffer = Some(
-            IndexBufferBuilder::new_triangle_strip(&data.size).create_buffer_init(&self.device),
-        );
-
-        let texture = Texture::new(&self.device, data, &self.texture_bind_group_layout);
-        texture.surface.write_to_queue(&self.queue);
-        self.texture = Some(texture);
-    }
-
-    fn set_amplitude(&mut self, data: Image<u16>) {
-        log::info!("Setting new amplitude image");
-        if let Some(texture) = &mut self.texture {
-            texture.amplitude.set_image(data);
-            texture.amplitude.write_to_queue(&self.queue);
-        }
-    }
-
-    fn get_pixel_value(
-        &mut self,
-        sender: futures::channel::oneshot::Sender<
-            Shared<std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>>>,
-        >,
-    ) {
-        if let Some(texture) = &self.texture {
-            self.pixel_picker.write_to_channel(
-                self.device.clone(),
-                texture.surface.image.clone(),
-                sender,
-            );
-        } else {
-            let future: std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>> =
-                Box::pin(async move {
-                    Err::<(u32, u32, f32), Arc<anyhow::Error>>(Arc::new(anyhow!(
-                        "Surface not initialized"
-                    )))
-                });
-            if let Err(_) = sender.send(future.shared()) {
-                log::error!("Failed to return error message");
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::ExportCameraPath { dir, fps } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.export_camera_path(dir, fps);
+                }
+            }
+            ViewerCommand::CancelJob(id) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.cancel_jobs(id);
+                }
+            }
+            ViewerCommand::DeleteSelection => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.delete_selection();
+                }
+            }
+            ViewerCommand::GetHistogram { layer, sender } => {
+                let histogram = self.active_state().and_then(|state| state.histogram(layer));
+                if sender.send(histogram).is_err() {
+                    log::error!("Failed to return histogram");
+                }
+            }
+            ViewerCommand::SetCvdSimulation(simulation) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_cvd_simulation(simulation);
+                }
+            }
+            ViewerCommand::SetCoalesceMouseMoves(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    log::info!("Coalescing mouse moves: {}", enabled);
+                    app_state.mouse.set_coalesce_moves(enabled);
+                }
+            }
+            ViewerCommand::SetNavigation(settings) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_navigation(settings);
+                }
+            }
+            ViewerCommand::SetTargetFrameTime(target) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_target_frame_time(target);
+                }
+            }
+            ViewerCommand::SetGamepadEnabled(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    log::info!("Gamepad navigation: {}", enabled);
+                    app_state.gamepads.set_enabled(enabled);
+                }
+            }
+            ViewerCommand::SetMaxFps(max_fps) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_max_fps(max_fps);
+                }
+            }
+            ViewerCommand::SetFullscreen(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_fullscreen(enabled);
+                }
+            }
+            ViewerCommand::SetReference(data) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_reference(data);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::LoadReference(path) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.load_reference(&path);
+                }
+            }
+            ViewerCommand::SetCheckerboard { enabled, tile_size } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_checkerboard(enabled, tile_size);
+                }
+            }
+            ViewerCommand::SetResidualMode(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_residual_mode(enabled);
+                }
+            }
+            ViewerCommand::SetBaseline(mode) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_baseline(mode);
+                }
+            }
+            ViewerCommand::TrackRegion(region) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.track_region(region);
+                }
+            }
+            ViewerCommand::TrackSelection => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.track_selection();
+                }
+            }
+            ViewerCommand::ExportRegionTrack { format, sender } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.export_region_track(format, sender);
+                } else if let Some(sender) = sender
+                    && sender.send(None).is_err()
+                {
+                    log::error!("Failed to return region track");
+                }
+            }
+            ViewerCommand::SetRegistration(enabled) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_registration(enabled);
+                }
             }
-        }
-    }
-
-    fn set_amplitude_shader(&mut self) {
-        log::info!("Setting amplitude shader");
-        self.use_height_shader = false;
-    }
-
-    fn set_height_shader(&mut self) {
-        log::info!("Setting height shader");
-        self.use_height_shader = true;
-    }
-
-    fn set_overlays(&mut self, overlays: Arc<Vec<Overlay>>) {
-        log::info!("Setting overlays");
-        if let Some(texture) = &mut self.texture {
-            texture.overlay.set_overlays(overlays);
-            texture.overlay.write_to_queue(&self.queue);
-        }
-    }
-
-    fn clear_overlays(&mut self) {
-        log::info!("Clearing overlays");
-        if let Some(texture) = &mut self.texture {
-            texture.overlay.set_overlays(Arc::new(Vec::new()));
-            texture.overlay.write_to_queue(&self.queue);
-        }
-    }
-
-    fn back_to_origin(&mut self) {
-        self.projection.reset();
-        self.transformation.reset();
-    }
-}
-
-struct ImageViewer3D {
-    #[cfg(target_arch = "wasm32")]
-    proxy: Option<winit::event_loop::EventLoopProxy<ViewerCommand>>,
-    state: Option<State>,
-}
-
-impl ImageViewer3D {
-    pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<ViewerCommand>) -> Self {
-        #[cfg(target_arch = "wasm32")]
-        let proxy = Some(event_loop.create_proxy());
-        Self {
-            state: None,
             #[cfg(target_arch = "wasm32")]
-            proxy,
-        }
-    }
-}
-
-impl ApplicationHandler<ViewerCommand> for ImageViewer3D {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        #[allow(unused_mut)]
-        let mut window_attributes = Window::default_attributes();
-
-        #[cfg(target_arch = "wasm32")]
-        {
-            use wasm_bindgen::JsCast;
-            use winit::platform::web::WindowAttributesExtWebSys;
-
-            const CANVAS_ID: &str = "canvas";
-
-            let window = wgpu::web_sys::window().unwrap_throw();
-            let document = window.document().unwrap_throw();
-            let canvas = document.get_element_by_id(CANVAS_ID).unwrap_throw();
-            let html_canvas_element = canvas.unchecked_into();
-            window_attributes = window_attributes.with_canvas(Some(html_canvas_element));
-        }
-
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            // If we are not on web we can use pollster to
-            // await the
-            self.state = Some(pollster::block_on(State::new(window)));
-        }
-
-        #[cfg(target_arch = "wasm32")]
-        {
-            // Run the future asynchronously and use the
-            // proxy to send the results to the event loop
-            if let Some(proxy) = self.proxy.take() {
-                wasm_bindgen_futures::spawn_local(async move {
-                    assert!(
-                        proxy
-                            .send_event(ViewerCommand::SetState(State::new(window).await))
-                            .is_ok()
-                    )
-                });
+            ViewerCommand::GetFrameShifts(sender) => {
+                let shifts = self
+                    .active_state()
+                    .and_then(|app_state| {
+                        let units = app_state.units;
+                        let shifts = app_state.registration.as_ref()?.shifts().iter();
+                        Some(
+                            shifts
+                                .map(|shift| FrameShift {
+                                    z: units.to_display(shift.z),
+                                    ..*shift
+                                })
+                                .collect(),
+                        )
+                    })
+                    .unwrap_or_default();
+                if sender.send(shifts).is_err() {
+                    log::error!("Failed to return frame shifts");
+                }
             }
-        }
-    }
-
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        if self.state.is_none() {
-            log::warn!("State is None, ignoring event");
-            return;
-        }
-
-        if let Some(app_state) = self.state.as_mut() {
-            match event {
-                WindowEvent::CloseRequested => {
-                    println!("The close button was pressed; stopping");
-                    event_loop.exit();
+            ViewerCommand::SetTemporalCollecting(collecting) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_temporal_collecting(collecting);
                 }
-                WindowEvent::RedrawRequested => {
-                    app_state.render();
+            }
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::GetTemporalMaps(sender) => {
+                let maps = self
+                    .active_state()
+                    .and_then(|app_state| app_state.renderer.temporal_maps());
+                if sender.send(maps).is_err() {
+                    log::error!("Failed to return temporal statistics");
                 }
-                WindowEvent::Resized(size) => {
-                    app_state.resize(size);
-                    app_state.projection.update_aspect_ratio(
-                        app_state.window.inner_size().width as f32
-                            / app_state.window.inner_size().height as f32,
-                    );
+            }
+            #[cfg(target_arch = "wasm32")]
+            ViewerCommand::SetTemporalMap(maps, map) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_temporal_map(*maps, map);
                 }
-                WindowEvent::CursorMoved {
-                    device_id: _,
-                    position,
-                } => {
-                    app_state.mouse.register_move_event(position);
-                    app_state.pixel_picker.update_mouse_position(position);
-                    if app_state.mouse.is_left_button_pressed() {
-                        match app_state
-                            .mouse
-                            .get_device_coordinates(app_state.window.inner_size())
-                        {
-                            Ok(new_position) => {
-                                if app_state.mouse.is_pointer_inside(Vec2::from(new_position)) {
-                                    if app_state.keyboard.is_control_pressed() {
-                                        app_state.projection.change_position(new_position);
-                                    } else {
-                                        app_state
-                                            .transformation
-                                            .rotate(Vec3::from((new_position, 1.0)));
-                                    }
-                                }
-                            }
-                            Err(e) => error!("Failed to calculate pointer position: {}", e),
-                        }
-                    }
-                    app_state.get_window().request_redraw();
+            }
+            ViewerCommand::EvaluateTolerance(sender) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.evaluate_tolerance(sender);
+                } else if sender.send(None).is_err() {
+                    log::error!("Failed to return tolerance evaluation");
                 }
-                WindowEvent::MouseInput {
-                    device_id: _,
-                    state,
-                    button,
-                } => {
-                    app_state.mouse.register_button_event(button, state);
-                    if app_state.mouse.is_left_button_pressed() {
-                        match app_state
-                            .mouse
-                            .get_device_coordinates(app_state.window.inner_size())
-                        {
-                            Ok(pos) => {
-                                if app_state.keyboard.is_control_pressed() {
-                                    app_state.projection.start_move(pos);
-                                } else {
-                                    app_state.transformation.start_move(Vec3::from((pos, 1.0)))
-                                };
-                            }
-                            Err(e) => error!("Failed to calculate pointer position: {}", e),
-                        }
-                    }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::WriteToleranceReport(path) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.after_jobs(move |state| state.write_tolerance_report(path));
                 }
-                WindowEvent::MouseWheel {
-                    device_id: _,
-                    delta,
-                    phase: _,
-                } => {
-                    app_state.mouse.register_scroll_event(delta);
-                    app_state.projection.zoom(app_state.mouse.get_zoom());
-                    app_state.get_window().request_redraw();
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ViewerCommand::ExportFigure(path) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.after_jobs(move |state| state.export_figure(path));
                 }
-                WindowEvent::KeyboardInput {
-                    device_id: _,
-                    event,
-                    is_synthetic: _,
-                } => {
-                    app_state.keyboard.register_event(event.clone());
-                    if let winit::keyboard::Key::Character(ref c) = event.logical_key {
-                        // Toggle shader with 'S' key
-                        if c.as_str() == "s" && event.state == winit::event::ElementState::Pressed {
-                            app_state.use_height_shader = !app_state.use_height_shader;
-                            app_state.get_window().request_redraw();
-                        }
-                        // Toggle overlay with 'T' key
-                        if c.as_str() == "t" && event.state == winit::event::ElementState::Pressed {
-                            if let Some(texture) = &mut app_state.texture {
-                                if texture.overlay.overlays.is_empty() {
-                                    app_state.set_overlays(Arc::new(texture::example_overlays()));
-                                } else {
-                                    app_state.clear_overlays();
-                                }
-                            }
-                            app_state.get_window().request_redraw();
-                        }
-                        // Move object to origin with 'O' key
-                        if c.as_str() == "o" && event.state == winit::event::ElementState::Pressed {
-                            app_state.projection.reset();
-                            app_state.transformation.reset();
-                            app_state.get_window().request_redraw();
-                        }
-                    }
+            }
+            ViewerCommand::SetTolerance { lower, upper } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_tolerance(lower, upper);
                 }
-                _ => (),
             }
-        }
-    }
-
-    #[allow(unused_mut)]
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: ViewerCommand) {
-        match event {
-            ViewerCommand::GetPixel(sender) => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.get_pixel_value(sender);
+            ViewerCommand::StoreBlinkState => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.store_blink_state();
                 }
             }
-            ViewerCommand::SetAmplitudeShader => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.set_amplitude_shader();
+            ViewerCommand::SwapBlinkState => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.swap_blink_state();
                 }
             }
-            ViewerCommand::SetHeightShader => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.set_height_shader();
+            ViewerCommand::SetBlinkInterval(interval) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_blink_interval(interval);
                 }
             }
-            ViewerCommand::SetOverlays(overlays) => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.set_overlays(overlays.clone());
+            ViewerCommand::SetHudVisible(visible) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.set_hud_visible(visible);
+                }
+            }
+            ViewerCommand::SetDatasetName(name) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_dataset_name(name);
+                }
+            }
+            ViewerCommand::SetLoading(progress) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_loading(progress);
+                }
+            }
+            ViewerCommand::SetPickingMethod(method) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_picking_method(method);
+                }
+            }
+            ViewerCommand::SetCameraSync(group) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_camera_sync(group);
                 }
             }
             ViewerCommand::ClearOverlays => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.clear_overlays();
+                if let Some(app_state) = self.active_state() {
+                    app_state.renderer.clear_overlays();
+                    app_state.set_selection(None);
+                    app_state.set_modified();
+                }
+            }
+            ViewerCommand::SetLanguage(language) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_language(language);
+                }
+            }
+            ViewerCommand::SetUiScale(scale) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_ui_scale(scale);
                 }
             }
             ViewerCommand::BackToOrigin => {
-                if let Some(app_state) = self.state.as_mut() {
+                if let Some(app_state) = self.active_state() {
                     app_state.back_to_origin();
                 }
             }
+            ViewerCommand::SetView {
+                azimuth,
+                elevation,
+                zoom,
+            } => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_view(azimuth, elevation, zoom);
+                }
+            }
+            ViewerCommand::SetNearClip(fraction) => {
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_near_clip(fraction);
+                }
+            }
             ViewerCommand::SetSurface(data) => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.set_surface(data);
+                if let Some(app_state) = self.active_state() {
+                    app_state.set_surface(data, None, None);
                 } else {
                     log::warn!("State is None, cannot set surface");
                 }
             }
             ViewerCommand::SetAmplitude(data) => {
-                if let Some(app_state) = self.state.as_mut() {
-                    app_state.set_amplitude(data);
+                if let Some(app_state) = self.active_state() {
+                    if let Err(e) = app_state.renderer.set_amplitude(data) {
+                        log::error!("Failed to set amplitude: {}", e);
+                    }
+                } else {
+                    log::warn!("State is None, cannot set amplitude");
                 }
             }
             ViewerCommand::SetState(mut state) => {
                 #[cfg(target_arch = "wasm32")]
                 {
-                    // Resize first while we still own the event
+                    // Resize first while we still own the event, this also updates the
+                    // projection aspect ratio to match the viewport
                     state.resize(state.window.inner_size());
-                    // Update projection aspect ratio to match viewport
-                    state.projection.update_aspect_ratio(
-                        state.window.inner_size().width as f32
-                            / state.window.inner_size().height as f32,
-                    );
                     // Store window reference for JavaScript to request redraws
                     wasm_commands::set_window(state.window.clone());
                 }
 
                 // Set state BEFORE requesting redraw so the RedrawRequested handler can access it
-                self.state = Some(state);
+                self.insert_state(*state);
 
                 #[cfg(target_arch = "wasm32")]
                 {
                     // Now request redraw - state is already set
-                    if let Some(state) = self.state.as_ref() {
+                    if let Some(state) = self.active_state() {
                         state.window.request_redraw();
                     }
                 }
             }
-            _ => {
-                log::warn!("Unhandled user event");
-            }
         }
-        if let Some(app_state) = self.state.as_mut() {
+        if let Some(app_state) = self.active_state() {
             app_state.get_window().request_redraw();
         }
     }
 }
 
+// Files are opened as TIFF, only web builds for raw images leave the decoder out
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "tiff")))]
+compile_error!("the native viewer needs the `tiff` feature");
+
 #[cfg(not(target_arch = "wasm32"))]
 pub fn run() -> anyhow::Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
         .format_timestamp_secs()
         .init();
+    crash::install();
+
+    if std::env::args().nth(1).as_deref() == Some("batch") {
+        let options = batch::BatchOptions::parse(std::env::args().skip(2))?;
+        return batch::run(&options);
+    }
+    if std::env::args().nth(1).as_deref() == Some("thumbnail") {
+        return thumbnail::run(std::env::args().skip(2));
+    }
+    if std::env::args().nth(1).as_deref() == Some("raw") {
+        return raw_image::run(std::env::args().skip(2));
+    }
+    if std::env::args().nth(1).as_deref() == Some("render") {
+        let options = offscreen::RenderOptions::parse(std::env::args().skip(2))?;
+        return offscreen::run(&options);
+    }
+    if std::env::args().nth(1).as_deref() == Some("--bench") {
+        let options = bench::BenchOptions::parse(std::env::args().skip(2))?;
+        return bench::run(&options);
+    }
 
-    let image = SurfaceAmplitudeImage::from_file("example-img.tiff").unwrap();
+    let options = cli::ViewerOptions::parse(std::env::args().skip(1))?;
+    let config = config::ViewerConfig::load(options.config.as_deref())?;
     let event_loop = EventLoop::with_user_event().build()?;
     let proxy = event_loop.create_proxy();
-    proxy
-        .send_event(ViewerCommand::SetSurface(image.surface))
-        .map_err(|e| anyhow!("Error: {}", e))
-        .unwrap();
+    let mut commands = Vec::new();
+    if !options.coalesce {
+        commands.push(ViewerCommand::SetCoalesceMouseMoves(false));
+    }
+    if options.max_fps.is_some() {
+        commands.push(ViewerCommand::SetMaxFps(options.max_fps));
+    }
+    if let Some(navigation) = options.navigation {
+        commands.push(ViewerCommand::SetNavigation(navigation));
+    }
+    if let Some(target) = options.target_frame_time {
+        commands.push(ViewerCommand::SetTargetFrameTime(target));
+    }
+    if let Some(aspect) = options.pixel_aspect {
+        commands.push(ViewerCommand::SetPixelAspect(aspect));
+    }
+    if let Some(compositing) = options.compositing {
+        commands.push(ViewerCommand::SetCompositing(compositing));
+    }
+    if options.overlay_ramp.is_some() {
+        commands.push(ViewerCommand::SetOverlayColorRamp(options.overlay_ramp));
+    }
+    if options.overlay_filter.is_some() {
+        commands.push(ViewerCommand::SetOverlayFilter(options.overlay_filter));
+    }
+    if options.register {
+        commands.push(ViewerCommand::SetRegistration(true));
+    }
+    if let Some(selector) = &options.compute_adapter {
+        let compute = pollster::block_on(compute::ComputeDevice::open(selector))?;
+        commands.push(ViewerCommand::SetComputeDevice(Some(compute)));
+    }
+    if let Some(color_space) = options.color_space {
+        commands.push(ViewerCommand::SetColorSpace(color_space));
+    }
+    if options.baseline.is_some() {
+        commands.push(ViewerCommand::SetBaseline(options.baseline));
+    }
+    if options.audit_key.is_some() {
+        commands.push(ViewerCommand::SetAuditKey(options.audit_key));
+    }
+    if options.row_leveling.is_some() {
+        commands.push(ViewerCommand::SetRowLeveling(options.row_leveling));
+    }
+    if options.raw_pixels {
+        commands.push(ViewerCommand::SetPhysicalProportions(false));
+    }
+    if options.gamepad {
+        commands.push(ViewerCommand::SetGamepadEnabled(true));
+    }
+    let replay = options
+        .replay
+        .map(|path| input::InputReplay::load(std::path::Path::new(&path)))
+        .transpose()?;
+    let mut paths = options.files.into_iter();
+    // The watched directory provides the surface, the example file or else the demo surface is
+    // only shown without one
+    const EXAMPLE: &str = "example-img.tiff";
+    let first = paths
+        .next()
+        .or_else(|| replay.as_ref().and_then(|replay| replay.dataset.clone()))
+        .or_else(|| {
+            (options.watch.is_none() && std::path::Path::new(EXAMPLE).is_file())
+                .then(|| EXAMPLE.to_owned())
+        });
+    if let Some(path) = options.record {
+        let recorder = input::InputRecorder::create(std::path::Path::new(&path), first.as_deref())
+            .map_err(|e| anyhow!("Failed to create {}: {}", path, e))?;
+        commands.push(ViewerCommand::RecordInput(Some(recorder)));
+    }
+    if replay.is_some() {
+        commands.push(ViewerCommand::ReplayInput(replay));
+    }
+    match (first, options.generator) {
+        (_, Some(generator)) => {
+            let size = image::ImageSize::new(Generator::DEFAULT_SIZE, Generator::DEFAULT_SIZE)?;
+            commands.push(ViewerCommand::SetDatasetName(generator.to_string()));
+            commands.push(ViewerCommand::SetSurface(generator.generate(size)));
+        }
+        (Some(first), None) => commands.push(ViewerCommand::LoadFile(first)),
+        (None, None) if options.watch.is_none() => commands.push(ViewerCommand::LoadDemo),
+        (None, None) => {}
+    }
+    match options.shader {
+        Some(config::Shader::Height) => commands.push(ViewerCommand::SetHeightShader),
+        Some(config::Shader::Amplitude) => commands.push(ViewerCommand::SetAmplitudeShader),
+        None => {}
+    }
+    if let Some(range) = options.amplitude_range {
+        commands.push(ViewerCommand::SetAmplitudeRange(range));
+    }
+    if let Some(reference) = options.reference {
+        commands.push(ViewerCommand::LoadReference(reference));
+        commands.push(ViewerCommand::SetResidualMode(true));
+        commands.extend(
+            options
+                .report
+                .map(|path| ViewerCommand::WriteToleranceReport(Some(path))),
+        );
+    }
+    if options.figure.is_some() {
+        commands.push(ViewerCommand::ExportFigure(options.figure));
+    }
+    if let Some(dir) = options.watch {
+        if let Some(archive) = &options.archive {
+            std::fs::create_dir_all(archive)?;
+        }
+        let watch = watch::FolderWatch::new(dir.into(), options.archive.map(Into::into));
+        commands.push(ViewerCommand::WatchFolder(Some(watch)));
+    }
+    commands.extend(paths.map(|path| ViewerCommand::OpenWindow(Some(path))));
+    for command in commands {
+        proxy
+            .send_event(command)
+            .map_err(|e| anyhow!("Error: {}", e))?;
+    }
 
-    let mut app = ImageViewer3D::new();
+    let cache = options.cache_size.and_then(|max_bytes| {
+        let dir = cache::DatasetCache::default_dir();
+        if dir.is_none() {
+            log::warn!("No cache directory, decoded files are not cached");
+        }
+        dir.map(|dir| cache::DatasetCache::new(dir, max_bytes))
+    });
+    let mut app = ImageViewer3D::new(options.power_preference, options.retention, cache);
+    app.window_size = options.window_size;
+    app.config = config;
     event_loop.run_app(&mut app)?;
 
     Ok(())
@@ -954,6 +6770,7 @@ pub fn run() -> anyhow::Result<()> {
 fn main() {
     #[cfg(not(target_arch = "wasm32"))]
     if let Err(e) = run() {
-        log::error!("Failed to run image viewer: {}", e)
+        log::error!("Failed to run image viewer: {}", e);
+        std::process::exit(1);
     };
 }
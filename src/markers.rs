@@ -0,0 +1,264 @@
+use wgpu::util::DeviceExt;
+
+use crate::{annotation_groups::AnnotationGroups, image::ImageSize, selection::Selection};
+
+/// Screen-aligned symbol drawn at a pixel of the surface
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub pixel: [u32; 2],
+    pub color: [u8; 4],
+    /// Diameter in logical pixels
+    pub size: f32,
+    /// Draw the marker over the surface instead of letting peaks in front of it hide it
+    pub on_top: bool,
+    pub shape: MarkerShape,
+    /// Height to draw the marker at instead of the surface height, e.g. for fitted geometry
+    pub height: Option<f32>,
+    /// Annotation group the marker belongs to, see `AnnotationGroups`
+    pub group: Option<String>,
+}
+
+/// Symbol drawn for a marker, the values are the shape indices used by the marker shader
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MarkerShape {
+    #[default]
+    Dot = 0,
+    /// Used for the 3D cursor
+    Crosshair = 1,
+}
+
+/// Per-instance data as read by the marker vertex shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct MarkerInstance {
+    pixel: [u32; 2],
+    color: [u8; 4],
+    size: f32,
+    shape: u32,
+    /// Object ID written to the picking target, 0 if the marker cannot be selected
+    id: u32,
+    height: f32,
+    /// 1 if `height` is used instead of the surface height
+    has_height: u32,
+}
+
+impl MarkerInstance {
+    fn new(marker: &Marker, id: u32) -> Self {
+        Self {
+            pixel: marker.pixel,
+            color: marker.color,
+            size: marker.size,
+            shape: marker.shape as u32,
+            id,
+            height: marker.height.unwrap_or(0.0),
+            has_height: marker.height.is_some() as u32,
+        }
+    }
+}
+
+#[derive(Default)]
+struct InstanceBuffer {
+    buffer: Option<wgpu::Buffer>,
+    count: u32,
+}
+
+impl InstanceBuffer {
+    fn set_instances(&mut self, device: &wgpu::Device, instances: &[MarkerInstance]) {
+        self.count = instances.len() as u32;
+        self.buffer = if instances.is_empty() {
+            None
+        } else {
+            Some(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("marker_instance_buffer"),
+                    contents: bytemuck::cast_slice(instances),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+            )
+        };
+    }
+
+    fn draw(&self, renderpass: &mut wgpu::RenderPass) {
+        if let Some(buffer) = &self.buffer {
+            renderpass.set_vertex_buffer(0, buffer.slice(..));
+            renderpass.draw(0..MarkerBuffer::VERTICES_PER_MARKER, 0..self.count);
+        }
+    }
+}
+
+/// Per-instance buffers holding all markers so they are drawn with a single instanced draw call
+/// per pass. Depth-tested markers and markers drawn on top of the surface are kept apart because
+/// they need different pipelines.
+#[derive(Default)]
+pub(crate) struct MarkerBuffer {
+    depth_tested: InstanceBuffer,
+    on_top: InstanceBuffer,
+    /// Markers can be selected by clicking on them, not the case for HUD elements like the cursor
+    selectable: bool,
+}
+
+impl MarkerBuffer {
+    /// Number of vertices of the quad drawn per marker
+    const VERTICES_PER_MARKER: u32 = 6;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            selectable: true,
+            ..Self::default()
+        }
+    }
+
+    /// Markers that are ignored when picking objects
+    pub(crate) fn not_selectable() -> Self {
+        Self::default()
+    }
+
+    /// Uploads the markers with the styles of their `groups`, hidden ones are left out while
+    /// the others keep their index as marker ID
+    pub(crate) fn set_markers(
+        &mut self,
+        device: &wgpu::Device,
+        markers: &[Marker],
+        groups: &AnnotationGroups,
+    ) {
+        let (on_top, depth_tested): (Vec<_>, Vec<_>) = markers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, marker)| {
+                let color = groups.display_color(marker.group.as_deref(), marker.color)?;
+                Some((
+                    index,
+                    Marker {
+                        color,
+                        ..marker.clone()
+                    },
+                ))
+            })
+            .partition(|(_, marker)| marker.on_top);
+        let selectable = self.selectable;
+        let instances = |markers: Vec<(usize, Marker)>| -> Vec<MarkerInstance> {
+            markers
+                .into_iter()
+                .map(|(index, marker)| {
+                    let id = if selectable {
+                        Selection::marker(index).to_id()
+                    } else {
+                        0
+                    };
+                    MarkerInstance::new(&marker, id)
+                })
+                .collect()
+        };
+        self.depth_tested
+            .set_instances(device, &instances(depth_tested));
+        self.on_top.set_instances(device, &instances(on_top));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.depth_tested.count == 0 && self.on_top.count == 0
+    }
+
+    /// Draws the markers that can be hidden by the surface
+    pub(crate) fn draw_depth_tested(&self, renderpass: &mut wgpu::RenderPass) {
+        self.depth_tested.draw(renderpass);
+    }
+
+    /// Draws the markers that stay visible regardless of the surface in front of them
+    pub(crate) fn draw_on_top(&self, renderpass: &mut wgpu::RenderPass) {
+        self.on_top.draw(renderpass);
+    }
+
+    pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<MarkerInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Uint32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Unorm8x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 5]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
+        }
+    }
+}
+
+/// Regular grid of markers covering the whole image, used to demo dense detection results.
+/// Every blue marker is drawn on top of the surface.
+pub fn example_markers(size: &ImageSize) -> Vec<Marker> {
+    const SPACING: usize = 16;
+    const COLORS: [[u8; 4]; 3] = [[255, 64, 64, 255], [64, 255, 64, 255], [64, 128, 255, 255]];
+    (0..size.height.get())
+        .step_by(SPACING)
+        .flat_map(|y| (0..size.width.get()).step_by(SPACING).map(move |x| [x, y]))
+        .enumerate()
+        .map(|(i, pixel)| Marker {
+            pixel,
+            color: COLORS[i % COLORS.len()],
+            size: 8.0,
+            on_top: i % COLORS.len() == 2,
+            shape: MarkerShape::Dot,
+            height: None,
+            group: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    #[test]
+    fn marker_layout_matches_vertex_attributes() {
+        assert_eq!(std::mem::size_of::<MarkerInstance>(), 32);
+        let layout = MarkerBuffer::desc();
+        assert_eq!(layout.array_stride, 32);
+        assert_eq!(layout.attributes[2].offset, 12);
+        assert_eq!(layout.attributes[3].offset, 16);
+        assert_eq!(layout.attributes[4].offset, 20);
+        assert_eq!(layout.attributes[6].offset, 28);
+    }
+
+    #[test]
+    fn example_markers_stay_inside_image() {
+        let size = ImageSize {
+            width: NonZeroU32::new(40).unwrap(),
+            height: NonZeroU32::new(20).unwrap(),
+        };
+        let markers = example_markers(&size);
+        assert_eq!(markers.len(), 3 * 2);
+        assert!(markers.iter().all(|m| m.pixel[0] < 40 && m.pixel[1] < 20));
+    }
+}
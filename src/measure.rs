@@ -0,0 +1,537 @@
+use anyhow::anyhow;
+use glam::{Vec2, Vec3};
+use std::str::FromStr;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{image::Image, units::Units};
+
+/// Measurement whose points are picked by clicking on the surface
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasureKind {
+    /// Vertex followed by one point on each of the two lines
+    Angle,
+    /// Circle fitted to the profile between two points, e.g. across a fillet
+    Circle,
+    /// Sphere fitted to the patch between two corners, e.g. on a lens
+    Sphere,
+}
+
+impl MeasureKind {
+    pub const ALL: [MeasureKind; 3] =
+        [MeasureKind::Angle, MeasureKind::Circle, MeasureKind::Sphere];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeasureKind::Angle => "angle",
+            MeasureKind::Circle => "circle",
+            MeasureKind::Sphere => "sphere",
+        }
+    }
+
+    /// Number of points to pick
+    fn points(&self) -> usize {
+        match self {
+            MeasureKind::Angle => 3,
+            MeasureKind::Circle | MeasureKind::Sphere => 2,
+        }
+    }
+}
+
+impl FromStr for MeasureKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MeasureKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported measurement: {}", s))
+    }
+}
+
+/// Collects the points of a measurement while the tool is active
+#[derive(Debug, Default)]
+pub struct MeasureTool {
+    kind: Option<MeasureKind>,
+    points: Vec<[u32; 2]>,
+}
+
+impl MeasureTool {
+    /// Most dots drawn along a guide line, long lines are sampled more sparsely
+    const MAX_LINE_DOTS: u32 = 256;
+
+    pub fn kind(&self) -> Option<MeasureKind> {
+        self.kind
+    }
+
+    /// Switches the tool, `None` leaves measuring. Picked points are discarded.
+    pub fn set_kind(&mut self, kind: Option<MeasureKind>) {
+        self.kind = kind;
+        self.points.clear();
+    }
+
+    pub fn points(&self) -> &[[u32; 2]] {
+        &self.points
+    }
+
+    /// Adds a picked pixel, a complete measurement is started over.
+    /// Returns whether all points of the measurement are picked.
+    pub fn add_point(&mut self, pixel: [u32; 2]) -> bool {
+        let Some(kind) = self.kind else {
+            return false;
+        };
+        if self.points.len() >= kind.points() {
+            self.points.clear();
+        }
+        self.points.push(pixel);
+        self.points.len() == kind.points()
+    }
+
+    /// Pixels to draw dots at so the lines of the measurement follow the surface
+    pub fn guide_pixels(&self) -> Vec<[u32; 2]> {
+        let Some((first, others)) = self.points.split_first() else {
+            return Vec::new();
+        };
+        match (self.kind, others) {
+            (Some(MeasureKind::Angle | MeasureKind::Circle), ends) => ends
+                .iter()
+                .flat_map(|end| line_pixels(*first, *end, Self::MAX_LINE_DOTS))
+                .collect(),
+            (Some(MeasureKind::Sphere), [corner]) => {
+                let corners = [
+                    *first,
+                    [corner[0], first[1]],
+                    *corner,
+                    [first[0], corner[1]],
+                ];
+                (0..4)
+                    .flat_map(|i| {
+                        line_pixels(corners[i], corners[(i + 1) % 4], Self::MAX_LINE_DOTS)
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Circle or sphere fitted to the picked profile or patch, `None` until both points are picked
+    pub(crate) fn radius_fit(&self, image: &Image<f32>, pixel_pitch: f32) -> Option<RadiusFit> {
+        match (self.kind, self.points.as_slice()) {
+            (Some(MeasureKind::Circle), [start, end]) => {
+                RadiusFit::profile(image, *start, *end, pixel_pitch)
+            }
+            (Some(MeasureKind::Sphere), [corner, opposite]) => {
+                RadiusFit::patch(image, *corner, *opposite, pixel_pitch)
+            }
+            _ => None,
+        }
+    }
+
+    /// Angle between the picked lines, `None` until all three points are picked
+    pub(crate) fn angle(&self, image: &Image<f32>, pixel_pitch: f32) -> Option<AngleMeasurement> {
+        match (self.kind, self.points.as_slice()) {
+            (Some(MeasureKind::Angle), [vertex, first, second]) => {
+                AngleMeasurement::new(image, *vertex, [*first, *second], pixel_pitch)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Angle at a vertex between two lines on the surface, e.g. the flanks of a V-groove, in degrees
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AngleMeasurement {
+    /// Angle between the lines seen from above
+    pub in_plane: f32,
+    /// Angle between the lines in space, the heights taken into account
+    pub spatial: f32,
+    /// Inclination of the first line out of the image plane, positive when rising from the vertex
+    pub first_elevation: f32,
+    /// Inclination of the second line out of the image plane
+    pub second_elevation: f32,
+}
+
+impl AngleMeasurement {
+    /// `pixel_pitch` is the lateral size of a pixel in the unit of the heights,
+    /// `None` if a point has no valid height or coincides with the vertex
+    pub(crate) fn new(
+        image: &Image<f32>,
+        vertex: [u32; 2],
+        ends: [[u32; 2]; 2],
+        pixel_pitch: f32,
+    ) -> Option<Self> {
+        let vertex = surface_point(image, vertex, pixel_pitch)?;
+        let first = surface_point(image, ends[0], pixel_pitch)? - vertex;
+        let second = surface_point(image, ends[1], pixel_pitch)? - vertex;
+        let elevation = |line: Vec3| line.z.atan2(line.truncate().length()).to_degrees();
+        Some(Self {
+            in_plane: angle_between(first.truncate(), second.truncate())?,
+            spatial: angle_between_3d(first, second)?,
+            first_elevation: elevation(first),
+            second_elevation: elevation(second),
+        })
+    }
+}
+
+/// Circle fitted to a profile or sphere fitted to a surface patch, lengths in the unit of the
+/// heights and the center position in pixels
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RadiusFit {
+    pub radius: f32,
+    pub center_x: f32,
+    pub center_y: f32,
+    /// Height of the center
+    pub center_z: f32,
+    /// Root mean square distance of the points from the fitted circle or sphere
+    pub rms_residual: f32,
+    pub max_residual: f32,
+    /// Number of valid pixels the fit is based on
+    pub points: u32,
+    /// Pixels and heights to draw the fitted geometry at
+    outline: Vec<([u32; 2], f32)>,
+}
+
+impl RadiusFit {
+    /// Samples per side of the patch drawn for a fitted sphere
+    const SPHERE_OUTLINE_SAMPLES: u32 = 24;
+    /// Dots drawn along a fitted circle
+    const CIRCLE_OUTLINE_SAMPLES: u32 = 96;
+
+    pub(crate) fn outline(&self) -> &[([u32; 2], f32)] {
+        &self.outline
+    }
+
+    /// Converts the lengths and the center height from the data unit to the display unit
+    pub fn into_display(self, units: Units) -> Self {
+        Self {
+            radius: units.to_display(self.radius),
+            center_z: units.to_display(self.center_z),
+            rms_residual: units.to_display(self.rms_residual),
+            max_residual: units.to_display(self.max_residual),
+            ..self
+        }
+    }
+
+    /// Fits a circle in the vertical plane through `start` and `end` to the heights between them
+    fn profile(
+        image: &Image<f32>,
+        start: [u32; 2],
+        end: [u32; 2],
+        pixel_pitch: f32,
+    ) -> Option<Self> {
+        let from = Vec2::new(start[0] as f32, start[1] as f32);
+        let direction = (Vec2::new(end[0] as f32, end[1] as f32) - from).normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+        // Position along the profile and height of every valid pixel on it
+        let samples: Vec<[f64; 2]> = line_pixels(start, end, u32::MAX)
+            .filter_map(|pixel| {
+                let z = image.get_pixel(pixel[0], pixel[1]);
+                let along = (Vec2::new(pixel[0] as f32, pixel[1] as f32) - from).dot(direction);
+                z.is_finite()
+                    .then_some([(along * pixel_pitch) as f64, z as f64])
+            })
+            .collect();
+        let (center, radius) = fit_sphere::<2, 3>(&samples)?;
+        let (rms_residual, max_residual) = residuals(&samples, &center, radius);
+        let center_pixel = from + direction * (center[0] as f32 / pixel_pitch);
+
+        // Draw the arc covered by the samples, measured from their mean direction
+        let angles = |sample: &[f64; 2]| (sample[1] - center[1]).atan2(sample[0] - center[0]);
+        let mean_direction = samples.iter().fold(glam::DVec2::ZERO, |sum, sample| {
+            let angle = angles(sample);
+            sum + glam::DVec2::new(angle.cos(), angle.sin())
+        });
+        let mean_angle = mean_direction.y.atan2(mean_direction.x);
+        let wrap = |angle: f64| {
+            (angle - mean_angle + std::f64::consts::PI).rem_euclid(std::f64::consts::TAU)
+                - std::f64::consts::PI
+        };
+        let (low, high) = samples
+            .iter()
+            .fold((0.0f64, 0.0f64), |(low, high), sample| {
+                let angle = wrap(angles(sample));
+                (low.min(angle), high.max(angle))
+            });
+        let size = &image.size;
+        let outline = (0..=Self::CIRCLE_OUTLINE_SAMPLES)
+            .filter_map(|i| {
+                let angle = mean_angle
+                    + low
+                    + (high - low) * i as f64 / Self::CIRCLE_OUTLINE_SAMPLES as f64;
+                let along = center[0] + radius * angle.cos();
+                let height = center[1] + radius * angle.sin();
+                let pixel = (from + direction * (along as f32 / pixel_pitch)).round();
+                inside(pixel, size).map(|pixel| (pixel, height as f32))
+            })
+            .collect();
+        Some(Self {
+            radius: radius as f32,
+            center_x: center_pixel.x,
+            center_y: center_pixel.y,
+            center_z: center[1] as f32,
+            rms_residual,
+            max_residual,
+            points: samples.len() as u32,
+            outline,
+        })
+    }
+
+    /// Fits a sphere to the heights in the rectangle spanned by two corners
+    fn patch(
+        image: &Image<f32>,
+        corner: [u32; 2],
+        opposite: [u32; 2],
+        pixel_pitch: f32,
+    ) -> Option<Self> {
+        let x_range = corner[0].min(opposite[0])..=corner[0].max(opposite[0]);
+        let y_range = corner[1].min(opposite[1])..=corner[1].max(opposite[1]);
+        let samples: Vec<[f64; 3]> = y_range
+            .clone()
+            .flat_map(|y| x_range.clone().map(move |x| [x, y]))
+            .filter_map(|pixel| surface_point(image, pixel, pixel_pitch))
+            .map(|point| point.as_dvec3().to_array())
+            .collect();
+        let (center, radius) = fit_sphere::<3, 4>(&samples)?;
+        let (rms_residual, max_residual) = residuals(&samples, &center, radius);
+
+        // Draw the half of the sphere the samples lie on
+        let above = samples
+            .iter()
+            .map(|sample| sample[2] - center[2])
+            .sum::<f64>()
+            >= 0.0;
+        let sign = if above { 1.0 } else { -1.0 };
+        let steps = Self::SPHERE_OUTLINE_SAMPLES;
+        let lerp = |range: &std::ops::RangeInclusive<u32>, i: u32| {
+            *range.start() + (range.end() - range.start()) * i / steps
+        };
+        let outline = (0..=steps)
+            .flat_map(|j| (0..=steps).map(move |i| (i, j)))
+            .filter_map(|(i, j)| {
+                let pixel = [lerp(&x_range, i), lerp(&y_range, j)];
+                let dx = pixel[0] as f64 * pixel_pitch as f64 - center[0];
+                let dy = pixel[1] as f64 * pixel_pitch as f64 - center[1];
+                let dz_squared = radius * radius - dx * dx - dy * dy;
+                (dz_squared >= 0.0).then(|| (pixel, (center[2] + sign * dz_squared.sqrt()) as f32))
+            })
+            .collect();
+        Some(Self {
+            radius: radius as f32,
+            center_x: (center[0] / pixel_pitch as f64) as f32,
+            center_y: (center[1] / pixel_pitch as f64) as f32,
+            center_z: center[2] as f32,
+            rms_residual,
+            max_residual,
+            points: samples.len() as u32,
+            outline,
+        })
+    }
+}
+
+/// Algebraic least squares fit of a circle (`D` = 2) or sphere (`D` = 3) with `N` = `D` + 1,
+/// returns center and radius. `None` for fewer than `N` points or if they lie on a line or plane.
+fn fit_sphere<const D: usize, const N: usize>(points: &[[f64; D]]) -> Option<([f64; D], f64)> {
+    if points.len() < N {
+        return None;
+    }
+    // Fit around the centroid to keep the normal equations well conditioned
+    let mut mean = [0.0; D];
+    for point in points {
+        for axis in 0..D {
+            mean[axis] += point[axis] / points.len() as f64;
+        }
+    }
+    // |p|² + a·p + c = 0 for every point p, solved for (a, c) via the normal equations
+    let mut normal = [[0.0; N]; N];
+    let mut rhs = [0.0; N];
+    for point in points {
+        let mut row = [1.0; N];
+        let mut squared = 0.0;
+        for axis in 0..D {
+            row[axis] = point[axis] - mean[axis];
+            squared += row[axis] * row[axis];
+        }
+        for i in 0..N {
+            for j in 0..N {
+                normal[i][j] += row[i] * row[j];
+            }
+            rhs[i] -= row[i] * squared;
+        }
+    }
+    let solution = solve(normal, rhs)?;
+    let mut center = [0.0; D];
+    let mut center_squared = 0.0;
+    for axis in 0..D {
+        let offset = -solution[axis] / 2.0;
+        center[axis] = mean[axis] + offset;
+        center_squared += offset * offset;
+    }
+    let radius_squared = center_squared - solution[D];
+    (radius_squared > 0.0).then(|| (center, radius_squared.sqrt()))
+}
+
+/// Gaussian elimination with partial pivoting, `None` for a singular matrix
+fn solve<const N: usize>(mut matrix: [[f64; N]; N], mut rhs: [f64; N]) -> Option<[f64; N]> {
+    let scale = matrix
+        .iter()
+        .flatten()
+        .fold(0.0f64, |max, value| max.max(value.abs()));
+    for column in 0..N {
+        let pivot = (column..N)
+            .max_by(|&a, &b| matrix[a][column].abs().total_cmp(&matrix[b][column].abs()))?;
+        if matrix[pivot][column].abs() <= scale * 1e-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        for row in column + 1..N {
+            let factor = matrix[row][column] / matrix[column][column];
+            let pivot_row = matrix[column];
+            for (value, pivot) in matrix[row][column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[column];
+        }
+    }
+    let mut solution = [0.0; N];
+    for row in (0..N).rev() {
+        let sum: f64 = (row + 1..N).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+/// Root mean square and largest distance of the points from the circle or sphere
+fn residuals<const D: usize>(points: &[[f64; D]], center: &[f64; D], radius: f64) -> (f32, f32) {
+    let (sum_squared, max) = points.iter().fold((0.0, 0.0f64), |(sum, max), point| {
+        let distance = (0..D)
+            .map(|axis| (point[axis] - center[axis]).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        let residual = (distance - radius).abs();
+        (sum + residual * residual, max.max(residual))
+    });
+    (
+        (sum_squared / points.len() as f64).sqrt() as f32,
+        max as f32,
+    )
+}
+
+fn inside(pixel: Vec2, size: &crate::image::ImageSize) -> Option<[u32; 2]> {
+    (pixel.x >= 0.0
+        && pixel.y >= 0.0
+        && (pixel.x as u32) < size.width.get()
+        && (pixel.y as u32) < size.height.get())
+    .then_some([pixel.x as u32, pixel.y as u32])
+}
+
+/// Position of a pixel on the surface with all coordinates in the unit of the heights
+fn surface_point(image: &Image<f32>, pixel: [u32; 2], pixel_pitch: f32) -> Option<Vec3> {
+    if pixel[0] >= image.size.width.get() || pixel[1] >= image.size.height.get() {
+        return None;
+    }
+    let z = image.get_pixel(pixel[0], pixel[1]);
+    z.is_finite().then(|| {
+        Vec3::new(
+            pixel[0] as f32 * pixel_pitch,
+            pixel[1] as f32 * pixel_pitch,
+            z,
+        )
+    })
+}
+
+fn angle_between(a: Vec2, b: Vec2) -> Option<f32> {
+    (a.length() > 0.0 && b.length() > 0.0).then(|| a.angle_to(b).abs().to_degrees())
+}
+
+fn angle_between_3d(a: Vec3, b: Vec3) -> Option<f32> {
+    (a.length() > 0.0 && b.length() > 0.0).then(|| a.angle_between(b).to_degrees())
+}
+
+/// Up to `max_dots` + 1 evenly spaced pixels from `from` to `to`, both included
+fn line_pixels(from: [u32; 2], to: [u32; 2], max_dots: u32) -> impl Iterator<Item = [u32; 2]> {
+    let from = Vec2::new(from[0] as f32, from[1] as f32);
+    let to = Vec2::new(to[0] as f32, to[1] as f32);
+    let steps = ((to - from).abs().max_element() as u32).clamp(1, max_dots);
+    (0..=steps).map(move |step| {
+        let pixel = from.lerp(to, step as f32 / steps as f32).round();
+        [pixel.x as u32, pixel.y as u32]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+    use std::num::NonZeroU32;
+
+    #[test]
+    fn angle_of_v_groove() {
+        // Groove along y with 45° flanks: height rises by one pixel pitch per pixel from x = 2
+        let size = ImageSize {
+            width: NonZeroU32::new(5).unwrap(),
+            height: NonZeroU32::new(3).unwrap(),
+        };
+        let data = (0..15)
+            .map(|i| (i % 5) as f32 - 2.0)
+            .map(|x: f32| x.abs() * 0.5)
+            .collect();
+        let image = Image::new(size, data).unwrap();
+        let mut tool = MeasureTool::default();
+        tool.set_kind(Some(MeasureKind::Angle));
+        assert!(!tool.add_point([2, 1]));
+        assert!(!tool.add_point([0, 1]));
+        assert!(tool.add_point([4, 1]));
+        let angle = tool.angle(&image, 0.5).unwrap();
+        assert!((angle.in_plane - 180.0).abs() < 1e-3);
+        assert!((angle.spatial - 90.0).abs() < 1e-3);
+        assert!((angle.first_elevation - 45.0).abs() < 1e-3);
+        assert!((angle.second_elevation - 45.0).abs() < 1e-3);
+        assert_eq!(tool.guide_pixels().len(), 2 * 3);
+        // The next point starts a new measurement
+        assert!(!tool.add_point([1, 1]));
+        assert_eq!(tool.points(), &[[1, 1]]);
+    }
+
+    #[test]
+    fn fits_circle_and_sphere() {
+        // Spherical cap of radius 20 centered at pixel (8, 8) and height -10
+        let size = ImageSize {
+            width: NonZeroU32::new(17).unwrap(),
+            height: NonZeroU32::new(17).unwrap(),
+        };
+        let data = (0..17 * 17)
+            .map(|i| {
+                let (x, y) = ((i % 17) as f32 - 8.0, (i / 17) as f32 - 8.0);
+                (400.0 - x * x - y * y).sqrt() - 10.0
+            })
+            .collect();
+        let image = Image::new(size, data).unwrap();
+        let mut tool = MeasureTool::default();
+        tool.set_kind(Some(MeasureKind::Circle));
+        tool.add_point([0, 8]);
+        tool.add_point([16, 8]);
+        let circle = tool.radius_fit(&image, 1.0).unwrap();
+        assert!((circle.radius - 20.0).abs() < 1e-3);
+        assert!((circle.center_x - 8.0).abs() < 1e-3 && (circle.center_y - 8.0).abs() < 1e-3);
+        assert!((circle.center_z + 10.0).abs() < 1e-3);
+        assert_eq!(circle.points, 17);
+        assert!(circle.max_residual < 1e-3);
+        assert!(!circle.outline().is_empty());
+
+        tool.set_kind(Some(MeasureKind::Sphere));
+        tool.add_point([2, 3]);
+        tool.add_point([14, 12]);
+        let sphere = tool.radius_fit(&image, 1.0).unwrap();
+        assert!((sphere.radius - 20.0).abs() < 1e-3);
+        assert!((sphere.center_z + 10.0).abs() < 1e-3);
+        assert_eq!(sphere.points, 13 * 10);
+        // A flat patch has no finite radius
+        let flat = Image::new(image.size.clone(), vec![1.0; 17 * 17]).unwrap();
+        assert!(tool.radius_fit(&flat, 1.0).is_none());
+    }
+}
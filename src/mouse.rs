@@ -1,13 +1,28 @@
 use glam::Vec2;
+
+use crate::ui_scale::UiScale;
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
     event::{ElementState, MouseButton, MouseScrollDelta},
 };
 
+/// Largest distance in physical pixels the mouse may move between press and release of a click
+const CLICK_TOLERANCE: f64 = 4.0;
+/// Smallest factor a single scroll step may change the zoom by
+const MIN_ZOOM_STEP: f32 = 0.1;
+
 pub struct Mouse {
     pub current_position: PhysicalPosition<f64>,
     left_button: ElementState,
+    /// Where the left button was last pressed
+    press_position: PhysicalPosition<f64>,
     current_zoom: f32,
+    /// Factor on scroll steps, negative to zoom the other way
+    zoom_speed: f32,
+    /// Apply only the latest cursor move per frame, high polling rate mice report many more
+    coalesce_moves: bool,
+    /// Latest cursor move not applied yet
+    pending_move: Option<PhysicalPosition<f64>>,
 }
 
 impl Default for Mouse {
@@ -21,42 +36,74 @@ impl Mouse {
         Self {
             current_position: PhysicalPosition::new(0.0, 0.0),
             left_button: ElementState::Released,
+            press_position: PhysicalPosition::new(0.0, 0.0),
             current_zoom: 1.0,
+            zoom_speed: 1.0,
+            coalesce_moves: true,
+            pending_move: None,
+        }
+    }
+
+    pub fn set_coalesce_moves(&mut self, enabled: bool) {
+        self.coalesce_moves = enabled;
+    }
+
+    /// Keeps the move for `take_pending_move` when moves are coalesced,
+    /// otherwise returns it to be applied right away
+    pub fn queue_move(&mut self, position: PhysicalPosition<f64>) -> Option<PhysicalPosition<f64>> {
+        if self.coalesce_moves {
+            self.pending_move = Some(position);
+            None
+        } else {
+            Some(position)
         }
     }
 
+    pub fn take_pending_move(&mut self) -> Option<PhysicalPosition<f64>> {
+        self.pending_move.take()
+    }
+
     pub fn register_button_event(&mut self, button: MouseButton, state: ElementState) {
-        match button {
-            MouseButton::Left => {
-                if state == ElementState::Pressed {
-                    self.left_button = ElementState::Pressed;
-                } else {
-                    self.left_button = ElementState::Released;
-                }
+        if button == MouseButton::Left {
+            self.left_button = state;
+            if state == ElementState::Pressed {
+                self.press_position = self.current_position;
             }
-            _ => (),
         }
     }
 
+    /// Whether the left button was released close to where it was pressed, i.e. not dragged
+    pub fn is_click(&self) -> bool {
+        let dx = self.current_position.x - self.press_position.x;
+        let dy = self.current_position.y - self.press_position.y;
+        self.left_button == ElementState::Released && dx.hypot(dy) <= CLICK_TOLERANCE
+    }
+
     pub fn register_move_event(&mut self, new_position: PhysicalPosition<f64>) {
         self.current_position = new_position;
     }
 
-    pub fn register_scroll_event(&mut self, delta: MouseScrollDelta) {
+    pub fn set_zoom_speed(&mut self, zoom_speed: f32) {
+        self.zoom_speed = zoom_speed;
+    }
+
+    /// Vertical scroll distance in lines, positive away from the user
+    pub fn scroll_lines(delta: MouseScrollDelta, ui_scale: &UiScale) -> f32 {
         match delta {
-            MouseScrollDelta::LineDelta(_delta_x, delta_y) => {
-                self.current_zoom *= -0.1 * delta_y + 1.0;
-            }
-            MouseScrollDelta::PixelDelta(pos) => {
-                let delta_y = pos.y as f32 / 100.0;
-                self.current_zoom *= -0.1 * delta_y + 1.0;
-            }
+            MouseScrollDelta::LineDelta(_delta_x, delta_y) => delta_y,
+            MouseScrollDelta::PixelDelta(pos) => ui_scale.to_logical(pos.y as f32) / 100.0,
         }
     }
 
+    pub fn register_scroll_event(&mut self, delta: MouseScrollDelta, ui_scale: &UiScale) {
+        let delta_y = Self::scroll_lines(delta, ui_scale);
+        // A fast zoom speed must not flip the surface with a large step
+        self.current_zoom *= (-0.1 * delta_y * self.zoom_speed + 1.0).max(MIN_ZOOM_STEP);
+    }
+
     pub fn get_device_coordinates(&self, window_size: PhysicalSize<u32>) -> anyhow::Result<Vec2> {
-        let w = f64::try_from(window_size.width - 1)?;
-        let h = f64::try_from(window_size.height - 1)?;
+        let w = f64::from(window_size.width.saturating_sub(1));
+        let h = f64::from(window_size.height.saturating_sub(1));
         let x = (2.0 * self.current_position.x / w - 1.0) as f32;
         let y = (1.0 - 2.0 * self.current_position.y / h) as f32;
         Ok(Vec2::new(x, y))
@@ -70,7 +117,26 @@ impl Mouse {
         self.current_zoom
     }
 
+    /// Keeps scrolling continuous after the zoom was changed from elsewhere
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.current_zoom = zoom;
+    }
+
     pub fn is_pointer_inside(&self, pos: Vec2) -> bool {
         pos.x >= -1.0 && pos.x <= 1.0 && pos.y >= -1.0 && pos.y <= 1.0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ui_scale_override_leaves_scrolling_alone() {
+        let delta = MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.0, 200.0));
+        let mut ui_scale = UiScale::new(2.0);
+        assert_eq!(Mouse::scroll_lines(delta, &ui_scale), 1.0);
+        ui_scale.set_override(Some(4.0));
+        assert_eq!(Mouse::scroll_lines(delta, &ui_scale), 1.0);
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::anyhow;
+use std::{fmt, str::FromStr};
+
+/// How strongly the mouse and gamepads rotate, pan and zoom the view, and which directions are
+/// inverted. Written as comma separated entries like "rotate=1.5,zoom=0.5,invert-y", omitted
+/// entries keep their default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NavigationSettings {
+    /// Factor on the rotation of a drag
+    pub rotate_speed: f32,
+    /// Factor on the pan of a drag with Ctrl
+    pub pan_speed: f32,
+    /// Factor on the zoom of a scroll step
+    pub zoom_speed: f32,
+    /// Horizontal drags rotate the other way
+    pub invert_x: bool,
+    /// Vertical drags rotate the other way
+    pub invert_y: bool,
+    /// Scrolling up zooms out instead of in
+    pub invert_zoom: bool,
+    /// Factor on the motion of gamepad and SpaceMouse axes
+    pub axis_speed: f32,
+    /// Fraction of an axis' range around its rest position that is ignored, so that a
+    /// stick which doesn't center perfectly doesn't drift the view
+    pub dead_zone: f32,
+}
+
+impl Default for NavigationSettings {
+    fn default() -> Self {
+        Self {
+            rotate_speed: 1.0,
+            pan_speed: 1.0,
+            zoom_speed: 1.0,
+            invert_x: false,
+            invert_y: false,
+            invert_zoom: false,
+            axis_speed: 1.0,
+            dead_zone: 0.1,
+        }
+    }
+}
+
+impl NavigationSettings {
+    /// Factor on the x and y movement of a rotating drag, negative for inverted axes
+    pub fn rotate_factors(&self) -> (f32, f32) {
+        let sign = |invert: bool| if invert { -1.0 } else { 1.0 };
+        (
+            sign(self.invert_x) * self.rotate_speed,
+            sign(self.invert_y) * self.rotate_speed,
+        )
+    }
+
+    /// Factor on scroll steps, negative for inverted zoom
+    pub fn zoom_factor(&self) -> f32 {
+        if self.invert_zoom {
+            -self.zoom_speed
+        } else {
+            self.zoom_speed
+        }
+    }
+}
+
+impl FromStr for NavigationSettings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut settings = Self::default();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            match entry.split_once('=') {
+                Some((name, value)) => {
+                    let value = value
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|value| value.is_finite() && *value >= 0.0)
+                        .ok_or_else(|| anyhow!("Unsupported {} value: {}", name, value))?;
+                    let speed = Some(value)
+                        .filter(|speed| *speed > 0.0)
+                        .ok_or_else(|| anyhow!("{} speed must be positive", name));
+                    match name.trim() {
+                        "rotate" => settings.rotate_speed = speed?,
+                        "pan" => settings.pan_speed = speed?,
+                        "zoom" => settings.zoom_speed = speed?,
+                        "axes" => settings.axis_speed = speed?,
+                        "dead-zone" => {
+                            settings.dead_zone = Some(value)
+                                .filter(|dead_zone| *dead_zone < 1.0)
+                                .ok_or_else(|| anyhow!("dead-zone must be below 1"))?
+                        }
+                        _ => return Err(anyhow!("Unsupported navigation setting: {}", entry)),
+                    }
+                }
+                None => match entry {
+                    "invert-x" => settings.invert_x = true,
+                    "invert-y" => settings.invert_y = true,
+                    "invert-zoom" => settings.invert_zoom = true,
+                    _ => return Err(anyhow!("Unsupported navigation setting: {}", entry)),
+                },
+            }
+        }
+        Ok(settings)
+    }
+}
+
+impl fmt::Display for NavigationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rotate={},pan={},zoom={},axes={},dead-zone={}",
+            self.rotate_speed, self.pan_speed, self.zoom_speed, self.axis_speed, self.dead_zone
+        )?;
+        for (invert, name) in [
+            (self.invert_x, "invert-x"),
+            (self.invert_y, "invert-y"),
+            (self.invert_zoom, "invert-zoom"),
+        ] {
+            if invert {
+                write!(f, ",{}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_settings() {
+        let settings: NavigationSettings = "rotate=2, zoom=0.5,invert-y".parse().unwrap();
+        assert_eq!(settings.rotate_factors(), (2.0, -2.0));
+        assert_eq!(settings.pan_speed, 1.0);
+        assert_eq!(settings.zoom_factor(), 0.5);
+        assert_eq!(
+            settings.to_string().parse::<NavigationSettings>().unwrap(),
+            settings
+        );
+        assert_eq!(
+            "".parse::<NavigationSettings>().unwrap(),
+            NavigationSettings::default()
+        );
+        assert!("rotate=-1".parse::<NavigationSettings>().is_err());
+        assert!("invert-w".parse::<NavigationSettings>().is_err());
+        assert_eq!(
+            "dead-zone=0"
+                .parse::<NavigationSettings>()
+                .unwrap()
+                .dead_zone,
+            0.0
+        );
+        assert!("dead-zone=1".parse::<NavigationSettings>().is_err());
+    }
+}
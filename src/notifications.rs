@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// How prominently a notification is shown
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    /// A requested operation like a load or an export completed
+    Success,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn log_level(self) -> log::Level {
+        match self {
+            Self::Info | Self::Success => log::Level::Info,
+            Self::Warning => log::Level::Warn,
+            Self::Error => log::Level::Error,
+        }
+    }
+}
+
+/// Message for the user, shown as a toast and kept in the notification log
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(getter_with_clone))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub id: u32,
+    pub severity: Severity,
+    pub message: String,
+    /// Milliseconds since the Unix epoch, e.g. for `new Date(timestamp)`
+    pub timestamp: f64,
+}
+
+/// The latest notifications, older ones are dropped
+#[derive(Debug, Default)]
+pub struct Notifications {
+    next_id: u32,
+    entries: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub const CAPACITY: usize = 200;
+
+    /// Adds a notification and writes it to the log as well
+    pub fn push(&mut self, severity: Severity, message: String) -> &Notification {
+        log::log!(severity.log_level(), "{}", message);
+        let timestamp = web_time::SystemTime::now()
+            .duration_since(web_time::SystemTime::UNIX_EPOCH)
+            .map_or(0.0, |since| since.as_secs_f64() * 1000.0);
+        if self.entries.len() == Self::CAPACITY {
+            self.entries.pop_front();
+        }
+        self.next_id = self.next_id.wrapping_add(1);
+        self.entries.push_back(Notification {
+            id: self.next_id,
+            severity,
+            message,
+            timestamp,
+        });
+        &self.entries[self.entries.len() - 1]
+    }
+
+    /// Oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &Notification> {
+        self.entries.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keeps_latest_notifications() {
+        let mut notifications = Notifications::default();
+        for i in 0..Notifications::CAPACITY + 5 {
+            notifications.push(Severity::Info, format!("{}", i));
+        }
+        let entries: Vec<_> = notifications.entries().collect();
+        assert_eq!(entries.len(), Notifications::CAPACITY);
+        assert_eq!(entries[0].message, "5");
+        assert!(entries.windows(2).all(|pair| pair[0].id < pair[1].id));
+        notifications.clear();
+        assert_eq!(notifications.entries().count(), 0);
+    }
+}
@@ -0,0 +1,574 @@
+use std::sync::Arc;
+use winit::dpi::PhysicalSize;
+
+use futures::FutureExt;
+
+use crate::{
+    camera_sync::CameraState,
+    error::ViewerError,
+    image::{RgbaImage, SurfaceAmplitudeImage},
+    pixel_picker::PixelPicker,
+    renderer::Renderer,
+};
+
+/// Color format of offscreen renders, 4 bytes per pixel
+const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Renders without a window for golden-image regression tests.
+/// Nothing changes between frames unless requested: the camera stays where it is put,
+/// there is no vsync and the UI scale is fixed to 1.
+/// The adapter can be selected with the `WGPU_ADAPTER_NAME` and `WGPU_BACKEND` environment
+/// variables, e.g. to force a software rasterizer in CI.
+pub struct OffscreenRenderer {
+    renderer: Renderer,
+    adapter: wgpu::AdapterInfo,
+    /// Color and picking target of `render_and_wait`, created on first use
+    targets: Option<(wgpu::TextureView, wgpu::TextureView)>,
+}
+
+impl OffscreenRenderer {
+    pub async fn new(width: u32, height: u32) -> Result<Self, ViewerError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None).await?;
+        Self::with_adapter(adapter, width, height).await
+    }
+
+    /// Renders with the fallback adapter of the platform, a software rasterizer like WARP or
+    /// lavapipe, so that tests see the same pixels on machines without a GPU
+    pub async fn fallback(width: u32, height: u32) -> Result<Self, ViewerError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                force_fallback_adapter: true,
+                ..Default::default()
+            })
+            .await?;
+        Self::with_adapter(adapter, width, height).await
+    }
+
+    async fn with_adapter(
+        adapter: wgpu::Adapter,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, ViewerError> {
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default())
+            .await?;
+        let size = PhysicalSize::new(width.max(1), height.max(1));
+        Ok(Self {
+            renderer: Renderer::new(Arc::new(device), queue, COLOR_FORMAT, size),
+            adapter: adapter.get_info(),
+            targets: None,
+        })
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter
+    }
+
+    /// Bytes allocated on the GPU, `None` if the backend doesn't report them
+    pub fn allocated_bytes(&self) -> Option<u64> {
+        self.renderer
+            .device()
+            .generate_allocator_report()
+            .map(|report| report.total_allocated_bytes)
+    }
+
+    /// Scene to render, set surface, overlays and markers here
+    pub(crate) fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    pub fn set_camera(&mut self, camera: CameraState) {
+        self.renderer.set_camera(camera);
+    }
+
+    /// Renders one frame and waits for the GPU to return it
+    pub fn render(&mut self) -> Result<RgbaImage, ViewerError> {
+        capture(&mut self.renderer, COLOR_FORMAT)
+    }
+
+    /// Renders one frame and reads the picking target at `x`, `y`: the surface pixel and the
+    /// object ID of an overlay or marker there, zero where nothing was drawn
+    pub fn pick(&mut self, x: u32, y: u32) -> Result<[u32; 4], ViewerError> {
+        let device = self.renderer.device().clone();
+        let size = self.renderer.size();
+        let color = create_target(&device, size, COLOR_FORMAT, wgpu::TextureUsages::empty());
+        let picking = create_target(
+            &device,
+            size,
+            PixelPicker::PICKING_FORMAT,
+            wgpu::TextureUsages::COPY_SRC,
+        );
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen_picking_buffer"),
+            size: std::mem::size_of::<[u32; 4]>() as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        self.renderer.render(
+            &mut encoder,
+            &color.create_view(&wgpu::TextureViewDescriptor::default()),
+            &picking.create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &picking,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: x.min(size.width - 1),
+                    y: y.min(size.height - 1),
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.renderer.queue().submit([encoder.finish()]);
+        let bytes = read_buffer(&device, &readback_buffer)?;
+        let mut picked = [0; 4];
+        picked.copy_from_slice(bytemuck::cast_slice(&bytes));
+        Ok(picked)
+    }
+
+    /// Renders one frame without reading it back and waits until the GPU finished it, e.g. for
+    /// benchmarks. Returns the time it took to encode and submit the frame.
+    pub fn render_and_wait(&mut self) -> Result<web_time::Duration, ViewerError> {
+        let device = self.renderer.device().clone();
+        let (color_view, picking_view) = self.targets.get_or_insert_with(|| {
+            let size = self.renderer.size();
+            let color = create_target(&device, size, COLOR_FORMAT, wgpu::TextureUsages::empty());
+            let picking = create_target(
+                &device,
+                size,
+                PixelPicker::PICKING_FORMAT,
+                wgpu::TextureUsages::empty(),
+            );
+            (
+                color.create_view(&wgpu::TextureViewDescriptor::default()),
+                picking.create_view(&wgpu::TextureViewDescriptor::default()),
+            )
+        });
+        let start = web_time::Instant::now();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        self.renderer.render(&mut encoder, color_view, picking_view);
+        self.renderer.queue().submit([encoder.finish()]);
+        let encoded = start.elapsed();
+        device.poll(wgpu::PollType::Wait)?;
+        Ok(encoded)
+    }
+}
+
+fn create_target(
+    device: &wgpu::Device,
+    size: PhysicalSize<u32>,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen_target"),
+        size: wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | usage,
+        view_formats: &[],
+    })
+}
+
+/// Renders one frame of `renderer` into a texture and waits for the GPU to return it, e.g. to
+/// take a screenshot of a window. `color_format` is the format the renderer was created with,
+/// it has to be an RGBA or BGRA format with 8 bit per channel.
+pub(crate) fn capture(
+    renderer: &mut Renderer,
+    color_format: wgpu::TextureFormat,
+) -> Result<RgbaImage, ViewerError> {
+    let device = renderer.device().clone();
+    let size = renderer.size();
+    let size = PhysicalSize::new(size.width.max(1), size.height.max(1));
+    let color_texture = create_target(&device, size, color_format, wgpu::TextureUsages::COPY_SRC);
+    let picking_texture = create_target(
+        &device,
+        size,
+        PixelPicker::PICKING_FORMAT,
+        wgpu::TextureUsages::empty(),
+    );
+    let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let picking_view = picking_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    // Rows of a texture copy have to be aligned to 256 bytes
+    let unpadded_bytes_per_row = size.width * 4;
+    let bytes_per_row = unpadded_bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("offscreen_readback_buffer"),
+        size: u64::from(bytes_per_row * size.height),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    renderer.render(&mut encoder, &color_view, &picking_view);
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture: &color_texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &readback_buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(size.height),
+            },
+        },
+        wgpu::Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        },
+    );
+    renderer.queue().submit([encoder.finish()]);
+
+    let mut pixels: Vec<u8> = read_buffer(&device, &readback_buffer)?
+        .chunks_exact(bytes_per_row as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+        .copied()
+        .collect();
+
+    if matches!(
+        color_format,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+    }
+
+    Ok(RgbaImage {
+        width: size.width,
+        height: size.height,
+        pixels,
+        color_space: renderer.color_space.clone(),
+    })
+}
+
+/// Waits for the GPU to finish the submitted work and copies the buffer to the CPU
+fn read_buffer(device: &wgpu::Device, buffer: &wgpu::Buffer) -> Result<Vec<u8>, ViewerError> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer.map_async(wgpu::MapMode::Read, .., move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    receiver
+        .recv()
+        .map_err(|e| ViewerError::Readback(e.to_string()))??;
+    let bytes = buffer.get_mapped_range(..).to_vec();
+    buffer.unmap();
+    Ok(bytes)
+}
+
+/// Options of the `render` subcommand
+#[derive(Debug, PartialEq)]
+pub struct RenderOptions {
+    pub file: String,
+    pub out: String,
+    pub width: u32,
+    pub height: u32,
+    /// Azimuth and elevation in degrees, the default view if `None`
+    pub view: Option<(f32, f32)>,
+    /// Positions in the rendered image to read the picking target at
+    pub picks: Vec<(u32, u32)>,
+    /// Renders with the software rasterizer of the platform, see `OffscreenRenderer::fallback`
+    pub fallback_adapter: bool,
+}
+
+impl RenderOptions {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let usage = || {
+            anyhow::anyhow!(
+                "Usage: render <file> <out.png> [--size <width>x<height>] \
+                 [--view <azimuth>,<elevation>] [--pick <x>,<y>]... [--fallback-adapter]"
+            )
+        };
+        let pair = |value: Option<String>, separator| {
+            let value = value.ok_or_else(usage)?;
+            let (a, b) = value.split_once(separator).ok_or_else(usage)?;
+            anyhow::Ok((a.trim().to_owned(), b.trim().to_owned()))
+        };
+        let (mut file, mut out) = (None, None);
+        let (mut width, mut height) = (256, 256);
+        let mut view = None;
+        let mut picks = Vec::new();
+        let mut fallback_adapter = false;
+        while let Some(arg) = args.next() {
+            if arg == "--size" {
+                let (w, h) = pair(args.next(), 'x')?;
+                (width, height) = (w.parse()?, h.parse()?);
+            } else if arg == "--view" {
+                let (azimuth, elevation) = pair(args.next(), ',')?;
+                view = Some((azimuth.parse()?, elevation.parse()?));
+            } else if arg == "--pick" {
+                let (x, y) = pair(args.next(), ',')?;
+                picks.push((x.parse()?, y.parse()?));
+            } else if arg == "--fallback-adapter" {
+                fallback_adapter = true;
+            } else if file.is_none() {
+                file = Some(arg);
+            } else if out.is_none() {
+                out = Some(arg);
+            } else {
+                return Err(anyhow::anyhow!("Unexpected argument: {}", arg));
+            }
+        }
+        Ok(Self {
+            file: file.ok_or_else(usage)?,
+            out: out.ok_or_else(usage)?,
+            width: width.max(1),
+            height: height.max(1),
+            view,
+            picks,
+            fallback_adapter,
+        })
+    }
+}
+
+/// Renders a file without a window into a PNG and prints what was picked at the requested
+/// positions as `pick <x> <y> <column> <row> <object>` lines, for tests of the whole path from
+/// the file to the pixels
+pub fn run(options: &RenderOptions) -> anyhow::Result<()> {
+    let mut offscreen = pollster::block_on(if options.fallback_adapter {
+        OffscreenRenderer::fallback(options.width, options.height).boxed_local()
+    } else {
+        OffscreenRenderer::new(options.width, options.height).boxed_local()
+    })?;
+    log::info!("Rendering with {}", offscreen.adapter_info().name);
+    let image = SurfaceAmplitudeImage::from_file(&options.file)?;
+    offscreen
+        .renderer()
+        .set_surface(image.surface, None, None)?;
+    offscreen.renderer().reset_camera();
+    if let Some((azimuth, elevation)) = options.view {
+        offscreen
+            .renderer()
+            .transformation
+            .set_orientation(azimuth, elevation);
+    }
+    std::fs::write(&options.out, offscreen.render()?.to_png()?)?;
+    for &(x, y) in &options.picks {
+        let [column, row, object, _] = offscreen.pick(x, y)?;
+        println!("pick {} {} {} {} {}", x, y, column, row, object);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use super::*;
+    use crate::{
+        baseline::BaselineMode,
+        compute::ComputeDevice,
+        debug_view::DebugView,
+        image::{Image, ImageSize},
+        markers::{Marker, MarkerShape},
+    };
+
+    fn bump(size: u32) -> Image<f32> {
+        let center = (size - 1) as f32 / 2.0;
+        Image {
+            size: ImageSize {
+                width: NonZeroU32::new(size).unwrap(),
+                height: NonZeroU32::new(size).unwrap(),
+            },
+            data: (0..size * size)
+                .map(|i| {
+                    let dx = (i % size) as f32 - center;
+                    let dy = (i / size) as f32 - center;
+                    (-(dx * dx + dy * dy) / (size * size) as f32 * 8.0).exp()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn offscreen_rendering_is_deterministic() {
+        let Ok(mut offscreen) = pollster::block_on(OffscreenRenderer::new(96, 64)) else {
+            eprintln!("No graphics adapter available, skipping golden-image test");
+            return;
+        };
+        offscreen
+            .renderer()
+            .set_surface(bump(64), None, None)
+            .unwrap();
+        let first = offscreen.render().unwrap();
+        let second = offscreen.render().unwrap();
+        assert_eq!(first.pixels.len(), 96 * 64 * 4);
+        assert_eq!(first.hash(), second.hash());
+        // The top of the bump faces the camera in the center, the corners stay clear
+        assert_ne!(first.get_pixel(48, 32), [0, 0, 0, 255]);
+        assert_eq!(first.get_pixel(0, 0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn debug_views_are_drawn() {
+        let Ok(mut offscreen) = pollster::block_on(OffscreenRenderer::new(96, 64)) else {
+            eprintln!("No graphics adapter available, skipping debug view test");
+            return;
+        };
+        offscreen
+            .renderer()
+            .set_surface(bump(64), None, None)
+            .unwrap();
+        let shaded = offscreen.render().unwrap();
+        let views = [
+            DebugView {
+                bounds: true,
+                ..Default::default()
+            },
+            DebugView {
+                normals: true,
+                ..Default::default()
+            },
+            DebugView {
+                overdraw: true,
+                ..Default::default()
+            },
+        ];
+        for view in views {
+            offscreen.renderer().set_debug_view(view);
+            let frame = offscreen.render().unwrap();
+            assert_ne!(frame.hash(), shaded.hash(), "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn markers_are_drawn_in_front_of_their_surface() {
+        let Ok(mut offscreen) = pollster::block_on(OffscreenRenderer::new(96, 64)) else {
+            eprintln!("No graphics adapter available, skipping marker test");
+            return;
+        };
+        // Flat but for a raised left quarter, so that the heights have a range
+        let flat = Image {
+            size: ImageSize {
+                width: NonZeroU32::new(64).unwrap(),
+                height: NonZeroU32::new(64).unwrap(),
+            },
+            data: (0..64 * 64)
+                .map(|i| if i % 64 < 16 { 2.0 } else { 1.0 })
+                .collect(),
+        };
+        offscreen.renderer().set_surface(flat, None, None).unwrap();
+        let surface = offscreen.render().unwrap();
+        let marker = Marker {
+            pixel: [32, 32],
+            color: [255, 0, 255, 255],
+            size: 16.0,
+            on_top: false,
+            shape: MarkerShape::Dot,
+            height: None,
+            group: None,
+        };
+        offscreen.renderer().set_markers(Arc::new(vec![marker]));
+        let with_marker = offscreen.render().unwrap();
+        // The marker sits on the flat part in the center and is depth-tested against it
+        let [red, green, blue, _] = with_marker.get_pixel(48, 32);
+        assert_ne!(with_marker.get_pixel(48, 32), surface.get_pixel(48, 32));
+        assert!(
+            red > 128 && blue > 128 && green < 64,
+            "{:?}",
+            [red, green, blue]
+        );
+    }
+
+    #[test]
+    fn live_baseline_is_compared_like_a_reference() {
+        let Ok(mut offscreen) = pollster::block_on(OffscreenRenderer::new(96, 64)) else {
+            eprintln!("No graphics adapter available, skipping baseline test");
+            return;
+        };
+        let renderer = offscreen.renderer();
+        renderer.set_surface(bump(64), None, None).unwrap();
+        renderer.set_reference(bump(64)).unwrap();
+        renderer.set_show_residual(true);
+        let with_reference = offscreen.render().unwrap();
+
+        let renderer = offscreen.renderer();
+        renderer.set_baseline(Some(BaselineMode::RollingAverage { weight: 0.5 }));
+        assert!(renderer.reference.is_none());
+        // Unchanged frames leave the baseline as it is
+        renderer.set_surface(bump(64), None, None).unwrap();
+        let with_baseline = offscreen.render().unwrap();
+        assert_eq!(with_reference.hash(), with_baseline.hash());
+
+        let bump = bump(64);
+        let raised = Image {
+            data: bump.data.iter().map(|height| height + 1.0).collect(),
+            ..bump
+        };
+        offscreen
+            .renderer()
+            .set_surface(raised, None, None)
+            .unwrap();
+        assert_ne!(offscreen.render().unwrap().hash(), with_baseline.hash());
+    }
+
+    #[test]
+    fn temporal_statistics_of_two_frames() {
+        // The same on a device of its own, uploaded from the CPU like on a second GPU
+        let compute = pollster::block_on(ComputeDevice::open("0")).ok();
+        for compute in [None, compute] {
+            let Ok(mut offscreen) = pollster::block_on(OffscreenRenderer::new(32, 32)) else {
+                eprintln!("No graphics adapter available, skipping temporal statistics test");
+                return;
+            };
+            let renderer = offscreen.renderer();
+            renderer.set_compute_device(compute);
+            assert!(renderer.temporal_maps().is_none());
+            renderer.set_temporal_collecting(true);
+            let bump = bump(16);
+            let raised = Image {
+                data: bump.data.iter().map(|height| height + 2.0).collect(),
+                size: bump.size.clone(),
+            };
+            let heights = bump.data.to_vec();
+            renderer.set_surface(bump, None, None).unwrap();
+            renderer.set_surface(raised, None, None).unwrap();
+
+            let maps = pollster::block_on(renderer.temporal_maps().unwrap()).unwrap();
+            assert_eq!(maps.frames, 2);
+            for ((height, mean), std_dev) in heights
+                .iter()
+                .zip(maps.mean.data.iter())
+                .zip(maps.std_dev.data.iter())
+            {
+                if height.is_nan() {
+                    assert!(mean.is_nan() && std_dev.is_nan());
+                } else {
+                    assert!((mean - (height + 1.0)).abs() < 1e-4);
+                    assert!((std_dev - std::f32::consts::SQRT_2).abs() < 1e-4);
+                }
+            }
+        }
+    }
+}
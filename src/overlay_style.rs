@@ -0,0 +1,174 @@
+// Overlays can carry numeric metadata like a severity or a classifier score. Styling rules are
+// evaluated when the overlays are rasterized, so that e.g. detection results can be explored by
+// threshold without sending the overlays again.
+
+use anyhow::anyhow;
+use std::{fmt, str::FromStr};
+
+use crate::{colormap::Colormap, texture::Overlay};
+
+/// Colors overlays by a metadata value, the text form is `key:colormap[:min:max]` like
+/// `severity:viridis:1:5`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorRamp {
+    pub key: String,
+    pub colormap: Colormap,
+    /// Values mapped to the ends of the colormap
+    pub range: [f32; 2],
+}
+
+impl ColorRamp {
+    pub const DEFAULT_RANGE: [f32; 2] = [0.0, 1.0];
+
+    /// Color of `value` with the given alpha
+    fn color(&self, value: f32, alpha: u8) -> [u8; 4] {
+        let [min, max] = self.range;
+        let t = if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.0
+        };
+        let [r, g, b] = self.colormap.srgb(t).map(|c| (c * 255.0).round() as u8);
+        [r, g, b, alpha]
+    }
+}
+
+impl fmt::Display for ColorRamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.key,
+            self.colormap.name(),
+            self.range[0],
+            self.range[1]
+        )
+    }
+}
+
+impl FromStr for ColorRamp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let range = match parts[..] {
+            [_, _] => Self::DEFAULT_RANGE,
+            [_, _, min, max] => [min.parse()?, max.parse()?],
+            _ => return Err(anyhow!("Invalid color ramp: {}", s)),
+        };
+        if parts[0].is_empty() {
+            return Err(anyhow!("Color ramp needs a metadata key: {}", s));
+        }
+        Ok(Self {
+            key: parts[0].to_string(),
+            colormap: parts[1].parse()?,
+            range,
+        })
+    }
+}
+
+/// Hides overlays whose metadata value is below a minimum, the text form is `key:min` like
+/// `score:0.8`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScoreFilter {
+    pub key: String,
+    pub min: f32,
+}
+
+impl fmt::Display for ScoreFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.key, self.min)
+    }
+}
+
+impl FromStr for ScoreFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, min) = s
+            .rsplit_once(':')
+            .filter(|(key, _)| !key.is_empty())
+            .ok_or_else(|| anyhow!("Invalid score filter: {}", s))?;
+        Ok(Self {
+            key: key.to_string(),
+            min: min
+                .parse()
+                .map_err(|_| anyhow!("Invalid minimum score: {}", min))?,
+        })
+    }
+}
+
+/// Rules for drawing overlays by their metadata
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverlayStyle {
+    pub ramp: Option<ColorRamp>,
+    pub filter: Option<ScoreFilter>,
+}
+
+impl OverlayStyle {
+    /// Color to draw the overlay in, `None` if it is filtered out. Overlays without the value a
+    /// rule looks at are not affected by it.
+    pub fn display_color(&self, overlay: &Overlay) -> Option<[u8; 4]> {
+        if let Some(filter) = &self.filter
+            && overlay
+                .metadata
+                .get(&filter.key)
+                .is_some_and(|&value| value < filter.min)
+        {
+            return None;
+        }
+        let color = self
+            .ramp
+            .as_ref()
+            .and_then(|ramp| {
+                let value = overlay.metadata.get(&ramp.key)?;
+                Some(ramp.color(*value, overlay.color[3]))
+            })
+            .unwrap_or(overlay.color);
+        Some(color)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn overlays_are_styled_by_their_metadata() {
+        let overlay = |score: Option<f32>| Overlay {
+            pixels: Vec::new(),
+            color: [255, 0, 0, 200],
+            group: None,
+            metadata: score
+                .map(|score| BTreeMap::from([("score".to_string(), score)]))
+                .unwrap_or_default(),
+        };
+        let style = OverlayStyle {
+            ramp: Some("score:classic:0:2".parse().unwrap()),
+            filter: Some("score:0.5".parse().unwrap()),
+        };
+        assert_eq!(style.display_color(&overlay(Some(0.2))), None);
+        assert_eq!(
+            style.display_color(&overlay(Some(1.0))),
+            Some([128, 128, 128, 200])
+        );
+        assert_eq!(
+            style.display_color(&overlay(Some(5.0))),
+            Some([255, 255, 255, 200])
+        );
+        assert_eq!(style.display_color(&overlay(None)), Some([255, 0, 0, 200]));
+    }
+
+    #[test]
+    fn text_forms_round_trip() {
+        let ramp: ColorRamp = "severity:viridis".parse().unwrap();
+        assert_eq!(ramp.range, ColorRamp::DEFAULT_RANGE);
+        assert_eq!(ramp.to_string().parse::<ColorRamp>().unwrap(), ramp);
+        let filter: ScoreFilter = "classifier:score:0.75".parse().unwrap();
+        assert_eq!(filter.key, "classifier:score");
+        assert_eq!(filter.to_string().parse::<ScoreFilter>().unwrap(), filter);
+        assert!("score".parse::<ColorRamp>().is_err());
+        assert!("score:high".parse::<ScoreFilter>().is_err());
+    }
+}
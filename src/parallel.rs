@@ -0,0 +1,47 @@
+// Data parallel loops over pixels. Natively they run on the rayon thread pool, in the browser
+// they run sequentially: WebAssembly threads need cross-origin isolated pages and a
+// `SharedArrayBuffer`, which the demo doesn't require. The results are the same either way.
+
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+
+/// `f` applied to every element
+pub fn map<T, U>(data: &[T], f: impl Fn(&T) -> U + Send + Sync) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    return data.par_iter().map(f).collect();
+    #[cfg(target_arch = "wasm32")]
+    return data.iter().map(f).collect();
+}
+
+/// Calls `f` with the index and the elements of every chunk of `chunk_size` elements, e.g. rows
+pub fn for_each_chunk_mut<T: Send>(
+    data: &mut [T],
+    chunk_size: usize,
+    f: impl Fn(usize, &mut [T]) + Send + Sync,
+) {
+    #[cfg(not(target_arch = "wasm32"))]
+    data.par_chunks_mut(chunk_size)
+        .enumerate()
+        .for_each(|(index, chunk)| f(index, chunk));
+    #[cfg(target_arch = "wasm32")]
+    data.chunks_mut(chunk_size)
+        .enumerate()
+        .for_each(|(index, chunk)| f(index, chunk));
+}
+
+/// Runs `f` on a single thread, to compare the speed of the parallel loops in `--bench`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sequential<R: Send>(f: impl FnOnce() -> R + Send) -> anyhow::Result<R> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build()?;
+    Ok(pool.install(f))
+}
+
+/// Threads the parallel loops run on
+#[cfg(not(target_arch = "wasm32"))]
+pub fn threads() -> usize {
+    rayon::current_num_threads()
+}
@@ -1,15 +1,22 @@
 use futures::FutureExt;
 use futures::future::Shared;
-use std::sync::{Arc, Mutex};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Arc,
+};
 use winit::dpi::{PhysicalPosition, PhysicalSize};
 
-use crate::image::Image;
+use crate::{image::Image, selection::Selection};
 
 /// Result type for pixel reads - must be Clone for Shared futures
 pub type PixelResult = Result<(u32, u32, f32), Arc<anyhow::Error>>;
 
+/// Shared future resolving to the pixel under the mouse
+pub type PixelFuture = Shared<std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>>>;
+
 pub struct PixelPicker {
-    /// Texture that stores picking data (pixel_x, pixel_y) for each fragment
+    /// Texture that stores picking data (pixel_x, pixel_y, object ID) for each fragment
     picking_texture: wgpu::Texture,
     pub picking_texture_view: wgpu::TextureView,
     /// Buffer to copy a single pixel from the picking texture
@@ -17,13 +24,13 @@ pub struct PixelPicker {
     mouse_position: PhysicalPosition<f64>,
     window_size: PhysicalSize<u32>,
     /// Cached shared future - if a read is in progress, subsequent calls get the same future
-    pending_read: Arc<
-        Mutex<Option<Shared<std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>>>>>,
-    >,
+    pending_read: Rc<RefCell<Option<PixelFuture>>>,
+    /// Overlay or marker under the mouse at the last completed read
+    picked_object: Rc<Cell<Option<Selection>>>,
 }
 
 impl PixelPicker {
-    pub const PICKING_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+    pub const PICKING_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
 
     pub fn new(device: &wgpu::Device, window_size: PhysicalSize<u32>) -> Self {
         let (picking_texture, picking_texture_view) =
@@ -36,7 +43,8 @@ impl PixelPicker {
             readback_buffer,
             mouse_position: PhysicalPosition::new(0.0, 0.0),
             window_size,
-            pending_read: Arc::new(Mutex::new(None)),
+            pending_read: Rc::new(RefCell::new(None)),
+            picked_object: Rc::new(Cell::new(None)),
         }
     }
 
@@ -54,10 +62,15 @@ impl PixelPicker {
         self.mouse_position = position;
     }
 
+    /// Topmost overlay or marker under the mouse when the pixel was last read
+    pub fn picked_object(&self) -> Option<Selection> {
+        self.picked_object.get()
+    }
+
     /// Copy the pixel at the current mouse position from the picking texture to the readback buffer.
     /// Only call this when is_idle() returns true!
     pub fn copy_pixel_at_mouse(&self, encoder: &mut wgpu::CommandEncoder) {
-        if self.pending_read.lock().unwrap().is_some() {
+        if self.pending_read.borrow().is_some() {
             return;
         }
         let x = (self.mouse_position.x as u32).min(self.window_size.width.saturating_sub(1));
@@ -91,19 +104,15 @@ impl PixelPicker {
         &self,
         device: Arc<wgpu::Device>,
         image: Arc<Image<f32>>,
-        sender: futures::channel::oneshot::Sender<
-            Shared<std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>>>,
-        >,
+        sender: futures::channel::oneshot::Sender<PixelFuture>,
     ) {
-        sender.send(self.get(device, image)).unwrap();
+        if sender.send(self.get(device, image)).is_err() {
+            log::error!("Pixel value receiver dropped");
+        }
     }
 
-    pub fn get(
-        &self,
-        device: Arc<wgpu::Device>,
-        image: Arc<Image<f32>>,
-    ) -> Shared<std::pin::Pin<Box<dyn std::future::Future<Output = PixelResult>>>> {
-        let mut pending = self.pending_read.lock().unwrap();
+    pub fn get(&self, device: Arc<wgpu::Device>, image: Arc<Image<f32>>) -> PixelFuture {
+        let mut pending = self.pending_read.borrow_mut();
 
         // If there's already a pending read, return a clone of it
         if let Some(ref shared) = *pending {
@@ -113,6 +122,7 @@ impl PixelPicker {
         // Create new read future
         let buffer = self.readback_buffer.clone();
         let pending_read = self.pending_read.clone();
+        let picked_object = self.picked_object.clone();
         let (tx, rx) = async_channel::bounded::<Result<(), wgpu::BufferAsyncError>>(1);
 
         buffer.map_async(wgpu::MapMode::Read, .., move |result| {
@@ -129,15 +139,14 @@ impl PixelPicker {
                     .map_err(|e| Arc::new(anyhow::anyhow!("Buffer map error: {:?}", e)))?;
 
                 let output_data = buffer.get_mapped_range(..);
-                let pixel = (
-                    bytemuck::cast_slice::<u8, u32>(&output_data)[0],
-                    bytemuck::cast_slice::<u8, u32>(&output_data)[1],
-                );
+                let picking = bytemuck::cast_slice::<u8, u32>(&output_data);
+                let pixel = (picking[0], picking[1]);
+                picked_object.set(Selection::from_id(picking[2]));
                 drop(output_data);
                 buffer.unmap();
 
                 // Clear the pending read so next call starts fresh
-                *pending_read.lock().unwrap() = None;
+                *pending_read.borrow_mut() = None;
                 let z = image.get_pixel(pixel.0, pixel.1);
                 Ok((pixel.0, pixel.1, z))
             });
@@ -172,7 +181,7 @@ impl PixelPicker {
     fn create_readback_buffer(device: &wgpu::Device) -> wgpu::Buffer {
         device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("picking_readback_buffer"),
-            size: std::mem::size_of::<[u32; 2]>() as u64,
+            size: std::mem::size_of::<[u32; 4]>() as u64,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
             mapped_at_creation: false,
         })
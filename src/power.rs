@@ -0,0 +1,71 @@
+use anyhow::anyhow;
+use web_time::{Duration, Instant};
+
+/// Reads the GPU power preference, "low" prefers an integrated GPU to save battery,
+/// "high" a discrete one and "default" leaves the choice to the driver
+pub fn parse_power_preference(s: &str) -> anyhow::Result<wgpu::PowerPreference> {
+    match s.trim().to_lowercase().as_str() {
+        "low" | "low-power" => Ok(wgpu::PowerPreference::LowPower),
+        "high" | "high-performance" => Ok(wgpu::PowerPreference::HighPerformance),
+        "default" | "none" => Ok(wgpu::PowerPreference::None),
+        _ => Err(anyhow!("Unsupported power preference: {}", s)),
+    }
+}
+
+/// Caps the frame rate of animations, which otherwise redraw as fast as the display refreshes
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    min_interval: Option<Duration>,
+    last_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// `None` or a non-positive rate removes the cap
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.min_interval = max_fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .map(|fps| Duration::from_secs_f32(1.0 / fps));
+    }
+
+    pub fn max_fps(&self) -> Option<f32> {
+        self.min_interval
+            .map(|interval| 1.0 / interval.as_secs_f32())
+    }
+
+    /// Records that a frame is rendered now
+    pub fn frame_rendered(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+    }
+
+    /// When the next frame may be rendered, `None` if right away
+    pub fn next_frame(&self, now: Instant) -> Option<Instant> {
+        let next = self.last_frame? + self.min_interval?;
+        (next > now).then_some(next)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limits_frame_rate() {
+        let mut limiter = FrameLimiter::default();
+        let start = Instant::now();
+        limiter.frame_rendered(start);
+        assert_eq!(limiter.next_frame(start), None);
+        limiter.set_max_fps(Some(4.0));
+        let next = start + Duration::from_millis(250);
+        assert_eq!(
+            limiter.next_frame(start + Duration::from_millis(10)),
+            Some(next)
+        );
+        assert_eq!(limiter.next_frame(next), None);
+        limiter.set_max_fps(Some(0.0));
+        assert_eq!(limiter.max_fps(), None);
+        assert_eq!(
+            parse_power_preference("Low").unwrap(),
+            wgpu::PowerPreference::LowPower
+        );
+    }
+}
@@ -0,0 +1,153 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use std::ops::Range;
+
+use crate::{image::Image, units::Units};
+
+/// Statistics of the surface heights in a small neighborhood around a pixel,
+/// used to judge the noise level at a point
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NeighborhoodStats {
+    pub mean: f32,
+    /// Standard deviation
+    pub std: f32,
+    pub min: f32,
+    pub max: f32,
+    /// Slope of the best fitting plane along x in height per pixel
+    pub gradient_x: f32,
+    /// Slope of the best fitting plane along y in height per pixel
+    pub gradient_y: f32,
+    /// Number of valid (finite) pixels the statistics are based on
+    pub count: u32,
+}
+
+impl NeighborhoodStats {
+    /// Side length of the probed square used when none is given
+    pub const DEFAULT_SIZE: u32 = 5;
+
+    /// Samples the `size` × `size` pixels centered on `pixel`, clipped at the image border.
+    /// Missing pixels are skipped, returns `None` if no valid pixel is left.
+    pub(crate) fn sample(image: &Image<f32>, pixel: [u32; 2], size: u32) -> Option<Self> {
+        let radius = size.max(1) / 2;
+        let width = image.size.width.get();
+        let height = image.size.height.get();
+        if pixel[0] >= width || pixel[1] >= height {
+            return None;
+        }
+        let columns = pixel[0].saturating_sub(radius)..(pixel[0] + radius + 1).min(width);
+        let rows = pixel[1].saturating_sub(radius)..(pixel[1] + radius + 1).min(height);
+        Self::rectangle(image, columns, rows)
+    }
+
+    /// Samples the pixels in `columns` and `rows`, which must be inside the image. Missing
+    /// pixels are skipped, returns `None` if no valid pixel is left.
+    pub(crate) fn rectangle(
+        image: &Image<f32>,
+        columns: Range<u32>,
+        rows: Range<u32>,
+    ) -> Option<Self> {
+        let samples: Vec<(f64, f64, f64)> = rows
+            .flat_map(|y| columns.clone().map(move |x| (x, y)))
+            .map(|(x, y)| (x as f64, y as f64, image.get_pixel(x, y) as f64))
+            .filter(|(_, _, z)| z.is_finite())
+            .collect();
+        if samples.is_empty() {
+            return None;
+        }
+
+        let n = samples.len() as f64;
+        let mean_of = |f: fn(&(f64, f64, f64)) -> f64| samples.iter().map(f).sum::<f64>() / n;
+        let (mean_x, mean_y, mean) = (mean_of(|s| s.0), mean_of(|s| s.1), mean_of(|s| s.2));
+        let variance = samples.iter().map(|s| (s.2 - mean).powi(2)).sum::<f64>() / n;
+
+        // Least-squares plane fit, the sample grid is symmetric unless clipped or holed
+        let (mut sxx, mut syy, mut sxy, mut sxz, mut syz) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (x, y, z) in &samples {
+            let (dx, dy, dz) = (x - mean_x, y - mean_y, z - mean);
+            sxx += dx * dx;
+            syy += dy * dy;
+            sxy += dx * dy;
+            sxz += dx * dz;
+            syz += dy * dz;
+        }
+        let determinant = sxx * syy - sxy * sxy;
+        let (gradient_x, gradient_y) = if determinant.abs() > f64::EPSILON {
+            (
+                (sxz * syy - syz * sxy) / determinant,
+                (syz * sxx - sxz * sxy) / determinant,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        let (min, max) = samples
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), s| {
+                (min.min(s.2), max.max(s.2))
+            });
+        Some(Self {
+            mean: mean as f32,
+            std: variance.sqrt() as f32,
+            min: min as f32,
+            max: max as f32,
+            gradient_x: gradient_x as f32,
+            gradient_y: gradient_y as f32,
+            count: samples.len() as u32,
+        })
+    }
+
+    /// Statistics of all valid pixels of `image`, the fitted plane is the overall tilt
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn whole_image(image: &Image<f32>) -> Option<Self> {
+        let (width, height) = (image.size.width.get(), image.size.height.get());
+        Self::sample(image, [width / 2, height / 2], 2 * width.max(height) + 1)
+    }
+
+    /// Converts all values from the data unit to the display unit
+    pub fn to_display(self, units: Units) -> Self {
+        Self {
+            mean: units.to_display(self.mean),
+            std: units.to_display(self.std),
+            min: units.to_display(self.min),
+            max: units.to_display(self.max),
+            gradient_x: units.to_display(self.gradient_x),
+            gradient_y: units.to_display(self.gradient_y),
+            count: self.count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    #[test]
+    fn stats_of_tilted_plane() {
+        let size = ImageSize::new(8, 8).unwrap();
+        let data = (0..64)
+            .map(|i| (i % 8) as f32 * 2.0 + (i / 8) as f32)
+            .collect();
+        let image = Image::new(size, data).unwrap();
+        let stats = NeighborhoodStats::sample(&image, [4, 4], 3).unwrap();
+        assert_eq!(stats.count, 9);
+        assert!((stats.mean - 12.0).abs() < 1e-5);
+        assert_eq!((stats.min, stats.max), (9.0, 15.0));
+        assert!((stats.gradient_x - 2.0).abs() < 1e-5);
+        assert!((stats.gradient_y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn skips_missing_pixels_and_clips_at_border() {
+        let size = ImageSize::new(4, 4).unwrap();
+        let mut data = vec![1.0; 16];
+        data[1] = f32::NAN;
+        let image = Image::new(size, data).unwrap();
+        let stats = NeighborhoodStats::sample(&image, [0, 0], 5).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.std, 0.0);
+        assert!(NeighborhoodStats::sample(&image, [4, 0], 5).is_none());
+    }
+}
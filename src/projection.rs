@@ -1,4 +1,4 @@
-use glam::{Mat4, Vec2, Vec4};
+use glam::{Mat4, Vec2, Vec3, Vec4};
 use wgpu::util::DeviceExt;
 
 pub struct Projection {
@@ -6,7 +6,16 @@ pub struct Projection {
     initial_delta: Vec2,
     current_delta: Vec2,
     zoom: f32,
+    /// Factor on the movement of a drag
+    pan_speed: f32,
     aspect_ratio: f32,
+    /// Center and half size of the area shown at zoom 1 without pan, see `fit`
+    fit_center: Vec2,
+    fit_extent: Vec2,
+    /// View space depth of the nearest and farthest point of the height field, see `fit_depth`
+    depth_range: (f32, f32),
+    /// Fraction of the depth range in front that is clipped away, see `set_near_clip`
+    near_clip: f32,
     pub bind_group: Option<wgpu::BindGroup>,
     buffer: Option<wgpu::Buffer>,
 }
@@ -18,26 +27,42 @@ impl Default for Projection {
 }
 
 impl Projection {
+    /// Depth of the near plane. The depth is reversed, 1 is near and 0 far, which together with
+    /// `CompareFunction::Greater` spreads the precision of the float depth buffer more evenly.
+    pub const NEAR_DEPTH: f32 = 1.0;
+    pub const FAR_DEPTH: f32 = 0.0;
+    /// Leaves a slice of the height field that can still be seen
+    pub const MAX_NEAR_CLIP: f32 = 0.95;
+    /// Closest zoom `frame` goes to, a fiftieth of the fitted view
+    pub const MIN_FRAME_ZOOM: f32 = 0.02;
+
     pub fn new() -> Self {
         Self {
             initial_position: Vec2::ZERO,
             initial_delta: Vec2::ZERO,
             current_delta: Vec2::ZERO,
             zoom: 1.0,
+            pan_speed: 1.0,
             aspect_ratio: 1.0,
+            fit_center: Vec2::ZERO,
+            // Large enough for the height field in any orientation
+            fit_extent: Vec2::splat(3.0_f32.sqrt()),
+            depth_range: (-3.0_f32.sqrt(), 3.0_f32.sqrt()),
+            near_clip: 0.0,
             bind_group: None,
             buffer: None,
         }
     }
 
+    /// Does nothing before `create_bind_group` was called
     pub fn update_gpu(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(
-            self.buffer
-                .as_ref()
-                .expect("Projection buffer not initialized"),
-            0,
-            bytemuck::cast_slice(&self.get_current().to_cols_array()),
-        );
+        if let Some(buffer) = &self.buffer {
+            queue.write_buffer(
+                buffer,
+                0,
+                bytemuck::cast_slice(&self.get_current().to_cols_array()),
+            );
+        }
     }
 
     pub fn reset(&mut self) {
@@ -53,7 +78,24 @@ impl Projection {
     }
 
     pub fn change_position(&mut self, position: Vec2) {
-        self.current_delta = position - self.initial_position + self.initial_delta;
+        self.current_delta =
+            (position - self.initial_position) * self.pan_speed + self.initial_delta;
+    }
+
+    pub fn set_pan_speed(&mut self, pan_speed: f32) {
+        self.pan_speed = pan_speed;
+    }
+
+    pub fn get_pan(&self) -> Vec2 {
+        self.current_delta
+    }
+
+    pub fn set_pan(&mut self, pan: Vec2) {
+        self.current_delta = pan;
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
     }
 
     pub fn zoom(&mut self, zoom_factor: f32) {
@@ -64,36 +106,68 @@ impl Projection {
         self.aspect_ratio = aspect_ratio;
     }
 
+    /// Shows the height field as oriented by `transformation` filling the viewport at zoom 1,
+    /// with a small margin. Other orientations may extend beyond the viewport.
+    pub fn fit(&mut self, transformation: Mat4) {
+        let (mut min, mut max) = (Vec2::INFINITY, Vec2::NEG_INFINITY);
+        for corner in height_field_corners(transformation) {
+            min = min.min(corner.truncate());
+            max = max.max(corner.truncate());
+        }
+        self.fit_center = (min + max) / 2.0;
+        self.fit_extent = ((max - min) / 2.0 * 1.05).max(Vec2::splat(1e-3));
+    }
+
+    /// Pans and zooms so that the view space rectangle from `min` to `max` fills about two
+    /// thirds of the viewport, without zooming out beyond the fitted view
+    pub fn frame(&mut self, min: Vec2, max: Vec2) {
+        self.current_delta = self.fit_center - (min + max) / 2.0;
+        self.zoom = ((max - min) / 2.0 * 1.5 / self.fit_extent)
+            .max_element()
+            .clamp(Self::MIN_FRAME_ZOOM, 1.0);
+    }
+
+    /// Fits the near and far plane tightly around the height field as rotated by
+    /// `transformation`, so that the depth buffer resolves nearly flat surfaces at high zoom
+    pub fn fit_depth(&mut self, transformation: Mat4) {
+        let (mut z_min, mut z_max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for corner in height_field_corners(transformation) {
+            z_min = z_min.min(corner.z);
+            z_max = z_max.max(corner.z);
+        }
+        // Keeps points on the box faces and markers slightly outside from being clipped
+        let pad = (z_max - z_min) * 0.01 + 1e-4;
+        self.depth_range = (z_min - pad, z_max + pad);
+    }
+
+    /// Moves the near plane into the height field by `fraction` of its depth, so that spikes
+    /// towering towards the camera are peeled away and the surface behind them shows
+    pub fn set_near_clip(&mut self, fraction: f32) {
+        self.near_clip = fraction.clamp(0.0, Self::MAX_NEAR_CLIP);
+    }
+
+    pub fn get_near_clip(&self) -> f32 {
+        self.near_clip
+    }
+
     pub fn get_current(&self) -> Mat4 {
-        let x_min = -self.zoom - self.current_delta.x;
-        let x_max = self.zoom - self.current_delta.x;
-        let y_min = -self.zoom - self.current_delta.y;
-        let y_max = self.zoom - self.current_delta.y;
-        let pad3d = 3.0_f32.sqrt();
-        let z_min = -pad3d;
-        let z_max = pad3d;
-
-        let mut dx = x_max - x_min;
-        let mut dy = y_max - y_min;
+        let center = self.fit_center - self.current_delta;
+        let (z_min, z_max) = self.depth_range;
+        let z_min = z_min + (z_max - z_min) * self.near_clip;
+
+        let mut dx = 2.0 * self.zoom * self.fit_extent.x;
+        let mut dy = 2.0 * self.zoom * self.fit_extent.y;
         let dz = z_max - z_min;
         if dx <= self.aspect_ratio * dy {
             dx = dy * self.aspect_ratio;
         } else {
             dy = dx / self.aspect_ratio;
         }
-        let pad_xy = 3.0_f32.sqrt();
-        dx *= pad_xy;
-        dy *= pad_xy;
         Mat4 {
             x_axis: Vec4::new(2.0 / dx, 0.0, 0.0, 0.0),
             y_axis: Vec4::new(0.0, 2.0 / dy, 0.0, 0.0),
-            z_axis: Vec4::new(0.0, 0.0, 1.0 / dz, 0.0),
-            w_axis: Vec4::new(
-                -(x_max + x_min) / dx,
-                -(y_max + y_min) / dy,
-                -z_min / dz,
-                1.0,
-            ),
+            z_axis: Vec4::new(0.0, 0.0, -1.0 / dz, 0.0),
+            w_axis: Vec4::new(-2.0 * center.x / dx, -2.0 * center.y / dy, z_max / dz, 1.0),
         }
     }
 
@@ -136,3 +210,75 @@ impl Projection {
         })
     }
 }
+
+/// Corners of the [-1, 1] x [-1, 1] x [0, 1] box of the height field in view space
+fn height_field_corners(transformation: Mat4) -> impl Iterator<Item = Vec3> {
+    (0..8).map(move |corner| {
+        transformation.transform_point3(Vec3::new(
+            if corner & 1 == 0 { -1.0 } else { 1.0 },
+            if corner & 2 == 0 { -1.0 } else { 1.0 },
+            if corner & 4 == 0 { 0.0 } else { 1.0 },
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn depth_is_reversed_and_fits_the_surface() {
+        let mut projection = Projection::new();
+        let rotation = Mat4::from_rotation_x(0.3);
+        projection.fit_depth(rotation);
+        let view_projection = projection.get_current() * rotation;
+        let depth = |point: Vec3| view_projection.project_point3(point).z;
+        // The top of the height field is at z = 0, in front of its bottom
+        assert!(depth(Vec3::new(0.0, 0.0, 0.0)) > depth(Vec3::new(0.0, 0.0, 1.0)));
+        // The nearest and farthest corner use almost the whole depth range
+        let near = depth(Vec3::new(-1.0, -1.0, 0.0));
+        let far = depth(Vec3::new(1.0, 1.0, 1.0));
+        assert!(near < Projection::NEAR_DEPTH && near > 0.98, "{}", near);
+        assert!(far > Projection::FAR_DEPTH && far < 0.02, "{}", far);
+    }
+
+    #[test]
+    fn near_clip_peels_the_front() {
+        let mut projection = Projection::new();
+        projection.fit_depth(Mat4::IDENTITY);
+        projection.set_near_clip(0.5);
+        let depth = |point: Vec3| projection.get_current().project_point3(point).z;
+        // Seen from above, the top of the height field at z = 0 is clipped and its bottom stays
+        assert!(depth(Vec3::new(0.0, 0.0, 0.1)) > Projection::NEAR_DEPTH);
+        assert!(depth(Vec3::new(0.0, 0.0, 0.9)) < Projection::NEAR_DEPTH);
+        projection.set_near_clip(2.0);
+        assert_eq!(projection.get_near_clip(), Projection::MAX_NEAR_CLIP);
+    }
+
+    #[test]
+    fn fitted_surface_fills_the_viewport() {
+        let mut projection = Projection::new();
+        projection.update_aspect_ratio(2.0);
+        projection.fit(Mat4::IDENTITY);
+        let corner = projection
+            .get_current()
+            .project_point3(Vec3::new(1.0, -1.0, 0.0));
+        // The height is limiting, the width is shown with the aspect ratio of the viewport
+        assert!((corner.y + 1.0 / 1.05).abs() < 1e-5, "{}", corner);
+        assert!((corner.x - 0.5 / 1.05).abs() < 1e-5, "{}", corner);
+    }
+
+    #[test]
+    fn framed_rectangle_is_centered_in_the_viewport() {
+        let mut projection = Projection::new();
+        projection.fit(Mat4::IDENTITY);
+        projection.frame(Vec2::new(0.2, 0.4), Vec2::new(0.6, 0.5));
+        let view = projection.get_current();
+        let center = view.project_point3(Vec3::new(0.4, 0.45, 0.5));
+        assert!(center.truncate().length() < 1e-5, "{}", center);
+        // The wider side spans two thirds of the viewport
+        let corner = view.project_point3(Vec3::new(0.6, 0.5, 0.5));
+        assert!((corner.x - 1.0 / 1.5).abs() < 1e-5, "{}", corner);
+        assert!(corner.y < corner.x);
+    }
+}
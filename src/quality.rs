@@ -0,0 +1,183 @@
+use web_time::{Duration, Instant};
+
+/// Mesh to draw while refining after motion
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Refinement {
+    /// Vertex step of the mesh
+    pub step: u32,
+    /// Vertex step of the coarser mesh the heights are faded from
+    pub from_step: u32,
+    /// 0 starts a new step on the heights of the coarser mesh, 1 shows the mesh as it is
+    pub fade: f32,
+}
+
+/// Draws a coarser mesh while the camera moves and frames take longer than a target, so that
+/// rotating stays smooth on weak hardware. Once the camera rests the mesh is refined a step at a
+/// time, every step faded in from the previous one, until the full mesh is drawn again.
+#[derive(Debug)]
+pub struct QualityScaler {
+    /// `None` always draws the full mesh
+    target: Option<Duration>,
+    /// Vertex step of the mesh learned while the camera moves, 1 is every pixel
+    step: u32,
+    /// Vertex step of the mesh drawn right now
+    shown: u32,
+    /// Smoothed time of the frames drawn with `step`
+    average: Option<Duration>,
+    last_motion: Option<Instant>,
+    /// Since when the mesh drawn right now fades in
+    fading: Option<Instant>,
+}
+
+impl Default for QualityScaler {
+    fn default() -> Self {
+        Self {
+            target: Some(Self::DEFAULT_TARGET),
+            step: 1,
+            shown: 1,
+            average: None,
+            last_motion: None,
+            fading: None,
+        }
+    }
+}
+
+impl QualityScaler {
+    pub const DEFAULT_TARGET: Duration = Duration::from_millis(16);
+    pub const MAX_STEP: u32 = 16;
+    /// Time without camera motion until the mesh is refined
+    pub const IDLE_DELAY: Duration = Duration::from_millis(300);
+    /// Time a refinement step fades in
+    pub const FADE_DURATION: Duration = Duration::from_millis(120);
+    /// Time between the frames of a fade
+    pub const FADE_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+    /// Frame time to hold while the camera moves, `None` turns the scaling off
+    pub fn set_target(&mut self, target: Option<Duration>) {
+        *self = Self {
+            target,
+            ..Self::default()
+        };
+    }
+
+    /// Records the time a frame took and returns the vertex step to draw the next frames with
+    /// if it changes
+    pub fn frame_rendered(
+        &mut self,
+        now: Instant,
+        frame_time: Duration,
+        camera_moved: bool,
+    ) -> Option<u32> {
+        let target = self.target?;
+        // Frames without motion, e.g. for hovering, don't need to be fast
+        if !camera_moved {
+            return None;
+        }
+        self.last_motion = Some(now);
+        if self.shown != self.step || self.fading.is_some() {
+            self.fading = None;
+            // Continue with the step learned during the previous motion
+            self.shown = self.step;
+            return Some(self.step);
+        }
+        let average = self.average.map_or(frame_time, |average| {
+            average.mul_f32(0.8) + frame_time.mul_f32(0.2)
+        });
+        self.average = Some(average);
+        // The gap between the limits keeps the step from alternating every frame
+        let step = if average > target.mul_f32(1.2) && self.step < Self::MAX_STEP {
+            self.step * 2
+        } else if average < target / 3 && self.step > 1 {
+            self.step / 2
+        } else {
+            return None;
+        };
+        self.step = step;
+        self.shown = step;
+        self.average = None;
+        Some(step)
+    }
+
+    /// When the mesh should be refined next, `None` if the full mesh is drawn already
+    pub fn refine_at(&self) -> Option<Instant> {
+        self.fading.or_else(|| {
+            self.last_motion
+                .filter(|_| self.shown > 1)
+                .map(|last_motion| last_motion + Self::IDLE_DELAY)
+        })
+    }
+
+    /// Continues fading in the mesh drawn right now, or starts the next finer one once it is
+    /// faded in completely
+    pub fn refine(&mut self, now: Instant) -> Refinement {
+        let start = match self.fading {
+            Some(start) => start,
+            None => {
+                self.shown = (self.shown / 2).max(1);
+                *self.fading.insert(now)
+            }
+        };
+        let fade = (now.saturating_duration_since(start).as_secs_f32()
+            / Self::FADE_DURATION.as_secs_f32())
+        .min(1.0);
+        if fade >= 1.0 {
+            self.fading = None;
+        }
+        Refinement {
+            step: self.shown,
+            from_step: self.shown * 2,
+            fade,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn coarsens_slow_frames_while_moving() {
+        let mut scaler = QualityScaler::default();
+        let now = Instant::now();
+        let slow = Duration::from_millis(40);
+        assert_eq!(scaler.frame_rendered(now, slow, false), None);
+        assert_eq!(scaler.frame_rendered(now, slow, true), Some(2));
+        assert_eq!(scaler.frame_rendered(now, slow, true), Some(4));
+        assert_eq!(
+            scaler.frame_rendered(now, Duration::from_millis(10), true),
+            None
+        );
+        assert_eq!(scaler.refine_at(), Some(now + QualityScaler::IDLE_DELAY));
+        // Every finer mesh fades in from the previous one
+        let idle = now + QualityScaler::IDLE_DELAY;
+        let refinement = |step, fade| Refinement {
+            step,
+            from_step: step * 2,
+            fade,
+        };
+        assert_eq!(scaler.refine(idle), refinement(2, 0.0));
+        assert_eq!(scaler.refine_at(), Some(idle));
+        let half = idle + QualityScaler::FADE_DURATION / 2;
+        assert!((scaler.refine(half).fade - 0.5).abs() < 1e-3);
+        let faded = idle + QualityScaler::FADE_DURATION;
+        assert_eq!(scaler.refine(faded), refinement(2, 1.0));
+        assert_eq!(scaler.refine(faded), refinement(1, 0.0));
+        assert_eq!(
+            scaler.refine(faded + QualityScaler::FADE_DURATION),
+            refinement(1, 1.0)
+        );
+        assert_eq!(scaler.refine_at(), None);
+        // The next motion starts with the coarse mesh right away
+        assert_eq!(
+            scaler.frame_rendered(now, Duration::from_millis(1), true),
+            Some(4)
+        );
+        // Fast frames refine the mesh once the smoothed time caught up with them
+        let steps: Vec<_> = (0..4)
+            .map(|_| scaler.frame_rendered(now, Duration::from_millis(1), true))
+            .collect();
+        assert_eq!(steps, [None, None, None, Some(2)]);
+        scaler.set_target(None);
+        assert_eq!(scaler.frame_rendered(now, slow, true), None);
+    }
+}
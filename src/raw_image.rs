@@ -0,0 +1,559 @@
+// Backends that already hold the heights in memory can send them in this format instead of a
+// TIFF, so that web builds without the TIFF decoder can show them. A 16 byte header is followed
+// by the samples, row by row from the top, little endian:
+//
+//   0  magic "DV3R"
+//   4  version, 1
+//   5  sample type, 0 for f32 heights and 1 for u16 amplitudes
+//   6  flags, 1 if the samples are compressed
+//   7  reserved, 0
+//   8  width as u32
+//  12  height as u32
+//
+// Surfaces can also be sent as tiles of whole rows, which the viewer uploads into the surface
+// texture as they arrive, so that large surfaces show up while they load. Tile messages can be
+// concatenated into one stream, their 28 byte header tells the length of their samples:
+//
+//   0  magic "DV3T"
+//   4  version, 1
+//   5  sample type, 0
+//   6  flags
+//   7  reserved, 0
+//   8  width of the surface as u32
+//  12  height of the surface as u32
+//  16  first row of the tile as u32
+//  20  rows of the tile as u32
+//  24  length of the samples in bytes as u32
+//
+// Compressed samples are an LZ4 block prepended with the decompressed length as u32.
+
+use crate::{
+    error::ViewerError,
+    image::{Image, ImageSize},
+};
+
+pub const MAGIC: [u8; 4] = *b"DV3R";
+pub const TILE_MAGIC: [u8; 4] = *b"DV3T";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+const TILE_HEADER_LEN: usize = 28;
+/// Flag of LZ4 compressed samples
+const LZ4: u8 = 1;
+
+/// Pixel type of a raw image
+pub trait RawSample: bytemuck::Pod + PartialOrd {
+    const TYPE: u8;
+    /// Name of the layer in errors
+    const LAYER: &'static str;
+    fn from_le(bytes: &[u8]) -> Self;
+    fn to_le(self) -> Vec<u8>;
+}
+
+impl RawSample for f32 {
+    const TYPE: u8 = 0;
+    const LAYER: &'static str = "surface";
+    fn from_le(bytes: &[u8]) -> Self {
+        f32::from_le_bytes(bytes.try_into().unwrap())
+    }
+    fn to_le(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl RawSample for u16 {
+    const TYPE: u8 = 1;
+    const LAYER: &'static str = "amplitude";
+    fn from_le(bytes: &[u8]) -> Self {
+        u16::from_le_bytes(bytes.try_into().unwrap())
+    }
+    fn to_le(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+/// Whether `bytes` start like a raw image, other data is read as TIFF
+pub fn is_raw(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Whether `bytes` start like a stream of tiles
+pub fn is_tile(bytes: &[u8]) -> bool {
+    bytes.starts_with(&TILE_MAGIC)
+}
+
+/// Width and height of a raw image or of the surface of a tile without decoding samples
+pub fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let header = bytes
+        .get(..HEADER_LEN)
+        .filter(|_| is_raw(bytes) || is_tile(bytes))?;
+    Some((u32_at(header, 8), u32_at(header, 12)))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Checks the version, sample type and flags of a header, returns the flags
+fn check_header<T: RawSample>(header: &[u8]) -> Result<u8, ViewerError> {
+    if header[4] != VERSION {
+        return Err(ViewerError::InvalidImage(format!(
+            "raw image version {} is not supported",
+            header[4]
+        )));
+    }
+    if header[5] != T::TYPE {
+        return Err(ViewerError::UnsupportedFormat(T::LAYER));
+    }
+    if header[6] & !LZ4 != 0 {
+        return Err(ViewerError::InvalidImage(format!(
+            "raw image flags {:#x} are not supported",
+            header[6]
+        )));
+    }
+    Ok(header[6])
+}
+
+/// Samples of `size`, decompressed if `flags` tell so
+fn samples<T: RawSample>(bytes: &[u8], flags: u8, size: &ImageSize) -> Result<Vec<T>, ViewerError> {
+    let sample_len = std::mem::size_of::<T>();
+    let expected = size.pixel_count().checked_mul(sample_len).ok_or_else(|| {
+        ViewerError::InvalidImage(format!(
+            "raw image of {}x{} pixels is too large",
+            size.width, size.height
+        ))
+    })?;
+    let decompressed;
+    let bytes = if flags & LZ4 != 0 {
+        decompressed = decompress(bytes, expected)?;
+        &decompressed[..]
+    } else {
+        bytes
+    };
+    if bytes.len() != expected {
+        return Err(ViewerError::InvalidImage(format!(
+            "raw image of {}x{} pixels has {} bytes of samples",
+            size.width,
+            size.height,
+            bytes.len()
+        )));
+    }
+    Ok(bytes.chunks_exact(sample_len).map(T::from_le).collect())
+}
+
+/// LZ4 block that has to decompress to `expected` bytes. The lengths are checked before
+/// anything is allocated, they come from the sender.
+fn decompress(bytes: &[u8], expected: usize) -> Result<Vec<u8>, ViewerError> {
+    let error = |e: lz4_flex::block::DecompressError| {
+        ViewerError::InvalidImage(format!("raw image samples: {}", e))
+    };
+    let (len, block) = lz4_flex::block::uncompressed_size(bytes).map_err(error)?;
+    if len != expected {
+        return Err(ViewerError::InvalidImage(format!(
+            "raw image samples decompress to {} bytes instead of {}",
+            len, expected
+        )));
+    }
+    // A byte of an LZ4 block expands to at most 255 bytes
+    if block.len().saturating_mul(255) < expected {
+        return Err(ViewerError::InvalidImage(format!(
+            "{} bytes of raw image samples can't hold {} bytes",
+            block.len(),
+            expected
+        )));
+    }
+    let mut decompressed = vec![0; expected];
+    if lz4_flex::block::decompress_into(block, &mut decompressed).map_err(error)? != expected {
+        return Err(ViewerError::InvalidImage(
+            "raw image samples are cut off".to_owned(),
+        ));
+    }
+    Ok(decompressed)
+}
+
+/// Samples to send, LZ4 compressed if `lz4`
+fn encode_samples<T: RawSample>(samples: &[T], lz4: bool) -> Vec<u8> {
+    let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le()).collect();
+    if lz4 {
+        lz4_flex::compress_prepend_size(&bytes)
+    } else {
+        bytes
+    }
+}
+
+pub fn decode<T: RawSample>(bytes: &[u8]) -> Result<Image<T>, ViewerError> {
+    if !is_raw(bytes) {
+        return Err(ViewerError::InvalidImage("not a raw image".to_owned()));
+    }
+    let (width, height) = dimensions(bytes)
+        .ok_or_else(|| ViewerError::InvalidImage("raw image header is cut off".to_owned()))?;
+    let flags = check_header::<T>(bytes)?;
+    let size = ImageSize::new(width, height)?;
+    let data = samples(&bytes[HEADER_LEN..], flags, &size)?;
+    Image::new(size, data)
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub fn encode<T: RawSample>(image: &Image<T>, lz4: bool) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + image.data.len() * std::mem::size_of::<T>());
+    bytes.extend(MAGIC);
+    bytes.extend([VERSION, T::TYPE, if lz4 { LZ4 } else { 0 }, 0]);
+    bytes.extend(image.size.width.get().to_le_bytes());
+    bytes.extend(image.size.height.get().to_le_bytes());
+    bytes.extend(encode_samples(&image.data, lz4));
+    bytes
+}
+
+/// Rows of a surface sent on their own
+pub struct SurfaceTile {
+    pub surface_size: ImageSize,
+    pub first_row: u32,
+    /// As wide as the surface
+    pub rows: Image<f32>,
+}
+
+impl SurfaceTile {
+    /// Length of the first tile message in `bytes`, `None` until its header is complete
+    fn message_len(bytes: &[u8]) -> Option<usize> {
+        let header = bytes.get(..TILE_HEADER_LEN)?;
+        Some(TILE_HEADER_LEN + u32_at(header, 24) as usize)
+    }
+
+    /// Reads one complete tile message
+    pub fn decode(bytes: &[u8]) -> Result<Self, ViewerError> {
+        if !is_tile(bytes) {
+            return Err(ViewerError::InvalidImage("not a surface tile".to_owned()));
+        }
+        if Self::message_len(bytes) != Some(bytes.len()) {
+            return Err(ViewerError::InvalidImage(
+                "surface tile is cut off".to_owned(),
+            ));
+        }
+        let flags = check_header::<f32>(bytes)?;
+        let surface_size = ImageSize::new(u32_at(bytes, 8), u32_at(bytes, 12))?;
+        let (first_row, row_count) = (u32_at(bytes, 16), u32_at(bytes, 20));
+        if first_row
+            .checked_add(row_count)
+            .is_none_or(|end| end > surface_size.height.get())
+        {
+            return Err(ViewerError::InvalidImage(format!(
+                "rows {} to {} of a tile are outside the surface of {} rows",
+                first_row,
+                first_row.saturating_add(row_count),
+                surface_size.height
+            )));
+        }
+        let size = ImageSize::new(surface_size.width.get(), row_count)?;
+        let data = samples(&bytes[TILE_HEADER_LEN..], flags, &size)?;
+        Ok(Self {
+            surface_size,
+            first_row,
+            rows: Image::new(size, data)?,
+        })
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn encode(&self, lz4: bool) -> Vec<u8> {
+        let samples = encode_samples(&self.rows.data, lz4);
+        let mut bytes = Vec::with_capacity(TILE_HEADER_LEN + samples.len());
+        bytes.extend(TILE_MAGIC);
+        bytes.extend([VERSION, f32::TYPE, if lz4 { LZ4 } else { 0 }, 0]);
+        for value in [
+            self.surface_size.width.get(),
+            self.surface_size.height.get(),
+            self.first_row,
+            self.rows.size.height.get(),
+            samples.len() as u32,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(samples);
+        bytes
+    }
+
+    /// Splits a surface into tiles of up to `rows` rows
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn split(surface: &Image<f32>, rows: u32) -> Vec<Self> {
+        let width = surface.size.width.get() as usize;
+        let height = surface.size.height.get();
+        (0..height)
+            .step_by(rows.max(1) as usize)
+            .map(|first_row| {
+                let count = rows.max(1).min(height - first_row);
+                let start = first_row as usize * width;
+                Self {
+                    surface_size: surface.size.clone(),
+                    first_row,
+                    rows: Image::new(
+                        ImageSize::new(width as u32, count).unwrap(),
+                        surface.data[start..start + count as usize * width].to_vec(),
+                    )
+                    .unwrap(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Splits a stream of tile messages arriving in chunks of any length into tiles
+#[derive(Default)]
+pub struct TileReader {
+    buffer: Vec<u8>,
+}
+
+impl TileReader {
+    /// Tiles completed by `chunk`
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<SurfaceTile>, ViewerError> {
+        self.buffer.extend_from_slice(chunk);
+        let mut tiles = Vec::new();
+        let mut start = 0;
+        while self.buffer.len() - start >= TILE_MAGIC.len() {
+            let rest = &self.buffer[start..];
+            if !is_tile(rest) {
+                return Err(ViewerError::InvalidImage(
+                    "stream holds something else than surface tiles".to_owned(),
+                ));
+            }
+            match SurfaceTile::message_len(rest) {
+                Some(len) if rest.len() >= len => {
+                    tiles.push(SurfaceTile::decode(&rest[..len])?);
+                    start += len;
+                }
+                _ => break,
+            }
+        }
+        self.buffer.drain(..start);
+        Ok(tiles)
+    }
+
+    /// Whether the stream ended between two messages
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+/// Surface put together from tiles arriving in any order
+pub struct TileAssembly {
+    size: ImageSize,
+    data: Vec<f32>,
+    received: Vec<bool>,
+    missing: u32,
+}
+
+impl TileAssembly {
+    /// Empty surface of `size`, rows not received yet are missing data
+    pub fn new(size: ImageSize) -> Self {
+        Self {
+            data: vec![f32::NAN; size.pixel_count()],
+            received: vec![false; size.height.get() as usize],
+            missing: size.height.get(),
+            size,
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn size(&self) -> &ImageSize {
+        &self.size
+    }
+
+    pub fn insert(&mut self, tile: &SurfaceTile) -> Result<(), ViewerError> {
+        if tile.surface_size != self.size {
+            return Err(ViewerError::InvalidImage(format!(
+                "tile of a surface of {}x{} pixels does not fit a surface of {}x{} pixels",
+                tile.surface_size.width,
+                tile.surface_size.height,
+                self.size.width,
+                self.size.height
+            )));
+        }
+        let width = self.size.width.get() as usize;
+        let start = tile.first_row as usize * width;
+        self.data[start..start + tile.rows.data.len()].copy_from_slice(&tile.rows.data);
+        let rows = tile.first_row as usize..(tile.first_row + tile.rows.size.height.get()) as usize;
+        for received in &mut self.received[rows] {
+            if !*received {
+                *received = true;
+                self.missing -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing == 0
+    }
+
+    /// Surface with the rows received so far
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn snapshot(&self) -> Image<f32> {
+        Image::new(self.size.clone(), self.data.clone()).unwrap()
+    }
+
+    pub fn into_image(self) -> Image<f32> {
+        Image::new(self.size, self.data).unwrap()
+    }
+}
+
+/// Surface of a complete stream of tiles, e.g. one saved to a file
+pub fn assemble(bytes: &[u8]) -> Result<Image<f32>, ViewerError> {
+    let mut reader = TileReader::default();
+    let tiles = reader.push(bytes)?;
+    if !reader.is_empty() {
+        return Err(ViewerError::InvalidImage(
+            "last surface tile is cut off".to_owned(),
+        ));
+    }
+    let first = tiles
+        .first()
+        .ok_or_else(|| ViewerError::InvalidImage("no surface tiles".to_owned()))?;
+    let mut assembly = TileAssembly::new(first.surface_size.clone());
+    for tile in &tiles {
+        assembly.insert(tile)?;
+    }
+    if !assembly.is_complete() {
+        return Err(ViewerError::InvalidImage(format!(
+            "{} rows of the surface are missing",
+            assembly.missing
+        )));
+    }
+    Ok(assembly.into_image())
+}
+
+/// Converts the surface of a TIFF file for a backend to serve, the arguments after `raw` are
+/// `<file> <out> [--lz4] [--tile-rows <rows>]`. With `--tile-rows` the output is a stream of
+/// tiles.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let usage = || anyhow::anyhow!("Usage: raw <file> <out> [--lz4] [--tile-rows <rows>]");
+    let mut paths = Vec::new();
+    let mut lz4 = false;
+    let mut tile_rows = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lz4" => lz4 = true,
+            "--tile-rows" => tile_rows = Some(args.next().ok_or_else(usage)?.parse::<u32>()?),
+            _ => paths.push(arg),
+        }
+    }
+    let [file, out] = <[String; 2]>::try_from(paths).map_err(|_| usage())?;
+    let surface = crate::image::SurfaceAmplitudeImage::from_file(&file)?.surface;
+    let bytes = match tile_rows {
+        Some(rows) => SurfaceTile::split(&surface, rows)
+            .iter()
+            .flat_map(|tile| tile.encode(lz4))
+            .collect(),
+        None => encode(&surface, lz4),
+    };
+    std::fs::write(&out, bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn raw_images_round_trip() {
+        let surface = Image::new(
+            ImageSize::new(3, 2).unwrap(),
+            vec![0.0, 1.5, f32::NAN, -2.0, 4.0, 5.0],
+        )
+        .unwrap();
+        let bytes = encode(&surface, false);
+        assert_eq!(bytes.len(), 16 + 6 * 4);
+        assert_eq!(dimensions(&bytes), Some((3, 2)));
+        let decoded = decode::<f32>(&bytes).unwrap();
+        assert_eq!(decoded.size, surface.size);
+        assert_eq!(decoded.data[..2], [0.0, 1.5]);
+        assert!(decoded.data[2].is_nan());
+
+        let amplitude = Image::new(ImageSize::new(2, 1).unwrap(), vec![7u16, 65535]).unwrap();
+        assert_eq!(
+            decode::<u16>(&encode(&amplitude, false)).unwrap().data[..],
+            [7, 65535]
+        );
+    }
+
+    #[test]
+    fn compressed_raw_images_round_trip() {
+        let surface = Image::new(ImageSize::new(64, 64).unwrap(), vec![1.25f32; 64 * 64]).unwrap();
+        let bytes = encode(&surface, true);
+        assert!(bytes.len() < 64 * 64);
+        assert_eq!(decode::<f32>(&bytes).unwrap().data[..], surface.data[..]);
+    }
+
+    #[test]
+    fn malformed_raw_images_are_rejected() {
+        let surface = Image::new(ImageSize::new(2, 2).unwrap(), vec![1.0f32; 4]).unwrap();
+        let bytes = encode(&surface, false);
+        assert!(decode::<f32>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode::<f32>(&bytes[..10]).is_err());
+        // Heights aren't read as amplitudes
+        assert!(decode::<u16>(&bytes).is_err());
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(decode::<f32>(&future).is_err());
+        let mut unknown_flag = bytes.clone();
+        unknown_flag[6] = 2;
+        assert!(decode::<f32>(&unknown_flag).is_err());
+    }
+
+    #[test]
+    fn tiles_arriving_in_chunks_assemble_the_surface() {
+        let surface = Image::new(
+            ImageSize::new(3, 5).unwrap(),
+            (0..15).map(|value| value as f32).collect(),
+        )
+        .unwrap();
+        let tiles = SurfaceTile::split(&surface, 2);
+        assert_eq!(
+            tiles.iter().map(|tile| tile.first_row).collect::<Vec<_>>(),
+            [0, 2, 4]
+        );
+        let stream: Vec<u8> = tiles
+            .iter()
+            .rev()
+            .enumerate()
+            .flat_map(|(index, tile)| tile.encode(index % 2 == 0))
+            .collect();
+        assert_eq!(dimensions(&stream), Some((3, 5)));
+
+        let mut reader = TileReader::default();
+        let mut assembly = TileAssembly::new(surface.size.clone());
+        for chunk in stream.chunks(7) {
+            for tile in reader.push(chunk).unwrap() {
+                assert!(!assembly.is_complete());
+                assembly.insert(&tile).unwrap();
+            }
+        }
+        assert!(reader.is_empty() && assembly.is_complete());
+        assert_eq!(assembly.into_image().data[..], surface.data[..]);
+        assert_eq!(assemble(&stream).unwrap().data[..], surface.data[..]);
+        assert!(assemble(&stream[..stream.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn tiles_outside_the_surface_are_rejected() {
+        let surface = Image::new(ImageSize::new(2, 2).unwrap(), vec![1.0f32; 4]).unwrap();
+        let mut bytes = SurfaceTile::split(&surface, 2)[0].encode(false);
+        bytes[16] = 1;
+        assert!(SurfaceTile::decode(&bytes).is_err());
+        assert!(TileReader::default().push(b"II*\0").is_err());
+    }
+
+    #[test]
+    fn compressed_lengths_are_checked_before_decompressing() {
+        let surface = Image::new(ImageSize::new(2, 2).unwrap(), vec![1.0f32; 4]).unwrap();
+        let mut bytes = encode(&surface, true);
+        // The prepended length no longer matches the 16 bytes of 2x2 samples
+        bytes[HEADER_LEN..HEADER_LEN + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode::<f32>(&bytes).is_err());
+
+        // A tile of 16384x16384 pixels, claiming its 1 GiB of samples in a few bytes
+        let mut tile = SurfaceTile::split(&surface, 2)[0].encode(true);
+        for (offset, value) in [(8, 16384u32), (12, 16384), (20, 16384)] {
+            tile[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        tile[TILE_HEADER_LEN..TILE_HEADER_LEN + 4].copy_from_slice(&(1u32 << 30).to_le_bytes());
+        assert!(SurfaceTile::decode(&tile).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    coordinates::{nearest_pixel, world_z},
+    image::{Image, ZValueRange},
+    projection::Projection,
+};
+
+/// How the pixel under the mouse is determined
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PickingMethod {
+    /// Read the picking render target back from the GPU
+    Readback,
+    /// Intersect the mouse ray with the height field on the CPU
+    RayCast,
+}
+
+impl PickingMethod {
+    /// Texture readback stalls for several frames on WebGL-class backends,
+    /// ray casting keeps the hover readout responsive there
+    pub fn for_backend(backend: wgpu::Backend) -> Self {
+        match backend {
+            wgpu::Backend::Gl => Self::RayCast,
+            _ => Self::Readback,
+        }
+    }
+}
+
+/// Upper bound for the number of samples taken along a single ray
+const MAX_STEPS: usize = 100_000;
+
+/// Finds the pixel of the height field that is visible at the normalized device coordinates `ndc`
+/// by marching along the view ray. `model_view_projection` has to match the matrix used by the
+/// vertex shader. Returns the pixel coordinates and its value.
+pub fn ray_cast(
+    image: &Image<f32>,
+    z_range: &ZValueRange<f32>,
+    model_view_projection: Mat4,
+    ndc: Vec2,
+) -> Option<(u32, u32, f32)> {
+    // Rays are traced in world space, see `ViewTransform`
+    let inverse = model_view_projection.inverse();
+    let near = inverse.project_point3(Vec3::new(ndc.x, ndc.y, Projection::NEAR_DEPTH));
+    let far = inverse.project_point3(Vec3::new(ndc.x, ndc.y, Projection::FAR_DEPTH));
+    let direction = far - near;
+
+    // The height field occupies [-1, 1] x [-1, 1] x [0, 1]
+    let (t_enter, t_exit) = intersect_box(
+        near,
+        direction,
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(1.0, 1.0, 1.0),
+    )?;
+    // Parts in front of the near plane are clipped, see `Projection::set_near_clip`
+    let t_enter = t_enter.max(0.0);
+    if t_exit < t_enter {
+        return None;
+    }
+
+    let width = image.size.width.get();
+    let height = image.size.height.get();
+    // Sample twice per pixel so narrow peaks are not skipped
+    let sample_distance = 1.0 / width.max(height).saturating_sub(1).max(1) as f32;
+    let ray_length = (t_exit - t_enter) * direction.length();
+    let steps = ((ray_length / sample_distance).ceil() as usize).clamp(1, MAX_STEPS);
+
+    let mut previous_distance: Option<f32> = None;
+    for step in 0..=steps {
+        let t = t_enter + (t_exit - t_enter) * step as f32 / steps as f32;
+        let point = near + direction * t;
+        let (col, row) = nearest_pixel(point, width, height);
+        let value = image.get_pixel(col, row);
+        if !value.is_finite() {
+            previous_distance = None;
+            continue;
+        }
+        let distance = point.z - world_z(value, z_range);
+        if let Some(previous) = previous_distance
+            && (distance == 0.0 || previous.signum() != distance.signum())
+        {
+            return Some((col, row, value));
+        }
+        previous_distance = Some(distance);
+    }
+    None
+}
+
+/// Ray parameters where the ray enters and leaves an axis aligned box
+fn intersect_box(origin: Vec3, direction: Vec3, min: Vec3, max: Vec3) -> Option<(f32, f32)> {
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    for axis in 0..3 {
+        if direction[axis].abs() < f32::EPSILON {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (min[axis] - origin[axis]) / direction[axis];
+        let t1 = (max[axis] - origin[axis]) / direction[axis];
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+    }
+    (t_enter <= t_exit).then_some((t_enter, t_exit))
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use super::*;
+    use crate::image::{ImageSize, value_range};
+
+    fn image(data: Vec<f32>, width: u32, height: u32) -> Image<f32> {
+        Image {
+            size: ImageSize {
+                width: NonZeroU32::new(width).unwrap(),
+                height: NonZeroU32::new(height).unwrap(),
+            },
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn ray_hits_pixel_under_cursor() {
+        let image = image((0..25).map(|v| v as f32).collect(), 5, 5);
+        let z_range = value_range(&image.data).unwrap();
+        let hit = ray_cast(&image, &z_range, Mat4::IDENTITY, Vec2::new(0.5, 0.5));
+        assert_eq!(hit, Some((3, 1, 8.0)));
+    }
+
+    #[test]
+    fn ray_through_missing_data_finds_nothing() {
+        let image = image(vec![f32::NAN; 9], 3, 3);
+        let z_range = value_range(&[0.0, 1.0]).unwrap();
+        let hit = ray_cast(&image, &z_range, Mat4::IDENTITY, Vec2::ZERO);
+        assert_eq!(hit, None);
+    }
+}
@@ -0,0 +1,320 @@
+// Stages drift while a sequence is measured, so the same feature lands on other pixels and at
+// another height in every frame. Registration aligns every frame with the first one before it is
+// shown, compared with a baseline or added to the temporal statistics: phase correlation of a
+// window in the middle of the frames finds the XY shift, the median height difference the Z
+// offset.
+
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use std::sync::Arc;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    image::{Image, ImageSize},
+    parallel,
+};
+
+/// Edge length of the largest correlation window, drifts up to half of it are found
+const MAX_WINDOW: u32 = 512;
+/// Frames without room for a window of this size aren't registered
+const MIN_WINDOW: u32 = 16;
+
+/// Displacement of a frame from the first frame of the sequence
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameShift {
+    /// Pixels to the right
+    pub x: f32,
+    /// Pixels down
+    pub y: f32,
+    /// Height offset in the data unit, in the display unit from `frame_shifts`
+    pub z: f32,
+}
+
+/// Square window in the middle of the frames, with a power of two edge for the FFT
+#[derive(Clone, Copy)]
+struct Window {
+    x: u32,
+    y: u32,
+    size: u32,
+}
+
+impl Window {
+    fn new(image_size: &ImageSize) -> Option<Self> {
+        let (width, height) = (image_size.width.get(), image_size.height.get());
+        let size = 1 << width.min(height).min(MAX_WINDOW).ilog2();
+        (size >= MIN_WINDOW).then_some(Self {
+            x: (width - size) / 2,
+            y: (height - size) / 2,
+            size,
+        })
+    }
+
+    /// Values in the window of an image with rows of `width` values
+    fn values(&self, data: &[f32], width: u32) -> Vec<f32> {
+        (self.y..self.y + self.size)
+            .flat_map(|y| {
+                let start = (y * width + self.x) as usize;
+                data[start..start + self.size as usize].iter().copied()
+            })
+            .collect()
+    }
+}
+
+/// The first frame, which every other frame is aligned with
+struct Anchor {
+    image_size: ImageSize,
+    window: Window,
+    heights: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+}
+
+/// Aligns the frames of a sequence with its first frame
+#[derive(Default)]
+pub struct Registration {
+    anchor: Option<Anchor>,
+    shifts: Vec<FrameShift>,
+}
+
+impl Registration {
+    /// Shifts of the frames registered so far, zero for the first one
+    #[cfg(target_arch = "wasm32")]
+    pub fn shifts(&self) -> &[FrameShift] {
+        &self.shifts
+    }
+
+    /// Starts a new sequence with `first` as the frame the others are aligned with
+    pub fn start(&mut self, first: &Image<f32>) {
+        self.shifts = vec![FrameShift::default()];
+        self.anchor = Window::new(&first.size).map(|window| {
+            let heights = window.values(&first.data, first.size.width.get());
+            Anchor {
+                image_size: first.size.clone(),
+                spectrum: spectrum(&heights, window.size as usize, &mut FftPlanner::new()),
+                window,
+                heights,
+            }
+        });
+    }
+
+    /// Moves `image` onto the first frame and returns it with its estimated shift. The first
+    /// frame and frames of another size start a new sequence and are returned as they are.
+    pub fn register(&mut self, image: Image<f32>) -> (Image<f32>, FrameShift) {
+        let anchor = match &self.anchor {
+            Some(anchor) if anchor.image_size == image.size => anchor,
+            _ => {
+                if !self.shifts.is_empty() {
+                    log::info!("Frame size changed, registering with the new frame");
+                }
+                self.start(&image);
+                return (image, FrameShift::default());
+            }
+        };
+        let mut planner = FftPlanner::new();
+        let width = image.size.width.get();
+        let size = anchor.window.size as usize;
+        let frame = spectrum(
+            &anchor.window.values(&image.data, width),
+            size,
+            &mut planner,
+        );
+        let (x, y) = correlate(&anchor.spectrum, frame, size, &mut planner);
+        let mut data = shift_back(&image, x, y);
+
+        let mut differences: Vec<f32> = anchor
+            .window
+            .values(&data, width)
+            .iter()
+            .zip(&anchor.heights)
+            .map(|(height, anchor)| height - anchor)
+            .filter(|difference| difference.is_finite())
+            .collect();
+        let z = if differences.is_empty() {
+            0.0
+        } else {
+            let middle = differences.len() / 2;
+            *differences.select_nth_unstable_by(middle, f32::total_cmp).1
+        };
+        data.iter_mut().for_each(|height| *height -= z);
+
+        let shift = FrameShift { x, y, z };
+        self.shifts.push(shift);
+        let registered = Image {
+            size: image.size,
+            data: data.into(),
+        };
+        (registered, shift)
+    }
+}
+
+/// Spectrum of the window with missing pixels set to the mean and a Hann taper, so that the
+/// edges of the window don't correlate more than the surface
+fn spectrum(heights: &[f32], size: usize, planner: &mut FftPlanner<f32>) -> Vec<Complex<f32>> {
+    let (sum, count) = heights
+        .iter()
+        .filter(|height| height.is_finite())
+        .fold((0.0, 0), |(sum, count), height| (sum + height, count + 1));
+    let mean = if count > 0 { sum / count as f32 } else { 0.0 };
+    let hann: Vec<f32> = (0..size)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / size as f32).cos())
+        .collect();
+    let mut data: Vec<Complex<f32>> = heights
+        .iter()
+        .enumerate()
+        .map(|(i, height)| {
+            let height = if height.is_finite() {
+                height - mean
+            } else {
+                0.0
+            };
+            Complex::new(height * hann[i % size] * hann[i / size], 0.0)
+        })
+        .collect();
+    fft_2d(&mut data, size, planner.plan_fft_forward(size));
+    data
+}
+
+/// Transforms the rows and then the columns of a square
+fn fft_2d(data: &mut [Complex<f32>], size: usize, fft: Arc<dyn Fft<f32>>) {
+    for _ in 0..2 {
+        fft.process(data);
+        for y in 0..size {
+            for x in y + 1..size {
+                data.swap(y * size + x, x * size + y);
+            }
+        }
+    }
+}
+
+/// Shift of the frame from the anchor at the peak of their phase correlation, to a fraction of a
+/// pixel with a parabola through the peak and its neighbors
+fn correlate(
+    anchor: &[Complex<f32>],
+    mut frame: Vec<Complex<f32>>,
+    size: usize,
+    planner: &mut FftPlanner<f32>,
+) -> (f32, f32) {
+    for (frame, anchor) in frame.iter_mut().zip(anchor) {
+        *frame *= anchor.conj();
+    }
+    // Frequencies without energy have random phases, which would drown the peak
+    let floor = frame.iter().map(|cross| cross.norm()).fold(0.0, f32::max) * 1e-6;
+    for cross in frame.iter_mut() {
+        let norm = cross.norm();
+        *cross = if norm > floor {
+            *cross / norm
+        } else {
+            Complex::default()
+        };
+    }
+    fft_2d(&mut frame, size, planner.plan_fft_inverse(size));
+    let peak = frame
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.re.total_cmp(&b.re))
+        .map_or(0, |(i, _)| i);
+    let (px, py) = (peak % size, peak / size);
+    let at = |x: usize, y: usize| frame[(y % size) * size + x % size].re;
+    let refine = |left: f32, center: f32, right: f32| {
+        let curvature = left - 2.0 * center + right;
+        if curvature < 0.0 {
+            (0.5 * (left - right) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+    let x = px as f32 + refine(at(px + size - 1, py), at(px, py), at(px + 1, py));
+    let y = py as f32 + refine(at(px, py + size - 1), at(px, py), at(px, py + 1));
+    // Peaks in the second half are shifts to the left or up
+    let wrap = |offset: f32| {
+        if offset > size as f32 / 2.0 {
+            offset - size as f32
+        } else {
+            offset
+        }
+    };
+    (wrap(x), wrap(y))
+}
+
+/// Heights of the image moved back by `x`, `y` pixels with bilinear interpolation, pixels moved
+/// in from outside are missing
+fn shift_back(image: &Image<f32>, x: f32, y: f32) -> Vec<f32> {
+    let (width, height) = (image.size.width.get(), image.size.height.get());
+    let sample = |sx: f32, sy: f32| {
+        let (x0, y0) = (sx.floor(), sy.floor());
+        let (fx, fy) = (sx - x0, sy - y0);
+        let mut value = 0.0;
+        for (px, py, weight) in [
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x0 + 1.0, y0, fx * (1.0 - fy)),
+            (x0, y0 + 1.0, (1.0 - fx) * fy),
+            (x0 + 1.0, y0 + 1.0, fx * fy),
+        ] {
+            if weight == 0.0 {
+                continue;
+            }
+            if px < 0.0 || py < 0.0 || px >= width as f32 || py >= height as f32 {
+                return f32::NAN;
+            }
+            value += weight * image.get_pixel(px as u32, py as u32);
+        }
+        value
+    };
+    let mut data = vec![f32::NAN; image.data.len()];
+    parallel::for_each_chunk_mut(&mut data, width as usize, |row_index, row| {
+        for (column, value) in row.iter_mut().enumerate() {
+            *value = sample(column as f32 + x, row_index as f32 + y);
+        }
+    });
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::num::NonZeroU32;
+
+    /// Waviness with a bump and roughness, moved by whole pixels
+    fn surface(size: u32, dx: i32, dy: i32, dz: f32) -> Image<f32> {
+        let height = |x: i32, y: i32| {
+            let (fx, fy) = (x as f32, y as f32);
+            let roughness = ((x * 7919 + y * 104729) as u32).wrapping_mul(2654435761) >> 16;
+            2.0 * (fx * 0.11 + fy * 0.07).sin()
+                + 3.0 * (-((fx - 20.0).powi(2) + (fy - 30.0).powi(2)) / 50.0).exp()
+                + roughness as f32 / 65536.0 * 0.2
+        };
+        let data = (0..size as i32 * size as i32)
+            .map(|i| height(i % size as i32 - dx, i / size as i32 - dy) + dz)
+            .collect();
+        Image::new(
+            ImageSize {
+                width: NonZeroU32::new(size).unwrap(),
+                height: NonZeroU32::new(size).unwrap(),
+            },
+            data,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn drifted_frames_are_moved_back() {
+        let mut registration = Registration::default();
+        let (first, shift) = registration.register(surface(64, 0, 0, 0.0));
+        assert_eq!(shift, FrameShift::default());
+
+        let (registered, shift) = registration.register(surface(64, 3, -2, 0.5));
+        assert!((shift.x - 3.0).abs() < 0.1, "{:?}", shift);
+        assert!((shift.y + 2.0).abs() < 0.1, "{:?}", shift);
+        assert!((shift.z - 0.5).abs() < 0.05, "{:?}", shift);
+        // The rows and columns moved in from outside are missing
+        assert!(registered.get_pixel(63, 10).is_nan());
+        assert!(registered.get_pixel(10, 0).is_nan());
+        assert!((registered.get_pixel(32, 32) - first.get_pixel(32, 32)).abs() < 0.05);
+
+        // Another size starts a new sequence with that frame
+        let (_, shift) = registration.register(surface(32, 3, 0, 0.0));
+        assert_eq!(shift, FrameShift::default());
+        let (_, shift) = registration.register(surface(32, 5, 0, 0.0));
+        assert!((shift.x - 2.0).abs() < 0.1, "{:?}", shift);
+    }
+}
@@ -0,0 +1,146 @@
+/// Display options that are passed to the shaders as a uniform
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct RenderSettings {
+    /// Discard triangles touching invalid (non-finite) pixels instead of stretching over them
+    pub cut_holes: u32,
+    /// Scale of HUD elements like markers
+    pub ui_scale: f32,
+    /// Size of the render target in physical pixels
+    pub viewport: [f32; 2],
+    /// See `Colormap::shader_index`
+    pub colormap: u32,
+    /// Colormap of the amplitude shader, chosen independently of the height colormap
+    pub amplitude_colormap: u32,
+    /// Lower and upper limit of the residual tolerance band
+    pub tolerance: [f32; 2],
+    /// Columns of the color vision deficiency simulation followed by the conversion to the
+    /// output color space, padded like a WGSL `mat3x3`
+    pub color_matrix: [[f32; 4]; 3],
+    /// Amplitudes at the lower and upper end of the amplitude colormap
+    pub amplitude_range: [f32; 2],
+    /// Heights at the lower and upper end of the height colormap
+    pub height_range: [f32; 2],
+    /// Object ID of the highlighted overlay or marker, see `Selection::to_id`, 0 for none
+    pub selected_id: u32,
+    /// 0 hides the overlays and their object IDs
+    pub show_overlays: u32,
+    /// 1 colors the heights at or above `threshold` and below it differently
+    pub show_threshold: u32,
+    pub threshold: f32,
+    /// See `Compositing::shader_index`
+    pub layer_blend: u32,
+    pub blend_weight: f32,
+    /// 1 dims everything outside the selected overlay
+    pub dim_outside_selection: u32,
+    /// Tile size in pixels of the checkerboard comparison with the reference, 0 for none
+    pub checker_size: u32,
+    /// Vertex step of the coarser mesh the heights fade from while refining, 0 for none
+    pub morph_step: u32,
+    /// From 0, the heights of the coarser mesh, to 1, the heights of the pixels
+    pub morph: f32,
+    _padding: [u32; 2],
+}
+
+impl RenderSettings {
+    pub(crate) fn set_color_matrix(&mut self, matrix: glam::Mat3) {
+        self.color_matrix = [
+            matrix.x_axis.extend(0.0).to_array(),
+            matrix.y_axis.extend(0.0).to_array(),
+            matrix.z_axis.extend(0.0).to_array(),
+        ];
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            cut_holes: 1,
+            ui_scale: 1.0,
+            viewport: [1.0, 1.0],
+            colormap: 0,
+            amplitude_colormap: 0,
+            tolerance: [-1.0, 1.0],
+            color_matrix: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+            ],
+            amplitude_range: [0.0, 4000.0],
+            height_range: [0.0, 1.0],
+            selected_id: 0,
+            show_overlays: 1,
+            show_threshold: 0,
+            threshold: 0.0,
+            layer_blend: 0,
+            blend_weight: 0.5,
+            dim_outside_selection: 0,
+            checker_size: 0,
+            morph_step: 0,
+            morph: 1.0,
+            _padding: [0; 2],
+        }
+    }
+}
+
+impl RenderSettings {
+    pub(crate) fn create_buffer(device: &wgpu::Device) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("render_settings_buffer"),
+            size: std::mem::size_of::<RenderSettings>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub(crate) fn write_buffer(&self, queue: &wgpu::Queue, buffer: &wgpu::Buffer) {
+        queue.write_buffer(buffer, 0, bytemuck::bytes_of(self));
+    }
+
+    pub fn get_bind_group_entry(buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: 3,
+            resource: buffer.as_entire_binding(),
+        }
+    }
+
+    pub fn get_bind_group_layout_entry() -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout_matches_shader() {
+        // WGSL aligns the mat3x3 to 16 bytes and pads each of its columns to 16 bytes
+        assert_eq!(std::mem::size_of::<RenderSettings>(), 144);
+        assert_eq!(std::mem::offset_of!(RenderSettings, tolerance), 24);
+        assert_eq!(std::mem::offset_of!(RenderSettings, color_matrix), 32);
+        assert_eq!(std::mem::offset_of!(RenderSettings, amplitude_range), 80);
+        assert_eq!(std::mem::offset_of!(RenderSettings, height_range), 88);
+        assert_eq!(std::mem::offset_of!(RenderSettings, selected_id), 96);
+        assert_eq!(std::mem::offset_of!(RenderSettings, show_overlays), 100);
+        assert_eq!(std::mem::offset_of!(RenderSettings, threshold), 108);
+        assert_eq!(std::mem::offset_of!(RenderSettings, layer_blend), 112);
+        assert_eq!(std::mem::offset_of!(RenderSettings, blend_weight), 116);
+        assert_eq!(
+            std::mem::offset_of!(RenderSettings, dim_outside_selection),
+            120
+        );
+        assert_eq!(std::mem::offset_of!(RenderSettings, checker_size), 124);
+        assert_eq!(std::mem::offset_of!(RenderSettings, morph_step), 128);
+        assert_eq!(std::mem::offset_of!(RenderSettings, morph), 132);
+    }
+}
@@ -0,0 +1,1594 @@
+use std::{borrow::Cow, sync::Arc};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    annotation_groups::{self, AnnotationGroups},
+    baseline::{Baseline, BaselineMode},
+    blink::VisualState,
+    camera_sync::CameraState,
+    color_space::OutputColorSpace,
+    colormap::{Colormap, CvdSimulation},
+    compositing::Compositing,
+    compute::ComputeDevice,
+    coordinates::ViewTransform,
+    debug_view::DebugView,
+    error::ViewerError,
+    histogram::{Histogram, Layer},
+    image::{Image, ImageSize, SurfaceProducts, ZValueRange},
+    index_buffer::{IndexBuffer, IndexBufferBuilder, MeshMode},
+    markers::{Marker, MarkerBuffer, MarkerShape},
+    overlay_style::{ColorRamp, OverlayStyle, ScoreFilter},
+    pixel_picker::PixelPicker,
+    projection::Projection,
+    quality::Refinement,
+    raw_image::SurfaceTile,
+    render_settings::RenderSettings,
+    residual::Tolerance,
+    retention::RetentionPolicy,
+    review::{self, ReviewItem, ReviewOrder},
+    selection::{Selection, SelectionKind},
+    simd,
+    temporal::{TemporalFuture, TemporalStats},
+    texture::{
+        AmplitudeLimits, AmplitudeRange, Overlay, OverlayBlendMode, Reload, SurfaceTexture,
+        Texture, displayed_color,
+    },
+    transformation::Transformation,
+    vertex_buffer::VertexBuffer,
+    view_state::ViewState,
+};
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Draws the surface with all its overlays and markers into any pair of color and picking
+/// targets, independent of a window
+pub(crate) struct Renderer {
+    device: Arc<wgpu::Device>,
+    queue: wgpu::Queue,
+    pub transformation: Transformation,
+    pub projection: Projection,
+    render_pipeline_amplitude: wgpu::RenderPipeline,
+    render_pipeline_height: wgpu::RenderPipeline,
+    render_pipeline_residual: wgpu::RenderPipeline,
+    render_pipeline_markers: wgpu::RenderPipeline,
+    render_pipeline_markers_on_top: wgpu::RenderPipeline,
+    render_pipeline_overdraw: wgpu::RenderPipeline,
+    render_pipeline_bounds: wgpu::RenderPipeline,
+    render_pipeline_normals: wgpu::RenderPipeline,
+    /// Developer visualizations of the mesh
+    pub debug_view: DebugView,
+    pub use_height_shader: bool,
+    /// Show the deviation from the reference surface instead of heights or amplitudes
+    pub show_residual: bool,
+    /// Nominal surface the residual is computed against
+    pub reference: Option<Arc<Image<f32>>>,
+    /// Baseline of the live surfaces the residual is computed against instead of a reference
+    pub baseline: Option<Baseline>,
+    /// Per-pixel statistics of the surfaces loaded while collecting
+    pub temporal: Option<TemporalStats>,
+    /// Device of a second adapter the temporal statistics run on
+    compute: Option<ComputeDevice>,
+    pub tolerance: Tolerance,
+    /// Tile size in pixels of the checkerboard alternating between the surface and the
+    /// reference, `None` shows the surface only
+    pub checkerboard: Option<u32>,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    vertex_buffer: Option<VertexBuffer>,
+    index_buffer: Option<IndexBuffer>,
+    /// Coarser mesh drawn instead of `index_buffer` while the camera moves, see `QualityScaler`
+    preview_index_buffer: Option<IndexBuffer>,
+    pub texture: Option<Texture>,
+    image_dims_buffer: wgpu::Buffer,
+    z_value_range_buffer: wgpu::Buffer,
+    image_info_bind_group: wgpu::BindGroup,
+    depth_view: wgpu::TextureView,
+    zoom_buffer: wgpu::Buffer,
+    pub overlay_blend_mode: OverlayBlendMode,
+    pub render_settings: RenderSettings,
+    render_settings_buffer: wgpu::Buffer,
+    pub z_range: Option<ZValueRange<f32>>,
+    pub mesh_mode: MeshMode,
+    /// Ratio of the y to the x spacing of the pixels
+    pixel_aspect: f32,
+    /// Shows the surface with the pixel aspect instead of square pixels
+    physical_proportions: bool,
+    /// Vertical exaggeration of the surface
+    pub z_scale: f32,
+    pub markers: MarkerBuffer,
+    /// Markers as last set, indexed by the marker IDs in the picking target
+    marker_list: Arc<Vec<Marker>>,
+    /// Visibility and colors of the named groups of markers and overlays
+    pub annotation_groups: AnnotationGroups,
+    /// Colors and filters the overlays by their metadata
+    pub overlay_style: OverlayStyle,
+    /// Crosshair at the surface pixel under the mouse
+    cursor: MarkerBuffer,
+    cursor_pixel: Option<[u32; 2]>,
+    /// Picked points and guide lines of the active measurement
+    measurement: MarkerBuffer,
+    /// Draw markers and other screen-space elements, hidden e.g. for presentations
+    pub hud_visible: bool,
+    pub colormap: Colormap,
+    pub amplitude_colormap: Colormap,
+    pub amplitude_range: AmplitudeRange,
+    /// How the layer that isn't shown modulates the colors of the shown one
+    pub compositing: Compositing,
+    pub cvd_simulation: CvdSimulation,
+    /// Color space of the display or the exported images the colors are converted to
+    pub color_space: OutputColorSpace,
+    /// What happens to the CPU copies of new images after their upload
+    pub retention: RetentionPolicy,
+    /// Linear sRGB color the frame is cleared with
+    background: glam::Vec3,
+    size: PhysicalSize<u32>,
+}
+
+impl Renderer {
+    /// `color_format` is the format of the color target views passed to `render`
+    pub(crate) fn new(
+        device: Arc<wgpu::Device>,
+        queue: wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
+        });
+
+        let image_info_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("image_info_bind_group_layout"),
+                entries: &[
+                    ImageSize::get_bind_group_layout_entry(),
+                    ZValueRange::<f32>::get_bind_group_layout_entry(),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    RenderSettings::get_bind_group_layout_entry(),
+                ],
+            });
+
+        let texture_bind_group_layout = Texture::create_bind_group_layout(&device);
+
+        let zoom_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("mip_level_buffer"),
+            contents: bytemuck::cast_slice(&[2u32]),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        let image_dims_buffer = ImageSize::create_buffer(&device);
+        let z_value_range_buffer = ZValueRange::<f32>::create_buffer(&device);
+        let render_settings_buffer = RenderSettings::create_buffer(&device);
+        let image_info_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("image_info_bind_group"),
+            layout: &image_info_bind_group_layout,
+            entries: &[
+                ImageSize::get_bind_group_entry(&image_dims_buffer),
+                ZValueRange::<f32>::get_bind_group_entry(&z_value_range_buffer),
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: zoom_buffer.as_entire_binding(),
+                },
+                RenderSettings::get_bind_group_entry(&render_settings_buffer),
+            ],
+        });
+
+        let mut transformation = Transformation::default();
+        let transformation_bind_group_layout = transformation.create_bind_group(&device);
+        let mut projection = Projection::default();
+        let projection_bind_group_layout = projection.create_bind_group(&device);
+        projection.update_aspect_ratio(size.width.max(1) as f32 / size.height.max(1) as f32);
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("render_pipeline_layout"),
+                bind_group_layouts: &[
+                    &texture_bind_group_layout,
+                    &image_info_bind_group_layout,
+                    &transformation_bind_group_layout,
+                    &projection_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+        // Two render targets: main color + picking texture
+        let texture_formats = [
+            Some(color_format.into()),
+            Some(PixelPicker::PICKING_FORMAT.into()),
+        ];
+        let amplitude_pipeline_descriptor = &wgpu::RenderPipelineDescriptor {
+            label: Some("amplitude_pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[VertexBuffer::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_amplitude"),
+                compilation_options: Default::default(),
+                targets: &texture_formats,
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: Some(wgpu::IndexFormat::Uint32),
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Greater,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        };
+
+        let render_pipeline_amplitude =
+            device.create_render_pipeline(amplitude_pipeline_descriptor);
+
+        let mut height_pipeline_descriptor = amplitude_pipeline_descriptor.clone();
+        height_pipeline_descriptor.label = Some("height_pipeline");
+        height_pipeline_descriptor.fragment = Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_height"),
+            compilation_options: Default::default(),
+            targets: &texture_formats,
+        });
+        let render_pipeline_height = device.create_render_pipeline(&height_pipeline_descriptor);
+
+        let mut residual_pipeline_descriptor = amplitude_pipeline_descriptor.clone();
+        residual_pipeline_descriptor.label = Some("residual_pipeline");
+        residual_pipeline_descriptor.fragment = Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_residual"),
+            compilation_options: Default::default(),
+            targets: &texture_formats,
+        });
+        let render_pipeline_residual = device.create_render_pipeline(&residual_pipeline_descriptor);
+
+        // Markers are instanced quads, one instance per marker
+        let marker_buffers = [MarkerBuffer::desc()];
+        let mut markers_pipeline_descriptor = amplitude_pipeline_descriptor.clone();
+        markers_pipeline_descriptor.label = Some("markers_pipeline");
+        markers_pipeline_descriptor.vertex = wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_marker"),
+            buffers: &marker_buffers,
+            compilation_options: Default::default(),
+        };
+        markers_pipeline_descriptor.fragment = Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_marker"),
+            compilation_options: Default::default(),
+            targets: &texture_formats,
+        });
+        markers_pipeline_descriptor.primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        };
+        let render_pipeline_markers = device.create_render_pipeline(&markers_pipeline_descriptor);
+
+        // Second marker pass ignoring the depth buffer so markers behind peaks stay visible
+        let mut markers_on_top_pipeline_descriptor = markers_pipeline_descriptor.clone();
+        markers_on_top_pipeline_descriptor.label = Some("markers_on_top_pipeline");
+        markers_on_top_pipeline_descriptor.depth_stencil = Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        });
+        let render_pipeline_markers_on_top =
+            device.create_render_pipeline(&markers_on_top_pipeline_descriptor);
+
+        // Debug visualizations leave the picking target as it is, the overdraw heatmap adds up
+        // all triangles regardless of their depth
+        let debug_targets = [
+            Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            }),
+            Some(wgpu::ColorTargetState {
+                format: PixelPicker::PICKING_FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::empty(),
+            }),
+        ];
+        let overdraw_targets = [
+            Some(wgpu::ColorTargetState {
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::REPLACE,
+                }),
+                ..debug_targets[0].clone().unwrap()
+            }),
+            debug_targets[1].clone(),
+        ];
+        let mut overdraw_pipeline_descriptor = amplitude_pipeline_descriptor.clone();
+        overdraw_pipeline_descriptor.label = Some("overdraw_pipeline");
+        overdraw_pipeline_descriptor.fragment = Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_overdraw"),
+            compilation_options: Default::default(),
+            targets: &overdraw_targets,
+        });
+        overdraw_pipeline_descriptor.depth_stencil =
+            markers_on_top_pipeline_descriptor.depth_stencil.clone();
+        let render_pipeline_overdraw = device.create_render_pipeline(&overdraw_pipeline_descriptor);
+
+        let mut bounds_pipeline_descriptor = overdraw_pipeline_descriptor.clone();
+        bounds_pipeline_descriptor.label = Some("bounds_pipeline");
+        bounds_pipeline_descriptor.vertex = wgpu::VertexState {
+            module: &shader,
+            entry_point: Some("vs_bounds"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        };
+        bounds_pipeline_descriptor.fragment = Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: Some("fs_debug_line"),
+            compilation_options: Default::default(),
+            targets: &debug_targets,
+        });
+        bounds_pipeline_descriptor.primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Default::default()
+        };
+        let render_pipeline_bounds = device.create_render_pipeline(&bounds_pipeline_descriptor);
+
+        // Normals are hidden behind the surface like markers
+        let mut normals_pipeline_descriptor = bounds_pipeline_descriptor.clone();
+        normals_pipeline_descriptor.label = Some("normals_pipeline");
+        normals_pipeline_descriptor.vertex.entry_point = Some("vs_normals");
+        normals_pipeline_descriptor.depth_stencil = Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::GreaterEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        });
+        let render_pipeline_normals = device.create_render_pipeline(&normals_pipeline_descriptor);
+
+        let depth_view = Self::create_depth_view(&device, size);
+
+        Self {
+            device,
+            queue,
+            transformation,
+            projection,
+            render_pipeline_amplitude,
+            render_pipeline_height,
+            render_pipeline_residual,
+            render_pipeline_markers,
+            render_pipeline_markers_on_top,
+            render_pipeline_overdraw,
+            render_pipeline_bounds,
+            render_pipeline_normals,
+            debug_view: DebugView::default(),
+            use_height_shader: true,
+            show_residual: false,
+            reference: None,
+            baseline: None,
+            temporal: None,
+            compute: None,
+            tolerance: Tolerance::default(),
+            checkerboard: None,
+            texture_bind_group_layout,
+            vertex_buffer: None,
+            index_buffer: None,
+            preview_index_buffer: None,
+            texture: None,
+            image_dims_buffer,
+            z_value_range_buffer,
+            image_info_bind_group,
+            depth_view,
+            zoom_buffer,
+            overlay_blend_mode: OverlayBlendMode::default(),
+            render_settings: RenderSettings::default(),
+            render_settings_buffer,
+            z_range: None,
+            mesh_mode: MeshMode::default(),
+            pixel_aspect: 1.0,
+            physical_proportions: true,
+            z_scale: 1.0,
+            markers: MarkerBuffer::new(),
+            marker_list: Arc::new(Vec::new()),
+            annotation_groups: AnnotationGroups::default(),
+            overlay_style: OverlayStyle::default(),
+            cursor: MarkerBuffer::not_selectable(),
+            cursor_pixel: None,
+            measurement: MarkerBuffer::not_selectable(),
+            hud_visible: true,
+            colormap: Colormap::default(),
+            amplitude_colormap: Colormap::default(),
+            amplitude_range: AmplitudeRange::default(),
+            compositing: Compositing::default(),
+            cvd_simulation: CvdSimulation::default(),
+            color_space: OutputColorSpace::default(),
+            retention: RetentionPolicy::default(),
+            background: glam::Vec3::ZERO,
+            size,
+        }
+    }
+
+    pub(crate) fn device(&self) -> &Arc<wgpu::Device> {
+        &self.device
+    }
+
+    pub(crate) fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Size of the render targets in physical pixels
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    fn create_depth_view(device: &wgpu::Device, size: PhysicalSize<u32>) -> wgpu::TextureView {
+        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth_texture"),
+            size: wgpu::Extent3d {
+                width: size.width.max(1),
+                height: size.height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        depth_texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    pub(crate) fn resize(&mut self, size: PhysicalSize<u32>) {
+        // Recreate depth texture to match the new size
+        self.depth_view = Self::create_depth_view(&self.device, size);
+        self.projection
+            .update_aspect_ratio(size.width.max(1) as f32 / size.height.max(1) as f32);
+        self.size = size;
+    }
+
+    /// Records the render pass into `encoder` and uploads the uniforms.
+    /// Both views must have the size last passed to `resize`.
+    pub(crate) fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        color_view: &wgpu::TextureView,
+        picking_view: &wgpu::TextureView,
+    ) {
+        let background = self.color_space.matrix() * self.background;
+        // Create the renderpass which will clear the screen.
+        // Two color attachments: main color + picking texture
+        let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: background.x as f64,
+                            g: background.y as f64,
+                            b: background.z as f64,
+                            a: 1.0,
+                        }),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    view: picking_view,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                }),
+            ],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(Projection::FAR_DEPTH),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let pipeline = if self.debug_view.overdraw {
+            &self.render_pipeline_overdraw
+        } else if self.show_residual && self.has_reference() {
+            &self.render_pipeline_residual
+        } else if self.use_height_shader {
+            &self.render_pipeline_height
+        } else {
+            &self.render_pipeline_amplitude
+        };
+        renderpass.set_pipeline(pipeline);
+        if let Some(texture) = &self.texture {
+            renderpass.set_bind_group(0, &texture.bind_group, &[]);
+        }
+        renderpass.set_bind_group(1, &self.image_info_bind_group, &[]);
+        renderpass.set_bind_group(2, &self.transformation.bind_group, &[]);
+        renderpass.set_bind_group(3, &self.projection.bind_group, &[]);
+        if let Some(vertex_buffer) = &self.vertex_buffer {
+            renderpass.set_vertex_buffer(0, vertex_buffer.buffer.slice(..));
+        }
+        if let Some(index_buffer) = self
+            .preview_index_buffer
+            .as_ref()
+            .or(self.index_buffer.as_ref())
+        {
+            renderpass.set_index_buffer(index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint32);
+            renderpass.draw_indexed(
+                0..index_buffer.buffer.size() as u32 / std::mem::size_of::<u32>() as u32,
+                0,
+                0..1,
+            );
+        }
+        if self.texture.is_some() && self.debug_view.bounds {
+            renderpass.set_pipeline(&self.render_pipeline_bounds);
+            renderpass.draw(0..24, 0..1);
+        }
+        if self.texture.is_some() && self.debug_view.normals {
+            renderpass.set_pipeline(&self.render_pipeline_normals);
+            renderpass.draw(0..2, 0..DebugView::NORMAL_GRID * DebugView::NORMAL_GRID);
+        }
+        if self.texture.is_some() && self.hud_visible {
+            renderpass.set_pipeline(&self.render_pipeline_markers);
+            self.markers.draw_depth_tested(&mut renderpass);
+            self.measurement.draw_depth_tested(&mut renderpass);
+            renderpass.set_pipeline(&self.render_pipeline_markers_on_top);
+            self.markers.draw_on_top(&mut renderpass);
+            self.measurement.draw_on_top(&mut renderpass);
+            self.cursor.draw_on_top(&mut renderpass);
+        }
+
+        // End the renderpass.
+        drop(renderpass);
+
+        let zoom = self.projection.get_zoom();
+        if zoom > 0.8 {
+            self.queue
+                .write_buffer(&self.zoom_buffer, 0, bytemuck::cast_slice(&[2u32]));
+        } else if zoom > 0.2 {
+            self.queue
+                .write_buffer(&self.zoom_buffer, 0, bytemuck::cast_slice(&[1u32]));
+        } else {
+            self.queue
+                .write_buffer(&self.zoom_buffer, 0, bytemuck::cast_slice(&[0u32]));
+        }
+        self.transformation.update_gpu(&self.queue);
+        self.projection.fit_depth(self.transformation.model());
+        self.projection.update_gpu(&self.queue);
+        self.render_settings.viewport = [
+            self.size.width.max(1) as f32,
+            self.size.height.max(1) as f32,
+        ];
+        self.render_settings.checker_size = self
+            .checkerboard
+            .filter(|_| self.has_reference())
+            .unwrap_or(0);
+        self.render_settings
+            .write_buffer(&self.queue, &self.render_settings_buffer);
+    }
+
+    /// `reload` decodes the surface again, so that its CPU copy can be dropped after the upload.
+    /// `products` are computed from the surface if the caller doesn't have them.
+    pub(crate) fn set_surface(
+        &mut self,
+        data: Image<f32>,
+        products: Option<SurfaceProducts>,
+        reload: Option<Reload>,
+    ) -> Result<(), ViewerError> {
+        log::info!("Setting new surface image");
+        data.size.check_limits(&self.device.limits())?;
+        let SurfaceProducts {
+            height_range: z_range,
+            mips,
+        } = match products {
+            Some(products) => products,
+            None => SurfaceProducts::compute(&data)?,
+        };
+        z_range.write_buffer(&self.queue, &self.z_value_range_buffer);
+        self.render_settings.height_range = [z_range.min(), z_range.max()];
+        self.z_range = Some(z_range);
+
+        data.size.write_buffer(&self.queue, &self.image_dims_buffer);
+
+        self.vertex_buffer = Some(VertexBuffer::new(&data, &self.device));
+
+        self.index_buffer = Some(self.create_index_buffer(&data));
+        self.preview_index_buffer = None;
+
+        let scale = data.size.lateral_scale(self.display_pixel_aspect());
+        self.transformation.set_scale(scale.extend(self.z_scale));
+        // Later surfaces keep the framing the user chose
+        if self.texture.is_none() {
+            self.projection.fit(self.transformation.model());
+        }
+
+        let mut texture = Texture::new(
+            &self.device,
+            data,
+            mips,
+            reload,
+            &self.texture_bind_group_layout,
+        );
+        texture.overlay.set_blend_mode(self.overlay_blend_mode);
+        texture.surface.write_to_queue(&self.queue);
+        // Before the CPU copy a compute device needs may be dropped
+        self.add_temporal_frame(&texture.surface);
+        texture.surface.retain(self.retention);
+        // Keep comparing new measurements of the same size with the reference
+        if let Some(reference) = &self.reference {
+            if reference.size == *texture.surface.image_size() {
+                texture.reference.write_to_queue(&self.queue, reference);
+            } else {
+                log::warn!("Reference surface does not match the new surface, removing it");
+                self.reference = None;
+            }
+        }
+        if let Some(baseline) = &mut self.baseline {
+            baseline.fold(
+                &self.device,
+                &self.queue,
+                &texture.surface,
+                &texture.reference,
+            );
+        }
+        self.texture = Some(texture);
+        Ok(())
+    }
+
+    /// Replaces the heights of the surface by edited ones of the same size, e.g. after leveling.
+    /// Unlike `set_surface` the amplitude, overlays, reference and camera are kept.
+    pub(crate) fn update_surface(&mut self, data: Arc<Image<f32>>) -> Result<(), ViewerError> {
+        let Some(texture) = &self.texture else {
+            return Err(ViewerError::InvalidImage("no surface to update".to_owned()));
+        };
+        if data.size != *texture.surface.image_size() {
+            return Err(ViewerError::InvalidImage(
+                "updated surface has a different size".to_owned(),
+            ));
+        }
+        let SurfaceProducts {
+            height_range: z_range,
+            mips,
+        } = SurfaceProducts::compute(&data)?;
+        z_range.write_buffer(&self.queue, &self.z_value_range_buffer);
+        self.render_settings.height_range = [z_range.min(), z_range.max()];
+        self.z_range = Some(z_range);
+        self.vertex_buffer = Some(VertexBuffer::new(&data, &self.device));
+        self.index_buffer = Some(self.create_index_buffer(&data));
+        self.preview_index_buffer = None;
+        if let Some(texture) = &mut self.texture {
+            texture.surface.set_image(data, mips);
+            texture.surface.write_to_queue(&self.queue);
+            texture.surface.retain(self.retention);
+        }
+        Ok(())
+    }
+
+    /// Uploads rows of a surface that arrive while it loads into the surface of its size, see
+    /// `raw_image::SurfaceTile`. The CPU copy and the coarser mip levels stay those the surface
+    /// was set with until the complete surface is set.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub(crate) fn write_surface_tile(&mut self, tile: &SurfaceTile) -> Result<(), ViewerError> {
+        let Some(texture) = &self.texture else {
+            return Err(ViewerError::InvalidImage("no surface to update".to_owned()));
+        };
+        if tile.surface_size != *texture.surface.image_size() {
+            return Err(ViewerError::InvalidImage(
+                "tile belongs to a surface of a different size".to_owned(),
+            ));
+        }
+        texture
+            .surface
+            .write_rows(&self.queue, tile.first_row, &tile.rows);
+        if let Some(vertex_buffer) = &self.vertex_buffer {
+            vertex_buffer.write_rows(&self.queue, tile.first_row, &tile.rows);
+        }
+        // Widened so that the heights of the new rows aren't clipped
+        if let (Some(z_range), Some((min, max))) = (&self.z_range, simd::min_max(&tile.rows.data)) {
+            let z_range = ZValueRange::new(z_range.min().min(min), z_range.max().max(max));
+            z_range.write_buffer(&self.queue, &self.z_value_range_buffer);
+            self.render_settings.height_range = [z_range.min(), z_range.max()];
+            self.z_range = Some(z_range);
+        }
+        Ok(())
+    }
+
+    /// Sets the nominal surface, e.g. from CAD, that must have the size of the surface
+    pub(crate) fn set_reference(&mut self, data: Image<f32>) -> Result<(), ViewerError> {
+        log::info!("Setting reference surface");
+        let Some(texture) = &self.texture else {
+            return Err(ViewerError::InvalidImage(
+                "a surface has to be loaded before its reference".to_owned(),
+            ));
+        };
+        let surface_size = texture.surface.image_size();
+        if data.size != *surface_size {
+            return Err(ViewerError::InvalidImage(format!(
+                "reference image of {}x{} pixels does not match surface of {}x{} pixels",
+                data.size.width, data.size.height, surface_size.width, surface_size.height
+            )));
+        }
+        let reference = Arc::new(data);
+        texture.reference.write_to_queue(&self.queue, &reference);
+        self.reference = Some(reference);
+        if self.baseline.take().is_some() {
+            log::info!("The reference surface replaces the live baseline");
+        }
+        Ok(())
+    }
+
+    /// Compares the surfaces loaded from now on with a baseline of them instead of a reference,
+    /// starting with the current surface. `None` stops keeping a baseline.
+    pub(crate) fn set_baseline(&mut self, mode: Option<BaselineMode>) {
+        log::info!(
+            "Live baseline: {}",
+            mode.map_or("off".to_string(), |mode| mode.to_string())
+        );
+        let Some(mode) = mode else {
+            self.baseline = None;
+            return;
+        };
+        if self.reference.take().is_some() {
+            log::info!("The live baseline replaces the reference surface");
+        }
+        let baseline = self
+            .baseline
+            .get_or_insert_with(|| Baseline::new(&self.device, mode));
+        baseline.mode = mode;
+        baseline.restart();
+        if let Some(texture) = &self.texture {
+            baseline.fold(
+                &self.device,
+                &self.queue,
+                &texture.surface,
+                &texture.reference,
+            );
+        }
+    }
+
+    /// Starts the temporal statistics anew with the current surface and adds every surface set
+    /// from now on to them, `false` stops adding surfaces but keeps the statistics
+    pub(crate) fn set_temporal_collecting(&mut self, collecting: bool) {
+        log::info!("Collecting frames for temporal statistics: {}", collecting);
+        if !collecting {
+            if let Some(temporal) = &mut self.temporal {
+                temporal.collecting = false;
+            }
+            return;
+        }
+        let device = self
+            .compute
+            .as_ref()
+            .map_or(&self.device, |compute| &compute.device);
+        self.temporal = Some(TemporalStats::new(device));
+        if let Some(texture) = self.texture.take() {
+            self.add_temporal_frame(&texture.surface);
+            self.texture = Some(texture);
+        }
+    }
+
+    /// Adds the surface to the temporal statistics while collecting, on the compute device from
+    /// its CPU copy if there is one
+    fn add_temporal_frame(&mut self, surface: &SurfaceTexture) {
+        let Some(temporal) = self
+            .temporal
+            .as_mut()
+            .filter(|temporal| temporal.collecting)
+        else {
+            return;
+        };
+        let added = match &self.compute {
+            Some(compute) => match surface.image() {
+                Some(image) => temporal.add_image(&compute.device, &compute.queue, &image),
+                None => Err(ViewerError::InvalidImage(
+                    "surface has no CPU copy to upload to the compute device".to_owned(),
+                )),
+            },
+            None => temporal.add(&self.device, &self.queue, surface),
+        };
+        if let Err(e) = added {
+            log::error!(
+                "Failed to add the surface to the temporal statistics: {}",
+                e
+            );
+        }
+    }
+
+    /// Per-pixel mean and standard deviation of the collected surfaces, `None` without any
+    pub(crate) fn temporal_maps(&self) -> Option<TemporalFuture> {
+        let temporal = self.temporal.as_ref()?;
+        match &self.compute {
+            Some(compute) => temporal.read(compute.device.clone(), &compute.queue),
+            None => temporal.read(self.device.clone(), &self.queue),
+        }
+    }
+
+    /// Runs the temporal statistics on another device than the one drawing, `None` draws and
+    /// computes on the same one. Statistics collected so far are dropped with their device.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn set_compute_device(&mut self, compute: Option<ComputeDevice>) {
+        if let Some(compute) = &compute {
+            log::info!("Computing temporal statistics on {}", compute.info.name);
+        }
+        self.compute = compute;
+        self.temporal = None;
+    }
+
+    /// Nominal surface or baseline to compare the surface with
+    pub(crate) fn has_reference(&self) -> bool {
+        self.reference.is_some()
+            || self
+                .baseline
+                .as_ref()
+                .is_some_and(|baseline| baseline.frames() > 0)
+    }
+
+    pub(crate) fn set_show_residual(&mut self, show: bool) {
+        log::info!("Showing residual to the reference: {}", show);
+        if show && !self.has_reference() {
+            log::warn!("No reference surface loaded");
+        }
+        self.show_residual = show;
+    }
+
+    pub(crate) fn set_checkerboard(&mut self, tile_size: Option<u32>) {
+        log::info!("Checkerboard with the reference: {:?}", tile_size);
+        if tile_size.is_some() && !self.has_reference() {
+            log::warn!("No reference surface loaded");
+        }
+        self.checkerboard = tile_size.filter(|&size| size > 0);
+    }
+
+    pub(crate) fn set_tolerance(&mut self, tolerance: Tolerance) {
+        log::info!(
+            "Setting tolerance band from {} to {}",
+            tolerance.lower,
+            tolerance.upper
+        );
+        self.tolerance = tolerance;
+        self.render_settings.tolerance = [tolerance.lower, tolerance.upper];
+    }
+
+    fn create_index_buffer(&self, image: &Image<f32>) -> IndexBuffer {
+        match self.mesh_mode {
+            MeshMode::Full => IndexBufferBuilder::new_triangle_strip(&image.size),
+            MeshMode::Adaptive { relative_tolerance } => {
+                let z_extent = self
+                    .z_range
+                    .as_ref()
+                    .map_or(0.0, |range| range.max() - range.min());
+                IndexBufferBuilder::new_adaptive(image, relative_tolerance * z_extent)
+            }
+        }
+        .create_buffer_init(&self.device)
+    }
+
+    pub(crate) fn set_mesh_mode(&mut self, mode: MeshMode) {
+        log::info!("Setting mesh mode to {:?}", mode);
+        self.mesh_mode = mode;
+        if let Some(image) = self
+            .texture
+            .as_ref()
+            .and_then(|texture| texture.surface.image())
+        {
+            self.index_buffer = Some(self.create_index_buffer(&image));
+        }
+    }
+
+    /// Draws only every `step`-th row and column of the surface, 1 draws the mesh of the
+    /// mesh mode
+    pub(crate) fn set_preview_step(&mut self, step: u32) {
+        log::debug!("Drawing every {}. pixel", step);
+        self.render_settings.morph_step = 0;
+        self.preview_index_buffer = match &self.texture {
+            Some(texture) if step > 1 => Some(
+                IndexBufferBuilder::new_strided(texture.surface.image_size(), step)
+                    .create_buffer_init(&self.device),
+            ),
+            _ => None,
+        };
+    }
+
+    /// Draws the mesh of the refinement with its heights faded from the coarser mesh
+    pub(crate) fn set_refinement(&mut self, refinement: Refinement) {
+        if refinement.fade == 0.0 {
+            self.set_preview_step(refinement.step);
+        }
+        self.render_settings.morph_step = if refinement.fade < 1.0 {
+            refinement.from_step
+        } else {
+            0
+        };
+        self.render_settings.morph = refinement.fade;
+    }
+
+    pub(crate) fn set_amplitude(&mut self, data: Image<u16>) -> Result<(), ViewerError> {
+        log::info!("Setting new amplitude image");
+        if let Some(texture) = &mut self.texture {
+            let surface_size = texture.surface.image_size();
+            if *surface_size != data.size {
+                return Err(ViewerError::InvalidImage(format!(
+                    "amplitude image of {}x{} pixels does not match surface of {}x{} pixels",
+                    data.size.width, data.size.height, surface_size.width, surface_size.height
+                )));
+            }
+            texture.amplitude.set_image(data);
+            texture.amplitude.write_to_queue(&self.queue);
+            texture.amplitude.retain(self.retention);
+        }
+        // Percentile limits depend on the image
+        self.set_amplitude_range(self.amplitude_range);
+        Ok(())
+    }
+
+    pub(crate) fn set_amplitude_colormap(&mut self, colormap: Colormap) {
+        log::info!("Setting amplitude colormap to {}", colormap.name());
+        self.amplitude_colormap = colormap;
+        self.render_settings.amplitude_colormap = colormap.shader_index();
+    }
+
+    pub(crate) fn set_amplitude_range(&mut self, range: AmplitudeRange) {
+        self.amplitude_range = range;
+        let limits = self.amplitude_limits();
+        log::info!("Amplitude colormap from {} to {}", limits.min, limits.max);
+        self.render_settings.amplitude_range = [limits.min, limits.max];
+    }
+
+    /// Maps `min` to `max` in data units to the ends of the colormap of `layer`
+    pub(crate) fn set_display_range(&mut self, layer: Layer, min: f32, max: f32) {
+        match layer {
+            Layer::Height => self.render_settings.height_range = [min, max],
+            Layer::Amplitude => self.set_amplitude_range(AmplitudeRange::Fixed { min, max }),
+        }
+    }
+
+    /// Distribution of the values of `layer` in data units, `None` without data
+    pub(crate) fn histogram(&self, layer: Layer) -> Option<Histogram> {
+        let texture = self.texture.as_ref()?;
+        match layer {
+            Layer::Height => {
+                let [display_min, display_max] = self.render_settings.height_range;
+                Histogram::new(
+                    texture.surface.image()?.data.iter().copied(),
+                    Histogram::DEFAULT_BINS,
+                )
+                .map(|histogram| histogram.with_display_range(display_min, display_max))
+            }
+            Layer::Amplitude => {
+                let image = texture.amplitude.image()?;
+                let limits = self.amplitude_limits();
+                Histogram::new(
+                    image.data.iter().map(|&value| value as f32),
+                    Histogram::DEFAULT_BINS,
+                )
+                .map(|histogram| histogram.with_display_range(limits.min, limits.max))
+            }
+        }
+    }
+
+    /// Amplitudes at the ends of the amplitude colormap
+    pub(crate) fn amplitude_limits(&self) -> AmplitudeLimits {
+        let image = self
+            .texture
+            .as_ref()
+            .and_then(|texture| texture.amplitude.image());
+        self.amplitude_range.limits(image)
+    }
+
+    pub(crate) fn set_background(&mut self, background: glam::Vec3) {
+        log::info!("Setting background to {}", background);
+        self.background = background;
+    }
+
+    pub(crate) fn set_amplitude_shader(&mut self) {
+        log::info!("Setting amplitude shader");
+        self.use_height_shader = false;
+    }
+
+    pub(crate) fn set_height_shader(&mut self) {
+        log::info!("Setting height shader");
+        self.use_height_shader = true;
+    }
+
+    pub(crate) fn set_overlays(&mut self, overlays: Arc<Vec<Overlay>>) {
+        log::info!("Setting overlays");
+        if let Some(texture) = &mut self.texture {
+            texture.overlay.set_overlays(overlays);
+            texture.overlay.write_to_queue(
+                &self.queue,
+                &self.annotation_groups,
+                &self.overlay_style,
+            );
+        }
+    }
+
+    pub(crate) fn set_overlay_blend_mode(&mut self, mode: OverlayBlendMode) {
+        log::info!("Setting overlay blend mode to {:?}", mode);
+        self.overlay_blend_mode = mode;
+        if let Some(texture) = &mut self.texture {
+            texture.overlay.set_blend_mode(mode);
+            texture.overlay.write_to_queue(
+                &self.queue,
+                &self.annotation_groups,
+                &self.overlay_style,
+            );
+        }
+    }
+
+    pub(crate) fn clear_overlays(&mut self) {
+        log::info!("Clearing overlays");
+        if let Some(texture) = &mut self.texture {
+            texture.overlay.set_overlays(Arc::new(Vec::new()));
+            texture.overlay.write_to_queue(
+                &self.queue,
+                &self.annotation_groups,
+                &self.overlay_style,
+            );
+        }
+    }
+
+    pub(crate) fn set_debug_view(&mut self, debug_view: DebugView) {
+        log::info!("Debug view: {:?}", debug_view);
+        self.debug_view = debug_view;
+    }
+
+    pub(crate) fn set_cut_holes(&mut self, cut_holes: bool) {
+        log::info!("Cutting holes at invalid pixels: {}", cut_holes);
+        self.render_settings.cut_holes = cut_holes.into();
+    }
+
+    pub(crate) fn set_ui_scale(&mut self, ui_scale: f32) {
+        self.render_settings.ui_scale = ui_scale;
+    }
+
+    pub(crate) fn set_markers(&mut self, markers: Arc<Vec<Marker>>) {
+        log::info!("Setting {} markers", markers.len());
+        self.markers
+            .set_markers(&self.device, &markers, &self.annotation_groups);
+        self.marker_list = markers;
+    }
+
+    /// Shows or hides the markers and overlays of `group`
+    pub(crate) fn set_group_visible(&mut self, group: &str, visible: bool) {
+        log::info!("Setting annotation group {} visible: {}", group, visible);
+        self.annotation_groups.set_visible(group, visible);
+        self.update_annotations();
+    }
+
+    /// Draws the markers and overlays of `group` in `color`, `None` restores their own colors
+    pub(crate) fn set_group_color(&mut self, group: &str, color: Option<[u8; 4]>) {
+        log::info!("Setting annotation group {} color to {:?}", group, color);
+        self.annotation_groups.set_color(group, color);
+        self.update_annotations();
+    }
+
+    /// Colors the overlays by a metadata value, `None` restores their own colors
+    pub(crate) fn set_overlay_color_ramp(&mut self, ramp: Option<ColorRamp>) {
+        log::info!("Setting overlay color ramp to {:?}", ramp);
+        self.overlay_style.ramp = ramp;
+        self.update_overlays();
+    }
+
+    /// Hides the overlays with a metadata value below a minimum, `None` shows all
+    pub(crate) fn set_overlay_filter(&mut self, filter: Option<ScoreFilter>) {
+        log::info!("Setting overlay filter to {:?}", filter);
+        self.overlay_style.filter = filter;
+        self.update_overlays();
+    }
+
+    /// Uploads the markers and overlays again after the group styles changed
+    fn update_annotations(&mut self) {
+        self.markers
+            .set_markers(&self.device, &self.marker_list, &self.annotation_groups);
+        self.update_overlays();
+    }
+
+    /// Rasterizes the overlays again after their styles changed
+    fn update_overlays(&mut self) {
+        if let Some(texture) = &self.texture {
+            texture.overlay.write_to_queue(
+                &self.queue,
+                &self.annotation_groups,
+                &self.overlay_style,
+            );
+        }
+    }
+
+    /// Shown overlays and markers in the review `order`
+    pub(crate) fn review_items(&self, order: &ReviewOrder) -> Vec<ReviewItem> {
+        let Some(texture) = &self.texture else {
+            return Vec::new();
+        };
+        let size = texture.surface.image_size();
+        let markers = self
+            .marker_list
+            .iter()
+            .enumerate()
+            .filter(|(_, marker)| {
+                self.annotation_groups
+                    .display_color(marker.group.as_deref(), marker.color)
+                    .is_some()
+            })
+            .map(|(index, marker)| ReviewItem::marker(index, marker, size));
+        let overlays = texture
+            .overlay
+            .overlays
+            .iter()
+            .enumerate()
+            .filter(|(_, overlay)| {
+                displayed_color(overlay, &self.annotation_groups, &self.overlay_style).is_some()
+            })
+            .filter_map(|(index, overlay)| ReviewItem::overlay(index, overlay, size, order));
+        let mut items: Vec<_> = overlays.chain(markers).collect();
+        review::sort(&mut items);
+        items
+    }
+
+    /// Pans and zooms to the surface pixels within `bounds`, keeping the orientation
+    pub(crate) fn frame_pixels(&mut self, bounds: [[u32; 2]; 2]) {
+        let (Some(view_transform), Some(image), Some(z_range)) =
+            (self.view_transform(), self.surface_image(), &self.z_range)
+        else {
+            return;
+        };
+        let [[left, top], [right, bottom]] = bounds;
+        let model = self.transformation.model();
+        let (mut min, mut max) = (glam::Vec2::INFINITY, glam::Vec2::NEG_INFINITY);
+        for (x, y) in [(left, top), (right, top), (left, bottom), (right, bottom)] {
+            let value = image.get_pixel(x, y);
+            let value = if value.is_finite() {
+                value
+            } else {
+                (z_range.min() + z_range.max()) / 2.0
+            };
+            let world = view_transform.pixel_to_world(glam::Vec2::new(x as f32, y as f32), value);
+            let view = model.transform_point3(world).truncate();
+            min = min.min(view);
+            max = max.max(view);
+        }
+        self.projection.frame(min, max);
+    }
+
+    /// Names of the groups the markers and overlays belong to
+    pub(crate) fn annotation_group_names(&self) -> Vec<String> {
+        let overlays = self
+            .texture
+            .as_ref()
+            .map_or(&[][..], |texture| &texture.overlay.overlays);
+        annotation_groups::group_names(&self.marker_list, overlays)
+    }
+
+    /// Markers and overlays as CSV, only those of `groups` if given, `None` without a surface
+    pub(crate) fn annotations_csv(&self, groups: Option<&[String]>) -> Option<String> {
+        let texture = self.texture.as_ref()?;
+        Some(annotation_groups::to_csv(
+            &self.marker_list,
+            &texture.overlay.overlays,
+            texture.surface.image_size(),
+            groups,
+        ))
+    }
+
+    /// Topmost overlay at a surface pixel, i.e. the one added last
+    pub(crate) fn overlay_at(&self, pixel: [u32; 2]) -> Option<Selection> {
+        let texture = self.texture.as_ref()?;
+        let index = pixel[1] * texture.surface.image_size().width.get() + pixel[0];
+        texture
+            .overlay
+            .overlays
+            .iter()
+            .rposition(|overlay| {
+                displayed_color(overlay, &self.annotation_groups, &self.overlay_style).is_some()
+                    && overlay.pixels.iter().any(|range| range.contains(&index))
+            })
+            .map(Selection::overlay)
+    }
+
+    /// Highlights the selected overlay or marker, `None` removes the highlight
+    pub(crate) fn set_selection(&mut self, selection: Option<Selection>) {
+        self.render_settings.selected_id = selection.map_or(0, Selection::to_id);
+    }
+
+    /// Dims the surface outside the selected overlay while an overlay is selected
+    pub(crate) fn set_dim_outside_selection(&mut self, dim: bool) {
+        log::info!("Setting dim outside selection to {}", dim);
+        self.render_settings.dim_outside_selection = dim.into();
+    }
+
+    /// Moves the selected overlay or marker by `offset` pixels within the image,
+    /// returns whether it existed
+    pub(crate) fn nudge(&mut self, selection: Selection, offset: [i32; 2]) -> bool {
+        let Some(texture) = &self.texture else {
+            return false;
+        };
+        let size = texture.surface.image_size().clone();
+        let index = selection.index as usize;
+        match selection.kind {
+            SelectionKind::Overlay => {
+                let Some(overlay) = texture.overlay.overlays.get(index) else {
+                    return false;
+                };
+                let mut overlays = texture.overlay.overlays.as_ref().clone();
+                overlays[index] = overlay.translated(offset, &size);
+                self.set_overlays(Arc::new(overlays));
+            }
+            SelectionKind::Marker => {
+                if index >= self.marker_list.len() {
+                    return false;
+                }
+                let mut markers = self.marker_list.as_ref().clone();
+                let pixel = &mut markers[index].pixel;
+                let max = [size.width.get() - 1, size.height.get() - 1];
+                for axis in 0..2 {
+                    pixel[axis] = pixel[axis]
+                        .saturating_add_signed(offset[axis])
+                        .min(max[axis]);
+                }
+                self.set_markers(Arc::new(markers));
+            }
+        }
+        true
+    }
+
+    /// Properties of the selected overlay or marker for display, `None` if it does not exist
+    pub(crate) fn describe(&self, selection: Selection) -> Option<String> {
+        let index = selection.index as usize;
+        match selection.kind {
+            SelectionKind::Overlay => {
+                let overlay = self.texture.as_ref()?.overlay.overlays.get(index)?;
+                let pixels: u32 = overlay.pixels.iter().map(|range| range.len() as u32).sum();
+                let metadata: String = overlay
+                    .metadata
+                    .iter()
+                    .map(|(key, value)| format!(", {} {}", key, value))
+                    .collect();
+                Some(format!(
+                    "Overlay {}: {} pixels, color {:?}{}",
+                    index, pixels, overlay.color, metadata
+                ))
+            }
+            SelectionKind::Marker => {
+                let marker = self.marker_list.get(index)?;
+                Some(format!(
+                    "Marker {}: pixel [{}/{}], {:?}, size {}, color {:?}",
+                    index,
+                    marker.pixel[0],
+                    marker.pixel[1],
+                    marker.shape,
+                    marker.size,
+                    marker.color
+                ))
+            }
+        }
+    }
+
+    /// Removes the selected overlay or marker, returns whether it existed
+    pub(crate) fn remove(&mut self, selection: Selection) -> bool {
+        let index = selection.index as usize;
+        match selection.kind {
+            SelectionKind::Overlay => {
+                let Some(texture) = &self.texture else {
+                    return false;
+                };
+                let overlays = &texture.overlay.overlays;
+                if index >= overlays.len() {
+                    return false;
+                }
+                let mut overlays = overlays.as_ref().clone();
+                overlays.remove(index);
+                self.set_overlays(Arc::new(overlays));
+            }
+            SelectionKind::Marker => {
+                if index >= self.marker_list.len() {
+                    return false;
+                }
+                let mut markers = self.marker_list.as_ref().clone();
+                markers.remove(index);
+                self.set_markers(Arc::new(markers));
+            }
+        }
+        true
+    }
+
+    pub(crate) fn set_colormap(&mut self, colormap: Colormap) {
+        log::info!("Setting colormap to {}", colormap.name());
+        self.colormap = colormap;
+        self.render_settings.colormap = colormap.shader_index();
+    }
+
+    pub(crate) fn set_compositing(&mut self, compositing: Compositing) {
+        log::info!("Setting layer blend to {}", compositing);
+        self.compositing = compositing;
+        self.render_settings.layer_blend = compositing.shader_index();
+        self.render_settings.blend_weight = compositing.weight;
+    }
+
+    pub(crate) fn set_cvd_simulation(&mut self, simulation: CvdSimulation) {
+        log::info!("Simulating color vision deficiency: {}", simulation.name());
+        self.cvd_simulation = simulation;
+        self.update_color_matrix();
+    }
+
+    /// The web relies on the browser managing the colors of the canvas
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn set_color_space(&mut self, color_space: OutputColorSpace) {
+        log::info!("Rendering for color space {}", color_space);
+        self.color_space = color_space;
+        self.update_color_matrix();
+    }
+
+    /// The deficiency is simulated on linear sRGB, before the conversion to the output
+    fn update_color_matrix(&mut self) {
+        self.render_settings
+            .set_color_matrix(self.color_space.matrix() * self.cvd_simulation.matrix());
+    }
+
+    pub(crate) fn visual_state(&self) -> VisualState {
+        VisualState {
+            use_height_shader: self.use_height_shader,
+            show_residual: self.show_residual,
+            colormap: self.colormap,
+            cvd_simulation: self.cvd_simulation,
+        }
+    }
+
+    /// Applies all settings of `state` at once, without logging as it happens while blinking
+    pub(crate) fn set_visual_state(&mut self, state: VisualState) {
+        self.use_height_shader = state.use_height_shader;
+        self.show_residual = state.show_residual;
+        self.colormap = state.colormap;
+        self.render_settings.colormap = state.colormap.shader_index();
+        self.cvd_simulation = state.cvd_simulation;
+        self.update_color_matrix();
+    }
+
+    pub(crate) fn set_overlays_visible(&mut self, visible: bool) {
+        log::info!("Setting overlays visible to {}", visible);
+        self.render_settings.show_overlays = visible.into();
+    }
+
+    pub(crate) fn view_state(&self) -> ViewState {
+        ViewState {
+            layer: if self.use_height_shader {
+                Layer::Height
+            } else {
+                Layer::Amplitude
+            },
+            show_residual: self.show_residual,
+            colormap: self.colormap,
+            amplitude_colormap: self.amplitude_colormap,
+            height_range: self.render_settings.height_range,
+            amplitude_range: self.amplitude_range,
+            compositing: self.compositing,
+            z_scale: self.z_scale,
+            camera: self.camera(),
+            overlays_visible: self.render_settings.show_overlays != 0,
+        }
+    }
+
+    pub(crate) fn apply_view_state(&mut self, state: &ViewState) {
+        self.use_height_shader = state.layer == Layer::Height;
+        self.set_show_residual(state.show_residual);
+        self.set_colormap(state.colormap);
+        self.set_amplitude_colormap(state.amplitude_colormap);
+        self.render_settings.height_range = state.height_range;
+        self.set_amplitude_range(state.amplitude_range);
+        self.set_compositing(state.compositing);
+        self.set_z_scale(state.z_scale);
+        self.set_camera(state.camera);
+        self.set_overlays_visible(state.overlays_visible);
+    }
+
+    pub(crate) fn set_hud_visible(&mut self, visible: bool) {
+        log::info!("Setting HUD visible to {}", visible);
+        self.hud_visible = visible;
+    }
+
+    /// Moves the 3D cursor to a surface pixel, `None` hides it
+    pub(crate) fn set_cursor(&mut self, pixel: Option<[u32; 2]>) {
+        if pixel == self.cursor_pixel {
+            return;
+        }
+        self.cursor_pixel = pixel;
+        let cursor = pixel.map(|pixel| Marker {
+            pixel,
+            color: [255, 255, 255, 255],
+            size: 24.0,
+            on_top: true,
+            shape: MarkerShape::Crosshair,
+            height: None,
+            group: None,
+        });
+        self.cursor.set_markers(
+            &self.device,
+            cursor.as_slice(),
+            &AnnotationGroups::default(),
+        );
+    }
+
+    /// Shows the picked points of a measurement, dots along its guide lines and fitted geometry,
+    /// which is drawn at the given heights
+    pub(crate) fn set_measurement(
+        &mut self,
+        points: &[[u32; 2]],
+        guide: &[[u32; 2]],
+        fitted: &[([u32; 2], f32)],
+    ) {
+        let dot = |pixel, size, on_top, height: Option<f32>| Marker {
+            pixel,
+            color: if height.is_some() {
+                [0, 220, 255, 255]
+            } else {
+                [255, 200, 0, 255]
+            },
+            size,
+            on_top,
+            shape: MarkerShape::Dot,
+            height,
+            group: None,
+        };
+        let markers: Vec<Marker> = points
+            .iter()
+            .map(|&pixel| dot(pixel, 12.0, true, None))
+            .chain(guide.iter().map(|&pixel| dot(pixel, 4.0, false, None)))
+            .chain(
+                fitted
+                    .iter()
+                    .map(|&(pixel, height)| dot(pixel, 4.0, true, Some(height))),
+            )
+            .collect();
+        self.measurement
+            .set_markers(&self.device, &markers, &AnnotationGroups::default());
+    }
+
+    pub(crate) fn clear_markers(&mut self) {
+        log::info!("Clearing markers");
+        self.markers
+            .set_markers(&self.device, &[], &self.annotation_groups);
+        self.marker_list = Arc::new(Vec::new());
+    }
+
+    /// Ratio of the y to the x spacing of the pixels, for instruments with anisotropic lateral
+    /// sampling. The surface is shown with these proportions and refitted to the viewport.
+    pub(crate) fn set_pixel_aspect(&mut self, aspect: f32) {
+        log::info!("Pixel aspect set to {}", aspect);
+        self.pixel_aspect = aspect;
+        self.update_scale();
+    }
+
+    /// `false` shows every pixel as a square regardless of the pixel aspect
+    pub(crate) fn set_physical_proportions(&mut self, enabled: bool) {
+        log::info!("Physical proportions: {}", enabled);
+        self.physical_proportions = enabled;
+        self.update_scale();
+    }
+
+    pub(crate) fn pixel_aspect(&self) -> f32 {
+        self.pixel_aspect
+    }
+
+    /// Colors the heights at or above `threshold` and below it differently in the height shader,
+    /// `None` shows the colormap only
+    pub(crate) fn set_threshold(&mut self, threshold: Option<f32>) {
+        self.render_settings.show_threshold = u32::from(threshold.is_some());
+        self.render_settings.threshold = threshold.unwrap_or(0.0);
+    }
+
+    pub(crate) fn threshold(&self) -> Option<f32> {
+        (self.render_settings.show_threshold != 0).then_some(self.render_settings.threshold)
+    }
+
+    /// Pixel aspect the surface is shown with, 1 for raw pixels
+    pub(crate) fn display_pixel_aspect(&self) -> f32 {
+        if self.physical_proportions {
+            self.pixel_aspect
+        } else {
+            1.0
+        }
+    }
+
+    /// Clips `fraction` of the depth of the surface in front of the camera, see
+    /// `Projection::set_near_clip`
+    pub(crate) fn set_near_clip(&mut self, fraction: f32) {
+        self.projection.set_near_clip(fraction);
+        log::info!(
+            "Near clip set to {:.0} %",
+            self.projection.get_near_clip() * 100.0
+        );
+    }
+
+    /// Stretches the heights by `scale`, 1.0 shows them as fitted to the surface
+    pub(crate) fn set_z_scale(&mut self, scale: f32) {
+        log::info!("Z scale set to {}", scale);
+        self.z_scale = scale;
+        self.update_scale();
+    }
+
+    fn update_scale(&mut self) {
+        if let Some(texture) = &self.texture {
+            let scale = texture
+                .surface
+                .image_size()
+                .lateral_scale(self.display_pixel_aspect());
+            self.transformation.set_scale(scale.extend(self.z_scale));
+            self.projection.fit(self.transformation.model());
+        }
+    }
+
+    pub(crate) fn reset_camera(&mut self) {
+        self.projection.reset();
+        self.transformation.reset();
+        self.projection.fit(self.transformation.model());
+    }
+
+    /// Sets exact view angles in degrees, see `Transformation::set_orientation`. `zoom` is the
+    /// magnification with 1.0 showing the whole surface, `None` keeps the current zoom.
+    pub(crate) fn set_view(&mut self, azimuth: f32, elevation: f32, zoom: Option<f32>) {
+        self.transformation.set_orientation(azimuth, elevation);
+        if let Some(zoom) = zoom {
+            self.projection.zoom(1.0 / zoom);
+        }
+    }
+
+    pub(crate) fn camera(&self) -> CameraState {
+        CameraState {
+            rotation: self.transformation.get_current(),
+            pan: self.projection.get_pan(),
+            zoom: self.projection.get_zoom(),
+        }
+    }
+
+    pub(crate) fn set_camera(&mut self, camera: CameraState) {
+        self.transformation.set_current(camera.rotation);
+        self.projection.set_pan(camera.pan);
+        self.projection.zoom(camera.zoom);
+    }
+
+    /// CPU copy of the surface, decoded again if it was dropped after the upload
+    pub(crate) fn surface_image(&self) -> Option<Arc<Image<f32>>> {
+        self.texture.as_ref()?.surface.image()
+    }
+
+    pub(crate) fn view_transform(&self) -> Option<ViewTransform> {
+        let (Some(texture), Some(z_range)) = (&self.texture, &self.z_range) else {
+            return None;
+        };
+        Some(ViewTransform::new(
+            texture.surface.image()?,
+            z_range.clone(),
+            self.transformation.model(),
+            self.projection.get_current(),
+            glam::Vec2::new(self.size.width as f32, self.size.height as f32),
+        ))
+    }
+}
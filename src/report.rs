@@ -0,0 +1,175 @@
+use base64::Engine;
+
+use crate::{
+    error::ViewerError,
+    image::RgbaImage,
+    provenance::{self, ProvenanceEntry},
+    residual::{Tolerance, ToleranceEvaluation},
+    units::Units,
+};
+
+/// Share of pixels that may be out of tolerance for a part to pass
+pub const DEFAULT_MAX_FRACTION: f32 = 0.0;
+
+/// Pass/fail report of a tolerance evaluation for QA, written as a self-contained HTML page
+/// with the screenshot embedded
+pub struct ToleranceReport<'a> {
+    pub dataset: &'a str,
+    pub evaluation: ToleranceEvaluation,
+    pub tolerance: Tolerance,
+    /// Largest accepted fraction of pixels out of tolerance
+    pub max_fraction: f32,
+    pub units: Units,
+    pub screenshot: &'a RgbaImage,
+    /// How the surface was loaded and processed, see `ProvenanceLog`
+    pub provenance: &'a [ProvenanceEntry],
+}
+
+impl ToleranceReport<'_> {
+    pub fn to_html(&self) -> Result<String, ViewerError> {
+        let screenshot =
+            base64::engine::general_purpose::STANDARD.encode(self.screenshot.to_png()?);
+        let evaluation = &self.evaluation;
+        let (verdict, color) = if evaluation.passed(self.max_fraction) {
+            ("PASS", "#1a7f37")
+        } else {
+            ("FAIL", "#cf222e")
+        };
+        let rows = [
+            ("Dataset", escape(self.dataset)),
+            (
+                "Tolerance band",
+                format!(
+                    "{} to {}",
+                    self.units.format(self.tolerance.lower),
+                    self.units.format(self.tolerance.upper)
+                ),
+            ),
+            ("Evaluated pixels", evaluation.evaluated.to_string()),
+            (
+                "Out of tolerance",
+                format!(
+                    "{} ({:.3} %): {} above, {} below",
+                    evaluation.out_of_tolerance(),
+                    evaluation.fraction_out_of_tolerance() * 100.0,
+                    evaluation.above,
+                    evaluation.below
+                ),
+            ),
+            (
+                "Largest violation",
+                if evaluation.out_of_tolerance() == 0 {
+                    "none".to_owned()
+                } else {
+                    format!(
+                        "{} at pixel [{}/{}]",
+                        self.units.format(evaluation.largest_violation),
+                        evaluation.largest_violation_x,
+                        evaluation.largest_violation_y
+                    )
+                },
+            ),
+            (
+                "Acceptance",
+                format!(
+                    "at most {:.3} % out of tolerance",
+                    self.max_fraction * 100.0
+                ),
+            ),
+        ];
+        let rows: String = rows
+            .iter()
+            .map(|(name, value)| format!("<tr><th>{}</th><td>{}</td></tr>\n", name, value))
+            .collect();
+        let provenance: String = self
+            .provenance
+            .iter()
+            .map(|entry| {
+                format!(
+                    "<li><time>{}</time> {}</li>\n",
+                    provenance::format_utc(entry.timestamp),
+                    escape(&entry.step)
+                )
+            })
+            .collect();
+        Ok(format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Tolerance report: {dataset}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2rem; }}
+th {{ text-align: left; padding-right: 2rem; }}
+.verdict {{ display: inline-block; padding: 0.25rem 1rem; color: white; background: {color}; font-weight: bold; }}
+img {{ max-width: 100%; margin-top: 1rem; }}
+</style>
+</head>
+<body>
+<h1>Tolerance report</h1>
+<p class="verdict">{verdict}</p>
+<table>
+{rows}</table>
+<h2>Provenance</h2>
+<ul>
+{provenance}</ul>
+<img alt="Deviation from the reference" src="data:image/png;base64,{screenshot}">
+</body>
+</html>
+"#,
+            dataset = escape(self.dataset),
+        ))
+    }
+
+    pub fn write(&self, path: &std::path::Path) -> Result<(), ViewerError> {
+        std::fs::write(path, self.to_html()?)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_embeds_verdict_and_screenshot() {
+        let screenshot = RgbaImage {
+            width: 2,
+            height: 1,
+            pixels: vec![255; 8],
+            color_space: Default::default(),
+        };
+        let report = ToleranceReport {
+            dataset: "part <1>.tiff",
+            evaluation: ToleranceEvaluation {
+                evaluated: 100,
+                above: 2,
+                below: 0,
+                largest_violation: 0.75,
+                largest_violation_x: 3,
+                largest_violation_y: 4,
+            },
+            tolerance: Tolerance::symmetric(0.5),
+            max_fraction: 0.01,
+            units: Units::default(),
+            screenshot: &screenshot,
+            provenance: &[ProvenanceEntry {
+                timestamp: 0.0,
+                step: "Loaded part <1>.tiff".to_owned(),
+            }],
+        };
+        let html = report.to_html().unwrap();
+        assert!(html.contains("FAIL"));
+        assert!(html.contains("part &lt;1&gt;.tiff"));
+        assert!(html.contains("0.750 µm at pixel [3/4]"));
+        assert!(html.contains("data:image/png;base64,iVBOR"));
+        assert!(html.contains("1970-01-01T00:00:00Z</time> Loaded part &lt;1&gt;.tiff"));
+    }
+}
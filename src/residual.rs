@@ -0,0 +1,181 @@
+use anyhow::anyhow;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{image::Image, units::Units};
+
+/// Accepted deviation of the measured surface from the reference in data units.
+/// Residuals within the band are drawn green, above red and below blue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// Lowest accepted residual, usually negative
+    pub lower: f32,
+    /// Highest accepted residual
+    pub upper: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self::symmetric(1.0)
+    }
+}
+
+impl Tolerance {
+    /// Band of ±`tolerance` around the reference
+    pub fn symmetric(tolerance: f32) -> Self {
+        Self {
+            lower: -tolerance.abs(),
+            upper: tolerance.abs(),
+        }
+    }
+
+    pub fn new(lower: f32, upper: f32) -> anyhow::Result<Self> {
+        if !lower.is_finite() || !upper.is_finite() || lower > upper {
+            return Err(anyhow!(
+                "Invalid tolerance band from {} to {}",
+                lower,
+                upper
+            ));
+        }
+        Ok(Self { lower, upper })
+    }
+
+    /// Distance of `residual` outside of the band, zero within
+    fn violation(&self, residual: f32) -> f32 {
+        if residual > self.upper {
+            residual - self.upper
+        } else if residual < self.lower {
+            self.lower - residual
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Pass/fail result of comparing the surface with the reference pixel by pixel
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToleranceEvaluation {
+    /// Pixels that are valid in both the surface and the reference
+    pub evaluated: u32,
+    /// Pixels above the tolerance band
+    pub above: u32,
+    /// Pixels below the tolerance band
+    pub below: u32,
+    /// Residual that is farthest outside of the band, zero if all pixels are within
+    pub largest_violation: f32,
+    /// Column of the largest violation
+    pub largest_violation_x: u32,
+    /// Row of the largest violation
+    pub largest_violation_y: u32,
+}
+
+impl ToleranceEvaluation {
+    /// Both images must have the same size
+    pub(crate) fn evaluate(
+        surface: &Image<f32>,
+        reference: &Image<f32>,
+        tolerance: Tolerance,
+    ) -> Self {
+        let width = surface.size.width.get() as usize;
+        let mut evaluation = Self {
+            evaluated: 0,
+            above: 0,
+            below: 0,
+            largest_violation: 0.0,
+            largest_violation_x: 0,
+            largest_violation_y: 0,
+        };
+        let mut largest_distance = 0.0;
+        let residuals = surface
+            .data
+            .iter()
+            .zip(reference.data.iter())
+            .map(|(measured, nominal)| measured - nominal)
+            .enumerate()
+            .filter(|(_, residual)| residual.is_finite());
+        for (index, residual) in residuals {
+            evaluation.evaluated += 1;
+            if residual > tolerance.upper {
+                evaluation.above += 1;
+            } else if residual < tolerance.lower {
+                evaluation.below += 1;
+            }
+            let distance = tolerance.violation(residual);
+            if distance > largest_distance {
+                largest_distance = distance;
+                evaluation.largest_violation = residual;
+                evaluation.largest_violation_x = (index % width) as u32;
+                evaluation.largest_violation_y = (index / width) as u32;
+            }
+        }
+        evaluation
+    }
+
+    /// Converts the largest violation from the data unit to the display unit
+    pub fn to_display(self, units: Units) -> Self {
+        Self {
+            largest_violation: units.to_display(self.largest_violation),
+            ..self
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl ToleranceEvaluation {
+    pub fn out_of_tolerance(&self) -> u32 {
+        self.above + self.below
+    }
+
+    /// Fraction of the evaluated pixels outside of the tolerance band
+    pub fn fraction_out_of_tolerance(&self) -> f32 {
+        if self.evaluated == 0 {
+            0.0
+        } else {
+            self.out_of_tolerance() as f32 / self.evaluated as f32
+        }
+    }
+
+    /// Passes if at most `max_fraction` of the pixels are out of tolerance
+    pub fn passed(&self, max_fraction: f32) -> bool {
+        self.evaluated > 0 && self.fraction_out_of_tolerance() <= max_fraction
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    #[test]
+    fn tolerance_band() {
+        assert_eq!(
+            Tolerance::symmetric(-0.5),
+            Tolerance::new(-0.5, 0.5).unwrap()
+        );
+        assert!(Tolerance::new(1.0, -1.0).is_err());
+        assert!(Tolerance::new(0.0, f32::NAN).is_err());
+    }
+
+    #[test]
+    fn evaluate_residual() {
+        let size = ImageSize::new(3, 2).unwrap();
+        let reference = Image::new(size.clone(), vec![1.0; 6]).unwrap();
+        let surface = Image::new(size, vec![1.0, 1.4, 2.0, f32::NAN, 0.2, 0.9]).unwrap();
+        let evaluation =
+            ToleranceEvaluation::evaluate(&surface, &reference, Tolerance::symmetric(0.5));
+        assert_eq!(evaluation.evaluated, 5);
+        assert_eq!((evaluation.above, evaluation.below), (1, 1));
+        assert_eq!(evaluation.largest_violation, 1.0);
+        assert_eq!(
+            (
+                evaluation.largest_violation_x,
+                evaluation.largest_violation_y
+            ),
+            (2, 0)
+        );
+        assert!((evaluation.fraction_out_of_tolerance() - 0.4).abs() < 1e-6);
+        assert!(!evaluation.passed(0.0));
+        assert!(evaluation.passed(0.5));
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+
+/// What happens to the CPU copy of an image after it is uploaded to the GPU. The copy is
+/// needed for picking, measurements and statistics, but doubles the memory of large datasets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RetentionPolicy {
+    /// Keep the decoded image in memory
+    #[default]
+    Keep,
+    /// Move the decoded image to a temporary file mapped into memory, so that the operating
+    /// system can page it out
+    MemoryMap,
+    /// Drop the image and decode its file again when it is needed. Images that were not loaded
+    /// from a file are memory-mapped instead.
+    DropAfterUpload,
+}
+
+impl RetentionPolicy {
+    pub const ALL: [Self; 3] = [Self::Keep, Self::MemoryMap, Self::DropAfterUpload];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Keep => "keep",
+            Self::MemoryMap => "map",
+            Self::DropAfterUpload => "drop",
+        }
+    }
+}
+
+impl FromStr for RetentionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RetentionPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.name() == s.to_lowercase())
+            .ok_or(anyhow!("Unsupported retention policy: {}", s))
+    }
+}
+
+/// Bytes in a temporary file mapped into memory, the file is deleted when they are dropped
+#[cfg(not(target_arch = "wasm32"))]
+pub struct MappedFile {
+    map: Option<memmap2::Mmap>,
+    path: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl MappedFile {
+    pub fn new(bytes: &[u8]) -> std::io::Result<Self> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "data-viewer-3d-{}-{}.bin",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, bytes)?;
+        let file = std::fs::File::open(&path)?;
+        // SAFETY: the file is private to this process and never written again while mapped
+        let map = unsafe { memmap2::Mmap::map(&file) };
+        match map {
+            Ok(map) => Ok(Self {
+                map: Some(map),
+                path,
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        self.map.as_deref().unwrap_or_default()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // Unmap first, some platforms refuse to delete mapped files
+        self.map = None;
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_policies() {
+        for policy in RetentionPolicy::ALL {
+            assert_eq!(policy.name().parse::<RetentionPolicy>().unwrap(), policy);
+        }
+        assert!("forever".parse::<RetentionPolicy>().is_err());
+    }
+
+    #[test]
+    fn removes_mapped_file() {
+        let mapped = MappedFile::new(&[1, 2, 3]).unwrap();
+        let path = mapped.path.clone();
+        assert_eq!(mapped.bytes(), &[1, 2, 3]);
+        drop(mapped);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,189 @@
+// Reviewing inspection results steps through the overlays and markers one by one, each framed by
+// the camera, in reading order or by a metadata value like the severity.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use crate::{image::ImageSize, markers::Marker, selection::Selection, texture::Overlay};
+
+/// Pixels shown around a marker when it is framed
+const MARKER_MARGIN: u32 = 10;
+
+/// Order in which the annotations are reviewed, the text form is `position` or the metadata key
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ReviewOrder {
+    /// Row by row from the top left, like reading
+    #[default]
+    Position,
+    /// Highest value of an overlay metadata key first, e.g. `severity`, annotations without it
+    /// come last in reading order
+    Metadata(String),
+}
+
+impl fmt::Display for ReviewOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Position => write!(f, "position"),
+            Self::Metadata(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+impl FromStr for ReviewOrder {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err(anyhow::anyhow!(
+                "Review order needs position or a metadata key"
+            )),
+            "position" => Ok(Self::Position),
+            key => Ok(Self::Metadata(key.to_string())),
+        }
+    }
+}
+
+/// Overlay or marker to review with the pixels framed for it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReviewItem {
+    pub selection: Selection,
+    /// Top left and bottom right pixel
+    pub bounds: [[u32; 2]; 2],
+    /// Metadata value of the review order
+    value: Option<f32>,
+}
+
+impl ReviewItem {
+    pub fn marker(index: usize, marker: &Marker, image_size: &ImageSize) -> Self {
+        let max = [image_size.width.get() - 1, image_size.height.get() - 1];
+        let [x, y] = marker.pixel;
+        Self {
+            selection: Selection::marker(index),
+            bounds: [
+                [
+                    x.saturating_sub(MARKER_MARGIN),
+                    y.saturating_sub(MARKER_MARGIN),
+                ],
+                [
+                    (x + MARKER_MARGIN).min(max[0]),
+                    (y + MARKER_MARGIN).min(max[1]),
+                ],
+            ],
+            value: None,
+        }
+    }
+
+    /// `None` for overlays without pixels
+    pub fn overlay(
+        index: usize,
+        overlay: &Overlay,
+        image_size: &ImageSize,
+        order: &ReviewOrder,
+    ) -> Option<Self> {
+        let width = image_size.width.get();
+        let (mut min, mut max) = ([u32::MAX; 2], [0; 2]);
+        for range in overlay.pixels.iter().filter(|range| !range.is_empty()) {
+            let (first, last) = (range.start, range.end - 1);
+            let (first_row, last_row) = (first / width, last / width);
+            // Ranges continuing on the next row cover its whole width
+            let (left, right) = if first_row == last_row {
+                (first % width, last % width)
+            } else {
+                (0, width - 1)
+            };
+            min = [min[0].min(left), min[1].min(first_row)];
+            max = [max[0].max(right), max[1].max(last_row)];
+        }
+        let value = match order {
+            ReviewOrder::Position => None,
+            ReviewOrder::Metadata(key) => overlay.metadata.get(key).copied(),
+        };
+        (min[0] <= max[0]).then_some(Self {
+            selection: Selection::overlay(index),
+            bounds: [min, max],
+            value,
+        })
+    }
+}
+
+/// Sorts the items in the review order
+pub fn sort(items: &mut [ReviewItem]) {
+    let position = |item: &ReviewItem| (item.bounds[0][1], item.bounds[0][0]);
+    items.sort_by(|a, b| match (a.value, b.value) {
+        (Some(a_value), Some(b_value)) => b_value
+            .partial_cmp(&a_value)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| position(a).cmp(&position(b))),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => position(a).cmp(&position(b)),
+    });
+}
+
+/// The item after `current` in the sorted `items`, before it if not `forward`. Wraps around at
+/// the ends and starts at the first or last item if `current` is not among them.
+pub fn step(items: &[ReviewItem], current: Option<Selection>, forward: bool) -> Option<ReviewItem> {
+    let count = items.len();
+    let index = match items
+        .iter()
+        .position(|item| Some(item.selection) == current)
+    {
+        Some(index) if forward => (index + 1) % count,
+        Some(index) => (index + count - 1) % count,
+        None if forward => 0,
+        None => count.checked_sub(1)?,
+    };
+    items.get(index).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn review_steps_through_the_order_and_wraps_around() {
+        let size = ImageSize::new(10, 10).unwrap();
+        let overlay = |pixels, severity: Option<f32>| Overlay {
+            pixels: vec![pixels, 95..96],
+            color: [255, 0, 0, 255],
+            group: None,
+            metadata: severity
+                .map(|severity| BTreeMap::from([("severity".to_string(), severity)]))
+                .unwrap_or_default(),
+        };
+        let overlays = [
+            overlay(52..54, Some(1.0)),
+            overlay(2..4, None),
+            overlay(30..31, Some(3.0)),
+        ];
+        let order = ReviewOrder::Metadata("severity".to_string());
+        let mut items: Vec<_> = overlays
+            .iter()
+            .enumerate()
+            .filter_map(|(index, overlay)| ReviewItem::overlay(index, overlay, &size, &order))
+            .collect();
+        assert_eq!(items[0].bounds, [[2, 5], [5, 9]]);
+
+        sort(&mut items);
+        let order: Vec<u32> = items.iter().map(|item| item.selection.index).collect();
+        assert_eq!(order, [2, 0, 1]);
+
+        assert_eq!(
+            step(&items, None, true).unwrap().selection,
+            Selection::overlay(2)
+        );
+        assert_eq!(
+            step(&items, Some(Selection::overlay(1)), true)
+                .unwrap()
+                .selection,
+            Selection::overlay(2)
+        );
+        assert_eq!(
+            step(&items, Some(Selection::overlay(2)), false)
+                .unwrap()
+                .selection,
+            Selection::overlay(1)
+        );
+        assert_eq!(step(&[], None, false), None);
+    }
+}
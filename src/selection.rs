@@ -0,0 +1,70 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// Kind of object that can be selected by clicking on it
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionKind {
+    Overlay = 1,
+    Marker = 2,
+}
+
+/// Overlay or marker picked with the mouse, `index` is its position in the list it was set with
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub kind: SelectionKind,
+    pub index: u32,
+}
+
+impl Selection {
+    /// The kind is stored in the top byte of an object ID and the index below it
+    const KIND_SHIFT: u32 = 24;
+    const INDEX_MASK: u32 = (1 << Self::KIND_SHIFT) - 1;
+
+    pub fn overlay(index: usize) -> Self {
+        Self {
+            kind: SelectionKind::Overlay,
+            index: index as u32,
+        }
+    }
+
+    pub fn marker(index: usize) -> Self {
+        Self {
+            kind: SelectionKind::Marker,
+            index: index as u32,
+        }
+    }
+
+    /// ID written to the picking target for the fragments of this object, never 0
+    pub(crate) fn to_id(self) -> u32 {
+        ((self.kind as u32) << Self::KIND_SHIFT) | (self.index & Self::INDEX_MASK)
+    }
+
+    /// Object with the ID read back from the picking target, `None` for 0 where nothing was hit
+    pub(crate) fn from_id(id: u32) -> Option<Self> {
+        let kind = match id >> Self::KIND_SHIFT {
+            1 => SelectionKind::Overlay,
+            2 => SelectionKind::Marker,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            index: id & Self::INDEX_MASK,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn id_round_trip() {
+        for selection in [Selection::overlay(0), Selection::marker(41)] {
+            assert_ne!(selection.to_id(), 0);
+            assert_eq!(Selection::from_id(selection.to_id()), Some(selection));
+        }
+        assert_eq!(Selection::from_id(0), None);
+    }
+}
@@ -1,5 +1,6 @@
 struct VertexInput {
     @location(0) index: u32,
+    @location(1) valid: u32,
 }
 @group(0) @binding(0)
 var surface_texture: texture_2d<f32>;
@@ -7,6 +8,11 @@ var surface_texture: texture_2d<f32>;
 var amplitude_texture: texture_2d<u32>;
 @group(0) @binding(2)
 var overlay_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var reference_texture: texture_2d<f32>;
+// Object ID of the topmost overlay per pixel, 0 without overlay
+@group(0) @binding(4)
+var overlay_id_texture: texture_2d<u32>;
 
 struct ImageDimensions {
     width: u32,
@@ -25,6 +31,46 @@ var<uniform> z_range: ZValueRange;
 @group(1) @binding(2)
 var<uniform> mip_level: u32;
 
+struct RenderSettings {
+    cut_holes: u32,
+    ui_scale: f32,
+    viewport: vec2<f32>,
+    // 0: classic, 1: viridis, 2: blue-yellow
+    colormap: u32,
+    // Same indices as `colormap`, for the amplitude shader
+    amplitude_colormap: u32,
+    // Lower and upper limit of the residual tolerance band
+    tolerance: vec2<f32>,
+    // Color vision deficiency simulation and conversion from linear sRGB to the linear RGB of
+    // the output color space
+    color_matrix: mat3x3<f32>,
+    // Amplitudes at the ends of the amplitude colormap
+    amplitude_range: vec2<f32>,
+    // Heights at the ends of the height colormap, within `z_range`
+    height_range: vec2<f32>,
+    // Object ID of the highlighted overlay or marker, 0 for none
+    selected_id: u32,
+    // 0 hides the overlays
+    show_overlays: u32,
+    // 1 colors the heights at or above `threshold` and below it differently
+    show_threshold: u32,
+    threshold: f32,
+    // 0: none, 1: the other layer sets the brightness, 2: its colors are mixed in
+    layer_blend: u32,
+    // From 0, only the shown layer, to 1, the full effect of the other layer
+    blend_weight: f32,
+    // 1 dims everything outside the selected overlay
+    dim_outside_selection: u32,
+    // Tile size in pixels of the checkerboard comparison with the reference, 0 for none
+    checker_size: u32,
+    // Vertex step of the coarser mesh the heights fade from while refining, 0 for none
+    morph_step: u32,
+    // From 0, the heights of the coarser mesh, to 1, the heights of the pixels
+    morph: f32,
+}
+@group(1) @binding(3)
+var<uniform> settings: RenderSettings;
+
 struct TransformationInput {
     col0: vec4<f32>,
     col1: vec4<f32>,
@@ -48,14 +94,16 @@ struct VertexOutput {
     @location(0) @interpolate(flat) pixel: vec2<u32>,
     @location(1) z_value: f32,
     @location(2) @interpolate(flat) resize: u32,
+    // 1.0 inside valid data, drops below 1.0 on triangles touching a missing pixel
+    @location(3) valid: f32,
 }
 
 // Fragment output with two render targets:
 // - location(0): visible color
-// - location(1): picking data (pixel_x, pixel_y)
+// - location(1): picking data (pixel_x, pixel_y, object ID, unused)
 struct FragmentOutput {
     @location(0) color: vec4<f32>,
-    @location(1) picking: vec2<u32>,
+    @location(1) picking: vec4<u32>,
 }
 
 @vertex
@@ -67,51 +115,218 @@ fn vs_main(data: VertexInput) -> VertexOutput {
     let x = 2.0 * f32(col) / f32(image_dims.width / resize - 1u) - 1.0;
     let y = 1.0 - 2.0 * f32(row) / f32(image_dims.height / resize - 1u);
     let z_value = textureLoad(surface_texture, vec2<u32>(col, row), i32(mip_level));
-    let z_clamped = clamp(z_value.x, z_range.min, z_range.max);
+    // Missing pixels are placed on the lowest level so they do not produce spikes
+    var z_clamped = z_range.min;
+    if (data.valid == 1u) {
+        z_clamped = clamp(z_value.x, z_range.min, z_range.max);
+        if (settings.morph_step > 1u) {
+            let coarse = coarse_height(data.index, settings.morph_step, resize);
+            z_clamped = mix(coarse, z_clamped, settings.morph);
+        }
+    }
     let z = 1.0 - (z_clamped - z_range.min) / (z_range.max - z_range.min);
     let points = vec4<f32>(x, y, z, 1.0);
 
+    var out: VertexOutput;
+    out.position = project(points);
+    out.pixel = vec2<u32>(col, row);
+    out.z_value = z_clamped;
+    out.resize = resize;
+    out.valid = f32(data.valid);
 
-    let transformation_matrix = mat4x4<f32>(
+    return out;
+}
+
+// Height of the pixel on the bilinear patch between the vertices of the coarser mesh with a
+// vertex every `step` pixels
+fn coarse_height(index: u32, step: u32, resize: u32) -> f32 {
+    let pixel = vec2<u32>(index % image_dims.width, index / image_dims.width);
+    let last = vec2<u32>(image_dims.width - 1u, image_dims.height - 1u);
+    let lower = pixel - pixel % step;
+    let upper = min(lower + step, last);
+    let t = vec2<f32>(pixel - lower) / vec2<f32>(max(upper - lower, vec2<u32>(1u)));
+    let level = i32(mip_level);
+    let h00 = textureLoad(surface_texture, lower / resize, level).x;
+    let h10 = textureLoad(surface_texture, vec2<u32>(upper.x, lower.y) / resize, level).x;
+    let h01 = textureLoad(surface_texture, vec2<u32>(lower.x, upper.y) / resize, level).x;
+    let h11 = textureLoad(surface_texture, upper / resize, level).x;
+    let height = mix(mix(h00, h10, t.x), mix(h01, h11, t.x), t.y);
+    return clamp(height, z_range.min, z_range.max);
+}
+
+fn transformation_matrix() -> mat4x4<f32> {
+    return mat4x4<f32>(
         transformation.col0,
         transformation.col1,
         transformation.col2,
         transformation.col3
     );
-    let projection_matrix = mat4x4<f32>(
+}
+
+fn projection_matrix() -> mat4x4<f32> {
+    return mat4x4<f32>(
         projection.col0,
         projection.col1,
         projection.col2,
         projection.col3
     );
-    let world_position = transformation_matrix * points;
-    let projected_position = projection_matrix * world_position;
+}
 
-    var out: VertexOutput;
-    out.position = projected_position;
-    out.pixel = vec2<u32>(col, row);
-    out.z_value = z_clamped;
-    out.resize = resize;
+fn project(points: vec4<f32>) -> vec4<f32> {
+    let world_position = transformation_matrix() * points;
+    return projection_matrix() * world_position;
+}
 
-    return out;
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return pow(color, vec3<f32>(2.2));
+}
+
+// Polynomial fit of matplotlib's viridis in sRGB
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3<f32>(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3<f32>(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3<f32>(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3<f32>(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3<f32>(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3<f32>(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+// Dark blue over gray to yellow in sRGB
+fn blue_yellow(t: f32) -> vec3<f32> {
+    let blue = vec3<f32>(0.0, 0.125, 0.302);
+    let gray = vec3<f32>(0.486, 0.482, 0.471);
+    let yellow = vec3<f32>(0.996, 0.910, 0.218);
+    if (t < 0.5) {
+        return mix(blue, gray, t * 2.0);
+    }
+    return mix(gray, yellow, t * 2.0 - 1.0);
+}
+
+// Maps a value normalized to [0, 1] to a linear color, `classic` is used for the classic colormap
+fn apply_colormap(colormap: u32, t: f32, classic: vec3<f32>) -> vec3<f32> {
+    let clamped = clamp(t, 0.0, 1.0);
+    switch colormap {
+        case 1u: {
+            return srgb_to_linear(clamp(viridis(clamped), vec3<f32>(0.0), vec3<f32>(1.0)));
+        }
+        case 2u: {
+            return srgb_to_linear(blue_yellow(clamped));
+        }
+        default: {
+            return classic;
+        }
+    }
+}
+
+// Linear sRGB to the color space of the display or exported image
+fn output_color(color: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(clamp(settings.color_matrix * color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
+
+fn discard_invalid(in: VertexOutput) {
+    if (settings.cut_holes == 1u && in.valid < 1.0) {
+        discard;
+    }
+}
+
+// Amplitude normalized to its display range
+fn amplitude_t(in: VertexOutput) -> f32 {
+    let sampled = textureLoad(amplitude_texture, in.pixel * in.resize, 0);
+    let range = settings.amplitude_range;
+    return clamp((f32(sampled.r) - range.x) / max(range.y - range.x, 1e-6), 0.0, 1.0);
+}
+
+// Modulates the color of the shown layer by the other layer, normalized to `t`, whose colormap
+// maps it to `other`
+fn blend_layers(color: vec3<f32>, t: f32, other: vec3<f32>) -> vec3<f32> {
+    switch settings.layer_blend {
+        case 1u: {
+            return color * mix(1.0, t, settings.blend_weight);
+        }
+        case 2u: {
+            return mix(color, other, settings.blend_weight);
+        }
+        default: {
+            return color;
+        }
+    }
 }
 
 @fragment
 fn fs_amplitude(in: VertexOutput) -> FragmentOutput {
-    let sampled = textureLoad(amplitude_texture, in.pixel * in.resize, 0);
+    discard_invalid(in);
+    let t = amplitude_t(in);
+    var color = apply_colormap(settings.amplitude_colormap, t, vec3<f32>(1.0 - t, t, 0.0));
+    if (settings.layer_blend != 0u) {
+        let range = settings.height_range;
+        let depth = clamp((in.z_value - range.x) / max(range.y - range.x, 1e-12), 0.0, 1.0);
+        color = blend_layers(color, depth, apply_colormap(settings.colormap, depth, vec3<f32>(depth)));
+    }
+    color = dim_outside_selection(color, in.pixel * in.resize);
     var out: FragmentOutput;
-    out.color = vec4<f32>(1.0 - f32(sampled.r) / 4000.0, f32(sampled.r) / 4000.0, 0.0, 1.0);
-    out.picking = vec2<u32>(in.pixel.x * in.resize, in.pixel.y * in.resize);
+    out.color = output_color(vec4<f32>(color, 1.0));
+    out.picking = vec4<u32>(in.pixel * in.resize, 0u, 0u);
     return out;
 }
 
+// How much the selected overlay is lightened towards white
+const SELECTION_HIGHLIGHT: f32 = 0.4;
+// How much the surface outside the selected overlay is darkened and desaturated
+const OUTSIDE_SELECTION_DIMMING: f32 = 0.7;
+
+// Focuses on the selected overlay as region of interest by dimming the surface outside of it.
+// The ID texture holds the topmost overlay, parts of the selection covered by another overlay
+// count as outside.
+fn dim_outside_selection(color: vec3<f32>, pixel: vec2<u32>) -> vec3<f32> {
+    // Object IDs of overlays have kind 1 in their top byte, see `Selection::to_id`
+    if (settings.dim_outside_selection == 0u || (settings.selected_id >> 24u) != 1u) {
+        return color;
+    }
+    if (textureLoad(overlay_id_texture, pixel, 0).r == settings.selected_id) {
+        return color;
+    }
+    let gray = vec3<f32>(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    return mix(color, gray * 0.3, OUTSIDE_SELECTION_DIMMING);
+}
+
 @fragment
-fn fs_height(in: VertexOutput) -> FragmentOutput {    
-    let overlay_color = textureLoad(overlay_texture, in.pixel * in.resize, 0);
+fn fs_height(in: VertexOutput) -> FragmentOutput {
+    discard_invalid(in);
+    let overlay_color = select(
+        vec4<f32>(0.0),
+        textureLoad(overlay_texture, in.pixel * in.resize, 0),
+        settings.show_overlays != 0u
+    );
     
     // Calculate base height color
-    let depth = (in.z_value - z_range.min) / (z_range.max - z_range.min);
-    var color = vec4<f32>(depth, depth, depth, 1.0);
+    let range = settings.height_range;
+    let depth = (in.z_value - range.x) / max(range.y - range.x, 1e-12);
+    var color = vec4<f32>(apply_colormap(settings.colormap, depth, vec3<f32>(depth)), 1.0);
+    if (settings.layer_blend != 0u) {
+        let t = amplitude_t(in);
+        let amplitude = apply_colormap(settings.amplitude_colormap, t, vec3<f32>(1.0 - t, t, 0.0));
+        color = vec4<f32>(blend_layers(color.rgb, t, amplitude), 1.0);
+    }
+
+    // Registration check: every other tile shows the reference with the same colormap, so that
+    // misaligned features break at the tile borders
+    if (settings.checker_size != 0u) {
+        let tile = in.pixel * in.resize / settings.checker_size;
+        let reference = textureLoad(reference_texture, in.pixel * in.resize, 0).x;
+        if ((tile.x + tile.y) % 2u == 1u && reference == reference) {
+            let t = (reference - range.x) / max(range.y - range.x, 1e-12);
+            color = vec4<f32>(apply_colormap(settings.colormap, t, vec3<f32>(t)), 1.0);
+        }
+    }
+
+    // Binarization preview: orange at or above the threshold, blue below, shaded by the colormap
+    if (settings.show_threshold != 0u) {
+        let above = in.z_value >= settings.threshold;
+        let tint = select(vec3<f32>(0.15, 0.35, 0.85), vec3<f32>(0.95, 0.55, 0.1), above);
+        color = vec4<f32>(mix(tint, color.rgb, 0.25), 1.0);
+    }
     
     // Blend overlay if present (alpha > 0)
     if (overlay_color.a > 0.0) {
@@ -123,8 +338,259 @@ fn fs_height(in: VertexOutput) -> FragmentOutput {
         );
     }
     
+    let object_id = select(0u, textureLoad(overlay_id_texture, in.pixel * in.resize, 0).r, overlay_color.a > 0.0);
+    if (object_id != 0u && object_id == settings.selected_id) {
+        color = vec4<f32>(mix(color.rgb, vec3<f32>(1.0), SELECTION_HIGHLIGHT), 1.0);
+    }
+    color = vec4<f32>(dim_outside_selection(color.rgb, in.pixel * in.resize), 1.0);
+
+    var out: FragmentOutput;
+    out.color = output_color(color);
+    out.picking = vec4<u32>(in.pixel * in.resize, object_id, 0u);
+    return out;
+}
+
+// Deviation from the reference surface: green within the tolerance band, red above, blue below
+@fragment
+fn fs_residual(in: VertexOutput) -> FragmentOutput {
+    discard_invalid(in);
+    let pixel = in.pixel * in.resize;
+    let measured = textureLoad(surface_texture, pixel, 0).x;
+    let reference = textureLoad(reference_texture, pixel, 0).x;
+    let residual = measured - reference;
+    let band = max(settings.tolerance.y - settings.tolerance.x, 1e-12);
+
+    var color = vec3<f32>(0.3);
+    if (residual > settings.tolerance.y) {
+        // Saturates one band width outside of the tolerance
+        let t = clamp((residual - settings.tolerance.y) / band, 0.0, 1.0);
+        color = mix(vec3<f32>(0.8, 0.3, 0.3), vec3<f32>(0.8, 0.0, 0.0), t);
+    } else if (residual < settings.tolerance.x) {
+        let t = clamp((settings.tolerance.x - residual) / band, 0.0, 1.0);
+        color = mix(vec3<f32>(0.3, 0.3, 0.8), vec3<f32>(0.0, 0.0, 0.8), t);
+    } else if (residual == residual) {
+        // Brighter towards the nominal value, NaN residuals stay gray
+        let center = (settings.tolerance.x + settings.tolerance.y) * 0.5;
+        let t = abs(residual - center) / (band * 0.5);
+        color = mix(vec3<f32>(0.1, 0.8, 0.1), vec3<f32>(0.05, 0.4, 0.05), t);
+    }
+    color = dim_outside_selection(color, pixel);
+
+    var out: FragmentOutput;
+    out.color = output_color(vec4<f32>(color, 1.0));
+    out.picking = vec4<u32>(in.pixel * in.resize, 0u, 0u);
+    return out;
+}
+
+struct MarkerInput {
+    @location(0) pixel: vec2<u32>,
+    @location(1) color: vec4<f32>,
+    @location(2) size: f32,
+    // 0: dot, 1: crosshair
+    @location(3) shape: u32,
+    // Object ID for picking, 0 if the marker cannot be selected
+    @location(4) id: u32,
+    // Drawn at `height` instead of on the surface if `has_height` is 1
+    @location(5) height: f32,
+    @location(6) has_height: u32,
+}
+
+struct MarkerOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) pixel: vec2<u32>,
+    @location(1) @interpolate(flat) color: vec4<f32>,
+    // position within the marker quad from (-1, -1) to (1, 1)
+    @location(2) corner: vec2<f32>,
+    @location(3) @interpolate(flat) shape: u32,
+    @location(4) @interpolate(flat) id: u32,
+}
+
+// Moves markers slightly towards the camera so they are not hidden by the surface they sit on,
+// with reverse-Z that is towards larger depths
+const MARKER_DEPTH_OFFSET: f32 = 0.002;
+// Selected markers are drawn larger so they stand out
+const SELECTED_MARKER_SCALE: f32 = 1.5;
+
+fn is_selected_marker(id: u32) -> bool {
+    return id != 0u && id == settings.selected_id;
+}
+
+@vertex
+fn vs_marker(@builtin(vertex_index) vertex_index: u32, marker: MarkerInput) -> MarkerOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    // Place the marker on the same mip level the surface is drawn with
+    let resize = max(mip_level * 2u, 1u);
+    let col = marker.pixel.x / resize;
+    let row = marker.pixel.y / resize;
+    let x = 2.0 * f32(col) / f32(image_dims.width / resize - 1u) - 1.0;
+    let y = 1.0 - 2.0 * f32(row) / f32(image_dims.height / resize - 1u);
+    let z_value = textureLoad(surface_texture, vec2<u32>(col, row), i32(mip_level));
+    let z_clamped = clamp(z_value.x, z_range.min, z_range.max);
+    // Explicit heights, e.g. of fitted geometry, may lie outside the surface
+    let height = select(z_clamped, marker.height, marker.has_height == 1u);
+    let z = 1.0 - (height - z_range.min) / (z_range.max - z_range.min);
+    let center = project(vec4<f32>(x, y, z, 1.0));
+
+    // Offset the corners in screen space so markers keep their size while zooming
+    let scale = select(1.0, SELECTED_MARKER_SCALE, is_selected_marker(marker.id));
+    let offset = corner * marker.size * scale * settings.ui_scale / settings.viewport * center.w;
+
+    var out: MarkerOutput;
+    out.position = vec4<f32>(
+        center.xy + offset,
+        center.z + MARKER_DEPTH_OFFSET * center.w,
+        center.w
+    );
+    out.pixel = marker.pixel;
+    out.color = marker.color;
+    out.corner = corner;
+    out.shape = marker.shape;
+    out.id = marker.id;
+    return out;
+}
+
+@fragment
+fn fs_marker(in: MarkerOutput) -> FragmentOutput {
+    var rim = 1.0;
+    if (in.shape == 1u) {
+        // Crosshair with a gap in the middle so the feature under it stays visible
+        let offset = abs(in.corner);
+        let distance = min(offset.x, offset.y);
+        if (distance > 0.12 || max(offset.x, offset.y) < 0.3) {
+            discard;
+        }
+        rim = select(1.0, 0.2, distance > 0.05);
+    } else {
+        let distance = length(in.corner);
+        if (distance > 1.0) {
+            discard;
+        }
+        // Darker rim keeps markers visible on surfaces of similar color
+        rim = select(1.0, 0.4, distance > 0.75);
+    }
+    var color = in.color.rgb * rim;
+    if (is_selected_marker(in.id) && rim < 1.0) {
+        // White rim around the selected marker
+        color = vec3<f32>(1.0);
+    }
+    var out: FragmentOutput;
+    out.color = output_color(vec4<f32>(color, 1.0));
+    out.picking = vec4<u32>(in.pixel, in.id, 0u);
+    return out;
+}
+
+// Debug visualizations, see `DebugView`
+
+struct DebugLineOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+// Length of the normal lines in world units, the surface spans 2 in x
+const NORMAL_LENGTH: f32 = 0.05;
+// Vertices along each side of the grid the normals are drawn on, `DebugView::NORMAL_GRID`
+const NORMAL_GRID: u32 = 48u;
+// Color added by every triangle covering a pixel of the overdraw heatmap
+const OVERDRAW_STEP: vec3<f32> = vec3<f32>(0.06, 0.025, 0.008);
+
+// Linear color of a mip level, green for full resolution over yellow to red
+fn level_color(level: u32) -> vec3<f32> {
+    var colors = array<vec3<f32>, 4>(
+        vec3<f32>(0.1, 0.9, 0.1),
+        vec3<f32>(0.9, 0.9, 0.1),
+        vec3<f32>(0.9, 0.4, 0.05),
+        vec3<f32>(0.9, 0.05, 0.05),
+    );
+    return colors[min(level, 3u)];
+}
+
+// Position of a vertex of the mesh at the current mip level in model space, as in `vs_main`
+fn grid_point(col: u32, row: u32) -> vec4<f32> {
+    let resize = max(mip_level * 2u, 1u);
+    let x = 2.0 * f32(col) / f32(image_dims.width / resize - 1u) - 1.0;
+    let y = 1.0 - 2.0 * f32(row) / f32(image_dims.height / resize - 1u);
+    let height = textureLoad(surface_texture, vec2<u32>(col, row), i32(mip_level)).x;
+    let z_clamped = clamp(height, z_range.min, z_range.max);
+    let z = 1.0 - (z_clamped - z_range.min) / (z_range.max - z_range.min);
+    return vec4<f32>(x, y, z, 1.0);
+}
+
+// The 12 edges of the box the surface fills, 2 vertices each
+@vertex
+fn vs_bounds(@builtin(vertex_index) vertex_index: u32) -> DebugLineOutput {
+    // Corners with the x, y and z bit set at the upper end of the box
+    var edges = array<vec2<u32>, 12>(
+        vec2<u32>(0u, 1u), vec2<u32>(2u, 3u), vec2<u32>(4u, 5u), vec2<u32>(6u, 7u),
+        vec2<u32>(0u, 2u), vec2<u32>(1u, 3u), vec2<u32>(4u, 6u), vec2<u32>(5u, 7u),
+        vec2<u32>(0u, 4u), vec2<u32>(1u, 5u), vec2<u32>(2u, 6u), vec2<u32>(3u, 7u),
+    );
+    let corner = edges[vertex_index / 2u][vertex_index % 2u];
+    let point = vec4<f32>(
+        select(-1.0, 1.0, (corner & 1u) != 0u),
+        select(-1.0, 1.0, (corner & 2u) != 0u),
+        f32((corner >> 2u) & 1u),
+        1.0
+    );
+    var out: DebugLineOutput;
+    out.position = project(point);
+    out.color = level_color(mip_level);
+    return out;
+}
+
+// One line per instance from a vertex of the grid along the normal of the surface there
+@vertex
+fn vs_normals(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> DebugLineOutput {
+    let resize = max(mip_level * 2u, 1u);
+    let last = vec2<u32>(image_dims.width / resize - 1u, image_dims.height / resize - 1u);
+    let grid = vec2<u32>(instance_index % NORMAL_GRID, instance_index / NORMAL_GRID);
+    let pixel = grid * last / (NORMAL_GRID - 1u);
+    let model = transformation_matrix();
+    let left = model * grid_point(max(pixel.x, 1u) - 1u, pixel.y);
+    let right = model * grid_point(min(pixel.x + 1u, last.x), pixel.y);
+    let up = model * grid_point(pixel.x, max(pixel.y, 1u) - 1u);
+    let down = model * grid_point(pixel.x, min(pixel.y + 1u, last.y));
+    var normal = cross(right.xyz - left.xyz, down.xyz - up.xyz);
+    // Point away from the material, heights grow towards lower z in model space
+    if (dot(normal, (model * vec4<f32>(0.0, 0.0, -1.0, 0.0)).xyz) < 0.0) {
+        normal = -normal;
+    }
+    if (length(normal) > 0.0) {
+        normal = normalize(normal);
+    }
+    let start = model * grid_point(pixel.x, pixel.y);
+    let end = start.xyz + normal * NORMAL_LENGTH * f32(vertex_index);
+    var out: DebugLineOutput;
+    out.position = projection_matrix() * vec4<f32>(end, 1.0);
+    out.color = srgb_to_linear(normal * 0.5 + 0.5);
+    return out;
+}
+
+@fragment
+fn fs_debug_line(in: DebugLineOutput) -> FragmentOutput {
+    var out: FragmentOutput;
+    out.color = vec4<f32>(in.color, 1.0);
+    out.picking = vec4<u32>(0u);
+    return out;
+}
+
+// Adds up the triangles covering each pixel, the picking target isn't written
+@fragment
+fn fs_overdraw(in: VertexOutput) -> FragmentOutput {
+    discard_invalid(in);
     var out: FragmentOutput;
-    out.color = color;
-    out.picking = vec2<u32>(in.pixel.x * in.resize, in.pixel.y * in.resize);
+    out.color = vec4<f32>(OVERDRAW_STEP, 1.0);
+    out.picking = vec4<u32>(in.pixel, 0u, 0u);
     return out;
-}
\ No newline at end of file
+}
@@ -0,0 +1,167 @@
+// Statistics kernels with explicit SIMD for the pixels of large images. On x86_64 the widest
+// instruction set of the CPU is picked at runtime. WebAssembly can't detect features at runtime
+// and uses SIMD128 only if the build enables it with `-C target-feature=+simd128`. Other targets
+// use the scalar kernel, which the compiler vectorizes as far as it can.
+
+/// Smallest and largest value ignoring NaN, `None` if there are only NaN values
+pub fn min_max(data: &[f32]) -> Option<(f32, f32)> {
+    #[cfg(target_arch = "x86_64")]
+    let (min, max) = x86::min_max(data);
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    let (min, max) = wasm::min_max(data);
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        all(target_arch = "wasm32", target_feature = "simd128")
+    )))]
+    let (min, max) = min_max_scalar(data);
+    (min <= max).then_some((min, max))
+}
+
+/// `f32::min` and `f32::max` return the other value if one is NaN
+fn min_max_scalar(data: &[f32]) -> (f32, f32) {
+    data.iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        })
+}
+
+/// Reduces the lanes of the vector kernels and the values after the last full vector
+#[cfg_attr(
+    not(any(
+        target_arch = "x86_64",
+        all(target_arch = "wasm32", target_feature = "simd128")
+    )),
+    allow(dead_code)
+)]
+fn reduce(mins: &[f32], maxs: &[f32], rest: &[f32]) -> (f32, f32) {
+    let (min, max) = min_max_scalar(rest);
+    (
+        mins.iter().fold(min, |min, &value| min.min(value)),
+        maxs.iter().fold(max, |max, &value| max.max(value)),
+    )
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    pub fn min_max(data: &[f32]) -> (f32, f32) {
+        if is_x86_feature_detected!("avx") {
+            // SAFETY: the CPU supports AVX
+            unsafe { min_max_avx(data) }
+        } else {
+            // SAFETY: SSE is part of every x86_64 CPU
+            unsafe { min_max_sse(data) }
+        }
+    }
+
+    #[target_feature(enable = "sse")]
+    pub(super) fn min_max_sse(data: &[f32]) -> (f32, f32) {
+        let chunks = data.chunks_exact(4);
+        let rest = chunks.remainder();
+        let mut min = _mm_set1_ps(f32::INFINITY);
+        let mut max = _mm_set1_ps(f32::NEG_INFINITY);
+        for chunk in chunks {
+            // SAFETY: the chunk holds 4 values, the load needs no alignment
+            let values = unsafe { _mm_loadu_ps(chunk.as_ptr()) };
+            // The second operand is returned if one is NaN, so NaN values are skipped
+            min = _mm_min_ps(values, min);
+            max = _mm_max_ps(values, max);
+        }
+        let (mut mins, mut maxs) = ([0.0; 4], [0.0; 4]);
+        // SAFETY: the arrays hold 4 values, the store needs no alignment
+        unsafe {
+            _mm_storeu_ps(mins.as_mut_ptr(), min);
+            _mm_storeu_ps(maxs.as_mut_ptr(), max);
+        }
+        super::reduce(&mins, &maxs, rest)
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) fn min_max_avx(data: &[f32]) -> (f32, f32) {
+        let chunks = data.chunks_exact(8);
+        let rest = chunks.remainder();
+        let mut min = _mm256_set1_ps(f32::INFINITY);
+        let mut max = _mm256_set1_ps(f32::NEG_INFINITY);
+        for chunk in chunks {
+            // SAFETY: the chunk holds 8 values, the load needs no alignment
+            let values = unsafe { _mm256_loadu_ps(chunk.as_ptr()) };
+            min = _mm256_min_ps(values, min);
+            max = _mm256_max_ps(values, max);
+        }
+        let (mut mins, mut maxs) = ([0.0; 8], [0.0; 8]);
+        // SAFETY: the arrays hold 8 values, the store needs no alignment
+        unsafe {
+            _mm256_storeu_ps(mins.as_mut_ptr(), min);
+            _mm256_storeu_ps(maxs.as_mut_ptr(), max);
+        }
+        super::reduce(&mins, &maxs, rest)
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod wasm {
+    use std::arch::wasm32::*;
+
+    pub fn min_max(data: &[f32]) -> (f32, f32) {
+        let chunks = data.chunks_exact(4);
+        let rest = chunks.remainder();
+        let mut min = f32x4_splat(f32::INFINITY);
+        let mut max = f32x4_splat(f32::NEG_INFINITY);
+        for chunk in chunks {
+            let values = f32x4(chunk[0], chunk[1], chunk[2], chunk[3]);
+            // The pseudo-minimum and -maximum keep the first operand if one is NaN
+            min = f32x4_pmin(min, values);
+            max = f32x4_pmax(max, values);
+        }
+        let lanes = |vector| {
+            [
+                f32x4_extract_lane::<0>(vector),
+                f32x4_extract_lane::<1>(vector),
+                f32x4_extract_lane::<2>(vector),
+                f32x4_extract_lane::<3>(vector),
+            ]
+        };
+        super::reduce(&lanes(min), &lanes(max), rest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kernels_match_the_scalar_kernel() {
+        // Lengths around the vector widths and NaN in every lane
+        for len in [0, 1, 3, 4, 7, 8, 9, 17, 100] {
+            let data: Vec<f32> = (0..len)
+                .map(|i| match i % 5 {
+                    0 => f32::NAN,
+                    _ => ((i * 37) % 23) as f32 - 11.0,
+                })
+                .collect();
+            let expected = min_max_scalar(&data);
+            #[cfg(target_arch = "x86_64")]
+            {
+                assert_eq!(
+                    unsafe { x86::min_max_sse(&data) },
+                    expected,
+                    "{} values",
+                    len
+                );
+                if is_x86_feature_detected!("avx") {
+                    assert_eq!(unsafe { x86::min_max_avx(&data) }, expected);
+                }
+            }
+            assert_eq!(
+                min_max(&data),
+                (expected.0 <= expected.1).then_some(expected)
+            );
+        }
+        assert_eq!(min_max(&[f32::NAN; 9]), None);
+        assert_eq!(
+            min_max(&[1.0, f32::INFINITY, -2.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            Some((-2.0, f32::INFINITY))
+        );
+    }
+}
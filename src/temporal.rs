@@ -0,0 +1,368 @@
+// Repeatability studies measure the same part many times. The per-pixel mean and standard
+// deviation of such a sequence are accumulated on the GPU as the frames are loaded, e.g. from a
+// watched directory or with `batch --temporal`, and can then be shown instead of the surface.
+
+use std::{borrow::Cow, future::Future, pin::Pin, sync::Arc};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    error::ViewerError,
+    image::{Image, ImageSize},
+    texture::SurfaceTexture,
+};
+
+/// Map of the temporal statistics that can be shown as the surface
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TemporalMap {
+    Mean,
+    StdDev,
+}
+
+impl TemporalMap {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mean => "mean",
+            Self::StdDev => "standard deviation",
+        }
+    }
+}
+
+/// Per-pixel statistics of the frames so far, pixels without enough valid values are NaN
+pub struct TemporalMaps {
+    pub mean: Image<f32>,
+    /// Sample standard deviation
+    pub std_dev: Image<f32>,
+    pub frames: u32,
+}
+
+impl TemporalMaps {
+    pub fn take(self, map: TemporalMap) -> Image<f32> {
+        match map {
+            TemporalMap::Mean => self.mean,
+            TemporalMap::StdDev => self.std_dev,
+        }
+    }
+}
+
+pub type TemporalFuture = Pin<Box<dyn Future<Output = Result<TemporalMaps, ViewerError>>>>;
+
+/// Buffers of the per-pixel moments for frames of one size
+struct Moments {
+    image_size: ImageSize,
+    bind_group: wgpu::BindGroup,
+    maps: wgpu::Buffer,
+}
+
+/// Accumulates the frames of a sequence on the GPU
+pub struct TemporalStats {
+    /// New frames are only added while collecting
+    pub collecting: bool,
+    accumulate: wgpu::ComputePipeline,
+    finish: wgpu::ComputePipeline,
+    moments_layout: wgpu::BindGroupLayout,
+    frame_layout: wgpu::BindGroupLayout,
+    moments: Option<Moments>,
+    frames: u32,
+}
+
+impl TemporalStats {
+    const WORKGROUP_SIZE: u32 = 8;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("temporal_shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("temporal.wgsl"))),
+        });
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let moments_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("temporal_moments_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+                storage_entry(2),
+                storage_entry(3),
+                storage_entry(4),
+            ],
+        });
+        let frame_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("temporal_frame_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+        });
+        let pipeline = |entry_point, layouts: &[&wgpu::BindGroupLayout]| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("temporal_pipeline_layout"),
+                bind_group_layouts: layouts,
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&layout),
+                module: &shader,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        Self {
+            collecting: true,
+            accumulate: pipeline("accumulate", &[&moments_layout, &frame_layout]),
+            finish: pipeline("finish", &[&moments_layout]),
+            moments_layout,
+            frame_layout,
+            moments: None,
+            frames: 0,
+        }
+    }
+
+    /// Frames added so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Adds the surface as the next frame. A surface of another size starts the statistics anew.
+    pub fn add(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface: &SurfaceTexture,
+    ) -> Result<(), ViewerError> {
+        self.add_view(device, queue, &surface.view, surface.image_size())
+    }
+
+    /// Uploads the image and adds it as the next frame, for a device other than the one the
+    /// surface texture lives on
+    pub fn add_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &Image<f32>,
+    ) -> Result<(), ViewerError> {
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("temporal_frame_texture"),
+                size: wgpu::Extent3d {
+                    width: image.size.width.get(),
+                    height: image.size.height.get(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(&image.data),
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.add_view(device, queue, &view, &image.size)
+    }
+
+    fn add_view(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        view: &wgpu::TextureView,
+        image_size: &ImageSize,
+    ) -> Result<(), ViewerError> {
+        if self
+            .moments
+            .as_ref()
+            .is_none_or(|moments| moments.image_size != *image_size)
+        {
+            if self.frames > 0 {
+                log::info!("Frame size changed, restarting the temporal statistics");
+            }
+            self.moments = Some(self.create_moments(device, image_size)?);
+            self.frames = 0;
+        }
+        let Some(moments) = &self.moments else {
+            return Ok(());
+        };
+        let frame = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("temporal_frame_bind_group"),
+            layout: &self.frame_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            }],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("temporal_accumulate_encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("temporal_accumulate_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.accumulate);
+            pass.set_bind_group(0, &moments.bind_group, &[]);
+            pass.set_bind_group(1, &frame, &[]);
+            Self::dispatch(&mut pass, image_size);
+        }
+        queue.submit(Some(encoder.finish()));
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Computes the maps on the GPU and reads them back, `None` before the first frame
+    pub fn read(&self, device: Arc<wgpu::Device>, queue: &wgpu::Queue) -> Option<TemporalFuture> {
+        let moments = self.moments.as_ref()?;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("temporal_readback_buffer"),
+            size: moments.maps.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("temporal_finish_encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("temporal_finish_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.finish);
+            pass.set_bind_group(0, &moments.bind_group, &[]);
+            Self::dispatch(&mut pass, &moments.image_size);
+        }
+        encoder.copy_buffer_to_buffer(&moments.maps, 0, &readback, 0, moments.maps.size());
+        queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = async_channel::bounded(1);
+        readback.map_async(wgpu::MapMode::Read, .., move |result| {
+            let _ = sender.try_send(result);
+        });
+        let image_size = moments.image_size.clone();
+        let frames = self.frames;
+        Some(Box::pin(async move {
+            // Blocks natively, the browser maps the buffer on its own
+            let _ = device.poll(wgpu::PollType::Wait);
+            receiver
+                .recv()
+                .await
+                .map_err(|e| ViewerError::Readback(e.to_string()))?
+                .map_err(|e| ViewerError::Readback(e.to_string()))?;
+            let values: Vec<f32> = bytemuck::cast_slice(&readback.get_mapped_range(..)).to_vec();
+            readback.unmap();
+            let (mean, std_dev) = values.split_at(image_size.pixel_count());
+            Ok(TemporalMaps {
+                mean: Image::new(image_size.clone(), mean.to_vec())?,
+                std_dev: Image::new(image_size, std_dev.to_vec())?,
+                frames,
+            })
+        }))
+    }
+
+    fn create_moments(
+        &self,
+        device: &wgpu::Device,
+        image_size: &ImageSize,
+    ) -> Result<Moments, ViewerError> {
+        let bytes = image_size.pixel_count() as u64 * std::mem::size_of::<f32>() as u64;
+        let limits = device.limits();
+        let max_binding =
+            u64::from(limits.max_storage_buffer_binding_size).min(limits.max_buffer_size);
+        if 2 * bytes > max_binding {
+            return Err(ViewerError::InvalidImage(format!(
+                "{}x{} pixels are too many for temporal statistics on this GPU",
+                image_size.width, image_size.height
+            )));
+        }
+        let size = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("temporal_size_buffer"),
+            contents: bytemuck::cast_slice(&[
+                image_size.width.get(),
+                image_size.height.get(),
+                0,
+                0,
+            ]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        // New buffers are zeroed, which is where every pixel starts
+        let buffer = |label, size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        };
+        let count = buffer("temporal_count_buffer", bytes);
+        let mean = buffer("temporal_mean_buffer", bytes);
+        let m2 = buffer("temporal_m2_buffer", bytes);
+        let maps = buffer("temporal_maps_buffer", 2 * bytes);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("temporal_moments_bind_group"),
+            layout: &self.moments_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: size.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: count.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: mean.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: m2.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: maps.as_entire_binding(),
+                },
+            ],
+        });
+        Ok(Moments {
+            image_size: image_size.clone(),
+            bind_group,
+            maps,
+        })
+    }
+
+    fn dispatch(pass: &mut wgpu::ComputePass, image_size: &ImageSize) {
+        pass.dispatch_workgroups(
+            image_size.width.get().div_ceil(Self::WORKGROUP_SIZE),
+            image_size.height.get().div_ceil(Self::WORKGROUP_SIZE),
+            1,
+        );
+    }
+}
@@ -0,0 +1,50 @@
+// Per-pixel mean and standard deviation of a sequence of frames, see `temporal.rs`
+
+@group(0) @binding(0)
+var<uniform> size: vec2<u32>;
+// Valid values, mean and sum of squared deviations of every pixel (Welford's algorithm)
+@group(0) @binding(1)
+var<storage, read_write> count: array<f32>;
+@group(0) @binding(2)
+var<storage, read_write> mean: array<f32>;
+@group(0) @binding(3)
+var<storage, read_write> m2: array<f32>;
+// Mean map followed by the standard deviation map, written by `finish`
+@group(0) @binding(4)
+var<storage, read_write> maps: array<f32>;
+
+@group(1) @binding(0)
+var frame: texture_2d<f32>;
+
+const F32_MAX: f32 = 3.40282347e38;
+
+@compute @workgroup_size(8, 8)
+fn accumulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let value = textureLoad(frame, vec2<i32>(id.xy), 0).x;
+    // Missing pixels don't count, NaN fails the comparison as well
+    if (!(abs(value) <= F32_MAX)) {
+        return;
+    }
+    let i = id.y * size.x + id.x;
+    let n = count[i] + 1.0;
+    let delta = value - mean[i];
+    mean[i] += delta / n;
+    m2[i] += delta * (value - mean[i]);
+    count[i] = n;
+}
+
+@compute @workgroup_size(8, 8)
+fn finish(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let i = id.y * size.x + id.x;
+    let n = count[i];
+    let nan = bitcast<f32>(0x7fc00000u);
+    // Pixels missing in every frame stay missing, a deviation needs two values
+    maps[i] = select(nan, mean[i], n >= 1.0);
+    maps[size.x * size.y + i] = select(nan, sqrt(m2[i] / (n - 1.0)), n >= 2.0);
+}
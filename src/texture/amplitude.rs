@@ -1,9 +1,87 @@
-use crate::image::{Image, ImageSize};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use std::sync::Arc;
+
+use crate::{
+    image::{Image, ImageSize},
+    retention::RetentionPolicy,
+    texture::surface::map_image,
+};
+
+/// Amplitudes mapped to the ends of the amplitude colormap, independent of the height range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AmplitudeRange {
+    /// Fixed limits in raw amplitude counts
+    Fixed { min: f32, max: f32 },
+    /// Limits at percentiles of the amplitude image, robust against saturated pixels
+    Percentile { lower: f32, upper: f32 },
+}
+
+impl Default for AmplitudeRange {
+    fn default() -> Self {
+        Self::Fixed {
+            min: 0.0,
+            max: 4000.0,
+        }
+    }
+}
+
+impl AmplitudeRange {
+    /// Clips the darkest and brightest percent, e.g. specular highlights
+    pub const PERCENTILES: Self = Self::Percentile {
+        lower: 1.0,
+        upper: 99.0,
+    };
+
+    /// Limits at the `lower` and `upper` percentiles, in this order from 0 to 100
+    pub fn percentile(lower: f32, upper: f32) -> anyhow::Result<Self> {
+        if !(0.0..=100.0).contains(&lower) || !(0.0..=100.0).contains(&upper) || lower >= upper {
+            anyhow::bail!("Invalid percentiles: {}, {}", lower, upper);
+        }
+        Ok(Self::Percentile { lower, upper })
+    }
+
+    /// Amplitudes at the ends of the colormap, percentiles fall back to the full range of `u16`
+    /// without an image
+    pub(crate) fn limits(&self, image: Option<&Image<u16>>) -> AmplitudeLimits {
+        match *self {
+            Self::Fixed { min, max } => AmplitudeLimits { min, max },
+            Self::Percentile { lower, upper } => {
+                let Some(image) = image else {
+                    return AmplitudeLimits {
+                        min: 0.0,
+                        max: u16::MAX as f32,
+                    };
+                };
+                let mut sorted = image.data.to_vec();
+                sorted.sort_unstable();
+                let at = |percentile: f32| {
+                    let index = (percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f32)
+                        .round() as usize;
+                    sorted[index] as f32
+                };
+                AmplitudeLimits {
+                    min: at(lower),
+                    max: at(upper),
+                }
+            }
+        }
+    }
+}
+
+/// Amplitudes at the lower and upper end of the amplitude colormap, e.g. to label its legend
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AmplitudeLimits {
+    pub min: f32,
+    pub max: f32,
+}
 
 pub struct AmplitudeTexture {
     pub data: wgpu::Texture,
     pub view: wgpu::TextureView,
-    image: Option<Image<u16>>,
+    image: Option<Arc<Image<u16>>>,
     size: wgpu::Extent3d,
 }
 
@@ -36,7 +114,21 @@ impl AmplitudeTexture {
     }
 
     pub fn set_image(&mut self, image: Image<u16>) {
-        self.image = Some(image);
+        self.image = Some(Arc::new(image));
+    }
+
+    pub fn image(&self) -> Option<&Image<u16>> {
+        self.image.as_deref()
+    }
+
+    /// Applies `policy` to the CPU copy, called after `write_to_queue`. Amplitudes have no file
+    /// to decode them from again, so they are mapped instead of dropped.
+    pub fn retain(&mut self, policy: RetentionPolicy) {
+        if policy != RetentionPolicy::Keep
+            && let Some(image) = &mut self.image
+        {
+            *image = map_image(image);
+        }
     }
 
     pub fn write_to_queue(&self, queue: &wgpu::Queue) {
@@ -59,3 +151,28 @@ impl AmplitudeTexture {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn percentile_limits() {
+        let image = Image::new(ImageSize::new(5, 2).unwrap(), (0..10).collect()).unwrap();
+        let range = AmplitudeRange::Percentile {
+            lower: 10.0,
+            upper: 90.0,
+        };
+        assert_eq!(
+            range.limits(Some(&image)),
+            AmplitudeLimits { min: 1.0, max: 8.0 }
+        );
+        assert_eq!(
+            AmplitudeRange::default().limits(Some(&image)),
+            AmplitudeLimits {
+                min: 0.0,
+                max: 4000.0
+            }
+        );
+    }
+}
@@ -1,16 +1,18 @@
 use std::sync::Arc;
 
 use crate::image::Image;
-pub use crate::texture::{amplitude::*, overlay::*, surface::*};
+pub use crate::texture::{amplitude::*, overlay::*, reference::*, surface::*};
 
 mod amplitude;
 mod overlay;
+mod reference;
 mod surface;
 
 pub(crate) struct Texture {
     pub overlay: OverlayTexture,
     pub surface: SurfaceTexture,
     pub amplitude: AmplitudeTexture,
+    pub reference: ReferenceTexture,
     pub bind_group: wgpu::BindGroup,
 }
 
@@ -18,14 +20,17 @@ impl Texture {
     pub(crate) fn new(
         device: &wgpu::Device,
         surface: Image<f32>,
+        mips: [Image<f32>; 2],
+        reload: Option<Reload>,
         layout: &wgpu::BindGroupLayout,
     ) -> Self {
-        let overlay_texture = OverlayTexture::new(&surface.size, &device);
-        let amplitude_texture = AmplitudeTexture::new(&surface.size, &device);
-        let surface_texture = SurfaceTexture::new(Arc::new(surface), &device);
+        let overlay_texture = OverlayTexture::new(&surface.size, device);
+        let amplitude_texture = AmplitudeTexture::new(&surface.size, device);
+        let reference_texture = ReferenceTexture::new(&surface.size, device);
+        let surface_texture = SurfaceTexture::new(Arc::new(surface), mips, reload, device);
         let group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("texture_bind_group"),
-            layout: layout,
+            layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
@@ -39,12 +44,21 @@ impl Texture {
                     binding: 2,
                     resource: wgpu::BindingResource::TextureView(&overlay_texture.view),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&reference_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&overlay_texture.id_view),
+                },
             ],
         });
         Self {
             overlay: overlay_texture,
             surface: surface_texture,
             amplitude: amplitude_texture,
+            reference: reference_texture,
             bind_group: group,
         }
     }
@@ -83,6 +97,26 @@ impl Texture {
                     },
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Uint,
+                    },
+                    count: None,
+                },
             ],
         })
     }
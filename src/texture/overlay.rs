@@ -1,16 +1,80 @@
-use crate::image::ImageSize;
-use std::{ops::Range, sync::Arc};
+use crate::{
+    annotation_groups::AnnotationGroups, image::ImageSize, overlay_style::OverlayStyle, parallel,
+    selection::Selection,
+};
+use std::{collections::BTreeMap, ops::Range, sync::Arc};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Overlay {
     pub pixels: Vec<Range<u32>>,
     pub color: [u8; 4],
+    /// Annotation group the overlay belongs to, see `AnnotationGroups`
+    pub group: Option<String>,
+    /// Numeric properties like a severity or a classifier score, see `OverlayStyle`
+    pub metadata: BTreeMap<String, f32>,
+}
+
+impl Overlay {
+    /// Moves the overlay by `offset` pixels, parts moved outside the image are cut off
+    pub fn translated(&self, offset: [i32; 2], image_size: &ImageSize) -> Self {
+        let width = image_size.width.get() as i64;
+        let height = image_size.height.get() as i64;
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for range in &self.pixels {
+            let mut start = range.start as i64;
+            let end = range.end as i64;
+            // Ranges may span several rows, move each row on its own
+            while start < end {
+                let row = start / width;
+                let row_end = end.min((row + 1) * width);
+                let new_row = row + offset[1] as i64;
+                let first = (start - row * width + offset[0] as i64).max(0);
+                let last = (row_end - row * width + offset[0] as i64).min(width);
+                if (0..height).contains(&new_row) && first < last {
+                    let base = new_row * width;
+                    pixels.push((base + first) as u32..(base + last) as u32);
+                }
+                start = row_end;
+            }
+        }
+        Self {
+            pixels,
+            color: self.color,
+            group: self.group.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// How overlapping translucent overlays are combined into the overlay texture
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlayBlendMode {
+    /// The overlay added last covers all earlier ones
+    #[default]
+    Topmost,
+    /// Weighted-blended order-independent transparency: colors are averaged
+    /// by their alpha and coverage accumulates like stacked transparent films, opt-in since it
+    /// changes how existing overlays look
+    WeightedBlended,
+}
+
+impl OverlayBlendMode {
+    pub fn toggled(&self) -> Self {
+        match self {
+            OverlayBlendMode::Topmost => OverlayBlendMode::WeightedBlended,
+            OverlayBlendMode::WeightedBlended => OverlayBlendMode::Topmost,
+        }
+    }
 }
 
 pub struct OverlayTexture {
     texture: wgpu::Texture,
     pub view: wgpu::TextureView,
+    /// Object ID of the topmost overlay per pixel, see `Selection::to_id`
+    id_texture: wgpu::Texture,
+    pub id_view: wgpu::TextureView,
     pub overlays: Arc<Vec<Overlay>>,
+    pub blend_mode: OverlayBlendMode,
     size: wgpu::Extent3d,
 }
 
@@ -23,10 +87,19 @@ impl OverlayTexture {
         };
         let texture = device.create_texture(&Self::desc(&size));
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let id_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("overlay_id_texture"),
+            format: wgpu::TextureFormat::R32Uint,
+            ..Self::desc(&size)
+        });
+        let id_view = id_texture.create_view(&wgpu::TextureViewDescriptor::default());
         Self {
             texture,
             view,
+            id_texture,
+            id_view,
             overlays: Arc::new(Vec::new()),
+            blend_mode: OverlayBlendMode::default(),
             size,
         }
     }
@@ -35,8 +108,16 @@ impl OverlayTexture {
         self.overlays = overlays;
     }
 
-    pub fn write_to_queue(&self, queue: &wgpu::Queue) {
-        let overlay_data = self.create_overlay_data();
+    /// Rasterizes the overlays with the styles of their `groups` and metadata
+    pub fn write_to_queue(
+        &self,
+        queue: &wgpu::Queue,
+        groups: &AnnotationGroups,
+        style: &OverlayStyle,
+    ) {
+        let overlays = displayed_overlays(&self.overlays, groups, style);
+        let total_pixels = (self.size.width * self.size.height) as usize;
+        let overlay_data = rasterize_overlays(&overlays, total_pixels, self.blend_mode);
         queue.write_texture(
             wgpu::TexelCopyTextureInfo {
                 texture: &self.texture,
@@ -52,29 +133,25 @@ impl OverlayTexture {
             },
             self.size,
         );
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.id_texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(&rasterize_overlay_ids(&overlays, total_pixels)),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.size.width * 4),
+                rows_per_image: Some(self.size.height),
+            },
+            self.size,
+        );
     }
 
-    /// Creates a texture data array where each pixel (u32 index) maps to an RGBA color
-    /// Returns a vec where each 4 bytes represents RGBA for that pixel index
-    /// If a pixel has no overlay, it's [0, 0, 0, 0]
-    fn create_overlay_data(&self) -> Vec<u8> {
-        let total_pixels = (self.size.width * self.size.height) as usize;
-        let mut data = vec![0u8; total_pixels * 4];
-
-        for overlay in self.overlays.iter() {
-            for range in &overlay.pixels {
-                for pixel_idx in range.start..range.end {
-                    let idx = (pixel_idx as usize) * 4;
-                    if idx + 3 < data.len() {
-                        data[idx] = overlay.color[0];
-                        data[idx + 1] = overlay.color[1];
-                        data[idx + 2] = overlay.color[2];
-                        data[idx + 3] = overlay.color[3];
-                    }
-                }
-            }
-        }
-        data
+    pub fn set_blend_mode(&mut self, blend_mode: OverlayBlendMode) {
+        self.blend_mode = blend_mode;
     }
 
     fn desc(size: &wgpu::Extent3d) -> wgpu::TextureDescriptor<'static> {
@@ -91,8 +168,123 @@ impl OverlayTexture {
     }
 }
 
+/// Color the overlay is drawn in with the styles of its group and its metadata, `None` if it is
+/// hidden. A color given to the group replaces the one of the metadata.
+pub fn displayed_color(
+    overlay: &Overlay,
+    groups: &AnnotationGroups,
+    style: &OverlayStyle,
+) -> Option<[u8; 4]> {
+    let color = style.display_color(overlay)?;
+    groups.display_color(overlay.group.as_deref(), color)
+}
+
+/// Overlays as drawn, hidden ones keep their place without pixels so that their indices still
+/// identify them when picking
+pub fn displayed_overlays(
+    overlays: &[Overlay],
+    groups: &AnnotationGroups,
+    style: &OverlayStyle,
+) -> Vec<Overlay> {
+    overlays
+        .iter()
+        .map(|overlay| match displayed_color(overlay, groups, style) {
+            Some(color) => Overlay {
+                color,
+                ..overlay.clone()
+            },
+            None => Overlay {
+                pixels: Vec::new(),
+                ..overlay.clone()
+            },
+        })
+        .collect()
+}
+
+/// Pixels rasterized together on one thread, overlays are clipped to them
+const CHUNK_PIXELS: usize = 1 << 16;
+
+/// Creates a texture data array where each pixel (u32 index) maps to an RGBA color
+/// Returns a vec where each 4 bytes represents RGBA for that pixel index
+/// If a pixel has no overlay, it's [0, 0, 0, 0]
+pub fn rasterize_overlays(
+    overlays: &[Overlay],
+    total_pixels: usize,
+    mode: OverlayBlendMode,
+) -> Vec<u8> {
+    let mut data = vec![0u8; total_pixels * 4];
+    parallel::for_each_chunk_mut(&mut data, CHUNK_PIXELS * 4, |index, chunk| {
+        let start = index * CHUNK_PIXELS;
+        let pixels = start..start + chunk.len() / 4;
+        match mode {
+            OverlayBlendMode::Topmost => {
+                for overlay in overlays {
+                    for pixel_idx in overlay_pixels(overlay, &pixels) {
+                        let idx = (pixel_idx - start) * 4;
+                        chunk[idx..idx + 4].copy_from_slice(&overlay.color);
+                    }
+                }
+            }
+            OverlayBlendMode::WeightedBlended => {
+                // Sum of alpha weighted colors (rgb) and alphas (a) per pixel
+                let mut accumulation = vec![[0.0f32; 4]; pixels.len()];
+                // Fraction of the background that is still visible per pixel
+                let mut revealage = vec![1.0f32; pixels.len()];
+                for overlay in overlays {
+                    let alpha = overlay.color[3] as f32 / 255.0;
+                    for pixel_idx in overlay_pixels(overlay, &pixels) {
+                        let accum = &mut accumulation[pixel_idx - start];
+                        for (sum, &value) in accum.iter_mut().zip(&overlay.color[..3]) {
+                            *sum += value as f32 * alpha;
+                        }
+                        accum[3] += alpha;
+                        revealage[pixel_idx - start] *= 1.0 - alpha;
+                    }
+                }
+                for (pixel_idx, accum) in accumulation.iter().enumerate() {
+                    if accum[3] <= 0.0 {
+                        continue;
+                    }
+                    let idx = pixel_idx * 4;
+                    for channel in 0..3 {
+                        chunk[idx + channel] = (accum[channel] / accum[3]).round() as u8;
+                    }
+                    chunk[idx + 3] = ((1.0 - revealage[pixel_idx]) * 255.0).round() as u8;
+                }
+            }
+        }
+    });
+    data
+}
+
+/// Object ID of the overlay added last per pixel, it is the one drawn on top, 0 without overlay
+fn rasterize_overlay_ids(overlays: &[Overlay], total_pixels: usize) -> Vec<u32> {
+    let mut ids = vec![0; total_pixels];
+    parallel::for_each_chunk_mut(&mut ids, CHUNK_PIXELS, |index, chunk| {
+        let start = index * CHUNK_PIXELS;
+        let pixels = start..start + chunk.len();
+        for (index, overlay) in overlays.iter().enumerate() {
+            let id = Selection::overlay(index).to_id();
+            for pixel_idx in overlay_pixels(overlay, &pixels) {
+                chunk[pixel_idx - start] = id;
+            }
+        }
+    });
+    ids
+}
+
+/// Pixels of the overlay within `pixels`
+fn overlay_pixels<'a>(
+    overlay: &'a Overlay,
+    pixels: &'a Range<usize>,
+) -> impl Iterator<Item = usize> + 'a {
+    overlay.pixels.iter().flat_map(|range| {
+        (range.start as usize).max(pixels.start)..(range.end as usize).min(pixels.end)
+    })
+}
+
 pub fn example_overlays() -> Vec<Overlay> {
-    vec![
+    let mut overlays = vec![
         Overlay {
             pixels: vec![
                 52775..52786,
@@ -225,6 +417,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 90715..90726,
             ],
             color: [0, 255, 255, 200],
+            group: Some("scratches".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -237,6 +431,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 73375..73378,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -249,6 +445,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142620..142623,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -261,6 +459,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142877..142880,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -273,6 +473,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 270904..270907,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -406,6 +608,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 159960..159971,
             ],
             color: [0, 255, 255, 200],
+            group: Some("scratches".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -418,6 +622,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 73375..73378,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -430,6 +636,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142620..142623,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -442,6 +650,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142877..142880,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -454,6 +664,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 270904..270907,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -587,6 +799,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 160217..160228,
             ],
             color: [0, 255, 255, 200],
+            group: Some("scratches".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -599,6 +813,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 73375..73378,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -611,6 +827,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142620..142623,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -623,6 +841,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142877..142880,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -635,6 +855,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 270904..270907,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -735,6 +957,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 277434..277442,
             ],
             color: [0, 255, 255, 200],
+            group: Some("scratches".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -747,6 +971,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 73375..73378,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -759,6 +985,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142620..142623,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -771,6 +999,8 @@ pub fn example_overlays() -> Vec<Overlay> {
                 142877..142880,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
         },
         Overlay {
             pixels: vec![
@@ -783,6 +1013,107 @@ pub fn example_overlays() -> Vec<Overlay> {
                 270904..270907,
             ],
             color: [255, 0, 0, 200],
+            group: Some("pits".to_string()),
+            metadata: BTreeMap::new(),
+        },
+    ];
+    // Scores of a made-up classifier to try the overlay styles with
+    for (index, overlay) in overlays.iter_mut().enumerate() {
+        let score = (index * 7 % 10) as f32 / 10.0 + 0.05;
+        overlay.metadata.insert("score".to_string(), score);
+    }
+    overlays
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        image::ImageSize,
+        selection::Selection,
+        texture::overlay::{
+            CHUNK_PIXELS, Overlay, OverlayBlendMode, rasterize_overlay_ids, rasterize_overlays,
         },
-    ]
+    };
+    use std::collections::BTreeMap;
+
+    fn overlays() -> Vec<Overlay> {
+        vec![
+            Overlay {
+                pixels: vec![0..1, 1..2],
+                color: [200, 0, 0, 128],
+                group: None,
+                metadata: BTreeMap::new(),
+            },
+            Overlay {
+                pixels: vec![1..2, 2..3],
+                color: [0, 0, 100, 128],
+                group: None,
+                metadata: BTreeMap::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_translated_clips_at_border() {
+        let size = ImageSize {
+            width: std::num::NonZeroU32::new(4).unwrap(),
+            height: std::num::NonZeroU32::new(3).unwrap(),
+        };
+        // Last two pixels of row 0 and first pixel of row 1
+        let overlay = Overlay {
+            pixels: vec![2..4, 4..5],
+            color: [255, 0, 0, 255],
+            group: None,
+            metadata: BTreeMap::new(),
+        };
+        assert_eq!(overlay.translated([1, 1], &size).pixels, vec![7..8, 9..10]);
+        assert_eq!(overlay.translated([0, -1], &size).pixels, vec![0..1]);
+        assert!(overlay.translated([4, 0], &size).pixels.is_empty());
+    }
+
+    #[test]
+    fn test_topmost_overwrites() {
+        let data = rasterize_overlays(&overlays(), 4, OverlayBlendMode::Topmost);
+        assert_eq!(data[4..8], [0, 0, 100, 128]);
+        assert_eq!(data[12..16], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_ids_of_topmost_overlay() {
+        let ids = rasterize_overlay_ids(&overlays(), 4);
+        let id = |index| Selection::overlay(index).to_id();
+        assert_eq!(ids, vec![id(0), id(1), id(1), 0]);
+    }
+
+    #[test]
+    fn test_overlays_span_chunks() {
+        let overlay = Overlay {
+            pixels: vec![0..1, CHUNK_PIXELS as u32 - 1..CHUNK_PIXELS as u32 + 1],
+            color: [255, 0, 0, 255],
+            group: None,
+            metadata: BTreeMap::new(),
+        };
+        let ids = rasterize_overlay_ids(std::slice::from_ref(&overlay), CHUNK_PIXELS + 2);
+        let id = Selection::overlay(0).to_id();
+        assert_eq!(ids[0..2], [id, 0]);
+        assert_eq!(ids[CHUNK_PIXELS - 2..], [0, id, id, 0]);
+        let data = rasterize_overlays(&[overlay], CHUNK_PIXELS + 1, OverlayBlendMode::Topmost);
+        assert_eq!(
+            data[(CHUNK_PIXELS - 1) * 4..],
+            [255, 0, 0, 255, 255, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn test_weighted_blended_is_order_independent() {
+        let mut reversed = overlays();
+        reversed.reverse();
+        let data = rasterize_overlays(&overlays(), 4, OverlayBlendMode::WeightedBlended);
+        let data_reversed = rasterize_overlays(&reversed, 4, OverlayBlendMode::WeightedBlended);
+        assert_eq!(data, data_reversed);
+        assert_eq!(data[0..4], [200, 0, 0, 128]);
+        // Equal alphas average the colors, coverage is 1 - (1 - 0.5)^2
+        assert_eq!(data[4..8], [100, 0, 50, 192]);
+        assert_eq!(data[12..16], [0, 0, 0, 0]);
+    }
 }
@@ -1,16 +1,33 @@
-use std::{num::NonZeroU32, sync::Arc};
+use std::{cell::RefCell, sync::Arc};
 
-use crate::image::{Image, ImageSize};
+use crate::{
+    error::ViewerError,
+    image::{Image, ImageSize, Resampling},
+    retention::RetentionPolicy,
+};
+
+/// Decodes a surface again after its CPU copy was dropped
+pub(crate) type Reload = Box<dyn Fn() -> Result<Image<f32>, ViewerError>>;
 
 pub struct SurfaceTexture {
     pub data: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub image: Arc<Image<f32>>,
-    size: wgpu::Extent3d,
+    /// CPU copy, `None` after it was dropped, see `RetentionPolicy`
+    image: RefCell<Option<Arc<Image<f32>>>>,
+    image_size: ImageSize,
+    reload: Option<Reload>,
+    /// Mip levels 1 and 2 until the first upload, see `SurfaceProducts`
+    mips: RefCell<Option<[Image<f32>; 2]>>,
 }
 
 impl SurfaceTexture {
-    pub fn new(image: Arc<Image<f32>>, device: &wgpu::Device) -> Self {
+    /// `reload` is needed to drop the CPU copy after the upload
+    pub fn new(
+        image: Arc<Image<f32>>,
+        mips: [Image<f32>; 2],
+        reload: Option<Reload>,
+        device: &wgpu::Device,
+    ) -> Self {
         let size = wgpu::Extent3d {
             width: image.size.width.get(),
             height: image.size.height.get(),
@@ -32,72 +49,136 @@ impl SurfaceTexture {
         Self {
             data: texture,
             view,
-            image,
-            size,
+            image_size: image.size.clone(),
+            image: RefCell::new(Some(image)),
+            reload,
+            mips: RefCell::new(Some(mips)),
+        }
+    }
+
+    /// Replaces the CPU copy by an edited surface of the same size, which can't be decoded again
+    pub fn set_image(&mut self, image: Arc<Image<f32>>, mips: [Image<f32>; 2]) {
+        *self.image.get_mut() = Some(image);
+        *self.mips.get_mut() = Some(mips);
+        self.reload = None;
+    }
+
+    pub fn image_size(&self) -> &ImageSize {
+        &self.image_size
+    }
+
+    /// CPU copy of the surface, decoded again if it was dropped. `None` if that fails.
+    pub fn image(&self) -> Option<Arc<Image<f32>>> {
+        let mut image = self.image.borrow_mut();
+        if image.is_none()
+            && let Some(reload) = &self.reload
+        {
+            log::info!("Reloading surface that was dropped after upload");
+            match reload() {
+                // Kept from now on, the surface is obviously needed on the CPU
+                Ok(reloaded) => *image = Some(Arc::new(reloaded)),
+                Err(e) => log::error!("Failed to reload surface: {}", e),
+            }
+        }
+        image.clone()
+    }
+
+    /// CPU copy of the surface if it is in memory or mapped, without decoding it again
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn resident_image(&self) -> Option<Arc<Image<f32>>> {
+        self.image.borrow().clone()
+    }
+
+    /// Applies `policy` to the CPU copy, called after `write_to_queue`
+    pub fn retain(&mut self, policy: RetentionPolicy) {
+        let image = self.image.get_mut();
+        match policy {
+            RetentionPolicy::Keep => {}
+            RetentionPolicy::DropAfterUpload if self.reload.is_some() => {
+                log::info!("Dropping CPU copy of the surface after upload");
+                *image = None;
+            }
+            RetentionPolicy::MemoryMap | RetentionPolicy::DropAfterUpload => {
+                if let Some(current) = image.as_mut() {
+                    *current = map_image(current);
+                }
+            }
         }
     }
 
     pub fn write_to_queue(&self, queue: &wgpu::Queue) {
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &self.data,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            bytemuck::cast_slice(&self.image.data),
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * self.image.size.width.get()),
-                rows_per_image: Some(self.image.size.height.get()),
-            },
-            self.size,
-        );
-        let mip_level_1_size = ImageSize {
-            width: NonZeroU32::new((self.image.size.width.get() / 2).max(1)).unwrap(),
-            height: NonZeroU32::new((self.image.size.height.get() / 2).max(1)).unwrap(),
-        };
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &self.data,
-                mip_level: 1,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            bytemuck::cast_slice(&self.image.resize(&mip_level_1_size).data),
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * mip_level_1_size.width.get()),
-                rows_per_image: Some(mip_level_1_size.height.get()),
-            },
-            wgpu::Extent3d {
-                width: mip_level_1_size.width.get(),
-                height: mip_level_1_size.height.get(),
-                depth_or_array_layers: 1,
-            },
-        );
-        let mip_level_2_size = ImageSize {
-            width: NonZeroU32::new((self.image.size.width.get() / 4).max(1)).unwrap(),
-            height: NonZeroU32::new((self.image.size.height.get() / 4).max(1)).unwrap(),
+        let Some(image) = self.image() else {
+            return;
         };
+        // Mip levels computed with the surface are only used for its first upload
+        let mips = self.mips.take().unwrap_or_else(|| {
+            [1, 2].map(|level| image.resize(&image.size.mip(level), Resampling::Area))
+        });
+        for (level, mip) in (0..).zip(std::iter::once(&*image).chain(&mips)) {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.data,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&mip.data),
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * mip.size.width.get()),
+                    rows_per_image: Some(mip.size.height.get()),
+                },
+                wgpu::Extent3d {
+                    width: mip.size.width.get(),
+                    height: mip.size.height.get(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Uploads rows of the full resolution level, `rows` is as wide as the surface
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn write_rows(&self, queue: &wgpu::Queue, first_row: u32, rows: &Image<f32>) {
         queue.write_texture(
             wgpu::TexelCopyTextureInfo {
                 texture: &self.data,
-                mip_level: 2,
-                origin: wgpu::Origin3d::ZERO,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: first_row,
+                    z: 0,
+                },
                 aspect: wgpu::TextureAspect::All,
             },
-            bytemuck::cast_slice(&self.image.resize(&mip_level_2_size).data),
+            bytemuck::cast_slice(&rows.data),
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * mip_level_2_size.width.get()),
-                rows_per_image: Some(mip_level_2_size.height.get()),
+                bytes_per_row: Some(4 * rows.size.width.get()),
+                rows_per_image: Some(rows.size.height.get()),
             },
             wgpu::Extent3d {
-                width: mip_level_2_size.width.get(),
-                height: mip_level_2_size.height.get(),
+                width: rows.size.width.get(),
+                height: rows.size.height.get(),
                 depth_or_array_layers: 1,
             },
         );
     }
 }
+
+/// Moves `image` to a mapped temporary file, keeps it in memory where that isn't possible
+pub(crate) fn map_image<T>(image: &Arc<Image<T>>) -> Arc<Image<T>>
+where
+    T: PartialOrd + bytemuck::Pod,
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    if !image.is_mapped() {
+        match image.to_mapped() {
+            Ok(mapped) => return Arc::new(mapped),
+            Err(e) => log::warn!("Failed to map image to a file, keeping it in memory: {}", e),
+        }
+    }
+    #[cfg(target_arch = "wasm32")]
+    log::warn!("Images can't be mapped to files in the browser, keeping it in memory");
+    image.clone()
+}
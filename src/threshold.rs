@@ -0,0 +1,91 @@
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{image::Image, units::Units};
+
+/// Split of the surface at a height threshold, e.g. to choose the threshold of a segmentation
+/// while its preview is shown
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThresholdAreas {
+    /// Height in the data unit, in the display unit after `to_display`
+    pub threshold: f32,
+    pub pixels_above: u32,
+    pub pixels_below: u32,
+    /// Lateral area of the pixels at or above the threshold in the squared unit of the pixel
+    /// pitch
+    pub area_above: f32,
+    pub area_below: f32,
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+impl ThresholdAreas {
+    /// Share of the valid pixels at or above the threshold, 0 without valid pixels
+    pub fn fraction_above(&self) -> f32 {
+        let valid = self.pixels_above + self.pixels_below;
+        if valid == 0 {
+            0.0
+        } else {
+            self.pixels_above as f32 / valid as f32
+        }
+    }
+}
+
+impl ThresholdAreas {
+    /// Pixels at the threshold count as above it, missing pixels don't count.
+    /// `pixel_area` is the lateral area of a pixel.
+    pub(crate) fn compute(image: &Image<f32>, threshold: f32, pixel_area: f32) -> Self {
+        let (mut pixels_above, mut pixels_below) = (0u32, 0u32);
+        for &height in image.data.iter().filter(|height| height.is_finite()) {
+            if height >= threshold {
+                pixels_above += 1;
+            } else {
+                pixels_below += 1;
+            }
+        }
+        Self {
+            threshold,
+            pixels_above,
+            pixels_below,
+            area_above: pixels_above as f32 * pixel_area,
+            area_below: pixels_below as f32 * pixel_area,
+        }
+    }
+
+    pub fn to_display(self, units: Units) -> Self {
+        let area = |area: f32| units.to_display(units.to_display(area));
+        Self {
+            threshold: units.to_display(self.threshold),
+            area_above: area(self.area_above),
+            area_below: area(self.area_below),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{image::ImageSize, units::Unit};
+
+    #[test]
+    fn areas_split_at_the_threshold() {
+        let image = Image::new(
+            ImageSize::new(3, 2).unwrap(),
+            vec![0.0f32, 1.0, 2.0, 3.0, f32::NAN, 0.5],
+        )
+        .unwrap();
+        let areas = ThresholdAreas::compute(&image, 1.0, 4.0);
+        assert_eq!((areas.pixels_above, areas.pixels_below), (3, 2));
+        assert_eq!((areas.area_above, areas.area_below), (12.0, 8.0));
+        assert_eq!(areas.fraction_above(), 0.6);
+
+        let units = Units {
+            data: Unit::Millimeter,
+            display: Unit::Micrometer,
+        };
+        let areas = areas.to_display(units);
+        assert_eq!(areas.threshold, 1000.0);
+        assert_eq!(areas.area_above, 12e6);
+    }
+}
@@ -0,0 +1,116 @@
+use std::num::NonZeroU32;
+
+use glam::Vec3;
+
+use crate::{
+    color_space::OutputColorSpace,
+    colormap::Colormap,
+    image::{Image, ImageSize, Resampling, RgbaImage},
+    simd,
+};
+
+/// Edge length of thumbnails if the caller doesn't choose one, like in file managers
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub const DEFAULT_SIZE: u32 = 256;
+
+/// Small shaded preview of a surface computed on the CPU, so that file browsers can show it
+/// without a GPU or loading the file into a viewer. The longer side has at most `max_dim`
+/// pixels and missing pixels are transparent.
+pub fn thumbnail(surface: &Image<f32>, max_dim: u32, colormap: Colormap) -> RgbaImage {
+    let (width, height) = (surface.size.width.get(), surface.size.height.get());
+    let scale = (max_dim.max(1) as f32 / width.max(height) as f32).min(1.0);
+    let side = |length: u32| {
+        NonZeroU32::new((length as f32 * scale).round() as u32).unwrap_or(NonZeroU32::MIN)
+    };
+    let size = ImageSize {
+        width: side(width),
+        height: side(height),
+    };
+    let small = surface.resize(&size, Resampling::Area);
+    // Same contrast as the viewer, which ignores the outliers for the height range
+    let heights = small.outlier_removed_data(2.0, 98.0);
+    let (width, height) = (size.width.get() as usize, size.height.get() as usize);
+    let mut pixels = vec![0; width * height * 4];
+    if let Some((min, max)) = simd::min_max(&heights) {
+        let range = (max - min).max(f32::EPSILON);
+        let normalized = |x: usize, y: usize| (heights[y * width + x] - min) / range;
+        // The heights span a quarter of the longer side, enough relief to see the shape
+        let relief = width.max(height) as f32 / 4.0;
+        let light = Vec3::new(-1.0, -1.0, 1.5).normalize();
+        for y in 0..height {
+            for x in 0..width {
+                let t = normalized(x, y);
+                if !t.is_finite() {
+                    continue;
+                }
+                // Missing neighbors count as the same height
+                let at = |x: usize, y: usize| Some(normalized(x, y)).filter(|t| t.is_finite());
+                let slope = |before: Option<f32>, after: Option<f32>| {
+                    (after.unwrap_or(t) - before.unwrap_or(t)) / 2.0 * relief
+                };
+                let dx = slope(
+                    x.checked_sub(1).and_then(|x| at(x, y)),
+                    (x + 1 < width).then(|| at(x + 1, y)).flatten(),
+                );
+                let dy = slope(
+                    y.checked_sub(1).and_then(|y| at(x, y)),
+                    (y + 1 < height).then(|| at(x, y + 1)).flatten(),
+                );
+                let normal = Vec3::new(-dx, -dy, 1.0).normalize();
+                let shade = 0.3 + 0.7 * normal.dot(light).max(0.0);
+                let color = colormap.srgb(t);
+                let pixel = &mut pixels[(y * width + x) * 4..][..4];
+                for (channel, value) in pixel.iter_mut().zip(color) {
+                    *channel = (value * shade * 255.0).round() as u8;
+                }
+                pixel[3] = 255;
+            }
+        }
+    }
+    RgbaImage {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+        color_space: OutputColorSpace::Srgb,
+    }
+}
+
+/// Writes the thumbnail of a file as PNG, the arguments after `thumbnail` are
+/// `<file> <out.png> [<size>]` like thumbnailers of file managers take them
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let usage = || anyhow::anyhow!("Usage: thumbnail <file> <out.png> [<size>]");
+    let file = args.next().ok_or_else(usage)?;
+    let out = args.next().ok_or_else(usage)?;
+    let size = args.next().map_or(Ok(DEFAULT_SIZE), |size| size.parse())?;
+    let image = crate::image::SurfaceAmplitudeImage::from_file(&file)?;
+    std::fs::write(&out, image.thumbnail(size).to_png()?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thumbnail_keeps_proportions_and_holes() {
+        let (width, height) = (100, 50);
+        let data = (0..width * height)
+            .map(|i| if i == 0 { f32::NAN } else { (i % width) as f32 })
+            .collect();
+        let surface = Image::new(ImageSize::new(width, height).unwrap(), data).unwrap();
+        let preview = thumbnail(&surface, 20, Colormap::Classic);
+        assert_eq!((preview.width, preview.height), (20, 10));
+        // The first pixel averages a missing pixel with valid ones
+        assert_eq!(preview.get_pixel(0, 0)[3], 255);
+        // Brighter towards the higher right side
+        let left = preview.get_pixel(2, 5);
+        let right = preview.get_pixel(17, 5);
+        assert!(right[0] > left[0], "{:?} {:?}", left, right);
+
+        let holes = Image::new(ImageSize::new(2, 1).unwrap(), vec![f32::NAN, 1.0]).unwrap();
+        let preview = thumbnail(&holes, 8, Colormap::Viridis);
+        assert_eq!(preview.get_pixel(0, 0), [0; 4]);
+        assert_eq!(preview.get_pixel(1, 0)[3], 255);
+    }
+}
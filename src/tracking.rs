@@ -0,0 +1,374 @@
+// A feature of interest, e.g. a weld seam or a wear mark, moves through the frames of a sequence
+// with the part or the stage. The region around it is found again in every new frame by matching
+// it with the region of the first frame, and its height statistics are recorded, so that their
+// evolution can be plotted and exported.
+
+use std::{fmt::Write as _, ops::Range};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+use crate::{image::Image, json::json_string, parallel, probe::NeighborhoodStats, units::Units};
+
+/// Pixels the region is searched for around its last position in every direction
+pub const DEFAULT_SEARCH_RADIUS: u32 = 16;
+
+/// Template pixels compared per candidate position, larger regions are compared on a grid
+const MAX_COMPARED_PIXELS: u32 = 4096;
+
+/// Rectangle of pixels
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// Smallest region containing the pixel ranges of an overlay in an image `image_width`
+    /// pixels wide
+    pub fn bounding(pixels: &[Range<u32>], image_width: u32) -> Option<Self> {
+        let mut bounds: Option<(Range<u32>, Range<u32>)> = None;
+        for range in pixels.iter().filter(|range| !range.is_empty()) {
+            let (first_row, last_row) = (range.start / image_width, (range.end - 1) / image_width);
+            // Ranges spanning rows cover the whole width
+            let (first_column, last_column) = if first_row == last_row {
+                (range.start % image_width, (range.end - 1) % image_width)
+            } else {
+                (0, image_width - 1)
+            };
+            let (columns, rows) = bounds.get_or_insert((first_column..0, first_row..0));
+            *columns = columns.start.min(first_column)..columns.end.max(last_column + 1);
+            *rows = rows.start.min(first_row)..rows.end.max(last_row + 1);
+        }
+        let (columns, rows) = bounds?;
+        Some(Self {
+            x: columns.start,
+            y: rows.start,
+            width: columns.len() as u32,
+            height: rows.len() as u32,
+        })
+    }
+
+    fn columns(&self) -> Range<u32> {
+        self.x..self.x + self.width
+    }
+
+    fn rows(&self) -> Range<u32> {
+        self.y..self.y + self.height
+    }
+
+    fn fits(&self, image: &Image<f32>) -> bool {
+        self.width > 0
+            && self.height > 0
+            && self.x + self.width <= image.size.width.get()
+            && self.y + self.height <= image.size.height.get()
+    }
+}
+
+/// Where the region was found in a frame and its height statistics there
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrackedFrame {
+    /// Index of the frame since tracking started, lost frames are skipped
+    pub frame: u32,
+    pub region: Region,
+    /// Zero-mean normalized cross-correlation with the first frame, 1 is a perfect match
+    pub score: f32,
+    /// In the data unit, exported in the display unit
+    pub stats: NeighborhoodStats,
+}
+
+/// Format of the exported track
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackFormat {
+    Csv,
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    Json,
+    /// Plot of the mean height with its range over the frames
+    Svg,
+}
+
+/// Follows a region through the frames of a sequence
+pub struct RegionTracker {
+    /// Heights of the region in the first frame
+    template: Vec<f32>,
+    search_radius: u32,
+    frames: Vec<TrackedFrame>,
+    /// Frames given so far, including lost ones
+    next_frame: u32,
+}
+
+impl RegionTracker {
+    /// Starts tracking `region` of the first frame, `None` if it isn't inside the frame or has
+    /// no valid pixels
+    pub fn new(first: &Image<f32>, region: Region, search_radius: u32) -> Option<Self> {
+        if !region.fits(first) {
+            return None;
+        }
+        let stats = NeighborhoodStats::rectangle(first, region.columns(), region.rows())?;
+        let template = region
+            .rows()
+            .flat_map(|y| region.columns().map(move |x| first.get_pixel(x, y)))
+            .collect();
+        Some(Self {
+            template,
+            search_radius,
+            frames: vec![TrackedFrame {
+                frame: 0,
+                region,
+                score: 1.0,
+                stats,
+            }],
+            next_frame: 1,
+        })
+    }
+
+    /// Region in the last frame it was found in
+    pub fn region(&self) -> Region {
+        self.frames[self.frames.len() - 1].region
+    }
+
+    /// Searches the region around its last position, `None` if it wasn't found in `image`
+    pub fn track(&mut self, image: &Image<f32>) -> Option<TrackedFrame> {
+        let frame = self.next_frame;
+        self.next_frame += 1;
+        let last = self.region();
+        let (width, height) = (image.size.width.get(), image.size.height.get());
+        if last.width > width || last.height > height {
+            return None;
+        }
+        let columns = last.x.saturating_sub(self.search_radius)
+            ..(last.x + self.search_radius).min(width - last.width) + 1;
+        let rows = last.y.saturating_sub(self.search_radius)
+            ..(last.y + self.search_radius).min(height - last.height) + 1;
+        let candidates: Vec<[u32; 2]> = rows
+            .flat_map(|y| columns.clone().map(move |x| [x, y]))
+            .collect();
+        let scores = parallel::map(&candidates, |&[x, y]| {
+            self.score(image, Region { x, y, ..last })
+        });
+        let (best, score) = scores
+            .iter()
+            .enumerate()
+            .filter(|(_, score)| score.is_finite())
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        let [x, y] = candidates[best];
+        let region = Region { x, y, ..last };
+        let tracked = TrackedFrame {
+            frame,
+            region,
+            score: *score,
+            stats: NeighborhoodStats::rectangle(image, region.columns(), region.rows())?,
+        };
+        self.frames.push(tracked);
+        Some(tracked)
+    }
+
+    /// Zero-mean normalized cross-correlation of the template with `region` of the image over
+    /// the pixels valid in both, NaN if they don't vary
+    fn score(&self, image: &Image<f32>, region: Region) -> f32 {
+        let step = (region.width * region.height)
+            .div_ceil(MAX_COMPARED_PIXELS)
+            .isqrt()
+            .max(1);
+        let pairs: Vec<(f64, f64)> = (0..region.height)
+            .step_by(step as usize)
+            .flat_map(|y| {
+                (0..region.width)
+                    .step_by(step as usize)
+                    .map(move |x| (x, y))
+            })
+            .map(|(x, y)| {
+                (
+                    self.template[(y * region.width + x) as usize] as f64,
+                    image.get_pixel(region.x + x, region.y + y) as f64,
+                )
+            })
+            .filter(|(a, b)| a.is_finite() && b.is_finite())
+            .collect();
+        let n = pairs.len() as f64;
+        let (mean_a, mean_b) = pairs
+            .iter()
+            .fold((0.0, 0.0), |(sa, sb), (a, b)| (sa + a / n, sb + b / n));
+        let (mut ab, mut aa, mut bb) = (0.0, 0.0, 0.0);
+        for (a, b) in &pairs {
+            let (da, db) = (a - mean_a, b - mean_b);
+            ab += da * db;
+            aa += da * da;
+            bb += db * db;
+        }
+        (ab / (aa * bb).sqrt()) as f32
+    }
+
+    pub fn export(&self, format: TrackFormat, units: Units) -> String {
+        match format {
+            TrackFormat::Csv => self.to_csv(units),
+            TrackFormat::Json => self.to_json(units),
+            TrackFormat::Svg => self.to_svg(units),
+        }
+    }
+
+    /// One row per frame, heights in the display unit
+    pub fn to_csv(&self, units: Units) -> String {
+        let mut csv = format!(
+            "frame,x,y,width,height,score,mean_{unit},std_{unit},min_{unit},max_{unit},valid_pixels\n",
+            unit = units.display.symbol()
+        );
+        for tracked in &self.frames {
+            let stats = tracked.stats.to_display(units);
+            let region = tracked.region;
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                tracked.frame,
+                region.x,
+                region.y,
+                region.width,
+                region.height,
+                tracked.score,
+                stats.mean,
+                stats.std,
+                stats.min,
+                stats.max,
+                stats.count
+            );
+        }
+        csv
+    }
+
+    pub fn to_json(&self, units: Units) -> String {
+        let frames: Vec<String> = self
+            .frames
+            .iter()
+            .map(|tracked| {
+                let stats = tracked.stats.to_display(units);
+                let region = tracked.region;
+                format!(
+                    "    {{\"frame\": {}, \"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}, \
+                     \"score\": {}, \"mean\": {}, \"std\": {}, \"min\": {}, \"max\": {}, \
+                     \"valid_pixels\": {}}}",
+                    tracked.frame,
+                    region.x,
+                    region.y,
+                    region.width,
+                    region.height,
+                    tracked.score,
+                    stats.mean,
+                    stats.std,
+                    stats.min,
+                    stats.max,
+                    stats.count
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"unit\": {},\n  \"frames\": [\n{}\n  ]\n}}\n",
+            json_string(units.display.symbol()),
+            frames.join(",\n")
+        )
+    }
+
+    /// Mean height over the frames as a line in the band from the minimum to the maximum
+    pub fn to_svg(&self, units: Units) -> String {
+        const WIDTH: f32 = 640.0;
+        const HEIGHT: f32 = 320.0;
+        const MARGIN: f32 = 48.0;
+        let stats: Vec<(u32, NeighborhoodStats)> = self
+            .frames
+            .iter()
+            .map(|tracked| (tracked.frame, tracked.stats.to_display(units)))
+            .collect();
+        let last_frame = stats.last().map_or(0, |(frame, _)| *frame).max(1) as f32;
+        let (low, high) = stats
+            .iter()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), (_, s)| {
+                (low.min(s.min), high.max(s.max))
+            });
+        let span = if high > low { high - low } else { 1.0 };
+        let x = |frame: u32| MARGIN + frame as f32 / last_frame * (WIDTH - 2.0 * MARGIN);
+        let y = |height: f32| HEIGHT - MARGIN - (height - low) / span * (HEIGHT - 2.0 * MARGIN);
+        let points = |value: fn(&NeighborhoodStats) -> f32| -> Vec<String> {
+            stats
+                .iter()
+                .map(|(frame, s)| format!("{:.1},{:.1}", x(*frame), y(value(s))))
+                .collect()
+        };
+        let mut band = points(|s| s.max);
+        band.extend(points(|s| s.min).into_iter().rev());
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" font-family="sans-serif" font-size="12">
+<rect width="100%" height="100%" fill="white"/>
+<polygon points="{band}" fill="#4682b4" fill-opacity="0.25"/>
+<polyline points="{mean}" fill="none" stroke="#4682b4" stroke-width="2"/>
+<line x1="{MARGIN}" y1="{bottom}" x2="{right}" y2="{bottom}" stroke="black"/>
+<line x1="{MARGIN}" y1="{MARGIN}" x2="{MARGIN}" y2="{bottom}" stroke="black"/>
+<text x="{MARGIN}" y="{label_y}">0</text>
+<text x="{right}" y="{label_y}" text-anchor="end">{last_frame} frames</text>
+<text x="4" y="{bottom}">{low:.3}</text>
+<text x="4" y="{top_label}">{high:.3}</text>
+<text x="{MARGIN}" y="20">Mean height with range in {unit}</text>
+</svg>
+"##,
+            band = band.join(" "),
+            mean = points(|s| s.mean).join(" "),
+            bottom = HEIGHT - MARGIN,
+            right = WIDTH - MARGIN,
+            label_y = HEIGHT - MARGIN + 18.0,
+            top_label = MARGIN + 4.0,
+            unit = units.display.symbol(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::image::ImageSize;
+
+    /// Rough plane with a bump at `center`, raised by `offset`
+    fn frame(center: [f32; 2], offset: f32) -> Image<f32> {
+        let data = (0..64 * 64)
+            .map(|i| {
+                let (x, y) = ((i % 64) as f32, (i / 64) as f32);
+                let distance = (x - center[0]).powi(2) + (y - center[1]).powi(2);
+                let roughness = ((i as u32).wrapping_mul(2654435761) >> 24) as f32 / 2560.0;
+                5.0 * (-distance / 20.0).exp() + roughness + offset
+            })
+            .collect();
+        Image::new(ImageSize::new(64, 64).unwrap(), data).unwrap()
+    }
+
+    #[test]
+    fn region_follows_the_feature() {
+        let region = Region {
+            x: 10,
+            y: 10,
+            width: 20,
+            height: 20,
+        };
+        let mut tracker = RegionTracker::new(&frame([20.0, 20.0], 0.0), region, 8).unwrap();
+        let tracked = tracker.track(&frame([25.0, 17.0], 1.0)).unwrap();
+        assert_eq!((tracked.region.x, tracked.region.y), (15, 7));
+        assert_eq!(tracked.frame, 1);
+        // The feature is found despite the offset, which shows in the statistics
+        assert!((tracked.stats.mean - tracker.frames[0].stats.mean - 1.0).abs() < 0.05);
+
+        let csv = tracker.to_csv(Units::default());
+        assert_eq!(csv.lines().count(), 3);
+        assert!(tracker.to_json(Units::default()).contains("\"x\": 15"));
+        assert!(tracker.to_svg(Units::default()).starts_with("<svg"));
+
+        let overlay = [70..75, 135..140];
+        assert_eq!(
+            Region::bounding(&overlay, 64),
+            Some(Region {
+                x: 6,
+                y: 1,
+                width: 6,
+                height: 2
+            })
+        );
+    }
+}
@@ -1,10 +1,48 @@
-use glam::{Mat4, Vec3, Vec4};
+use glam::{EulerRot, Mat4, Vec2, Vec3, Vec4};
 use wgpu::util::DeviceExt;
 
+/// Restricts how a drag rotates the surface
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RotationConstraint {
+    /// Only rotate around this axis of the surface
+    pub axis: Option<Vec3>,
+    /// Round the rotation angle of a drag to multiples of this many degrees
+    pub snap_degrees: Option<f32>,
+}
+
+impl RotationConstraint {
+    pub const SNAP_DEGREES: f32 = 15.0;
+}
+
+/// How dragging the mouse rotates the surface
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum RotationMode {
+    /// Free rotation around any axis, the horizon can end up tilted
+    #[default]
+    Arcball,
+    /// Horizontal drags change the azimuth and vertical drags the elevation, keeping the
+    /// horizon level
+    Turntable,
+}
+
+impl RotationMode {
+    pub fn toggled(&self) -> Self {
+        match self {
+            RotationMode::Arcball => RotationMode::Turntable,
+            RotationMode::Turntable => RotationMode::Arcball,
+        }
+    }
+}
+
 pub struct Transformation {
     current: Mat4,
     initial: Mat4,
     initial_position: Vec3,
+    mode: RotationMode,
+    /// Factors on the x and y movement of a drag, see `NavigationSettings::rotate_factors`
+    speed: Vec2,
+    /// Lateral size of the mesh before the rotation, see `Renderer::set_pixel_aspect`
+    scale: Vec3,
     pub bind_group: Option<wgpu::BindGroup>,
     buffer: Option<wgpu::Buffer>,
 }
@@ -22,6 +60,9 @@ impl Transformation {
             initial: default,
             current: default,
             initial_position: Vec3::new(0.0, 0.0, 1.0),
+            mode: RotationMode::default(),
+            speed: Vec2::ONE,
+            scale: Vec3::ONE,
             bind_group: None,
             buffer: None,
         }
@@ -34,14 +75,62 @@ impl Transformation {
         self.initial_position = Vec3::new(0.0, 0.0, 1.0);
     }
 
+    pub fn get_current(&self) -> Mat4 {
+        self.current
+    }
+
+    /// Rotation with the lateral scale of the mesh as used by the shaders
+    pub fn model(&self) -> Mat4 {
+        self.current * Mat4::from_scale(self.scale)
+    }
+
+    pub fn set_scale(&mut self, scale: Vec3) {
+        self.scale = scale;
+    }
+
+    pub fn set_current(&mut self, current: Mat4) {
+        self.current = current;
+    }
+
+    pub fn get_mode(&self) -> RotationMode {
+        self.mode
+    }
+
+    pub fn set_speed(&mut self, speed: Vec2) {
+        self.speed = speed;
+    }
+
+    /// Switching to the turntable mode levels the horizon of the current view
+    pub fn set_mode(&mut self, mode: RotationMode) {
+        self.mode = mode;
+        if mode == RotationMode::Turntable {
+            let (azimuth, elevation) = self.get_orientation();
+            self.set_orientation(azimuth, elevation);
+        }
+    }
+
+    /// Orients the surface from view angles in degrees. The azimuth turns the surface around its
+    /// z axis, the elevation tilts it towards the viewer with 90° looking straight down.
+    pub fn set_orientation(&mut self, azimuth: f32, elevation: f32) {
+        let tilt = Mat4::from_rotation_x((90.0 - elevation).to_radians());
+        self.current = tilt * Mat4::from_rotation_z(azimuth.to_radians());
+        self.initial = self.current;
+    }
+
+    /// Azimuth and elevation in degrees as accepted by `set_orientation`, roll is ignored
+    pub fn get_orientation(&self) -> (f32, f32) {
+        orientation(self.current)
+    }
+
+    /// Does nothing before `create_bind_group` was called
     pub fn update_gpu(&self, queue: &wgpu::Queue) {
-        queue.write_buffer(
-            self.buffer
-                .as_ref()
-                .expect("Transformation buffer not initialized"),
-            0,
-            bytemuck::cast_slice(&self.current.to_cols_array()),
-        );
+        if let Some(buffer) = &self.buffer {
+            queue.write_buffer(
+                buffer,
+                0,
+                bytemuck::cast_slice(&self.model().to_cols_array()),
+            );
+        }
     }
 
     pub fn start_move(&mut self, position: Vec3) {
@@ -49,10 +138,72 @@ impl Transformation {
         self.initial = self.current;
     }
 
-    pub fn rotate(&mut self, new_position: Vec3) {
-        let rot_axis = self.initial_position.cross(new_position);
-        let axis_len = rot_axis.length();
-        let rot = mat4_from_rotation_axis(rot_axis, axis_len * 100.0);
+    pub fn rotate(&mut self, new_position: Vec3, constraint: RotationConstraint) {
+        let delta = (new_position - self.initial_position).truncate() * self.speed;
+        let new_position = self.initial_position + Vec3::from((delta, 0.0));
+        match self.mode {
+            RotationMode::Arcball => self.rotate_arcball(new_position, constraint),
+            RotationMode::Turntable => self.rotate_turntable(new_position, constraint),
+        }
+    }
+
+    /// Turns the surface by `angles` in radians around the x, y and z axis of the screen, e.g.
+    /// for gamepads. The turntable mode ignores the roll around z.
+    pub fn rotate_by(&mut self, angles: Vec3) {
+        match self.mode {
+            RotationMode::Arcball => {
+                self.current =
+                    Mat4::from_euler(EulerRot::XYZ, angles.x, angles.y, angles.z) * self.current;
+                self.initial = self.current;
+            }
+            RotationMode::Turntable => {
+                let (azimuth, elevation) = self.get_orientation();
+                self.set_orientation(
+                    azimuth + angles.y.to_degrees(),
+                    (elevation + angles.x.to_degrees()).clamp(-90.0, 90.0),
+                );
+            }
+        }
+    }
+
+    fn rotate_turntable(&mut self, new_position: Vec3, constraint: RotationConstraint) {
+        // Degrees per unit of normalized device coordinates
+        const SPEED: f32 = 90.0;
+        let delta = (new_position - self.initial_position) * SPEED;
+        let (mut azimuth, mut elevation) = orientation(self.initial);
+        // Locking to the z axis only spins the surface, locking to x or y only tilts it
+        if constraint.axis.is_none_or(|axis| axis == Vec3::Z) {
+            azimuth += delta.x;
+        }
+        if constraint.axis.is_none_or(|axis| axis != Vec3::Z) {
+            elevation -= delta.y;
+        }
+        if let Some(step) = constraint.snap_degrees {
+            azimuth = (azimuth / step).round() * step;
+            elevation = (elevation / step).round() * step;
+        }
+        let initial = self.initial;
+        self.set_orientation(azimuth, elevation.clamp(-90.0, 90.0));
+        self.initial = initial;
+    }
+
+    fn rotate_arcball(&mut self, new_position: Vec3, constraint: RotationConstraint) {
+        let mut rot_axis = self.initial_position.cross(new_position);
+        let mut angle = rot_axis.length() * 100.0;
+        if let Some(axis) = constraint.axis {
+            // The rotation is applied in view space, so the surface axis is rotated along
+            let view_axis = self.initial.transform_vector3(axis).normalize();
+            angle *= rot_axis.normalize_or_zero().dot(view_axis);
+            rot_axis = view_axis;
+        }
+        if let Some(step) = constraint.snap_degrees {
+            angle = (angle / step).round() * step;
+        }
+        if angle == 0.0 || rot_axis == Vec3::ZERO {
+            self.current = self.initial;
+            return;
+        }
+        let rot = mat4_from_rotation_axis(rot_axis, angle);
         self.current = rot * self.initial;
     }
 
@@ -74,7 +225,7 @@ impl Transformation {
     fn create_buffer_init(&self, device: &wgpu::Device) -> wgpu::Buffer {
         device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("transformation_buffer"),
-            contents: bytemuck::cast_slice(&self.current.to_cols_array()),
+            contents: bytemuck::cast_slice(&self.model().to_cols_array()),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         })
     }
@@ -96,6 +247,17 @@ impl Transformation {
     }
 }
 
+/// Azimuth and elevation in degrees of a rotation built by `Transformation::set_orientation`
+fn orientation(rotation: Mat4) -> (f32, f32) {
+    let z = rotation.z_axis;
+    let tilt = (-z.y).atan2(z.z);
+    let x = rotation.x_axis;
+    // Undo the tilt to read the azimuth in the plane of the surface
+    let sin_azimuth = x.y * tilt.cos() + x.z * tilt.sin();
+    let azimuth = sin_azimuth.atan2(x.x).to_degrees();
+    (azimuth, 90.0 - tilt.to_degrees())
+}
+
 fn mat4_from_rotation_axis(axs: Vec3, phi: f32) -> Mat4 {
     let a = Vec3::normalize(axs);
     let t = phi * std::f32::consts::PI / 180.0;
@@ -121,3 +283,77 @@ fn mat4_from_rotation_axis(axs: Vec3, phi: f32) -> Mat4 {
         w_axis: Vec4::new(0.0, 0.0, 0.0, 1.0),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn orientation_round_trip() {
+        let mut transformation = Transformation::new();
+        assert_eq!(transformation.get_orientation(), (0.0, 90.0));
+        transformation.set_orientation(45.0, 30.0);
+        let (azimuth, elevation) = transformation.get_orientation();
+        assert!((azimuth - 45.0).abs() < 1e-3);
+        assert!((elevation - 30.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn turntable_keeps_horizon_level() {
+        let mut transformation = Transformation::new();
+        transformation.set_mode(RotationMode::Turntable);
+        transformation.start_move(Vec3::new(0.0, 0.0, 1.0));
+        transformation.rotate(Vec3::new(0.4, -0.3, 1.0), RotationConstraint::default());
+        transformation.start_move(Vec3::new(0.4, -0.3, 1.0));
+        transformation.rotate(Vec3::new(-0.2, 0.1, 1.0), RotationConstraint::default());
+        // The screen x axis stays parallel to the surface plane
+        let screen_x = transformation
+            .get_current()
+            .inverse()
+            .transform_vector3(Vec3::X);
+        assert!(screen_x.z.abs() < 1e-5);
+        let (_, elevation) = transformation.get_orientation();
+        assert!((elevation - 54.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn inverted_drag_rotates_the_other_way() {
+        let mut transformation = Transformation::new();
+        transformation.set_mode(RotationMode::Turntable);
+        transformation.set_orientation(0.0, 45.0);
+        transformation.set_speed(Vec2::new(1.0, -2.0));
+        transformation.start_move(Vec3::new(0.0, 0.0, 1.0));
+        transformation.rotate(Vec3::new(0.0, 0.1, 1.0), RotationConstraint::default());
+        let (_, elevation) = transformation.get_orientation();
+        // Twice as fast and upwards instead of downwards
+        assert!((elevation - 63.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn locked_rotation_keeps_axis_fixed() {
+        let mut transformation = Transformation::new();
+        transformation.start_move(Vec3::new(0.0, 0.0, 1.0));
+        let constraint = RotationConstraint {
+            axis: Some(Vec3::Z),
+            snap_degrees: None,
+        };
+        transformation.rotate(Vec3::new(0.3, 0.2, 1.0), constraint);
+        let z = transformation.get_current().transform_vector3(Vec3::Z);
+        assert!(z.abs_diff_eq(Vec3::Z, 1e-5));
+    }
+
+    #[test]
+    fn snapped_rotation_is_a_multiple_of_the_step() {
+        let mut transformation = Transformation::new();
+        transformation.start_move(Vec3::new(0.0, 0.0, 1.0));
+        let constraint = RotationConstraint {
+            axis: Some(Vec3::Y),
+            snap_degrees: Some(RotationConstraint::SNAP_DEGREES),
+        };
+        transformation.rotate(Vec3::new(0.2, 0.0, 1.0), constraint);
+        let x = transformation.get_current().transform_vector3(Vec3::X);
+        let degrees = x.z.atan2(x.x).to_degrees().abs();
+        assert!((degrees / 15.0 - (degrees / 15.0).round()).abs() < 1e-3);
+        assert!(degrees > 1.0);
+    }
+}
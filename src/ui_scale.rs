@@ -0,0 +1,54 @@
+/// Global scale factor for HUD elements and hit targets.
+/// Follows the window scale factor unless the user overrides it.
+pub struct UiScale {
+    window_scale_factor: f64,
+    user_override: Option<f32>,
+}
+
+impl UiScale {
+    pub const MIN: f32 = 0.5;
+    pub const MAX: f32 = 4.0;
+    const STEP: f32 = 0.25;
+
+    pub fn new(window_scale_factor: f64) -> Self {
+        Self {
+            window_scale_factor,
+            user_override: None,
+        }
+    }
+
+    pub fn get(&self) -> f32 {
+        self.user_override
+            .unwrap_or(self.window_scale_factor as f32)
+            .clamp(Self::MIN, Self::MAX)
+    }
+
+    pub fn set_window_scale_factor(&mut self, scale_factor: f64) {
+        self.window_scale_factor = scale_factor;
+    }
+
+    /// Overrides the auto-detected scale, `None` returns to the window scale factor
+    pub fn set_override(&mut self, scale: Option<f32>) {
+        self.user_override = scale.map(|s| s.clamp(Self::MIN, Self::MAX));
+    }
+
+    pub fn increase(&mut self) {
+        self.set_override(Some(self.get() + Self::STEP));
+    }
+
+    pub fn decrease(&mut self) {
+        self.set_override(Some(self.get() - Self::STEP));
+    }
+
+    /// Scale on top of the window scale factor, 1 unless overridden. Pages size their own
+    /// elements in CSS pixels, which already follow the device pixel ratio.
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub fn relative(&self) -> f32 {
+        self.get() / self.window_scale_factor.max(f64::EPSILON) as f32
+    }
+
+    /// Converts a size in physical pixels to logical pixels, the override only scales the HUD
+    pub fn to_logical(&self, physical: f32) -> f32 {
+        physical / self.window_scale_factor as f32
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+
+/// Length unit of height values
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Unit {
+    Nanometer,
+    #[default]
+    Micrometer,
+    Millimeter,
+    /// Thousandth of an inch
+    Mil,
+    Inch,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 5] = [
+        Unit::Nanometer,
+        Unit::Micrometer,
+        Unit::Millimeter,
+        Unit::Mil,
+        Unit::Inch,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Nanometer => "nm",
+            Unit::Micrometer => "µm",
+            Unit::Millimeter => "mm",
+            Unit::Mil => "mil",
+            Unit::Inch => "in",
+        }
+    }
+
+    /// Returns the unit following this one, used to cycle through units with a key
+    pub fn next(&self) -> Unit {
+        let index = Self::ALL.iter().position(|u| u == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    fn nanometers(&self) -> f64 {
+        match self {
+            Unit::Nanometer => 1.0,
+            Unit::Micrometer => 1e3,
+            Unit::Millimeter => 1e6,
+            Unit::Mil => 25_400.0,
+            Unit::Inch => 25_400_000.0,
+        }
+    }
+
+    /// Decimals that resolve about a nanometer without printing noise
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn decimals(&self) -> usize {
+        match self {
+            Unit::Nanometer => 1,
+            Unit::Micrometer | Unit::Mil => 3,
+            Unit::Millimeter => 6,
+            Unit::Inch => 7,
+        }
+    }
+
+    /// Reads a unit from TIFF metadata, the image description has to contain e.g. `unit=nm`
+    pub fn from_description(description: &str) -> Option<Unit> {
+        description
+            .split(|c: char| c.is_whitespace() || c == ';' || c == ',')
+            .find_map(|entry| entry.strip_prefix("unit="))
+            .and_then(|unit| unit.parse().ok())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "nm" | "nanometer" | "nanometers" => Ok(Unit::Nanometer),
+            "µm" | "μm" | "um" | "micrometer" | "micrometers" | "micron" | "microns" => {
+                Ok(Unit::Micrometer)
+            }
+            "mm" | "millimeter" | "millimeters" => Ok(Unit::Millimeter),
+            "mil" | "mils" | "thou" => Ok(Unit::Mil),
+            "in" | "inch" | "inches" => Ok(Unit::Inch),
+            _ => Err(anyhow!("Unsupported unit: {}", s)),
+        }
+    }
+}
+
+/// Unit the data is stored in and unit values are shown in.
+/// All displayed and exported values go through `to_display` or `format`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Units {
+    pub data: Unit,
+    pub display: Unit,
+}
+
+impl Units {
+    /// Shows values in the unit they are stored in
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn new(data: Unit) -> Self {
+        Self {
+            data,
+            display: data,
+        }
+    }
+
+    pub fn to_display(self, value: f32) -> f32 {
+        (value as f64 * self.data.nanometers() / self.display.nanometers()) as f32
+    }
+
+    /// Converts a value entered in the display unit to the data unit
+    pub fn to_data(self, value: f32) -> f32 {
+        (value as f64 * self.display.nanometers() / self.data.nanometers()) as f32
+    }
+
+    /// Value in the display unit with its symbol, e.g. `1.250 µm`
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub fn format(self, value: f32) -> String {
+        format!(
+            "{:.*} {}",
+            self.display.decimals(),
+            self.to_display(value),
+            self.display.symbol()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn convert_between_units() {
+        let units = Units {
+            data: Unit::Micrometer,
+            display: Unit::Nanometer,
+        };
+        assert_eq!(units.to_display(1.5), 1500.0);
+        assert_eq!(units.format(1.5), "1500.0 nm");
+        assert_eq!(units.to_data(250.0), 0.25);
+        let units = Units {
+            data: Unit::Inch,
+            display: Unit::Mil,
+        };
+        assert!((units.to_display(0.002) - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn unit_from_metadata() {
+        assert_eq!(
+            Unit::from_description("Profilometer scan; unit=nm"),
+            Some(Unit::Nanometer)
+        );
+        assert_eq!(Unit::from_description("no unit here"), None);
+        assert_eq!("um".parse::<Unit>().unwrap(), Unit::Micrometer);
+    }
+}
@@ -6,6 +6,8 @@ use crate::image::Image;
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct Vertex {
     vertex_id: [u32; 1],
+    /// 1 if the pixel holds a finite value, 0 for missing data
+    valid: [u32; 1],
 }
 
 pub(crate) struct VertexBuffer {
@@ -13,32 +15,56 @@ pub(crate) struct VertexBuffer {
 }
 
 impl VertexBuffer {
-    pub(crate) fn new<T>(image: &Image<T>, device: &wgpu::Device) -> Self {
-        // Interleave z values and vertex indices into a single vertex buffer
-        let mut vertices: Vec<Vertex> =
-            Vec::with_capacity((image.size.width.get() * image.size.height.get()) as usize);
-        for i in 0..image.data.len() {
-            vertices.push(Vertex {
-                vertex_id: [i as u32],
-            });
-        }
+    pub(crate) fn new<T: num_traits::Float + bytemuck::Pod>(
+        image: &Image<T>,
+        device: &wgpu::Device,
+    ) -> Self {
         let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            contents: bytemuck::cast_slice(&Self::vertices(&image.data, 0)),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         });
         Self { buffer }
     }
 
+    /// Vertex indices and whether there is data for the pixels starting at `first`
+    fn vertices<T: num_traits::Float>(data: &[T], first: u32) -> Vec<Vertex> {
+        (first..)
+            .zip(data)
+            .map(|(i, value)| Vertex {
+                vertex_id: [i],
+                valid: [value.is_finite().into()],
+            })
+            .collect()
+    }
+
+    /// Updates the vertices of rows of the surface, `rows` is as wide as it
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    pub(crate) fn write_rows(&self, queue: &wgpu::Queue, first_row: u32, rows: &Image<f32>) {
+        let first = first_row * rows.size.width.get();
+        queue.write_buffer(
+            &self.buffer,
+            first as u64 * std::mem::size_of::<Vertex>() as u64,
+            bytemuck::cast_slice(&Self::vertices(&rows.data, first)),
+        );
+    }
+
     pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 0,
-                format: wgpu::VertexFormat::Uint32,
-            }],
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 1]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+            ],
         }
     }
 }
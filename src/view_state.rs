@@ -0,0 +1,196 @@
+use anyhow::anyhow;
+use glam::{Mat4, Vec2};
+use std::{fmt, str::FromStr};
+
+use crate::{
+    camera_sync::CameraState, colormap::Colormap, compositing::Compositing, histogram::Layer,
+    texture::AmplitudeRange,
+};
+
+/// Everything that decides how the dataset is shown, so that hosts can store it as a preset,
+/// put it into a link or send it to another viewer. The text form is a query string like
+/// `layer=height&colormap=viridis&height-range=0,1.5&...`, every key is optional when parsing
+/// and missing keys keep the current setting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewState {
+    pub layer: Layer,
+    /// Difference to the reference instead of the heights
+    pub show_residual: bool,
+    pub colormap: Colormap,
+    pub amplitude_colormap: Colormap,
+    /// Heights at the ends of the height colormap in the data unit
+    pub height_range: [f32; 2],
+    pub amplitude_range: AmplitudeRange,
+    /// Modulation of the shown layer by the other one
+    pub compositing: Compositing,
+    /// Vertical exaggeration, 1 is the height the surface is fitted to
+    pub z_scale: f32,
+    pub camera: CameraState,
+    pub overlays_visible: bool,
+}
+
+impl ViewState {
+    /// Applies the settings of the text form to `self`, see `FromStr`
+    pub fn update(&mut self, s: &str) -> anyhow::Result<()> {
+        let flag = |value: &str| match value {
+            "1" | "true" => Ok(true),
+            "0" | "false" => Ok(false),
+            _ => Err(anyhow!("Expected 1 or 0 instead of {}", value)),
+        };
+        let floats = |value: &str| {
+            value
+                .split(',')
+                .map(|value| value.trim().parse::<f32>())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let pair = |value: &str| match floats(value)?[..] {
+            [a, b] if a.is_finite() && b.is_finite() => Ok([a, b]),
+            _ => Err(anyhow!("Expected two numbers instead of {}", value)),
+        };
+        for setting in s.trim_start_matches(['#', '?']).split('&') {
+            if setting.is_empty() {
+                continue;
+            }
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Missing value of {}", setting))?;
+            match key {
+                "layer" => self.layer = value.parse()?,
+                "residual" => self.show_residual = flag(value)?,
+                "colormap" => self.colormap = value.parse()?,
+                "amplitude-colormap" => self.amplitude_colormap = value.parse()?,
+                "height-range" => self.height_range = pair(value)?,
+                "amplitude-range" => {
+                    let (kind, limits) = value
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("Invalid amplitude range: {}", value))?;
+                    let [a, b] = pair(limits)?;
+                    self.amplitude_range = match kind {
+                        "fixed" => AmplitudeRange::Fixed { min: a, max: b },
+                        "percentile" => AmplitudeRange::Percentile { lower: a, upper: b },
+                        _ => return Err(anyhow!("Invalid amplitude range: {}", value)),
+                    };
+                }
+                "blend" => self.compositing = value.parse()?,
+                "z-scale" => {
+                    self.z_scale = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|scale| scale.is_finite() && *scale > 0.0)
+                        .ok_or_else(|| anyhow!("Invalid z-scale: {}", value))?
+                }
+                "camera" => {
+                    self.camera = CameraState::from_slice(&floats(value)?)
+                        .ok_or_else(|| anyhow!("Invalid camera: {}", value))?
+                }
+                "overlays" => self.overlays_visible = flag(value)?,
+                _ => return Err(anyhow!("Unknown view setting: {}", key)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ViewState {
+    fn default() -> Self {
+        Self {
+            layer: Layer::Height,
+            show_residual: false,
+            colormap: Colormap::default(),
+            amplitude_colormap: Colormap::default(),
+            height_range: [0.0, 1.0],
+            amplitude_range: AmplitudeRange::default(),
+            compositing: Compositing::default(),
+            z_scale: 1.0,
+            camera: CameraState {
+                rotation: Mat4::IDENTITY,
+                pan: Vec2::ZERO,
+                zoom: 1.0,
+            },
+            overlays_visible: true,
+        }
+    }
+}
+
+impl fmt::Display for ViewState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, [a, b]) = match self.amplitude_range {
+            AmplitudeRange::Fixed { min, max } => ("fixed", [min, max]),
+            AmplitudeRange::Percentile { lower, upper } => ("percentile", [lower, upper]),
+        };
+        let camera: Vec<String> = self
+            .camera
+            .to_array()
+            .iter()
+            .map(|value| value.to_string())
+            .collect();
+        write!(
+            f,
+            "layer={}&residual={}&colormap={}&amplitude-colormap={}&height-range={},{}\
+             &amplitude-range={}:{},{}&blend={}&z-scale={}&camera={}&overlays={}",
+            self.layer.name(),
+            u8::from(self.show_residual),
+            self.colormap.name(),
+            self.amplitude_colormap.name(),
+            self.height_range[0],
+            self.height_range[1],
+            kind,
+            a,
+            b,
+            self.compositing,
+            self.z_scale,
+            camera.join(","),
+            u8::from(self.overlays_visible),
+        )
+    }
+}
+
+/// Missing keys have their default value
+impl FromStr for ViewState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut state = Self::default();
+        state.update(s)?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compositing::LayerBlend;
+
+    #[test]
+    fn text_form_round_trips() {
+        let state = ViewState {
+            layer: Layer::Amplitude,
+            show_residual: true,
+            colormap: Colormap::Viridis,
+            amplitude_colormap: Colormap::BlueYellow,
+            height_range: [-0.25, 1.5],
+            amplitude_range: AmplitudeRange::PERCENTILES,
+            compositing: Compositing::new(Some(LayerBlend::Overlay), 0.75),
+            z_scale: 2.5,
+            camera: CameraState {
+                rotation: Mat4::from_rotation_x(0.3),
+                pan: Vec2::new(0.1, -0.2),
+                zoom: 0.5,
+            },
+            overlays_visible: false,
+        };
+        assert_eq!(state.to_string().parse::<ViewState>().unwrap(), state);
+    }
+
+    #[test]
+    fn update_keeps_missing_settings() {
+        let mut state = ViewState::default();
+        state.update("#colormap=viridis&z-scale=3").unwrap();
+        assert_eq!(state.colormap, Colormap::Viridis);
+        assert_eq!(state.z_scale, 3.0);
+        assert_eq!(state.layer, Layer::Height);
+        assert!(state.update("z-scale=0").is_err());
+        assert!(state.update("height-range=1").is_err());
+        assert!(state.update("zoom=2").is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Extensions of the files the viewer can load
+const SUPPORTED_EXTENSIONS: [&str; 2] = ["tif", "tiff"];
+/// Extensions of compressed files added to the supported ones, see `compression`
+const COMPRESSED_EXTENSIONS: [&str; 3] = ["gz", "zst", "lz4"];
+
+/// Size and modification time of a file, a file is complete once they stop changing
+#[derive(Clone, Debug, PartialEq)]
+struct FileStamp {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+/// Watches a directory, e.g. the share an inline measurement station writes its results to,
+/// and reports the newest supported file once it has stopped changing for the debounce time
+pub struct FolderWatch {
+    dir: PathBuf,
+    /// Screenshots of every loaded file are written here if set
+    archive: Option<PathBuf>,
+    debounce: Duration,
+    /// Newest file seen and since when it has not changed
+    pending: Option<(FileStamp, Instant)>,
+    /// Modification time of the last reported file, older files are ignored
+    loaded: Option<SystemTime>,
+}
+
+impl FolderWatch {
+    pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+    /// Time between two scans of the directory
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    pub fn new(dir: PathBuf, archive: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            archive,
+            debounce: Self::DEFAULT_DEBOUNCE,
+            pending: None,
+            loaded: None,
+        }
+    }
+
+    pub fn archive(&self) -> Option<&Path> {
+        self.archive.as_deref()
+    }
+
+    /// Scans the directory, returns the file to load if a new one is complete
+    pub fn poll(&mut self, now: Instant) -> Option<PathBuf> {
+        let newest = match newest_file(&self.dir) {
+            Ok(newest) => newest,
+            Err(e) => {
+                log::error!("Failed to scan {}: {}", self.dir.display(), e);
+                None
+            }
+        };
+        self.update(newest, now)
+    }
+
+    fn update(&mut self, newest: Option<FileStamp>, now: Instant) -> Option<PathBuf> {
+        let newest = newest.filter(|file| self.loaded.is_none_or(|loaded| file.modified > loaded));
+        let Some(newest) = newest else {
+            self.pending = None;
+            return None;
+        };
+        match &self.pending {
+            Some((pending, since)) if *pending == newest => {
+                if now.duration_since(*since) < self.debounce {
+                    return None;
+                }
+                self.loaded = Some(newest.modified);
+                self.pending = None;
+                Some(newest.path)
+            }
+            // New file or still being written
+            _ => {
+                self.pending = Some((newest, now));
+                None
+            }
+        }
+    }
+}
+
+fn newest_file(dir: &Path) -> std::io::Result<Option<FileStamp>> {
+    let mut newest: Option<FileStamp> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if !is_supported(&path) || !metadata.is_file() {
+            continue;
+        }
+        let file = FileStamp {
+            path,
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        };
+        if newest
+            .as_ref()
+            .is_none_or(|newest| file.modified > newest.modified)
+        {
+            newest = Some(file);
+        }
+    }
+    Ok(newest)
+}
+
+/// Whether the file is a TIFF, compressed ones like "part.tiff.zst" included
+fn is_supported(path: &Path) -> bool {
+    let has_extension = |path: &Path, extensions: &[&str]| {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                extensions
+                    .iter()
+                    .any(|supported| extension.eq_ignore_ascii_case(supported))
+            })
+    };
+    if has_extension(path, &COMPRESSED_EXTENSIONS) {
+        has_extension(&path.with_extension(""), &SUPPORTED_EXTENSIONS)
+    } else {
+        has_extension(path, &SUPPORTED_EXTENSIONS)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compressed_tiffs_are_supported() {
+        for supported in ["part.tiff", "part.TIF", "part.tiff.gz", "part.tif.zst"] {
+            assert!(is_supported(Path::new(supported)), "{}", supported);
+        }
+        for unsupported in ["part.png", "part.gz", "part.csv.lz4", "tiff"] {
+            assert!(!is_supported(Path::new(unsupported)), "{}", unsupported);
+        }
+    }
+
+    #[test]
+    fn reports_new_file_after_debounce() {
+        let mut watch = FolderWatch::new(PathBuf::from("share"), None);
+        let start = Instant::now();
+        let file = |len, seconds| FileStamp {
+            path: PathBuf::from("share/part.tiff"),
+            len,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(seconds),
+        };
+        assert_eq!(watch.update(Some(file(10, 1)), start), None);
+        // Still being written
+        let later = start + FolderWatch::DEFAULT_DEBOUNCE;
+        assert_eq!(watch.update(Some(file(20, 2)), later), None);
+        assert_eq!(
+            watch.update(Some(file(20, 2)), later + FolderWatch::DEFAULT_DEBOUNCE),
+            Some(PathBuf::from("share/part.tiff"))
+        );
+        // Loaded already
+        let much_later = later + 4 * FolderWatch::DEFAULT_DEBOUNCE;
+        assert_eq!(watch.update(Some(file(20, 2)), much_later), None);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::i18n::{Localizer, MessageArgs};
+
+/// Long running operation that is reflected in the window title
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// Completed fraction from 0 to 1, `None` if the total amount of work is unknown
+    pub fraction: Option<f32>,
+}
+
+/// Contents of the window title so the native app shows what is open like other desktop tools:
+/// dataset name, dimensions, unsaved changes and running operations
+#[derive(Clone, Debug, Default)]
+pub struct WindowTitle {
+    pub dataset: Option<String>,
+    pub size: Option<(u32, u32)>,
+    /// Annotations like overlays or markers were changed since the dataset was loaded
+    pub modified: bool,
+    /// Data is being loaded
+    pub loading: Option<Progress>,
+    /// The built-in demo surface is shown instead of a file
+    pub demo: bool,
+}
+
+impl WindowTitle {
+    pub fn format(&self, localizer: &Localizer) -> String {
+        let mut parts = vec![];
+        if let Some(progress) = self.loading {
+            parts.push(match progress.fraction {
+                Some(fraction) => {
+                    let mut args = MessageArgs::new();
+                    args.set("percent", (fraction.clamp(0.0, 1.0) * 100.0).round());
+                    localizer.format("title-loading-percent", Some(&args))
+                }
+                None => localizer.text("title-loading"),
+            });
+        }
+        if let Some(dataset) = &self.dataset {
+            let modified = if self.modified { "*" } else { "" };
+            parts.push(match self.size {
+                Some((width, height)) => format!("{dataset}{modified} ({width} × {height})"),
+                None => format!("{dataset}{modified}"),
+            });
+        }
+        // Without a file the title tells how to open one
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.loading.is_none() && (self.dataset.is_none() || self.demo) {
+            parts.push(localizer.text("title-empty"));
+        }
+        parts.push(localizer.text("window-title"));
+        parts.join(" — ")
+    }
+}
+
+/// Icon shown in the title bar and taskbar, a small height map so no image file is needed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn app_icon() -> Option<winit::window::Icon> {
+    const SIZE: u32 = 32;
+    let rgba = (0..SIZE * SIZE)
+        .flat_map(|i| {
+            let x = (i % SIZE) as f32 / (SIZE - 1) as f32 * 2.0 - 1.0;
+            let y = (i / SIZE) as f32 / (SIZE - 1) as f32 * 2.0 - 1.0;
+            let height = (-3.0 * (x * x + y * y)).exp();
+            // Cyan valleys turning magenta towards the peak
+            [
+                (255.0 * height) as u8,
+                (220.0 * (1.0 - height)) as u8,
+                230,
+                255,
+            ]
+        })
+        .collect();
+    match winit::window::Icon::from_rgba(rgba, SIZE, SIZE) {
+        Ok(icon) => Some(icon),
+        Err(e) => {
+            log::warn!("Failed to create window icon: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::i18n::Language;
+
+    #[test]
+    fn title_shows_dataset_and_progress() {
+        let localizer = Localizer::new(Language::English);
+        let mut title = WindowTitle::default();
+        assert_eq!(
+            title.format(&localizer),
+            "Open a surface with Ctrl+O or drop it here — 3D Data Viewer"
+        );
+        title.dataset = Some("Demo surface".to_owned());
+        title.demo = true;
+        assert_eq!(
+            title.format(&localizer),
+            "Demo surface — Open a surface with Ctrl+O or drop it here — 3D Data Viewer"
+        );
+        title.demo = false;
+        title.dataset = Some("scan.tiff".to_owned());
+        title.size = Some((640, 480));
+        title.modified = true;
+        assert_eq!(
+            title.format(&localizer),
+            "scan.tiff* (640 × 480) — 3D Data Viewer"
+        );
+        title.loading = Some(Progress {
+            fraction: Some(0.25),
+        });
+        assert!(title.format(&localizer).starts_with("Loading 25%"));
+    }
+}
@@ -0,0 +1,143 @@
+// Helpers of the integration tests: synthetic surfaces are written as TIFF files and rendered
+// by the `render` subcommand of the viewer without a window, on the fallback adapter so that
+// machines without a GPU like CI runners render them too.
+
+use std::{
+    path::PathBuf,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Heights of a synthetic surface, row by row
+pub struct Surface {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl Surface {
+    pub fn from_fn(width: u32, height: u32, f: impl Fn(u32, u32) -> f32) -> Self {
+        Self {
+            width,
+            height,
+            data: (0..width * height)
+                .map(|i| f(i % width, i / width))
+                .collect(),
+        }
+    }
+
+    /// Gaussian bump in the center, highest in the middle
+    pub fn bump(size: u32) -> Self {
+        let center = (size - 1) as f32 / 2.0;
+        Self::from_fn(size, size, |x, y| {
+            let (dx, dy) = (x as f32 - center, y as f32 - center);
+            (-(dx * dx + dy * dy) / (size * size) as f32 * 8.0).exp()
+        })
+    }
+
+    /// Surface and amplitude pages like the files of the instruments
+    fn write_tiff(&self, path: &PathBuf) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut encoder = tiff::encoder::TiffEncoder::new(file).unwrap();
+        for _ in 0..2 {
+            encoder
+                .write_image::<tiff::encoder::colortype::Gray32Float>(
+                    self.width,
+                    self.height,
+                    &self.data,
+                )
+                .unwrap();
+        }
+    }
+}
+
+/// What was read from the picking target at a position of the rendered image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pick {
+    pub x: u32,
+    pub y: u32,
+    pub column: u32,
+    pub row: u32,
+    pub object: u32,
+}
+
+pub struct Rendered {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA rows
+    pub pixels: Vec<u8>,
+    pub picks: Vec<Pick>,
+}
+
+impl Rendered {
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.pixels[i..i + 4].try_into().unwrap()
+    }
+}
+
+/// Unique path in the temporary directory, tests run in parallel
+fn temporary(extension: &str) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "data-viewer-3d-test-{}-{}.{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
+
+/// Renders `surface` with further `render` arguments, e.g. `--size` and `--pick`.
+/// `None` if the machine has no graphics adapter at all, the tests are skipped then.
+pub fn render(surface: &Surface, args: &[&str]) -> Option<Rendered> {
+    let (input, output) = (temporary("tiff"), temporary("png"));
+    surface.write_tiff(&input);
+    let result = Command::new(env!("CARGO_BIN_EXE_data-viewer-3d"))
+        .arg("render")
+        .arg(&input)
+        .arg(&output)
+        .arg("--fallback-adapter")
+        .args(args)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&input).unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if stderr.contains("No compatible graphics adapter found") {
+        eprintln!("No graphics adapter available, skipping headless test");
+        return None;
+    }
+    assert!(result.status.success(), "render failed:\n{}", stderr);
+
+    let decoder = png::Decoder::new(std::io::BufReader::new(
+        std::fs::File::open(&output).unwrap(),
+    ));
+    let mut reader = decoder.read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba);
+
+    let picks = String::from_utf8_lossy(&result.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("pick "))
+        .map(|line| {
+            let values: Vec<u32> = line
+                .split_whitespace()
+                .map(|value| value.parse().unwrap())
+                .collect();
+            Pick {
+                x: values[0],
+                y: values[1],
+                column: values[2],
+                row: values[3],
+                object: values[4],
+            }
+        })
+        .collect();
+    Some(Rendered {
+        width: info.width,
+        height: info.height,
+        pixels,
+        picks,
+    })
+}
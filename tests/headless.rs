@@ -0,0 +1,60 @@
+// The whole path from a file to the pixels on a headless device: decoding, vertex and index
+// buffers, shaders, readback and picking
+
+mod common;
+
+use common::{Surface, render};
+
+const BACKGROUND: [u8; 4] = [0, 0, 0, 255];
+
+#[test]
+fn renders_surface_deterministically() {
+    let surface = Surface::bump(48);
+    let Some(first) = render(&surface, &["--size", "96x64"]) else {
+        return;
+    };
+    let second = render(&surface, &["--size", "96x64"]).unwrap();
+    assert_eq!((first.width, first.height), (96, 64));
+    assert_eq!(first.pixels, second.pixels);
+    // The surface fills the center, the corners stay clear
+    assert_ne!(first.get_pixel(48, 32), BACKGROUND);
+    assert_eq!(first.get_pixel(0, 0), BACKGROUND);
+}
+
+#[test]
+fn higher_pixels_get_other_colors() {
+    // Left half low, right half high
+    let surface = Surface::from_fn(32, 32, |x, _| if x < 16 { 0.0 } else { 1.0 });
+    let Some(rendered) = render(&surface, &["--size", "64x64"]) else {
+        return;
+    };
+    assert_ne!(rendered.get_pixel(24, 32), rendered.get_pixel(40, 32));
+    assert_eq!(rendered.get_pixel(20, 20), rendered.get_pixel(20, 44));
+}
+
+#[test]
+fn picks_surface_pixels() {
+    let surface = Surface::from_fn(32, 32, |x, y| ((x + y) % 2) as f32 / 100.0);
+    let Some(rendered) = render(
+        &surface,
+        &[
+            "--size", "64x64", "--pick", "32,32", "--pick", "16,32", "--pick", "48,32", "--pick",
+            "0,0",
+        ],
+    ) else {
+        return;
+    };
+    let [center, left, right, corner] = rendered.picks[..] else {
+        panic!("expected 4 picks, got {:?}", rendered.picks);
+    };
+    assert_eq!((center.x, center.y), (32, 32));
+    // Picks snap to the vertices of the mesh, which has fewer vertices than pixels
+    assert!(center.column.abs_diff(16) <= 4, "{:?}", center);
+    assert!(center.row.abs_diff(16) <= 4, "{:?}", center);
+    assert_eq!(center.object, 0);
+    // The columns follow the image from left to right, the row stays
+    assert!(left.column < center.column && center.column < right.column);
+    assert!(left.row.abs_diff(right.row) <= 4, "{:?} {:?}", left, right);
+    // Nothing is drawn in the corner
+    assert_eq!((corner.column, corner.row, corner.object), (0, 0, 0));
+}
@@ -6,8 +6,16 @@
  */
 
 import init, {
+    apiVersion,
+    Disposition,
+    LayerBlend,
+    SelectionKind,
+    Severity,
+    TemporalMap,
+    TrackFormat,
     WasmViewer
 } from './assets/wasm/data-viewer-3d.js';
+import { startWorkerViewer } from './worker-viewer.js';
 
 // DOM Elements
 const loadingOverlay = document.getElementById('loading-overlay');
@@ -19,6 +27,11 @@ const statusFps = document.getElementById('status-fps');
 const pixelX = document.getElementById('pixel-x');
 const pixelY = document.getElementById('pixel-y');
 const pixelZ = document.getElementById('pixel-z');
+const probeReadout = document.getElementById('probe-readout');
+const probeMean = document.getElementById('probe-mean');
+const probeStd = document.getElementById('probe-std');
+const probeRange = document.getElementById('probe-range');
+const probeGradient = document.getElementById('probe-gradient');
 
 // Control buttons
 const btnHeight = document.getElementById('btn-height');
@@ -26,6 +39,68 @@ const btnAmplitude = document.getElementById('btn-amplitude');
 const btnReset = document.getElementById('btn-reset');
 const btnSetOverlay = document.getElementById('btn-set-overlay');
 const btnClearOverlay = document.getElementById('btn-clear-overlay');
+const btnFocusSelection = document.getElementById('btn-focus-selection');
+const annotationGroups = document.getElementById('annotation-groups');
+const btnExportAnnotations = document.getElementById('btn-export-annotations');
+const btnColorByScore = document.getElementById('btn-color-by-score');
+const inputMinScore = document.getElementById('input-min-score');
+const btnReviewPrevious = document.getElementById('btn-review-previous');
+const btnReviewNext = document.getElementById('btn-review-next');
+const btnReviewByScore = document.getElementById('btn-review-by-score');
+const btnAccept = document.getElementById('btn-accept');
+const btnReject = document.getElementById('btn-reject');
+const btnExportAudit = document.getElementById('btn-export-audit');
+const btnSetView = document.getElementById('btn-set-view');
+const btnAlignLay = document.getElementById('btn-align-lay');
+const inputAzimuth = document.getElementById('input-azimuth');
+const inputElevation = document.getElementById('input-elevation');
+const inputZoom = document.getElementById('input-zoom');
+const inputNearClip = document.getElementById('input-near-clip');
+const inputReference = document.getElementById('input-reference');
+const inputTolerance = document.getElementById('input-tolerance');
+const btnResidual = document.getElementById('btn-residual');
+const btnEvaluate = document.getElementById('btn-evaluate');
+const btnBaseline = document.getElementById('btn-baseline');
+const btnTemporal = document.getElementById('btn-temporal');
+const btnTemporalMean = document.getElementById('btn-temporal-mean');
+const btnTemporalStdDev = document.getElementById('btn-temporal-std-dev');
+const btnRegister = document.getElementById('btn-register');
+const btnFrameShifts = document.getElementById('btn-frame-shifts');
+const registrationResult = document.getElementById('registration-result');
+const btnTrack = document.getElementById('btn-track');
+const btnTrackExport = document.getElementById('btn-track-export');
+const trackPlot = document.getElementById('track-plot');
+const btnCheckerboard = document.getElementById('btn-checkerboard');
+const inputTileSize = document.getElementById('input-tile-size');
+const toleranceResult = document.getElementById('tolerance-result');
+const inputAmplitudeMin = document.getElementById('input-amplitude-min');
+const inputAmplitudeMax = document.getElementById('input-amplitude-max');
+const btnAmplitudeRange = document.getElementById('btn-amplitude-range');
+const btnAmplitudePercentiles = document.getElementById('btn-amplitude-percentiles');
+const btnAmplitudeColormap = document.getElementById('btn-amplitude-colormap');
+const amplitudeLegendMin = document.getElementById('amplitude-legend-min');
+const amplitudeLegendMax = document.getElementById('amplitude-legend-max');
+const amplitudeLegendBar = document.getElementById('amplitude-legend-bar');
+const btnAutoContrast = document.getElementById('btn-auto-contrast');
+const btnBlendBrightness = document.getElementById('btn-blend-brightness');
+const btnBlendOverlay = document.getElementById('btn-blend-overlay');
+const inputBlendWeight = document.getElementById('input-blend-weight');
+const btnThreshold = document.getElementById('btn-threshold');
+const inputThreshold = document.getElementById('input-threshold');
+const thresholdResult = document.getElementById('threshold-result');
+const inputPixelPitch = document.getElementById('input-pixel-pitch');
+const inputPixelAspect = document.getElementById('input-pixel-aspect');
+const btnRawPixels = document.getElementById('btn-raw-pixels');
+const measureButtons = document.querySelectorAll('[data-measure]');
+const debugViewButtons = document.querySelectorAll('[data-debug-view]');
+const measureResult = document.getElementById('measure-result');
+const selectionProperties = document.getElementById('selection-properties');
+const btnDeleteSelection = document.getElementById('btn-delete-selection');
+const notificationLog = document.getElementById('notification-log');
+const datasetInfo = document.getElementById('dataset-info');
+const btnClearNotifications = document.getElementById('btn-clear-notifications');
+const toastContainer = document.getElementById('toast-container');
+const languageButtons = document.querySelectorAll('[data-language]');
 
 // State
 let wasmModule = null;
@@ -33,6 +108,19 @@ let wasmViewer = null;
 let isHeightMode = true;
 let isPollingEnabled = false;
 let isPolling = false;
+let isProbing = false;
+let isResidualMode = false;
+let measureTool = null;
+let amplitudeColormap = 0;
+let levelsWidgets = [];
+let hiddenGroups = new Set();
+
+// Amplitude colormaps and matching CSS gradients for the legend
+const AMPLITUDE_COLORMAPS = [
+    { name: 'classic', gradient: 'linear-gradient(to right, #ff0000, #00ff00)' },
+    { name: 'viridis', gradient: 'linear-gradient(to right, #440154, #3b528b, #21918c, #5ec962, #fde725)' },
+    { name: 'blue-yellow', gradient: 'linear-gradient(to right, #00204d, #7c7b78, #ffea46)' },
+];
 
 /**
  * Check if WebGPU is available
@@ -102,52 +190,29 @@ function hideLoading() {
 }
 
 /**
- * Load surface data from assets/data/surface.tiff
+ * Open the dataset and view of a shared link, or the example data from assets/data
  */
-async function loadSurfaceData() {
-    try {
-        console.log('Loading surface.tiff...');
-        const response = await fetch('./src/assets/data/surface.tiff');
-
-        if (!response.ok) {
-            throw new Error(`Failed to load surface.tiff: ${response.status} ${response.statusText}`);
-        }
-
-        const arrayBuffer = await response.arrayBuffer();
-        const uint8Array = new Uint8Array(arrayBuffer);
+const SURFACE_URL = './src/assets/data/surface.tiff';
 
-        console.log('Surface data loaded:', uint8Array.length, 'bytes');
-        return uint8Array;
-    } catch (error) {
-        console.error('Error loading surface data:', error);
-        throw error;
-    }
-}
-
-async function loadAmplitudeData() {
+async function loadSurfaceData() {
+    // Keep the address bar up to date so that it can be shared
+    wasmViewer.set_url_hash_sync(true);
     try {
-        console.log('Loading amplitude.tiff...');
-        const response = await fetch('./src/assets/data/amplitude.tiff');
-
-        if (!response.ok) {
-            throw new Error(`Failed to load amplitude.tiff: ${response.status} ${response.statusText}`);
+        if (await wasmViewer.restore_url_hash()) {
+            console.log('Restored view from URL hash');
+            return;
         }
-
-        const arrayBuffer = await response.arrayBuffer();
-        const uint8Array = new Uint8Array(arrayBuffer);
-
-        console.log('Amplitude data loaded:', uint8Array.length, 'bytes');
-        return uint8Array;
     } catch (error) {
-        console.error('Error loading amplitude data:', error);
-        throw error;
+        console.warn('Failed to restore view from URL hash:', error);
     }
+    console.log('Loading surface.tiff and amplitude.tiff...');
+    await wasmViewer.load_url(SURFACE_URL, './src/assets/data/amplitude.tiff');
 }
 
 /**
  * Update the pixel readout in the UI
  */
-function renderPixelReadout(x, y, z) {
+function renderPixelReadout(x, y, z, unit) {
     if (!pixelX || !pixelY || !pixelZ) {
         return;
     }
@@ -164,9 +229,156 @@ function renderPixelReadout(x, y, z) {
 
     pixelX.textContent = formatValue(x, true);
     pixelY.textContent = formatValue(y, true);
-    pixelZ.textContent = formatValue(z, false, 2);
+    const zText = formatValue(z, false, 2);
+    pixelZ.textContent = unit && zText !== '--' ? `${zText} ${unit}` : zText;
+}
+
+/**
+ * Show the statistics of the neighborhood under the cursor
+ */
+function renderProbeReadout(stats, unit) {
+    const format = (value) => Number.isFinite(value) ? `${value.toFixed(3)} ${unit}` : '--';
+    probeMean.textContent = format(stats.mean);
+    probeStd.textContent = format(stats.std);
+    probeRange.textContent = `${format(stats.min)} / ${format(stats.max)}`;
+    probeGradient.textContent = `${stats.gradient_x.toFixed(4)}, ${stats.gradient_y.toFixed(4)} ${unit}/px`;
+    stats.free();
+}
+
+function refreshLevels() {
+    levelsWidgets.forEach((widget) => widget.refresh());
 }
 
+/**
+ * Label the amplitude legend with the amplitudes at the ends of the colormap
+ */
+async function updateAmplitudeLegend() {
+    amplitudeLegendBar.style.background = AMPLITUDE_COLORMAPS[amplitudeColormap].gradient;
+    try {
+        const limits = await wasmViewer.amplitude_limits();
+        amplitudeLegendMin.textContent = limits.min.toFixed(0);
+        amplitudeLegendMax.textContent = limits.max.toFixed(0);
+        limits.free();
+    } catch (err) {
+        console.warn('Failed to read amplitude limits:', err);
+    }
+}
+
+/**
+ * Histogram of a layer with draggable handles that set its display range live,
+ * like the "levels" adjustment of image editors
+ */
+function createLevelsWidget(layer) {
+    const canvas = document.getElementById(`levels-${layer}`);
+    const labelMin = document.getElementById(`levels-${layer}-min`);
+    const labelMax = document.getElementById(`levels-${layer}-max`);
+    const context = canvas.getContext('2d');
+    let levels = null;
+    let dragging = null;
+    let unit = '';
+
+    const toX = (value) => (value - levels.min) / (levels.max - levels.min || 1) * canvas.width;
+    const toValue = (x) => levels.min + Math.min(Math.max(x / canvas.width, 0), 1) * (levels.max - levels.min);
+    const format = (value) => `${value.toFixed(layer === 'height' ? 3 : 0)} ${unit}`.trim();
+
+    function draw() {
+        context.clearRect(0, 0, canvas.width, canvas.height);
+        if (!levels) {
+            return;
+        }
+        // Logarithmic counts so that small populations stay visible
+        const peak = Math.log1p(Math.max(...levels.counts));
+        const binWidth = canvas.width / levels.counts.length;
+        context.fillStyle = '#8888a0';
+        levels.counts.forEach((count, bin) => {
+            const height = peak > 0 ? Math.log1p(count) / peak * canvas.height : 0;
+            context.fillRect(bin * binWidth, canvas.height - height, Math.ceil(binWidth), height);
+        });
+        // Dim what is clipped and mark the handles
+        const left = toX(levels.displayMin);
+        const right = toX(levels.displayMax);
+        context.fillStyle = 'rgba(0, 0, 0, 0.5)';
+        context.fillRect(0, 0, left, canvas.height);
+        context.fillRect(right, 0, canvas.width - right, canvas.height);
+        context.fillStyle = '#00d9ff';
+        context.fillRect(left - 1, 0, 2, canvas.height);
+        context.fillRect(right - 1, 0, 2, canvas.height);
+        labelMin.textContent = format(levels.displayMin);
+        labelMax.textContent = format(levels.displayMax);
+    }
+
+    function canvasX(event) {
+        const rect = canvas.getBoundingClientRect();
+        return (event.clientX - rect.left) / rect.width * canvas.width;
+    }
+
+    canvas.addEventListener('pointerdown', (event) => {
+        if (!levels) {
+            return;
+        }
+        const x = canvasX(event);
+        dragging = Math.abs(x - toX(levels.displayMin)) <= Math.abs(x - toX(levels.displayMax)) ? 'displayMin' : 'displayMax';
+        canvas.setPointerCapture(event.pointerId);
+    });
+
+    canvas.addEventListener('pointermove', (event) => {
+        if (!dragging || !wasmViewer) {
+            return;
+        }
+        const value = toValue(canvasX(event));
+        // Keep the handles from crossing
+        const step = (levels.max - levels.min) / levels.counts.length;
+        levels[dragging] = dragging === 'displayMin'
+            ? Math.min(value, levels.displayMax - step)
+            : Math.max(value, levels.displayMin + step);
+        wasmViewer.set_display_range(layer, levels.displayMin, levels.displayMax);
+        draw();
+    });
+
+    canvas.addEventListener('pointerup', (event) => {
+        dragging = null;
+        canvas.releasePointerCapture(event.pointerId);
+        if (layer === 'amplitude') {
+            updateAmplitudeLegend();
+        }
+    });
+
+    return {
+        async refresh() {
+            if (!wasmViewer) {
+                return;
+            }
+            try {
+                const histogram = await wasmViewer.histogram(layer);
+                levels = {
+                    min: histogram.min,
+                    max: histogram.max,
+                    displayMin: histogram.display_min,
+                    displayMax: histogram.display_max,
+                    counts: histogram.counts,
+                };
+                histogram.free();
+                unit = layer === 'height' ? await wasmViewer.display_unit() : '';
+            } catch (err) {
+                levels = null;
+                console.warn(`No histogram for ${layer}:`, err);
+            }
+            draw();
+        },
+    };
+}
+
+// Probe the neighborhood while Alt is held
+function setProbing(probing) {
+    isProbing = probing;
+    if (probeReadout) {
+        probeReadout.hidden = !probing;
+    }
+}
+window.addEventListener('keydown', (event) => setProbing(event.altKey));
+window.addEventListener('keyup', (event) => setProbing(event.altKey));
+window.addEventListener('blur', () => setProbing(false));
+
 /**
  * Initialize the WASM module and create the viewer
  */
@@ -183,7 +395,7 @@ async function initWasm() {
         console.log('init() completed successfully');
         console.log('wasmModule after init:', !!wasmModule);
 
-        statusWasm.textContent = 'Ready';
+        statusWasm.textContent = `Ready (API ${apiVersion()})`;
         statusWasm.classList.add('success');
 
         console.log('WASM module initialized:', wasmModule);
@@ -208,10 +420,134 @@ async function initWasm() {
     }
 }
 
+const TOAST_DURATION_MS = 4000;
+const NOTIFICATION_LOG_SIZE = 200;
+
+/**
+ * CSS class for the severity of a notification
+ */
+function severityClass(severity) {
+    switch (severity) {
+        case Severity.Success: return 'severity-success';
+        case Severity.Warning: return 'severity-warning';
+        case Severity.Error: return 'severity-error';
+        default: return 'severity-info';
+    }
+}
+
+/**
+ * Show a notification as a toast and append it to the notification log
+ */
+function showNotification(notification) {
+    const className = severityClass(notification.severity);
+
+    const toast = document.createElement('div');
+    toast.className = `toast ${className}`;
+    toast.textContent = notification.message;
+    toastContainer.appendChild(toast);
+    setTimeout(() => toast.remove(), TOAST_DURATION_MS);
+
+    const entry = document.createElement('li');
+    entry.className = className;
+    const time = document.createElement('time');
+    const date = new Date(notification.timestamp);
+    time.dateTime = date.toISOString();
+    time.textContent = date.toLocaleTimeString();
+    entry.append(time, notification.message);
+    notificationLog.querySelector('.notification-empty').hidden = true;
+    notificationLog.appendChild(entry);
+    // The viewer keeps the same number of notifications
+    const entries = notificationLog.querySelectorAll('li:not(.notification-empty)');
+    if (entries.length > NOTIFICATION_LOG_SIZE) {
+        entries[0].remove();
+    }
+    notificationLog.scrollTop = notificationLog.scrollHeight;
+}
+
+/**
+ * Show the metadata rows of the loaded dataset in the info panel
+ */
+function showDatasetInfo(entries) {
+    datasetInfo.replaceChildren();
+    for (const entry of entries) {
+        const label = document.createElement('dt');
+        label.textContent = entry.label;
+        const value = document.createElement('dd');
+        value.textContent = entry.value;
+        datasetInfo.append(label, value);
+    }
+}
+
+/**
+ * One row per annotation group to show, hide or recolor its markers and overlays
+ */
+async function refreshAnnotationGroups() {
+    if (!wasmViewer) {
+        return;
+    }
+    const groups = await wasmViewer.annotation_groups();
+    annotationGroups.replaceChildren(...groups.map((group) => {
+        const row = document.createElement('label');
+        row.className = 'annotation-group';
+        const visible = document.createElement('input');
+        visible.type = 'checkbox';
+        visible.checked = !hiddenGroups.has(group);
+        visible.addEventListener('change', () => {
+            if (visible.checked) {
+                hiddenGroups.delete(group);
+            } else {
+                hiddenGroups.add(group);
+            }
+            wasmViewer.set_annotation_group_visible(group, visible.checked);
+        });
+        const name = document.createElement('span');
+        name.textContent = group;
+        const color = document.createElement('input');
+        color.type = 'color';
+        color.value = '#ffcc00';
+        color.addEventListener('input', () => {
+            const rgb = parseInt(color.value.slice(1), 16);
+            wasmViewer.set_annotation_group_color(
+                group,
+                new Uint8Array([rgb >> 16, (rgb >> 8) & 255, rgb & 255, 200]),
+            );
+        });
+        row.append(visible, name, color);
+        return row;
+    }));
+}
+
+/**
+ * Replace the text of all elements marked with data-i18n by the viewer's translation
+ */
+function applyTranslations() {
+    if (!wasmViewer) {
+        return;
+    }
+    document.querySelectorAll('[data-i18n]').forEach((element) => {
+        element.textContent = wasmViewer.translate(element.dataset.i18n);
+    });
+    document.title = wasmViewer.translate('window-title');
+    const language = wasmViewer.language();
+    languageButtons.forEach((button) => {
+        button.classList.toggle('active', button.dataset.language === language);
+    });
+}
+
 /**
  * Set up button event handlers
  */
 function setupControls() {
+    // Language buttons - switch viewer language and re-translate the page
+    languageButtons.forEach((button) => {
+        button.addEventListener('click', () => {
+            if (wasmViewer) {
+                wasmViewer.set_language(button.dataset.language);
+                applyTranslations();
+            }
+        });
+    });
+
     // Shader mode buttons - call viewer methods directly
     btnHeight.addEventListener('click', () => {
         if (!isHeightMode && wasmViewer) {
@@ -238,10 +574,371 @@ function setupControls() {
         }
     });
 
+    // Apply the typed view angles and zoom
+    btnSetView.addEventListener('click', () => {
+        if (wasmViewer) {
+            const zoom = parseFloat(inputZoom.value);
+            wasmViewer.set_view(
+                parseFloat(inputAzimuth.value) || 0,
+                parseFloat(inputElevation.value) || 0,
+                zoom > 0 ? zoom : undefined,
+            );
+        }
+    });
+
+    // Turn machining marks horizontal, so that datasets are compared in the same orientation
+    btnAlignLay.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            const lay = await wasmViewer.align_lay();
+            lay.free();
+        } catch (err) {
+            console.warn('Aligning the lay failed:', err);
+        }
+    });
+
+    // Compare the surface with a nominal surface of the same size
+    inputReference.addEventListener('change', async () => {
+        const file = inputReference.files[0];
+        if (wasmViewer && file) {
+            try {
+                await wasmViewer.set_reference(new Uint8Array(await file.arrayBuffer()));
+                isResidualMode = true;
+                wasmViewer.set_residual_mode(true);
+                btnResidual.classList.add('active');
+            } catch (err) {
+                console.error('Failed to load reference surface:', err);
+            }
+        }
+    });
+
+    btnResidual.addEventListener('click', () => {
+        if (wasmViewer) {
+            isResidualMode = !isResidualMode;
+            wasmViewer.set_residual_mode(isResidualMode);
+            btnResidual.classList.toggle('active', isResidualMode);
+        }
+    });
+
+    // Live monitoring: every surface set from now on is compared with the rolling average of
+    // the surfaces so far, starting with the current one
+    btnBaseline.addEventListener('click', () => {
+        if (wasmViewer) {
+            const enabled = !btnBaseline.classList.contains('active');
+            wasmViewer.set_baseline(enabled ? 'rolling' : undefined);
+            isResidualMode = enabled;
+            wasmViewer.set_residual_mode(enabled);
+            btnResidual.classList.toggle('active', enabled);
+            btnBaseline.classList.toggle('active', enabled);
+        }
+    });
+
+    // Repeatability: the per-pixel mean and standard deviation of the current surface and every
+    // surface set while collecting, either map can then be shown instead of the surface
+    btnTemporal.addEventListener('click', () => {
+        if (wasmViewer) {
+            const collecting = !btnTemporal.classList.contains('active');
+            wasmViewer.set_temporal_collecting(collecting);
+            btnTemporal.classList.toggle('active', collecting);
+        }
+    });
+
+    for (const [button, map] of [[btnTemporalMean, TemporalMap.Mean], [btnTemporalStdDev, TemporalMap.StdDev]]) {
+        button.addEventListener('click', async () => {
+            if (!wasmViewer) {
+                return;
+            }
+            try {
+                await wasmViewer.show_temporal_map(map);
+                btnTemporal.classList.remove('active');
+            } catch (err) {
+                console.warn('Showing the temporal statistics failed:', err);
+            }
+        });
+    }
+
+    // Drifting stages: every surface set from now on is moved onto the current one before it is
+    // compared or collected
+    btnRegister.addEventListener('click', () => {
+        if (wasmViewer) {
+            const enabled = !btnRegister.classList.contains('active');
+            wasmViewer.set_registration(enabled);
+            btnRegister.classList.toggle('active', enabled);
+        }
+    });
+
+    btnFrameShifts.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            const shifts = await wasmViewer.frame_shifts();
+            const unit = await wasmViewer.display_unit();
+            console.table(shifts.map(({ x, y, z }) => ({ x, y, z })));
+            const last = shifts[shifts.length - 1];
+            registrationResult.textContent = last
+                ? `${shifts.length} frames, last shifted by ${last.x.toFixed(2)}, ${last.y.toFixed(2)} px, ${last.z.toFixed(3)} ${unit}`
+                : '';
+            shifts.forEach((shift) => shift.free());
+        } catch (err) {
+            console.warn('Reading the frame shifts failed:', err);
+        }
+    });
+
+    // Follow the selected overlay through the surfaces set from now on
+    btnTrack.addEventListener('click', () => {
+        if (wasmViewer) {
+            const tracking = !btnTrack.classList.contains('active');
+            if (tracking) {
+                wasmViewer.track_selection();
+            } else {
+                wasmViewer.stop_tracking();
+            }
+            btnTrack.classList.toggle('active', tracking);
+        }
+    });
+
+    // Plot the heights of the tracked region and download them as CSV
+    btnTrackExport.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            trackPlot.innerHTML = await wasmViewer.export_region_track(TrackFormat.Svg);
+            const csv = await wasmViewer.export_region_track(TrackFormat.Csv);
+            const link = document.createElement('a');
+            link.href = URL.createObjectURL(new Blob([csv], { type: 'text/csv' }));
+            link.download = 'track.csv';
+            link.click();
+            URL.revokeObjectURL(link.href);
+        } catch (err) {
+            console.warn('Exporting the region track failed:', err);
+        }
+    });
+
+    // Registration check: tiles alternate between the surface and the reference
+    function updateCheckerboard() {
+        if (wasmViewer) {
+            const tileSize = parseInt(inputTileSize.value, 10);
+            wasmViewer.set_checkerboard(
+                btnCheckerboard.classList.contains('active'),
+                tileSize > 0 ? tileSize : undefined,
+            );
+        }
+    }
+
+    btnCheckerboard.addEventListener('click', () => {
+        btnCheckerboard.classList.toggle('active');
+        updateCheckerboard();
+    });
+
+    inputTileSize.addEventListener('change', updateCheckerboard);
+
+    // Pass if no pixel is out of tolerance
+    btnEvaluate.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            const evaluation = await wasmViewer.evaluate_tolerance();
+            const unit = await wasmViewer.display_unit();
+            const percent = (evaluation.fraction_out_of_tolerance() * 100).toFixed(3);
+            const verdict = evaluation.passed(0) ? 'PASS' : 'FAIL';
+            toleranceResult.textContent = evaluation.out_of_tolerance() === 0
+                ? `${verdict}: ${percent} %`
+                : `${verdict}: ${percent} %, max ${evaluation.largest_violation.toFixed(3)} ${unit}`
+                    + ` @ [${evaluation.largest_violation_x}/${evaluation.largest_violation_y}]`;
+            evaluation.free();
+        } catch (err) {
+            toleranceResult.textContent = String(err);
+        }
+    });
+
+    inputTolerance.addEventListener('change', () => {
+        const tolerance = Math.abs(parseFloat(inputTolerance.value));
+        if (wasmViewer && Number.isFinite(tolerance)) {
+            wasmViewer.set_tolerance(-tolerance, tolerance);
+        }
+    });
+
+    // Amplitudes have their own contrast and colormap, independent of the heights
+    btnAmplitudeRange.addEventListener('click', () => {
+        const min = parseFloat(inputAmplitudeMin.value);
+        const max = parseFloat(inputAmplitudeMax.value);
+        if (wasmViewer && Number.isFinite(min) && Number.isFinite(max)) {
+            wasmViewer.set_amplitude_range(min, max);
+            updateAmplitudeLegend();
+            refreshLevels();
+        }
+    });
+
+    btnAmplitudePercentiles.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.set_amplitude_percentiles(1, 99);
+            updateAmplitudeLegend();
+            refreshLevels();
+        }
+    });
+
+    levelsWidgets = ['height', 'amplitude'].map(createLevelsWidget);
+
+    btnAmplitudeColormap.addEventListener('click', () => {
+        if (wasmViewer) {
+            amplitudeColormap = (amplitudeColormap + 1) % AMPLITUDE_COLORMAPS.length;
+            wasmViewer.set_amplitude_colormap(AMPLITUDE_COLORMAPS[amplitudeColormap].name);
+            updateAmplitudeLegend();
+        }
+    });
+
+    inputPixelPitch.addEventListener('change', () => {
+        const pitch = parseFloat(inputPixelPitch.value);
+        if (wasmViewer && pitch > 0) {
+            wasmViewer.set_pixel_pitch(pitch);
+        }
+    });
+
+    // Anisotropic lateral sampling, shown with its physical proportions unless raw pixels are on
+    inputPixelAspect.addEventListener('change', () => {
+        const aspect = parseFloat(inputPixelAspect.value);
+        if (wasmViewer && aspect > 0) {
+            wasmViewer.set_pixel_aspect(aspect);
+        }
+    });
+
+    // Stretch the height colors over what is in view, e.g. after zooming into a shallow feature
+    btnAutoContrast.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            await wasmViewer.auto_contrast_visible();
+            refreshLevels();
+        } catch (err) {
+            console.warn('Auto contrast failed:', err);
+        }
+    });
+
+    // The shown layer keeps its colormap, the other one darkens it or is mixed in by the weight
+    function updateLayerBlend() {
+        if (!wasmViewer) {
+            return;
+        }
+        const blend = btnBlendBrightness.classList.contains('active') ? LayerBlend.Brightness
+            : btnBlendOverlay.classList.contains('active') ? LayerBlend.Overlay
+            : undefined;
+        wasmViewer.set_layer_blend(blend, parseFloat(inputBlendWeight.value));
+    }
+
+    [btnBlendBrightness, btnBlendOverlay].forEach((button) => {
+        button.addEventListener('click', () => {
+            const enabled = !button.classList.contains('active');
+            btnBlendBrightness.classList.remove('active');
+            btnBlendOverlay.classList.remove('active');
+            button.classList.toggle('active', enabled);
+            updateLayerBlend();
+        });
+    });
+
+    inputBlendWeight.addEventListener('input', updateLayerBlend);
+
+    // Clips the surface in front of the camera to look under towering noise spikes
+    inputNearClip.addEventListener('input', () => {
+        if (wasmViewer) {
+            wasmViewer.set_near_clip(parseFloat(inputNearClip.value));
+        }
+    });
+
+    // Binarization preview: the slider spans the heights of the surface and moves the threshold
+    // live, the viewer answers every move with a `threshold-areas` event
+    btnThreshold.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        const enabled = !btnThreshold.classList.contains('active');
+        if (enabled) {
+            try {
+                const histogram = await wasmViewer.histogram('height');
+                inputThreshold.min = histogram.min;
+                inputThreshold.max = histogram.max;
+                inputThreshold.step = (histogram.max - histogram.min) / 1000 || 0.001;
+                inputThreshold.value = (histogram.display_min + histogram.display_max) / 2;
+                histogram.free();
+            } catch (err) {
+                thresholdResult.textContent = String(err);
+                return;
+            }
+        }
+        wasmViewer.set_threshold(enabled ? parseFloat(inputThreshold.value) : undefined);
+        btnThreshold.classList.toggle('active', enabled);
+        inputThreshold.disabled = !enabled;
+        if (!enabled) {
+            thresholdResult.textContent = '';
+        }
+    });
+
+    inputThreshold.addEventListener('input', () => {
+        if (wasmViewer && !inputThreshold.disabled) {
+            wasmViewer.set_threshold(parseFloat(inputThreshold.value));
+        }
+    });
+
+    btnRawPixels.addEventListener('click', () => {
+        if (wasmViewer) {
+            const raw = !btnRawPixels.classList.contains('active');
+            wasmViewer.set_physical_proportions(!raw);
+            btnRawPixels.classList.toggle('active', raw);
+        }
+    });
+
+    // While measuring, clicks on the surface pick the points: the vertex and one point on each
+    // line of an angle, the ends of the profile a circle is fitted to or the corners of the patch
+    // a sphere is fitted to
+    measureButtons.forEach((button) => {
+        button.addEventListener('click', () => {
+            if (wasmViewer) {
+                const measuring = !button.classList.contains('active');
+                wasmViewer.set_measure_tool(measuring ? button.dataset.measure : undefined);
+                measureButtons.forEach((other) => other.classList.toggle('active', measuring && other === button));
+                measureTool = measuring ? button.dataset.measure : null;
+                measureResult.textContent = '';
+            }
+        });
+    });
+
+    // Developer visualizations of the mesh, any combination can be shown
+    debugViewButtons.forEach((button) => {
+        button.addEventListener('click', () => {
+            if (wasmViewer) {
+                button.classList.toggle('active');
+                const shown = (name) =>
+                    document.querySelector(`[data-debug-view="${name}"]`).classList.contains('active');
+                wasmViewer.set_debug_view(shown('bounds'), shown('normals'), shown('overdraw'));
+            }
+        });
+    });
+
+    btnDeleteSelection.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.delete_selection();
+            selectionProperties.textContent = '--';
+        }
+    });
+
+    btnClearNotifications.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.clear_notifications();
+            notificationLog.querySelectorAll('li:not(.notification-empty)').forEach((entry) => entry.remove());
+            notificationLog.querySelector('.notification-empty').hidden = false;
+        }
+    });
+
     // Set overlay - call viewer method directly
     btnSetOverlay.addEventListener('click', () => {
         if (wasmViewer) {
             wasmViewer.set_overlays();
+            refreshAnnotationGroups();
         }
     });
 
@@ -249,12 +946,181 @@ function setupControls() {
     btnClearOverlay.addEventListener('click', () => {
         if (wasmViewer) {
             wasmViewer.clear_overlays();
+            refreshAnnotationGroups();
+        }
+    });
+
+    // Dims everything outside the selected overlay, click an overlay to select it
+    btnFocusSelection.addEventListener('click', () => {
+        if (wasmViewer) {
+            const focus = !btnFocusSelection.classList.contains('active');
+            wasmViewer.set_dim_outside_selection(focus);
+            btnFocusSelection.classList.toggle('active', focus);
+        }
+    });
+
+    // Styles the example overlays by the scores of their made-up classifier
+    btnColorByScore.addEventListener('click', () => {
+        if (wasmViewer) {
+            const enabled = !btnColorByScore.classList.contains('active');
+            wasmViewer.set_overlay_color_ramp(enabled ? 'score' : undefined, 'viridis', 0, 1);
+            btnColorByScore.classList.toggle('active', enabled);
+        }
+    });
+
+    inputMinScore.addEventListener('input', () => {
+        if (wasmViewer) {
+            const min = parseFloat(inputMinScore.value);
+            wasmViewer.set_overlay_filter(min > 0 ? 'score' : undefined, min);
+        }
+    });
+
+    // Steps through the shown overlays and markers, the camera frames each one
+    btnReviewPrevious.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.review_previous();
+        }
+    });
+
+    btnReviewNext.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.review_next();
+        }
+    });
+
+    btnReviewByScore.addEventListener('click', () => {
+        if (wasmViewer) {
+            const byScore = !btnReviewByScore.classList.contains('active');
+            wasmViewer.set_review_order(byScore ? 'score' : 'position');
+            btnReviewByScore.classList.toggle('active', byScore);
+        }
+    });
+
+    // Downloads the markers and overlays of the shown groups
+    btnExportAnnotations.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            const csv = await wasmViewer.export_annotations();
+            const link = document.createElement('a');
+            link.href = URL.createObjectURL(new Blob([csv], { type: 'text/csv' }));
+            link.download = 'annotations.csv';
+            link.click();
+            URL.revokeObjectURL(link.href);
+        } catch (err) {
+            console.warn('Exporting the annotations failed:', err);
+        }
+    });
+
+    // Verdicts on the selected annotation go to the audit trail, then the next one is shown
+    btnAccept.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.set_disposition(Disposition.Accepted);
+        }
+    });
+
+    btnReject.addEventListener('click', () => {
+        if (wasmViewer) {
+            wasmViewer.set_disposition(Disposition.Rejected);
+        }
+    });
+
+    // Downloads the audit trail of the review with its signature as a second file
+    btnExportAudit.addEventListener('click', async () => {
+        if (!wasmViewer) {
+            return;
+        }
+        try {
+            const [log, signature] = await wasmViewer.export_audit_trail(false);
+            for (const [content, name] of [[log, 'audit.csv'], [signature + '\n', 'audit.csv.sig']]) {
+                const link = document.createElement('a');
+                link.href = URL.createObjectURL(new Blob([content], { type: 'text/plain' }));
+                link.download = name;
+                link.click();
+                URL.revokeObjectURL(link.href);
+            }
+        } catch (err) {
+            console.warn('Exporting the audit trail failed:', err);
         }
     });
 
     // Set up mouse movement tracking
+    // Dispatched by the viewer when it panics, the report is what a bug report needs
+    window.addEventListener('viewer-crash', (event) => {
+        const report = event.detail;
+        console.error('Viewer crash report', {
+            message: report.message,
+            location: report.location,
+            version: report.version,
+            adapter: report.adapter,
+            dataset: report.dataset,
+            camera: report.camera,
+            commands: report.commands,
+        });
+        showNotification({
+            severity: Severity.Error,
+            message: `The viewer crashed: ${report.message}`,
+            timestamp: Date.now(),
+        });
+    });
+
     const canvas = document.getElementById('canvas');
     if (canvas) {
+        // Dispatched by the viewer when Enter is pressed with an overlay or marker selected
+        canvas.addEventListener('annotation-properties', (event) => {
+            const selection = event.detail;
+            const kind = selection.kind === SelectionKind.Overlay ? 'Overlay' : 'Marker';
+            selectionProperties.textContent = `${kind} ${selection.index}`;
+        });
+
+        // Dispatched by the viewer for loads, exports, measurements and errors
+        canvas.addEventListener('notification', (event) => {
+            showNotification(event.detail);
+        });
+
+        canvas.addEventListener('dataset-info', (event) => {
+            showDatasetInfo(event.detail);
+        });
+
+        canvas.addEventListener('near-clip', (event) => {
+            inputNearClip.value = event.detail;
+        });
+
+        // Keeps the notifications at the size of the HUD markers
+        canvas.addEventListener('ui-scale', (event) => {
+            document.documentElement.style.setProperty('--ui-scale', event.detail);
+        });
+
+        canvas.addEventListener('threshold-areas', async (event) => {
+            const areas = event.detail;
+            const unit = await wasmViewer.display_unit();
+            const percent = (areas.fraction_above() * 100).toFixed(2);
+            thresholdResult.textContent = `${areas.threshold.toFixed(3)} ${unit}: ${percent} % above`
+                + ` (${areas.area_above.toPrecision(4)} / ${areas.area_below.toPrecision(4)} ${unit}²)`;
+        });
+
+        canvas.addEventListener('click', async () => {
+            if (!wasmViewer || !measureTool) {
+                return;
+            }
+            try {
+                if (measureTool === 'angle') {
+                    const angle = await wasmViewer.angle();
+                    measureResult.textContent = `${angle.in_plane.toFixed(2)}° / ${angle.spatial.toFixed(2)}°`;
+                    angle.free();
+                } else {
+                    const fit = await wasmViewer.radius_fit();
+                    const unit = await wasmViewer.display_unit();
+                    measureResult.textContent = `R ${fit.radius.toFixed(3)} ${unit} ± ${fit.rms_residual.toFixed(3)}`;
+                    fit.free();
+                }
+            } catch {
+                // Not all points picked yet
+                measureResult.textContent = '';
+            }
+        });
+
         console.log('Setting up canvas mouse tracking');
         canvas.addEventListener('mousemove', () => {
             if (wasmViewer) {
@@ -299,7 +1165,11 @@ function startPixelPolling() {
                 const x = result[0];
                 const y = result[1];
                 const z = result[2];
-                renderPixelReadout(x, y, z);
+                const unit = await wasmViewer.display_unit();
+                renderPixelReadout(x, y, z, unit);
+                if (isProbing) {
+                    renderProbeReadout(await wasmViewer.probe_neighborhood(), unit);
+                }
             } else {
                 console.log('Invalid result format:', result);
             }
@@ -358,6 +1228,18 @@ async function main() {
     statusWebGPU.classList.add('success');
     console.log('✅ WebGPU available:', gpuCheck.info);
 
+    // `?worker` renders from a worker with basic navigation where the browser allows it
+    if (new URLSearchParams(location.search).has('worker')) {
+        updateLoadingText('Starting worker...');
+        const surfaceUrl = new URL(SURFACE_URL, location.href).href;
+        if (await startWorkerViewer(document.getElementById('canvas'), surfaceUrl)) {
+            statusWasm.textContent = 'Worker';
+            statusWasm.classList.add('success');
+            hideLoading();
+            return;
+        }
+    }
+
     // Initialize WASM
     const wasmReady = await initWasm();
 
@@ -367,6 +1249,7 @@ async function main() {
 
     // Set up controls
     setupControls();
+    applyTranslations();
 
     // Start FPS counter
     setupFpsCounter();
@@ -378,15 +1261,14 @@ async function main() {
         // Load surface data and set it in WASM
         updateLoadingText('Loading surface data...');
         try {
-            const surfaceData = await loadSurfaceData();
-            const amplitudeData = await loadAmplitudeData();
-            if (wasmViewer && typeof wasmViewer.set_surface === 'function') {
-                wasmViewer.set_surface(surfaceData);
-                wasmViewer.set_amplitude(amplitudeData);
+            if (wasmViewer && typeof wasmViewer.load_url === 'function') {
+                await loadSurfaceData();
+                updateAmplitudeLegend();
+                refreshLevels();
                 console.log('✅ Surface data set in WASM viewer');
                 hideLoading();
             } else {
-                console.warn('set_surface method not available on wasmViewer');
+                console.warn('load_url method not available on wasmViewer');
             }
         } catch (error) {
             console.error('Failed to load surface data:', error);
@@ -412,7 +1294,8 @@ function handleResize() {
     const canvas = document.getElementById('canvas');
     const container = canvas.parentElement;
 
-    if (canvas && container) {
+    // The worker sizes a transferred canvas itself
+    if (canvas && container && !canvas.dataset.worker) {
         canvas.width = container.clientWidth;
         canvas.height = container.clientHeight;
     }
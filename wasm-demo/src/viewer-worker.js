@@ -0,0 +1,63 @@
+/**
+ * 3D Data Viewer - worker rendering into an OffscreenCanvas
+ *
+ * Receives the canvas and the pointer input from `worker-viewer.js` and draws a frame whenever
+ * the view changed, so that the main thread of the page never waits for the GPU.
+ */
+
+import init, { WorkerViewer } from './assets/wasm/data-viewer-3d.js';
+
+let viewer = null;
+let framePending = false;
+
+const nextFrame = self.requestAnimationFrame
+    ? (callback) => self.requestAnimationFrame(callback)
+    : (callback) => setTimeout(callback, 16);
+
+function requestFrame(changed = true) {
+    if (!changed || framePending || !viewer) {
+        return;
+    }
+    framePending = true;
+    nextFrame(() => {
+        framePending = false;
+        try {
+            viewer.render_frame();
+        } catch (error) {
+            console.error('Failed to render frame:', error);
+        }
+    });
+}
+
+self.onmessage = async ({ data }) => {
+    switch (data.type) {
+        case 'init':
+            try {
+                await init();
+                viewer = await WorkerViewer.create(data.canvas);
+                const response = await fetch(data.surfaceUrl);
+                viewer.load(new Uint8Array(await response.arrayBuffer()));
+                self.postMessage({ type: 'ready' });
+                requestFrame();
+            } catch (error) {
+                viewer = null;
+                self.postMessage({ type: 'failed', message: String(error) });
+            }
+            break;
+        case 'resize':
+            requestFrame(viewer?.resize(data.width, data.height));
+            break;
+        case 'pointer-move':
+            requestFrame(viewer?.pointer_moved(data.x, data.y));
+            break;
+        case 'pointer-button':
+            requestFrame(viewer?.pointer_button(data.button, data.pressed));
+            break;
+        case 'wheel':
+            requestFrame(viewer?.wheel(data.lines));
+            break;
+        case 'reset-camera':
+            requestFrame(viewer?.reset_camera());
+            break;
+    }
+};
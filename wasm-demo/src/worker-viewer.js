@@ -0,0 +1,79 @@
+/**
+ * 3D Data Viewer - rendering from a worker
+ *
+ * Transfers the canvas to `viewer-worker.js` on browsers with OffscreenCanvas and forwards its
+ * pointer input there. Resolves to `null` where that isn't possible, after putting a fresh
+ * canvas in place of the transferred one, so that the page falls back to the main thread.
+ */
+
+/** Lines scrolled by a wheel event, as the viewer counts them on the main thread */
+function wheelLines(event) {
+    switch (event.deltaMode) {
+        case WheelEvent.DOM_DELTA_LINE:
+            return -event.deltaY;
+        case WheelEvent.DOM_DELTA_PAGE:
+            return -event.deltaY * 10;
+        default:
+            return -event.deltaY / 100;
+    }
+}
+
+export async function startWorkerViewer(canvas, surfaceUrl) {
+    if (!('transferControlToOffscreen' in canvas) || typeof Worker === 'undefined') {
+        console.log('OffscreenCanvas not supported, rendering on the main thread');
+        return null;
+    }
+    const worker = new Worker(new URL('./viewer-worker.js', import.meta.url), { type: 'module' });
+    const offscreen = canvas.transferControlToOffscreen();
+    // The size now belongs to the worker, see `handleResize`
+    canvas.dataset.worker = 'true';
+    const started = await new Promise((resolve) => {
+        worker.onmessage = ({ data }) => {
+            if (data.type === 'failed') {
+                console.warn('Worker rendering failed, rendering on the main thread:', data.message);
+            }
+            resolve(data.type === 'ready');
+        };
+        worker.onerror = (error) => {
+            console.warn('Viewer worker failed to start:', error.message);
+            resolve(false);
+        };
+        worker.postMessage({ type: 'init', canvas: offscreen, surfaceUrl }, [offscreen]);
+    });
+    if (!started) {
+        worker.terminate();
+        // A canvas can only be transferred once
+        delete canvas.dataset.worker;
+        canvas.replaceWith(canvas.cloneNode(false));
+        return null;
+    }
+
+    const scale = () => window.devicePixelRatio || 1;
+    const resize = () => worker.postMessage({
+        type: 'resize',
+        width: Math.max(1, Math.round(canvas.clientWidth * scale())),
+        height: Math.max(1, Math.round(canvas.clientHeight * scale())),
+    });
+    new ResizeObserver(resize).observe(canvas);
+    resize();
+
+    canvas.addEventListener('pointermove', (event) => worker.postMessage({
+        type: 'pointer-move',
+        x: event.offsetX * scale(),
+        y: event.offsetY * scale(),
+    }));
+    canvas.addEventListener('pointerdown', (event) => {
+        canvas.setPointerCapture(event.pointerId);
+        worker.postMessage({ type: 'pointer-button', button: event.button, pressed: true });
+    });
+    canvas.addEventListener('pointerup', (event) => {
+        worker.postMessage({ type: 'pointer-button', button: event.button, pressed: false });
+    });
+    canvas.addEventListener('contextmenu', (event) => event.preventDefault());
+    canvas.addEventListener('wheel', (event) => {
+        event.preventDefault();
+        worker.postMessage({ type: 'wheel', lines: wheelLines(event) });
+    }, { passive: false });
+    canvas.addEventListener('dblclick', () => worker.postMessage({ type: 'reset-camera' }));
+    return worker;
+}